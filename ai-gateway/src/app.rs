@@ -15,45 +15,64 @@ use opentelemetry::global;
 use rustc_hash::FxHashMap as HashMap;
 use telemetry::{make_span::SpanFactory, tracing::MakeRequestId};
 use tokio::sync::RwLock;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tower::{ServiceBuilder, buffer::BufferLayer, util::BoxCloneService};
 use tower_http::{
     ServiceBuilderExt,
     add_extension::AddExtension,
     catch_panic::CatchPanicLayer,
-    compression::CompressionLayer,
-    cors::{Any, CorsLayer},
+    compression::{
+        CompressionLayer,
+        predicate::{NotForContentType, Predicate, SizeAbove},
+    },
     normalize_path::NormalizePathLayer,
     sensitive_headers::SetSensitiveHeadersLayer,
     trace::TraceLayer,
 };
-use tracing::{Level, info};
+use tracing::{Level, info, warn};
 
 use crate::{
     app_state::{AppState, InnerAppState},
-    cache::{CacheClient, RedisCacheManager},
+    cache::{self, CacheClient, RedisCacheManager},
     cli,
-    config::{Config, DeploymentTarget, cache::CacheStore, server::TlsConfig},
+    config::{
+        Config, DeploymentTarget, cache::CacheStore,
+        compression::CompressionEncoding, server::TlsConfig,
+    },
     control_plane::control_plane_state::ControlPlaneState,
     discover::monitor::{
+        backoff::ProviderBackoffs, circuit_breaker::CircuitBreakers,
         health::provider::HealthMonitorMap, metrics::EndpointMetricsRegistry,
-        rate_limit::RateLimitMonitorMap,
+        pool::ProviderPools, rate_limit::RateLimitMonitorMap,
+    },
+    dispatcher::{
+        client::ProviderClients, concurrency::ProviderConcurrencyLimits,
     },
     error::{init::InitError, runtime::RuntimeError},
     logger::service::JawnClient,
     metrics::{self, Metrics, attribute_extractor::AttributeExtractor},
-    middleware::response_headers::ResponseHeaderLayer,
+    middleware::{
+        budget::SpendTracker, cors, idempotency::IdempotencyStore,
+        rate_limit::store::InMemoryStore,
+        response_headers::ResponseHeaderLayer,
+    },
     router::meta::MetaRouter,
-    store::{connect, minio::BaseMinioClient, router::RouterStore},
+    store::{
+        connect, minio::BaseMinioClient, minio_batch::BodyBatcher,
+        router::RouterStore,
+    },
     types::provider::ProviderKeys,
     utils::{
-        catch_panic::PanicResponder, handle_error::ErrorHandlerLayer,
-        health_check::HealthCheckLayer, timer::TimerLayer,
-        validate_config::ValidateRouterConfigLayer,
+        catch_panic::PanicResponder, clock::Clock,
+        handle_error::ErrorHandlerLayer, health_check::HealthCheckLayer,
+        timer::TimerLayer, validate_config::ValidateRouterConfigLayer,
     },
 };
 
 const APP_BUFFER_SIZE: usize = 1024;
 const SERVICE_NAME: &str = "ai-gateway";
+/// One model list per router and organization, so this is plenty.
+const MODEL_LIST_CACHE_CAPACITY: u64 = 10_000;
 
 pub type AppResponseBody = tower_http::body::UnsyncBoxBody<
     bytes::Bytes,
@@ -181,8 +200,23 @@ impl tower::Service<crate::types::request::Request> for App {
 
 impl App {
     pub async fn new(config: Config) -> Result<Self, InitError> {
+        let provider_keys = ProviderKeys::new(&config);
+        Self::with_provider_keys(config, provider_keys).await
+    }
+
+    /// Like [`App::new`], but with the given provider keys rather than the
+    /// ones discovered from the environment.
+    pub async fn with_provider_keys(
+        config: Config,
+        provider_keys: ProviderKeys,
+    ) -> Result<Self, InitError> {
         tracing::debug!("creating app");
-        let app_state = Self::build_app_state(config).await?;
+        config
+            .validate_with_provider_keys(&provider_keys)
+            .inspect_err(|e| {
+                tracing::error!(error = %e, "configuration validation failed");
+            })?;
+        let app_state = Self::build_app_state(config, provider_keys).await?;
         let service_stack =
             Self::build_service_stack(app_state.clone()).await?;
 
@@ -197,7 +231,10 @@ impl App {
     /// Initializes all the clients, managers, and other stateful components
     /// that are shared across the application. This includes setting up
     /// metrics, monitoring, caching, and API keys.
-    async fn build_app_state(config: Config) -> Result<AppState, InitError> {
+    async fn build_app_state(
+        config: Config,
+        provider_keys: ProviderKeys,
+    ) -> Result<AppState, InitError> {
         let minio = BaseMinioClient::new(config.minio.clone())?;
         let (pg_pool, router_store) =
             if config.deployment_target == DeploymentTarget::Cloud {
@@ -215,17 +252,7 @@ impl App {
         let health_monitor = HealthMonitorMap::default();
         let rate_limit_monitor = RateLimitMonitorMap::default();
 
-        let global_rate_limit = config
-            .global
-            .rate_limit
-            .as_ref()
-            .map(|rl| {
-                crate::config::rate_limit::limiter_config(&rl.limits)
-                    .map(Arc::new)
-            })
-            .transpose()?;
-
-        let cache_manager = setup_cache(&config, metrics.clone())?;
+        let cache_manager = setup_cache(&config, metrics.clone()).await?;
 
         let router_api_keys = if config.deployment_target
             == DeploymentTarget::Cloud
@@ -238,7 +265,15 @@ impl App {
         } else {
             None
         };
-        let provider_keys = ProviderKeys::new(&config);
+        let router_configs = config.routers.clone();
+        let provider_concurrency_limits =
+            ProviderConcurrencyLimits::new(&config);
+        let circuit_breakers = CircuitBreakers::new(&config);
+        let model_lists = Cache::builder()
+            .time_to_live(config.model_list.cache_ttl)
+            .max_capacity(MODEL_LIST_CACHE_CAPACITY)
+            .build();
+        let body_batcher = config.minio.batching.map(BodyBatcher::new);
 
         let app_state = AppState(Arc::new(InnerAppState {
             config,
@@ -246,12 +281,26 @@ impl App {
             router_store,
             pg_pool,
             jawn_http_client,
+            logging_tasks: TaskTracker::new(),
+            shutdown: CancellationToken::new(),
+            clock: Clock::default(),
+            body_batcher,
             control_plane_state: Arc::new(RwLock::new(
                 ControlPlaneState::default(),
             )),
             provider_keys,
-            global_rate_limit,
-            router_rate_limits: RwLock::new(HashMap::default()),
+            provider_clients: ProviderClients::default(),
+            invalid_credentials: RwLock::default(),
+            unhealthy_probes: RwLock::default(),
+            last_probed: RwLock::default(),
+            provider_concurrency_limits,
+            circuit_breakers,
+            provider_backoffs: ProviderBackoffs::default(),
+            provider_pools: ProviderPools::default(),
+            spend: SpendTracker::default(),
+            idempotency: IdempotencyStore::default(),
+            model_lists,
+            in_memory_rate_limits: Arc::new(InMemoryStore::default()),
             metrics,
             endpoint_metrics,
             health_monitors: health_monitor,
@@ -260,6 +309,7 @@ impl App {
             rate_limit_receivers: RwLock::new(HashMap::default()),
             cache_manager,
             router_tx: RwLock::new(None),
+            router_configs: RwLock::new(router_configs),
             helicone_api_keys: RwLock::new(router_api_keys),
             router_organization_map: RwLock::new(HashMap::default()),
         }));
@@ -283,16 +333,20 @@ impl App {
 
         let router = MetaRouter::build(app_state.clone()).await?;
 
+        let compression = &app_state.config().compression;
+        // outside the cache, so that cached responses are stored uncompressed
         let compression_layer = CompressionLayer::new()
-            .gzip(true)
-            .br(true)
-            .deflate(true)
-            .zstd(true);
-
-        let cors_layer = CorsLayer::new()
-            .allow_headers(Any)
-            .allow_methods(Any)
-            .allow_origin(Any);
+            .gzip(compression.is_enabled(CompressionEncoding::Gzip))
+            .br(compression.is_enabled(CompressionEncoding::Br))
+            .deflate(compression.is_enabled(CompressionEncoding::Deflate))
+            .zstd(compression.is_enabled(CompressionEncoding::Zstd))
+            .compress_when(
+                SizeAbove::new(compression.min_size_bytes)
+                    .and(NotForContentType::GRPC)
+                    .and(NotForContentType::IMAGES)
+                    // buffering events would delay them
+                    .and(NotForContentType::SSE),
+            );
 
         // global middleware is applied here
         let service_stack = ServiceBuilder::new()
@@ -315,8 +369,9 @@ impl App {
             .layer(NormalizePathLayer::trim_trailing_slash())
             .layer(metrics::request_count::Layer::new(app_state.clone()))
             .layer(compression_layer)
-            .layer(cors_layer)
-            .layer(HealthCheckLayer::new())
+            // before auth, so that preflights don't need credentials
+            .layer(cors::Layer::new(&app_state))
+            .layer(HealthCheckLayer::new(app_state.clone()))
             .layer(ValidateRouterConfigLayer::new())
             .layer(TimerLayer::new())
             .layer(ErrorHandlerLayer::new(app_state.clone()))
@@ -350,38 +405,50 @@ impl meltdown::Service for App {
             tokio::time::sleep(std::time::Duration::from_millis(250)).await;
             cli::helpers::show_welcome_banner(&addr);
 
-            match &config.server.tls {
+            let mut server: BoxFuture<'_, std::io::Result<()>> = match &config
+                .server
+                .tls
+            {
                 TlsConfig::Enabled { cert, key } => {
                     let tls_config =
                         RustlsConfig::from_pem_file(cert.clone(), key.clone())
                             .await
                             .map_err(InitError::Tls)?;
-
-                    tokio::select! {
-                        biased;
-                        server_output = axum_server::bind_rustls(addr, tls_config)
+                    Box::pin(
+                        axum_server::bind_rustls(addr, tls_config)
                             // Why `NoDelayAcceptor`? See:
                             // https://brooker.co.za/blog/2024/05/09/nagle.html
                             .acceptor(NoDelayAcceptor)
                             .handle(handle.clone())
-                            .serve(app_factory) => server_output.map_err(RuntimeError::Serve)?,
-                        () = token => {
-                            handle.graceful_shutdown(Some(config.server.shutdown_timeout));
-                        }
-                    };
+                            .serve(app_factory),
+                    )
                 }
-                TlsConfig::Disabled => {
-                    tokio::select! {
-                        biased;
-                        server_output = axum_server::bind(addr)
-                            .handle(handle.clone())
-                            .serve(app_factory) => server_output.map_err(RuntimeError::Serve)?,
-                        () = token => {
-                            handle.graceful_shutdown(Some(config.server.shutdown_timeout));
-                        }
-                    };
+                TlsConfig::Disabled => Box::pin(
+                    axum_server::bind(addr)
+                        .handle(handle.clone())
+                        .serve(app_factory),
+                ),
+            };
+
+            tokio::select! {
+                biased;
+                server_output = &mut server => server_output.map_err(RuntimeError::Serve)?,
+                () = token => {
+                    info!(
+                        timeout = ?config.server.shutdown_timeout,
+                        "draining in-flight requests"
+                    );
+                    app_state.0.shutdown.cancel();
+                    // stops accepting connections, but the server must still
+                    // be polled for in-flight requests to complete
+                    handle.graceful_shutdown(Some(config.server.shutdown_timeout));
+                    server.await.map_err(RuntimeError::Serve)?;
                 }
-            }
+            };
+
+            info!("flushing request logs");
+            app_state.flush_logs().await;
+            app_state.snapshot_cache().await;
             Ok(())
         })
     }
@@ -506,14 +573,32 @@ fn setup_redis_cache(
     RedisCacheManager::new(host_url)
 }
 
-fn setup_cache(
+async fn setup_cache(
     config: &Config,
     metrics: Metrics,
 ) -> std::result::Result<Option<CacheClient>, InitError> {
     match &config.cache_store {
-        Some(CacheStore::InMemory { max_size }) => {
+        Some(CacheStore::InMemory {
+            max_size,
+            snapshot_path,
+        }) => {
             tracing::debug!("Using in-memory cache");
             let moka_manager = setup_moka_cache(*max_size, metrics);
+            if let Some(path) = snapshot_path {
+                // a cold cache is not worth failing startup over
+                match cache::load_snapshot(&moka_manager, path).await {
+                    Ok(restored) => info!(
+                        path = %path.display(),
+                        restored,
+                        "restored cache snapshot"
+                    ),
+                    Err(error) => warn!(
+                        path = %path.display(),
+                        error = %error,
+                        "failed to restore cache snapshot"
+                    ),
+                }
+            }
             Ok(Some(CacheClient::Moka(moka_manager)))
         }
         Some(CacheStore::Redis { host_url }) => {
@@ -1,37 +1,53 @@
 use std::{collections::HashSet, sync::Arc};
 
+use chrono::{DateTime, Utc};
 use rustc_hash::FxHashMap as HashMap;
 use sqlx::PgPool;
 use tokio::sync::{
     RwLock,
     mpsc::{Receiver, Sender},
 };
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tower::discover::Change;
 
 use crate::{
-    cache::CacheClient,
+    cache::{self, CacheClient},
     config::{
-        Config, rate_limit::RateLimiterConfig,
-        response_headers::ResponseHeadersConfig,
+        Config, cache::CacheStore, response_headers::ResponseHeadersConfig,
+        router::RouterConfigs,
     },
     control_plane::{control_plane_state::ControlPlaneState, types::Key},
     discover::monitor::{
+        backoff::ProviderBackoffs, circuit_breaker::CircuitBreakers,
         health::provider::HealthMonitorMap, metrics::EndpointMetricsRegistry,
-        rate_limit::RateLimitMonitorMap,
+        pool::ProviderPools, rate_limit::RateLimitMonitorMap,
+    },
+    dispatcher::{
+        client::ProviderClients, concurrency::ProviderConcurrencyLimits,
+    },
+    error::{
+        api::ApiError, auth::AuthError, init::InitError,
+        internal::InternalError,
     },
-    error::init::InitError,
     logger::service::JawnClient,
     metrics::Metrics,
-    router::service::Router,
-    store::{minio::BaseMinioClient, router::RouterStore},
+    middleware::{
+        budget::SpendTracker, idempotency::IdempotencyStore,
+        rate_limit::store::InMemoryStore,
+    },
+    router::{models::ModelListCache, service::Router},
+    store::{
+        minio::BaseMinioClient, minio_batch::BodyBatcher, router::RouterStore,
+    },
     types::{
         org::OrgId,
-        provider::ProviderKeys,
+        provider::{InferenceProvider, ProviderKey, ProviderKeys},
         rate_limit::{
             RateLimitEvent, RateLimitEventReceivers, RateLimitEventSenders,
         },
         router::RouterId,
     },
+    utils::clock::Clock,
 };
 
 #[derive(Debug, Clone)]
@@ -40,13 +56,61 @@ pub struct AppState(pub Arc<InnerAppState>);
 impl AppState {
     #[must_use]
     pub fn response_headers_config(&self) -> ResponseHeadersConfig {
-        self.0.config.response_headers
+        self.0.config.response_headers.clone()
     }
 
     #[must_use]
     pub fn config(&self) -> &Config {
         &self.0.config
     }
+
+    /// Waits for pending request logs to be sent, giving up after the
+    /// configured flush timeout.
+    pub async fn flush_logs(&self) {
+        let tasks = &self.0.logging_tasks;
+        tasks.close();
+        // logs wait for their bodies' batch to be uploaded
+        if let Some(batcher) = &self.0.body_batcher {
+            batcher.flush();
+        }
+        let flush_timeout = self.config().server.flush_timeout;
+        if tokio::time::timeout(flush_timeout, tasks.wait())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                pending = tasks.len(),
+                "timed out flushing request logs"
+            );
+        }
+    }
+
+    /// Writes the in-memory cache to its configured snapshot path, if any,
+    /// so that it can be restored on the next startup.
+    pub async fn snapshot_cache(&self) {
+        let (
+            Some(CacheStore::InMemory {
+                snapshot_path: Some(path),
+                ..
+            }),
+            Some(CacheClient::Moka(moka)),
+        ) = (&self.config().cache_store, &self.0.cache_manager)
+        else {
+            return;
+        };
+        match cache::save_snapshot(moka, path).await {
+            Ok(entries) => tracing::info!(
+                path = %path.display(),
+                entries,
+                "wrote cache snapshot"
+            ),
+            Err(error) => tracing::warn!(
+                path = %path.display(),
+                error = %error,
+                "failed to write cache snapshot"
+            ),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -56,9 +120,20 @@ pub struct InnerAppState {
     pub router_store: Option<RouterStore>,
     pub pg_pool: Option<PgPool>,
     pub jawn_http_client: JawnClient,
+    /// Background tasks which log requests to Jawn and upload bodies to
+    /// MinIO, waited on during shutdown so that logs aren't lost.
+    pub logging_tasks: TaskTracker,
+    /// Cancelled once graceful shutdown begins, so that requests waiting in
+    /// rate limit queues are rejected rather than holding up the drain.
+    pub shutdown: CancellationToken,
+    /// The time provider schedules, rate limited provider re-adds and cached
+    /// response expiry go by, which tests can move forward.
+    pub clock: Clock,
+    /// Set if request bodies are uploaded in batches.
+    pub body_batcher: Option<BodyBatcher>,
     pub cache_manager: Option<CacheClient>,
-    pub global_rate_limit: Option<Arc<RateLimiterConfig>>,
-    pub router_rate_limits: RwLock<HashMap<RouterId, Arc<RateLimiterConfig>>>,
+    /// The state of in memory rate limits, shared by all rate limited scopes.
+    pub in_memory_rate_limits: Arc<InMemoryStore>,
     /// Top level metrics which are exported to OpenTelemetry.
     pub metrics: Metrics,
     /// Metrics to track provider health and rate limits.
@@ -70,10 +145,33 @@ pub struct InnerAppState {
     pub rate_limit_senders: RateLimitEventSenders,
     pub rate_limit_receivers: RateLimitEventReceivers,
     pub router_tx: RwLock<Option<Sender<Change<RouterId, Router>>>>,
+    /// The router configs currently being served, updated on hot reload.
+    pub router_configs: RwLock<RouterConfigs>,
 
     pub control_plane_state: Arc<RwLock<ControlPlaneState>>,
 
     pub provider_keys: ProviderKeys,
+    /// Shared by every dispatcher to a provider, so that they reuse its
+    /// connections.
+    pub provider_clients: ProviderClients,
+    /// Providers whose credential was rejected by the last credential check.
+    pub invalid_credentials: RwLock<HashSet<InferenceProvider>>,
+    /// Providers which have failed enough consecutive probes to be considered
+    /// unhealthy.
+    pub unhealthy_probes: RwLock<HashSet<InferenceProvider>>,
+    /// When each provider was last probed.
+    pub last_probed: RwLock<HashMap<InferenceProvider, DateTime<Utc>>>,
+    pub provider_concurrency_limits: ProviderConcurrencyLimits,
+    pub circuit_breakers: CircuitBreakers,
+    pub provider_backoffs: ProviderBackoffs,
+    /// Which providers each router is load balancing across.
+    pub provider_pools: ProviderPools,
+    /// Each organization's spend in the current budget period.
+    pub spend: SpendTracker,
+    /// Responses stored for the idempotency keys of requests.
+    pub idempotency: IdempotencyStore,
+    /// Model lists served by the unified API and routers.
+    pub model_lists: ModelListCache,
     pub helicone_api_keys: RwLock<Option<HashSet<Key>>>,
     pub router_organization_map: RwLock<HashMap<RouterId, OrgId>>,
 }
@@ -195,4 +293,64 @@ impl AppState {
             self.0.router_organization_map.read().await;
         router_organization_map.get(router_id).copied()
     }
+
+    /// Whether the provider passes the checks done independently of the
+    /// health monitor's error ratio, i.e. its credential hasn't been
+    /// rejected, it isn't failing probes and its circuit isn't open. Each
+    /// check only counts when it is enabled.
+    pub async fn passes_provider_checks(
+        &self,
+        provider: &InferenceProvider,
+    ) -> bool {
+        let monitor = &self.config().discover.monitor;
+        if monitor
+            .credentials
+            .as_ref()
+            .is_some_and(|c| c.remove_unhealthy)
+            && self.0.invalid_credentials.read().await.contains(provider)
+        {
+            return false;
+        }
+        if monitor.probes.is_some()
+            && self.0.unhealthy_probes.read().await.contains(provider)
+        {
+            return false;
+        }
+        self.0.circuit_breakers.allows(provider)
+    }
+
+    /// The key to call the provider with on behalf of the organization.
+    ///
+    /// The organization's own key from the control plane takes precedence.
+    /// In the cloud, keys the organization stored are used next, fetched from
+    /// the database the first time they're needed. Otherwise the platform's
+    /// key for the provider is used, if there is one.
+    pub async fn resolve_provider_key(
+        &self,
+        provider: &InferenceProvider,
+        org_id: Option<&OrgId>,
+    ) -> Result<ProviderKey, ApiError> {
+        if let Some(org_id) = org_id
+            && let Some(key) = self
+                .0
+                .control_plane_state
+                .read()
+                .await
+                .org_provider_key(org_id, provider)
+        {
+            return Ok(key.clone());
+        }
+        let router_store = self.0.router_store.as_ref();
+        self.0
+            .provider_keys
+            .resolve(provider, org_id, |org_id| async move {
+                router_store
+                    .ok_or(ApiError::Internal(InternalError::Internal))?
+                    .get_org_provider_keys(org_id)
+                    .await
+                    .map_err(|_| ApiError::Internal(InternalError::Internal))
+            })
+            .await?
+            .ok_or(ApiError::Authentication(AuthError::ProviderKeyNotFound))
+    }
 }
@@ -1,3 +1,5 @@
+use std::{path::Path, time::SystemTime};
+
 use http_cache::{CacheManager, HttpResponse, MokaManager, Result};
 use http_cache_semantics::CachePolicy;
 use r2d2::Pool;
@@ -23,6 +25,76 @@ struct Store {
     policy: CachePolicy,
 }
 
+/// An entry of an in-memory cache snapshot.
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEntry {
+    key: String,
+    response: HttpResponse,
+    policy: CachePolicy,
+}
+
+/// Writes the entries of the in-memory cache to `path`, returning the number
+/// of entries written.
+///
+/// The snapshot is written to a temporary file first and then renamed, so
+/// that a crash mid-write never leaves a truncated snapshot behind.
+pub async fn save_snapshot(
+    moka: &MokaManager,
+    path: &Path,
+) -> std::io::Result<usize> {
+    let now = SystemTime::now();
+    let mut entries = Vec::new();
+    for (key, _) in moka.cache.iter() {
+        let Ok(Some((response, policy))) = moka.get(&key).await else {
+            continue;
+        };
+        if policy.time_to_live(now).is_zero() {
+            continue;
+        }
+        entries.push(SnapshotEntry {
+            key: (*key).clone(),
+            response,
+            policy,
+        });
+    }
+
+    let serialized = serde_json::to_vec(&entries)?;
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, serialized).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(entries.len())
+}
+
+/// Restores the in-memory cache from a snapshot written by
+/// [`save_snapshot`], returning the number of entries restored.
+///
+/// Entries whose time to live has run out since the snapshot was taken are
+/// skipped. A missing snapshot restores nothing.
+pub async fn load_snapshot(
+    moka: &MokaManager,
+    path: &Path,
+) -> std::io::Result<usize> {
+    let serialized = match tokio::fs::read(path).await {
+        Ok(serialized) => serialized,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let entries: Vec<SnapshotEntry> = serde_json::from_slice(&serialized)?;
+
+    let now = SystemTime::now();
+    let mut restored = 0;
+    for entry in entries {
+        if entry.policy.time_to_live(now).is_zero() {
+            continue;
+        }
+        moka.put(entry.key, entry.response, entry.policy)
+            .await
+            .map_err(std::io::Error::other)?;
+        restored += 1;
+    }
+    Ok(restored)
+}
+
 impl RedisCacheManager {
     pub fn new(url: url::Url) -> std::result::Result<Self, InitError> {
         let client = Client::open(url)?;
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Translation between the OpenAI chat completions and responses APIs, for
+/// when clients and providers don't speak the same one.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiTranslation {
+    /// Serve requests to the `responses` endpoint with the chat completions
+    /// API of the router's providers, including OpenAI. Providers other than
+    /// OpenAI are always served this way.
+    ResponsesToChat,
+    /// Serve requests to the `chat/completions` endpoint with the responses
+    /// API, for models that are only available through it.
+    ChatToResponses,
+}
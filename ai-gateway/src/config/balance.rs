@@ -1,14 +1,17 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
 use derive_more::{AsRef, From};
 use indexmap::IndexSet;
-use nonempty_collections::{NESet, nes};
+use nonempty_collections::{NESet, NEVec, nes};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     endpoints::EndpointType,
     types::{model_id::ModelId, provider::InferenceProvider},
+    utils::{deserialize_from_str, serialize_to_str},
 };
 
 /// A registry of balance configs for each endpoint type,
@@ -41,7 +44,10 @@ impl BalanceConfig {
                 providers: nes![WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::from(1),
+                    schedule: None,
                 }],
+                sticky: false,
+                seed: None,
             },
         )]))
     }
@@ -55,7 +61,10 @@ impl BalanceConfig {
                 providers: nes![WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::from(1),
+                    schedule: None,
                 }],
+                sticky: false,
+                seed: None,
             },
         )]))
     }
@@ -69,7 +78,10 @@ impl BalanceConfig {
                 providers: nes![WeightedProvider {
                     provider: InferenceProvider::GoogleGemini,
                     weight: Decimal::from(1),
+                    schedule: None,
                 }],
+                sticky: false,
+                seed: None,
             },
         )]))
     }
@@ -83,7 +95,10 @@ impl BalanceConfig {
                 providers: nes![WeightedProvider {
                     provider: InferenceProvider::Ollama,
                     weight: Decimal::from(1),
+                    schedule: None,
                 }],
+                sticky: false,
+                seed: None,
             },
         )]))
     }
@@ -97,7 +112,10 @@ impl BalanceConfig {
                 providers: nes![WeightedProvider {
                     provider: InferenceProvider::Bedrock,
                     weight: Decimal::from(1),
+                    schedule: None,
                 }],
+                sticky: false,
+                seed: None,
             },
         )]))
     }
@@ -109,13 +127,30 @@ impl BalanceConfig {
             EndpointType::Chat,
             BalanceConfigInner::ProviderWeighted {
                 providers: nes![WeightedProvider {
-                    provider: InferenceProvider::Named("mistral".into()),
+                    provider: InferenceProvider::Mistral,
                     weight: Decimal::from(1),
+                    schedule: None,
                 }],
+                sticky: false,
+                seed: None,
             },
         )]))
     }
 
+    /// The endpoint type of the balance config that serves requests of
+    /// `endpoint_type`, see [`EndpointType::fallback`].
+    #[must_use]
+    pub fn balanced_endpoint_type(
+        &self,
+        endpoint_type: EndpointType,
+    ) -> EndpointType {
+        if self.0.contains_key(&endpoint_type) {
+            endpoint_type
+        } else {
+            endpoint_type.fallback().unwrap_or(endpoint_type)
+        }
+    }
+
     #[must_use]
     pub fn providers(&self) -> IndexSet<InferenceProvider> {
         self.0
@@ -135,7 +170,19 @@ impl BalanceConfig {
 pub enum BalanceConfigInner {
     /// Distributes and load balances requests among a set of providers.
     #[serde(alias = "weighted")]
-    ProviderWeighted { providers: NESet<WeightedProvider> },
+    ProviderWeighted {
+        providers: NESet<WeightedProvider>,
+        /// Pin each user to a single provider so that provider side prompt
+        /// caching stays warm across a conversation.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        sticky: bool,
+        /// Seeds the random selection of providers, so that the same sequence
+        /// of requests is routed to the same sequence of providers, e.g. in
+        /// tests. Unset in production, where the selection is
+        /// non-deterministic.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seed: Option<u64>,
+    },
     /// Distributes and load balances requests among a set of providers.
     /// This means there is an element of randomness in the selection of the
     /// provider, so generally requests will go to the provider with lowest
@@ -143,21 +190,92 @@ pub enum BalanceConfigInner {
     #[serde(alias = "latency")]
     BalancedLatency { providers: NESet<InferenceProvider> },
     /// Distributes and load balances requests among a set of (providers,model).
+    ///
+    /// Models may share a provider, e.g. to canary a new version of a model,
+    /// and responses are cached separately for each model.
     ModelWeighted { models: NESet<WeightedModel> },
     /// Distributes and load balances requests among a set of (providers,model).
     ModelLatency { models: NESet<ModelId> },
+    /// Sends every request to the first available provider in the list.
+    /// Later providers only receive traffic while all of the earlier ones
+    /// have been removed for being unhealthy or rate limited.
+    Priority { providers: NEVec<InferenceProvider> },
+    /// Gradually shifts traffic from a stable provider to a canary provider.
+    ///
+    /// Requests are bucketed by the value of `header`, so a single user stays
+    /// on the same arm as the percentage is increased. Requests without the
+    /// header are assigned an arm at random.
+    Canary {
+        stable: InferenceProvider,
+        canary: InferenceProvider,
+        /// Percentage of traffic sent to the canary provider, between 0 and
+        /// 100.
+        percentage: Decimal,
+        #[serde(default = "default_canary_header")]
+        header: String,
+    },
+    /// Sends requests with the same key to the same provider, e.g. so that
+    /// requests sharing a system prompt hit a warm provider side prompt cache.
+    ///
+    /// Keys are assigned to providers with a hash ring, so removing a
+    /// provider only moves the keys that were assigned to it. Requests
+    /// without the key are distributed by weight, as with `provider-weighted`.
+    ConsistentHash {
+        providers: NESet<WeightedProvider>,
+        key: HashKeySource,
+    },
+}
+
+/// Where the key for
+/// [`ConsistentHash`](BalanceConfigInner::ConsistentHash) balancing is read
+/// from.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashKeySource {
+    /// The value of a request header.
+    Header(String),
+    /// A [JSON pointer](https://datatracker.ietf.org/doc/html/rfc6901) into
+    /// the request body, e.g. `/messages/0/content`.
+    JsonPointer(String),
+}
+
+fn default_canary_header() -> String {
+    "helicone-user-id".to_string()
 }
 
 impl BalanceConfigInner {
+    /// The sum of the weights for weighted strategies.
+    #[must_use]
+    pub fn total_weight(&self) -> Option<Decimal> {
+        match self {
+            Self::ProviderWeighted { providers, .. }
+            | Self::ConsistentHash { providers, .. } => {
+                Some(providers.iter().map(|p| p.weight).sum())
+            }
+            Self::ModelWeighted { models } => {
+                Some(models.iter().map(|m| m.weight).sum())
+            }
+            Self::BalancedLatency { .. }
+            | Self::ModelLatency { .. }
+            | Self::Priority { .. }
+            | Self::Canary { .. } => None,
+        }
+    }
+
     #[must_use]
     pub fn providers(&self) -> IndexSet<InferenceProvider> {
         match self {
-            Self::ProviderWeighted { providers } => {
+            Self::ProviderWeighted { providers, .. }
+            | Self::ConsistentHash { providers, .. } => {
                 providers.iter().map(|t| t.provider.clone()).collect()
             }
             Self::BalancedLatency { providers } => {
                 providers.iter().cloned().collect()
             }
+            Self::Priority { providers } => providers.iter().cloned().collect(),
+            Self::Canary { stable, canary, .. } => {
+                IndexSet::from([stable.clone(), canary.clone()])
+            }
             Self::ModelWeighted { models } => models
                 .iter()
                 .filter_map(|model| {
@@ -185,6 +303,69 @@ impl BalanceConfigInner {
 pub struct WeightedProvider {
     pub provider: InferenceProvider,
     pub weight: Decimal,
+    /// When the provider is taken out of the rotation, e.g. during its
+    /// maintenance windows. Unset by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ProviderSchedule>,
+}
+
+/// Recurring windows during which a provider's weight is zero, so that its
+/// share of requests is sent to the other providers instead.
+///
+/// A provider is still sent requests during its windows if no other
+/// provider is available.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProviderSchedule {
+    /// The timezone the windows are in, e.g. `America/New_York`. Defaults to
+    /// UTC.
+    #[serde(
+        default = "default_timezone",
+        deserialize_with = "deserialize_from_str",
+        serialize_with = "serialize_to_str"
+    )]
+    pub timezone: Tz,
+    pub exclude: Vec<ExclusionWindow>,
+}
+
+impl ProviderSchedule {
+    /// Whether `now` falls in any of the windows.
+    #[must_use]
+    pub fn is_excluded(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.timezone).naive_local();
+        self.exclude.iter().any(|window| window.contains(local))
+    }
+}
+
+/// A daily window, e.g. from `22:00` to `06:00`. Windows which end before
+/// they start span midnight.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ExclusionWindow {
+    /// The days the window starts on, e.g. `sat`. Every day if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl ExclusionWindow {
+    fn contains(&self, local: NaiveDateTime) -> bool {
+        let starts_on =
+            |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let (day, time) = (local.weekday(), local.time());
+        if self.start <= self.end {
+            starts_on(day) && self.start <= time && time < self.end
+        } else {
+            // the early hours are the end of the previous day's window
+            (starts_on(day) && self.start <= time)
+                || (starts_on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
@@ -193,3 +374,56 @@ pub struct WeightedModel {
     pub model: ModelId,
     pub weight: Decimal,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn schedule(yaml: &str) -> ProviderSchedule {
+        serde_yml::from_str(yaml).expect("invalid schedule")
+    }
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2025-06-02 is a Monday
+        Utc.with_ymd_and_hms(2025, 6, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn windows_spanning_midnight_end_the_next_day() {
+        let schedule = schedule(
+            r"
+exclude:
+  - days: [fri]
+    start: '22:00'
+    end: '06:00'
+",
+        );
+        assert_eq!(schedule.timezone, Tz::UTC);
+        assert!(!schedule.is_excluded(utc(6, 21, 59)));
+        assert!(schedule.is_excluded(utc(6, 22, 0)));
+        assert!(schedule.is_excluded(utc(7, 5, 59)));
+        assert!(!schedule.is_excluded(utc(7, 6, 0)));
+        // the window starts on fridays only
+        assert!(!schedule.is_excluded(utc(7, 23, 0)));
+        assert!(!schedule.is_excluded(utc(5, 5, 0)));
+    }
+
+    #[test]
+    fn windows_are_in_the_schedule_timezone() {
+        let schedule = schedule(
+            r"
+timezone: America/New_York
+exclude:
+  - start: '09:00'
+    end: '17:00'
+",
+        );
+        // 09:00 in New York is 13:00 UTC during daylight saving time
+        assert!(!schedule.is_excluded(utc(2, 12, 59)));
+        assert!(schedule.is_excluded(utc(2, 13, 0)));
+        assert!(schedule.is_excluded(utc(3, 20, 59)));
+        assert!(!schedule.is_excluded(utc(3, 21, 0)));
+    }
+}
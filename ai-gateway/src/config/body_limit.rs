@@ -0,0 +1,56 @@
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+/// 10 MiB, far more than any chat request needs.
+const DEFAULT_MAX_REQUEST_BODY_BYTES: u64 = 10 * 1024 * 1024;
+/// 25 MiB, OpenAI's limit on audio uploads.
+const DEFAULT_MAX_MULTIPART_BODY_BYTES: u64 = 25 * 1024 * 1024;
+
+/// Limits on the size of request bodies, larger requests are rejected with a
+/// 413 before they're buffered.
+///
+/// The global limits apply to every request. Routers and the unified API can
+/// set stricter limits, but not more lenient ones.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct BodyLimitConfig {
+    pub max_request_body_bytes: u64,
+    /// The limit for multipart requests, e.g. audio transcriptions, instead
+    /// of `max-request-body-bytes`.
+    pub max_multipart_body_bytes: u64,
+}
+
+impl Default for BodyLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_request_body_bytes: DEFAULT_MAX_REQUEST_BODY_BYTES,
+            max_multipart_body_bytes: DEFAULT_MAX_MULTIPART_BODY_BYTES,
+        }
+    }
+}
+
+impl BodyLimitConfig {
+    /// The limit for a request with `headers`, depending on whether it's
+    /// multipart.
+    #[must_use]
+    pub fn limit_for(&self, headers: &HeaderMap) -> u64 {
+        let is_multipart = headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| {
+                content_type.trim_start().starts_with("multipart/")
+            });
+        if is_multipart {
+            self.max_multipart_body_bytes
+        } else {
+            self.max_request_body_bytes
+        }
+    }
+
+    /// Whether every limit is at most the corresponding limit of `other`.
+    #[must_use]
+    pub fn is_within(&self, other: &Self) -> bool {
+        self.max_request_body_bytes <= other.max_request_body_bytes
+            && self.max_multipart_body_bytes <= other.max_multipart_body_bytes
+    }
+}
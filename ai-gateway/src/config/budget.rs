@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// A spend budget per organization, in USD.
+///
+/// The cost of each request is estimated from the token usage reported by
+/// the provider, and once an organization's spend for the current period
+/// reaches its limit, further requests are rejected with a `402`. Spend is
+/// tracked by each gateway instance, and resets at the start of each period
+/// (UTC).
+///
+/// The control plane can set limits for individual organizations, which take
+/// precedence over `limit`.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct BudgetConfig {
+    #[serde(alias = "monthly-limit")]
+    pub limit: Decimal,
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// Responses include a warning header once an organization has spent
+    /// this fraction of its limit.
+    #[serde(default = "default_warning_threshold")]
+    pub warning_threshold: Decimal,
+    /// Prices by model. A model's price is the one with the longest name
+    /// that the model starts with, e.g. `gpt-4o` applies to
+    /// `gpt-4o-2024-08-06`.
+    #[serde(default)]
+    pub prices: HashMap<String, TokenPrice>,
+    /// Used for models without a price.
+    #[serde(default)]
+    pub default_price: TokenPrice,
+}
+
+/// How often spend resets.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetPeriod {
+    Daily,
+    /// Weeks start on Monday.
+    Weekly,
+    #[default]
+    Monthly,
+}
+
+impl BudgetPeriod {
+    /// The start of the period `now` falls in.
+    #[must_use]
+    pub fn start(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.date_naive();
+        let start = match self {
+            Self::Daily => today,
+            Self::Weekly => {
+                today
+                    - Days::new(u64::from(
+                        today.weekday().num_days_from_monday(),
+                    ))
+            }
+            Self::Monthly => today.with_day(1).unwrap_or(today),
+        };
+        start.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    /// The end of the period starting at `start`, when spend resets.
+    #[must_use]
+    pub fn end(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        let end = match self {
+            Self::Daily => start.checked_add_days(Days::new(1)),
+            Self::Weekly => start.checked_add_days(Days::new(7)),
+            Self::Monthly => start.checked_add_months(Months::new(1)),
+        };
+        end.unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+impl BudgetConfig {
+    #[must_use]
+    pub fn price(&self, model: &str) -> &TokenPrice {
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map_or(&self.default_price, |(_, price)| price)
+    }
+}
+
+/// Prices in USD per million tokens.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TokenPrice {
+    pub input: Decimal,
+    pub output: Decimal,
+}
+
+impl TokenPrice {
+    #[must_use]
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> Decimal {
+        (self.input * Decimal::from(input_tokens)
+            + self.output * Decimal::from(output_tokens))
+            / Decimal::from(1_000_000)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for BudgetConfig {
+    fn test_default() -> Self {
+        Self {
+            limit: Decimal::from(10),
+            period: BudgetPeriod::default(),
+            warning_threshold: default_warning_threshold(),
+            prices: HashMap::new(),
+            default_price: TokenPrice {
+                input: Decimal::from(1),
+                output: Decimal::from(4),
+            },
+        }
+    }
+}
+
+fn default_warning_threshold() -> Decimal {
+    Decimal::new(8, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_start_and_end_on_their_boundaries() {
+        // a Thursday
+        let now = "2025-01-30T15:04:05Z".parse::<DateTime<Utc>>().unwrap();
+        let boundary = |period: BudgetPeriod| {
+            let start = period.start(now);
+            (start.to_rfc3339(), period.end(start).to_rfc3339())
+        };
+        assert_eq!(
+            boundary(BudgetPeriod::Daily),
+            (
+                "2025-01-30T00:00:00+00:00".to_string(),
+                "2025-01-31T00:00:00+00:00".to_string()
+            )
+        );
+        assert_eq!(
+            boundary(BudgetPeriod::Weekly),
+            (
+                "2025-01-27T00:00:00+00:00".to_string(),
+                "2025-02-03T00:00:00+00:00".to_string()
+            )
+        );
+        assert_eq!(
+            boundary(BudgetPeriod::Monthly),
+            (
+                "2025-01-01T00:00:00+00:00".to_string(),
+                "2025-02-01T00:00:00+00:00".to_string()
+            )
+        );
+    }
+}
@@ -1,11 +1,11 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 pub(crate) const MAX_BUCKET_SIZE: u8 = 10;
 pub(crate) const DEFAULT_BUCKETS: u8 = 1;
 
-#[derive(
-    Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq, Hash,
-)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct CacheConfig {
     /// Cache-control header: <https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Headers/Cache-Control>
@@ -15,6 +15,39 @@ pub struct CacheConfig {
     pub buckets: u8,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<String>,
+    /// Responses with larger bodies, such as generated images returned as
+    /// `b64_json`, are not cached.
+    #[serde(default = "default_max_cached_body_bytes")]
+    pub max_cached_body_bytes: usize,
+    /// The top level request body fields that make up the cache key, e.g.
+    /// `[model, messages, tools, tool_choice, temperature, response_format]`
+    /// to share cached responses between requests with different `user`s.
+    /// Whether the request streams always matters. If unset, every field
+    /// does.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_fields: Option<Vec<String>>,
+    /// Completions are only cached if every choice finished for one of these
+    /// reasons, e.g. so that completions truncated by `length` aren't served
+    /// again. Responses without a finish reason, such as embeddings, are
+    /// cached regardless.
+    #[serde(default = "default_cacheable_finish_reasons")]
+    pub cacheable_finish_reasons: Vec<String>,
+    /// Whether completions without any content are cached.
+    pub cache_empty_completions: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            directive: None,
+            buckets: default_buckets(),
+            seed: None,
+            max_cached_body_bytes: default_max_cached_body_bytes(),
+            key_fields: None,
+            cacheable_finish_reasons: default_cacheable_finish_reasons(),
+            cache_empty_completions: false,
+        }
+    }
 }
 
 #[cfg(feature = "testing")]
@@ -24,6 +57,10 @@ impl crate::tests::TestDefault for CacheConfig {
             directive: None,
             buckets: DEFAULT_BUCKETS,
             seed: None,
+            max_cached_body_bytes: default_max_cached_body_bytes(),
+            key_fields: None,
+            cacheable_finish_reasons: default_cacheable_finish_reasons(),
+            cache_empty_completions: false,
         }
     }
 }
@@ -41,6 +78,15 @@ pub enum CacheStore {
         // manually
         #[serde(rename = "max-size", default = "default_max_size")]
         max_size: usize,
+        /// If set, the cache is written to this file on graceful shutdown
+        /// and restored from it on startup. Entries which expired in the
+        /// meantime are not restored.
+        #[serde(
+            rename = "snapshot-path",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        snapshot_path: Option<PathBuf>,
     },
 }
 
@@ -48,6 +94,7 @@ impl Default for CacheStore {
     fn default() -> Self {
         Self::InMemory {
             max_size: default_max_size(),
+            snapshot_path: None,
         }
     }
 }
@@ -61,6 +108,26 @@ fn default_buckets() -> u8 {
     1
 }
 
+fn default_max_cached_body_bytes() -> usize {
+    // 16MB, enough for a few generated images
+    1024 * 1024 * 16
+}
+
+/// The reasons `OpenAI` and Anthropic completions finish without being cut
+/// short.
+fn default_cacheable_finish_reasons() -> Vec<String> {
+    [
+        "stop",
+        "tool_calls",
+        "function_call",
+        "end_turn",
+        "tool_use",
+        "stop_sequence",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
 fn default_host_url() -> url::Url {
     "redis://localhost:6340".parse().unwrap()
 }
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// Compresses responses with an encoding the client accepts.
+///
+/// Responses are compressed after the cache, so cached responses are stored
+/// uncompressed and served in whichever encoding each client accepts. Event
+/// streams are never compressed, since compression would buffer events and
+/// delay them.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CompressionConfig {
+    /// The encodings responses may be compressed with, the client's
+    /// `accept-encoding` preferences choose between them. Responses are
+    /// never compressed if empty.
+    pub encodings: Vec<CompressionEncoding>,
+    /// Smaller responses are sent uncompressed, since compressing them saves
+    /// little.
+    pub min_size_bytes: u16,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompressionEncoding {
+    Gzip,
+    Br,
+    Deflate,
+    Zstd,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            encodings: vec![
+                CompressionEncoding::Gzip,
+                CompressionEncoding::Br,
+                CompressionEncoding::Deflate,
+                CompressionEncoding::Zstd,
+            ],
+            min_size_bytes: 1024,
+        }
+    }
+}
+
+impl CompressionConfig {
+    #[must_use]
+    pub fn is_enabled(&self, encoding: CompressionEncoding) -> bool {
+        self.encodings.contains(&encoding)
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    num::{NonZeroU32, NonZeroUsize},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Caps the number of concurrent requests through a router, so that one busy
+/// router can't starve the others.
+///
+/// Requests beyond the limit wait in a bounded queue for a slot to free up,
+/// and are rejected with a `503` if the queue is full or the wait times out.
+/// Cached responses don't count towards the limit.
+///
+/// Queued requests are admitted by their [`PriorityClass`] using weighted fair
+/// queuing, so that e.g. batch jobs can't starve interactive traffic while
+/// still making progress themselves.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConcurrencyLimitConfig {
+    /// Maximum number of in-flight requests.
+    pub limit: NonZeroUsize,
+    /// Maximum number of requests waiting for a slot.
+    #[serde(default = "default_queue_depth")]
+    pub queue_depth: usize,
+    /// Maximum time a request waits for a slot.
+    #[serde(default = "default_queue_timeout", with = "humantime_serde")]
+    pub queue_timeout: Duration,
+    /// Share of freed slots each priority class gets while requests are
+    /// queued.
+    #[serde(default)]
+    pub priority_weights: PriorityWeights,
+}
+
+/// Set with the `helicone-priority` header, requests without it are
+/// [`PriorityClass::Normal`].
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Deserialize,
+    Serialize,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum PriorityClass {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// Relative weights of the priority classes, e.g. with the defaults queued
+/// high priority requests are admitted at 4 times the rate of low priority
+/// ones.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PriorityWeights {
+    pub high: NonZeroU32,
+    pub normal: NonZeroU32,
+    pub low: NonZeroU32,
+}
+
+impl PriorityWeights {
+    #[must_use]
+    pub fn weight(&self, class: PriorityClass) -> NonZeroU32 {
+        match class {
+            PriorityClass::High => self.high,
+            PriorityClass::Normal => self.normal,
+            PriorityClass::Low => self.low,
+        }
+    }
+}
+
+impl Default for PriorityWeights {
+    fn default() -> Self {
+        Self {
+            high: NonZeroU32::new(4).unwrap(),
+            normal: NonZeroU32::new(2).unwrap(),
+            low: NonZeroU32::MIN,
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for ConcurrencyLimitConfig {
+    fn test_default() -> Self {
+        Self {
+            limit: NonZeroUsize::MIN,
+            queue_depth: 1,
+            queue_timeout: Duration::from_secs(5),
+            priority_weights: PriorityWeights::default(),
+        }
+    }
+}
+
+fn default_queue_depth() -> usize {
+    100
+}
+
+fn default_queue_timeout() -> Duration {
+    Duration::from_secs(30)
+}
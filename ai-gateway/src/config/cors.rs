@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Allows any origin, method or header.
+pub const CORS_WILDCARD: &str = "*";
+
+/// Cross-origin resource sharing, for browsers calling the gateway directly.
+///
+/// Preflight requests are answered before authentication, so that browsers
+/// can send them without credentials.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CorsConfig {
+    /// The origins browsers may call the gateway from, e.g.
+    /// `https://app.example.com`, or `*` for any origin. A `*` within an
+    /// origin matches any subdomain, e.g. `https://*.example.com`. Requests
+    /// from other origins get no CORS headers, so browsers block them.
+    pub allowed_origins: Vec<String>,
+    /// The methods allowed, or `*` for any method.
+    pub allowed_methods: Vec<String>,
+    /// The request headers allowed, or `*` for any header.
+    pub allowed_headers: Vec<String>,
+    /// The response headers scripts can read, besides the headers browsers
+    /// always expose. Defaults to the headers the gateway sets.
+    pub exposed_headers: Vec<String>,
+    /// How long browsers may cache preflight responses. Browsers cap this,
+    /// and use a few seconds if it's unset.
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<Duration>,
+    /// Whether browsers send cookies with requests and let scripts read the
+    /// responses. Can't be combined with a `*`.
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![CORS_WILDCARD.to_string()],
+            allowed_methods: vec![CORS_WILDCARD.to_string()],
+            allowed_headers: vec![CORS_WILDCARD.to_string()],
+            exposed_headers: default_exposed_headers(),
+            max_age: None,
+            allow_credentials: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Whether any of the allowed origins, methods or headers is `*`. Origins
+    /// with a wildcard subdomain are fine, since they're echoed back.
+    #[must_use]
+    pub fn has_wildcard(&self) -> bool {
+        [
+            &self.allowed_origins,
+            &self.allowed_methods,
+            &self.allowed_headers,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|value| value == CORS_WILDCARD)
+    }
+}
+
+fn default_exposed_headers() -> Vec<String> {
+    [
+        "helicone-cache",
+        "helicone-cache-bucket-idx",
+        "helicone-provider",
+        "helicone-provider-req-id",
+        "helicone-provider-override",
+        "helicone-canary-arm",
+        "helicone-budget-warning",
+        "helicone-params-adjusted",
+        "helicone-json-repaired",
+        "idempotent-replayed",
+        "x-request-id",
+        "retry-after",
+        "x-ratelimit-limit",
+        "x-ratelimit-remaining",
+        "x-ratelimit-reset",
+        "x-ratelimit-after",
+    ]
+    .map(String::from)
+    .to_vec()
+}
@@ -1,20 +1,140 @@
 use std::time::Duration;
 
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
+/// Headers the gateway sets itself when authenticating requests to
+/// providers, which upstream headers may not override.
+const PROTECTED_UPSTREAM_HEADERS: [&str; 7] = [
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+    "host",
+    "content-length",
+];
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 15);
+const DEFAULT_STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DispatcherConfig {
-    #[serde(default = "default_timeout", with = "humantime_serde")]
-    pub timeout: Duration,
-    #[serde(default = "default_connection_timeout", with = "humantime_serde")]
-    pub connection_timeout: Duration,
+    /// The default time to wait for providers, which can be overridden per
+    /// router and per provider.
+    #[serde(default, skip_serializing_if = "TimeoutsConfig::is_empty")]
+    pub timeouts: TimeoutsConfig,
+    /// How connections to providers are kept open and reused, which can be
+    /// overridden per provider.
+    #[serde(default)]
+    pub connection_pool: ConnectionPoolConfig,
+    /// Headers added to every request sent to a provider, replacing any sent
+    /// by the client. A provider's `upstream-headers` take precedence.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub default_upstream_headers: IndexMap<String, String>,
+    /// Respond to every request with a synthetic completion instead of
+    /// calling the provider, while still authenticating, load balancing,
+    /// checking the cache, logging and recording metrics as usual. Useful to
+    /// validate a config before sending it production traffic. Can also be
+    /// enabled per router.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
 }
 
 impl Default for DispatcherConfig {
     fn default() -> Self {
         Self {
-            timeout: default_timeout(),
-            connection_timeout: default_connection_timeout(),
+            timeouts: TimeoutsConfig::default(),
+            connection_pool: ConnectionPoolConfig::default(),
+            default_upstream_headers: IndexMap::new(),
+            dry_run: false,
+        }
+    }
+}
+
+/// How long to wait for a provider before giving up on a request with a
+/// `504`. Timeouts not set fall back to the router's, then the dispatcher's,
+/// then the defaults.
+///
+/// Set per provider, they take precedence over those of the router.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq,
+)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TimeoutsConfig {
+    /// The time to wait for a provider to accept a connection, 10 seconds by
+    /// default. Connections are shared by every router, so this can't be set
+    /// per router.
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub connect: Option<Duration>,
+    /// The time to wait for a provider to respond, 15 minutes by default. For
+    /// streams this is the time to wait for the first event. Retries of a
+    /// request each get the full timeout.
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub request: Option<Duration>,
+    /// The time to wait between the events of a stream, 5 minutes by
+    /// default. Streams which go idle for longer end with an error event.
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub stream_idle: Option<Duration>,
+}
+
+impl TimeoutsConfig {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fills the timeouts which aren't set with those of `fallback`.
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            connect: self.connect.or(fallback.connect),
+            request: self.request.or(fallback.request),
+            stream_idle: self.stream_idle.or(fallback.stream_idle),
+        }
+    }
+
+    #[must_use]
+    pub fn connect(&self) -> Duration {
+        self.connect.unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    #[must_use]
+    pub fn request(&self) -> Duration {
+        self.request.unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    #[must_use]
+    pub fn stream_idle(&self) -> Duration {
+        self.stream_idle.unwrap_or(DEFAULT_STREAM_IDLE_TIMEOUT)
+    }
+}
+
+/// The connections kept open to each provider, which are shared by every
+/// router and direct proxy dispatching to it.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ConnectionPoolConfig {
+    /// The most idle connections kept open per host. `0` disables pooling,
+    /// so that every request opens a new connection.
+    pub max_idle_per_host: usize,
+    /// How long idle connections are kept open before being closed.
+    #[serde(with = "humantime_serde")]
+    pub idle_timeout: Duration,
+    /// The interval of the TCP keep-alive probes sent on open connections,
+    /// so that connections aren't silently dropped by load balancers and
+    /// NATs between requests.
+    #[serde(with = "humantime_serde")]
+    pub tcp_keepalive: Duration,
+}
+
+impl Default for ConnectionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: 32,
+            idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: Duration::from_secs(60),
         }
     }
 }
@@ -26,10 +146,10 @@ impl crate::tests::TestDefault for DispatcherConfig {
     }
 }
 
-fn default_timeout() -> Duration {
-    Duration::from_secs(60 * 15)
-}
-
-fn default_connection_timeout() -> Duration {
-    Duration::from_secs(10)
+/// Whether the gateway sets the header itself, including the AWS signature
+/// headers of Bedrock requests.
+pub(crate) fn is_protected_upstream_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    PROTECTED_UPSTREAM_HEADERS.contains(&name.as_str())
+        || name.starts_with("x-amz-")
 }
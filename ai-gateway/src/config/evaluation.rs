@@ -0,0 +1,58 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::types::provider::InferenceProvider;
+
+/// Replaced with the client's request body when rendering the prompt.
+pub const REQUEST_PLACEHOLDER: &str = "{{request}}";
+/// Replaced with the provider's response body when rendering the prompt.
+pub const RESPONSE_PLACEHOLDER: &str = "{{response}}";
+
+/// Scores a sample of a router's chat completions with a judge model, e.g. to
+/// monitor the quality of responses from a new provider.
+///
+/// The judge request is sent after the response has been returned to the
+/// client, so it never affects the client. The first number in the judge's
+/// reply is recorded as the score in the request's log. Streaming requests are
+/// not evaluated.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub struct EvaluationConfig {
+    pub provider: InferenceProvider,
+    /// The judge model, e.g. `openai/gpt-4o`.
+    pub model: String,
+    /// Fraction of requests to evaluate, between 0 and 1.
+    pub sample_rate: Decimal,
+    /// The prompt sent to the judge, see [`REQUEST_PLACEHOLDER`] and
+    /// [`RESPONSE_PLACEHOLDER`].
+    #[serde(default = "default_prompt_template")]
+    pub prompt_template: String,
+}
+
+fn default_prompt_template() -> String {
+    "Rate the quality of the assistant's response to the request below on a \
+     scale from 1 to 10. Reply with only the \
+     number.\n\nRequest:\n{{request}}\n\nResponse:\n{{response}}"
+        .to_string()
+}
+
+impl EvaluationConfig {
+    #[must_use]
+    pub fn render_prompt(&self, request: &str, response: &str) -> String {
+        self.prompt_template
+            .replace(REQUEST_PLACEHOLDER, request)
+            .replace(RESPONSE_PLACEHOLDER, response)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for EvaluationConfig {
+    fn test_default() -> Self {
+        Self {
+            provider: InferenceProvider::Anthropic,
+            model: "anthropic/claude-3-5-haiku".to_string(),
+            sample_rate: Decimal::ONE,
+            prompt_template: default_prompt_template(),
+        }
+    }
+}
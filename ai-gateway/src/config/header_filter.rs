@@ -0,0 +1,244 @@
+use http::{HeaderMap, HeaderName};
+use serde::{Deserialize, Serialize};
+
+/// Matches any header.
+pub const HEADER_WILDCARD: &str = "*";
+
+/// Client headers forwarded to providers by default, including the
+/// organization, project and `x-stainless-*` headers sent by `OpenAI`'s SDKs.
+/// Credentials aren't listed, since the gateway authenticates requests
+/// itself.
+const DEFAULT_ALLOWED_REQUEST_HEADERS: [&str; 9] = [
+    "accept",
+    "content-type",
+    "user-agent",
+    "anthropic-version",
+    "anthropic-beta",
+    "openai-beta",
+    "openai-organization",
+    "openai-project",
+    "x-stainless-*",
+];
+
+/// Client headers never forwarded to providers by default, even if allowed.
+const DEFAULT_DENIED_REQUEST_HEADERS: [&str; 3] =
+    ["cookie", "helicone-*", "x-helicone-*"];
+
+/// Provider headers forwarded to clients by default: request ids, rate limit
+/// details and processing times.
+const DEFAULT_ALLOWED_RESPONSE_HEADERS: [&str; 10] = [
+    "content-type",
+    "request-id",
+    "x-request-id",
+    "openai-processing-ms",
+    "openai-version",
+    "x-ratelimit-*",
+    "anthropic-ratelimit-*",
+    "retry-after",
+    "retry-after-ms",
+    "x-envoy-upstream-service-time",
+];
+
+/// Provider headers never forwarded to clients by default, even if allowed:
+/// cookies and the account details providers echo back.
+const DEFAULT_DENIED_RESPONSE_HEADERS: [&str; 4] = [
+    "set-cookie",
+    "openai-organization",
+    "openai-project",
+    "anthropic-organization-id",
+];
+
+/// Which headers are forwarded between clients and providers, on top of the
+/// defaults. Patterns are header names, prefixes ending in `*`, e.g.
+/// `x-ratelimit-*`, or `*` for any header.
+///
+/// A header is forwarded if it matches `allow` or the default allow list,
+/// unless it matches `deny` or the default deny list. Headers on the default
+/// deny list are only forwarded when allowed by name, e.g. `set-cookie`
+/// isn't forwarded by allowing `*`.
+///
+/// Only headers sent by clients and providers are filtered. Headers the
+/// gateway sets itself, such as provider credentials, `upstream-headers` and
+/// `helicone-*` response headers, are always sent.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HeaderFilterConfig {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl HeaderFilterConfig {
+    /// The client headers of `headers` which are forwarded to providers.
+    #[must_use]
+    pub fn request_headers(&self, headers: &HeaderMap) -> HeaderMap {
+        self.filter(
+            headers,
+            &DEFAULT_ALLOWED_REQUEST_HEADERS,
+            &DEFAULT_DENIED_REQUEST_HEADERS,
+        )
+    }
+
+    /// The provider headers of `headers` which are forwarded to clients.
+    #[must_use]
+    pub fn response_headers(&self, headers: &HeaderMap) -> HeaderMap {
+        self.filter(
+            headers,
+            &DEFAULT_ALLOWED_RESPONSE_HEADERS,
+            &DEFAULT_DENIED_RESPONSE_HEADERS,
+        )
+    }
+
+    /// Patterns which aren't a header name, a prefix ending in `*` or `*`.
+    #[must_use]
+    pub fn invalid_patterns(&self) -> Vec<String> {
+        self.allow
+            .iter()
+            .chain(&self.deny)
+            .filter(|pattern| {
+                let name =
+                    pattern.strip_suffix(HEADER_WILDCARD).unwrap_or(pattern);
+                let is_valid = name.is_empty()
+                    || (!name.contains(HEADER_WILDCARD)
+                        && HeaderName::from_bytes(name.as_bytes()).is_ok());
+                !is_valid
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn filter(
+        &self,
+        headers: &HeaderMap,
+        default_allow: &[&str],
+        default_deny: &[&str],
+    ) -> HeaderMap {
+        headers
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.as_str();
+                let allowed_by_name = self
+                    .allow
+                    .iter()
+                    .any(|pattern| pattern.eq_ignore_ascii_case(name));
+                let allowed = matches_any(&self.allow, name)
+                    || matches_any(default_allow, name);
+                let forwarded = allowed_by_name
+                    || (allowed && !matches_any(default_deny, name));
+                forwarded && !matches_any(&self.deny, name)
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+fn matches_any(patterns: &[impl AsRef<str>], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| matches(pattern.as_ref(), name))
+}
+
+/// Whether the lowercase header `name` matches `pattern`.
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix(HEADER_WILDCARD) {
+        Some(prefix) => name
+            .get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn headers(names: &[&'static str]) -> HeaderMap {
+        names
+            .iter()
+            .map(|name| {
+                (HeaderName::from_static(name), HeaderValue::from_static("1"))
+            })
+            .collect()
+    }
+
+    fn names(headers: &HeaderMap) -> Vec<&str> {
+        let mut names: Vec<_> =
+            headers.keys().map(HeaderName::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn defaults_forward_sdk_and_rate_limit_headers_only() {
+        let filter = HeaderFilterConfig::default();
+        let response = headers(&[
+            "request-id",
+            "x-ratelimit-remaining-tokens",
+            "set-cookie",
+            "openai-organization",
+            "server",
+        ]);
+        let request = headers(&[
+            "content-type",
+            "anthropic-version",
+            "openai-organization",
+            "x-stainless-os",
+            "helicone-property-user",
+            "cookie",
+        ]);
+
+        assert_eq!(
+            names(&filter.response_headers(&response)),
+            ["request-id", "x-ratelimit-remaining-tokens"]
+        );
+        assert_eq!(
+            names(&filter.request_headers(&request)),
+            [
+                "anthropic-version",
+                "content-type",
+                "openai-organization",
+                "x-stainless-os"
+            ]
+        );
+    }
+
+    #[test]
+    fn denied_headers_are_only_forwarded_when_allowed_by_name() {
+        let filter = HeaderFilterConfig {
+            allow: vec!["*".to_string(), "set-cookie".to_string()],
+            deny: vec!["x-ratelimit-*".to_string()],
+        };
+        let response = headers(&[
+            "request-id",
+            "x-ratelimit-remaining-tokens",
+            "set-cookie",
+            "openai-organization",
+            "server",
+        ]);
+
+        assert_eq!(
+            names(&filter.response_headers(&response)),
+            ["request-id", "server", "set-cookie"]
+        );
+    }
+
+    #[test]
+    fn patterns_must_be_header_names_or_prefixes() {
+        let filter = HeaderFilterConfig {
+            allow: vec![
+                "*".to_string(),
+                "x-ratelimit-*".to_string(),
+                "not a header".to_string(),
+            ],
+            deny: vec!["x-*-id".to_string()],
+        };
+
+        assert_eq!(
+            filter.invalid_patterns(),
+            vec!["not a header".to_string(), "x-*-id".to_string()]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Replays the response of a request to clients which retry it with the same
+/// `Idempotency-Key` header, rather than calling the provider again.
+///
+/// Unlike the response cache, requests are only replayed when the client
+/// sends the key, and the key alone identifies the request.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IdempotencyConfig {
+    /// How long a response is replayed for after the request completes.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// Responses with larger bodies aren't stored, so retries of them are
+    /// sent to the provider again.
+    pub max_stored_body_bytes: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(24 * 60 * 60),
+            // 16MB, as for cached responses
+            max_stored_body_bytes: 1024 * 1024 * 16,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// What a router does when the completion of a `json_object` request isn't
+/// valid JSON.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonRepairMode {
+    /// Send the completion to the client as is.
+    #[default]
+    Off,
+    /// Strip markdown code fences and text around the JSON value.
+    Repair,
+    /// Send the request to the provider once more, repairing the completion
+    /// of the retry if it isn't valid either.
+    Retry,
+}
+
+impl JsonRepairMode {
+    #[must_use]
+    pub fn is_off(&self) -> bool {
+        *self == Self::Off
+    }
+}
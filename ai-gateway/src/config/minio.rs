@@ -1,3 +1,5 @@
+use std::{num::NonZeroUsize, time::Duration};
+
 use serde::{Deserialize, Serialize};
 use url::Url;
 
@@ -28,6 +30,48 @@ impl From<UrlStyle> for rusty_s3::UrlStyle {
     }
 }
 
+/// How the raw audio of audio requests and responses is stored. Audio
+/// metadata, such as the file name and size, is always stored in its place.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq,
+)]
+#[serde(rename_all = "kebab-case", tag = "mode")]
+pub enum AudioStorage {
+    /// Don't store any of the audio.
+    #[default]
+    Skip,
+    /// Store up to `max-bytes` of the audio, base64 encoded, e.g. to
+    /// identify the file.
+    Truncate {
+        #[serde(rename = "max-bytes", default = "default_max_audio_bytes")]
+        max_bytes: usize,
+    },
+}
+
+/// Uploads the bodies of several requests together, as newline delimited JSON
+/// objects, rather than one object per request.
+///
+/// A batch is uploaded once it has `max-logs` bodies or its first body has
+/// waited for `max-wait`, and pending batches are uploaded on shutdown.
+/// Batches are per organization, since objects are stored per organization.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BatchingConfig {
+    #[serde(default = "default_batch_max_logs")]
+    pub max_logs: NonZeroUsize,
+    #[serde(with = "humantime_serde", default = "default_batch_max_wait")]
+    pub max_wait: Duration,
+}
+
+impl Default for BatchingConfig {
+    fn default() -> Self {
+        Self {
+            max_logs: default_batch_max_logs(),
+            max_wait: default_batch_max_wait(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Config {
@@ -49,6 +93,11 @@ pub struct Config {
     /// Only required if running with `DeploymentTarget::Cloud`.
     #[serde(default = "default_secret_key")]
     pub secret_key: Secret<String>,
+    #[serde(default)]
+    pub audio: AudioStorage,
+    /// If not set, the bodies of each request are uploaded on their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batching: Option<BatchingConfig>,
 }
 
 impl Default for Config {
@@ -60,6 +109,8 @@ impl Default for Config {
             region: default_region(),
             access_key: default_access_key(),
             secret_key: default_secret_key(),
+            audio: AudioStorage::default(),
+            batching: None,
         }
     }
 }
@@ -88,6 +139,18 @@ fn default_secret_key() -> Secret<String> {
     Secret::from("minioadmin".to_string())
 }
 
+fn default_max_audio_bytes() -> usize {
+    4096
+}
+
+fn default_batch_max_logs() -> NonZeroUsize {
+    NonZeroUsize::new(100).unwrap()
+}
+
+fn default_batch_max_wait() -> Duration {
+    Duration::from_secs(5)
+}
+
 #[cfg(feature = "testing")]
 impl crate::tests::TestDefault for Config {
     fn test_default() -> Self {
@@ -1,34 +1,56 @@
+pub mod api_translation;
 pub mod balance;
+pub mod body_limit;
+pub mod budget;
 pub mod cache;
+pub mod compression;
+pub mod concurrency_limit;
+pub mod cors;
 pub mod database;
 pub mod discover;
 pub mod dispatcher;
+pub mod evaluation;
+pub mod header_filter;
 pub mod helicone;
+pub mod idempotency;
+pub mod json_repair;
 pub mod minio;
+pub mod model_alias;
+pub mod model_list;
 pub mod model_mapping;
 pub mod monitor;
 pub mod providers;
 pub mod rate_limit;
+pub mod redaction;
 pub mod redis;
+pub mod request_logging;
 pub mod response_headers;
 pub mod retry;
 pub mod router;
+pub mod routing_rules;
 pub mod server;
+pub mod shadow;
+pub mod stream_limit;
+pub mod streaming;
+pub mod transform;
 pub mod validation;
 use std::path::PathBuf;
 
 use config::ConfigError;
 use displaydoc::Display;
 use json_patch::merge;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use strum::IntoStaticStr;
 use thiserror::Error;
 use url::Url;
 
 use crate::{
+    config::validation::ConfigValidationErrors,
     error::init::InitError,
-    types::{provider::InferenceProvider, secret::Secret},
+    types::{
+        provider::{InferenceProvider, ProviderKeys},
+        secret::Secret,
+    },
 };
 
 const ROUTER_ID_REGEX: &str = r"^[A-Za-z0-9_-]{1,12}$";
@@ -67,6 +89,12 @@ pub struct MiddlewareConfig {
     pub rate_limit: Option<self::rate_limit::RateLimitConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub retries: Option<self::retry::RetryConfig>,
+    /// Defaults to [`BodyLimitConfig::default`] globally, and to no stricter
+    /// limit for the unified API.
+    ///
+    /// [`BodyLimitConfig::default`]: self::body_limit::BodyLimitConfig
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_limit: Option<self::body_limit::BodyLimitConfig>,
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -79,11 +107,26 @@ pub struct Config {
     pub dispatcher: self::dispatcher::DispatcherConfig,
     pub discover: self::discover::DiscoverConfig,
     pub response_headers: self::response_headers::ResponseHeadersConfig,
+    /// Which client headers are forwarded to providers, which can be
+    /// overridden per router.
+    pub request_headers: self::header_filter::HeaderFilterConfig,
+    pub compression: self::compression::CompressionConfig,
+    pub cors: self::cors::CorsConfig,
+    /// Redaction applied to logged bodies for every router, unless the
+    /// router configures its own.
+    pub redaction: self::redaction::RedactionConfig,
     pub deployment_target: DeploymentTarget,
 
     /// If a request is made with a model that is not in the `RouterConfig`
     /// model mapping, then we fallback to this.
     pub default_model_mapping: self::model_mapping::ModelMappingConfig,
+    /// Logical model names, resolved per provider after a provider is
+    /// selected.
+    #[serde(
+        skip_serializing_if = "self::model_alias::ModelAliasConfig::is_empty"
+    )]
+    pub model_aliases: self::model_alias::ModelAliasConfig,
+    pub model_list: self::model_list::ModelListConfig,
     pub helicone: self::helicone::HeliconeConfig,
     /// *ALL* supported providers, independent of router configuration.
     pub providers: self::providers::ProvidersConfig,
@@ -92,6 +135,12 @@ pub struct Config {
     pub cache_store: Option<self::cache::CacheStore>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rate_limit_store: Option<self::rate_limit::RateLimitStore>,
+    /// Monthly spend budget applied to every organization.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<self::budget::BudgetConfig>,
+    /// Replays responses to requests retried with an `Idempotency-Key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency: Option<self::idempotency::IdempotencyConfig>,
     /// Global middleware configuration, e.g. rate limiting, caching, etc.
     ///
     /// This configuration will be for middleware that is applied to ALL
@@ -169,32 +218,72 @@ impl Config {
                 Secret::from(helicone_control_plane_api_key);
         }
 
-        if let Ok(bedrock_region) = std::env::var("AWS_REGION")
-            && let Some(bedrock_provider) =
-                config.providers.get_mut(&InferenceProvider::Bedrock)
+        if let Some(bedrock_provider) =
+            config.providers.get_mut(&InferenceProvider::Bedrock)
         {
-            let bedrock_url = format!(
-                "https://bedrock-runtime.{bedrock_region}.amazonaws.com"
-            );
-            bedrock_provider.base_url =
-                Url::parse(&bedrock_url).map_err(Error::UrlParse)?;
+            if bedrock_provider.region.is_none() {
+                bedrock_provider.region = std::env::var("AWS_REGION").ok();
+            }
+            // the default base url is the us-east-1 endpoint, an overridden
+            // base url (e.g. a VPC endpoint) is kept as is
+            let default_base_url = self::providers::ProvidersConfig::default()
+                .get(&InferenceProvider::Bedrock)
+                .map(|provider| provider.base_url.clone());
+            if let Some(bedrock_region) = &bedrock_provider.region
+                && default_base_url.as_ref() == Some(&bedrock_provider.base_url)
+            {
+                let bedrock_url = format!(
+                    "https://bedrock-runtime.{bedrock_region}.amazonaws.com"
+                );
+                bedrock_provider.base_url =
+                    Url::parse(&bedrock_url).map_err(Error::UrlParse)?;
+            }
+        }
+
+        if let Some(vertex_provider) =
+            config.providers.get_mut(&InferenceProvider::VertexAI)
+        {
+            if vertex_provider.project.is_none() {
+                vertex_provider.project =
+                    std::env::var("GOOGLE_CLOUD_PROJECT").ok();
+            }
+            // as for bedrock, the default base url is the us-central1
+            // endpoint and an overridden base url is kept as is
+            let default_base_url = self::providers::ProvidersConfig::default()
+                .get(&InferenceProvider::VertexAI)
+                .map(|provider| provider.base_url.clone());
+            if let Some(vertex_region) = &vertex_provider.region
+                && default_base_url.as_ref() == Some(&vertex_provider.base_url)
+            {
+                let vertex_url = format!(
+                    "https://{vertex_region}-aiplatform.googleapis.com"
+                );
+                vertex_provider.base_url =
+                    Url::parse(&vertex_url).map_err(Error::UrlParse)?;
+            }
         }
 
         Ok(config)
     }
 
+    /// Validate the config, checking provider credentials against those
+    /// found in the environment.
     pub fn validate(&self) -> Result<(), InitError> {
-        let router_id_regex =
-            Regex::new(ROUTER_ID_REGEX).expect("always valid if tests pass");
-        for (router_id, router_config) in self.routers.as_ref() {
-            router_config.validate()?;
-            if !router_id_regex.is_match(router_id.as_ref()) {
-                return Err(InitError::InvalidRouterId(router_id.to_string()));
-            }
-        }
+        self.validate_with_provider_keys(&ProviderKeys::new(self))
+    }
+
+    pub fn validate_with_provider_keys(
+        &self,
+        provider_keys: &ProviderKeys,
+    ) -> Result<(), InitError> {
         // TODO: merged configs make this brittle. bring it back after we've
         // improved that self.validate_model_mappings()?;
-        Ok(())
+        let errors = self.validation_errors(provider_keys);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(InitError::InvalidConfig(ConfigValidationErrors(errors)))
+        }
     }
 }
 
@@ -214,6 +303,8 @@ impl crate::tests::TestDefault for Config {
             dispatcher: self::dispatcher::DispatcherConfig::test_default(),
             default_model_mapping:
                 self::model_mapping::ModelMappingConfig::default(),
+            model_aliases: self::model_alias::ModelAliasConfig::default(),
+            model_list: self::model_list::ModelListConfig::default(),
             global: MiddlewareConfig::default(),
             unified_api: MiddlewareConfig::default(),
             providers: self::providers::ProvidersConfig::default(),
@@ -222,9 +313,15 @@ impl crate::tests::TestDefault for Config {
             discover: self::discover::DiscoverConfig::test_default(),
             cache_store: Some(self::cache::CacheStore::default()),
             rate_limit_store: Some(self::rate_limit::RateLimitStore::default()),
+            budget: None,
+            idempotency: None,
             routers: self::router::RouterConfigs::test_default(),
             response_headers:
                 self::response_headers::ResponseHeadersConfig::default(),
+            request_headers: self::header_filter::HeaderFilterConfig::default(),
+            compression: self::compression::CompressionConfig::default(),
+            cors: self::cors::CorsConfig::default(),
+            redaction: self::redaction::RedactionConfig::default(),
         }
     }
 }
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::mapper::MapperError,
+    types::{
+        model_id::{ModelId, ModelName},
+        provider::InferenceProvider,
+    },
+};
+
+/// Logical model names, each resolved to a concrete model for whichever
+/// provider a request is routed to, e.g. `gpt-4o-mini` to `claude-3-5-haiku`
+/// on Anthropic.
+///
+/// Aliases are resolved after a provider is selected, and take precedence
+/// over the model mappings. A request for an alias without a model for the
+/// selected provider fails rather than being mapped, so priority routers
+/// fail over to the next provider in their list.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ModelAliasConfig(
+    pub(crate) HashMap<ModelName<'static>, HashMap<InferenceProvider, String>>,
+);
+
+impl ModelAliasConfig {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The model `model` resolves to on `provider`, or `None` if it isn't an
+    /// alias.
+    #[must_use]
+    pub fn resolve(
+        &self,
+        model: &ModelId,
+        provider: &InferenceProvider,
+    ) -> Option<Result<ModelId, MapperError>> {
+        let alias = ModelName::from_model(model);
+        let models = self.0.get(&alias)?;
+        let resolved = models
+            .get(provider)
+            .ok_or_else(|| {
+                MapperError::NoModelMapping(provider.clone(), alias.to_string())
+            })
+            .and_then(|model| {
+                ModelId::from_str_and_provider(provider.clone(), model)
+            });
+        Some(resolved)
+    }
+}
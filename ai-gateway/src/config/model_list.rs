@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// The model lists served by `GET /ai/models` and `GET /router/{id}/models`.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ModelListConfig {
+    /// How long an assembled model list is cached in-process.
+    #[serde(with = "humantime_serde", default = "default_cache_ttl")]
+    pub cache_ttl: Duration,
+}
+
+impl Default for ModelListConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl: default_cache_ttl(),
+        }
+    }
+}
+
+fn default_cache_ttl() -> Duration {
+    Duration::from_secs(60)
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{num::NonZeroU32, time::Duration};
 
 use rust_decimal::{
     Decimal,
@@ -12,6 +12,25 @@ const DEFAULT_ERROR_THRESHOLD: f64 = 0.15;
 #[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
 pub struct MonitorConfig {
     pub health: HealthMonitorConfig,
+    /// Periodically verify provider credentials. Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<CredentialMonitorConfig>,
+    /// Periodically probe providers independent of live traffic. Disabled by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probes: Option<ProbeMonitorConfig>,
+    /// Stop sending requests to providers that are failing most of them.
+    /// Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Reduce the weight of providers whose rate limit headers report that
+    /// they are running out of capacity, before they start rate limiting
+    /// requests. Only applies to `provider-weighted` routers. Disabled by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_headers: Option<RateLimitHeadersConfig>,
+    /// How providers removed for being rate limited are re-added.
+    pub rate_limit: RateLimitMonitorConfig,
 }
 
 impl MonitorConfig {
@@ -65,6 +84,188 @@ pub enum HealthMonitorConfig {
     },
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CredentialMonitorConfig {
+    /// Interval at which each provider credential is verified with a cheap
+    /// authenticated request.
+    #[serde(default = "default_credential_interval", with = "humantime_serde")]
+    pub interval: Duration,
+    /// Remove providers whose credentials are rejected from load balancers
+    /// until the credential is accepted again.
+    #[serde(default)]
+    pub remove_unhealthy: bool,
+}
+
+impl Default for CredentialMonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_credential_interval(),
+            remove_unhealthy: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProbeMonitorConfig {
+    /// Interval at which each provider is probed.
+    #[serde(default = "default_probe_interval", with = "humantime_serde")]
+    pub interval: Duration,
+    /// Probes which take longer than this are counted as failures.
+    #[serde(default = "default_probe_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    #[serde(default)]
+    pub kind: ProbeKind,
+    /// Consecutive failed probes before a provider is removed from load
+    /// balancers.
+    #[serde(default = "default_unhealthy_threshold")]
+    pub unhealthy_threshold: NonZeroU32,
+    /// Consecutive successful probes before a removed provider is added
+    /// back.
+    #[serde(default = "default_healthy_threshold")]
+    pub healthy_threshold: NonZeroU32,
+}
+
+impl Default for ProbeMonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_probe_interval(),
+            timeout: default_probe_timeout(),
+            kind: ProbeKind::default(),
+            unhealthy_threshold: default_unhealthy_threshold(),
+            healthy_threshold: default_healthy_threshold(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CircuitBreakerConfig {
+    /// The ratio of failed requests, i.e. 5xx responses, timeouts and
+    /// connection errors, at which a provider's circuit opens.
+    #[serde(default = "default_breaker_ratio")]
+    pub error_ratio: Decimal,
+    /// The window over which to measure the error ratio.
+    #[serde(default = "default_breaker_window", with = "humantime_serde")]
+    pub window: Duration,
+    /// The minimum number of requests within the window before the circuit
+    /// can open.
+    #[serde(default = "default_breaker_min_requests")]
+    pub min_requests: u32,
+    /// How long the circuit stays open before trial requests are let
+    /// through.
+    #[serde(default = "default_open_duration", with = "humantime_serde")]
+    pub open_duration: Duration,
+    /// Consecutive successful trial requests before the circuit closes.
+    #[serde(default = "default_half_open_successes")]
+    pub half_open_successes: NonZeroU32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            error_ratio: default_breaker_ratio(),
+            window: default_breaker_window(),
+            min_requests: default_breaker_min_requests(),
+            open_duration: default_open_duration(),
+            half_open_successes: default_half_open_successes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RateLimitHeadersConfig {
+    /// The fraction of any of a provider's request or token limits remaining
+    /// below which its weight is reduced. Its weight is restored once every
+    /// limit is above it again.
+    #[serde(default = "default_capacity_threshold")]
+    pub threshold: Decimal,
+    /// The fraction of its configured weight a provider keeps while it is
+    /// below the threshold, between 0 exclusive and 1.
+    #[serde(default = "default_reduced_weight")]
+    pub reduced_weight: Decimal,
+}
+
+impl Default for RateLimitHeadersConfig {
+    fn default() -> Self {
+        Self {
+            threshold: default_capacity_threshold(),
+            reduced_weight: default_reduced_weight(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct RateLimitMonitorConfig {
+    /// The fraction of its configured weight a provider is re-added with
+    /// once its rate limit has elapsed, between 0 exclusive and 1. Only
+    /// applies to weighted routers.
+    pub initial_weight: Decimal,
+    /// How long re-added providers take to ramp up from their
+    /// `initial-weight` to their configured weight.
+    #[serde(with = "humantime_serde")]
+    pub ramp_up: Duration,
+    /// Providers rate limited again within this long of being re-added are
+    /// removed for longer than their `retry-after`, doubling with each
+    /// consecutive removal.
+    #[serde(with = "humantime_serde")]
+    pub flapping_window: Duration,
+    /// How much longer than its `retry-after` a provider is removed for the
+    /// first time it's rate limited again within the `flapping-window`.
+    #[serde(with = "humantime_serde")]
+    pub backoff: Duration,
+    /// The most a provider's removal is extended by.
+    #[serde(with = "humantime_serde")]
+    pub max_backoff: Duration,
+}
+
+impl Default for RateLimitMonitorConfig {
+    fn default() -> Self {
+        Self {
+            initial_weight: Decimal::from_f64(0.1).unwrap(),
+            ramp_up: Duration::from_secs(60),
+            flapping_window: Duration::from_secs(60 * 5),
+            backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(60 * 10),
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for RateLimitMonitorConfig {
+    fn test_default() -> Self {
+        Self {
+            initial_weight: Decimal::from_f64(0.1).unwrap(),
+            ramp_up: Duration::from_millis(500),
+            flapping_window: Duration::from_secs(5),
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(4),
+        }
+    }
+}
+
+/// The request sent to probe a provider.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProbeKind {
+    /// A `GET` to the provider's base URL, or its `probe-path` if set, which
+    /// only checks that the provider is reachable.
+    #[default]
+    Http,
+    /// A completion limited to a single output token with the provider's
+    /// first configured model, using the configured provider key.
+    Completion,
+    /// Lists the provider's models using the configured provider key, which
+    /// checks the provider is serving authenticated requests without
+    /// generating any tokens.
+    Models,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, untagged, rename_all = "kebab-case")]
 pub enum GracePeriod {
@@ -83,6 +284,54 @@ fn default_health_interval() -> Duration {
     Duration::from_secs(5)
 }
 
+fn default_credential_interval() -> Duration {
+    Duration::from_secs(60 * 5)
+}
+
+fn default_probe_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_probe_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_unhealthy_threshold() -> NonZeroU32 {
+    NonZeroU32::new(3).unwrap()
+}
+
+fn default_healthy_threshold() -> NonZeroU32 {
+    NonZeroU32::new(2).unwrap()
+}
+
+fn default_breaker_ratio() -> Decimal {
+    Decimal::from_f64(0.5).unwrap()
+}
+
+fn default_breaker_window() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_breaker_min_requests() -> u32 {
+    10
+}
+
+fn default_open_duration() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_half_open_successes() -> NonZeroU32 {
+    NonZeroU32::new(1).unwrap()
+}
+
+fn default_capacity_threshold() -> Decimal {
+    Decimal::from_f64(0.05).unwrap()
+}
+
+fn default_reduced_weight() -> Decimal {
+    Decimal::from_f64(0.1).unwrap()
+}
+
 fn default_buckets() -> usize {
     10
 }
@@ -125,6 +374,11 @@ impl crate::tests::TestDefault for MonitorConfig {
     fn test_default() -> Self {
         Self {
             health: HealthMonitorConfig::test_default(),
+            credentials: None,
+            probes: None,
+            circuit_breaker: None,
+            rate_limit_headers: None,
+            rate_limit: RateLimitMonitorConfig::test_default(),
         }
     }
 }
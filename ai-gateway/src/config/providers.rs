@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, num::NonZeroUsize};
 
 use derive_more::{AsRef, Deref, DerefMut};
 use indexmap::{IndexMap, IndexSet};
@@ -8,11 +8,15 @@ use serde::{
 };
 use url::Url;
 
-use crate::types::{model_id::ModelId, provider::InferenceProvider};
+use crate::{
+    config::dispatcher::{ConnectionPoolConfig, TimeoutsConfig},
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
 
 const PROVIDERS_YAML: &str =
     include_str!("../../config/embedded/providers.yaml");
 pub(crate) const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+pub(crate) const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
 
 /// Global configuration for providers, shared across all routers.
 ///
@@ -26,6 +30,71 @@ pub struct GlobalProviderConfig {
     pub base_url: Url,
     #[serde(default)]
     pub version: Option<String>,
+    /// Maximum number of in-flight requests to this provider across all
+    /// routers. When reached, load balanced routers will prefer other
+    /// providers, and requests wait briefly for a slot if every provider is
+    /// at its limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<NonZeroUsize>,
+    /// The HTTP version used for connections to this provider.
+    #[serde(default, skip_serializing_if = "HttpVersion::is_auto")]
+    pub http_version: HttpVersion,
+    /// Overrides the router's and the dispatcher's `timeouts` for this
+    /// provider.
+    #[serde(default, skip_serializing_if = "TimeoutsConfig::is_empty")]
+    pub timeouts: TimeoutsConfig,
+    /// Overrides the dispatcher's `connection_pool` for this provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_pool: Option<ConnectionPoolConfig>,
+    /// The AWS region Bedrock requests are signed for, or the location
+    /// Vertex AI requests are sent to.
+    ///
+    /// When `base-url` is left as the default, requests are sent to the
+    /// regional endpoint. Set `base-url` as well to use a VPC endpoint, whose
+    /// host doesn't contain the region.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// The Google Cloud project Vertex AI requests are sent to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Maps model names to the Azure OpenAI deployments serving them.
+    ///
+    /// Models without an entry are sent to a deployment with the same name
+    /// as the model.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub deployments: IndexMap<String, String>,
+    /// The path `http` probes request instead of the base URL, e.g. `health`
+    /// for a llama.cpp server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe_path: Option<String>,
+    /// Headers added to every request sent to this provider, overriding
+    /// the dispatcher's `default_upstream_headers`.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub upstream_headers: IndexMap<String, String>,
+}
+
+/// The HTTP version used for outbound connections to a provider.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpVersion {
+    /// Let the client negotiate the version with the provider.
+    #[default]
+    Auto,
+    /// Always use HTTP/1.1, for providers with issues over HTTP/2.
+    Http1,
+    /// Prefer HTTP/2. Over TLS it is negotiated with ALPN, so providers
+    /// without HTTP/2 are still reached over HTTP/1.1. Over plain HTTP it is
+    /// used without negotiating it first, so the provider must support it.
+    Http2,
+}
+
+impl HttpVersion {
+    #[must_use]
+    pub fn is_auto(&self) -> bool {
+        *self == Self::Auto
+    }
 }
 
 /// Map of *ALL* supported providers.
@@ -48,6 +117,24 @@ impl<'de> Deserialize<'de> for ProvidersConfig {
             base_url: Url,
             #[serde(default)]
             version: Option<String>,
+            #[serde(default)]
+            max_concurrent_requests: Option<NonZeroUsize>,
+            #[serde(default)]
+            http_version: HttpVersion,
+            #[serde(default)]
+            timeouts: TimeoutsConfig,
+            #[serde(default)]
+            connection_pool: Option<ConnectionPoolConfig>,
+            #[serde(default)]
+            region: Option<String>,
+            #[serde(default)]
+            project: Option<String>,
+            #[serde(default)]
+            deployments: IndexMap<String, String>,
+            #[serde(default)]
+            probe_path: Option<String>,
+            #[serde(default)]
+            upstream_headers: IndexMap<String, String>,
         }
 
         impl<'de> Visitor<'de> for ProvidersConfigVisitor {
@@ -97,6 +184,16 @@ impl<'de> Deserialize<'de> for ProvidersConfig {
                         models,
                         base_url: raw_config.base_url,
                         version: raw_config.version,
+                        max_concurrent_requests: raw_config
+                            .max_concurrent_requests,
+                        http_version: raw_config.http_version,
+                        timeouts: raw_config.timeouts,
+                        connection_pool: raw_config.connection_pool,
+                        region: raw_config.region,
+                        project: raw_config.project,
+                        deployments: raw_config.deployments,
+                        probe_path: raw_config.probe_path,
+                        upstream_headers: raw_config.upstream_headers,
                     };
 
                     providers.insert(provider, config);
@@ -123,6 +220,24 @@ impl Serialize for ProvidersConfig {
             base_url: Url,
             #[serde(skip_serializing_if = "Option::is_none")]
             version: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            max_concurrent_requests: Option<NonZeroUsize>,
+            #[serde(skip_serializing_if = "HttpVersion::is_auto")]
+            http_version: HttpVersion,
+            #[serde(skip_serializing_if = "TimeoutsConfig::is_empty")]
+            timeouts: TimeoutsConfig,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            connection_pool: Option<ConnectionPoolConfig>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            region: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            project: Option<String>,
+            #[serde(skip_serializing_if = "IndexMap::is_empty")]
+            deployments: IndexMap<String, String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            probe_path: Option<String>,
+            #[serde(skip_serializing_if = "IndexMap::is_empty")]
+            upstream_headers: IndexMap<String, String>,
         }
 
         let mut map = serializer.serialize_map(Some(self.0.len()))?;
@@ -136,6 +251,15 @@ impl Serialize for ProvidersConfig {
                 models: models_as_strings,
                 base_url: config.base_url.clone(),
                 version: config.version.clone(),
+                max_concurrent_requests: config.max_concurrent_requests,
+                http_version: config.http_version,
+                timeouts: config.timeouts,
+                connection_pool: config.connection_pool,
+                region: config.region.clone(),
+                project: config.project.clone(),
+                deployments: config.deployments.clone(),
+                probe_path: config.probe_path.clone(),
+                upstream_headers: config.upstream_headers.clone(),
             };
 
             map.serialize_entry(provider, &serialized_config)?;
@@ -235,4 +359,41 @@ anthropic:
             }
         );
     }
+
+    #[test]
+    fn test_max_concurrent_requests_round_trip() {
+        let yaml = r#"
+openai:
+  models:
+    - "gpt-4o"
+  base-url: https://api.openai.com
+  max-concurrent-requests: 10
+anthropic:
+  models:
+    - "claude-3-opus-20240229"
+  base-url: https://api.anthropic.com
+"#;
+
+        let config: ProvidersConfig = serde_yml::from_str(yaml).unwrap();
+        let openai_config = config.get(&InferenceProvider::OpenAI).unwrap();
+        assert_eq!(
+            openai_config.max_concurrent_requests,
+            NonZeroUsize::new(10)
+        );
+        let anthropic_config =
+            config.get(&InferenceProvider::Anthropic).unwrap();
+        assert_eq!(anthropic_config.max_concurrent_requests, None);
+
+        let serialized = serde_yml::to_string(&config).unwrap();
+        assert_eq!(serialized.matches("max-concurrent-requests").count(), 1);
+        let deserialized: ProvidersConfig =
+            serde_yml::from_str(&serialized).unwrap();
+        assert_eq!(
+            deserialized
+                .get(&InferenceProvider::OpenAI)
+                .unwrap()
+                .max_concurrent_requests,
+            NonZeroUsize::new(10)
+        );
+    }
 }
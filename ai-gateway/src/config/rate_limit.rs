@@ -1,27 +1,9 @@
 use std::{num::NonZeroU32, time::Duration};
 
-use axum_core::response::IntoResponse;
-use http::StatusCode;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use tower_governor::governor::{GovernorConfig, GovernorConfigBuilder};
-
-use crate::{
-    config::redis::RedisConfig,
-    error::{
-        api::{ErrorDetails, ErrorResponse},
-        init::InitError,
-    },
-    middleware::{
-        mapper::openai::SERVER_ERROR_TYPE,
-        rate_limit::extractor::RateLimitKeyExtractor,
-    },
-    types::json::Json,
-};
-
-pub type RateLimiterConfig = GovernorConfig<
-    RateLimitKeyExtractor,
-    governor::middleware::StateInformationMiddleware,
->;
+
+use crate::config::redis::RedisConfig;
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "kebab-case")]
@@ -32,84 +14,44 @@ pub struct RateLimitConfig {
     pub store: Option<RateLimitStore>,
     #[serde(default, flatten)]
     pub limits: LimitsConfig,
+    /// If set, requests over their limits wait for them rather than being
+    /// rejected right away. Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<QueueConfig>,
+}
+
+/// Parks requests over their rate limits until they're allowed, e.g. for
+/// batch workloads which would rather wait than handle 429s.
+///
+/// Each API key's queued requests are admitted in the order they arrived.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct QueueConfig {
+    /// How long a request waits for its limits before it's rejected with a
+    /// 429.
+    #[serde(with = "humantime_serde", default = "default_max_wait")]
+    pub max_wait: Duration,
+    /// The most requests waiting at once, beyond which requests over their
+    /// limits are rejected right away.
+    #[serde(default = "default_max_queue_depth")]
+    pub max_queue_depth: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_wait: default_max_wait(),
+            max_queue_depth: default_max_queue_depth(),
+        }
+    }
 }
 
-pub(crate) fn limiter_config(
-    limits: &LimitsConfig,
-) -> Result<RateLimiterConfig, InitError> {
-    let gcra = &limits.per_api_key;
-    let per_cell_duration = gcra
-        .refill_frequency
-        .checked_div(gcra.capacity.into())
-        .unwrap_or_else(|| {
-            tracing::warn!(
-                "fill_frequency is too small for capacity, using default fill \
-                 frequency"
-            );
-            default_refill_frequency()
-        });
-
-    GovernorConfigBuilder::default()
-        .period(per_cell_duration)
-        .burst_size(gcra.capacity.get())
-        .use_headers()
-        .key_extractor(RateLimitKeyExtractor)
-        .error_handler(|mut e| match &e {
-            tower_governor::GovernorError::TooManyRequests { .. } => {
-                tracing::debug!("rate limite exceeded");
-                let governor_response: http::Response<String> = e.as_response();
-                let (parts, _) = governor_response.into_parts();
-                let body = ErrorResponse {
-                    error: ErrorDetails {
-                        message: "Too many requests".to_string(),
-                        r#type: Some("rate_limit_exceeded".to_string()),
-                        param: None,
-                        code: None,
-                    },
-                };
-                let json = Json(body);
-                let openai_response = json.into_response();
-                let (_, body) = openai_response.into_parts();
-                http::Response::from_parts(parts, body)
-            }
-            tower_governor::GovernorError::UnableToExtractKey => {
-                tracing::warn!(
-                    "unable to extract key, rate limiting enabled without \
-                     enabling authentication!!!"
-                );
-                let body = ErrorResponse {
-                    error: ErrorDetails {
-                        message: "Internal error, server misconfigured"
-                            .to_string(),
-                        r#type: Some(SERVER_ERROR_TYPE.to_string()),
-                        param: None,
-                        code: None,
-                    },
-                };
-                let json = Json(body);
-                (StatusCode::INTERNAL_SERVER_ERROR, json).into_response()
-            }
-            tower_governor::GovernorError::Other { code, msg, .. } => {
-                tracing::error!(
-                    msg = msg.as_deref().unwrap_or("Unknown error"),
-                    "Other error"
-                );
-                let body = ErrorResponse {
-                    error: ErrorDetails {
-                        message: "Internal error".to_string(),
-                        r#type: Some(SERVER_ERROR_TYPE.to_string()),
-                        param: None,
-                        code: None,
-                    },
-                };
-                let json = Json(body);
-                (*code, json).into_response()
-            }
-        })
-        .finish()
-        .ok_or(InitError::InvalidRateLimitConfig(
-            "either burst size or period interval are zero",
-        ))
+fn default_max_wait() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_max_queue_depth() -> usize {
+    1000
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
@@ -134,6 +76,7 @@ impl crate::tests::TestDefault for RateLimitConfig {
         Self {
             store: None,
             limits: LimitsConfig::test_default(),
+            queue: None,
         }
     }
 }
@@ -145,6 +88,7 @@ pub fn config_enabled_for_test() -> RateLimitConfig {
     RateLimitConfig {
         limits: LimitsConfig::test_default(),
         store: Some(RateLimitStore::InMemory),
+        queue: None,
     }
 }
 
@@ -163,13 +107,42 @@ pub fn store_enabled_for_test_redis() -> RateLimitStore {
             "redis://localhost:6340".parse::<url::Url>().unwrap(),
         ),
         connection_timeout: Duration::from_secs(1),
+        ..Default::default()
     })
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct LimitsConfig {
+    /// Limits each API key, or each organization for requests without one.
     pub per_api_key: GcraConfig,
+    /// Limits all of an organization's API keys together, e.g. so that one
+    /// organization can't starve the others on a shared cloud deployment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_org: Option<GcraConfig>,
+    /// Limits the tokens each API key uses per minute, e.g. so that a few
+    /// very large prompts can't exhaust a provider's quota.
+    ///
+    /// A request's input tokens are estimated and debited before it's sent,
+    /// then reconciled with the usage the provider reports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<NonZeroU32>,
+    /// Limits each API key's requests to the models matching a glob, e.g.
+    /// `openai/o3*` or `*`, in addition to the other limits.
+    ///
+    /// Only the most specific glob matching a request's model applies: exact
+    /// models first, then the globs with the most literal characters, then
+    /// the glob listed first.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub per_model: IndexMap<String, ModelLimitConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ModelLimitConfig {
+    /// Requests per minute, with bursts of up to the whole minute's
+    /// requests.
+    pub rpm: NonZeroU32,
 }
 
 #[cfg(feature = "testing")]
@@ -177,6 +150,9 @@ impl crate::tests::TestDefault for LimitsConfig {
     fn test_default() -> Self {
         Self {
             per_api_key: GcraConfig::test_default(),
+            per_org: None,
+            tokens_per_minute: None,
+            per_model: IndexMap::new(),
         }
     }
 }
@@ -184,10 +160,12 @@ impl crate::tests::TestDefault for LimitsConfig {
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct GcraConfig {
-    /// The duration it takes to refill the entire rate limit quota.
+    /// The duration it takes to refill the entire rate limit quota, e.g. a
+    /// capacity of 60 with a refill frequency of `1m` allows 60 requests per
+    /// minute with bursts of up to 60 requests.
     #[serde(with = "humantime_serde", default = "default_refill_frequency")]
     pub refill_frequency: Duration,
-    /// The rate limit quota capacity.
+    /// The rate limit quota capacity, i.e. the largest burst allowed.
     #[serde(default = "default_capacity")]
     pub capacity: NonZeroU32,
 }
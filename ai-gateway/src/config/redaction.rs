@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+const EMAIL_REGEX: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+/// 13 to 19 digits, optionally separated by spaces or dashes.
+const CREDIT_CARD_REGEX: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+
+/// Redaction applied to request and response bodies before they are logged.
+///
+/// Only the copies of the bodies uploaded for logging are redacted; the
+/// provider and the client always see the original bodies.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RedactionConfig {
+    /// Replace message content, such as the messages of a chat completion
+    /// request and the content of its response, with the placeholder while
+    /// keeping the structure of the body.
+    pub message_content: bool,
+    /// Replace matches of these patterns in any string in the body with the
+    /// placeholder.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<RedactionPattern>,
+    /// Defaults to `[REDACTED]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+}
+
+impl RedactionConfig {
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.message_content || !self.patterns.is_empty()
+    }
+
+    #[must_use]
+    pub fn placeholder(&self) -> &str {
+        self.placeholder.as_deref().unwrap_or("[REDACTED]")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RedactionPattern {
+    Email,
+    CreditCard,
+    /// A custom regular expression.
+    Regex(String),
+}
+
+impl RedactionPattern {
+    #[must_use]
+    pub fn regex(&self) -> &str {
+        match self {
+            Self::Email => EMAIL_REGEX,
+            Self::CreditCard => CREDIT_CARD_REGEX,
+            Self::Regex(regex) => regex,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_deserialize_from_yaml() {
+        let yaml = r#"
+message-content: true
+patterns:
+  - email
+  - credit-card
+  - regex: "sk-[A-Za-z0-9]+"
+"#;
+        let config = serde_yml::from_str::<RedactionConfig>(yaml).unwrap();
+        assert!(config.message_content);
+        assert_eq!(
+            config.patterns,
+            vec![
+                RedactionPattern::Email,
+                RedactionPattern::CreditCard,
+                RedactionPattern::Regex("sk-[A-Za-z0-9]+".to_string()),
+            ]
+        );
+        assert_eq!(config.placeholder(), "[REDACTED]");
+    }
+
+    #[test]
+    fn builtin_patterns_are_valid() {
+        assert!(regex::Regex::new(EMAIL_REGEX).is_ok());
+        assert!(regex::Regex::new(CREDIT_CARD_REGEX).is_ok());
+    }
+}
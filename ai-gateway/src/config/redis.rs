@@ -11,6 +11,21 @@ pub struct RedisConfig {
     pub host_url: Secret<url::Url>,
     #[serde(with = "humantime_serde", default = "default_connection_timeout")]
     pub connection_timeout: Duration,
+    /// What rate limiting does while Redis is unreachable.
+    #[serde(default)]
+    pub on_unavailable: UnavailablePolicy,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnavailablePolicy {
+    /// Requests are let through without being rate limited.
+    #[default]
+    FailOpen,
+    /// Requests are rejected until Redis is reachable again.
+    FailClosed,
 }
 
 impl Default for RedisConfig {
@@ -18,6 +33,7 @@ impl Default for RedisConfig {
         Self {
             host_url: default_url(),
             connection_timeout: default_connection_timeout(),
+            on_unavailable: UnavailablePolicy::default(),
         }
     }
 }
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// How much of a router's traffic is logged to Helicone, when observability
+/// is enabled.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequestLogging {
+    /// Log request metadata along with the request and response bodies.
+    #[default]
+    Full,
+    /// Log request metadata, such as the provider, model, status and
+    /// latency, without uploading the request and response bodies. Useful
+    /// for routers whose traffic contains sensitive data.
+    MetadataOnly,
+    /// Don't log the router's requests.
+    Off,
+}
+
+impl RequestLogging {
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        *self == Self::Full
+    }
+}
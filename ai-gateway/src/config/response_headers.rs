@@ -1,15 +1,27 @@
 use serde::{Deserialize, Serialize};
 
-use crate::utils::default_true;
+use crate::{config::header_filter::HeaderFilterConfig, utils::default_true};
 
-/// Response headers useful for additional observability.
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+/// Response headers useful for additional observability, and which of the
+/// provider's response headers are forwarded.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ResponseHeadersConfig {
     #[serde(default = "default_true")]
     pub provider: bool,
     #[serde(default = "default_true")]
     pub provider_request_id: bool,
+    /// The arm of a canary router which served the request.
+    #[serde(default = "default_true")]
+    pub canary_arm: bool,
+    /// Whether the client pinned the request to its provider, see
+    /// [`RouterConfig::allow_provider_override`](crate::config::router::RouterConfig::allow_provider_override).
+    #[serde(default = "default_true")]
+    pub provider_override: bool,
+    /// The `allow` and `deny` lists of provider headers, which can be
+    /// overridden per router.
+    #[serde(flatten)]
+    pub forwarding: HeaderFilterConfig,
 }
 
 impl Default for ResponseHeadersConfig {
@@ -17,6 +29,9 @@ impl Default for ResponseHeadersConfig {
         Self {
             provider: true,
             provider_request_id: true,
+            canary_arm: true,
+            provider_override: true,
+            forwarding: HeaderFilterConfig::default(),
         }
     }
 }
@@ -5,9 +5,28 @@ use serde::{Deserialize, Serialize};
 
 pub(crate) const DEFAULT_RETRY_FACTOR: f32 = 2.0;
 
+/// Retries requests which fail transiently, e.g. with a 502 or a reset
+/// connection, before the client sees the failure.
+///
+/// Only requests which haven't started streaming to the client are retried,
+/// so streams are retried until their first event.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RetryConfig {
+    #[serde(flatten)]
+    pub strategy: RetryStrategy,
+    /// Which failures are retried.
+    #[serde(default)]
+    pub retry_on: RetryOn,
+    #[serde(default)]
+    pub budget: RetryBudgetConfig,
+}
+
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Hash, Serialize)]
 #[serde(rename_all = "kebab-case", tag = "strategy")]
-pub enum RetryConfig {
+pub enum RetryStrategy {
+    /// Backs off exponentially between `min-delay` and `max-delay`, with
+    /// jitter.
     Exponential {
         #[serde(
             with = "humantime_serde",
@@ -26,6 +45,7 @@ pub enum RetryConfig {
         #[serde(default = "default_factor")]
         factor: Decimal,
     },
+    /// Waits a jittered `delay` between attempts.
     Constant {
         #[serde(with = "humantime_serde", default = "default_min_delay")]
         delay: Duration,
@@ -34,6 +54,106 @@ pub enum RetryConfig {
     },
 }
 
+/// The failures worth retrying. Responses with a `retry-after` header are
+/// retried no sooner than it asks.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Hash, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RetryOn {
+    /// Defaults to 429 and every 5xx.
+    pub status_codes: Option<Vec<u16>>,
+    pub errors: Vec<RetryableError>,
+}
+
+impl Default for RetryOn {
+    fn default() -> Self {
+        Self {
+            status_codes: None,
+            errors: vec![
+                RetryableError::Connection,
+                RetryableError::TruncatedResponse,
+                RetryableError::Timeout,
+            ],
+        }
+    }
+}
+
+impl RetryOn {
+    #[must_use]
+    pub fn status(&self, status: http::StatusCode) -> bool {
+        match &self.status_codes {
+            Some(status_codes) => status_codes.contains(&status.as_u16()),
+            None => {
+                status.is_server_error()
+                    || status == http::StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn error(&self, error: RetryableError) -> bool {
+        self.errors.contains(&error)
+    }
+}
+
+/// Failures without a response status.
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetryableError {
+    /// The connection to the provider couldn't be made or was reset before
+    /// the provider responded.
+    Connection,
+    /// The provider's response was cut short or couldn't be parsed.
+    TruncatedResponse,
+    /// The provider didn't respond within the request timeout. Each attempt
+    /// gets the full timeout.
+    Timeout,
+}
+
+/// Caps retries to a fraction of the requests sent, so that a failing
+/// provider isn't sent more and more load.
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Hash, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RetryBudgetConfig {
+    /// The retries allowed per request, between 0 and 1, so that retries at
+    /// most double the load on a provider.
+    pub ratio: Decimal,
+    /// Retries allowed regardless of the ratio, so that providers which
+    /// rarely receive requests are still retried.
+    pub min_retries_per_second: u32,
+    /// How long requests count towards the budget, between 1s and 60s.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self {
+            ratio: Decimal::new(2, 1),
+            min_retries_per_second: 10,
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryBudgetConfig {
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        (Decimal::ZERO..=Decimal::ONE).contains(&self.ratio)
+            && (Duration::from_secs(1)..=Duration::from_secs(60))
+                .contains(&self.window)
+    }
+}
+
+impl RetryConfig {
+    #[must_use]
+    pub fn max_retries(&self) -> u8 {
+        match self.strategy {
+            RetryStrategy::Exponential { max_retries, .. }
+            | RetryStrategy::Constant { max_retries, .. } => max_retries,
+        }
+    }
+}
+
 fn default_factor() -> Decimal {
     Decimal::try_from(DEFAULT_RETRY_FACTOR).expect("always valid if tests pass")
 }
@@ -53,9 +173,43 @@ fn default_max_delay() -> Duration {
 #[cfg(feature = "testing")]
 impl crate::tests::TestDefault for RetryConfig {
     fn test_default() -> Self {
-        Self::Constant {
-            delay: Duration::from_millis(5),
-            max_retries: 2,
+        Self {
+            strategy: RetryStrategy::Constant {
+                delay: Duration::from_millis(5),
+                max_retries: 2,
+            },
+            retry_on: RetryOn::default(),
+            budget: RetryBudgetConfig::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_deserialize_from_yaml() {
+        let yaml = r"
+strategy: exponential
+max-retries: 3
+retry-on:
+  status-codes: [502, 503]
+  errors: [connection]
+budget:
+  ratio: 0.5
+";
+        let config = serde_yml::from_str::<RetryConfig>(yaml).unwrap();
+        assert_eq!(config.max_retries(), 3);
+        assert!(config.retry_on.status(http::StatusCode::BAD_GATEWAY));
+        assert!(!config.retry_on.status(http::StatusCode::TOO_MANY_REQUESTS));
+        assert!(!config.retry_on.error(RetryableError::TruncatedResponse));
+        assert_eq!(config.budget.ratio, Decimal::new(5, 1));
+        assert_eq!(config.budget.window, Duration::from_secs(10));
+
+        let config =
+            serde_yml::from_str::<RetryConfig>("strategy: constant").unwrap();
+        assert!(config.retry_on.status(http::StatusCode::TOO_MANY_REQUESTS));
+        assert!(config.retry_on.error(RetryableError::Connection));
+    }
+}
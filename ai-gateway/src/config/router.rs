@@ -1,19 +1,24 @@
 use std::collections::HashMap;
 
 use derive_more::{AsMut, AsRef};
-use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
 use super::{
-    balance::{BalanceConfig, BalanceConfigInner},
-    model_mapping::ModelMappingConfig,
-    retry::RetryConfig,
+    api_translation::ApiTranslation, balance::BalanceConfig,
+    body_limit::BodyLimitConfig, concurrency_limit::ConcurrencyLimitConfig,
+    cors::CorsConfig, dispatcher::TimeoutsConfig, evaluation::EvaluationConfig,
+    header_filter::HeaderFilterConfig, json_repair::JsonRepairMode,
+    model_mapping::ModelMappingConfig, redaction::RedactionConfig,
+    request_logging::RequestLogging, retry::RetryConfig,
+    routing_rules::RoutingRulesConfig, shadow::ShadowConfig,
+    stream_limit::StreamLimitConfig, streaming::StreamingMode,
+    transform::TransformRule,
 };
 use crate::{
     config::{cache::CacheConfig, rate_limit::RateLimitConfig},
     error::init::InitError,
-    types::{provider::InferenceProvider, router::RouterId},
+    types::{model_id::ModelId, provider::InferenceProvider, router::RouterId},
 };
 
 #[derive(
@@ -49,36 +54,87 @@ pub struct RouterConfig {
     pub rate_limit: Option<RateLimitConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub providers: Option<HashMap<InferenceProvider, RouterProviderConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_streams: Option<StreamLimitConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<ConcurrencyLimitConfig>,
+    #[serde(skip_serializing_if = "StreamingMode::is_passthrough")]
+    pub streaming_mode: StreamingMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<ShadowConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evaluation: Option<EvaluationConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_translation: Option<ApiTranslation>,
+    /// See [`TransformRule`] for when these are applied.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<TransformRule>,
+    #[serde(skip_serializing_if = "RequestLogging::is_full")]
+    pub logging: RequestLogging,
+    /// Overrides the global redaction config for this router.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<RedactionConfig>,
+    /// If set, only these models may be requested. Models match regardless
+    /// of version, e.g. `openai/gpt-4o-mini` allows
+    /// `openai/gpt-4o-mini-2024-07-18`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowed_models: Vec<ModelId>,
+    /// Models which may not be requested, even if allowed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub denied_models: Vec<ModelId>,
+    /// Rewrites the requested model before a provider is selected, e.g.
+    /// `fast` to `openai/gpt-4o-mini`. The rewritten model is what's sent,
+    /// cached, logged and billed. Aliases can't resolve to other aliases.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub model_aliases: HashMap<String, String>,
+    /// Echo the alias a client requested in the `model` field of responses,
+    /// rather than the model it was rewritten to.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub preserve_alias_in_response: bool,
+    /// Rewrites the requested model or picks the provider based on the
+    /// request's content, e.g. sending short prompts to a cheaper model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing_rules: Option<RoutingRulesConfig>,
+    /// Respond with a synthetic completion rather than calling providers,
+    /// see [`DispatcherConfig::dry_run`](crate::config::dispatcher::DispatcherConfig::dry_run).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// Send request parameters as is, rather than adjusting them to what the
+    /// target model supports, so that providers reject unsupported ones.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub strict_params: bool,
+    /// See [`JsonRepairMode`].
+    #[serde(skip_serializing_if = "JsonRepairMode::is_off")]
+    pub json_repair: JsonRepairMode,
+    /// Let clients pin a request to one of the router's providers with the
+    /// `helicone-provider` header, bypassing load balancing.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allow_provider_override: bool,
+    /// Overrides the dispatcher's `timeouts` for this router, apart from
+    /// `connect`.
+    #[serde(skip_serializing_if = "TimeoutsConfig::is_empty")]
+    pub timeouts: TimeoutsConfig,
+    /// Stricter limits on the size of request bodies than the global ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_limit: Option<BodyLimitConfig>,
+    /// Overrides the global CORS config for requests to this router.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsConfig>,
+    /// Overrides which client headers are forwarded to providers. Headers
+    /// set by `transforms` are always forwarded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_headers: Option<HeaderFilterConfig>,
+    /// Overrides which provider headers are forwarded to clients.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_headers: Option<HeaderFilterConfig>,
 }
 
 impl RouterConfig {
     pub fn validate(&self) -> Result<(), InitError> {
-        for balance_config in self.load_balance.0.values() {
-            match balance_config {
-                BalanceConfigInner::ProviderWeighted { providers } => {
-                    let total =
-                        providers.iter().map(|t| t.weight).sum::<Decimal>();
-                    if total != Decimal::from(1) {
-                        return Err(InitError::InvalidBalancer(format!(
-                            "Balance weights dont sum to 1: {total}"
-                        )));
-                    }
-                }
-                BalanceConfigInner::ModelWeighted { models } => {
-                    let total =
-                        models.iter().map(|m| m.weight).sum::<Decimal>();
-                    if total != Decimal::from(1) {
-                        return Err(InitError::InvalidBalancer(format!(
-                            "Balance weights dont sum to 1: {total}"
-                        )));
-                    }
-                }
-                BalanceConfigInner::BalancedLatency { .. }
-                | BalanceConfigInner::ModelLatency { .. } => {}
-            }
+        match self.validation_errors().into_iter().next() {
+            Some(error) => Err(InitError::InvalidRouterConfig(error)),
+            None => Ok(()),
         }
-
-        Ok(())
     }
 
     #[must_use]
@@ -93,19 +149,15 @@ impl crate::tests::TestDefault for RouterConfigs {
         Self(HashMap::from([(
             RouterId::Named(compact_str::CompactString::new("my-router")),
             RouterConfig {
-                model_mappings: None,
-                cache: None,
                 load_balance: BalanceConfig(HashMap::from([(
                     crate::endpoints::EndpointType::Chat,
-                    BalanceConfigInner::BalancedLatency {
+                    super::balance::BalanceConfigInner::BalancedLatency {
                         providers: nonempty_collections::nes![
                             crate::types::provider::InferenceProvider::OpenAI
                         ],
                     },
                 )])),
-                retries: None,
-                rate_limit: None,
-                providers: None,
+                ..Default::default()
             },
         )]))
     }
@@ -123,22 +175,37 @@ pub struct RouterProviderConfig {
 mod tests {
     use std::time::Duration;
 
+    use rust_decimal::Decimal;
+
     use super::*;
-    use crate::config::cache::CacheConfig;
+    use crate::config::{
+        cache::CacheConfig,
+        concurrency_limit::PriorityWeights,
+        retry::{RetryBudgetConfig, RetryOn, RetryStrategy, RetryableError},
+        routing_rules::{RoutingRule, RoutingTarget, RuleConditions},
+    };
 
     fn test_router_config() -> RouterConfig {
         let cache = CacheConfig {
             directive: Some("max-age=3600, max-stale=1800".to_string()),
             buckets: 10,
             seed: Some("test-seed".to_string()),
+            ..Default::default()
         };
 
         let balance = BalanceConfig::default();
-        let retries = RetryConfig::Exponential {
-            min_delay: Duration::from_secs(1),
-            max_delay: Duration::from_secs(10),
-            max_retries: 3,
-            factor: Decimal::from(2),
+        let retries = RetryConfig {
+            strategy: RetryStrategy::Exponential {
+                min_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(10),
+                max_retries: 3,
+                factor: Decimal::from(2),
+            },
+            retry_on: RetryOn {
+                status_codes: Some(vec![502, 503]),
+                errors: vec![RetryableError::Connection],
+            },
+            budget: RetryBudgetConfig::default(),
         };
 
         RouterConfig {
@@ -148,6 +215,71 @@ mod tests {
             retries: Some(retries),
             rate_limit: None,
             providers: None,
+            max_concurrent_streams: Some(StreamLimitConfig {
+                limit: std::num::NonZeroUsize::new(10).unwrap(),
+                queue_depth: 5,
+                queue_timeout: Duration::from_secs(10),
+            }),
+            max_concurrent_requests: Some(ConcurrencyLimitConfig {
+                limit: std::num::NonZeroUsize::new(20).unwrap(),
+                queue_depth: 10,
+                queue_timeout: Duration::from_secs(5),
+                priority_weights: PriorityWeights::default(),
+            }),
+            shadow: None,
+            api_translation: None,
+            transforms: Vec::new(),
+            evaluation: None,
+            streaming_mode: StreamingMode::default(),
+            logging: RequestLogging::MetadataOnly,
+            redaction: Some(RedactionConfig {
+                message_content: true,
+                ..Default::default()
+            }),
+            allowed_models: vec!["openai/gpt-4o-mini".parse().unwrap()],
+            denied_models: Vec::new(),
+            model_aliases: HashMap::from([(
+                "fast".to_string(),
+                "openai/gpt-4o-mini".to_string(),
+            )]),
+            preserve_alias_in_response: true,
+            routing_rules: Some(RoutingRulesConfig {
+                rules: vec![RoutingRule {
+                    when: RuleConditions {
+                        max_input_tokens: Some(500),
+                        ..Default::default()
+                    },
+                    target: RoutingTarget {
+                        model: Some("openai/gpt-4o-mini".to_string()),
+                        provider: Some(InferenceProvider::OpenAI),
+                    },
+                }],
+                default: None,
+            }),
+            dry_run: false,
+            strict_params: false,
+            json_repair: JsonRepairMode::Repair,
+            allow_provider_override: true,
+            timeouts: TimeoutsConfig {
+                connect: None,
+                request: Some(std::time::Duration::from_secs(30)),
+                stream_idle: Some(std::time::Duration::from_secs(10)),
+            },
+            body_limit: Some(BodyLimitConfig {
+                max_request_body_bytes: 1024 * 1024,
+                ..Default::default()
+            }),
+            cors: Some(CorsConfig {
+                allowed_origins: vec!["https://*.example.com".to_string()],
+                max_age: Some(std::time::Duration::from_secs(600)),
+                allow_credentials: true,
+                ..Default::default()
+            }),
+            request_headers: Some(HeaderFilterConfig {
+                allow: vec!["x-tenant-id".to_string()],
+                deny: vec!["user-agent".to_string()],
+            }),
+            response_headers: None,
         }
     }
 
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::provider::InferenceProvider;
+
+/// Routes requests to a model or provider based on their content, e.g.
+/// sending short prompts to a cheaper model.
+///
+/// Rules are evaluated top to bottom and the first rule matching the request
+/// applies, or the `default` if none do. They apply after the router's model
+/// aliases are resolved and before the model is checked against the router's
+/// allowed models, cached or load balanced.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RoutingRulesConfig {
+    pub rules: Vec<RoutingRule>,
+    /// Applied to requests matching none of the rules. If not set, those
+    /// requests are routed as requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<RoutingTarget>,
+}
+
+impl RoutingRulesConfig {
+    /// Whether any rule needs the input tokens of the request, which are
+    /// estimated locally.
+    #[must_use]
+    pub fn counts_tokens(&self) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.when.min_input_tokens.is_some()
+                || rule.when.max_input_tokens.is_some()
+        })
+    }
+
+    /// The targets of the rules and the default.
+    pub fn targets(&self) -> impl Iterator<Item = &RoutingTarget> {
+        self.rules
+            .iter()
+            .map(|rule| &rule.target)
+            .chain(self.default.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RoutingRule {
+    #[serde(default)]
+    pub when: RuleConditions,
+    #[serde(flatten)]
+    pub target: RoutingTarget,
+}
+
+/// What a request must look like for a rule to apply. Every condition set
+/// must hold, and a rule without conditions matches every request.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq,
+)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RuleConditions {
+    /// The fewest input tokens the request may have, as estimated with the
+    /// requested model's tokenizer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_input_tokens: Option<usize>,
+    /// The most input tokens the request may have.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<usize>,
+    /// Whether the request must, or must not, offer the model tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_tools: Option<bool>,
+    /// The fewest messages the request may have.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_messages: Option<usize>,
+    /// The most messages the request may have.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_messages: Option<usize>,
+}
+
+/// Where a request matching a rule is sent. At least one of `model` and
+/// `provider` must be set.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RoutingTarget {
+    /// Replaces the requested model, in the form `provider/model`. The
+    /// rewritten model is what's sent, cached, logged and billed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Sends the request to this provider, bypassing load balancing. Must be
+    /// one of the router's providers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<InferenceProvider>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routing_rules_deserialize_from_yaml() {
+        let yaml = r"
+rules:
+  - when:
+      max-input-tokens: 500
+      has-tools: false
+    model: openai/gpt-4o-mini
+  - when:
+      min-messages: 20
+    provider: anthropic
+default:
+  model: openai/gpt-4o
+";
+        let config = serde_yml::from_str::<RoutingRulesConfig>(yaml).unwrap();
+        assert_eq!(
+            config.rules[0],
+            RoutingRule {
+                when: RuleConditions {
+                    max_input_tokens: Some(500),
+                    has_tools: Some(false),
+                    ..Default::default()
+                },
+                target: RoutingTarget {
+                    model: Some("openai/gpt-4o-mini".to_string()),
+                    provider: None,
+                },
+            }
+        );
+        assert_eq!(
+            config.rules[1].target.provider,
+            Some(InferenceProvider::Anthropic)
+        );
+        assert!(config.counts_tokens());
+        assert_eq!(
+            config.default.unwrap().model.as_deref(),
+            Some("openai/gpt-4o")
+        );
+    }
+}
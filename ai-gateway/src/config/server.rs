@@ -16,8 +16,19 @@ pub struct ServerConfig {
     pub port: u16,
     #[serde(default)]
     pub tls: TlsConfig,
+    /// How long in-flight requests, including streams, are given to
+    /// complete after shutdown begins. New connections are refused while
+    /// draining.
     #[serde(with = "humantime_serde", default = "default_shutdown_timeout")]
     pub shutdown_timeout: Duration,
+    /// How long pending request logs are given to be sent to Jawn and
+    /// uploaded to MinIO once in-flight requests have drained.
+    #[serde(with = "humantime_serde", default = "default_flush_timeout")]
+    pub flush_timeout: Duration,
+    /// Serve the gRPC health checking protocol on this port, on the same
+    /// address as the HTTP listener. Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_health_port: Option<u16>,
 }
 
 impl Default for ServerConfig {
@@ -27,6 +38,8 @@ impl Default for ServerConfig {
             port: default_port(),
             tls: TlsConfig::default(),
             shutdown_timeout: default_shutdown_timeout(),
+            flush_timeout: default_flush_timeout(),
+            grpc_health_port: None,
         }
     }
 }
@@ -63,6 +76,10 @@ fn default_shutdown_timeout() -> Duration {
     Duration::from_secs(30)
 }
 
+fn default_flush_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
 #[cfg(feature = "testing")]
 impl crate::tests::TestDefault for ServerConfig {
     fn test_default() -> Self {
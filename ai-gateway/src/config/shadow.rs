@@ -0,0 +1,28 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::types::provider::InferenceProvider;
+
+/// Mirrors a sample of a router's requests to a secondary provider, e.g. to
+/// evaluate a new provider against production traffic.
+///
+/// Shadow requests are fire-and-forget: their responses are never returned to
+/// the client or logged, and their outcome is only recorded in metrics.
+/// Streaming requests are not mirrored.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub struct ShadowConfig {
+    pub provider: InferenceProvider,
+    /// Fraction of requests to mirror, between 0 and 1.
+    pub sample_rate: Decimal,
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for ShadowConfig {
+    fn test_default() -> Self {
+        Self {
+            provider: InferenceProvider::Anthropic,
+            sample_rate: Decimal::ONE,
+        }
+    }
+}
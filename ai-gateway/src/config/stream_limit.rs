@@ -0,0 +1,39 @@
+use std::{num::NonZeroUsize, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// Caps the number of concurrent streaming requests through a router.
+///
+/// Streams beyond the limit wait in a bounded queue for a slot to free up,
+/// and are rejected with a `429` if the queue is full or the wait times out.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub struct StreamLimitConfig {
+    /// Maximum number of in-flight streams.
+    pub limit: NonZeroUsize,
+    /// Maximum number of streams waiting for a slot.
+    #[serde(default = "default_queue_depth")]
+    pub queue_depth: usize,
+    /// Maximum time a stream waits for a slot.
+    #[serde(default = "default_queue_timeout", with = "humantime_serde")]
+    pub queue_timeout: Duration,
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for StreamLimitConfig {
+    fn test_default() -> Self {
+        Self {
+            limit: NonZeroUsize::MIN,
+            queue_depth: 1,
+            queue_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+fn default_queue_depth() -> usize {
+    100
+}
+
+fn default_queue_timeout() -> Duration {
+    Duration::from_secs(30)
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// How a router sends streaming responses to the client.
+#[derive(
+    Debug, Default, Clone, Copy, Deserialize, Serialize, Eq, PartialEq, Hash,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum StreamingMode {
+    /// Send each event to the client as soon as it is received from the
+    /// provider.
+    #[default]
+    Passthrough,
+    /// Buffer the whole response from the provider and only send it to the
+    /// client once the stream has completed without errors, so that a failed
+    /// stream results in an error response rather than partial content. This
+    /// trades time to first token for all-or-nothing responses.
+    BufferValidate,
+}
+
+impl StreamingMode {
+    #[must_use]
+    pub fn is_passthrough(&self) -> bool {
+        *self == Self::Passthrough
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// A declarative rule applied to every request through a router.
+///
+/// Rules are applied in the order they are configured, after the request is
+/// authenticated and its prompt is resolved, but before the request is
+/// checked against the cache or sent to a provider by the load balancer. This
+/// means cache keys are computed from the transformed body, so changing a
+/// router's transforms will not serve responses cached before the change.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case", tag = "action")]
+pub enum TransformRule {
+    /// Insert a system message before the request's messages. Multiple
+    /// prepended messages keep their configured order.
+    PrependSystemMessage { content: String },
+    /// Add a system message after the request's messages.
+    AppendSystemMessage { content: String },
+    /// Set a header, replacing any value sent by the client.
+    SetHeader { name: String, value: String },
+    /// Remove a header sent by the client.
+    RemoveHeader { name: String },
+    /// Set a top level body parameter, e.g. `temperature`, only if the client
+    /// did not send it.
+    DefaultParam {
+        param: String,
+        value: serde_json::Value,
+    },
+}
+
+impl TransformRule {
+    /// Whether the rule needs to read and rewrite the request body.
+    #[must_use]
+    pub fn modifies_body(&self) -> bool {
+        match self {
+            Self::PrependSystemMessage { .. }
+            | Self::AppendSystemMessage { .. }
+            | Self::DefaultParam { .. } => true,
+            Self::SetHeader { .. } | Self::RemoveHeader { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_rules_deserialize_from_yaml() {
+        let yaml = r"
+- action: prepend-system-message
+  content: Always answer in French.
+- action: remove-header
+  name: x-client-secret
+- action: default-param
+  param: temperature
+  value: 0.2
+";
+        let rules = serde_yml::from_str::<Vec<TransformRule>>(yaml).unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                TransformRule::PrependSystemMessage {
+                    content: "Always answer in French.".to_string(),
+                },
+                TransformRule::RemoveHeader {
+                    name: "x-client-secret".to_string(),
+                },
+                TransformRule::DefaultParam {
+                    param: "temperature".to_string(),
+                    value: serde_json::json!(0.2),
+                },
+            ]
+        );
+    }
+}
@@ -1,15 +1,763 @@
+use std::{fmt, str::FromStr};
+
+use http::{HeaderName, HeaderValue, Method};
 use indexmap::IndexSet;
+use regex::Regex;
+use rust_decimal::Decimal;
 use thiserror::Error;
 
 use crate::{
-    config::{Config, router::RouterConfig},
+    config::{
+        Config, ROUTER_ID_REGEX,
+        balance::{BalanceConfigInner, HashKeySource},
+        cache::{CacheConfig, MAX_BUCKET_SIZE},
+        cors::{CORS_WILDCARD, CorsConfig},
+        dispatcher::is_protected_upstream_header,
+        header_filter::HeaderFilterConfig,
+        redaction::RedactionConfig,
+        router::RouterConfig,
+        routing_rules::RoutingRulesConfig,
+        transform::TransformRule,
+    },
     types::{
         model_id::{ModelId, ModelName},
-        provider::InferenceProvider,
+        provider::{InferenceProvider, ProviderKeys},
         router::RouterId,
     },
 };
 
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RouterValidationError {
+    #[error("Balance weights must sum to 1, got {total}")]
+    InvalidBalanceWeights { total: Decimal },
+
+    #[error(
+        "Cache buckets must be between 1 and {MAX_BUCKET_SIZE}, got {buckets}"
+    )]
+    InvalidCacheBuckets { buckets: u8 },
+
+    #[error("Shadow sample rate must be between 0 and 1, got {sample_rate}")]
+    InvalidShadowSampleRate { sample_rate: Decimal },
+
+    #[error(
+        "Evaluation sample rate must be between 0 and 1, got {sample_rate}"
+    )]
+    InvalidEvaluationSampleRate { sample_rate: Decimal },
+
+    #[error("Provider {provider} is listed more than once in a priority list")]
+    DuplicatePriorityProvider { provider: InferenceProvider },
+
+    #[error("Invalid header in transform rule: {name}")]
+    InvalidTransformHeader { name: String },
+
+    #[error("Canary percentage must be between 0 and 100, got {percentage}")]
+    InvalidCanaryPercentage { percentage: Decimal },
+
+    #[error("Invalid canary bucketing header: {name}")]
+    InvalidCanaryHeader { name: String },
+
+    #[error("Invalid consistent hash key header: {name}")]
+    InvalidHashKeyHeader { name: String },
+
+    #[error("Invalid consistent hash key JSON pointer: {pointer}")]
+    InvalidHashKeyPointer { pointer: String },
+
+    #[error(
+        "Provider {provider} has a schedule, which is only supported by \
+         provider weighted balancing"
+    )]
+    UnsupportedProviderSchedule { provider: InferenceProvider },
+
+    #[error(
+        "Schedule of provider {provider} has a window which starts when it \
+         ends"
+    )]
+    EmptyScheduleWindow { provider: InferenceProvider },
+
+    #[error("Invalid redaction pattern: {pattern}")]
+    InvalidRedactionPattern { pattern: String },
+
+    #[error("Invalid CORS {field}: {value}")]
+    InvalidCorsValue { field: &'static str, value: String },
+
+    #[error("CORS can't allow credentials with a `*` origin, method or header")]
+    CorsWildcardWithCredentials,
+
+    #[error("Invalid forwarded header pattern: {pattern}")]
+    InvalidHeaderPattern { pattern: String },
+
+    #[error(
+        "Retry budget ratio must be between 0 and 1, and its window between \
+         1s and 60s"
+    )]
+    InvalidRetryBudget,
+
+    #[error(
+        "Connect timeouts can't be set per router, since connections to \
+         providers are shared by every router"
+    )]
+    UnsupportedConnectTimeout,
+
+    #[error(
+        "Model alias {alias} must resolve to a model in the form \
+         provider/model, got {model}"
+    )]
+    InvalidModelAlias { alias: String, model: String },
+
+    #[error("Model alias {alias} resolves to another alias: {model}")]
+    ChainedModelAlias { alias: String, model: String },
+
+    #[error("Model alias {alias} resolves to an unknown provider: {provider}")]
+    UnknownModelAliasProvider {
+        alias: String,
+        provider: InferenceProvider,
+    },
+
+    #[error("Routing rule {rule} must set a model, a provider or both")]
+    EmptyRoutingTarget { rule: String },
+
+    #[error(
+        "Routing rule {rule} must route to a model in the form \
+         provider/model, got {model}"
+    )]
+    InvalidRoutingModel { rule: String, model: String },
+
+    #[error(
+        "Routing rule {rule} routes to {provider}, which isn't one of the \
+         router's providers"
+    )]
+    UnknownRoutingProvider {
+        rule: String,
+        provider: InferenceProvider,
+    },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigValidationError {
+    #[error("Invalid router id: {0}")]
+    InvalidRouterId(RouterId),
+
+    #[error("Invalid config for router {router}: {error}")]
+    Router {
+        router: RouterId,
+        error: RouterValidationError,
+    },
+
+    #[error(
+        "Invalid {scope} cache config: buckets must be between 1 and \
+         {MAX_BUCKET_SIZE}, got {buckets}"
+    )]
+    InvalidCacheBuckets { scope: &'static str, buckets: u8 },
+
+    #[error(
+        "Invalid {scope} retry config: budget ratio must be between 0 and 1, \
+         and its window between 1s and 60s"
+    )]
+    InvalidRetryBudget { scope: &'static str },
+
+    #[error(
+        "Provider {provider} referenced in router {router} has no credentials \
+         configured"
+    )]
+    MissingProviderCredentials {
+        router: RouterId,
+        provider: InferenceProvider,
+    },
+
+    #[error("Invalid global redaction pattern: {pattern}")]
+    InvalidRedactionPattern { pattern: String },
+
+    #[error(
+        "Vertex AI is referenced in router {router} but no project is \
+         configured"
+    )]
+    MissingVertexProject { router: RouterId },
+
+    #[error("Model alias {alias} has an invalid model for {provider}: {model}")]
+    InvalidModelAlias {
+        alias: String,
+        provider: InferenceProvider,
+        model: String,
+    },
+
+    #[error("Circuit breaker error ratio must be between 0 and 1, got {ratio}")]
+    InvalidCircuitBreakerRatio { ratio: Decimal },
+
+    #[error(
+        "Rate limit headers threshold must be between 0 and 1, got {threshold}"
+    )]
+    InvalidCapacityThreshold { threshold: Decimal },
+
+    #[error(
+        "Rate limit headers reduced weight must be between 0 exclusive and 1, \
+         got {weight}"
+    )]
+    InvalidReducedWeight { weight: Decimal },
+
+    #[error(
+        "Rate limit monitor initial weight must be between 0 exclusive and 1, \
+         got {weight}"
+    )]
+    InvalidInitialWeight { weight: Decimal },
+
+    #[error(
+        "Body limits of {scope} are more lenient than the global ones, which \
+         apply first"
+    )]
+    BodyLimitAboveGlobal { scope: String },
+
+    #[error("gRPC health port {port} is already used by the HTTP listener")]
+    GrpcHealthPortConflict { port: u16 },
+
+    #[error("Invalid upstream header: {name}")]
+    InvalidUpstreamHeader { name: String },
+
+    #[error(
+        "Upstream header {name} is set by the gateway and can't be overridden"
+    )]
+    ProtectedUpstreamHeader { name: String },
+
+    #[error("Invalid CORS {field}: {value}")]
+    InvalidCorsValue { field: &'static str, value: String },
+
+    #[error("CORS can't allow credentials with a `*` origin, method or header")]
+    CorsWildcardWithCredentials,
+
+    #[error("Invalid forwarded header pattern: {pattern}")]
+    InvalidHeaderPattern { pattern: String },
+}
+
+/// Every problem found while validating a [`Config`], so that they can all be
+/// fixed at once rather than one startup at a time.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigValidationErrors(pub Vec<ConfigValidationError>);
+
+impl fmt::Display for ConfigValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "found {} problem(s)", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigValidationErrors {}
+
+impl RouterConfig {
+    /// Collect every problem with this router's config.
+    #[must_use]
+    pub fn validation_errors(&self) -> Vec<RouterValidationError> {
+        let mut errors = Vec::new();
+        for balance_config in self.load_balance.0.values() {
+            if let Some(total) = balance_config.total_weight()
+                && total != Decimal::ONE
+            {
+                errors.push(RouterValidationError::InvalidBalanceWeights {
+                    total,
+                });
+            }
+            if let BalanceConfigInner::Priority { providers } = balance_config {
+                let mut seen = IndexSet::new();
+                for provider in providers {
+                    if !seen.insert(provider) {
+                        errors.push(
+                            RouterValidationError::DuplicatePriorityProvider {
+                                provider: provider.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+            if let BalanceConfigInner::Canary {
+                percentage, header, ..
+            } = balance_config
+            {
+                if !(Decimal::ZERO..=Decimal::ONE_HUNDRED).contains(percentage)
+                {
+                    errors.push(
+                        RouterValidationError::InvalidCanaryPercentage {
+                            percentage: *percentage,
+                        },
+                    );
+                }
+                if HeaderName::from_str(header).is_err() {
+                    errors.push(RouterValidationError::InvalidCanaryHeader {
+                        name: header.clone(),
+                    });
+                }
+            }
+            if let BalanceConfigInner::ProviderWeighted { providers, .. } =
+                balance_config
+            {
+                for target in providers {
+                    let Some(schedule) = &target.schedule else {
+                        continue;
+                    };
+                    if schedule
+                        .exclude
+                        .iter()
+                        .any(|window| window.start == window.end)
+                    {
+                        errors.push(
+                            RouterValidationError::EmptyScheduleWindow {
+                                provider: target.provider.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+            if let BalanceConfigInner::ConsistentHash { key, providers } =
+                balance_config
+            {
+                for target in providers {
+                    if target.schedule.is_some() {
+                        errors.push(
+                            RouterValidationError::UnsupportedProviderSchedule {
+                                provider: target.provider.clone(),
+                            },
+                        );
+                    }
+                }
+                match key {
+                    HashKeySource::Header(name) => {
+                        if HeaderName::from_str(name).is_err() {
+                            errors.push(
+                                RouterValidationError::InvalidHashKeyHeader {
+                                    name: name.clone(),
+                                },
+                            );
+                        }
+                    }
+                    // an empty pointer refers to the whole body
+                    HashKeySource::JsonPointer(pointer) => {
+                        if !pointer.is_empty() && !pointer.starts_with('/') {
+                            errors.push(
+                                RouterValidationError::InvalidHashKeyPointer {
+                                    pointer: pointer.clone(),
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(cache) = &self.cache
+            && !valid_buckets(cache)
+        {
+            errors.push(RouterValidationError::InvalidCacheBuckets {
+                buckets: cache.buckets,
+            });
+        }
+
+        if let Some(shadow) = &self.shadow
+            && !(Decimal::ZERO..=Decimal::ONE).contains(&shadow.sample_rate)
+        {
+            errors.push(RouterValidationError::InvalidShadowSampleRate {
+                sample_rate: shadow.sample_rate,
+            });
+        }
+
+        if let Some(evaluation) = &self.evaluation
+            && !(Decimal::ZERO..=Decimal::ONE).contains(&evaluation.sample_rate)
+        {
+            errors.push(RouterValidationError::InvalidEvaluationSampleRate {
+                sample_rate: evaluation.sample_rate,
+            });
+        }
+
+        for rule in &self.transforms {
+            let (name, value) = match rule {
+                TransformRule::SetHeader { name, value } => (name, Some(value)),
+                TransformRule::RemoveHeader { name } => (name, None),
+                TransformRule::PrependSystemMessage { .. }
+                | TransformRule::AppendSystemMessage { .. }
+                | TransformRule::DefaultParam { .. } => continue,
+            };
+            if HeaderName::from_str(name).is_err()
+                || value
+                    .is_some_and(|value| HeaderValue::from_str(value).is_err())
+            {
+                errors.push(RouterValidationError::InvalidTransformHeader {
+                    name: name.clone(),
+                });
+            }
+        }
+
+        errors.extend(
+            self.redaction
+                .iter()
+                .flat_map(invalid_redaction_patterns)
+                .map(|pattern| {
+                    RouterValidationError::InvalidRedactionPattern { pattern }
+                }),
+        );
+
+        if let Some(cors) = &self.cors {
+            errors.extend(invalid_cors_values(cors).into_iter().map(
+                |(field, value)| RouterValidationError::InvalidCorsValue {
+                    field,
+                    value,
+                },
+            ));
+            if cors.allow_credentials && cors.has_wildcard() {
+                errors.push(RouterValidationError::CorsWildcardWithCredentials);
+            }
+        }
+
+        errors.extend(
+            self.request_headers
+                .iter()
+                .chain(&self.response_headers)
+                .flat_map(HeaderFilterConfig::invalid_patterns)
+                .map(|pattern| RouterValidationError::InvalidHeaderPattern {
+                    pattern,
+                }),
+        );
+
+        if self
+            .retries
+            .as_ref()
+            .is_some_and(|retries| !retries.budget.is_valid())
+        {
+            errors.push(RouterValidationError::InvalidRetryBudget);
+        }
+
+        if self.timeouts.connect.is_some() {
+            errors.push(RouterValidationError::UnsupportedConnectTimeout);
+        }
+
+        for (alias, model) in &self.model_aliases {
+            if self.model_aliases.contains_key(model) {
+                errors.push(RouterValidationError::ChainedModelAlias {
+                    alias: alias.clone(),
+                    model: model.clone(),
+                });
+            } else if model_alias_provider(model).is_none() {
+                errors.push(RouterValidationError::InvalidModelAlias {
+                    alias: alias.clone(),
+                    model: model.clone(),
+                });
+            }
+        }
+
+        if let Some(routing_rules) = &self.routing_rules {
+            errors.extend(self.routing_rule_errors(routing_rules));
+        }
+
+        errors
+    }
+
+    fn routing_rule_errors(
+        &self,
+        routing_rules: &RoutingRulesConfig,
+    ) -> Vec<RouterValidationError> {
+        let providers = self.load_balance.providers();
+        let rules = (1..=routing_rules.rules.len())
+            .map(|index| index.to_string())
+            .chain(routing_rules.default.as_ref().map(|_| "default".into()));
+        let mut errors = Vec::new();
+        for (rule, target) in rules.zip(routing_rules.targets()) {
+            if target.model.is_none() && target.provider.is_none() {
+                errors.push(RouterValidationError::EmptyRoutingTarget {
+                    rule: rule.clone(),
+                });
+            }
+            if let Some(model) = &target.model
+                && model_alias_provider(model).is_none()
+            {
+                errors.push(RouterValidationError::InvalidRoutingModel {
+                    rule: rule.clone(),
+                    model: model.clone(),
+                });
+            }
+            if let Some(provider) = &target.provider
+                && !providers.contains(provider)
+            {
+                errors.push(RouterValidationError::UnknownRoutingProvider {
+                    rule,
+                    provider: provider.clone(),
+                });
+            }
+        }
+        errors
+    }
+}
+
+/// The provider prefix of a router's model alias or routed model, if it's in
+/// the form `provider/model`.
+fn model_alias_provider(model: &str) -> Option<&str> {
+    model
+        .split_once('/')
+        .filter(|(provider, model)| !provider.is_empty() && !model.is_empty())
+        .map(|(provider, _)| provider)
+}
+
+/// Patterns which don't compile, so would prevent bodies from being logged.
+fn invalid_redaction_patterns(redaction: &RedactionConfig) -> Vec<String> {
+    redaction
+        .patterns
+        .iter()
+        .filter(|pattern| Regex::new(pattern.regex()).is_err())
+        .map(|pattern| pattern.regex().to_string())
+        .collect()
+}
+
+/// The fields and values which don't parse, which the CORS layer would skip.
+fn invalid_cors_values(cors: &CorsConfig) -> Vec<(&'static str, String)> {
+    fn invalid(
+        field: &'static str,
+        values: &[String],
+        is_valid: fn(&str) -> bool,
+    ) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        values
+            .iter()
+            .filter(move |value| *value != CORS_WILDCARD && !is_valid(value))
+            .map(move |value| (field, value.clone()))
+    }
+
+    // origins may have a single wildcard, e.g. `https://*.example.com`
+    invalid("origin", &cors.allowed_origins, |v| {
+        v.matches(CORS_WILDCARD).count() <= 1
+            && HeaderValue::from_str(v).is_ok()
+    })
+    .chain(invalid("method", &cors.allowed_methods, |v| {
+        Method::from_str(v).is_ok()
+    }))
+    .chain(invalid("header", &cors.allowed_headers, |v| {
+        HeaderName::from_str(v).is_ok()
+    }))
+    .chain(invalid("exposed header", &cors.exposed_headers, |v| {
+        HeaderName::from_str(v).is_ok()
+    }))
+    .collect()
+}
+
+fn valid_buckets(cache: &CacheConfig) -> bool {
+    (1..=MAX_BUCKET_SIZE).contains(&cache.buckets)
+}
+
+impl Config {
+    /// Collect every problem with this config. Provider credentials are only
+    /// checked for sidecar deployments, since cloud deployments load them
+    /// per organization at runtime.
+    #[must_use]
+    pub fn validation_errors(
+        &self,
+        provider_keys: &ProviderKeys,
+    ) -> Vec<ConfigValidationError> {
+        let router_id_regex =
+            Regex::new(ROUTER_ID_REGEX).expect("always valid if tests pass");
+        let mut errors = Vec::new();
+
+        for (scope, middleware) in
+            [("global", &self.global), ("unified-api", &self.unified_api)]
+        {
+            if let Some(cache) = &middleware.cache
+                && !valid_buckets(cache)
+            {
+                errors.push(ConfigValidationError::InvalidCacheBuckets {
+                    scope,
+                    buckets: cache.buckets,
+                });
+            }
+            if middleware
+                .retries
+                .as_ref()
+                .is_some_and(|retries| !retries.budget.is_valid())
+            {
+                errors
+                    .push(ConfigValidationError::InvalidRetryBudget { scope });
+            }
+        }
+
+        errors.extend(
+            invalid_redaction_patterns(&self.redaction).into_iter().map(
+                |pattern| ConfigValidationError::InvalidRedactionPattern {
+                    pattern,
+                },
+            ),
+        );
+
+        if let Some(circuit_breaker) = &self.discover.monitor.circuit_breaker
+            && !(Decimal::ZERO..=Decimal::ONE)
+                .contains(&circuit_breaker.error_ratio)
+        {
+            errors.push(ConfigValidationError::InvalidCircuitBreakerRatio {
+                ratio: circuit_breaker.error_ratio,
+            });
+        }
+
+        if let Some(rate_limit_headers) =
+            &self.discover.monitor.rate_limit_headers
+        {
+            if !(Decimal::ZERO..=Decimal::ONE)
+                .contains(&rate_limit_headers.threshold)
+            {
+                errors.push(ConfigValidationError::InvalidCapacityThreshold {
+                    threshold: rate_limit_headers.threshold,
+                });
+            }
+            let weight = rate_limit_headers.reduced_weight;
+            if weight <= Decimal::ZERO || weight > Decimal::ONE {
+                errors.push(ConfigValidationError::InvalidReducedWeight {
+                    weight,
+                });
+            }
+        }
+
+        let weight = self.discover.monitor.rate_limit.initial_weight;
+        if weight <= Decimal::ZERO || weight > Decimal::ONE {
+            errors.push(ConfigValidationError::InvalidInitialWeight { weight });
+        }
+
+        if self.server.grpc_health_port == Some(self.server.port) {
+            errors.push(ConfigValidationError::GrpcHealthPortConflict {
+                port: self.server.port,
+            });
+        }
+
+        let upstream_headers =
+            self.dispatcher.default_upstream_headers.iter().chain(
+                self.providers.values().flat_map(|provider_config| {
+                    &provider_config.upstream_headers
+                }),
+            );
+        for (name, value) in upstream_headers {
+            if HeaderName::from_str(name).is_err()
+                || HeaderValue::from_str(value).is_err()
+            {
+                errors.push(ConfigValidationError::InvalidUpstreamHeader {
+                    name: name.clone(),
+                });
+            } else if is_protected_upstream_header(name) {
+                errors.push(ConfigValidationError::ProtectedUpstreamHeader {
+                    name: name.clone(),
+                });
+            }
+        }
+
+        errors.extend(invalid_cors_values(&self.cors).into_iter().map(
+            |(field, value)| ConfigValidationError::InvalidCorsValue {
+                field,
+                value,
+            },
+        ));
+        if self.cors.allow_credentials && self.cors.has_wildcard() {
+            errors.push(ConfigValidationError::CorsWildcardWithCredentials);
+        }
+        errors.extend(
+            [&self.request_headers, &self.response_headers.forwarding]
+                .into_iter()
+                .flat_map(HeaderFilterConfig::invalid_patterns)
+                .map(|pattern| ConfigValidationError::InvalidHeaderPattern {
+                    pattern,
+                }),
+        );
+
+        for (alias, models) in &self.model_aliases.0 {
+            for (provider, model) in models {
+                if ModelId::from_str_and_provider(provider.clone(), model)
+                    .is_err()
+                {
+                    errors.push(ConfigValidationError::InvalidModelAlias {
+                        alias: alias.to_string(),
+                        provider: provider.clone(),
+                        model: model.clone(),
+                    });
+                }
+            }
+        }
+
+        let global_body_limit = self.global.body_limit.unwrap_or_default();
+        if self
+            .unified_api
+            .body_limit
+            .is_some_and(|body_limit| !body_limit.is_within(&global_body_limit))
+        {
+            errors.push(ConfigValidationError::BodyLimitAboveGlobal {
+                scope: "the unified API".to_string(),
+            });
+        }
+
+        for (router_id, router_config) in self.routers.as_ref() {
+            if !router_id_regex.is_match(router_id.as_ref()) {
+                errors.push(ConfigValidationError::InvalidRouterId(
+                    router_id.clone(),
+                ));
+            }
+            if router_config.body_limit.is_some_and(|body_limit| {
+                !body_limit.is_within(&global_body_limit)
+            }) {
+                errors.push(ConfigValidationError::BodyLimitAboveGlobal {
+                    scope: format!("router {router_id}"),
+                });
+            }
+            errors.extend(router_config.validation_errors().into_iter().map(
+                |error| ConfigValidationError::Router {
+                    router: router_id.clone(),
+                    error,
+                },
+            ));
+
+            for (alias, model) in &router_config.model_aliases {
+                let Some(provider) = model_alias_provider(model) else {
+                    continue;
+                };
+                let Ok(provider) = InferenceProvider::from_str(provider);
+                if !self.providers.contains_key(&provider) {
+                    errors.push(ConfigValidationError::Router {
+                        router: router_id.clone(),
+                        error:
+                            RouterValidationError::UnknownModelAliasProvider {
+                                alias: alias.clone(),
+                                provider,
+                            },
+                    });
+                }
+            }
+
+            let mut providers = router_config.load_balance.providers();
+            if let Some(shadow) = &router_config.shadow {
+                providers.insert(shadow.provider.clone());
+            }
+            if let Some(evaluation) = &router_config.evaluation {
+                providers.insert(evaluation.provider.clone());
+            }
+            if providers.contains(&InferenceProvider::VertexAI)
+                && self
+                    .providers
+                    .get(&InferenceProvider::VertexAI)
+                    .is_none_or(|provider| provider.project.is_none())
+            {
+                errors.push(ConfigValidationError::MissingVertexProject {
+                    router: router_id.clone(),
+                });
+            }
+
+            let ProviderKeys::Sidecar(keys) = provider_keys else {
+                continue;
+            };
+            for provider in providers {
+                // ollama doesn't require an API key
+                if provider != InferenceProvider::Ollama
+                    && !keys.contains_key(&provider)
+                {
+                    errors.push(
+                        ConfigValidationError::MissingProviderCredentials {
+                            router: router_id.clone(),
+                            provider,
+                        },
+                    );
+                }
+            }
+        }
+
+        errors
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ModelMappingValidationError {
     #[error(
@@ -152,9 +900,726 @@ impl Config {
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::HashMap, time::Duration};
+
     use compact_str::CompactString;
+    use indexmap::IndexMap;
+    use nonempty_collections::{nes, nev};
 
     use super::*;
+    use crate::{
+        config::{
+            DeploymentTarget,
+            balance::{
+                BalanceConfig, ExclusionWindow, ProviderSchedule,
+                WeightedProvider,
+            },
+            body_limit::BodyLimitConfig,
+            model_alias::ModelAliasConfig,
+            monitor::{CircuitBreakerConfig, RateLimitHeadersConfig},
+            redaction::RedactionPattern,
+            retry::{RetryBudgetConfig, RetryConfig},
+            routing_rules::{RoutingRule, RoutingTarget, RuleConditions},
+        },
+        endpoints::EndpointType,
+        tests::TestDefault,
+        types::provider::ProviderKeyMap,
+    };
+
+    fn router_config(load_balance: BalanceConfig) -> RouterConfig {
+        RouterConfig {
+            load_balance,
+            ..Default::default()
+        }
+    }
+
+    fn config_with_router(router_config: RouterConfig) -> Config {
+        Config {
+            deployment_target: DeploymentTarget::Sidecar,
+            routers: crate::config::router::RouterConfigs::new(HashMap::from(
+                [(
+                    RouterId::Named(CompactString::new("my-router")),
+                    router_config,
+                )],
+            )),
+            ..Config::test_default()
+        }
+    }
+
+    #[test]
+    fn zero_weight_config_fails_validation() {
+        let config =
+            config_with_router(router_config(BalanceConfig(HashMap::from([
+                (
+                    EndpointType::Chat,
+                    BalanceConfigInner::ProviderWeighted {
+                        providers: nes![
+                            WeightedProvider {
+                                provider: InferenceProvider::OpenAI,
+                                weight: Decimal::ZERO,
+                                schedule: None,
+                            },
+                            WeightedProvider {
+                                provider: InferenceProvider::Anthropic,
+                                weight: Decimal::ZERO,
+                                schedule: None,
+                            }
+                        ],
+                        sticky: false,
+                        seed: None,
+                    },
+                ),
+            ]))));
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::Router {
+                router: RouterId::Named(CompactString::new("my-router")),
+                error: RouterValidationError::InvalidBalanceWeights {
+                    total: Decimal::ZERO,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_priority_provider_fails_validation() {
+        let router_config = router_config(BalanceConfig(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::Priority {
+                providers: nev![
+                    InferenceProvider::OpenAI,
+                    InferenceProvider::Anthropic,
+                    InferenceProvider::OpenAI
+                ],
+            },
+        )])));
+
+        let errors = router_config.validation_errors();
+
+        assert_eq!(
+            errors,
+            vec![RouterValidationError::DuplicatePriorityProvider {
+                provider: InferenceProvider::OpenAI,
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_canary_fails_validation() {
+        let router_config = router_config(BalanceConfig(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::Canary {
+                stable: InferenceProvider::OpenAI,
+                canary: InferenceProvider::Anthropic,
+                percentage: Decimal::from(150),
+                header: "not a header".to_string(),
+            },
+        )])));
+
+        let errors = router_config.validation_errors();
+
+        assert_eq!(
+            errors,
+            vec![
+                RouterValidationError::InvalidCanaryPercentage {
+                    percentage: Decimal::from(150),
+                },
+                RouterValidationError::InvalidCanaryHeader {
+                    name: "not a header".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_hash_key_fails_validation() {
+        let balance_config = |key| {
+            BalanceConfig(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::ConsistentHash {
+                    providers: nes![WeightedProvider {
+                        provider: InferenceProvider::OpenAI,
+                        weight: Decimal::ONE,
+                        schedule: None,
+                    }],
+                    key,
+                },
+            )]))
+        };
+
+        let header = router_config(balance_config(HashKeySource::Header(
+            "not a header".to_string(),
+        )));
+        let pointer = router_config(balance_config(
+            HashKeySource::JsonPointer("messages/0/content".to_string()),
+        ));
+
+        assert_eq!(
+            header.validation_errors(),
+            vec![RouterValidationError::InvalidHashKeyHeader {
+                name: "not a header".to_string(),
+            }]
+        );
+        assert_eq!(
+            pointer.validation_errors(),
+            vec![RouterValidationError::InvalidHashKeyPointer {
+                pointer: "messages/0/content".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_schedules_fail_validation() {
+        let noon = chrono::NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        let schedule = ProviderSchedule {
+            timezone: chrono_tz::Tz::UTC,
+            exclude: vec![ExclusionWindow {
+                days: vec![],
+                start: noon,
+                end: noon,
+            }],
+        };
+        let target = WeightedProvider {
+            provider: InferenceProvider::OpenAI,
+            weight: Decimal::ONE,
+            schedule: Some(schedule),
+        };
+        let weighted = router_config(BalanceConfig(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ProviderWeighted {
+                providers: nes![target.clone()],
+                sticky: false,
+                seed: None,
+            },
+        )])));
+        let consistent_hash = router_config(BalanceConfig(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ConsistentHash {
+                providers: nes![target],
+                key: HashKeySource::Header("x-session-id".to_string()),
+            },
+        )])));
+
+        assert_eq!(
+            weighted.validation_errors(),
+            vec![RouterValidationError::EmptyScheduleWindow {
+                provider: InferenceProvider::OpenAI,
+            }]
+        );
+        assert_eq!(
+            consistent_hash.validation_errors(),
+            vec![RouterValidationError::UnsupportedProviderSchedule {
+                provider: InferenceProvider::OpenAI,
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_retry_budget_fails_validation() {
+        let mut retries = RetryConfig::test_default();
+        retries.budget.ratio = Decimal::from(2);
+        let router_config = RouterConfig {
+            retries: Some(retries.clone()),
+            ..router_config(BalanceConfig::default())
+        };
+        assert_eq!(
+            router_config.validation_errors(),
+            vec![RouterValidationError::InvalidRetryBudget]
+        );
+
+        retries.budget = RetryBudgetConfig {
+            window: Duration::from_secs(120),
+            ..Default::default()
+        };
+        let mut config = Config::test_default();
+        config.global.retries = Some(retries);
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+        assert!(config.validation_errors(&provider_keys).contains(
+            &ConfigValidationError::InvalidRetryBudget { scope: "global" }
+        ));
+    }
+
+    #[test]
+    fn lenient_router_body_limit_fails_validation() {
+        let mut config = config_with_router(RouterConfig {
+            body_limit: Some(BodyLimitConfig {
+                max_request_body_bytes: 1024,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        config.global.body_limit = Some(BodyLimitConfig {
+            max_request_body_bytes: 512,
+            ..Default::default()
+        });
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::BodyLimitAboveGlobal {
+                scope: "router my-router".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn router_connect_timeout_fails_validation() {
+        let mut router_config = router_config(BalanceConfig::default());
+        router_config.timeouts.request = Some(Duration::from_secs(30));
+        assert!(router_config.validation_errors().is_empty());
+
+        router_config.timeouts.connect = Some(Duration::from_secs(1));
+        assert_eq!(
+            router_config.validation_errors(),
+            vec![RouterValidationError::UnsupportedConnectTimeout]
+        );
+    }
+
+    #[test]
+    fn invalid_transform_header_fails_validation() {
+        let router_config = RouterConfig {
+            transforms: vec![
+                TransformRule::SetHeader {
+                    name: "x-valid".to_string(),
+                    value: "value".to_string(),
+                },
+                TransformRule::RemoveHeader {
+                    name: "not a header".to_string(),
+                },
+            ],
+            ..router_config(BalanceConfig::default())
+        };
+
+        let errors = router_config.validation_errors();
+
+        assert_eq!(
+            errors,
+            vec![RouterValidationError::InvalidTransformHeader {
+                name: "not a header".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_redaction_pattern_fails_validation() {
+        let router_config = RouterConfig {
+            redaction: Some(RedactionConfig {
+                patterns: vec![
+                    RedactionPattern::Email,
+                    RedactionPattern::Regex("sk-[".to_string()),
+                ],
+                ..Default::default()
+            }),
+            ..router_config(BalanceConfig::default())
+        };
+
+        let errors = router_config.validation_errors();
+
+        assert_eq!(
+            errors,
+            vec![RouterValidationError::InvalidRedactionPattern {
+                pattern: "sk-[".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_router_model_aliases_fail_validation() {
+        let config = config_with_router(RouterConfig {
+            model_aliases: HashMap::from([
+                ("fast".to_string(), "openai/gpt-4o-mini".to_string()),
+                ("quick".to_string(), "fast".to_string()),
+                ("smart".to_string(), "gpt-4o".to_string()),
+                ("cheap".to_string(), "unknown/model".to_string()),
+            ]),
+            ..Default::default()
+        });
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        let router_error = |error| ConfigValidationError::Router {
+            router: RouterId::Named(CompactString::new("my-router")),
+            error,
+        };
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        for error in [
+            RouterValidationError::ChainedModelAlias {
+                alias: "quick".to_string(),
+                model: "fast".to_string(),
+            },
+            RouterValidationError::InvalidModelAlias {
+                alias: "smart".to_string(),
+                model: "gpt-4o".to_string(),
+            },
+            RouterValidationError::UnknownModelAliasProvider {
+                alias: "cheap".to_string(),
+                provider: InferenceProvider::Named("unknown".into()),
+            },
+        ] {
+            assert!(errors.contains(&router_error(error)));
+        }
+    }
+
+    #[test]
+    fn invalid_routing_rules_fail_validation() {
+        let rule = |model: Option<&str>, provider| RoutingRule {
+            when: RuleConditions::default(),
+            target: RoutingTarget {
+                model: model.map(ToString::to_string),
+                provider,
+            },
+        };
+        let config = RouterConfig {
+            routing_rules: Some(RoutingRulesConfig {
+                rules: vec![
+                    rule(Some("openai/gpt-4o-mini"), None),
+                    rule(None, None),
+                    rule(Some("gpt-4o"), Some(InferenceProvider::OpenAI)),
+                ],
+                default: Some(RoutingTarget {
+                    model: None,
+                    provider: Some(InferenceProvider::Anthropic),
+                }),
+            }),
+            ..router_config(BalanceConfig::openai_chat())
+        };
+
+        assert_eq!(
+            config.validation_errors(),
+            vec![
+                RouterValidationError::EmptyRoutingTarget {
+                    rule: "2".to_string(),
+                },
+                RouterValidationError::InvalidRoutingModel {
+                    rule: "3".to_string(),
+                    model: "gpt-4o".to_string(),
+                },
+                RouterValidationError::UnknownRoutingProvider {
+                    rule: "default".to_string(),
+                    provider: InferenceProvider::Anthropic,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_model_alias_fails_validation() {
+        let config = Config {
+            model_aliases: ModelAliasConfig(HashMap::from([(
+                ModelName::borrowed("fast"),
+                HashMap::from([
+                    (InferenceProvider::OpenAI, "gpt-4o-mini".to_string()),
+                    (InferenceProvider::Anthropic, "claude-".to_string()),
+                ]),
+            )])),
+            ..Config::test_default()
+        };
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::InvalidModelAlias {
+                alias: "fast".to_string(),
+                provider: InferenceProvider::Anthropic,
+                model: "claude-".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_circuit_breaker_ratio_fails_validation() {
+        let mut config = Config::test_default();
+        config.discover.monitor.circuit_breaker = Some(CircuitBreakerConfig {
+            error_ratio: Decimal::TWO,
+            ..Default::default()
+        });
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::InvalidCircuitBreakerRatio {
+                ratio: Decimal::TWO,
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_rate_limit_headers_config_fails_validation() {
+        let mut config = Config::test_default();
+        config.discover.monitor.rate_limit_headers =
+            Some(RateLimitHeadersConfig {
+                threshold: Decimal::TWO,
+                reduced_weight: Decimal::ZERO,
+            });
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![
+                ConfigValidationError::InvalidCapacityThreshold {
+                    threshold: Decimal::TWO,
+                },
+                ConfigValidationError::InvalidReducedWeight {
+                    weight: Decimal::ZERO,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_rate_limit_monitor_initial_weight_fails_validation() {
+        let mut config = Config::test_default();
+        config.discover.monitor.rate_limit.initial_weight = Decimal::TWO;
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::InvalidInitialWeight {
+                weight: Decimal::TWO,
+            }]
+        );
+    }
+
+    #[test]
+    fn grpc_health_port_conflict_fails_validation() {
+        let mut config = Config::test_default();
+        config.server.grpc_health_port = Some(config.server.port);
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::GrpcHealthPortConflict {
+                port: config.server.port,
+            }]
+        );
+    }
+
+    #[test]
+    fn protected_or_invalid_upstream_headers_fail_validation() {
+        let mut config = Config::test_default();
+        config.dispatcher.default_upstream_headers = IndexMap::from([
+            ("x-company-tenant".to_string(), "acme".to_string()),
+            ("Authorization".to_string(), "Bearer sk-other".to_string()),
+        ]);
+        config
+            .providers
+            .get_mut(&InferenceProvider::Anthropic)
+            .unwrap()
+            .upstream_headers = IndexMap::from([
+            ("user agent".to_string(), "acme".to_string()),
+            ("x-api-key".to_string(), "sk-ant-other".to_string()),
+        ]);
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![
+                ConfigValidationError::ProtectedUpstreamHeader {
+                    name: "Authorization".to_string(),
+                },
+                ConfigValidationError::InvalidUpstreamHeader {
+                    name: "user agent".to_string(),
+                },
+                ConfigValidationError::ProtectedUpstreamHeader {
+                    name: "x-api-key".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_cors_config_fails_validation() {
+        let mut config = Config::test_default();
+        config.cors = CorsConfig {
+            allowed_origins: vec![
+                "https://app.example.com\n".to_string(),
+                "https://*.example.com".to_string(),
+                "https://*.*.example.com".to_string(),
+            ],
+            allowed_methods: vec!["GET".to_string(), "PO ST".to_string()],
+            allow_credentials: true,
+            ..Default::default()
+        };
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![
+                ConfigValidationError::InvalidCorsValue {
+                    field: "origin",
+                    value: "https://app.example.com\n".to_string(),
+                },
+                ConfigValidationError::InvalidCorsValue {
+                    field: "origin",
+                    value: "https://*.*.example.com".to_string(),
+                },
+                ConfigValidationError::InvalidCorsValue {
+                    field: "method",
+                    value: "PO ST".to_string(),
+                },
+                ConfigValidationError::CorsWildcardWithCredentials,
+            ]
+        );
+    }
+
+    #[test]
+    fn router_cors_wildcard_with_credentials_fails_validation() {
+        let router_config = RouterConfig {
+            cors: Some(CorsConfig {
+                allow_credentials: true,
+                ..Default::default()
+            }),
+            ..router_config(BalanceConfig::default())
+        };
+
+        let errors = router_config.validation_errors();
+
+        assert_eq!(
+            errors,
+            vec![RouterValidationError::CorsWildcardWithCredentials]
+        );
+    }
+
+    #[test]
+    fn invalid_header_patterns_fail_validation() {
+        let mut config = config_with_router(RouterConfig {
+            response_headers: Some(HeaderFilterConfig {
+                deny: vec!["x-*-id".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        config.request_headers.allow = vec!["x-tenant id".to_string()];
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![
+                ConfigValidationError::InvalidHeaderPattern {
+                    pattern: "x-tenant id".to_string(),
+                },
+                ConfigValidationError::Router {
+                    router: RouterId::Named(CompactString::new("my-router")),
+                    error: RouterValidationError::InvalidHeaderPattern {
+                        pattern: "x-*-id".to_string(),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_credentials_fails_validation() {
+        let config =
+            config_with_router(router_config(BalanceConfig(HashMap::from([
+                (
+                    EndpointType::Chat,
+                    BalanceConfigInner::BalancedLatency {
+                        providers: nes![
+                            InferenceProvider::OpenAI,
+                            InferenceProvider::Anthropic,
+                            InferenceProvider::Ollama
+                        ],
+                    },
+                ),
+            ]))));
+        let keys = ProviderKeyMap::test_default()
+            .iter()
+            .filter(|(provider, _)| **provider != InferenceProvider::Anthropic)
+            .map(|(provider, key)| (provider.clone(), key.clone()))
+            .collect();
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::from_db(keys));
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::MissingProviderCredentials {
+                router: RouterId::Named(CompactString::new("my-router")),
+                provider: InferenceProvider::Anthropic,
+            }]
+        );
+    }
+
+    #[test]
+    fn vertex_without_project_fails_validation() {
+        let config =
+            config_with_router(router_config(BalanceConfig(HashMap::from([
+                (
+                    EndpointType::Chat,
+                    BalanceConfigInner::BalancedLatency {
+                        providers: nes![InferenceProvider::VertexAI],
+                    },
+                ),
+            ]))));
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(
+            errors,
+            vec![ConfigValidationError::MissingVertexProject {
+                router: RouterId::Named(CompactString::new("my-router")),
+            }]
+        );
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let mut router = router_config(BalanceConfig::openai_chat());
+        router.cache = Some(CacheConfig {
+            buckets: 0,
+            ..Default::default()
+        });
+        let mut config = config_with_router(router);
+        config.global.cache = Some(CacheConfig {
+            buckets: MAX_BUCKET_SIZE + 1,
+            ..Default::default()
+        });
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::from_db(Default::default()));
+
+        let errors = config.validation_errors(&provider_keys);
+
+        assert_eq!(errors.len(), 3, "{errors:?}");
+    }
 
     #[test]
     fn default_config_passes_validation() {
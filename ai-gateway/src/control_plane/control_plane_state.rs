@@ -1,27 +1,102 @@
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
+use compact_str::CompactString;
+use rust_decimal::Decimal;
+use rustc_hash::FxHashMap as HashMap;
 
-use super::types::{Config, ControlPlaneError, MessageTypeRX, Update};
+use super::types::{
+    Config, ControlPlaneError, MessageTypeRX, OrgProviderKey, OrgSpendLimit,
+    RateLimitRule, Update,
+};
+use crate::{
+    config::{rate_limit::GcraConfig, router::RouterConfig},
+    middleware::rate_limit::store::Gcra,
+    types::{
+        org::OrgId,
+        provider::{InferenceProvider, ProviderKey},
+        router::RouterId,
+        user::UserId,
+    },
+};
 const MAX_HISTORY_SIZE: usize = 100;
 
 #[derive(Debug, Default)]
 pub struct ControlPlaneState {
     pub last_heartbeat: Option<DateTime<Utc>>,
     pub config: Config,
+    /// The organizations' own provider keys from [`Config::provider_keys`],
+    /// indexed for lookups on every request.
+    org_provider_keys: HashMap<OrgId, HashMap<InferenceProvider, ProviderKey>>,
+    /// The organizations' spend limits from [`Config::spend_limits`].
+    org_spend_limits: HashMap<OrgId, Decimal>,
+    /// The rate limits from [`Config::rate_limits`], keyed by organization
+    /// and user, with `None` for the limits of whole organizations.
+    rate_limits: HashMap<(OrgId, Option<UserId>), Gcra>,
 
     // used mainly for debugging and testing, can remove later
     pub history: Vec<MessageTypeRX>,
 }
 
+/// A router config pushed by the control plane that differs from the one we
+/// last received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterConfigUpdate {
+    pub router_id: RouterId,
+    pub router_config: RouterConfig,
+}
+
 impl ControlPlaneState {
     #[must_use]
     pub fn new() -> Self {
         Self {
             last_heartbeat: None,
             config: Config::default(),
+            org_provider_keys: HashMap::default(),
+            org_spend_limits: HashMap::default(),
+            rate_limits: HashMap::default(),
             history: Vec::new(),
         }
     }
-    pub fn update(&mut self, m: MessageTypeRX) {
+    /// Replace the config, e.g. with the one the control plane sent on
+    /// connecting.
+    pub fn set_config(&mut self, config: Config) {
+        self.org_provider_keys = index_provider_keys(&config.provider_keys);
+        self.org_spend_limits = index_spend_limits(&config.spend_limits);
+        self.rate_limits = index_rate_limits(&config.rate_limits);
+        self.config = config;
+    }
+
+    /// The key the organization brings for the provider, if any.
+    #[must_use]
+    pub fn org_provider_key(
+        &self,
+        org_id: &OrgId,
+        provider: &InferenceProvider,
+    ) -> Option<&ProviderKey> {
+        self.org_provider_keys.get(org_id)?.get(provider)
+    }
+
+    /// The spend limit the control plane set for the organization, if any.
+    #[must_use]
+    pub fn org_spend_limit(&self, org_id: &OrgId) -> Option<Decimal> {
+        self.org_spend_limits.get(org_id).copied()
+    }
+
+    /// The rate limit the control plane set for the user of the
+    /// organization, or for the whole organization if `user_id` is `None`.
+    #[must_use]
+    pub fn rate_limit(
+        &self,
+        org_id: OrgId,
+        user_id: Option<UserId>,
+    ) -> Option<Gcra> {
+        self.rate_limits.get(&(org_id, user_id)).copied()
+    }
+
+    /// Apply a message from the control plane, returning the router config to
+    /// rebuild the router with if the message changed it.
+    pub fn update(&mut self, m: MessageTypeRX) -> Option<RouterConfigUpdate> {
         self.history.push(m.clone());
         if self.history.len() > MAX_HISTORY_SIZE {
             self.history.remove(0);
@@ -34,8 +109,25 @@ impl ControlPlaneState {
             MessageTypeRX::Update(Update::AuthData { data }) => {
                 self.config.auth = data;
             }
+            MessageTypeRX::Update(Update::ProviderKeys { data }) => {
+                self.org_provider_keys = index_provider_keys(&data);
+                self.config.provider_keys = data;
+            }
+            MessageTypeRX::Update(Update::SpendLimits { data }) => {
+                self.org_spend_limits = index_spend_limits(&data);
+                self.config.spend_limits = data;
+            }
+            MessageTypeRX::Update(Update::RateLimits { data }) => {
+                self.rate_limits = index_rate_limits(&data);
+                self.config.rate_limits = data;
+            }
             MessageTypeRX::Update(Update::Config { data }) => {
-                self.config = data;
+                let router_changed = data.router_id != self.config.router_id
+                    || data.router_config != self.config.router_config;
+                self.set_config(data);
+                if router_changed {
+                    return self.router_config_update();
+                }
             }
             MessageTypeRX::Ack(_) => todo!(),
             MessageTypeRX::Error(ControlPlaneError::Unauthorized {
@@ -47,5 +139,67 @@ impl ControlPlaneState {
                 );
             }
         }
+        None
     }
+
+    fn router_config_update(&self) -> Option<RouterConfigUpdate> {
+        if self.config.router_id.is_empty() {
+            return None;
+        }
+        let router_config =
+            serde_json::from_str::<RouterConfig>(&self.config.router_config)
+                .inspect_err(|e| {
+                    tracing::warn!(
+                        error = %e,
+                        router_id = %self.config.router_id,
+                        "Received invalid router config from control plane",
+                    );
+                })
+                .ok()?;
+        Some(RouterConfigUpdate {
+            router_id: RouterId::Named(CompactString::from(
+                self.config.router_id.as_str(),
+            )),
+            router_config,
+        })
+    }
+}
+
+fn index_provider_keys(
+    provider_keys: &[OrgProviderKey],
+) -> HashMap<OrgId, HashMap<InferenceProvider, ProviderKey>> {
+    let mut index: HashMap<OrgId, HashMap<_, _>> = HashMap::default();
+    for key in provider_keys {
+        index
+            .entry(key.organization_id)
+            .or_default()
+            .insert(key.provider.clone(), ProviderKey::Secret(key.key.clone()));
+    }
+    index
+}
+
+fn index_spend_limits(
+    spend_limits: &[OrgSpendLimit],
+) -> HashMap<OrgId, Decimal> {
+    spend_limits
+        .iter()
+        .map(|spend_limit| (spend_limit.organization_id, spend_limit.limit))
+        .collect()
+}
+
+fn index_rate_limits(
+    rate_limits: &[RateLimitRule],
+) -> HashMap<(OrgId, Option<UserId>), Gcra> {
+    rate_limits
+        .iter()
+        .map(|rule| {
+            let gcra = Gcra::from(&GcraConfig {
+                capacity: rule.capacity,
+                refill_frequency: Duration::from_millis(
+                    rule.refill_frequency_ms,
+                ),
+            });
+            ((rule.organization_id, rule.user_id), gcra)
+        })
+        .collect()
 }
@@ -1,10 +1,13 @@
-use std::fmt::Write;
+use std::{fmt::Write, num::NonZeroU32};
 
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use ts_rs::TS;
 
-use crate::types::org::OrgId;
+use crate::types::{
+    org::OrgId, provider::InferenceProvider, secret::Secret, user::UserId,
+};
 
 /// Computes the hash of an API key for storage and lookup in the control plane.
 /// This function adds a "Bearer " prefix to the key before hashing to match
@@ -60,6 +63,56 @@ pub struct Key {
     pub organization_id: OrgId,
 }
 
+/// A provider key an organization brings for itself, used for its requests
+/// instead of the gateway's own key for the provider.
+#[derive(TS, Serialize, Deserialize, Debug, Clone)]
+#[ts(export)]
+#[ts(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct OrgProviderKey {
+    #[ts(as = "String")]
+    pub organization_id: OrgId,
+    #[ts(as = "String")]
+    pub provider: InferenceProvider,
+    #[ts(as = "String")]
+    pub key: Secret<String>,
+}
+
+/// The spend limit of an organization for each budget period, in USD,
+/// overriding the gateway's configured
+/// [`BudgetConfig::limit`](crate::config::budget::BudgetConfig::limit).
+#[derive(TS, Serialize, Deserialize, Debug, Clone)]
+#[ts(export)]
+#[ts(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct OrgSpendLimit {
+    #[ts(as = "String")]
+    pub organization_id: OrgId,
+    #[ts(as = "String")]
+    pub limit: Decimal,
+}
+
+/// A rate limit of an organization, or of one of its users, which sidecars
+/// enforce locally rather than asking the control plane on every request.
+#[derive(TS, Serialize, Deserialize, Debug, Clone)]
+#[ts(export)]
+#[ts(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitRule {
+    #[ts(as = "String")]
+    pub organization_id: OrgId,
+    /// Limits the user's requests, or all of the organization's if `None`.
+    #[ts(as = "Option<String>")]
+    #[serde(default)]
+    pub user_id: Option<UserId>,
+    /// The number of requests allowed in a burst.
+    #[ts(as = "u32")]
+    pub capacity: NonZeroU32,
+    /// How long it takes to refill the whole capacity, in milliseconds.
+    #[ts(type = "number")]
+    pub refill_frequency_ms: u64,
+}
+
 #[derive(TS, Serialize, Deserialize, Debug, Clone)]
 #[ts(export)]
 #[ts(rename_all = "camelCase")]
@@ -68,6 +121,12 @@ pub struct Key {
 pub struct Config {
     pub auth: AuthData,
     pub keys: Vec<Key>,
+    #[serde(default)]
+    pub provider_keys: Vec<OrgProviderKey>,
+    #[serde(default)]
+    pub spend_limits: Vec<OrgSpendLimit>,
+    #[serde(default)]
+    pub rate_limits: Vec<RateLimitRule>,
     pub router_id: String,
     pub router_config: String, // TODO: replace with router config
 }
@@ -98,6 +157,9 @@ impl crate::tests::TestDefault for Config {
                 owner_id: user_id.to_string(),
                 organization_id: OrgId::new(organization_id),
             }],
+            provider_keys: Vec::new(),
+            spend_limits: Vec::new(),
+            rate_limits: Vec::new(),
             router_id: "my-router".to_string(),
             router_config: "{}".to_string(),
         }
@@ -110,6 +172,9 @@ pub enum Update {
     AuthData { data: AuthData },
     Config { data: Config },
     Keys { data: Vec<Key> },
+    ProviderKeys { data: Vec<OrgProviderKey> },
+    SpendLimits { data: Vec<OrgSpendLimit> },
+    RateLimits { data: Vec<RateLimitRule> },
 }
 
 #[derive(TS, Serialize, Deserialize, Debug, Clone)]
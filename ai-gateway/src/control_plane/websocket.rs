@@ -21,7 +21,9 @@ use super::{
     types::{MessageTypeRX, MessageTypeTX},
 };
 use crate::{
+    app_state::AppState,
     config::helicone::HeliconeConfig,
+    discover::router::reload::reload_router,
     error::{init::InitError, runtime::RuntimeError},
 };
 type TlsWebSocketStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
@@ -39,17 +41,29 @@ pub struct ControlPlaneClient {
     /// Config about Control plane, such as the websocket url,
     /// reconnect interval/backoff policy, heartbeat interval, etc.
     config: HeliconeConfig,
+    /// Used to rebuild routers when the control plane pushes a new router
+    /// config.
+    app_state: Option<AppState>,
 }
 
 async fn handle_message(
     state: &Arc<RwLock<ControlPlaneState>>,
+    app_state: Option<&AppState>,
     message: Message,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let bytes = message.into_data();
     let m: MessageTypeRX = serde_json::from_slice(&bytes)?;
     tracing::debug!(websocket_msg = ?m, "received message");
-    let mut state_guard = state.write().await;
-    state_guard.update(m);
+    let update = state.write().await.update(m);
+
+    if let (Some(update), Some(app_state)) = (update, app_state) {
+        tracing::info!(
+            router_id = %update.router_id,
+            "router config updated by control plane, rebuilding router"
+        );
+        reload_router(app_state, update.router_id, update.router_config)
+            .await?;
+    }
 
     Ok(())
 }
@@ -136,9 +150,18 @@ impl ControlPlaneClient {
             channel,
             config,
             state: control_plane_state,
+            app_state: None,
         })
     }
 
+    /// Rebuild routers in place when the control plane pushes a new router
+    /// config.
+    #[must_use]
+    pub fn with_app_state(mut self, app_state: AppState) -> Self {
+        self.app_state = Some(app_state);
+        self
+    }
+
     pub async fn send_message(
         &mut self,
         m: MessageTypeTX,
@@ -166,11 +189,15 @@ impl ControlPlaneClient {
             while let Some(message) = self.channel.msg_rx.next().await {
                 match message {
                     Ok(message) => {
-                        let _ = handle_message(&state_clone, message)
-                            .await
-                            .inspect_err(|e| {
-                                tracing::error!(error = ?e, "websocket error");
-                            });
+                        let _ = handle_message(
+                            &state_clone,
+                            self.app_state.as_ref(),
+                            message,
+                        )
+                        .await
+                        .inspect_err(|e| {
+                            tracing::error!(error = ?e, "websocket error");
+                        });
                     }
                     Err(tungstenite::Error::AlreadyClosed) => {
                         tracing::error!(
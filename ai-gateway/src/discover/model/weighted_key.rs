@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    hash::{Hash, Hasher},
     sync::Arc,
     task::{Context, Poll},
 };
@@ -24,13 +25,32 @@ use crate::{
     types::{model_id::ModelId, router::RouterId},
 };
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// Keys are identified by their model and endpoint type alone, so that a
+/// model's weight can be changed by replacing its key, and removing the key
+/// removes the model whatever its current weight.
+#[derive(Debug, Clone)]
 pub struct WeightedKey {
     pub model_id: ModelId,
     pub endpoint_type: EndpointType,
     pub weight: Weight,
 }
 
+impl PartialEq for WeightedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.model_id == other.model_id
+            && self.endpoint_type == other.endpoint_type
+    }
+}
+
+impl Eq for WeightedKey {}
+
+impl Hash for WeightedKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.model_id.hash(state);
+        self.endpoint_type.hash(state);
+    }
+}
+
 impl WeightedKey {
     #[must_use]
     pub fn new(
@@ -44,6 +64,15 @@ impl WeightedKey {
             weight,
         }
     }
+
+    /// The same key at another weight.
+    #[must_use]
+    pub fn with_weight(&self, weight: Weight) -> Self {
+        Self {
+            weight,
+            ..self.clone()
+        }
+    }
 }
 
 impl DispatcherDiscovery<WeightedKey> {
@@ -79,6 +108,26 @@ impl DispatcherDiscovery<WeightedKey> {
                             .to_string(),
                     ));
                 }
+                BalanceConfigInner::Priority { .. } => {
+                    return Err(InitError::InvalidBalancer(
+                        "Priority balancer not supported for weighted \
+                         discovery"
+                            .to_string(),
+                    ));
+                }
+                BalanceConfigInner::Canary { .. } => {
+                    return Err(InitError::InvalidBalancer(
+                        "Canary balancer not supported for weighted discovery"
+                            .to_string(),
+                    ));
+                }
+                BalanceConfigInner::ConsistentHash { .. } => {
+                    return Err(InitError::InvalidBalancer(
+                        "Consistent hash balancer not supported for weighted \
+                         discovery"
+                            .to_string(),
+                    ));
+                }
             };
             for target_model_id in weighted_balance_targets {
                 let provider = target_model_id
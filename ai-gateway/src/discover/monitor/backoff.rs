@@ -0,0 +1,84 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use http::HeaderValue;
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::types::{provider::InferenceProvider, router::RouterId};
+
+/// When each rate limited provider said it may be retried, from the
+/// `Retry-After` of its last 429 response.
+///
+/// Backoffs are scoped to the router whose request was rate limited, since
+/// routers may call a provider with different credentials, e.g. an
+/// organization's own key. Priority routers skip providers which are backing
+/// off, and the router's dispatchers reject requests to them with a 429 until
+/// the backoff has elapsed.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderBackoffs(
+    Arc<Mutex<HashMap<(RouterId, InferenceProvider), Instant>>>,
+);
+
+impl ProviderBackoffs {
+    /// Back off from the router's provider for `retry_after`, unless it is
+    /// already backing off for longer.
+    pub fn back_off(
+        &self,
+        router_id: &RouterId,
+        provider: &InferenceProvider,
+        retry_after: Duration,
+    ) {
+        let until = Instant::now() + retry_after;
+        let mut backoffs =
+            self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let backoff = backoffs
+            .entry((router_id.clone(), provider.clone()))
+            .or_insert(until);
+        *backoff = (*backoff).max(until);
+    }
+
+    /// How much longer the router's provider is backing off for, or `None`
+    /// if it may be sent requests.
+    #[must_use]
+    pub fn remaining(
+        &self,
+        router_id: &RouterId,
+        provider: &InferenceProvider,
+    ) -> Option<Duration> {
+        let key = (router_id.clone(), provider.clone());
+        let mut backoffs =
+            self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let until = *backoffs.get(&key)?;
+        let remaining = until.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            backoffs.remove(&key);
+            return None;
+        }
+        Some(remaining)
+    }
+
+    /// The shortest remaining backoff of the router's `providers` which are
+    /// backing off.
+    #[must_use]
+    pub fn min_remaining<'a>(
+        &self,
+        router_id: &RouterId,
+        providers: impl IntoIterator<Item = &'a InferenceProvider>,
+    ) -> Option<Duration> {
+        providers
+            .into_iter()
+            .filter_map(|provider| self.remaining(router_id, provider))
+            .min()
+    }
+}
+
+/// A `Retry-After` header value for `retry_after`, rounded up so that clients
+/// never retry early.
+#[must_use]
+pub fn retry_after_header(retry_after: Duration) -> HeaderValue {
+    let seconds =
+        retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    HeaderValue::from(seconds)
+}
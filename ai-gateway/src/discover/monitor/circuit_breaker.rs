@@ -0,0 +1,177 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+use rust_decimal::prelude::ToPrimitive;
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{
+    config::{Config, monitor::CircuitBreakerConfig},
+    metrics::rolling_counter::RollingCounter,
+    types::provider::InferenceProvider,
+};
+
+const BUCKETS: u32 = 10;
+
+/// The state of a provider's circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent to the provider as usual.
+    Closed,
+    /// The provider failed too many requests, so none are sent to it until
+    /// the open duration has elapsed.
+    Open { since: Instant },
+    /// Trial requests are sent to the provider. A failure opens the circuit
+    /// again, while enough consecutive successes close it.
+    HalfOpen { successes: u32 },
+}
+
+/// Per provider circuit breakers, configured via
+/// `discover.monitor.circuit-breaker`.
+///
+/// Like the concurrency limits, breakers are shared by every router and
+/// direct proxy that dispatches to the provider. Open circuits fail the
+/// provider's health check, so the health monitor removes the provider from
+/// load balancers until its circuit is half-open again.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreakers(Arc<HashMap<InferenceProvider, CircuitBreaker>>);
+
+impl CircuitBreakers {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        let Some(breaker_config) = &config.discover.monitor.circuit_breaker
+        else {
+            return Self::default();
+        };
+        let breakers = config
+            .providers
+            .keys()
+            .map(|provider| {
+                (
+                    provider.clone(),
+                    CircuitBreaker::new(breaker_config.clone()),
+                )
+            })
+            .collect();
+        Self(Arc::new(breakers))
+    }
+
+    /// Whether requests may be sent to the provider, moving an open circuit
+    /// to half-open once its open duration has elapsed.
+    #[must_use]
+    pub fn allows(&self, provider: &InferenceProvider) -> bool {
+        self.0.get(provider).is_none_or(CircuitBreaker::allows)
+    }
+
+    /// Record the outcome of a request sent to the provider.
+    pub fn record(&self, provider: &InferenceProvider, is_failure: bool) {
+        if let Some(breaker) = self.0.get(provider) {
+            breaker.record(provider, is_failure);
+        }
+    }
+
+    #[must_use]
+    pub fn state(&self, provider: &InferenceProvider) -> CircuitState {
+        self.0
+            .get(provider)
+            .map_or(CircuitState::Closed, |breaker| {
+                breaker
+                    .inner
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .state
+            })
+    }
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<CircuitBreakerInner>,
+}
+
+#[derive(Debug)]
+struct CircuitBreakerInner {
+    state: CircuitState,
+    requests: RollingCounter,
+    failures: RollingCounter,
+}
+
+impl CircuitBreaker {
+    fn new(config: CircuitBreakerConfig) -> Self {
+        let inner = Mutex::new(CircuitBreakerInner {
+            state: CircuitState::Closed,
+            requests: RollingCounter::new(config.window, BUCKETS),
+            failures: RollingCounter::new(config.window, BUCKETS),
+        });
+        Self { config, inner }
+    }
+
+    fn allows(&self) -> bool {
+        let mut inner =
+            self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        match inner.state {
+            CircuitState::Closed | CircuitState::HalfOpen { .. } => true,
+            CircuitState::Open { since }
+                if since.elapsed() >= self.config.open_duration =>
+            {
+                inner.state = CircuitState::HalfOpen { successes: 0 };
+                true
+            }
+            CircuitState::Open { .. } => false,
+        }
+    }
+
+    fn record(&self, provider: &InferenceProvider, is_failure: bool) {
+        let mut inner =
+            self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        match inner.state {
+            CircuitState::Closed => {
+                inner.requests.incr();
+                if is_failure {
+                    inner.failures.incr();
+                }
+                let requests = inner.requests.total();
+                if requests < self.config.min_requests {
+                    return;
+                }
+                let error_ratio =
+                    f64::from(inner.failures.total()) / f64::from(requests);
+                let threshold = self.config.error_ratio.to_f64().unwrap_or(1.0);
+                if error_ratio >= threshold {
+                    tracing::info!(
+                        provider = %provider,
+                        error_ratio,
+                        "circuit opened"
+                    );
+                    inner.state = CircuitState::Open {
+                        since: Instant::now(),
+                    };
+                }
+            }
+            CircuitState::HalfOpen { .. } if is_failure => {
+                tracing::info!(provider = %provider, "trial request failed");
+                inner.state = CircuitState::Open {
+                    since: Instant::now(),
+                };
+            }
+            CircuitState::HalfOpen { successes } => {
+                let successes = successes + 1;
+                if successes < self.config.half_open_successes.get() {
+                    inner.state = CircuitState::HalfOpen { successes };
+                    return;
+                }
+                tracing::info!(provider = %provider, "circuit closed");
+                // failures from before the circuit opened no longer count
+                *inner = CircuitBreakerInner {
+                    state: CircuitState::Closed,
+                    requests: RollingCounter::new(self.config.window, BUCKETS),
+                    failures: RollingCounter::new(self.config.window, BUCKETS),
+                };
+            }
+            // responses to requests sent before the circuit opened
+            CircuitState::Open { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Periodically verify provider credentials with a cheap authenticated
+//! request, see [`CredentialMonitorConfig`].
+//!
+//! Rejected credentials are recorded in [`InnerAppState::invalid_credentials`]
+//! so that the [`HealthMonitor`] can remove the provider from load balancers
+//! when `remove-unhealthy` is enabled.
+//!
+//! [`CredentialMonitorConfig`]: crate::config::monitor::CredentialMonitorConfig
+//! [`InnerAppState::invalid_credentials`]: crate::app_state::InnerAppState::invalid_credentials
+//! [`HealthMonitor`]: super::health::HealthMonitor
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture};
+use http::StatusCode;
+use indexmap::IndexSet;
+use meltdown::Token;
+use opentelemetry::KeyValue;
+use tokio::time;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    app_state::AppState,
+    dispatcher::client::Client,
+    error::runtime::RuntimeError,
+    types::provider::{InferenceProvider, ProviderKey},
+};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of verifying a single provider credential.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CredentialStatus {
+    Valid,
+    Invalid,
+    /// The check failed for a reason unrelated to the credential, e.g. the
+    /// provider is unreachable, so we keep the previous status.
+    Unknown,
+}
+
+/// Only supported for sidecar deployments, in the cloud provider keys are per
+/// organization.
+#[derive(Debug, Clone)]
+pub struct CredentialMonitor {
+    app_state: AppState,
+}
+
+impl CredentialMonitor {
+    #[must_use]
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    pub async fn run_forever(self) -> Result<(), RuntimeError> {
+        info!("starting provider credential monitor");
+        let interval_duration = self
+            .app_state
+            .config()
+            .discover
+            .monitor
+            .credentials
+            .clone()
+            .unwrap_or_default()
+            .interval;
+        let mut interval = time::interval(interval_duration);
+        loop {
+            interval.tick().await;
+            self.check_credentials().await;
+        }
+    }
+
+    async fn check_credentials(&self) {
+        let providers = self
+            .app_state
+            .0
+            .router_configs
+            .read()
+            .await
+            .values()
+            .flat_map(|router_config| router_config.load_balance.providers())
+            .collect::<IndexSet<_>>();
+
+        // a check that can't be sent only affects its own provider, it never
+        // stops the monitor
+        let checks = providers.into_iter().map(|provider| async move {
+            let status = self.check_credential(&provider).await;
+            (provider, status)
+        });
+        let results = future::join_all(checks).await;
+
+        let mut invalid_credentials =
+            self.app_state.0.invalid_credentials.write().await;
+        for (provider, status) in results {
+            let metric_attributes =
+                [KeyValue::new("provider", provider.to_string())];
+            let metrics = &self.app_state.0.metrics;
+            match status {
+                CredentialStatus::Valid => {
+                    if invalid_credentials.remove(&provider) {
+                        info!(provider = %provider, "provider credential is valid again");
+                    }
+                    metrics
+                        .provider_credential_health
+                        .record(1, &metric_attributes);
+                }
+                CredentialStatus::Invalid => {
+                    error!(provider = %provider, "provider rejected the configured credential");
+                    invalid_credentials.insert(provider);
+                    metrics
+                        .provider_credential_health
+                        .record(0, &metric_attributes);
+                    metrics.credential_failures.add(1, &metric_attributes);
+                }
+                CredentialStatus::Unknown => {}
+            }
+        }
+    }
+
+    async fn check_credential(
+        &self,
+        provider: &InferenceProvider,
+    ) -> CredentialStatus {
+        let provider_key = self
+            .app_state
+            .0
+            .provider_keys
+            .get_provider_key(provider, None)
+            .await;
+        // only API keys can be verified with a plain request, AWS credentials
+        // require signing and some providers don't need credentials at all
+        if !matches!(provider_key, Some(ProviderKey::Secret(_))) {
+            return CredentialStatus::Unknown;
+        }
+        let Some(url) = models_url(&self.app_state, provider) else {
+            return CredentialStatus::Unknown;
+        };
+
+        let client = match Client::new(&self.app_state, provider.clone()).await
+        {
+            Ok(client) => client,
+            Err(e) => {
+                error!(provider = %provider, error = %e, "failed to create client for provider credential check");
+                return CredentialStatus::Unknown;
+            }
+        };
+        let response = match client
+            .as_ref()
+            .get(url)
+            .timeout(CHECK_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(provider = %provider, error = %e, "failed to check provider credential");
+                return CredentialStatus::Unknown;
+            }
+        };
+
+        let status = response.status();
+        debug!(provider = %provider, status = %status, "checked provider credential");
+        if status.is_success() {
+            CredentialStatus::Valid
+        } else if status == StatusCode::UNAUTHORIZED
+            || status == StatusCode::FORBIDDEN
+        {
+            CredentialStatus::Invalid
+        } else {
+            CredentialStatus::Unknown
+        }
+    }
+}
+
+/// Listing models is free and authenticated for every provider that uses an
+/// API key.
+pub(super) fn models_url(
+    app_state: &AppState,
+    provider: &InferenceProvider,
+) -> Option<url::Url> {
+    let base_url = &app_state.config().providers.get(provider)?.base_url;
+    let path = match provider {
+        InferenceProvider::GoogleGemini => "v1beta/models",
+        // the native API lists the models which have been pulled
+        InferenceProvider::Ollama => "api/tags",
+        _ => "v1/models",
+    };
+    base_url.join(path).ok()
+}
+
+impl meltdown::Service for CredentialMonitor {
+    type Future = BoxFuture<'static, Result<(), RuntimeError>>;
+
+    fn run(self, mut token: Token) -> Self::Future {
+        Box::pin(async move {
+            tokio::select! {
+                result = self.run_forever() => {
+                    if let Err(e) = result {
+                        error!(name = "provider-credential-monitor-task", error = ?e, "Monitor encountered error, shutting down");
+                    } else {
+                        debug!(name = "provider-credential-monitor-task", "Monitor shut down successfully");
+                    }
+                    token.trigger();
+                }
+                () = &mut token => {
+                    debug!(name = "provider-credential-monitor-task", "task shut down successfully");
+                }
+            }
+            Ok(())
+        })
+    }
+}
@@ -24,6 +24,7 @@ use crate::{
         model::{
             key::Key as ModelKey, weighted_key::WeightedKey as ModelWeightedKey,
         },
+        monitor::{circuit_breaker::CircuitState, pool::RemovalReason},
         provider::{
             key::Key as ProviderKey,
             weighted_key::WeightedKey as ProviderWeightedKey,
@@ -131,7 +132,7 @@ async fn check_provider_weighted_monitor(
         inner.router_config.load_balance.as_ref()
     {
         match balance_config {
-            BalanceConfigInner::ProviderWeighted { providers } => {
+            BalanceConfigInner::ProviderWeighted { providers, .. } => {
                 for target in providers {
                     let provider = &target.provider;
                     let weight = Weight::from(
@@ -144,12 +145,17 @@ async fn check_provider_weighted_monitor(
                         provider.clone(),
                         *endpoint_type,
                         weight,
+                    )
+                    .with_schedule(
+                        target.schedule.clone(),
+                        inner.app_state.0.clock.clone(),
                     );
-                    let is_healthy = inner.check_health(provider)?;
+                    let is_healthy = inner.check_health(provider).await?;
                     let was_unhealthy = inner.unhealthy_keys.contains(&key);
 
                     if !is_healthy && !was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became unhealthy, removing");
+                        inner.record_removal(provider);
                         if let Err(e) =
                             inner.tx.send(Change::Remove(key.clone())).await
                         {
@@ -158,6 +164,7 @@ async fn check_provider_weighted_monitor(
                         inner.unhealthy_keys.insert(key);
                     } else if is_healthy && was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became healthy, adding back");
+                        inner.record_restore(provider);
                         inner.unhealthy_keys.remove(&key);
 
                         let service = Dispatcher::new(
@@ -210,6 +217,24 @@ async fn check_provider_weighted_monitor(
                 );
                 return Err(InternalError::Internal.into());
             }
+            BalanceConfigInner::Priority { .. } => {
+                tracing::error!(
+                    "Priority entries in a provider weighted monitor"
+                );
+                return Err(InternalError::Internal.into());
+            }
+            BalanceConfigInner::Canary { .. } => {
+                tracing::error!(
+                    "Canary entries in a provider weighted monitor"
+                );
+                return Err(InternalError::Internal.into());
+            }
+            BalanceConfigInner::ConsistentHash { .. } => {
+                tracing::error!(
+                    "Consistent hash entries in a provider weighted monitor"
+                );
+                return Err(InternalError::Internal.into());
+            }
         }
     }
 
@@ -241,11 +266,12 @@ async fn check_model_weighted_monitor(
                         *endpoint_type,
                         weight,
                     );
-                    let is_healthy = inner.check_health(&provider)?;
+                    let is_healthy = inner.check_health(&provider).await?;
                     let was_unhealthy = inner.unhealthy_keys.contains(&key);
 
                     if !is_healthy && !was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became unhealthy, removing");
+                        inner.record_removal(&provider);
                         let all_models_of_unhealthy_provider = models
                             .iter()
                             .filter(|m| {
@@ -298,6 +324,7 @@ async fn check_model_weighted_monitor(
                         }
                     } else if is_healthy && was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became healthy, adding back");
+                        inner.record_restore(&provider);
                         let all_models_of_now_healthy_provider = models
                             .iter()
                             .filter(|m| {
@@ -375,6 +402,20 @@ async fn check_model_weighted_monitor(
                 );
                 return Err(InternalError::Internal.into());
             }
+            BalanceConfigInner::Priority { .. } => {
+                tracing::error!("Priority entries in a model weighted monitor");
+                return Err(InternalError::Internal.into());
+            }
+            BalanceConfigInner::Canary { .. } => {
+                tracing::error!("Canary entries in a model weighted monitor");
+                return Err(InternalError::Internal.into());
+            }
+            BalanceConfigInner::ConsistentHash { .. } => {
+                tracing::error!(
+                    "Consistent hash entries in a model weighted monitor"
+                );
+                return Err(InternalError::Internal.into());
+            }
         }
     }
 
@@ -388,15 +429,21 @@ async fn check_provider_latency_monitor(
         inner.router_config.load_balance.as_ref()
     {
         match balance_config {
-            BalanceConfigInner::BalancedLatency { providers } => {
-                for provider in providers {
+            // priority, canary and consistent hash balancers are keyed the
+            // same way as a P2C balancer
+            BalanceConfigInner::BalancedLatency { .. }
+            | BalanceConfigInner::Priority { .. }
+            | BalanceConfigInner::Canary { .. }
+            | BalanceConfigInner::ConsistentHash { .. } => {
+                for provider in &balance_config.providers() {
                     let key =
                         ProviderKey::new(provider.clone(), *endpoint_type);
-                    let is_healthy = inner.check_health(provider)?;
+                    let is_healthy = inner.check_health(provider).await?;
                     let was_unhealthy = inner.unhealthy_keys.contains(&key);
 
                     if !is_healthy && !was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became unhealthy, removing");
+                        inner.record_removal(provider);
                         if let Err(e) =
                             inner.tx.send(Change::Remove(key.clone())).await
                         {
@@ -405,6 +452,7 @@ async fn check_provider_latency_monitor(
                         inner.unhealthy_keys.insert(key);
                     } else if is_healthy && was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became healthy, adding back");
+                        inner.record_restore(provider);
                         inner.unhealthy_keys.remove(&key);
 
                         let service = Dispatcher::new(
@@ -474,11 +522,12 @@ async fn check_model_latency_monitor(
                             InitError::ModelIdNotRecognized(model.to_string())
                         })?;
                     let key = ModelKey::new(model.clone(), *endpoint_type);
-                    let is_healthy = inner.check_health(&provider)?;
+                    let is_healthy = inner.check_health(&provider).await?;
                     let was_unhealthy = inner.unhealthy_keys.contains(&key);
 
                     if !is_healthy && !was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became unhealthy, removing");
+                        inner.record_removal(&provider);
                         let all_models_of_unhealthy_provider = models
                             .iter()
                             .filter(|m| {
@@ -521,6 +570,7 @@ async fn check_model_latency_monitor(
                         }
                     } else if is_healthy && was_unhealthy {
                         trace!(provider = ?provider, endpoint_type = ?endpoint_type, "Provider became healthy, adding back");
+                        inner.record_restore(&provider);
                         let all_models_of_now_healthy_provider = models
                             .iter()
                             .filter(|m| {
@@ -583,6 +633,20 @@ async fn check_model_latency_monitor(
                 );
                 return Err(InternalError::Internal.into());
             }
+            BalanceConfigInner::Priority { .. } => {
+                tracing::error!("Priority entries in a model latency monitor");
+                return Err(InternalError::Internal.into());
+            }
+            BalanceConfigInner::Canary { .. } => {
+                tracing::error!("Canary entries in a model latency monitor");
+                return Err(InternalError::Internal.into());
+            }
+            BalanceConfigInner::ConsistentHash { .. } => {
+                tracing::error!(
+                    "Consistent hash entries in a model latency monitor"
+                );
+                return Err(InternalError::Internal.into());
+            }
         }
     }
 
@@ -616,12 +680,16 @@ impl<K> ProviderMonitorInner<K> {
         }
     }
 
-    fn check_health(
+    async fn check_health(
         &self,
         provider: &InferenceProvider,
     ) -> Result<bool, InternalError> {
-        let provider_endpoints = provider.endpoints();
+        if !self.app_state.passes_provider_checks(provider).await {
+            return Ok(false);
+        }
+
         let config = self.app_state.config();
+        let provider_endpoints = provider.endpoints();
         let grace_period = config.discover.monitor.grace_period();
         let mut all_healthy = true;
         for endpoint in provider_endpoints {
@@ -646,6 +714,32 @@ impl<K> ProviderMonitorInner<K> {
 
         Ok(all_healthy)
     }
+
+    /// Records the provider's removal from the router's pool, attributed to
+    /// its circuit breaker if its circuit is open.
+    fn record_removal(&self, provider: &InferenceProvider) {
+        let reason = if matches!(
+            self.app_state.0.circuit_breakers.state(provider),
+            CircuitState::Open { .. }
+        ) {
+            RemovalReason::CircuitOpen
+        } else {
+            RemovalReason::Unhealthy
+        };
+        self.app_state.0.provider_pools.remove(
+            &self.router_id,
+            provider,
+            reason,
+            None,
+        );
+    }
+
+    fn record_restore(&self, provider: &InferenceProvider) {
+        self.app_state
+            .0
+            .provider_pools
+            .restore(&self.router_id, provider);
+    }
 }
 
 #[derive(Debug, Clone)]
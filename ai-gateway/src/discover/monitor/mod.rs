@@ -1,3 +1,8 @@
+pub mod backoff;
+pub mod circuit_breaker;
+pub mod credentials;
 pub mod health;
 pub mod metrics;
+pub mod pool;
+pub mod probe;
 pub mod rate_limit;
@@ -0,0 +1,205 @@
+//! Which providers each router currently load balances across, recorded by
+//! the health and rate limit monitors as they remove providers from and
+//! re-add them to the router's balancer.
+//!
+//! The balancers themselves can't be inspected, so this is what `GET
+//! /admin/providers/health` serves.
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rustc_hash::FxHashMap as HashMap;
+use serde::Serialize;
+
+use crate::types::{provider::InferenceProvider, router::RouterId};
+
+/// Why a provider was removed from a router's pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemovalReason {
+    /// The provider responded with a 429.
+    RateLimited,
+    /// The provider's circuit breaker is open.
+    CircuitOpen,
+    /// The provider failed its other health checks, i.e. its error ratio,
+    /// probes or credential check.
+    Unhealthy,
+}
+
+/// How many times a provider was removed from a router's pool since the
+/// gateway started, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RemovalCounts {
+    pub rate_limited: u64,
+    pub circuit_open: u64,
+    pub unhealthy: u64,
+}
+
+impl RemovalCounts {
+    fn incr(&mut self, reason: RemovalReason) {
+        let count = match reason {
+            RemovalReason::RateLimited => &mut self.rate_limited,
+            RemovalReason::CircuitOpen => &mut self.circuit_open,
+            RemovalReason::Unhealthy => &mut self.unhealthy,
+        };
+        *count += 1;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Removal {
+    pub reason: RemovalReason,
+    pub removed_at: DateTime<Utc>,
+    /// When the provider is re-added, for rate limited providers. Unhealthy
+    /// providers are re-added once they pass their health checks again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProviderPoolState {
+    /// Set while the provider is removed from the pool.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removal: Option<Removal>,
+    pub removals: RemovalCounts,
+}
+
+impl ProviderPoolState {
+    #[must_use]
+    pub fn in_pool(&self) -> bool {
+        self.removal.is_none()
+    }
+}
+
+type RouterPools =
+    HashMap<RouterId, HashMap<InferenceProvider, ProviderPoolState>>;
+
+/// The pool state of each provider of each router.
+///
+/// Providers are removed from a balancer per endpoint type or model, but
+/// recorded per provider, so a provider is out of the pool if any of its
+/// keys are.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderPools(Arc<Mutex<RouterPools>>);
+
+impl ProviderPools {
+    pub fn remove(
+        &self,
+        router_id: &RouterId,
+        provider: &InferenceProvider,
+        reason: RemovalReason,
+        retry_after: Option<Duration>,
+    ) {
+        let removed_at = Utc::now();
+        let retry_after = retry_after
+            .and_then(|retry_after| {
+                chrono::Duration::from_std(retry_after).ok()
+            })
+            .map(|retry_after| removed_at + retry_after);
+        let mut pools = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = pools
+            .entry(router_id.clone())
+            .or_default()
+            .entry(provider.clone())
+            .or_default();
+        // each of the provider's keys is removed separately, which is still a
+        // single removal of the provider
+        if state
+            .removal
+            .as_ref()
+            .is_none_or(|removal| removal.reason != reason)
+        {
+            state.removals.incr(reason);
+        }
+        state.removal = Some(Removal {
+            reason,
+            removed_at,
+            retry_after,
+        });
+    }
+
+    pub fn restore(&self, router_id: &RouterId, provider: &InferenceProvider) {
+        let mut pools = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = pools
+            .get_mut(router_id)
+            .and_then(|providers| providers.get_mut(provider))
+        {
+            state.removal = None;
+        }
+    }
+
+    /// Rebuilt routers start with every provider in their pool, while the
+    /// removal counts are kept.
+    pub fn reset(&self, router_id: &RouterId) {
+        let mut pools = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(providers) = pools.get_mut(router_id) {
+            for state in providers.values_mut() {
+                state.removal = None;
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn state(
+        &self,
+        router_id: &RouterId,
+        provider: &InferenceProvider,
+    ) -> ProviderPoolState {
+        let pools = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        pools
+            .get(router_id)
+            .and_then(|providers| providers.get(provider))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compact_str::CompactString;
+
+    use super::*;
+
+    #[test]
+    fn removals_are_counted_once_per_removal() {
+        let pools = ProviderPools::default();
+        let router_id = RouterId::Named(CompactString::new("my-router"));
+        let provider = InferenceProvider::OpenAI;
+        assert!(pools.state(&router_id, &provider).in_pool());
+
+        let retry_after = Some(Duration::from_secs(30));
+        // e.g. once for chat completions and once for embeddings
+        pools.remove(&router_id, &provider, RemovalReason::RateLimited, None);
+        pools.remove(
+            &router_id,
+            &provider,
+            RemovalReason::RateLimited,
+            retry_after,
+        );
+        let state = pools.state(&router_id, &provider);
+        assert!(!state.in_pool());
+        let removal = state.removal.unwrap();
+        assert_eq!(removal.reason, RemovalReason::RateLimited);
+        assert!(removal.retry_after.unwrap() > removal.removed_at);
+        assert_eq!(state.removals.rate_limited, 1);
+
+        pools.restore(&router_id, &provider);
+        pools.remove(&router_id, &provider, RemovalReason::CircuitOpen, None);
+        pools.reset(&router_id);
+        let state = pools.state(&router_id, &provider);
+        assert!(state.in_pool());
+        assert_eq!(
+            state.removals,
+            RemovalCounts {
+                rate_limited: 1,
+                circuit_open: 1,
+                unhealthy: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Periodically probe providers independent of live traffic, see
+//! [`ProbeMonitorConfig`].
+//!
+//! Providers which fail enough consecutive probes are recorded in
+//! [`InnerAppState::unhealthy_probes`] so that the [`HealthMonitor`] removes
+//! them from load balancers, and they are added back after enough consecutive
+//! successful probes.
+//!
+//! Probes are sent directly with the provider's client rather than through a
+//! router, so they are never logged and don't count toward any request or
+//! spend metrics.
+//!
+//! [`ProbeMonitorConfig`]: crate::config::monitor::ProbeMonitorConfig
+//! [`InnerAppState::unhealthy_probes`]: crate::app_state::InnerAppState::unhealthy_probes
+//! [`HealthMonitor`]: super::health::HealthMonitor
+use std::collections::HashMap;
+
+use chrono::Utc;
+use futures::future::{self, BoxFuture};
+use http::StatusCode;
+use indexmap::IndexSet;
+use meltdown::Token;
+use serde_json::json;
+use tokio::time;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    app_state::AppState,
+    config::monitor::{ProbeKind, ProbeMonitorConfig},
+    discover::monitor::credentials::models_url,
+    dispatcher::client::Client,
+    error::runtime::RuntimeError,
+    types::provider::InferenceProvider,
+};
+
+/// The outcome of a single probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeStatus {
+    Success,
+    Failure,
+    /// The probe couldn't be sent or says nothing about the provider's
+    /// health, e.g. it was rate limited, so we keep the previous status.
+    Unknown,
+}
+
+/// Consecutive probe results for a provider.
+#[derive(Debug, Default, Clone, Copy)]
+struct Streak {
+    successes: u32,
+    failures: u32,
+}
+
+impl Streak {
+    fn record(&mut self, status: ProbeStatus) {
+        match status {
+            ProbeStatus::Success => {
+                self.successes = self.successes.saturating_add(1);
+                self.failures = 0;
+            }
+            ProbeStatus::Failure => {
+                self.failures = self.failures.saturating_add(1);
+                self.successes = 0;
+            }
+            ProbeStatus::Unknown => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeMonitor {
+    app_state: AppState,
+    streaks: HashMap<InferenceProvider, Streak>,
+}
+
+impl ProbeMonitor {
+    #[must_use]
+    pub fn new(app_state: AppState) -> Self {
+        Self {
+            app_state,
+            streaks: HashMap::default(),
+        }
+    }
+
+    pub async fn run_forever(mut self) -> Result<(), RuntimeError> {
+        info!("starting provider probe monitor");
+        let config = self
+            .app_state
+            .config()
+            .discover
+            .monitor
+            .probes
+            .clone()
+            .unwrap_or_default();
+        let mut interval = time::interval(config.interval);
+        loop {
+            interval.tick().await;
+            self.probe_providers(&config).await;
+        }
+    }
+
+    async fn probe_providers(&mut self, config: &ProbeMonitorConfig) {
+        let providers = self
+            .app_state
+            .0
+            .router_configs
+            .read()
+            .await
+            .values()
+            .flat_map(|router_config| router_config.load_balance.providers())
+            .collect::<IndexSet<_>>();
+
+        let this = &*self;
+        // a probe that can't be sent only affects its own provider, it never
+        // stops the monitor
+        let probes = providers.into_iter().map(|provider| async move {
+            let status = this.probe(config, &provider).await;
+            (provider, status)
+        });
+        let results = future::join_all(probes).await;
+
+        let now = Utc::now();
+        let mut last_probed = self.app_state.0.last_probed.write().await;
+        let mut unhealthy_probes =
+            self.app_state.0.unhealthy_probes.write().await;
+        for (provider, status) in results {
+            last_probed.insert(provider.clone(), now);
+            let streak = self.streaks.entry(provider.clone()).or_default();
+            streak.record(status);
+            if streak.failures >= config.unhealthy_threshold.get() {
+                if unhealthy_probes.insert(provider.clone()) {
+                    warn!(provider = %provider, failures = streak.failures, "provider failed consecutive probes");
+                }
+            } else if streak.successes >= config.healthy_threshold.get()
+                && unhealthy_probes.remove(&provider)
+            {
+                info!(provider = %provider, "provider is passing probes again");
+            }
+        }
+    }
+
+    async fn probe(
+        &self,
+        config: &ProbeMonitorConfig,
+        provider: &InferenceProvider,
+    ) -> ProbeStatus {
+        let Some(provider_config) =
+            self.app_state.config().providers.get(provider)
+        else {
+            return ProbeStatus::Unknown;
+        };
+        let client = match Client::new(&self.app_state, provider.clone()).await
+        {
+            Ok(client) => client,
+            Err(e) => {
+                error!(provider = %provider, error = %e, "failed to create client for provider probe");
+                return ProbeStatus::Unknown;
+            }
+        };
+        let request = match config.kind {
+            ProbeKind::Http => {
+                let url = match &provider_config.probe_path {
+                    Some(path) => match provider_config.base_url.join(path) {
+                        Ok(url) => url,
+                        Err(e) => {
+                            warn!(provider = %provider, error = %e, "invalid probe path");
+                            return ProbeStatus::Unknown;
+                        }
+                    },
+                    None => provider_config.base_url.clone(),
+                };
+                client.as_ref().get(url)
+            }
+            ProbeKind::Completion => {
+                // AWS credentials require signing, Cohere doesn't accept
+                // chat completions shaped requests, Azure's path depends on
+                // the deployment, and Vertex's on the project and requires
+                // an access token
+                if matches!(
+                    provider,
+                    InferenceProvider::Bedrock
+                        | InferenceProvider::Cohere
+                        | InferenceProvider::AzureOpenAI
+                        | InferenceProvider::VertexAI
+                ) {
+                    return ProbeStatus::Unknown;
+                }
+                let Some(model) = provider_config.models.first() else {
+                    return ProbeStatus::Unknown;
+                };
+                let Ok(url) =
+                    provider_config.base_url.join(completion_path(provider))
+                else {
+                    return ProbeStatus::Unknown;
+                };
+                client.as_ref().post(url).json(&json!({
+                    "model": model.to_string(),
+                    "max_tokens": 1,
+                    "messages": [
+                        {
+                            "role": "user",
+                            "content": "ping"
+                        }
+                    ]
+                }))
+            }
+            ProbeKind::Models => {
+                // AWS credentials require signing, Azure's model list
+                // requires an api version, and Vertex requires an access
+                // token
+                if matches!(
+                    provider,
+                    InferenceProvider::Bedrock
+                        | InferenceProvider::AzureOpenAI
+                        | InferenceProvider::VertexAI
+                ) {
+                    return ProbeStatus::Unknown;
+                }
+                let Some(url) = models_url(&self.app_state, provider) else {
+                    return ProbeStatus::Unknown;
+                };
+                client.as_ref().get(url)
+            }
+        };
+
+        let response = match request.timeout(config.timeout).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                debug!(provider = %provider, error = %e, "provider probe failed");
+                return ProbeStatus::Failure;
+            }
+        };
+        let status = response.status();
+        debug!(provider = %provider, status = %status, "probed provider");
+        probe_status(config.kind, status)
+    }
+}
+
+fn completion_path(provider: &InferenceProvider) -> &'static str {
+    match provider {
+        InferenceProvider::Anthropic => "v1/messages",
+        InferenceProvider::GoogleGemini => "v1beta/openai/chat/completions",
+        _ => "v1/chat/completions",
+    }
+}
+
+fn probe_status(kind: ProbeKind, status: StatusCode) -> ProbeStatus {
+    match kind {
+        // any response that isn't a server error means the provider is up
+        ProbeKind::Http if status.is_server_error() => ProbeStatus::Failure,
+        ProbeKind::Http => ProbeStatus::Success,
+        // rate limits are handled by the rate limit monitor
+        ProbeKind::Completion | ProbeKind::Models
+            if status == StatusCode::TOO_MANY_REQUESTS =>
+        {
+            ProbeStatus::Unknown
+        }
+        ProbeKind::Completion | ProbeKind::Models if status.is_success() => {
+            ProbeStatus::Success
+        }
+        ProbeKind::Completion | ProbeKind::Models => ProbeStatus::Failure,
+    }
+}
+
+impl meltdown::Service for ProbeMonitor {
+    type Future = BoxFuture<'static, Result<(), RuntimeError>>;
+
+    fn run(self, mut token: Token) -> Self::Future {
+        Box::pin(async move {
+            tokio::select! {
+                result = self.run_forever() => {
+                    if let Err(e) = result {
+                        error!(name = "provider-probe-monitor-task", error = ?e, "Monitor encountered error, shutting down");
+                    } else {
+                        debug!(name = "provider-probe-monitor-task", "Monitor shut down successfully");
+                    }
+                    token.trigger();
+                }
+                () = &mut token => {
+                    debug!(name = "provider-probe-monitor-task", "task shut down successfully");
+                }
+            }
+            Ok(())
+        })
+    }
+}
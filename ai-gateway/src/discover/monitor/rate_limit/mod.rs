@@ -1,4 +1,5 @@
 mod provider;
+mod readmission;
 pub use self::provider::{
     ProviderRateLimitMonitor, RateLimitMonitor, RateLimitMonitorMap,
 };
@@ -27,6 +27,10 @@ use crate::{
         model::{
             key::Key as ModelKey, weighted_key::WeightedKey as ModelWeightedKey,
         },
+        monitor::{
+            pool::RemovalReason,
+            rate_limit::readmission::{RampUpStep, Readmission},
+        },
         provider::{
             key::Key as ProviderKey,
             weighted_key::WeightedKey as ProviderWeightedKey,
@@ -148,10 +152,61 @@ impl<K> ProviderMonitorInner<K> {
     }
 }
 
+impl<K: Clone> ProviderMonitorInner<K> {
+    /// Replaces a key of a weighted balancer with the same key at another
+    /// weight. Weighted keys are equal whatever their weight, so the key is
+    /// removed before it's inserted again.
+    async fn replace(
+        &self,
+        key: &K,
+        reweighted: K,
+        api_endpoint: &ApiEndpoint,
+    ) -> Result<(), RuntimeError> {
+        let service = Dispatcher::new(
+            self.app_state.clone(),
+            &self.router_id,
+            &self.router_config,
+            api_endpoint.provider(),
+        )
+        .await
+        .inspect_err(|e| {
+            error!(
+                error = ?e,
+                provider = ?api_endpoint.provider(),
+                router_id = ?self.router_id,
+                "Failed to create dispatcher for reweighted provider"
+            );
+        })?;
+        if let Err(e) = self.tx.send(Change::Remove(key.clone())).await {
+            error!(
+                error = ?e,
+                "Failed to send remove event for reweighted provider"
+            );
+        }
+        self.tx
+            .send(Change::Insert(reweighted, service))
+            .await
+            .map_err(|e| {
+                error!(
+                    error = ?e,
+                    router_id = ?self.router_id,
+                    "Failed to send insert event for reweighted provider"
+                );
+                RuntimeError::ChannelSendFailed
+            })
+    }
+}
+
 impl ProviderMonitorInner<ProviderKey> {
-    fn create_key_for_endpoint(api_endpoint: &ApiEndpoint) -> ProviderKey {
+    fn create_key_for_endpoint(
+        &self,
+        api_endpoint: &ApiEndpoint,
+    ) -> ProviderKey {
         let provider = api_endpoint.provider();
-        let endpoint_type = api_endpoint.endpoint_type();
+        let endpoint_type = self
+            .router_config
+            .load_balance
+            .balanced_endpoint_type(api_endpoint.endpoint_type());
         ProviderKey::new(provider, endpoint_type)
     }
 
@@ -166,12 +221,16 @@ impl ProviderMonitorInner<ProviderKey> {
         let mut pending_restores: FuturesUnordered<
             ProviderRestore<ProviderKey>,
         > = FuturesUnordered::new();
+        let mut readmission = Readmission::new(
+            self.app_state.config().discover.monitor.rate_limit.clone(),
+            self.app_state.0.clock.clone(),
+        );
 
         loop {
             tokio::select! {
                 // Handle incoming rate limit events
                 Some(event) = rx.recv() => {
-                    let key = Self::create_key_for_endpoint(&event.api_endpoint);
+                    let key = self.create_key_for_endpoint(&event.api_endpoint);
                     if rate_limited_providers.contains(&key) {
                         info!(
                             provider = ?event.api_endpoint.provider(),
@@ -191,16 +250,27 @@ impl ProviderMonitorInner<ProviderKey> {
                         }
 
 
-                        let duration = Duration::from_secs(
-                            event.retry_after_seconds.unwrap_or(DEFAULT_WAIT_SECONDS)
-                        ) + RATE_LIMIT_BUFFER_SECONDS;
+                        let duration = readmission.removal_duration(
+                            &key,
+                            Duration::from_secs(
+                                event
+                                    .retry_after_seconds
+                                    .unwrap_or(DEFAULT_WAIT_SECONDS),
+                            ) + RATE_LIMIT_BUFFER_SECONDS,
+                        );
 
                         let restore = ProviderRestore {
                             key: Some(key.clone()),
                             api_endpoint: event.api_endpoint.clone(),
-                            timer: tokio::time::sleep(duration),
+                            timer: self.app_state.0.clock.sleep(duration),
                         };
                         pending_restores.push(restore);
+                        self.app_state.0.provider_pools.remove(
+                            &self.router_id,
+                            &event.api_endpoint.provider(),
+                            RemovalReason::RateLimited,
+                            Some(duration),
+                        );
                         rate_limited_providers.insert(key);
                         info!(
                             provider = ?event.api_endpoint.provider(),
@@ -212,6 +282,9 @@ impl ProviderMonitorInner<ProviderKey> {
                 }
                 // Handle provider restoration
                 Some((key, api_endpoint)) = pending_restores.next() => {
+                    if self.tx.is_closed() {
+                        break;
+                    }
                     info!(
                         provider = ?api_endpoint.provider(),
                         endpoint = ?api_endpoint.endpoint_type(),
@@ -240,6 +313,17 @@ impl ProviderMonitorInner<ProviderKey> {
                         RuntimeError::ChannelSendFailed
                     })?;
                     rate_limited_providers.remove(&key);
+                    readmission.restored(&key);
+                    self.app_state
+                        .0
+                        .provider_pools
+                        .restore(&self.router_id, &api_endpoint.provider());
+                }
+                // The balancer is dropped when the router is rebuilt or
+                // removed, the rebuilt router is watched by its own monitor
+                () = self.tx.closed() => {
+                    info!(router_id = ?self.router_id, "Balancer closed, shutting down stale rate limit monitor");
+                    break;
                 }
                 // Channel closed - shutdown gracefully
                 else => {
@@ -258,7 +342,10 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
         api_endpoint: &ApiEndpoint,
     ) -> Result<ProviderWeightedKey, InternalError> {
         let provider = api_endpoint.provider();
-        let endpoint_type = api_endpoint.endpoint_type();
+        let endpoint_type = self
+            .router_config
+            .load_balance
+            .balanced_endpoint_type(api_endpoint.endpoint_type());
 
         let Some(balance_config) =
             self.router_config.load_balance.0.get(&endpoint_type)
@@ -272,7 +359,7 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
         };
 
         match balance_config {
-            BalanceConfigInner::ProviderWeighted { providers } => {
+            BalanceConfigInner::ProviderWeighted { providers, .. } => {
                 for target in providers {
                     if target.provider == provider {
                         let weight = Weight::from(
@@ -285,6 +372,10 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
                             provider,
                             endpoint_type,
                             weight,
+                        )
+                        .with_schedule(
+                            target.schedule.clone(),
+                            self.app_state.0.clock.clone(),
                         ));
                     }
                 }
@@ -300,6 +391,50 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
         }
     }
 
+    /// Reduces the weight of a provider whose rate limit headers report that
+    /// it's running out of capacity, and restores its configured weight once
+    /// they report that it has recovered.
+    async fn reweight(
+        &self,
+        key: ProviderWeightedKey,
+        api_endpoint: &ApiEndpoint,
+        remaining_capacity: f64,
+        reduced_providers: &mut HashSet<ProviderWeightedKey>,
+    ) -> Result<(), RuntimeError> {
+        let Some(config) =
+            &self.app_state.config().discover.monitor.rate_limit_headers
+        else {
+            return Ok(());
+        };
+        let is_low =
+            remaining_capacity < config.threshold.to_f64().unwrap_or(0.0);
+        let weight = match (is_low, reduced_providers.contains(&key)) {
+            (true, false) => Weight::from(
+                f64::from(key.weight)
+                    * config.reduced_weight.to_f64().unwrap_or(1.0),
+            ),
+            (false, true) => key.weight,
+            _ => return Ok(()),
+        };
+        info!(
+            provider = ?api_endpoint.provider(),
+            endpoint_type = ?key.endpoint_type,
+            router_id = ?self.router_id,
+            remaining_capacity,
+            weight = f64::from(weight),
+            "Reweighting provider by its remaining rate limit capacity"
+        );
+
+        self.replace(&key, key.with_weight(weight), api_endpoint)
+            .await?;
+        if is_low {
+            reduced_providers.insert(key);
+        } else {
+            reduced_providers.remove(&key);
+        }
+        Ok(())
+    }
+
     async fn monitor(
         self,
         mut rx: Receiver<RateLimitEvent>,
@@ -311,12 +446,37 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
         let mut pending_restores: FuturesUnordered<
             ProviderRestore<ProviderWeightedKey>,
         > = FuturesUnordered::new();
+        let mut readmission = Readmission::new(
+            self.app_state.config().discover.monitor.rate_limit.clone(),
+            self.app_state.0.clock.clone(),
+        );
+        let mut pending_ramp_ups: FuturesUnordered<
+            RampUpStep<ProviderWeightedKey>,
+        > = FuturesUnordered::new();
+        // providers whose weight is reduced while they're running out of
+        // capacity
+        let mut reduced_providers: HashSet<ProviderWeightedKey> =
+            HashSet::default();
 
         loop {
             tokio::select! {
                 // Handle incoming rate limit events
                 Some(event) = rx.recv() => {
                     let key = self.create_key_for_endpoint(&event.api_endpoint)?;
+                    if let Some(remaining_capacity) = event.remaining_capacity {
+                        if !rate_limited_providers.contains_key(&key)
+                            && !readmission.is_ramping_up(&key)
+                        {
+                            self.reweight(
+                                key,
+                                &event.api_endpoint,
+                                remaining_capacity,
+                                &mut reduced_providers,
+                            )
+                            .await?;
+                        }
+                        continue;
+                    }
                     if let std::collections::hash_map::Entry::Vacant(e) = rate_limited_providers.entry(key.clone()) {
                         debug!(
                             provider = ?event.api_endpoint.provider(),
@@ -329,10 +489,17 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
                             error!(error = ?e, "Failed to send remove event for rate-limited provider");
                         }
                         e.insert(Instant::now());
+                        // the provider's weight is ramped up once it's re-added
+                        reduced_providers.remove(&key);
 
-                        let duration = Duration::from_secs(
-                            event.retry_after_seconds.unwrap_or(DEFAULT_WAIT_SECONDS)
-                        ) + RATE_LIMIT_BUFFER_SECONDS;
+                        let duration = readmission.removal_duration(
+                            &key,
+                            Duration::from_secs(
+                                event
+                                    .retry_after_seconds
+                                    .unwrap_or(DEFAULT_WAIT_SECONDS),
+                            ) + RATE_LIMIT_BUFFER_SECONDS,
+                        );
                         info!(
                             provider = ?event.api_endpoint.provider(),
                             endpoint_type = ?event.api_endpoint.endpoint_type(),
@@ -345,9 +512,15 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
                         let restore = ProviderRestore {
                             key: Some(key),
                             api_endpoint: event.api_endpoint.clone(),
-                            timer: tokio::time::sleep(duration),
+                            timer: self.app_state.0.clock.sleep(duration),
                         };
                         pending_restores.push(restore);
+                        self.app_state.0.provider_pools.remove(
+                            &self.router_id,
+                            &event.api_endpoint.provider(),
+                            RemovalReason::RateLimited,
+                            Some(duration),
+                        );
                     } else {
                         info!(
                             provider = ?event.api_endpoint.provider(),
@@ -358,6 +531,9 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
                 }
                 // Handle provider restoration when rate limit expires
                 Some((key, api_endpoint)) = pending_restores.next() => {
+                    if self.tx.is_closed() {
+                        break;
+                    }
                     info!(
                         provider = ?api_endpoint.provider(),
                         endpoint = ?api_endpoint.endpoint_type(),
@@ -365,6 +541,9 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
                         router_id = ?self.router_id,
                         "Re-adding provider to Weighted balancer after rate limit expired"
                     );
+                    let (fraction, ramp_up) =
+                        readmission.ramp_up(&key, api_endpoint.clone());
+                    pending_ramp_ups.extend(ramp_up);
 
                     let service = Dispatcher::new(
                         self.app_state.clone(),
@@ -382,13 +561,39 @@ impl ProviderMonitorInner<ProviderWeightedKey> {
                             "Failed to create dispatcher for recovered provider"
                         );
                     })?;
-                    self.tx.send(Change::Insert(key.clone(), service))
+                    let weight =
+                        Weight::from(f64::from(key.weight) * fraction);
+                    self.tx.send(Change::Insert(key.with_weight(weight), service))
                         .await
                         .map_err(|e| {
                             error!(error = ?e, router_id = ?self.router_id, "Failed to send insert event for recovered provider");
                             RuntimeError::ChannelSendFailed
                         })?;
                     rate_limited_providers.remove(&key);
+                    self.app_state
+                        .0
+                        .provider_pools
+                        .restore(&self.router_id, &api_endpoint.provider());
+                }
+                // Ramp re-added providers up to their configured weight
+                Some(ramp_up) = pending_ramp_ups.next() => {
+                    let (key, api_endpoint) =
+                        (ramp_up.key.clone(), ramp_up.api_endpoint.clone());
+                    if let Some((fraction, next_step)) =
+                        readmission.step(ramp_up)
+                    {
+                        pending_ramp_ups.extend(next_step);
+                        let weight =
+                            Weight::from(f64::from(key.weight) * fraction);
+                        self.replace(&key, key.with_weight(weight), &api_endpoint)
+                            .await?;
+                    }
+                }
+                // The balancer is dropped when the router is rebuilt or
+                // removed, the rebuilt router is watched by its own monitor
+                () = self.tx.closed() => {
+                    info!(router_id = ?self.router_id, "Balancer closed, shutting down stale rate limit monitor");
+                    break;
                 }
                 // Channel closed - shutdown gracefully
                 else => {
@@ -414,7 +619,10 @@ impl ProviderMonitorInner<ModelWeightedKey> {
             );
             return Err(InternalError::Internal);
         };
-        let endpoint_type = event.api_endpoint.endpoint_type();
+        let endpoint_type = self
+            .router_config
+            .load_balance
+            .balanced_endpoint_type(event.api_endpoint.endpoint_type());
         let model_config =
             if let Some(BalanceConfigInner::ModelWeighted { models }) =
                 self.router_config.load_balance.0.get(&endpoint_type)
@@ -456,6 +664,13 @@ impl ProviderMonitorInner<ModelWeightedKey> {
         let mut pending_restores: FuturesUnordered<
             ProviderRestore<ModelWeightedKey>,
         > = FuturesUnordered::new();
+        let mut readmission = Readmission::new(
+            self.app_state.config().discover.monitor.rate_limit.clone(),
+            self.app_state.0.clock.clone(),
+        );
+        let mut pending_ramp_ups: FuturesUnordered<
+            RampUpStep<ModelWeightedKey>,
+        > = FuturesUnordered::new();
 
         loop {
             tokio::select! {
@@ -475,9 +690,14 @@ impl ProviderMonitorInner<ModelWeightedKey> {
                         }
                         e.insert(Instant::now());
 
-                        let duration = Duration::from_secs(
-                            event.retry_after_seconds.unwrap_or(DEFAULT_WAIT_SECONDS)
-                        ) + RATE_LIMIT_BUFFER_SECONDS;
+                        let duration = readmission.removal_duration(
+                            &key,
+                            Duration::from_secs(
+                                event
+                                    .retry_after_seconds
+                                    .unwrap_or(DEFAULT_WAIT_SECONDS),
+                            ) + RATE_LIMIT_BUFFER_SECONDS,
+                        );
                         info!(
                             provider = ?event.api_endpoint.provider(),
                             endpoint_type = ?event.api_endpoint.endpoint_type(),
@@ -490,9 +710,15 @@ impl ProviderMonitorInner<ModelWeightedKey> {
                         let restore = ProviderRestore {
                             key: Some(key),
                             api_endpoint: event.api_endpoint.clone(),
-                            timer: tokio::time::sleep(duration),
+                            timer: self.app_state.0.clock.sleep(duration),
                         };
                         pending_restores.push(restore);
+                        self.app_state.0.provider_pools.remove(
+                            &self.router_id,
+                            &event.api_endpoint.provider(),
+                            RemovalReason::RateLimited,
+                            Some(duration),
+                        );
                     } else {
                         info!(
                             provider = ?event.api_endpoint.provider(),
@@ -503,6 +729,9 @@ impl ProviderMonitorInner<ModelWeightedKey> {
                 }
                 // Handle provider restoration when rate limit expires
                 Some((key, api_endpoint)) = pending_restores.next() => {
+                    if self.tx.is_closed() {
+                        break;
+                    }
                     info!(
                         provider = ?api_endpoint.provider(),
                         endpoint = ?api_endpoint.endpoint_type(),
@@ -510,6 +739,9 @@ impl ProviderMonitorInner<ModelWeightedKey> {
                         router_id = ?self.router_id,
                         "Re-adding provider to Weighted balancer after rate limit expired"
                     );
+                    let (fraction, ramp_up) =
+                        readmission.ramp_up(&key, api_endpoint.clone());
+                    pending_ramp_ups.extend(ramp_up);
 
                     let service = Dispatcher::new(
                         self.app_state.clone(),
@@ -527,13 +759,39 @@ impl ProviderMonitorInner<ModelWeightedKey> {
                             "Failed to create dispatcher for recovered provider"
                         );
                     })?;
-                    self.tx.send(Change::Insert(key.clone(), service))
+                    let weight =
+                        Weight::from(f64::from(key.weight) * fraction);
+                    self.tx.send(Change::Insert(key.with_weight(weight), service))
                         .await
                         .map_err(|e| {
                             error!(error = ?e, router_id = ?self.router_id, "Failed to send insert event for recovered provider");
                             RuntimeError::ChannelSendFailed
                         })?;
                     rate_limited_providers.remove(&key);
+                    self.app_state
+                        .0
+                        .provider_pools
+                        .restore(&self.router_id, &api_endpoint.provider());
+                }
+                // Ramp re-added providers up to their configured weight
+                Some(ramp_up) = pending_ramp_ups.next() => {
+                    let (key, api_endpoint) =
+                        (ramp_up.key.clone(), ramp_up.api_endpoint.clone());
+                    if let Some((fraction, next_step)) =
+                        readmission.step(ramp_up)
+                    {
+                        pending_ramp_ups.extend(next_step);
+                        let weight =
+                            Weight::from(f64::from(key.weight) * fraction);
+                        self.replace(&key, key.with_weight(weight), &api_endpoint)
+                            .await?;
+                    }
+                }
+                // The balancer is dropped when the router is rebuilt or
+                // removed, the rebuilt router is watched by its own monitor
+                () = self.tx.closed() => {
+                    info!(router_id = ?self.router_id, "Balancer closed, shutting down stale rate limit monitor");
+                    break;
                 }
                 // Channel closed - shutdown gracefully
                 else => {
@@ -559,7 +817,10 @@ impl ProviderMonitorInner<ModelKey> {
             );
             return Err(InternalError::Internal);
         };
-        let endpoint_type = event.api_endpoint.endpoint_type();
+        let endpoint_type = self
+            .router_config
+            .load_balance
+            .balanced_endpoint_type(event.api_endpoint.endpoint_type());
         Ok(ModelKey::new(model_id, endpoint_type))
     }
 
@@ -573,6 +834,10 @@ impl ProviderMonitorInner<ModelKey> {
             HashMap::default();
         let mut pending_restores: FuturesUnordered<ProviderRestore<ModelKey>> =
             FuturesUnordered::new();
+        let mut readmission = Readmission::new(
+            self.app_state.config().discover.monitor.rate_limit.clone(),
+            self.app_state.0.clock.clone(),
+        );
 
         loop {
             tokio::select! {
@@ -592,9 +857,14 @@ impl ProviderMonitorInner<ModelKey> {
                         }
                         e.insert(Instant::now());
 
-                        let duration = Duration::from_secs(
-                            event.retry_after_seconds.unwrap_or(DEFAULT_WAIT_SECONDS)
-                        ) + RATE_LIMIT_BUFFER_SECONDS;
+                        let duration = readmission.removal_duration(
+                            &key,
+                            Duration::from_secs(
+                                event
+                                    .retry_after_seconds
+                                    .unwrap_or(DEFAULT_WAIT_SECONDS),
+                            ) + RATE_LIMIT_BUFFER_SECONDS,
+                        );
                         info!(
                             provider = ?event.api_endpoint.provider(),
                             endpoint_type = ?event.api_endpoint.endpoint_type(),
@@ -607,9 +877,15 @@ impl ProviderMonitorInner<ModelKey> {
                         let restore = ProviderRestore {
                             key: Some(key),
                             api_endpoint: event.api_endpoint.clone(),
-                            timer: tokio::time::sleep(duration),
+                            timer: self.app_state.0.clock.sleep(duration),
                         };
                         pending_restores.push(restore);
+                        self.app_state.0.provider_pools.remove(
+                            &self.router_id,
+                            &event.api_endpoint.provider(),
+                            RemovalReason::RateLimited,
+                            Some(duration),
+                        );
                     } else {
                         info!(
                             provider = ?event.api_endpoint.provider(),
@@ -620,6 +896,9 @@ impl ProviderMonitorInner<ModelKey> {
                 }
                 // Handle provider restoration when rate limit expires
                 Some((key, api_endpoint)) = pending_restores.next() => {
+                    if self.tx.is_closed() {
+                        break;
+                    }
                     info!(
                         provider = ?api_endpoint.provider(),
                         endpoint = ?api_endpoint.endpoint_type(),
@@ -651,6 +930,17 @@ impl ProviderMonitorInner<ModelKey> {
                             RuntimeError::ChannelSendFailed
                         })?;
                     rate_limited_providers.remove(&key);
+                    readmission.restored(&key);
+                    self.app_state
+                        .0
+                        .provider_pools
+                        .restore(&self.router_id, &api_endpoint.provider());
+                }
+                // The balancer is dropped when the router is rebuilt or
+                // removed, the rebuilt router is watched by its own monitor
+                () = self.tx.closed() => {
+                    info!(router_id = ?self.router_id, "Balancer closed, shutting down stale rate limit monitor");
+                    break;
                 }
                 // Channel closed - shutdown gracefully
                 else => {
@@ -0,0 +1,273 @@
+//! How providers removed for being rate limited are re-added, see
+//! [`RateLimitMonitorConfig`].
+//!
+//! A provider re-added at its full weight as soon as its rate limit elapses
+//! is immediately sent its full share of requests again, and is often rate
+//! limited again straight away. Re-added providers instead ramp up from a
+//! fraction of their weight, and providers that keep being rate limited
+//! shortly after being re-added are removed for exponentially longer.
+use std::{hash::Hash, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use rust_decimal::prelude::ToPrimitive;
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{
+    config::monitor::RateLimitMonitorConfig, endpoints::ApiEndpoint,
+    utils::clock::Clock,
+};
+
+/// The number of times a re-added provider's weight is increased until it's
+/// back to its configured weight.
+const RAMP_UP_STEPS: u32 = 5;
+
+/// A step of a key's ramp up, which completes once the key's weight should
+/// be increased.
+pub(super) type RampUpStep<K> = BoxFuture<'static, RampUp<K>>;
+
+#[derive(Debug)]
+pub(super) struct RampUp<K> {
+    pub(super) key: K,
+    pub(super) api_endpoint: ApiEndpoint,
+    step: u32,
+    /// Identifies the ramp up, so that the steps of a ramp up interrupted by
+    /// the key being rate limited again are ignored.
+    id: u64,
+}
+
+#[derive(Debug, Default)]
+struct Removals {
+    /// The removals of the key since it was last re-added for longer than
+    /// the flapping window, not counting the first.
+    consecutive: u32,
+    restored_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub(super) struct Readmission<K> {
+    config: RateLimitMonitorConfig,
+    clock: Clock,
+    removals: HashMap<K, Removals>,
+    /// The id of the ramp up of each key currently ramping up.
+    ramp_ups: HashMap<K, u64>,
+    next_ramp_up_id: u64,
+}
+
+impl<K> Readmission<K>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+{
+    pub(super) fn new(config: RateLimitMonitorConfig, clock: Clock) -> Self {
+        Self {
+            config,
+            clock,
+            removals: HashMap::default(),
+            ramp_ups: HashMap::default(),
+            next_ramp_up_id: 0,
+        }
+    }
+
+    /// How long a rate limited key is removed for: `retry_after`, extended
+    /// by a jittered exponential backoff if the key keeps being rate limited
+    /// shortly after being re-added.
+    pub(super) fn removal_duration(
+        &mut self,
+        key: &K,
+        retry_after: Duration,
+    ) -> Duration {
+        self.ramp_ups.remove(key);
+        let now = self.clock.now();
+        let removals = self.removals.entry(key.clone()).or_default();
+        let is_flapping = removals.restored_at.take().is_some_and(|at| {
+            (now - at).to_std().unwrap_or_default()
+                <= self.config.flapping_window
+        });
+        removals.consecutive = if is_flapping {
+            removals.consecutive.saturating_add(1)
+        } else {
+            0
+        };
+        let Some(exponent) = removals.consecutive.checked_sub(1) else {
+            return retry_after;
+        };
+        let backoff = self
+            .config
+            .backoff
+            .saturating_mul(2_u32.saturating_pow(exponent))
+            .min(self.config.max_backoff);
+        // half of the backoff is random, so that providers rate limited
+        // together aren't all re-added together
+        let jitter = backoff.mul_f64(rand::random::<f64>()) / 2;
+        retry_after + backoff / 2 + jitter
+    }
+
+    /// Records that the key was re-added.
+    pub(super) fn restored(&mut self, key: &K) {
+        self.removals.entry(key.clone()).or_default().restored_at =
+            Some(self.clock.now());
+    }
+
+    /// Records that the key of a weighted balancer was re-added, returning
+    /// the fraction of its weight it's re-added with, and the first step of
+    /// its ramp up if it doesn't start at its full weight.
+    pub(super) fn ramp_up(
+        &mut self,
+        key: &K,
+        api_endpoint: ApiEndpoint,
+    ) -> (f64, Option<RampUpStep<K>>) {
+        self.restored(key);
+        let initial_weight = self.config.initial_weight.to_f64().unwrap_or(1.0);
+        if initial_weight >= 1.0 || self.config.ramp_up.is_zero() {
+            return (1.0, None);
+        }
+        let id = self.next_ramp_up_id;
+        self.next_ramp_up_id += 1;
+        self.ramp_ups.insert(key.clone(), id);
+        let ramp_up = RampUp {
+            key: key.clone(),
+            api_endpoint,
+            step: 0,
+            id,
+        };
+        (initial_weight, Some(self.next_step(ramp_up)))
+    }
+
+    /// The fraction of its weight the key is increased to by the ramp up
+    /// step and the step after it, or `None` if the ramp up was interrupted.
+    pub(super) fn step(
+        &mut self,
+        ramp_up: RampUp<K>,
+    ) -> Option<(f64, Option<RampUpStep<K>>)> {
+        if self.ramp_ups.get(&ramp_up.key) != Some(&ramp_up.id) {
+            return None;
+        }
+        if ramp_up.step >= RAMP_UP_STEPS {
+            self.ramp_ups.remove(&ramp_up.key);
+            return Some((1.0, None));
+        }
+        let initial_weight = self.config.initial_weight.to_f64().unwrap_or(1.0);
+        let fraction = initial_weight
+            + (1.0 - initial_weight) * f64::from(ramp_up.step)
+                / f64::from(RAMP_UP_STEPS);
+        Some((fraction, Some(self.next_step(ramp_up))))
+    }
+
+    /// Whether the key is ramping up, during which its weight is managed by
+    /// its ramp up.
+    pub(super) fn is_ramping_up(&self, key: &K) -> bool {
+        self.ramp_ups.contains_key(key)
+    }
+
+    fn next_step(&self, mut ramp_up: RampUp<K>) -> RampUpStep<K> {
+        let delay = self.clock.sleep(self.config.ramp_up / RAMP_UP_STEPS);
+        ramp_up.step += 1;
+        Box::pin(async move {
+            delay.await;
+            ramp_up
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::{endpoints::openai::OpenAI, tests::TestDefault};
+
+    fn readmission() -> Readmission<&'static str> {
+        Readmission::new(
+            RateLimitMonitorConfig {
+                initial_weight: Decimal::new(2, 1),
+                ramp_up: Duration::from_millis(50),
+                flapping_window: Duration::from_secs(60),
+                backoff: Duration::from_secs(10),
+                max_backoff: Duration::from_secs(25),
+            },
+            Clock::default(),
+        )
+    }
+
+    fn api_endpoint() -> ApiEndpoint {
+        ApiEndpoint::OpenAI(OpenAI::chat_completions())
+    }
+
+    #[test]
+    fn flapping_providers_are_removed_for_longer() {
+        let mut readmission = readmission();
+        let retry_after = Duration::from_secs(2);
+        assert_eq!(
+            readmission.removal_duration(&"openai", retry_after),
+            retry_after
+        );
+        // only removals shortly after being re-added are backed off
+        assert_eq!(
+            readmission.removal_duration(&"openai", retry_after),
+            retry_after
+        );
+
+        for (min, max) in [(7, 12), (12, 22), (14, 27), (14, 27)] {
+            readmission.restored(&"openai");
+            let duration = readmission.removal_duration(&"openai", retry_after);
+            assert!(
+                (Duration::from_secs(min)..=Duration::from_secs(max))
+                    .contains(&duration),
+                "{duration:?} should be between {min}s and {max}s"
+            );
+        }
+        // other providers are backed off independently
+        assert_eq!(
+            readmission.removal_duration(&"anthropic", retry_after),
+            retry_after
+        );
+
+        // and providers removed long after being re-added aren't
+        readmission.clock.set(Utc::now());
+        readmission.restored(&"openai");
+        readmission.clock.advance(Duration::from_secs(61));
+        assert_eq!(
+            readmission.removal_duration(&"openai", retry_after),
+            retry_after
+        );
+    }
+
+    #[tokio::test]
+    async fn restored_providers_ramp_up_to_their_weight() {
+        let mut readmission = readmission();
+        let (mut fraction, mut next_step) =
+            readmission.ramp_up(&"openai", api_endpoint());
+        let mut fractions = vec![fraction];
+        while let Some(step) = next_step {
+            assert!(readmission.is_ramping_up(&"openai"));
+            (fraction, next_step) = readmission.step(step.await).unwrap();
+            fractions.push(fraction);
+        }
+        assert_eq!(fractions.len(), 6);
+        assert!(fractions.is_sorted());
+        assert!((fractions[0] - 0.2).abs() < f64::EPSILON);
+        assert!((fractions[5] - 1.0).abs() < f64::EPSILON);
+        assert!(!readmission.is_ramping_up(&"openai"));
+    }
+
+    #[tokio::test]
+    async fn rate_limits_interrupt_ramp_ups() {
+        let mut readmission = readmission();
+        let (_, first_step) = readmission.ramp_up(&"openai", api_endpoint());
+        readmission.removal_duration(&"openai", Duration::from_secs(2));
+        assert!(!readmission.is_ramping_up(&"openai"));
+        let _ramp_up = readmission.ramp_up(&"openai", api_endpoint());
+        // the step of the interrupted ramp up is ignored
+        assert!(readmission.step(first_step.unwrap().await).is_none());
+        assert!(readmission.is_ramping_up(&"openai"));
+
+        let config = RateLimitMonitorConfig {
+            initial_weight: Decimal::ONE,
+            ..RateLimitMonitorConfig::test_default()
+        };
+        let (fraction, next_step) = Readmission::new(config, Clock::default())
+            .ramp_up(&"openai", api_endpoint());
+        assert!((fraction - 1.0).abs() < f64::EPSILON);
+        assert!(next_step.is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    hash::{Hash, Hasher},
     sync::Arc,
     task::{Context, Poll},
 };
@@ -13,7 +14,10 @@ use weighted_balance::weight::{HasWeight, Weight, WeightedDiscover};
 
 use crate::{
     app_state::AppState,
-    config::{balance::BalanceConfigInner, router::RouterConfig},
+    config::{
+        balance::{BalanceConfigInner, ProviderSchedule},
+        router::RouterConfig,
+    },
     discover::{
         ServiceMap,
         dispatcher::{DispatcherDiscovery, factory::DispatcherDiscoverFactory},
@@ -22,13 +26,37 @@ use crate::{
     endpoints::EndpointType,
     error::init::InitError,
     types::{provider::InferenceProvider, router::RouterId},
+    utils::clock::Clock,
 };
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// Keys are identified by their provider and endpoint type alone, so that a
+/// provider's weight can be changed by replacing its key, and removing the
+/// key removes the provider whatever its current weight.
+#[derive(Debug, Clone)]
 pub struct WeightedKey {
     pub provider: InferenceProvider,
     pub endpoint_type: EndpointType,
     pub weight: Weight,
+    /// When the provider's weight is zero, checked against `clock` whenever
+    /// the balancer samples a provider.
+    pub schedule: Option<ProviderSchedule>,
+    pub clock: Clock,
+}
+
+impl PartialEq for WeightedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.provider == other.provider
+            && self.endpoint_type == other.endpoint_type
+    }
+}
+
+impl Eq for WeightedKey {}
+
+impl Hash for WeightedKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.provider.hash(state);
+        self.endpoint_type.hash(state);
+    }
 }
 
 impl WeightedKey {
@@ -42,6 +70,31 @@ impl WeightedKey {
             provider,
             endpoint_type,
             weight,
+            schedule: None,
+            clock: Clock::default(),
+        }
+    }
+
+    /// The same key, weighted zero during the windows of `schedule`.
+    #[must_use]
+    pub fn with_schedule(
+        self,
+        schedule: Option<ProviderSchedule>,
+        clock: Clock,
+    ) -> Self {
+        Self {
+            schedule,
+            clock,
+            ..self
+        }
+    }
+
+    /// The same key at another weight.
+    #[must_use]
+    pub fn with_weight(&self, weight: Weight) -> Self {
+        Self {
+            weight,
+            ..self.clone()
         }
     }
 }
@@ -58,7 +111,9 @@ impl DispatcherDiscovery<WeightedKey> {
             router_config.load_balance.as_ref()
         {
             let weighted_balance_targets = match balance_config {
-                BalanceConfigInner::ProviderWeighted { providers } => providers,
+                BalanceConfigInner::ProviderWeighted { providers, .. } => {
+                    providers
+                }
                 BalanceConfigInner::ModelWeighted { .. } => {
                     return Err(InitError::InvalidBalancer(
                         "Model weighted balancer not supported for provider \
@@ -79,6 +134,26 @@ impl DispatcherDiscovery<WeightedKey> {
                             .to_string(),
                     ));
                 }
+                BalanceConfigInner::Priority { .. } => {
+                    return Err(InitError::InvalidBalancer(
+                        "Priority balancer not supported for weighted \
+                         discovery"
+                            .to_string(),
+                    ));
+                }
+                BalanceConfigInner::Canary { .. } => {
+                    return Err(InitError::InvalidBalancer(
+                        "Canary balancer not supported for weighted discovery"
+                            .to_string(),
+                    ));
+                }
+                BalanceConfigInner::ConsistentHash { .. } => {
+                    return Err(InitError::InvalidBalancer(
+                        "Consistent hash balancer not supported for weighted \
+                         discovery"
+                            .to_string(),
+                    ));
+                }
             };
             for target in weighted_balance_targets {
                 let weight =
@@ -89,6 +164,10 @@ impl DispatcherDiscovery<WeightedKey> {
                     target.provider.clone(),
                     *endpoint_type,
                     weight,
+                )
+                .with_schedule(
+                    target.schedule.clone(),
+                    app_state.0.clock.clone(),
                 );
                 let dispatcher = Dispatcher::new(
                     app_state.clone(),
@@ -112,7 +191,15 @@ impl DispatcherDiscovery<WeightedKey> {
 
 impl HasWeight for WeightedKey {
     fn weight(&self) -> Weight {
-        self.weight
+        let is_excluded = self
+            .schedule
+            .as_ref()
+            .is_some_and(|schedule| schedule.is_excluded(self.clock.now()));
+        if is_excluded {
+            Weight::MIN
+        } else {
+            self.weight
+        }
     }
 }
 
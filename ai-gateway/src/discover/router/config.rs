@@ -7,6 +7,8 @@ use std::{
 
 use futures::Stream;
 use pin_project_lite::pin_project;
+use tokio::sync::mpsc::Receiver;
+use tokio_stream::wrappers::ReceiverStream;
 use tower::discover::Change;
 
 use crate::{
@@ -15,16 +17,22 @@ use crate::{
 };
 
 pin_project! {
-    /// Reads available routers from the config file
+    /// Reads available routers from the config file, and then applies any
+    /// changes sent when the config is hot reloaded.
     #[derive(Debug)]
     pub struct ConfigDiscovery {
         #[pin]
         initial: ServiceMap<RouterId, Router>,
+        #[pin]
+        events: Option<ReceiverStream<Change<RouterId, Router>>>,
     }
 }
 
 impl ConfigDiscovery {
-    pub async fn new(app_state: &AppState) -> Result<Self, InitError> {
+    pub async fn new(
+        app_state: &AppState,
+        rx: Option<Receiver<Change<RouterId, Router>>>,
+    ) -> Result<Self, InitError> {
         let mut service_map: HashMap<RouterId, Router> = HashMap::new();
         for (router_id, router_config) in app_state.0.config.routers.as_ref() {
             let key = router_id.clone();
@@ -40,6 +48,7 @@ impl ConfigDiscovery {
         tracing::debug!("Created config router discovery");
         Ok(Self {
             initial: ServiceMap::new(service_map),
+            events: rx.map(ReceiverStream::new),
         })
     }
 }
@@ -57,7 +66,14 @@ impl Stream for ConfigDiscovery {
             return handle_change(change);
         }
 
-        Poll::Ready(None)
+        match this.events.as_mut().as_pin_mut() {
+            Some(events) => match events.poll_next(ctx) {
+                Poll::Ready(Some(change)) => handle_change(change),
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(None) => Poll::Ready(None),
+            },
+            None => Poll::Ready(None),
+        }
     }
 }
 
@@ -41,7 +41,7 @@ impl RouterDiscovery {
     ) -> Result<Self, InitError> {
         match app_state.0.config.deployment_target {
             DeploymentTarget::Sidecar => Ok(Self::Config {
-                inner: ConfigDiscovery::new(app_state).await?,
+                inner: ConfigDiscovery::new(app_state, rx).await?,
             }),
             DeploymentTarget::Cloud => {
                 let rx = rx.ok_or(InitError::RouterRxNotConfigured)?;
@@ -2,3 +2,4 @@ pub mod cloud;
 pub mod config;
 pub mod discover;
 pub mod factory;
+pub mod reload;
@@ -0,0 +1,221 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures::future::BoxFuture;
+use meltdown::Token;
+use opentelemetry::KeyValue;
+use tokio::signal::unix::{SignalKind, signal};
+use tower::discover::Change;
+use tracing::{error, info};
+
+use crate::{
+    app_state::AppState,
+    config::{
+        Config,
+        router::{RouterConfig, RouterConfigs},
+    },
+    error::{init::InitError, runtime::RuntimeError},
+    router::service::Router,
+    types::router::RouterId,
+};
+
+/// How often the config file is checked for modifications.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The routers affected by a reload.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReloadSummary {
+    pub added: Vec<RouterId>,
+    pub updated: Vec<RouterId>,
+    pub removed: Vec<RouterId>,
+}
+
+impl ReloadSummary {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.removed.is_empty()
+    }
+}
+
+/// Atomically swap in a new set of [`RouterConfigs`].
+///
+/// Only routers whose config changed are rebuilt, so unchanged routers keep
+/// their cache, balancer and monitor state. Requests that are already
+/// in-flight hold onto the previous router service and complete on the old
+/// config.
+///
+/// All new routers are built before any change is published, so an invalid
+/// config leaves the running routers untouched.
+pub async fn reload_routers(
+    app_state: &AppState,
+    new_configs: RouterConfigs,
+) -> Result<ReloadSummary, InitError> {
+    let result = reload_routers_inner(app_state, new_configs).await;
+    let status = if result.is_ok() { "success" } else { "error" };
+    app_state
+        .0
+        .metrics
+        .config_reloads
+        .add(1, &[KeyValue::new("status", status)]);
+    result
+}
+
+/// Rebuild a single router with a new config, leaving every other router
+/// untouched. Requests in-flight on the previous router complete on the old
+/// config, see [`reload_routers`].
+pub async fn reload_router(
+    app_state: &AppState,
+    router_id: RouterId,
+    router_config: RouterConfig,
+) -> Result<ReloadSummary, InitError> {
+    router_config.validate()?;
+    let mut configs = app_state.0.router_configs.read().await.clone();
+    configs.as_mut().insert(router_id, router_config);
+    reload_routers(app_state, configs).await
+}
+
+async fn reload_routers_inner(
+    app_state: &AppState,
+    new_configs: RouterConfigs,
+) -> Result<ReloadSummary, InitError> {
+    let tx = app_state
+        .get_router_tx()
+        .await
+        .ok_or(InitError::RouterTxNotConfigured)?;
+    let mut current_configs = app_state.0.router_configs.write().await;
+
+    let mut summary = ReloadSummary::default();
+    let mut changes = Vec::new();
+    for (router_id, router_config) in new_configs.iter() {
+        let is_new = match current_configs.get(router_id) {
+            Some(current) if current == router_config => continue,
+            Some(_) => false,
+            None => true,
+        };
+        let router = Router::new(
+            router_id.clone(),
+            Arc::new(router_config.clone()),
+            app_state.clone(),
+        )
+        .await?;
+        changes.push(Change::Insert(router_id.clone(), router));
+        if is_new {
+            summary.added.push(router_id.clone());
+        } else {
+            summary.updated.push(router_id.clone());
+        }
+    }
+    for router_id in current_configs.keys() {
+        if !new_configs.contains_key(router_id) {
+            changes.push(Change::Remove(router_id.clone()));
+            summary.removed.push(router_id.clone());
+        }
+    }
+
+    for change in changes {
+        tx.send(change)
+            .await
+            .map_err(|_| InitError::RouterTxNotConfigured)?;
+    }
+    *current_configs = new_configs;
+
+    info!(
+        added = ?summary.added,
+        updated = ?summary.updated,
+        removed = ?summary.removed,
+        "reloaded router configs"
+    );
+    Ok(summary)
+}
+
+/// Watches the config file and reloads the router configs when it changes,
+/// or when the process receives `SIGHUP`.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    app_state: AppState,
+    config_path: Option<PathBuf>,
+    poll_interval: Duration,
+}
+
+impl ConfigWatcher {
+    #[must_use]
+    pub fn new(app_state: AppState, config_path: Option<PathBuf>) -> Self {
+        Self {
+            app_state,
+            config_path,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    async fn modified_at(&self) -> Option<SystemTime> {
+        let path = self.config_path.as_ref()?;
+        tokio::fs::metadata(path).await.ok()?.modified().ok()
+    }
+
+    async fn reload(&self) {
+        let config_path = self.config_path.clone();
+        let config = match tokio::task::spawn_blocking(move || {
+            Config::try_read(config_path)
+        })
+        .await
+        {
+            Ok(Ok(config)) => config,
+            Ok(Err(e)) => {
+                error!(error = %e, "failed to read config, skipping reload");
+                return;
+            }
+            Err(e) => {
+                error!(error = %e, "config read panicked, skipping reload");
+                return;
+            }
+        };
+        if let Err(e) =
+            config.validate_with_provider_keys(&self.app_state.0.provider_keys)
+        {
+            error!(error = %e, "invalid config, skipping reload");
+            return;
+        }
+        if let Err(e) = reload_routers(&self.app_state, config.routers).await {
+            error!(error = %e, "failed to reload router configs");
+        }
+    }
+}
+
+impl meltdown::Service for ConfigWatcher {
+    type Future = BoxFuture<'static, Result<(), RuntimeError>>;
+
+    fn run(self, mut token: Token) -> Self::Future {
+        Box::pin(async move {
+            let mut sighup =
+                signal(SignalKind::hangup()).map_err(RuntimeError::Signal)?;
+            let mut last_modified = self.modified_at().await;
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let modified = self.modified_at().await;
+                        if modified != last_modified {
+                            last_modified = modified;
+                            info!("config file changed, reloading routers");
+                            self.reload().await;
+                        }
+                    }
+                    _ = sighup.recv() => {
+                        info!("SIGHUP received, reloading routers");
+                        self.reload().await;
+                    }
+                    () = &mut token => {
+                        info!(name = "config-watcher-task", "task shutting down");
+                        break;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
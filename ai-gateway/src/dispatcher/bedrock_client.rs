@@ -27,6 +27,10 @@ pub struct Client {
     pub(super) inner: reqwest::Client,
     pub(super) access_key: Option<Secret<String>>,
     pub(super) secret_key: Option<Secret<String>>,
+    pub(super) session_token: Option<Secret<String>>,
+    /// Overrides the region parsed from the request host, which is required
+    /// for VPC endpoints.
+    pub(super) region: Option<String>,
 }
 
 impl Client {
@@ -67,6 +71,10 @@ impl Client {
             inner,
             access_key: access_key.cloned(),
             secret_key: secret_key.cloned(),
+            session_token: provider_key
+                .and_then(ProviderKey::aws_session_token)
+                .cloned(),
+            region: provider_config.region.clone(),
         })
     }
 
@@ -85,10 +93,14 @@ impl Client {
             .as_ref()
             .ok_or(ApiError::Authentication(AuthError::InvalidCredentials))?
             .expose();
+        let session_token = self
+            .session_token
+            .as_ref()
+            .map(|token| token.expose().clone());
         let identity = Credentials::new(
             access_key_id,
             secret_key,
-            None,
+            session_token,
             None,
             "Environment",
         )
@@ -109,12 +121,17 @@ impl Client {
                 "host is required in request url".to_string(),
             ))?
             .to_string();
-        let host_region: Vec<&str> = host.split('.').collect();
-        let host_region = host_region.get(1).ok_or(
-            InvalidRequestError::UnsupportedEndpoint(
-                "host is required in request url".to_string(),
-            ),
-        )?;
+        // regional endpoints look like `bedrock-runtime.{region}.amazonaws.com`
+        let host_region = match &self.region {
+            Some(region) => region.as_str(),
+            None => host.split('.').nth(1).ok_or(
+                InvalidRequestError::UnsupportedEndpoint(
+                    "region is required for bedrock endpoints without one in \
+                     the host"
+                        .to_string(),
+                ),
+            )?,
+        };
 
         let signing_settings = SigningSettings::default();
         let signing_params = v4::SigningParams::builder()
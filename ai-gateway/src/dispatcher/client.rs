@@ -1,25 +1,42 @@
-use bytes::Bytes;
-use futures::StreamExt;
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use eventsource_stream::Eventsource;
+use futures::{Stream, StreamExt};
+use http::{HeaderMap, HeaderValue};
 use http_body_util::BodyExt;
-use reqwest::RequestBuilder;
-use reqwest_eventsource::{Event, EventSource, RequestBuilderExt};
+use opentelemetry::KeyValue;
+use reqwest::{ClientBuilder, RequestBuilder};
+use reqwest_eventsource::Event;
+use rustc_hash::FxHashMap as HashMap;
+use tokio::time;
 use tracing::{Instrument, info_span};
 
 use crate::{
     app_state::AppState,
-    config::DeploymentTarget,
+    config::{Config, DeploymentTarget, providers::HttpVersion},
     discover::monitor::metrics::EndpointMetricsRegistry,
     dispatcher::{
         SSEStream, anthropic_client::Client as AnthropicClient,
-        bedrock_client::Client as BedrockClient,
+        azure_client::Client as AzureClient,
+        bedrock_client::Client as BedrockClient, event_stream,
+        gemini_client::Client as GeminiClient,
         ollama_client::Client as OllamaClient,
         openai_compatible_client::Client as OpenAICompatibleClient,
+        vertex_client::Client as VertexClient,
     },
     endpoints::ApiEndpoint,
     error::{
-        api::ApiError, auth::AuthError, init::InitError,
-        internal::InternalError, stream::StreamError,
+        api::{ApiError, TimeoutKind},
+        auth::AuthError,
+        init::InitError,
+        internal::InternalError,
+        stream::StreamError,
     },
+    metrics::Metrics,
     types::{
         extensions::AuthContext,
         provider::{InferenceProvider, ProviderKey},
@@ -49,7 +66,14 @@ impl ProviderClient for Client {
         match self {
             Client::Bedrock(inner) => inner
                 .extract_and_sign_aws_headers(request_builder, req_body_bytes),
-            Client::OpenAICompatible(_) | Client::Anthropic(_) => {
+            // vertex always uses the gateway's own google credentials
+            Client::Vertex(inner) => {
+                inner.set_auth_header(request_builder).await
+            }
+            Client::OpenAICompatible(_)
+            | Client::Anthropic(_)
+            | Client::AzureOpenAI(_)
+            | Client::Gemini(_) => {
                 self.authenticate_inner(
                     app_state,
                     request_builder,
@@ -67,8 +91,11 @@ impl ProviderClient for Client {
 pub enum Client {
     OpenAICompatible(OpenAICompatibleClient),
     Anthropic(AnthropicClient),
+    AzureOpenAI(AzureClient),
+    Gemini(GeminiClient),
     Ollama(OllamaClient),
     Bedrock(BedrockClient),
+    Vertex(VertexClient),
 }
 
 impl Client {
@@ -79,125 +106,158 @@ impl Client {
         auth_ctx: Option<&AuthContext>,
         provider: InferenceProvider,
     ) -> Result<reqwest::RequestBuilder, ApiError> {
-        match app_state.0.config.deployment_target {
-            DeploymentTarget::Cloud => {
-                if let Some(auth_ctx) = auth_ctx {
-                    let org_id = auth_ctx.org_id;
-
-                    let provider_key = app_state
-                        .0
-                        .provider_keys
-                        .get_provider_key(&provider, Some(&org_id))
-                        .await;
-
-                    if let Some(ProviderKey::Secret(key)) = provider_key
-                        && key.expose() != ""
-                    {
-                        let request_builder = match self {
-                            Client::OpenAICompatible(_) => {
-                                OpenAICompatibleClient::set_auth_header(
-                                    request_builder,
-                                    &key,
-                                )
-                            }
-                            Client::Anthropic(_) => {
-                                AnthropicClient::set_auth_header(
-                                    request_builder,
-                                    &key,
-                                )
-                            }
-                            _ => request_builder,
-                        };
-
-                        return Ok(request_builder);
-                    }
-
-                    let refetched_org_provider_keys = app_state
-                        .0
-                        .router_store
-                        .as_ref()
-                        .ok_or(ApiError::Internal(InternalError::Internal))?
-                        .get_org_provider_keys(org_id)
-                        .await
-                        .map_err(|_| {
-                            ApiError::Internal(InternalError::Internal)
-                        })?;
-
-                    let provider_key =
-                        refetched_org_provider_keys.get(&provider);
-
-                    app_state
-                        .0
-                        .provider_keys
-                        .set_org_provider_keys(
-                            org_id,
-                            refetched_org_provider_keys.clone(),
-                        )
-                        .await;
-
-                    if let Some(ProviderKey::Secret(key)) = provider_key {
-                        let request_builder = match self {
-                            Client::OpenAICompatible(_) => {
-                                OpenAICompatibleClient::set_auth_header(
-                                    request_builder,
-                                    key,
-                                )
-                            }
-                            Client::Anthropic(_) => {
-                                AnthropicClient::set_auth_header(
-                                    request_builder,
-                                    key,
-                                )
-                            }
-                            _ => request_builder,
-                        };
-
-                        return Ok(request_builder);
-                    }
-
-                    return Err(ApiError::Authentication(
-                        AuthError::ProviderKeyNotFound,
-                    ));
-                }
-                Err(ApiError::Authentication(AuthError::ProviderKeyNotFound))
+        let org_id = auth_ctx.map(|auth_ctx| auth_ctx.org_id);
+        let provider_key = match app_state
+            .resolve_provider_key(&provider, org_id.as_ref())
+            .await
+        {
+            Ok(ProviderKey::Secret(key)) => key,
+            Ok(_) => return Ok(request_builder),
+            // sidecar providers may not need a key, e.g. self-hosted models
+            Err(ApiError::Authentication(AuthError::ProviderKeyNotFound))
+                if app_state.config().deployment_target
+                    == DeploymentTarget::Sidecar =>
+            {
+                return Ok(request_builder);
             }
-            DeploymentTarget::Sidecar => Ok(request_builder),
-        }
+            Err(e) => return Err(e),
+        };
+        let request_builder = match self {
+            Client::OpenAICompatible(_) => {
+                OpenAICompatibleClient::set_auth_header(
+                    request_builder,
+                    &provider_key,
+                )
+            }
+            Client::Anthropic(_) => {
+                AnthropicClient::set_auth_header(request_builder, &provider_key)
+            }
+            Client::AzureOpenAI(_) => {
+                AzureClient::set_auth_header(request_builder, &provider_key)
+            }
+            Client::Gemini(_) => {
+                GeminiClient::set_auth_header(request_builder, &provider_key)
+            }
+            _ => request_builder,
+        };
+        Ok(request_builder)
     }
 
+    /// Sends a request which responds with SSE, returning the provider's
+    /// response headers along with the stream.
     pub(crate) async fn sse_stream<B>(
         request_builder: RequestBuilder,
         body: B,
         api_endpoint: Option<ApiEndpoint>,
         metrics_registry: &EndpointMetricsRegistry,
-    ) -> Result<SSEStream, ApiError>
+        idle_timeout: IdleTimeout,
+    ) -> Result<(HeaderMap, SSEStream), ApiError>
     where
         B: Into<reqwest::Body>,
     {
-        let event_source = request_builder
-            .body(body)
-            .eventsource()
-            .map_err(|_e| InternalError::Internal)?;
-        let stream =
-            sse_stream(event_source, api_endpoint, metrics_registry.clone())
-                .await?;
-        Ok(stream)
+        let response = send_stream_request(
+            request_builder,
+            body,
+            api_endpoint.clone(),
+            metrics_registry,
+        )
+        .await?;
+        let content_type =
+            response.headers().get(http::header::CONTENT_TYPE).cloned();
+        let is_event_stream = content_type
+            .as_ref()
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(|content_type| content_type.parse::<mime::Mime>().ok())
+            .is_some_and(|mime| {
+                mime.type_() == mime::TEXT
+                    && mime.subtype() == mime::EVENT_STREAM
+            });
+        if !is_event_stream {
+            let error = reqwest_eventsource::Error::InvalidContentType(
+                content_type.unwrap_or(HeaderValue::from_static("")),
+                response,
+            );
+            handle_stream_error(error, api_endpoint, metrics_registry).await?;
+            // `handle_stream_error` always returns an error for an invalid
+            // content type
+            return Err(InternalError::Internal.into());
+        }
+        let headers = response.headers().clone();
+        let events = response.bytes_stream().eventsource().map(|event| {
+            event
+                .map(Event::Message)
+                .map_err(reqwest_eventsource::Error::from)
+        });
+        let stream = sse_stream(
+            Box::pin(events),
+            api_endpoint,
+            metrics_registry.clone(),
+            idle_timeout,
+        )
+        .await?;
+        Ok((headers, stream))
     }
 
+    /// Like [`Client::sse_stream`], for providers which don't stream
+    /// server-sent events.
+    pub(crate) async fn framed_stream<B>(
+        request_builder: RequestBuilder,
+        body: B,
+        framing: StreamFraming,
+        api_endpoint: Option<ApiEndpoint>,
+        metrics_registry: &EndpointMetricsRegistry,
+        idle_timeout: IdleTimeout,
+    ) -> Result<(HeaderMap, SSEStream), ApiError>
+    where
+        B: Into<reqwest::Body>,
+    {
+        let response = send_stream_request(
+            request_builder,
+            body,
+            api_endpoint.clone(),
+            metrics_registry,
+        )
+        .await?;
+        let headers = response.headers().clone();
+        let stream = framed_stream(
+            response,
+            framing,
+            api_endpoint,
+            metrics_registry.clone(),
+            idle_timeout,
+        );
+        Ok((headers, stream))
+    }
+
+    /// The provider's client, which is created once and shared so that
+    /// every dispatcher to the provider reuses the same connection pool.
     pub(crate) async fn new(
         app_state: &AppState,
         inference_provider: InferenceProvider,
     ) -> Result<Self, InitError> {
-        if inference_provider == InferenceProvider::Ollama {
-            return Self::new_inner(app_state, inference_provider, None);
+        if let Some(client) =
+            app_state.0.provider_clients.get(&inference_provider)
+        {
+            return Ok(client);
         }
-        let api_key = &app_state
+        let client = if inference_provider == InferenceProvider::Ollama {
+            Self::new_inner(app_state, inference_provider.clone(), None)?
+        } else {
+            let api_key = &app_state
+                .0
+                .provider_keys
+                .get_provider_key(&inference_provider, None)
+                .await;
+            Self::new_inner(
+                app_state,
+                inference_provider.clone(),
+                api_key.as_ref(),
+            )?
+        };
+        Ok(app_state
             .0
-            .provider_keys
-            .get_provider_key(&inference_provider, None)
-            .await;
-
-        Self::new_inner(app_state, inference_provider, api_key.as_ref())
+            .provider_clients
+            .get_or_insert(inference_provider, client))
     }
 
     fn new_inner(
@@ -205,15 +265,16 @@ impl Client {
         inference_provider: InferenceProvider,
         api_key: Option<&ProviderKey>,
     ) -> Result<Self, InitError> {
-        // connection timeout, timeout, etc.
-        let base_client = reqwest::Client::builder()
-            .connect_timeout(app_state.0.config.dispatcher.connection_timeout)
-            .timeout(app_state.0.config.dispatcher.timeout)
-            .tcp_nodelay(true);
+        let base_client =
+            base_client_builder(&app_state.0.config, &inference_provider);
 
         match inference_provider {
             InferenceProvider::OpenAI
-            | InferenceProvider::GoogleGemini
+            | InferenceProvider::Cohere
+            | InferenceProvider::Mistral
+            | InferenceProvider::Groq
+            | InferenceProvider::XAI
+            | InferenceProvider::DeepSeek
             | InferenceProvider::Named(_) => {
                 let openai_compatible_client = OpenAICompatibleClient::new(
                     app_state,
@@ -226,9 +287,20 @@ impl Client {
             InferenceProvider::Anthropic => Ok(Self::Anthropic(
                 AnthropicClient::new(app_state, base_client, api_key)?,
             )),
+            InferenceProvider::AzureOpenAI => Ok(Self::AzureOpenAI(
+                AzureClient::new(app_state, base_client, api_key)?,
+            )),
+            InferenceProvider::GoogleGemini => Ok(Self::Gemini(
+                GeminiClient::new(app_state, base_client, api_key)?,
+            )),
             InferenceProvider::Bedrock => Ok(Self::Bedrock(
                 BedrockClient::new(app_state, base_client, api_key)?,
             )),
+            InferenceProvider::VertexAI => Ok(Self::Vertex(VertexClient::new(
+                app_state,
+                base_client,
+                api_key,
+            )?)),
             InferenceProvider::Ollama => {
                 Ok(Self::Ollama(OllamaClient::new(app_state, base_client)?))
             }
@@ -236,27 +308,160 @@ impl Client {
     }
 }
 
+/// The client of each provider, see [`Client::new`].
+///
+/// The global provider config isn't reloaded, so clients are never rebuilt.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderClients(Arc<Mutex<HashMap<InferenceProvider, Client>>>);
+
+impl ProviderClients {
+    #[must_use]
+    pub fn get(&self, provider: &InferenceProvider) -> Option<Client> {
+        let clients = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        clients.get(provider).cloned()
+    }
+
+    /// Inserts the client unless another was inserted while it was being
+    /// created, returning the provider's client.
+    fn get_or_insert(
+        &self,
+        provider: InferenceProvider,
+        client: Client,
+    ) -> Client {
+        let mut clients = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        clients.entry(provider).or_insert(client).clone()
+    }
+}
+
+/// Settings shared by the clients for every provider, plus the provider's
+/// HTTP version, connection pool and connect timeout.
+fn base_client_builder(
+    config: &Config,
+    inference_provider: &InferenceProvider,
+) -> ClientBuilder {
+    let provider_config = config.providers.get(inference_provider);
+    let connection_pool = provider_config
+        .and_then(|provider_config| provider_config.connection_pool)
+        .unwrap_or(config.dispatcher.connection_pool);
+    let timeouts = provider_config
+        .map(|provider_config| provider_config.timeouts)
+        .unwrap_or_default()
+        .or(config.dispatcher.timeouts);
+    // the request and stream idle timeouts are enforced by the dispatcher,
+    // since they can be set per router
+    let builder = reqwest::Client::builder()
+        .connect_timeout(timeouts.connect())
+        .tcp_nodelay(true)
+        .pool_max_idle_per_host(connection_pool.max_idle_per_host)
+        .pool_idle_timeout(connection_pool.idle_timeout)
+        .tcp_keepalive(connection_pool.tcp_keepalive);
+    let http_version = provider_config
+        .map(|provider_config| provider_config.http_version)
+        .unwrap_or_default();
+    let is_plaintext = provider_config.is_some_and(|provider_config| {
+        provider_config.base_url.scheme() == "http"
+    });
+    match http_version {
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Http2 if is_plaintext => builder.http2_prior_knowledge(),
+        // over TLS, HTTP/2 is negotiated with ALPN, which a provider without
+        // HTTP/2 can decline
+        HttpVersion::Auto | HttpVersion::Http2 => builder,
+    }
+}
+
 impl AsRef<reqwest::Client> for Client {
     fn as_ref(&self) -> &reqwest::Client {
         match self {
             Client::OpenAICompatible(client) => &client.0,
             Client::Anthropic(client) => &client.0,
+            Client::AzureOpenAI(client) => &client.0,
+            Client::Gemini(client) => &client.0,
             Client::Ollama(client) => &client.0,
             Client::Bedrock(client) => &client.inner,
+            Client::Vertex(client) => &client.inner,
         }
     }
 }
 
+/// How long a stream may go without an event from the provider before it's
+/// ended.
+#[derive(Debug, Clone)]
+pub(crate) struct IdleTimeout {
+    pub(crate) duration: Duration,
+    pub(crate) provider: InferenceProvider,
+    pub(crate) metrics: Metrics,
+}
+
+impl IdleTimeout {
+    /// Records that the stream went idle, returning the error it ends with.
+    fn elapsed(&self) -> StreamError {
+        tracing::warn!(idle_timeout = ?self.duration, "stream went idle, cancelling stream");
+        record_timeout(&self.metrics, &self.provider, TimeoutKind::StreamIdle);
+        StreamError::IdleTimeout(self.duration)
+    }
+}
+
+/// Counts a timeout of the provider, by the kind of timeout.
+pub(super) fn record_timeout(
+    metrics: &Metrics,
+    provider: &InferenceProvider,
+    kind: TimeoutKind,
+) {
+    metrics.provider_timeouts.add(
+        1,
+        &[
+            KeyValue::new("provider", provider.to_string()),
+            KeyValue::new("kind", kind.as_ref().to_string()),
+        ],
+    );
+}
+
+/// Sends a streaming request, failing with the provider's error if it doesn't
+/// respond successfully.
+async fn send_stream_request<B>(
+    request_builder: RequestBuilder,
+    body: B,
+    api_endpoint: Option<ApiEndpoint>,
+    metrics_registry: &EndpointMetricsRegistry,
+) -> Result<reqwest::Response, ApiError>
+where
+    B: Into<reqwest::Body>,
+{
+    let response = request_builder
+        .body(body)
+        .send()
+        .await
+        .map_err(InternalError::ReqwestError)?;
+    let status = response.status();
+    if !status.is_success() {
+        let error =
+            reqwest_eventsource::Error::InvalidStatusCode(status, response);
+        handle_stream_error(error, api_endpoint, metrics_registry).await?;
+        // `handle_stream_error` always returns an error for an invalid
+        // status code
+        return Err(InternalError::Internal.into());
+    }
+    Ok(response)
+}
+
 /// Request which responds with SSE.
 /// [server-sent events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events/Using_server-sent_events#event_stream_format)
+///
+/// The stream ends with [`StreamError::IdleTimeout`] if the provider sends no
+/// events for the idle timeout.
 pub(super) async fn sse_stream(
-    mut event_source: EventSource,
+    mut events: impl Stream<Item = Result<Event, reqwest_eventsource::Error>>
+    + Send
+    + Unpin
+    + 'static,
     api_endpoint: Option<ApiEndpoint>,
     metrics_registry: EndpointMetricsRegistry,
+    idle_timeout: IdleTimeout,
 ) -> Result<SSEStream, StreamError> {
     let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
     // we want to await the first event so that we can propagate errors
-    match event_source.next().await {
+    match events.next().await {
         Some(Ok(event)) => match event {
             Event::Message(message) if message.data != "[DONE]" => {
                 let data = Bytes::from(message.data);
@@ -276,7 +481,27 @@ pub(super) async fn sse_stream(
 
     tokio::spawn(
         async move {
-            while let Some(ev) = event_source.next().await {
+            loop {
+                // stop reading from the provider as soon as the client
+                // disconnects rather than on the next event, so that the
+                // upstream request is cancelled
+                let ev = tokio::select! {
+                    () = tx.closed() => {
+                        tracing::debug!("client disconnected, cancelling stream");
+                        break;
+                    }
+                    ev = time::timeout(idle_timeout.duration, events.next()) => ev,
+                };
+                let Ok(ev) = ev else {
+                    let error = idle_timeout.elapsed();
+                    if let Err(_e) = tx.send(Err(error.into())) {
+                        tracing::trace!("rx dropped before stream ended");
+                    }
+                    break;
+                };
+                let Some(ev) = ev else {
+                    break;
+                };
                 match ev {
                     Err(e) => {
                         if matches!(e, reqwest_eventsource::Error::StreamEnded) {
@@ -310,8 +535,6 @@ pub(super) async fn sse_stream(
                     },
                 }
             }
-
-            event_source.close();
         }
         .instrument(info_span!("sse_stream")),
     );
@@ -321,6 +544,108 @@ pub(super) async fn sse_stream(
     ))
 }
 
+/// How a streaming response which isn't SSE is split into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StreamFraming {
+    /// The AWS event stream binary framing, one chunk per event.
+    AwsEventStream,
+    /// Server-sent events forwarded verbatim, one chunk per event including
+    /// its `event:` line, for passthrough requests whose responses aren't
+    /// mapped.
+    RawSse,
+}
+
+impl StreamFraming {
+    /// Splits the next chunk off of `buffer`, if it holds a complete one.
+    ///
+    /// Returns an empty chunk for frames which should be skipped.
+    fn next_chunk(
+        self,
+        buffer: &mut BytesMut,
+    ) -> Result<Option<Bytes>, StreamError> {
+        match self {
+            Self::AwsEventStream => event_stream::decode_message(buffer),
+            Self::RawSse => Ok(buffer
+                .windows(2)
+                .position(|window| window == b"\n\n")
+                .map(|end| buffer.split_to(end + 2).freeze())),
+        }
+    }
+}
+
+/// Splits a streaming response into chunks according to `framing`, so that
+/// it can be mapped like an SSE stream.
+fn framed_stream(
+    response: reqwest::Response,
+    framing: StreamFraming,
+    api_endpoint: Option<ApiEndpoint>,
+    metrics_registry: EndpointMetricsRegistry,
+    idle_timeout: IdleTimeout,
+) -> SSEStream {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut body = response.bytes_stream();
+    tokio::spawn(
+        async move {
+            let mut buffer = BytesMut::new();
+            'stream: loop {
+                let chunk = tokio::select! {
+                    () = tx.closed() => {
+                        tracing::debug!("client disconnected, cancelling stream");
+                        break;
+                    }
+                    chunk = time::timeout(idle_timeout.duration, body.next()) => chunk,
+                };
+                let Ok(chunk) = chunk else {
+                    let error = idle_timeout.elapsed();
+                    if let Err(_e) = tx.send(Err(error.into())) {
+                        tracing::trace!("rx dropped before stream ended");
+                    }
+                    break;
+                };
+                let at_end = match chunk {
+                    Some(Ok(chunk)) => {
+                        buffer.extend_from_slice(&chunk);
+                        false
+                    }
+                    Some(Err(e)) => {
+                        let error = reqwest_eventsource::Error::Transport(e);
+                        if let Err(e) = handle_stream_error_with_tx(error, tx.clone(), api_endpoint.clone(), &metrics_registry).await {
+                            tracing::error!(error = %e, "failed to handle stream error");
+                        }
+                        break;
+                    }
+                    None => true,
+                };
+                loop {
+                    match framing.next_chunk(&mut buffer) {
+                        Ok(Some(chunk)) if chunk.is_empty() => {}
+                        Ok(Some(chunk)) => {
+                            if let Err(_e) = tx.send(Ok(chunk)) {
+                                tracing::trace!("rx dropped before stream ended");
+                                break 'stream;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            tracing::error!(error = %e, "failed to decode stream");
+                            if let Err(_e) = tx.send(Err(e.into())) {
+                                tracing::trace!("rx dropped before stream ended");
+                            }
+                            break 'stream;
+                        }
+                    }
+                }
+                if at_end {
+                    break;
+                }
+            }
+        }
+        .instrument(info_span!("framed_stream")),
+    );
+
+    Box::pin(tokio_stream::wrappers::UnboundedReceiverStream::new(rx))
+}
+
 async fn handle_stream_error_with_tx(
     error: reqwest_eventsource::Error,
     tx: tokio::sync::mpsc::UnboundedSender<Result<Bytes, ApiError>>,
@@ -433,3 +758,124 @@ fn record_stream_err_metrics(
         }).ok();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{
+        app::App,
+        tests::TestDefault,
+        types::provider::{ProviderKeyMap, ProviderKeys},
+    };
+
+    /// Sends a request with the client for `provider` and returns the first
+    /// line the server receives.
+    async fn request_line(
+        config: &Config,
+        provider: &InferenceProvider,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let client = base_client_builder(config, provider).build().unwrap();
+        let request = tokio::spawn(async move { client.get(url).send().await });
+        let (mut socket, _) = listener.accept().await.unwrap();
+        // both `GET / HTTP/1.1\r\n` and the HTTP/2 connection preface
+        // `PRI * HTTP/2.0\r\n` are 16 bytes
+        let mut line = [0_u8; 16];
+        socket.read_exact(&mut line).await.unwrap();
+        request.abort();
+        String::from_utf8_lossy(&line).trim_end().to_string()
+    }
+
+    /// Only plaintext connections are covered, negotiating HTTP/2 over TLS
+    /// with ALPN would need a provider with a trusted certificate.
+    #[tokio::test]
+    async fn providers_use_their_configured_http_version() {
+        let mut config = Config::default();
+        for (provider, http_version) in [
+            (InferenceProvider::OpenAI, HttpVersion::Http1),
+            (InferenceProvider::Anthropic, HttpVersion::Http2),
+        ] {
+            let provider_config = config.providers.get_mut(&provider).unwrap();
+            provider_config.http_version = http_version;
+            // prior knowledge is only used for plaintext connections
+            provider_config.base_url =
+                url::Url::parse("http://localhost/").unwrap();
+        }
+
+        assert_eq!(
+            request_line(&config, &InferenceProvider::OpenAI).await,
+            "GET / HTTP/1.1"
+        );
+        assert_eq!(
+            request_line(&config, &InferenceProvider::Anthropic).await,
+            "PRI * HTTP/2.0"
+        );
+    }
+
+    /// Responds `ok` to every request, keeping connections open, and counts
+    /// the connections it accepts.
+    async fn serve_keep_alive(
+        listener: TcpListener,
+        accepts: Arc<AtomicUsize>,
+    ) {
+        const RESPONSE: &[u8] =
+            b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            accepts.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0_u8; 1024];
+                loop {
+                    let Ok(read @ 1..) = socket.read(&mut buf).await else {
+                        return;
+                    };
+                    request.extend_from_slice(&buf[..read]);
+                    // requests without a body end with an empty line
+                    if request.ends_with(b"\r\n\r\n") {
+                        request.clear();
+                        socket.write_all(RESPONSE).await.unwrap();
+                    }
+                }
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn sequential_requests_to_a_provider_reuse_its_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let accepts = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(serve_keep_alive(listener, accepts.clone()));
+
+        let mut config = Config::test_default();
+        config
+            .providers
+            .get_mut(&InferenceProvider::OpenAI)
+            .unwrap()
+            .base_url = url::Url::parse(&url).unwrap();
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+        let app = App::with_provider_keys(config, provider_keys)
+            .await
+            .expect("failed to create app");
+
+        // as if sent by the dispatchers of different routers
+        for _ in 0..3 {
+            let client = Client::new(&app.state, InferenceProvider::OpenAI)
+                .await
+                .unwrap();
+            let response = client.as_ref().get(&url).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "ok");
+        }
+        assert_eq!(accepts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use opentelemetry::KeyValue;
+use rustc_hash::FxHashMap as HashMap;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::PollSemaphore;
+
+use crate::{
+    config::Config, metrics::Metrics, types::provider::InferenceProvider,
+};
+
+/// How long a request waits for a saturated provider to free a slot before
+/// it is rejected with a `503`.
+pub const MAX_SATURATED_WAIT: Duration = Duration::from_secs(5);
+
+/// Per provider limits on the number of in-flight requests, configured via
+/// `max-concurrent-requests` in the provider config.
+///
+/// The limits are shared by every router and direct proxy that dispatches to
+/// the provider.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderConcurrencyLimits(
+    Arc<HashMap<InferenceProvider, (usize, Arc<Semaphore>)>>,
+);
+
+impl ProviderConcurrencyLimits {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        let limits = config
+            .providers
+            .iter()
+            .filter_map(|(provider, provider_config)| {
+                let max = provider_config.max_concurrent_requests?.get();
+                Some((provider.clone(), (max, Arc::new(Semaphore::new(max)))))
+            })
+            .collect();
+        Self(Arc::new(limits))
+    }
+
+    #[must_use]
+    pub fn is_limited(&self, provider: &InferenceProvider) -> bool {
+        self.0.contains_key(provider)
+    }
+
+    /// Whether the provider has a limit and every request it allows is
+    /// in-flight.
+    #[must_use]
+    pub fn is_saturated(&self, provider: &InferenceProvider) -> bool {
+        self.0
+            .get(provider)
+            .is_some_and(|(_, semaphore)| semaphore.available_permits() == 0)
+    }
+
+    #[must_use]
+    pub fn limit_for(
+        &self,
+        provider: &InferenceProvider,
+        metrics: &Metrics,
+    ) -> Option<ConcurrencyLimit> {
+        let (max, semaphore) = self.0.get(provider)?;
+        Some(ConcurrencyLimit {
+            provider: provider.clone(),
+            max: *max,
+            semaphore: PollSemaphore::new(semaphore.clone()),
+            metrics: metrics.clone(),
+        })
+    }
+}
+
+/// A handle on a provider's concurrency limit held by a single dispatcher.
+///
+/// Unlike [`tower::limit::ConcurrencyLimit`], a saturated provider reports
+/// `Poll::Pending` from `poll_ready` so that the balancer picks a different
+/// provider rather than queueing the request.
+///
+/// Permits are only held while a request is in-flight, not while a ready
+/// service sits idle in a balancer, since the same provider is shared by the
+/// dispatchers of every router.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    provider: InferenceProvider,
+    max: usize,
+    semaphore: PollSemaphore,
+    metrics: Metrics,
+}
+
+impl ConcurrencyLimit {
+    /// Returns `Poll::Ready` if the provider has capacity for another
+    /// request, otherwise registers for a wakeup once a permit is released.
+    pub fn poll_capacity(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.semaphore.available_permits() > 0 {
+            return Poll::Ready(());
+        }
+        match self.semaphore.poll_acquire(cx) {
+            // a permit was released in the meantime, hand it back so that it
+            // can be acquired in `call`
+            Poll::Ready(permit) => {
+                drop(permit);
+                Poll::Ready(())
+            }
+            Poll::Pending => {
+                tracing::debug!(
+                    provider = %self.provider,
+                    "provider at max concurrent requests"
+                );
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Acquire a permit for a single request, waiting up to
+    /// [`MAX_SATURATED_WAIT`] for a slot.
+    ///
+    /// Returns `None` if the provider is still saturated, which can happen
+    /// for services that are called without first being polled for
+    /// readiness, such as direct proxies, or when another router takes the
+    /// last slot between `poll_ready` and `call`.
+    pub async fn acquire(&self) -> Option<InFlightPermit> {
+        let semaphore = self.semaphore.clone_inner();
+        let permit =
+            tokio::time::timeout(MAX_SATURATED_WAIT, semaphore.acquire_owned())
+                .await
+                .ok()?
+                .ok()?;
+        let permit = InFlightPermit {
+            provider: self.provider.clone(),
+            max: self.max,
+            semaphore: self.semaphore.clone_inner(),
+            permit: Some(permit),
+            metrics: self.metrics.clone(),
+        };
+        permit.record_in_flight();
+        Some(permit)
+    }
+}
+
+/// Held for the duration of a request to a provider with a concurrency
+/// limit, releasing its slot on drop.
+#[derive(Debug)]
+pub struct InFlightPermit {
+    provider: InferenceProvider,
+    max: usize,
+    semaphore: Arc<Semaphore>,
+    permit: Option<OwnedSemaphorePermit>,
+    metrics: Metrics,
+}
+
+impl InFlightPermit {
+    fn record_in_flight(&self) {
+        let in_flight =
+            self.max.saturating_sub(self.semaphore.available_permits());
+        self.metrics.provider_in_flight.record(
+            u64::try_from(in_flight).unwrap_or(u64::MAX),
+            &[KeyValue::new("provider", self.provider.to_string())],
+        );
+    }
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        self.record_in_flight();
+    }
+}
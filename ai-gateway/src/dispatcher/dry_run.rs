@@ -0,0 +1,91 @@
+//! Synthetic responses returned instead of calling the provider, see
+//! [`DispatcherConfig::dry_run`](crate::config::dispatcher::DispatcherConfig::dry_run).
+//!
+//! Responses are OpenAI chat completions whatever the provider, and are
+//! passed through the mapper as is. They are marked `no-store` so that they
+//! are never cached.
+use bytes::Bytes;
+use http::{HeaderName, HeaderValue, StatusCode, header};
+use serde_json::{Value, json};
+use tokio::sync::oneshot;
+
+use crate::{
+    error::{api::ApiError, internal::InternalError},
+    types::{
+        body::{Body, BodyReader},
+        extensions::{DryRun, MapperContext},
+    },
+};
+
+pub(crate) const DRY_RUN_HEADER: HeaderName =
+    HeaderName::from_static("helicone-dry-run");
+
+const CONTENT: &str = "This is a dry run response, no provider was called.";
+
+/// The synthetic response, along with a reader of its body for the logger.
+pub(crate) fn response(
+    mapper_ctx: &MapperContext,
+) -> Result<(http::Response<Body>, BodyReader, oneshot::Receiver<()>), ApiError>
+{
+    let model = mapper_ctx
+        .model
+        .as_ref()
+        .map_or_else(|| "dry-run".to_string(), ToString::to_string);
+    let created = chrono::Utc::now().timestamp();
+    let (content_type, events) = if mapper_ctx.is_stream {
+        let chunk = json!({
+            "id": "chatcmpl-dry-run",
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "delta": { "role": "assistant", "content": CONTENT },
+                "finish_reason": "stop",
+            }],
+        });
+        (
+            "text/event-stream; charset=utf-8",
+            vec![sse_event(&chunk), Bytes::from_static(b"data: [DONE]\n\n")],
+        )
+    } else {
+        let completion = json!({
+            "id": "chatcmpl-dry-run",
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": CONTENT },
+                "finish_reason": "stop",
+            }],
+            "usage": {
+                "prompt_tokens": 0,
+                "completion_tokens": 0,
+                "total_tokens": 0,
+            },
+        });
+        (
+            "application/json",
+            vec![Bytes::from(completion.to_string())],
+        )
+    };
+
+    let (body, body_reader, tfft_rx) = BodyReader::wrap_stream(
+        futures::stream::iter(events.into_iter().map(Ok::<_, ApiError>)),
+        false,
+    );
+    let mut response = http::Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-store")
+        .header(DRY_RUN_HEADER, HeaderValue::from_static("true"))
+        .body(body)
+        .map_err(InternalError::HttpError)?;
+    response.extensions_mut().insert(DryRun);
+    Ok((response, body_reader, tfft_rx))
+}
+
+fn sse_event(data: &Value) -> Bytes {
+    Bytes::from(format!("data: {data}\n\n"))
+}
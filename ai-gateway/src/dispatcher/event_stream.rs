@@ -0,0 +1,161 @@
+//! Decodes the [AWS event stream] messages which Bedrock uses for streaming
+//! responses.
+//!
+//! Each message is decoded to a JSON chunk of the form
+//! `{"<event type>": <payload>}`, so that it can be mapped like an SSE stream.
+//!
+//! [AWS event stream]: https://docs.aws.amazon.com/transcribe/latest/dg/streaming-setting-up.html#streaming-event-stream
+use aws_smithy_eventstream::frame::{DecodedFrame, MessageFrameDecoder};
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::error::stream::StreamError;
+
+/// Decodes the next complete message in `buffer`, if there is one.
+///
+/// Returns `Ok(None)` if more bytes are needed. Messages without an event
+/// type, such as the initial response, are decoded to an empty chunk and
+/// should be skipped.
+pub(crate) fn decode_message(
+    buffer: &mut BytesMut,
+) -> Result<Option<Bytes>, StreamError> {
+    // the decoder consumes the prelude of incomplete messages, so it's given
+    // a view of the buffer which is only advanced once a message is complete
+    let mut unread = &buffer[..];
+    let message = match MessageFrameDecoder::new()
+        .decode_frame(&mut unread)
+        .map_err(|e| StreamError::EventStream(e.to_string()))?
+    {
+        DecodedFrame::Incomplete => return Ok(None),
+        DecodedFrame::Complete(message) => message,
+    };
+    let message_len = buffer.len() - unread.len();
+    buffer.advance(message_len);
+
+    let header = |name: &str| {
+        message
+            .headers()
+            .iter()
+            .find(|header| header.name().as_str() == name)
+            .and_then(|header| header.value().as_string().ok())
+            .map(|value| value.as_str().to_string())
+    };
+    let event_type = header(":event-type");
+    match header(":message-type").as_deref() {
+        Some("event") | None => {}
+        Some(_) => {
+            let exception_type = header(":exception-type")
+                .or(event_type)
+                .unwrap_or_else(|| "exception".to_string());
+            let payload = String::from_utf8_lossy(message.payload());
+            return Err(StreamError::EventStream(format!(
+                "{exception_type}: {payload}"
+            )));
+        }
+    }
+    let Some(event_type) = event_type else {
+        return Ok(Some(Bytes::new()));
+    };
+    Ok(Some(json_chunk(&event_type, message.payload())?))
+}
+
+/// `{"<event type>": <payload>}`
+fn json_chunk(event_type: &str, payload: &[u8]) -> Result<Bytes, StreamError> {
+    let payload = if payload.is_empty() { b"{}" } else { payload };
+    let event_type = serde_json::to_vec(event_type)
+        .map_err(|_e| StreamError::EventStream("invalid event type".into()))?;
+    let mut chunk =
+        BytesMut::with_capacity(event_type.len() + payload.len() + 3);
+    chunk.extend_from_slice(b"{");
+    chunk.extend_from_slice(&event_type);
+    chunk.extend_from_slice(b":");
+    chunk.extend_from_slice(payload);
+    chunk.extend_from_slice(b"}");
+    Ok(chunk.freeze())
+}
+
+/// Encodes a message, for tests.
+#[cfg(any(test, feature = "testing"))]
+#[must_use]
+pub fn encode_message(headers: &[(&str, &str)], payload: &[u8]) -> Bytes {
+    use aws_sdk_bedrockruntime::primitives::event_stream::{
+        Header, HeaderValue, Message,
+    };
+
+    let message = headers.iter().fold(
+        Message::new(payload.to_vec()),
+        |message, (name, value)| {
+            message.add_header(Header::new(
+                name.to_string(),
+                HeaderValue::String(value.to_string().into()),
+            ))
+        },
+    );
+    let mut encoded = Vec::new();
+    aws_smithy_eventstream::frame::write_message_to(&message, &mut encoded)
+        .unwrap();
+    Bytes::from(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, payload: &str) -> Bytes {
+        encode_message(
+            &[(":message-type", "event"), (":event-type", event_type)],
+            payload.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn messages_are_decoded_across_chunks() {
+        let first = event("messageStart", r#"{"role":"assistant"}"#);
+        let second = event(
+            "contentBlockDelta",
+            r#"{"contentBlockIndex":0,"delta":{"text":"Hi"}}"#,
+        );
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&first[..5]);
+        assert!(decode_message(&mut buffer).unwrap().is_none());
+        buffer.extend_from_slice(&first[5..]);
+        buffer.extend_from_slice(&second[..20]);
+        assert_eq!(
+            decode_message(&mut buffer).unwrap().unwrap(),
+            r#"{"messageStart":{"role":"assistant"}}"#
+        );
+        assert!(decode_message(&mut buffer).unwrap().is_none());
+        buffer.extend_from_slice(&second[20..]);
+        assert_eq!(
+            decode_message(&mut buffer).unwrap().unwrap(),
+            r#"{"contentBlockDelta":{"contentBlockIndex":0,"delta":{"text":"Hi"}}}"#
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn exceptions_are_errors() {
+        let message = encode_message(
+            &[
+                (":message-type", "exception"),
+                (":exception-type", "throttlingException"),
+            ],
+            br#"{"message":"slow down"}"#,
+        );
+        let mut buffer = BytesMut::from(&message[..]);
+        let error = decode_message(&mut buffer).unwrap_err();
+        assert!(error.to_string().contains("throttlingException"));
+    }
+
+    #[test]
+    fn corrupt_messages_are_errors() {
+        let mut message =
+            event("messageStop", r#"{"stopReason":"end_turn"}"#).to_vec();
+        let last = message.len() - 1;
+        message[last] ^= 0xFF;
+        let mut buffer = BytesMut::from(&message[..]);
+        assert!(matches!(
+            decode_message(&mut buffer),
+            Err(StreamError::EventStream(_))
+        ));
+    }
+}
@@ -1,10 +1,18 @@
 pub mod anthropic_client;
+pub mod azure_client;
 mod bedrock_client;
 pub mod client;
+pub mod concurrency;
+mod dry_run;
+pub mod event_stream;
 mod extensions;
+pub mod gemini_client;
 pub mod ollama_client;
 pub mod openai_compatible_client;
+mod rate_limit_headers;
+mod retry;
 pub mod service;
+pub mod vertex_client;
 
 use std::pin::Pin;
 
@@ -0,0 +1,115 @@
+//! Reads the remaining rate limit capacity providers report on every
+//! response, so that providers running out of capacity can be shed before
+//! they start responding with 429s.
+//!
+//! `OpenAI` style providers send `x-ratelimit-limit-*` and
+//! `x-ratelimit-remaining-*` headers, Anthropic sends
+//! `anthropic-ratelimit-*-limit` and `anthropic-ratelimit-*-remaining`.
+use http::HeaderMap;
+
+/// The limits `OpenAI` style providers report, e.g.
+/// `x-ratelimit-remaining-requests`.
+const OPENAI_LIMITS: [&str; 2] = ["requests", "tokens"];
+
+/// The limits Anthropic reports, e.g.
+/// `anthropic-ratelimit-requests-remaining`.
+const ANTHROPIC_LIMITS: [&str; 4] =
+    ["requests", "tokens", "input-tokens", "output-tokens"];
+
+/// The lowest fraction of any limit the provider has remaining, between 0
+/// and 1, or `None` if the response has no rate limit headers.
+#[must_use]
+pub fn remaining_capacity(headers: &HeaderMap) -> Option<f64> {
+    let openai = OPENAI_LIMITS.iter().filter_map(|limit| {
+        fraction(
+            headers,
+            &format!("x-ratelimit-remaining-{limit}"),
+            &format!("x-ratelimit-limit-{limit}"),
+        )
+    });
+    let anthropic = ANTHROPIC_LIMITS.iter().filter_map(|limit| {
+        fraction(
+            headers,
+            &format!("anthropic-ratelimit-{limit}-remaining"),
+            &format!("anthropic-ratelimit-{limit}-limit"),
+        )
+    });
+    openai.chain(anthropic).reduce(f64::min)
+}
+
+fn fraction(headers: &HeaderMap, remaining: &str, limit: &str) -> Option<f64> {
+    let remaining = header_value(headers, remaining)?;
+    let limit = header_value(headers, limit)?;
+    if limit <= 0.0 {
+        return None;
+    }
+    Some((remaining / limit).clamp(0.0, 1.0))
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<f64> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn openai_headers() {
+        let headers = headers(&[
+            ("x-ratelimit-limit-requests", "10000"),
+            ("x-ratelimit-remaining-requests", "9000"),
+            ("x-ratelimit-limit-tokens", "1000000"),
+            ("x-ratelimit-remaining-tokens", "20000"),
+            ("x-ratelimit-reset-tokens", "6m0s"),
+        ]);
+        assert_eq!(remaining_capacity(&headers), Some(0.02));
+    }
+
+    #[test]
+    fn anthropic_headers() {
+        let headers = headers(&[
+            ("anthropic-ratelimit-requests-limit", "50"),
+            ("anthropic-ratelimit-requests-remaining", "5"),
+            ("anthropic-ratelimit-input-tokens-limit", "40000"),
+            ("anthropic-ratelimit-input-tokens-remaining", "30000"),
+            ("anthropic-ratelimit-requests-reset", "2025-01-01T00:00:00Z"),
+        ]);
+        assert_eq!(remaining_capacity(&headers), Some(0.1));
+    }
+
+    #[test]
+    fn missing_or_invalid_headers() {
+        assert_eq!(remaining_capacity(&HeaderMap::new()), None);
+        // a remaining count without its limit
+        let headers = headers(&[
+            ("x-ratelimit-remaining-requests", "10"),
+            ("anthropic-ratelimit-tokens-limit", "0"),
+            ("anthropic-ratelimit-tokens-remaining", "0"),
+            ("x-ratelimit-limit-tokens", "unlimited"),
+            ("x-ratelimit-remaining-tokens", "5"),
+        ]);
+        assert_eq!(remaining_capacity(&headers), None);
+    }
+}
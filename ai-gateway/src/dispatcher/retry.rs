@@ -0,0 +1,202 @@
+//! Retries requests which fail transiently, see [`RetryConfig`].
+//!
+//! Each dispatcher budgets its retries against the requests it sends, so a
+//! failing provider is sent at most `1 + ratio` times its usual load.
+use std::{
+    future::Future,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use backon::{Backoff, BackoffBuilder, ConstantBuilder, ExponentialBuilder};
+use http::HeaderMap;
+use rust_decimal::prelude::ToPrimitive;
+use tokio::sync::oneshot;
+use tower::retry::budget::{Budget, TpsBudget};
+use tracing::{Instrument, info_span};
+
+use super::service::extract_retry_after;
+use crate::{
+    config::retry::{
+        DEFAULT_RETRY_FACTOR, RetryBudgetConfig, RetryConfig, RetryOn,
+        RetryStrategy, RetryableError,
+    },
+    error::{
+        api::{ApiError, TimeoutKind},
+        internal::InternalError,
+        stream::StreamError,
+    },
+    types::body::{Body, BodyReader},
+    utils::retry::RetryWithResult,
+};
+
+pub(super) type Dispatched =
+    (http::Response<Body>, BodyReader, oneshot::Receiver<()>);
+
+/// A dispatcher's retry budget, created with the budget of the first request
+/// it retries, since a dispatcher only serves requests of one router.
+#[derive(Debug, Clone, Default)]
+pub(super) struct RetryBudget(Arc<OnceLock<TpsBudget>>);
+
+impl RetryBudget {
+    fn get(&self, config: &RetryBudgetConfig) -> &TpsBudget {
+        self.0.get_or_init(|| {
+            TpsBudget::new(
+                config.window,
+                config.min_retries_per_second,
+                config.ratio.to_f32().unwrap_or_default(),
+            )
+        })
+    }
+}
+
+/// Dispatches a request with `dispatch`, retrying the failures the retry
+/// config retries while the budget allows.
+pub(super) async fn dispatch_with_retry<F, Fut>(
+    mut dispatch: F,
+    retry_config: Option<&RetryConfig>,
+    budget: &RetryBudget,
+) -> Result<Dispatched, ApiError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Dispatched, ApiError>>,
+{
+    let Some(retry_config) = retry_config else {
+        return dispatch().await;
+    };
+    let budget = budget.get(&retry_config.budget);
+    // only the first attempt of a request adds to the budget
+    budget.deposit();
+    let attempt = AtomicU32::new(0);
+    let future_fn = || {
+        let attempt = attempt.fetch_add(1, Ordering::Relaxed) + 1;
+        dispatch().instrument(info_span!("upstream_attempt", attempt))
+    };
+    RetryWithResult::new(future_fn, backoff(&retry_config.strategy))
+        .when(|result: &Result<_, _>| {
+            is_retryable(&retry_config.retry_on, result)
+        })
+        .adjust(|result: &Result<_, _>, delay: Option<Duration>| {
+            // `None` once the retries are used up
+            let delay = delay?;
+            if !budget.withdraw() {
+                tracing::warn!("retry budget exhausted, not retrying");
+                return None;
+            }
+            let retry_after = retry_after(result);
+            Some(
+                retry_after.map_or(delay, |retry_after| retry_after.max(delay)),
+            )
+        })
+        .notify(|result: &Result<Dispatched, _>, delay: Duration| {
+            let attempt = attempt.load(Ordering::Relaxed);
+            match result {
+                Ok((response, ..)) => tracing::warn!(
+                    attempt,
+                    status = %response.status(),
+                    retry_in = ?delay,
+                    "upstream attempt failed, retrying"
+                ),
+                Err(error) => tracing::warn!(
+                    attempt,
+                    error = %error,
+                    retry_in = ?delay,
+                    "upstream attempt failed, retrying"
+                ),
+            }
+        })
+        .await
+}
+
+fn backoff(strategy: &RetryStrategy) -> Box<dyn Backoff> {
+    match strategy {
+        RetryStrategy::Exponential {
+            min_delay,
+            max_delay,
+            max_retries,
+            factor,
+        } => Box::new(
+            ExponentialBuilder::default()
+                .with_max_delay(*max_delay)
+                .with_min_delay(*min_delay)
+                .with_max_times(usize::from(*max_retries))
+                .with_factor(factor.to_f32().unwrap_or(DEFAULT_RETRY_FACTOR))
+                .with_jitter()
+                .build(),
+        ),
+        RetryStrategy::Constant { delay, max_retries } => Box::new(
+            ConstantBuilder::default()
+                .with_delay(*delay)
+                .with_max_times(usize::from(*max_retries))
+                .with_jitter()
+                .build(),
+        ),
+    }
+}
+
+fn is_retryable(
+    retry_on: &RetryOn,
+    result: &Result<Dispatched, ApiError>,
+) -> bool {
+    let error = match result {
+        Ok((response, ..)) => return retry_on.status(response.status()),
+        Err(error) => error,
+    };
+    match error {
+        ApiError::Internal(InternalError::ReqwestError(error)) => {
+            match error.status() {
+                Some(status) => retry_on.status(status),
+                // resets while sending the request are request errors
+                None => {
+                    (error.is_connect() || error.is_request())
+                        && retry_on.error(RetryableError::Connection)
+                }
+            }
+        }
+        ApiError::ProviderTimeout { kind, .. } => match kind {
+            TimeoutKind::Connect => retry_on.error(RetryableError::Connection),
+            TimeoutKind::Request => retry_on.error(RetryableError::Timeout),
+            // a stream that went idle has already been sent to the client
+            TimeoutKind::StreamIdle => false,
+        },
+        ApiError::Internal(InternalError::TruncatedResponse(_)) => {
+            retry_on.error(RetryableError::TruncatedResponse)
+        }
+        ApiError::StreamError(StreamError::StreamError(error)) => {
+            match &**error {
+                reqwest_eventsource::Error::Transport(_) => {
+                    retry_on.error(RetryableError::Connection)
+                }
+                reqwest_eventsource::Error::Utf8(_)
+                | reqwest_eventsource::Error::Parser(_) => {
+                    retry_on.error(RetryableError::TruncatedResponse)
+                }
+                reqwest_eventsource::Error::InvalidStatusCode(status, _) => {
+                    retry_on.status(*status)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// How long the provider asked to wait before retrying, if it did.
+fn retry_after(result: &Result<Dispatched, ApiError>) -> Option<Duration> {
+    let headers: &HeaderMap = match result {
+        Ok((response, ..)) => response.headers(),
+        Err(ApiError::StreamError(StreamError::StreamError(error))) => {
+            match &**error {
+                reqwest_eventsource::Error::InvalidStatusCode(_, response) => {
+                    response.headers()
+                }
+                _ => return None,
+            }
+        }
+        Err(_) => return None,
+    };
+    extract_retry_after(headers).map(Duration::from_secs)
+}
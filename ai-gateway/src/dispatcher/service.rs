@@ -1,19 +1,21 @@
 use std::{
     str::FromStr,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
     task::{Context, Poll},
     time::Duration,
 };
 
-use backon::{BackoffBuilder, ConstantBuilder, ExponentialBuilder, Retryable};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use futures::{TryStreamExt, future::BoxFuture};
+use futures::future::BoxFuture;
 use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, uri::PathAndQuery};
 use http_body_util::BodyExt;
 use opentelemetry::KeyValue;
 use reqwest::RequestBuilder;
-use rust_decimal::prelude::ToPrimitive;
+use serde::Deserialize;
 use tokio::{
     sync::{mpsc::Sender, oneshot},
     time::Instant,
@@ -23,14 +25,38 @@ use tracing::{Instrument, info_span};
 
 use crate::{
     app_state::AppState,
-    config::{retry::RetryConfig, router::RouterConfig},
+    config::{
+        api_translation::ApiTranslation,
+        balance::BalanceConfigInner,
+        dispatcher::{TimeoutsConfig, is_protected_upstream_header},
+        header_filter::HeaderFilterConfig,
+        minio::AudioStorage,
+        providers::DEFAULT_AZURE_API_VERSION,
+        request_logging::RequestLogging,
+        retry::RetryConfig,
+        router::RouterConfig,
+        transform::TransformRule,
+    },
     discover::monitor::metrics::EndpointMetricsRegistry,
     dispatcher::{
-        client::{Client, ProviderClient},
+        client::{
+            Client, IdleTimeout, ProviderClient, StreamFraming, record_timeout,
+        },
+        concurrency::ConcurrencyLimit,
+        dry_run,
         extensions::ExtensionsCopier,
+        rate_limit_headers,
+        retry::{Dispatched, RetryBudget, dispatch_with_retry},
+    },
+    endpoints::{
+        ApiEndpoint, google::generate_contents::GeminiApiError, vertex,
+    },
+    error::{
+        api::{ApiError, TimeoutKind},
+        init::InitError,
+        internal::InternalError,
+        stream::StreamError,
     },
-    endpoints::ApiEndpoint,
-    error::{api::ApiError, init::InitError, internal::InternalError},
     logger::service::LoggerService,
     metrics::tfft::TFFTFuture,
     middleware::{
@@ -39,8 +65,12 @@ use crate::{
     },
     types::{
         body::BodyReader,
-        extensions::{MapperContext, RequestContext, RequestKind},
+        extensions::{
+            CanaryArm, EvaluationScore, MapperContext, PriorityFailover,
+            ProviderOverride, RequestContext, RequestKind,
+        },
         model_id::ModelId,
+        multipart::{self, MultipartMetadata},
         provider::InferenceProvider,
         rate_limit::RateLimitEvent,
         request::Request,
@@ -49,6 +79,20 @@ use crate::{
     utils::handle_error::{ErrorHandler, ErrorHandlerLayer},
 };
 
+/// Consecutive timeouts after which a provider is removed from load
+/// balancing, in the same way as when it is rate limited.
+const TIMEOUTS_BEFORE_DEPRIORITIZING: u32 = 2;
+
+const ANTHROPIC_BETA_HEADER: HeaderName =
+    HeaderName::from_static("anthropic-beta");
+
+const ANTHROPIC_API_KEY_HEADER: HeaderName =
+    HeaderName::from_static("x-api-key");
+
+/// Query parameter with the Azure OpenAI API version, which every Azure
+/// request requires.
+const AZURE_API_VERSION: &str = "api-version";
+
 pub type DispatcherFuture = BoxFuture<
     'static,
     Result<http::Response<crate::types::body::Body>, ApiError>,
@@ -64,8 +108,17 @@ pub struct Dispatcher {
     client: Client,
     app_state: AppState,
     provider: InferenceProvider,
-    /// Is `Some` for load balanced routers, `None` for direct proxies.
+    /// Is `Some` for routers, `None` for direct proxies.
+    router_id: Option<RouterId>,
+    /// Is `Some` for load balanced routers, `None` for direct proxies and
+    /// requests the gateway sends on its own behalf.
     rate_limit_tx: Option<Sender<RateLimitEvent>>,
+    /// Is `Some` if the provider has `max-concurrent-requests` configured.
+    concurrency_limit: Option<ConcurrencyLimit>,
+    /// Requests to the provider which timed out since the last response.
+    consecutive_timeouts: Arc<AtomicU32>,
+    upstream_headers: Arc<HeaderMap>,
+    retry_budget: RetryBudget,
 }
 
 impl Dispatcher {
@@ -74,15 +127,22 @@ impl Dispatcher {
         router_id: &RouterId,
         provider: InferenceProvider,
         model_mapper: ModelMapper,
+        api_translation: Option<ApiTranslation>,
+        normalize_params: bool,
+        rate_limit_tx: Option<Sender<RateLimitEvent>>,
     ) -> Result<DispatcherService, InitError> {
         let client = Client::new(&app_state, provider.clone()).await?;
-        let rate_limit_tx = app_state.get_rate_limit_tx(router_id).await?;
 
         let dispatcher = Self {
             client,
             app_state: app_state.clone(),
             provider: provider.clone(),
-            rate_limit_tx: Some(rate_limit_tx),
+            router_id: Some(router_id.clone()),
+            rate_limit_tx,
+            concurrency_limit: concurrency_limit(&app_state, &provider),
+            consecutive_timeouts: Arc::default(),
+            upstream_headers: upstream_headers(&app_state, &provider),
+            retry_budget: RetryBudget::default(),
         };
         let converter_registry = EndpointConverterRegistry::new(&model_mapper);
 
@@ -94,7 +154,11 @@ impl Dispatcher {
         Ok(ServiceBuilder::new()
             .layer(extensions_layer)
             .layer(ErrorHandlerLayer::new(app_state))
-            .layer(crate::middleware::mapper::Layer::new(converter_registry))
+            .layer(crate::middleware::mapper::Layer::new(
+                converter_registry,
+                api_translation,
+                normalize_params,
+            ))
             // other middleware: rate limiting, logging, etc, etc
             // will be added here as well
             .service(dispatcher))
@@ -110,7 +174,43 @@ impl Dispatcher {
             app_state.clone(),
             router_config.clone(),
         );
-        Self::new_inner(app_state, router_id, provider, model_mapper).await
+        let rate_limit_tx = app_state.get_rate_limit_tx(router_id).await?;
+        Self::new_inner(
+            app_state,
+            router_id,
+            provider,
+            model_mapper,
+            router_config.api_translation,
+            !router_config.strict_params,
+            Some(rate_limit_tx),
+        )
+        .await
+    }
+
+    /// Like [`Dispatcher::new`], but the provider's rate limits are not
+    /// reported to the router's rate limit monitor. Used for requests the
+    /// gateway sends on its own behalf, which shouldn't take the provider
+    /// out of the router's rotation.
+    pub async fn new_without_rate_limit_events(
+        app_state: AppState,
+        router_id: &RouterId,
+        router_config: &Arc<RouterConfig>,
+        provider: InferenceProvider,
+    ) -> Result<DispatcherService, InitError> {
+        let model_mapper = ModelMapper::new_for_router(
+            app_state.clone(),
+            router_config.clone(),
+        );
+        Self::new_inner(
+            app_state,
+            router_id,
+            provider,
+            model_mapper,
+            router_config.api_translation,
+            !router_config.strict_params,
+            None,
+        )
+        .await
     }
 
     pub async fn new_with_model_id(
@@ -125,7 +225,17 @@ impl Dispatcher {
             router_config.clone(),
             model_id,
         );
-        Self::new_inner(app_state, router_id, provider, model_mapper).await
+        let rate_limit_tx = app_state.get_rate_limit_tx(router_id).await?;
+        Self::new_inner(
+            app_state,
+            router_id,
+            provider,
+            model_mapper,
+            router_config.api_translation,
+            !router_config.strict_params,
+            Some(rate_limit_tx),
+        )
+        .await
     }
 
     pub async fn new_direct_proxy(
@@ -138,7 +248,12 @@ impl Dispatcher {
             client,
             app_state: app_state.clone(),
             provider: provider.clone(),
+            router_id: None,
             rate_limit_tx: None,
+            concurrency_limit: concurrency_limit(&app_state, provider),
+            consecutive_timeouts: Arc::default(),
+            upstream_headers: upstream_headers(&app_state, provider),
+            retry_budget: RetryBudget::default(),
         };
         let model_mapper = ModelMapper::new(app_state.clone());
         let converter_registry = EndpointConverterRegistry::new(&model_mapper);
@@ -151,7 +266,12 @@ impl Dispatcher {
         Ok(ServiceBuilder::new()
             .layer(extensions_layer)
             .layer(ErrorHandlerLayer::new(app_state))
-            .layer(crate::middleware::mapper::Layer::new(converter_registry))
+            // direct proxy requests are sent as is
+            .layer(crate::middleware::mapper::Layer::new(
+                converter_registry,
+                None,
+                false,
+            ))
             // other middleware: rate limiting, logging, etc, etc
             // will be added here as well
             .service(dispatcher))
@@ -167,7 +287,12 @@ impl Dispatcher {
             client,
             app_state: app_state.clone(),
             provider: provider.clone(),
+            router_id: None,
             rate_limit_tx: None,
+            concurrency_limit: concurrency_limit(&app_state, provider),
+            consecutive_timeouts: Arc::default(),
+            upstream_headers: upstream_headers(&app_state, provider),
+            retry_budget: RetryBudget::default(),
         };
 
         let extensions_layer = AddExtensionsLayer::builder()
@@ -191,18 +316,47 @@ impl Service<Request> for Dispatcher {
 
     fn poll_ready(
         &mut self,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
+        // a saturated provider is not ready, so the balancer will pick a
+        // different provider instead of queueing the request
+        if let Some(concurrency_limit) = self.concurrency_limit.as_mut() {
+            std::task::ready!(concurrency_limit.poll_capacity(cx));
+        }
         Poll::Ready(Ok(()))
     }
 
-    #[tracing::instrument(name = "dispatcher", skip_all)]
     fn call(&mut self, req: Request) -> Self::Future {
         // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
         let this = self.clone();
         let this = std::mem::replace(self, this);
-        tracing::trace!(provider = ?this.provider, "dispatcher received request");
-        Box::pin(async move { this.dispatch(req).await })
+        let span = info_span!(
+            "dispatcher",
+            provider = %this.provider,
+            router_id = tracing::field::Empty,
+        );
+        if let Some(router_id) = req.extensions().get::<RouterId>() {
+            span.record("router_id", tracing::field::display(router_id));
+        }
+        Box::pin(
+            async move {
+                tracing::trace!(provider = ?this.provider, "dispatcher received request");
+                let permit = match this.concurrency_limit.as_ref() {
+                    Some(concurrency_limit) => Some(
+                        concurrency_limit
+                            .acquire()
+                            .await
+                            .ok_or(ApiError::ProvidersSaturated)?,
+                    ),
+                    None => None,
+                };
+                let result = this.dispatch(req).await;
+                // release the slot once the provider has responded
+                drop(permit);
+                result
+            }
+            .instrument(span),
+        )
     }
 }
 
@@ -212,9 +366,36 @@ impl Dispatcher {
         &self,
         mut req: Request,
     ) -> Result<http::Response<crate::types::body::Body>, ApiError> {
+        let circuit_breakers = &self.app_state.0.circuit_breakers;
+        // load balancers stop routing to the provider once the health
+        // monitor sees the open circuit, this rejects requests until then
+        if !circuit_breakers.allows(&self.provider) {
+            return Err(ApiError::CircuitOpen(self.provider.clone()));
+        }
+        // rather than sending the provider requests it already said it would
+        // reject, which may also extend its rate limit
+        if let Some(router_id) = &self.router_id
+            && let Some(retry_after) = self
+                .app_state
+                .0
+                .provider_backoffs
+                .remaining(router_id, &self.provider)
+        {
+            return Err(ApiError::ProviderBackingOff {
+                provider: self.provider.clone(),
+                retry_after,
+            });
+        }
+        let canary_arm = req.extensions().get::<CanaryArm>().copied();
+        let is_provider_override =
+            req.extensions().get::<ProviderOverride>().is_some();
+        let evaluation_score =
+            req.extensions().get::<EvaluationScore>().cloned();
+        let is_priority_failover =
+            req.extensions().get::<PriorityFailover>().is_some();
         // Extract request context and extensions
         let (
-            mapper_ctx,
+            mut mapper_ctx,
             req_ctx,
             api_endpoint,
             extracted_path_and_query,
@@ -227,41 +408,95 @@ impl Dispatcher {
 
         let auth_ctx = req_ctx.auth_context.as_ref();
         let target_provider = &self.provider;
+        // priority routers retry transient errors on the next provider in
+        // their chain rather than on the same provider
+        let retry_config = if is_priority_failover {
+            None
+        } else {
+            get_retry_config(&self.app_state, request_kind, &req_ctx)
+        };
         {
             let h = req.headers_mut();
             h.remove(http::header::HOST);
             h.remove(http::header::AUTHORIZATION);
+            // callers' Anthropic keys, since the configured provider key is
+            // injected when authenticating the request
+            h.remove(ANTHROPIC_API_KEY_HEADER);
             h.remove(http::header::CONTENT_LENGTH);
             h.remove(HeaderName::from_str("helicone-api-key").unwrap());
+        }
+        let method = req.method().clone();
+        let headers = req.headers().clone();
+        let upstream_request_headers = {
+            let mut h = self.forwarded_request_headers(&req_ctx, &headers);
             // TODO: properly support accept encoding
-            h.remove(http::header::ACCEPT_ENCODING);
             h.insert(
                 http::header::ACCEPT_ENCODING,
                 HeaderValue::from_static("identity"),
             );
-        }
-        let method = req.method().clone();
-        let headers = req.headers().clone();
+            // beta features such as prompt caching are only understood by
+            // Anthropic, and other providers may reject unknown betas
+            if *target_provider != InferenceProvider::Anthropic {
+                h.remove(ANTHROPIC_BETA_HEADER);
+            }
+            for (name, value) in self.upstream_headers.iter() {
+                h.insert(name, value.clone());
+            }
+            // replaces any incoming `traceparent` so the provider's spans are
+            // parented to this dispatch rather than to the caller
+            telemetry::tracing::inject_current_context(&mut h);
+            h
+        };
         let target_url = self.build_target_url(
             &req_ctx,
             target_provider,
             extracted_path_and_query.as_str(),
         )?;
-        // TODO: could change request type of dispatcher to
-        // http::Request<reqwest::Body>
-        // to avoid collecting the body twice
-        let req_body_bytes = req
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
-            .to_bytes();
+        // multipart uploads are streamed to the provider rather than
+        // collected, and summarized for the logger as they are forwarded
+        let (req_body_bytes, upload) = if let Some(boundary) =
+            multipart::boundary(&headers)
+        {
+            let file_prefix_bytes = match self.app_state.config().minio.audio {
+                AudioStorage::Skip => None,
+                AudioStorage::Truncate { max_bytes } => Some(max_bytes),
+            };
+            let (body, multipart_metadata) = multipart::tee(
+                req.into_body().into_data_stream(),
+                &boundary,
+                file_prefix_bytes,
+            );
+            (
+                Bytes::new(),
+                Some((reqwest::Body::wrap_stream(body), multipart_metadata)),
+            )
+        } else {
+            // TODO: could change request type of dispatcher to
+            // http::Request<reqwest::Body>
+            // to avoid collecting the body twice
+            let req_body_bytes = req
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            (req_body_bytes, None)
+        };
+        let (upload, multipart_metadata) = upload.unzip();
+        // direct proxies don't deserialize the body before it gets here
+        if matches!(request_kind, RequestKind::DirectProxy)
+            && api_endpoint.is_some()
+        {
+            mapper_ctx.is_stream =
+                serde_json::from_slice::<StreamRequest>(&req_body_bytes)
+                    .is_ok_and(|req| req.stream);
+        }
 
         let request_builder = self
             .client
             .as_ref()
             .request(method.clone(), target_url.clone())
-            .headers(headers.clone());
+            .headers(upstream_request_headers);
 
         let request_builder = self
             .client
@@ -284,31 +519,92 @@ impl Dispatcher {
             endpoint_metrics.incr_req_count();
         }
 
-        let (mut client_response, response_body_for_logger, tfft_rx): (
-            http::Response<crate::types::body::Body>,
-            crate::types::body::BodyReader,
-            oneshot::Receiver<()>,
-        ) = if mapper_ctx.is_stream {
-            dispatch_stream_with_retry(
-                &self.app_state,
-                request_builder,
-                req_body_bytes.clone(),
-                api_endpoint.clone(),
-                metrics_for_stream,
-                &req_ctx,
-                request_kind,
+        let is_dry_run = self.is_dry_run(&req_ctx);
+        let timeouts = self.timeouts(&req_ctx);
+        let idle_timeout = IdleTimeout {
+            duration: timeouts.stream_idle(),
+            provider: self.provider.clone(),
+            metrics: self.app_state.0.metrics.clone(),
+        };
+        let request_timeout = timeouts.request();
+        let response_header_filter = self.response_header_filter(&req_ctx);
+        let dispatched = if is_dry_run {
+            tracing::debug!("dry run, not calling the provider");
+            dry_run::response(&mapper_ctx)
+        } else if let Some(upload) = upload {
+            // a streamed upload can't be replayed, so it is only sent once
+            self.with_timeout(
+                request_timeout,
+                Self::dispatch_sync(&request_builder, upload),
             )
-            .await?
+            .instrument(info_span!("dispatch_sync"))
+            .await
+        } else if mapper_ctx.is_stream {
+            let framing = stream_framing(api_endpoint.as_ref(), request_kind);
+            dispatch_with_retry(
+                || {
+                    self.with_timeout(
+                        request_timeout,
+                        Self::dispatch_stream(
+                            &request_builder,
+                            req_body_bytes.clone(),
+                            api_endpoint.clone(),
+                            framing,
+                            metrics_for_stream.clone(),
+                            idle_timeout.clone(),
+                            response_header_filter,
+                        ),
+                    )
+                },
+                retry_config,
+                &self.retry_budget,
+            )
+            .await
         } else {
-            self.dispatch_sync_with_retry(
-                request_builder,
-                req_body_bytes.clone(),
-                &req_ctx,
-                request_kind,
+            dispatch_with_retry(
+                || {
+                    self.with_timeout(
+                        request_timeout,
+                        Self::dispatch_sync(
+                            &request_builder,
+                            req_body_bytes.clone(),
+                        ),
+                    )
+                },
+                retry_config,
+                &self.retry_budget,
             )
             .instrument(info_span!("dispatch_sync"))
-            .await?
+            .await
         };
+        let dispatched = match dispatched {
+            Err(ApiError::ProviderTimeout { provider, kind }) => {
+                circuit_breakers.record(&self.provider, true);
+                self.handle_timeout(api_endpoint, kind).await;
+                return Err(ApiError::ProviderTimeout { provider, kind });
+            }
+            dispatched => dispatched,
+        };
+        let (mut client_response, response_body_for_logger, tfft_rx): (
+            http::Response<crate::types::body::Body>,
+            crate::types::body::BodyReader,
+            oneshot::Receiver<()>,
+        ) = dispatched.inspect_err(|error| {
+            // client errors from a stream are the provider working as intended
+            let is_failure = match error {
+                ApiError::StreamError(error) => error.is_retryable(),
+                _ => true,
+            };
+            circuit_breakers.record(&self.provider, is_failure);
+        })?;
+        // a dry run says nothing about the provider's health
+        if !is_dry_run {
+            circuit_breakers.record(
+                &self.provider,
+                client_response.status().is_server_error(),
+            );
+            self.consecutive_timeouts.store(0, Ordering::Relaxed);
+        }
         tracing::info!(
             method = %method,
             target_url = %target_url,
@@ -340,6 +636,15 @@ impl Dispatcher {
 
         let response_status = client_response.status();
         let response_headers = client_response.headers();
+        if response_status.is_success()
+            && let Some(api_endpoint) = api_endpoint.clone()
+        {
+            self.report_remaining_capacity(
+                &req_ctx,
+                response_headers,
+                api_endpoint,
+            );
+        }
         self.handle_error_and_rate_limiting(
             response_status,
             response_headers,
@@ -355,13 +660,26 @@ impl Dispatcher {
             target_url,
             headers,
             req_body_bytes,
+            multipart_metadata,
             &client_response,
             response_body_for_logger,
             tfft_rx,
             &mapper_ctx,
             router_id,
+            canary_arm,
+            is_provider_override,
+            evaluation_score,
         );
 
+        // after the rate limit headers are read and the response is logged.
+        // Streams are filtered when dispatched, and dry run headers are the
+        // gateway's own.
+        if !mapper_ctx.is_stream && !is_dry_run {
+            let forwarded = response_header_filter
+                .response_headers(client_response.headers());
+            *client_response.headers_mut() = forwarded;
+        }
+
         Ok(client_response)
     }
 
@@ -443,6 +761,184 @@ impl Dispatcher {
         ))
     }
 
+    /// Whether to respond with a synthetic completion instead of calling the
+    /// provider, either for every request or for the request's router.
+    fn is_dry_run(&self, req_ctx: &RequestContext) -> bool {
+        self.app_state.config().dispatcher.dry_run
+            || req_ctx
+                .router_config
+                .as_ref()
+                .is_some_and(|router_config| router_config.dry_run)
+    }
+
+    /// The client's headers which are sent to the provider, along with those
+    /// set by the router's transforms.
+    fn forwarded_request_headers(
+        &self,
+        req_ctx: &RequestContext,
+        headers: &HeaderMap,
+    ) -> HeaderMap {
+        let router_config = req_ctx.router_config.as_deref();
+        let filter = router_config
+            .and_then(|router_config| router_config.request_headers.as_ref())
+            .unwrap_or(&self.app_state.config().request_headers);
+        let mut forwarded = filter.request_headers(headers);
+        let transforms = router_config
+            .iter()
+            .flat_map(|router_config| &router_config.transforms);
+        for rule in transforms {
+            if let TransformRule::SetHeader { name, .. } = rule
+                && let Ok(name) = HeaderName::from_str(name)
+                && let Some(value) = headers.get(&name)
+            {
+                forwarded.insert(name, value.clone());
+            }
+        }
+        forwarded
+    }
+
+    /// Which of the provider's headers are sent to the client.
+    fn response_header_filter<'a>(
+        &'a self,
+        req_ctx: &'a RequestContext,
+    ) -> &'a HeaderFilterConfig {
+        req_ctx
+            .router_config
+            .as_ref()
+            .and_then(|router_config| router_config.response_headers.as_ref())
+            .unwrap_or(&self.app_state.config().response_headers.forwarding)
+    }
+
+    /// The timeouts for the provider, falling back to the router's, then the
+    /// dispatcher's.
+    fn timeouts(&self, req_ctx: &RequestContext) -> TimeoutsConfig {
+        let config = self.app_state.config();
+        let provider_timeouts = config
+            .providers
+            .get(&self.provider)
+            .map(|provider_config| provider_config.timeouts)
+            .unwrap_or_default();
+        let router_timeouts = req_ctx
+            .router_config
+            .as_ref()
+            .map(|router_config| router_config.timeouts)
+            .unwrap_or_default();
+        provider_timeouts
+            .or(router_timeouts)
+            .or(config.dispatcher.timeouts)
+    }
+
+    /// Runs one attempt of a request, failing it with a
+    /// [`ApiError::ProviderTimeout`] if the provider doesn't respond within
+    /// `timeout`. Dropping the attempt on timeout aborts the upstream request.
+    async fn with_timeout(
+        &self,
+        timeout: Duration,
+        attempt: impl Future<Output = Result<Dispatched, ApiError>>,
+    ) -> Result<Dispatched, ApiError> {
+        let kind = match tokio::time::timeout(timeout, attempt).await {
+            Ok(Err(error)) if is_connect_timeout(&error) => {
+                TimeoutKind::Connect
+            }
+            Ok(dispatched) => return dispatched,
+            Err(_elapsed) => TimeoutKind::Request,
+        };
+        tracing::debug!(kind = kind.as_ref(), "upstream attempt timed out");
+        Err(ApiError::ProviderTimeout {
+            provider: self.provider.clone(),
+            kind,
+        })
+    }
+
+    /// Counts the timeout, deprioritizing the provider once it has timed out
+    /// repeatedly.
+    async fn handle_timeout(
+        &self,
+        api_endpoint: Option<ApiEndpoint>,
+        kind: TimeoutKind,
+    ) {
+        record_timeout(&self.app_state.0.metrics, &self.provider, kind);
+        let timeouts =
+            self.consecutive_timeouts.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::info!(
+            provider = ?self.provider,
+            kind = kind.as_ref(),
+            consecutive_timeouts = timeouts,
+            "Provider timed out"
+        );
+        if timeouts < TIMEOUTS_BEFORE_DEPRIORITIZING {
+            return;
+        }
+        self.consecutive_timeouts.store(0, Ordering::Relaxed);
+        if let Some(api_endpoint) = api_endpoint
+            && let Some(rate_limit_tx) = &self.rate_limit_tx
+        {
+            tracing::info!(
+                provider = ?self.provider,
+                api_endpoint = ?api_endpoint,
+                "Provider repeatedly timed out, signaling monitor"
+            );
+            if let Err(e) = rate_limit_tx
+                .send(RateLimitEvent::new(api_endpoint, None))
+                .await
+            {
+                tracing::error!(error = %e, "failed to send rate limit event");
+            }
+        }
+    }
+
+    /// Reports the fraction of its rate limits the provider has remaining to
+    /// the rate limit monitor, for `provider-weighted` routers that shed
+    /// providers running out of capacity.
+    fn report_remaining_capacity(
+        &self,
+        req_ctx: &RequestContext,
+        response_headers: &HeaderMap,
+        api_endpoint: ApiEndpoint,
+    ) {
+        let Some(rate_limit_tx) = &self.rate_limit_tx else {
+            return;
+        };
+        if self
+            .app_state
+            .config()
+            .discover
+            .monitor
+            .rate_limit_headers
+            .is_none()
+        {
+            return;
+        }
+        let is_provider_weighted =
+            req_ctx.router_config.as_ref().is_some_and(|router_config| {
+                let endpoint_type = router_config
+                    .load_balance
+                    .balanced_endpoint_type(api_endpoint.endpoint_type());
+                matches!(
+                    router_config.load_balance.0.get(&endpoint_type),
+                    Some(BalanceConfigInner::ProviderWeighted { .. })
+                )
+            });
+        if !is_provider_weighted {
+            return;
+        }
+        let Some(remaining_capacity) =
+            rate_limit_headers::remaining_capacity(response_headers)
+        else {
+            return;
+        };
+        // reported on every response, so rather than waiting for the monitor
+        // the report is dropped if it's busy
+        if let Err(e) =
+            rate_limit_tx.try_send(RateLimitEvent::remaining_capacity(
+                api_endpoint,
+                remaining_capacity,
+            ))
+        {
+            tracing::debug!(error = %e, "failed to report remaining capacity");
+        }
+    }
+
     /// Handles error responses and rate limiting
     async fn handle_error_and_rate_limiting(
         &self,
@@ -460,8 +956,20 @@ impl Dispatcher {
                 endpoint_metrics.incr_remote_internal_error_count();
             }
         } else if response_status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = extract_retry_after(response_headers);
+            // only the router's own traffic backs off, rather than requests
+            // the gateway sends on its own behalf
+            if let Some(retry_after) = retry_after
+                && let Some(router_id) = &self.router_id
+                && self.rate_limit_tx.is_some()
+            {
+                self.app_state.0.provider_backoffs.back_off(
+                    router_id,
+                    &self.provider,
+                    Duration::from_secs(retry_after),
+                );
+            }
             if let Some(ref api_endpoint) = api_endpoint {
-                let retry_after = extract_retry_after(response_headers);
                 tracing::info!(
                     provider = ?self.provider,
                     api_endpoint = ?api_endpoint,
@@ -495,16 +1003,34 @@ impl Dispatcher {
         target_url: url::Url,
         headers: HeaderMap,
         req_body_bytes: Bytes,
+        multipart_metadata: Option<oneshot::Receiver<MultipartMetadata>>,
         client_response: &http::Response<crate::types::body::Body>,
         response_body_for_logger: BodyReader,
         tfft_rx: oneshot::Receiver<()>,
         mapper_ctx: &MapperContext,
         router_id: Option<RouterId>,
+        canary_arm: Option<CanaryArm>,
+        is_provider_override: bool,
+        evaluation_score: Option<EvaluationScore>,
     ) {
         let deployment_target =
             self.app_state.config().deployment_target.clone();
-        if self.app_state.config().helicone.is_observability_enabled() {
+        let logging = req_ctx
+            .router_config
+            .as_ref()
+            .map(|router_config| router_config.logging)
+            .unwrap_or_default();
+        if self.app_state.config().helicone.is_observability_enabled()
+            && logging != RequestLogging::Off
+        {
             if let Some(auth_ctx) = req_ctx.auth_context.clone() {
+                let redaction = req_ctx
+                    .router_config
+                    .as_ref()
+                    .and_then(|router_config| router_config.redaction.clone())
+                    .unwrap_or_else(|| {
+                        self.app_state.config().redaction.clone()
+                    });
                 let response_logger = LoggerService::builder()
                     .app_state(self.app_state.clone())
                     .auth_ctx(auth_ctx)
@@ -513,6 +1039,7 @@ impl Dispatcher {
                     .target_url(target_url)
                     .request_headers(headers)
                     .request_body(req_body_bytes)
+                    .multipart_metadata(multipart_metadata)
                     .response_status(client_response.status())
                     .response_body(response_body_for_logger)
                     .provider(self.provider.clone())
@@ -520,10 +1047,15 @@ impl Dispatcher {
                     .mapper_ctx(mapper_ctx.clone())
                     .router_id(router_id)
                     .deployment_target(deployment_target)
+                    .canary_arm(canary_arm)
+                    .is_provider_override(is_provider_override)
+                    .evaluation_score(evaluation_score)
+                    .logging(logging)
+                    .redaction(redaction)
                     .build();
 
                 let app_state = self.app_state.clone();
-                tokio::spawn(
+                self.app_state.0.logging_tasks.spawn(
                     async move {
                         if let Err(e) = response_logger.log().await {
                             let error_str = e.as_ref().to_string();
@@ -573,36 +1105,84 @@ impl Dispatcher {
         extracted_path_and_query: &str,
     ) -> Result<url::Url, ApiError> {
         let config = self.app_state.config();
-        if let Some(router_config) = req_ctx.router_config.as_ref()
+        let base_url = if let Some(router_config) =
+            req_ctx.router_config.as_ref()
             && let Some(router_provider_config) =
                 router_config.providers.as_ref()
             && let Some(provider_config) =
                 router_provider_config.get(target_provider)
         {
-            return Ok(provider_config
+            &provider_config.base_url
+        } else {
+            &config
+                .providers
+                .get(target_provider)
+                .ok_or_else(|| {
+                    InternalError::ProviderNotConfigured(
+                        target_provider.clone(),
+                    )
+                })?
                 .base_url
-                .join(extracted_path_and_query)
-                .expect(
-                    "PathAndQuery joined with valid url will always succeed",
-                ));
+        };
+        let mut url = if *target_provider == InferenceProvider::VertexAI {
+            // vertex serves each project and location under its own path,
+            // which startup validation ensures can be built
+            let provider_config =
+                config.providers.get(target_provider).ok_or_else(|| {
+                    InternalError::ProviderNotConfigured(
+                        target_provider.clone(),
+                    )
+                })?;
+            let project =
+                provider_config.project.as_deref().ok_or_else(|| {
+                    InternalError::ProviderNotConfigured(
+                        target_provider.clone(),
+                    )
+                })?;
+            let location = provider_config
+                .region
+                .as_deref()
+                .unwrap_or(vertex::DEFAULT_VERTEX_LOCATION);
+            base_url
+                .join(&vertex::project_path(project, location))
+                .and_then(|project_url| {
+                    project_url
+                        .join(extracted_path_and_query.trim_start_matches('/'))
+                })
+                .map_err(|_| {
+                    InternalError::ProviderNotConfigured(
+                        target_provider.clone(),
+                    )
+                })?
+        } else {
+            base_url.join(extracted_path_and_query).expect(
+                "PathAndQuery joined with valid url will always succeed",
+            )
+        };
+        if *target_provider == InferenceProvider::AzureOpenAI
+            && !url.query_pairs().any(|(key, _)| key == AZURE_API_VERSION)
+        {
+            let api_version = config
+                .providers
+                .get(target_provider)
+                .and_then(|provider_config| provider_config.version.as_deref())
+                .unwrap_or(DEFAULT_AZURE_API_VERSION);
+            url.query_pairs_mut()
+                .append_pair(AZURE_API_VERSION, api_version);
         }
-        let provider_config =
-            config.providers.get(target_provider).ok_or_else(|| {
-                InternalError::ProviderNotConfigured(target_provider.clone())
-            })?;
-        Ok(provider_config
-            .base_url
-            .join(extracted_path_and_query)
-            .expect("PathAndQuery joined with valid url will always succeed"))
+        Ok(url)
     }
 
-    /// We take a `&RequestBuilder` so that `dispatch_stream` implements `FnMut`
-    /// so we can use the [`backon`] crate for retries.
+    /// We take a `&RequestBuilder` so that `dispatch_stream` can be called
+    /// once per attempt when retrying.
     async fn dispatch_stream(
         request_builder: &RequestBuilder,
         req_body_bytes: Bytes,
         api_endpoint: Option<ApiEndpoint>,
+        framing: Option<StreamFraming>,
         metrics_registry: EndpointMetricsRegistry,
+        idle_timeout: IdleTimeout,
+        response_header_filter: &HeaderFilterConfig,
     ) -> Result<
         (
             http::Response<crate::types::body::Body>,
@@ -619,19 +1199,40 @@ impl Dispatcher {
             );
             ApiError::Internal(InternalError::Internal)
         })?;
-        let response_stream = Client::sse_stream(
-            request_builder,
-            req_body_bytes,
-            api_endpoint,
-            &metrics_registry,
-        )
-        .await?;
+        let (provider_headers, response_stream) = if let Some(framing) = framing
+        {
+            Client::framed_stream(
+                request_builder,
+                req_body_bytes,
+                framing,
+                api_endpoint,
+                &metrics_registry,
+                idle_timeout,
+            )
+            .await?
+        } else {
+            Client::sse_stream(
+                request_builder,
+                req_body_bytes,
+                api_endpoint,
+                &metrics_registry,
+                idle_timeout,
+            )
+            .await?
+        };
+        let mut headers =
+            response_header_filter.response_headers(&provider_headers);
+        // the stream is re-framed as SSE, so the gateway's stream headers
+        // replace the provider's
+        headers.extend(stream_response_headers());
         let mut resp_builder = http::Response::builder();
-        *resp_builder.headers_mut().unwrap() = stream_response_headers();
+        *resp_builder.headers_mut().unwrap() = headers;
         resp_builder = resp_builder.status(StatusCode::OK);
 
+        // raw events are already framed, for the client and the logger
+        let append_newlines = framing != Some(StreamFraming::RawSse);
         let (user_resp_body, body_reader, tfft_rx) =
-            BodyReader::wrap_stream(response_stream, true);
+            BodyReader::wrap_stream(response_stream, append_newlines);
 
         let response = resp_builder
             .body(user_resp_body)
@@ -641,7 +1242,7 @@ impl Dispatcher {
 
     async fn dispatch_sync(
         request_builder: &RequestBuilder,
-        req_body_bytes: Bytes,
+        req_body: impl Into<reqwest::Body>,
     ) -> Result<
         (
             http::Response<crate::types::body::Body>,
@@ -659,7 +1260,7 @@ impl Dispatcher {
             ApiError::Internal(InternalError::Internal)
         })?;
         let response: reqwest::Response = request_builder
-            .body(req_body_bytes)
+            .body(req_body)
             .send()
             .await
             .map_err(InternalError::ReqwestError)?;
@@ -675,6 +1276,11 @@ impl Dispatcher {
                 response.text().await.map_err(InternalError::ReqwestError)?;
             tracing::debug!(status_code = %status, error_resp = %body, "received error response");
             let bytes = bytes::Bytes::from(body);
+            add_retry_after_from_body(
+                status,
+                resp_builder.headers_mut().unwrap(),
+                &bytes,
+            );
             let stream = futures::stream::once(futures::future::ok::<
                 _,
                 ApiError,
@@ -688,10 +1294,14 @@ impl Dispatcher {
             return Ok((response, error_reader, tfft_rx));
         }
 
+        let body = collect_sync_body(response).await?;
+        add_retry_after_from_body(
+            status,
+            resp_builder.headers_mut().unwrap(),
+            &body,
+        );
         let (user_resp_body, body_reader, tfft_rx) = BodyReader::wrap_stream(
-            response
-                .bytes_stream()
-                .map_err(|e| InternalError::ReqwestError(e).into()),
+            futures::stream::once(futures::future::ok::<_, ApiError>(body)),
             false,
         );
         let response = resp_builder
@@ -699,244 +1309,96 @@ impl Dispatcher {
             .map_err(InternalError::HttpError)?;
         Ok((response, body_reader, tfft_rx))
     }
+}
 
-    #[allow(clippy::too_many_lines)]
-    async fn dispatch_sync_with_retry(
-        &self,
-        request_builder: RequestBuilder,
-        req_body_bytes: Bytes,
-        req_ctx: &RequestContext,
-        request_kind: RequestKind,
-    ) -> Result<
-        (
-            http::Response<crate::types::body::Body>,
-            crate::types::body::BodyReader,
-            oneshot::Receiver<()>,
-        ),
-        ApiError,
-    > {
-        let retry_config =
-            get_retry_config(&self.app_state, request_kind, req_ctx);
-        if let Some(retry_config) = retry_config {
-            match retry_config {
-                RetryConfig::Exponential {
-                    min_delay,
-                    max_delay,
-                    max_retries,
-                    factor,
-                } => {
-                    let retry_strategy = ExponentialBuilder::default()
-                        .with_max_delay(*max_delay)
-                        .with_min_delay(*min_delay)
-                        .with_max_times(usize::from(*max_retries))
-                        .with_factor(factor.to_f32().unwrap_or(
-                            crate::config::retry::DEFAULT_RETRY_FACTOR,
-                        ))
-                        .with_jitter()
-                        .build();
-                    let future_fn = || async {
-                        let result = Self::dispatch_sync(
-                            &request_builder,
-                            req_body_bytes.clone(),
-                        )
-                        .await?;
-
-                        Ok(result)
-                    };
-
-                    crate::utils::retry::RetryWithResult::new(future_fn, retry_strategy)
-                    .when(|result: &Result<_, _>| match result {
-                        Ok(response) => response.0.status().is_server_error(),
-                        Err(e) => match e {
-                            ApiError::Internal(InternalError::ReqwestError(
-                                reqwest_error,
-                            )) => reqwest_error.is_connect() || reqwest_error.status().is_some_and(|s| s.is_server_error()),
-                            _ => false,
-                        },
-                    })
-                    .notify(|result: &Result<_, _>, dur: Duration| match result {
-                        Ok(result) if result.0.status().is_server_error() => {
-                                tracing::warn!(
-                                    error = %result.0.status(),
-                                    retry_in = ?dur,
-                                    "got error dispatching sync request, retrying...",
-                                );
-                        }
-                        Err(ApiError::Internal(InternalError::ReqwestError(
-                            reqwest_error,
-                        ))) if reqwest_error.is_connect() || reqwest_error.status().is_some_and(|s| s.is_server_error()) => {
-                                tracing::warn!(
-                                    error = %reqwest_error,
-                                    retry_in = ?dur,
-                                    "got error dispatching sync request, retrying...",
-                                );
-                            }
-                        _ => {}
-                    })
-                    .await
-                }
-                RetryConfig::Constant { delay, max_retries } => {
-                    let retry_strategy = ConstantBuilder::default()
-                        .with_delay(*delay)
-                        .with_max_times(usize::from(*max_retries))
-                        .with_jitter()
-                        .build();
-                    let future_fn = || async {
-                        Self::dispatch_sync(
-                            &request_builder,
-                            req_body_bytes.clone(),
-                        )
-                        .await
-                    };
-
-                    crate::utils::retry::RetryWithResult::new(future_fn, retry_strategy)
-                    .when(|result: &Result<_, _>| match result {
-                        Ok(response) => response.0.status().is_server_error(),
-                        Err(e) => match e {
-                            ApiError::Internal(InternalError::ReqwestError(
-                                reqwest_error,
-                            )) => reqwest_error.is_connect() || reqwest_error.status().is_some_and(|s| s.is_server_error()),
-                            _ => false,
-                        },
-                    })
-                    .notify(|result: &Result<_, _>, dur: Duration| match result {
-                        Ok(result) if result.0.status().is_server_error() => {
-                                tracing::warn!(
-                                    error = %result.0.status(),
-                                    retry_in = ?dur,
-                                    "got error dispatching sync request, retrying...",
-                                );
-                        }
-                        Err(ApiError::Internal(InternalError::ReqwestError(
-                            reqwest_error,
-                        ))) if reqwest_error.is_connect() || reqwest_error.status().is_some_and(|s| s.is_server_error()) => {
-                                tracing::warn!(
-                                    error = %reqwest_error,
-                                    retry_in = ?dur,
-                                    "got error dispatching sync request, retrying...",
-                                );
-                            }
-                        _ => {}
-                    })
-                    .await
-                }
-            }
+/// Buffer a non-streaming response body, detecting bodies that were truncated
+/// by the provider, e.g. when the connection was dropped mid-response.
+async fn collect_sync_body(
+    response: reqwest::Response,
+) -> Result<Bytes, ApiError> {
+    let expected_len = response.content_length();
+    let is_json = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("json"));
+    let body = response.bytes().await.map_err(|e| {
+        if e.is_body() || e.is_decode() {
+            InternalError::TruncatedResponse(e.to_string())
         } else {
-            Self::dispatch_sync(&request_builder, req_body_bytes.clone()).await
+            InternalError::ReqwestError(e)
         }
+    })?;
+
+    if let Some(expected_len) = expected_len
+        && u64::try_from(body.len()).ok() != Some(expected_len)
+    {
+        return Err(InternalError::TruncatedResponse(format!(
+            "expected {expected_len} bytes, got {}",
+            body.len()
+        ))
+        .into());
+    }
+    if is_json
+        && let Err(e) = serde_json::from_slice::<serde::de::IgnoredAny>(&body)
+        && e.is_eof()
+    {
+        return Err(InternalError::TruncatedResponse(e.to_string()).into());
     }
+
+    Ok(body)
 }
 
-async fn dispatch_stream_with_retry(
+fn concurrency_limit(
     app_state: &AppState,
-    request_builder: RequestBuilder,
-    req_body_bytes: Bytes,
-    api_endpoint: Option<ApiEndpoint>,
-    metrics_registry: EndpointMetricsRegistry,
-    request_ctx: &RequestContext,
-    request_kind: RequestKind,
-) -> Result<
-    (
-        http::Response<crate::types::body::Body>,
-        crate::types::body::BodyReader,
-        oneshot::Receiver<()>,
-    ),
-    ApiError,
-> {
-    let retry_config = get_retry_config(app_state, request_kind, request_ctx);
-
-    if let Some(retry_config) = retry_config {
-        match retry_config {
-            RetryConfig::Exponential {
-                min_delay,
-                max_delay,
-                max_retries,
-                factor,
-            } => {
-                let retry_strategy =
-                    ExponentialBuilder::default()
-                        .with_max_delay(*max_delay)
-                        .with_min_delay(*min_delay)
-                        .with_max_times(usize::from(*max_retries))
-                        .with_factor(factor.to_f32().unwrap_or(
-                            crate::config::retry::DEFAULT_RETRY_FACTOR,
-                        ))
-                        .with_jitter()
-                        .build();
-                (|| async {
-                    Dispatcher::dispatch_stream(
-                        &request_builder,
-                        req_body_bytes.clone(),
-                        api_endpoint.clone(),
-                        metrics_registry.clone(),
-                    )
-                    .await
-                })
-                .retry(retry_strategy)
-                .sleep(tokio::time::sleep)
-                .when(|e: &ApiError| match e {
-                    ApiError::StreamError(s) => s.is_retryable(),
-                    _ => false,
-                })
-                .notify(|err: &ApiError, dur: Duration| {
-                    if let ApiError::StreamError(_s) = err {
-                        tracing::warn!(
-                            error = %err,
-                            retry_in = ?dur,
-                            "upstream server error in stream, retrying...",
-                        );
-                    }
-                })
-                .await
-            }
-            RetryConfig::Constant { delay, max_retries } => {
-                let retry_strategy = ConstantBuilder::default()
-                    .with_delay(*delay)
-                    .with_max_times(usize::from(*max_retries))
-                    .with_jitter()
-                    .build();
-                (|| async {
-                    Dispatcher::dispatch_stream(
-                        &request_builder,
-                        req_body_bytes.clone(),
-                        api_endpoint.clone(),
-                        metrics_registry.clone(),
-                    )
-                    .await
-                })
-                .retry(retry_strategy)
-                .sleep(tokio::time::sleep)
-                .when(|e: &ApiError| match e {
-                    ApiError::StreamError(s) => s.is_retryable(),
-                    _ => false,
-                })
-                .notify(|err: &ApiError, dur: Duration| {
-                    if let ApiError::StreamError(_s) = err {
-                        tracing::warn!(
-                            error = %err,
-                            retry_in = ?dur,
-                            "upstream server error in stream, retrying...",
-                        );
-                    }
-                })
-                .await
-            }
+    provider: &InferenceProvider,
+) -> Option<ConcurrencyLimit> {
+    app_state
+        .0
+        .provider_concurrency_limits
+        .limit_for(provider, &app_state.0.metrics)
+}
+
+/// The dispatcher's `default_upstream_headers`, overridden by the provider's
+/// `upstream_headers`.
+fn upstream_headers(
+    app_state: &AppState,
+    provider: &InferenceProvider,
+) -> Arc<HeaderMap> {
+    let config = app_state.config();
+    let provider_headers = config
+        .providers
+        .get(provider)
+        .into_iter()
+        .flat_map(|provider_config| &provider_config.upstream_headers);
+    let mut headers = HeaderMap::new();
+    for (name, value) in config
+        .dispatcher
+        .default_upstream_headers
+        .iter()
+        .chain(provider_headers)
+    {
+        // these are rejected when the config is validated, but the
+        // provider's credentials must never be replaced
+        if is_protected_upstream_header(name) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) =
+            (HeaderName::from_str(name), HeaderValue::from_str(value))
+        {
+            headers.insert(name, value);
         }
-    } else {
-        Dispatcher::dispatch_stream(
-            &request_builder,
-            req_body_bytes.clone(),
-            api_endpoint,
-            metrics_registry,
-        )
-        .await
     }
+    Arc::new(headers)
 }
 
-fn extract_retry_after(headers: &HeaderMap) -> Option<u64> {
-    let retry_after_str = headers
+pub(super) fn extract_retry_after(headers: &HeaderMap) -> Option<u64> {
+    let Some(retry_after_str) = headers
         .get(http::header::RETRY_AFTER)
-        .and_then(|v| v.to_str().ok())?;
+        .and_then(|v| v.to_str().ok())
+    else {
+        return extract_retry_after_ms(headers);
+    };
 
     // First try to parse as seconds (u64)
     if let Ok(seconds) = retry_after_str.parse::<u64>() {
@@ -959,7 +1421,82 @@ fn extract_retry_after(headers: &HeaderMap) -> Option<u64> {
         }
     }
 
-    None
+    extract_retry_after_ms(headers)
+}
+
+/// Azure OpenAI also sends the non-standard `retry-after-ms` header, in
+/// milliseconds. It's rounded up to whole seconds.
+fn extract_retry_after_ms(headers: &HeaderMap) -> Option<u64> {
+    let millis = headers
+        .get("retry-after-ms")
+        .and_then(|v| v.to_str().ok())?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(millis.div_ceil(1000))
+}
+
+/// Gemini signals when to retry a rate limited request in the error body
+/// rather than with a `Retry-After` header, so we add the header ourselves
+/// for the rate limit monitor and clients.
+fn add_retry_after_from_body(
+    status: StatusCode,
+    headers: &mut HeaderMap,
+    body: &[u8],
+) {
+    if status != StatusCode::TOO_MANY_REQUESTS
+        || headers.contains_key(http::header::RETRY_AFTER)
+    {
+        return;
+    }
+    let retry_delay = serde_json::from_slice::<GeminiApiError>(body)
+        .ok()
+        .and_then(|error| error.retry_delay());
+    if let Some(retry_delay) = retry_delay {
+        // round up so that we never retry early
+        let seconds =
+            retry_delay.as_secs() + u64::from(retry_delay.subsec_nanos() > 0);
+        headers.insert(http::header::RETRY_AFTER, HeaderValue::from(seconds));
+    }
+}
+
+/// The `stream` field of a direct proxy request body.
+#[derive(Deserialize)]
+struct StreamRequest {
+    #[serde(default)]
+    stream: bool,
+}
+
+/// How the stream is split into events when the provider doesn't stream SSE,
+/// or when the events are passed through rather than mapped.
+fn stream_framing(
+    api_endpoint: Option<&ApiEndpoint>,
+    request_kind: RequestKind,
+) -> Option<StreamFraming> {
+    match (api_endpoint, request_kind) {
+        // Bedrock streams AWS event stream messages rather than SSE
+        (Some(ApiEndpoint::Bedrock(_)), _) => {
+            Some(StreamFraming::AwsEventStream)
+        }
+        (_, RequestKind::DirectProxy) => Some(StreamFraming::RawSse),
+        _ => None,
+    }
+}
+
+/// Whether the provider didn't accept a connection within the connect
+/// timeout.
+fn is_connect_timeout(error: &ApiError) -> bool {
+    let error = match error {
+        ApiError::Internal(InternalError::ReqwestError(error)) => error,
+        ApiError::StreamError(StreamError::StreamError(error)) => {
+            match &**error {
+                reqwest_eventsource::Error::Transport(error) => error,
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+    error.is_connect() && error.is_timeout()
 }
 
 fn stream_response_headers() -> HeaderMap {
@@ -995,6 +1532,6 @@ fn get_retry_config<'a>(
         RequestKind::UnifiedApi => {
             app_state.config().unified_api.retries.as_ref()
         }
-        RequestKind::DirectProxy => None,
+        RequestKind::DirectProxy | RequestKind::Admin => None,
     }
 }
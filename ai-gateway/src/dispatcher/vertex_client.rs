@@ -0,0 +1,378 @@
+//! Vertex AI authenticates with short lived OAuth access tokens rather than
+//! API keys. Tokens are exchanged for a signed service account assertion, or
+//! fetched from the metadata server with workload identity, and cached until
+//! shortly before they expire.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use backon::{ExponentialBuilder, Retryable};
+use displaydoc::Display;
+use http::{HeaderMap, HeaderValue, StatusCode};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::ClientBuilder;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::{
+    app_state::AppState,
+    error::{
+        api::ApiError, auth::AuthError, init::InitError,
+        provider::ProviderError,
+    },
+    types::{
+        provider::{InferenceProvider, ProviderKey},
+        secret::Secret,
+    },
+    utils::host_header,
+};
+
+const TOKEN_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const JWT_BEARER_GRANT_TYPE: &str =
+    "urn:ietf:params:oauth:grant-type:jwt-bearer";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+/// Overrides the metadata server's host, as with Google's own clients.
+const METADATA_HOST_ENV: &str = "GCE_METADATA_HOST";
+const DEFAULT_METADATA_HOST: &str = "metadata.google.internal";
+const METADATA_TOKEN_PATH: &str =
+    "computeMetadata/v1/instance/service-accounts/default/token";
+/// The longest lifetime Google accepts for a service account assertion.
+const ASSERTION_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// Tokens are refreshed this long before they expire, so that a request is
+/// never sent with a token which expires in flight.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// Transient failures are retried this many times within a refresh.
+const REFRESH_RETRIES: usize = 2;
+const REFRESH_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// After a refresh fails, requests fail fast (or use the previous token
+/// while it's still valid) rather than each attempting another refresh.
+/// The wait doubles with each consecutive failure, up to the max.
+const MIN_REFRESH_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_REFRESH_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub(super) inner: reqwest::Client,
+    /// Shared by clones so that every request reuses the cached token.
+    tokens: Option<Arc<TokenSource>>,
+}
+
+impl Client {
+    pub fn new(
+        app_state: &AppState,
+        client_builder: ClientBuilder,
+        provider_key: Option<&ProviderKey>,
+    ) -> Result<Self, InitError> {
+        let config = app_state.config();
+        let provider_config = config
+            .providers
+            .get(&InferenceProvider::VertexAI)
+            .ok_or(ProviderError::ProviderNotConfigured(
+                InferenceProvider::VertexAI,
+            ))?;
+
+        let mut default_headers = HeaderMap::new();
+        default_headers
+            .insert(http::header::HOST, host_header(&provider_config.base_url));
+        default_headers.insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_str(mime::APPLICATION_JSON.essence_str())
+                .expect("application/json is always a valid header value"),
+        );
+        let inner = client_builder
+            .default_headers(default_headers)
+            .build()
+            .map_err(InitError::CreateReqwestClient)?;
+
+        let credentials = match provider_key {
+            Some(ProviderKey::GoogleServiceAccount(key)) => {
+                Some(Credentials::service_account(key)?)
+            }
+            Some(ProviderKey::GoogleWorkloadIdentity) => {
+                Some(Credentials::metadata_server()?)
+            }
+            _ => None,
+        };
+        // the provider client's default headers are for Vertex, so tokens
+        // are fetched with a separate client
+        let token_client = reqwest::Client::builder()
+            .connect_timeout(config.dispatcher.timeouts.connect())
+            .timeout(config.dispatcher.timeouts.request())
+            .build()
+            .map_err(InitError::CreateReqwestClient)?;
+        let tokens = credentials.map(|credentials| {
+            Arc::new(TokenSource {
+                credentials,
+                client: token_client,
+                state: Mutex::default(),
+            })
+        });
+
+        Ok(Self { inner, tokens })
+    }
+
+    pub async fn set_auth_header(
+        &self,
+        request_builder: reqwest::RequestBuilder,
+    ) -> Result<reqwest::RequestBuilder, ApiError> {
+        let tokens = self
+            .tokens
+            .as_ref()
+            .ok_or(ApiError::Authentication(AuthError::ProviderKeyNotFound))?;
+        let token = tokens.access_token().await?;
+        Ok(request_builder.bearer_auth(token.expose()))
+    }
+}
+
+enum Credentials {
+    ServiceAccount {
+        client_email: String,
+        key: EncodingKey,
+        token_uri: Url,
+    },
+    MetadataServer {
+        token_url: Url,
+    },
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ServiceAccount {
+                client_email,
+                token_uri,
+                ..
+            } => f
+                .debug_struct("ServiceAccount")
+                .field("client_email", client_email)
+                .field("token_uri", token_uri)
+                .finish_non_exhaustive(),
+            Self::MetadataServer { token_url } => f
+                .debug_struct("MetadataServer")
+                .field("token_url", token_url)
+                .finish(),
+        }
+    }
+}
+
+/// The fields we use from a service account key.
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default)]
+    token_uri: Option<String>,
+}
+
+impl Credentials {
+    fn service_account(key: &Secret<String>) -> Result<Self, InitError> {
+        let key = serde_json::from_str::<ServiceAccountKey>(key.expose())
+            .map_err(|e| InitError::InvalidServiceAccount(e.to_string()))?;
+        let token_uri =
+            Url::parse(key.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI))
+                .map_err(|e| InitError::InvalidServiceAccount(e.to_string()))?;
+        let private_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+            .map_err(|e| InitError::InvalidServiceAccount(e.to_string()))?;
+        Ok(Self::ServiceAccount {
+            client_email: key.client_email,
+            key: private_key,
+            token_uri,
+        })
+    }
+
+    fn metadata_server() -> Result<Self, InitError> {
+        let host = std::env::var(METADATA_HOST_ENV)
+            .unwrap_or_else(|_| DEFAULT_METADATA_HOST.to_string());
+        let token_url =
+            Url::parse(&format!("http://{host}/{METADATA_TOKEN_PATH}"))
+                .map_err(InitError::OAuthConfig)?;
+        Ok(Self::MetadataServer { token_url })
+    }
+}
+
+#[derive(Debug)]
+struct TokenSource {
+    credentials: Credentials,
+    client: reqwest::Client,
+    /// Locked for the duration of a refresh, so concurrent requests wait for
+    /// a single refresh rather than each starting their own.
+    state: Mutex<TokenState>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    token: Option<AccessToken>,
+    consecutive_failures: u32,
+    /// Refreshing isn't attempted again before this, after a failure.
+    retry_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+struct AccessToken {
+    value: Secret<String>,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Error, Display)]
+enum TokenError {
+    /// Failed to sign assertion: {0}
+    Sign(#[from] jsonwebtoken::errors::Error),
+    /// Token request failed: {0}
+    Request(#[from] reqwest::Error),
+    /// Token endpoint responded with {0}
+    Status(StatusCode),
+}
+
+impl TokenError {
+    /// Whether retrying could succeed. Client errors mean the credentials
+    /// were rejected.
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::Sign(_) => false,
+            Self::Request(_) => true,
+            Self::Status(status) => {
+                status.is_server_error()
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AssertionClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+impl TokenSource {
+    async fn access_token(&self) -> Result<Secret<String>, AuthError> {
+        let mut state = self.state.lock().await;
+        let now = Instant::now();
+        if let Some(token) = &state.token
+            && now + REFRESH_MARGIN < token.expires_at
+        {
+            return Ok(token.value.clone());
+        }
+        // a token inside the refresh margin is still usable if refreshing
+        // fails
+        let unexpired = state
+            .token
+            .as_ref()
+            .filter(|token| now < token.expires_at)
+            .map(|token| token.value.clone());
+        let refresh_failed =
+            AuthError::ProviderTokenRefresh(InferenceProvider::VertexAI);
+        if let Some(retry_at) = state.retry_at
+            && now < retry_at
+        {
+            return unexpired.ok_or(refresh_failed);
+        }
+
+        match self.refresh().await {
+            Ok(token) => {
+                let value = token.value.clone();
+                *state = TokenState {
+                    token: Some(token),
+                    ..TokenState::default()
+                };
+                Ok(value)
+            }
+            Err(e) => {
+                state.consecutive_failures += 1;
+                let backoff = MIN_REFRESH_BACKOFF
+                    .saturating_mul(
+                        2_u32.saturating_pow(state.consecutive_failures - 1),
+                    )
+                    .min(MAX_REFRESH_BACKOFF);
+                state.retry_at = Some(Instant::now() + backoff);
+                tracing::warn!(
+                    error = %e,
+                    consecutive_failures = state.consecutive_failures,
+                    backoff = ?backoff,
+                    "failed to refresh vertex access token"
+                );
+                unexpired.ok_or(refresh_failed)
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<AccessToken, TokenError> {
+        (|| self.fetch())
+            .retry(
+                ExponentialBuilder::default()
+                    .with_min_delay(REFRESH_RETRY_DELAY)
+                    .with_max_times(REFRESH_RETRIES),
+            )
+            .when(TokenError::is_transient)
+            .notify(|e: &TokenError, delay: Duration| {
+                tracing::debug!(error = %e, delay = ?delay, "retrying vertex token refresh");
+            })
+            .await
+    }
+
+    async fn fetch(&self) -> Result<AccessToken, TokenError> {
+        let request = match &self.credentials {
+            Credentials::ServiceAccount {
+                client_email,
+                key,
+                token_uri,
+            } => {
+                let assertion = assertion(client_email, key, token_uri)?;
+                self.client.post(token_uri.clone()).form(&[
+                    ("grant_type", JWT_BEARER_GRANT_TYPE),
+                    ("assertion", assertion.as_str()),
+                ])
+            }
+            Credentials::MetadataServer { token_url } => self
+                .client
+                .get(token_url.clone())
+                .header("metadata-flavor", "Google"),
+        };
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(TokenError::Status(status));
+        }
+        let token = response.json::<TokenResponse>().await?;
+        Ok(AccessToken {
+            value: Secret::from(token.access_token),
+            expires_at: Instant::now() + Duration::from_secs(token.expires_in),
+        })
+    }
+}
+
+/// A self-signed JWT which is exchanged for an access token.
+fn assertion(
+    client_email: &str,
+    key: &EncodingKey,
+    token_uri: &Url,
+) -> Result<String, TokenError> {
+    let iat = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let claims = AssertionClaims {
+        iss: client_email,
+        scope: TOKEN_SCOPE,
+        aud: token_uri.as_str(),
+        iat,
+        exp: iat + ASSERTION_LIFETIME.as_secs(),
+    };
+    Ok(jsonwebtoken::encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        key,
+    )?)
+}
@@ -0,0 +1,47 @@
+use super::{Endpoint, EndpointType};
+use crate::types::model_id::ModelId;
+
+/// Azure OpenAI serves the OpenAI API, but each model is served by a
+/// deployment which is addressed in the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter)]
+pub enum Azure {
+    ChatCompletions(ChatCompletions),
+}
+
+impl Azure {
+    /// The path for the deployment named by `model_id`. The converter has
+    /// already mapped the model to its deployment name.
+    #[must_use]
+    pub fn path(self, model_id: &ModelId) -> String {
+        match self {
+            Self::ChatCompletions(_) => {
+                format!("openai/deployments/{model_id}/chat/completions")
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn chat_completions() -> Self {
+        Self::ChatCompletions(ChatCompletions)
+    }
+
+    #[must_use]
+    pub fn endpoint_type(self) -> EndpointType {
+        match self {
+            Self::ChatCompletions(_) => EndpointType::Chat,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChatCompletions;
+
+impl Endpoint for ChatCompletions {
+    const PATH: &'static str =
+        "openai/deployments/{deployment}/chat/completions";
+    type RequestBody = async_openai::types::CreateChatCompletionRequest;
+    type ResponseBody = async_openai::types::CreateChatCompletionResponse;
+    type StreamResponseBody =
+        async_openai::types::CreateChatCompletionStreamResponse;
+    type ErrorResponseBody = async_openai::error::WrappedError;
+}
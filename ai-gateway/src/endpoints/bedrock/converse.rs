@@ -1,6 +1,5 @@
-use aws_sdk_bedrockruntime::{
-    operation::converse::{ConverseInput, ConverseOutput},
-    types::ConverseStreamOutput,
+use aws_sdk_bedrockruntime::operation::converse::{
+    ConverseInput, ConverseOutput,
 };
 use serde::{Deserialize, Serialize};
 
@@ -17,7 +16,7 @@ impl Endpoint for Converse {
     const PATH: &'static str = "model/{model_id}/converse";
     type RequestBody = ConverseInput;
     type ResponseBody = ConverseOutput;
-    type StreamResponseBody = ConverseStreamOutput;
+    type StreamResponseBody = ConverseStreamEvent;
     type ErrorResponseBody = ConverseError;
 }
 
@@ -37,3 +36,93 @@ impl AiRequest for ConverseInput {
 // struct and simply rely on the http status codes to map to the OpenAI error.
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct ConverseError;
+
+/// An event from the `converse-stream` API.
+///
+/// Bedrock sends these as binary event stream messages, which the dispatcher
+/// decodes into JSON objects keyed by the `:event-type` header, e.g.
+/// `{"contentBlockDelta": {"contentBlockIndex": 0, "delta": {"text": "Hi"}}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConverseStreamEvent {
+    MessageStart(MessageStartEvent),
+    ContentBlockStart(ContentBlockStartEvent),
+    ContentBlockDelta(ContentBlockDeltaEvent),
+    ContentBlockStop(ContentBlockStopEvent),
+    MessageStop(MessageStopEvent),
+    Metadata(MetadataEvent),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageStartEvent {
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockStartEvent {
+    pub content_block_index: u32,
+    pub start: ContentBlockStart,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockStart {
+    #[serde(default)]
+    pub tool_use: Option<ToolUseBlockStart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUseBlockStart {
+    pub tool_use_id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockDeltaEvent {
+    pub content_block_index: u32,
+    pub delta: ContentBlockDelta,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockDelta {
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub tool_use: Option<ToolUseBlockDelta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolUseBlockDelta {
+    /// A fragment of the JSON tool input.
+    pub input: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockStopEvent {
+    pub content_block_index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStopEvent {
+    pub stop_reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataEvent {
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
@@ -0,0 +1,192 @@
+//! The Cohere v2 chat API.
+//!
+//! See: <https://docs.cohere.com/reference/chat>
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endpoints::{AiRequest, Endpoint, cohere::CohereError},
+    error::mapper::MapperError,
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Chat;
+
+impl Endpoint for Chat {
+    const PATH: &'static str = "v2/chat";
+    type RequestBody = ChatRequest;
+    type ResponseBody = ChatResponse;
+    type StreamResponseBody = ChatStreamEvent;
+    type ErrorResponseBody = CohereError;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    /// Documents the model may cite, which have no OpenAI equivalent so are
+    /// only set by requests sent directly to Cohere.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+}
+
+impl AiRequest for ChatRequest {
+    fn is_stream(&self) -> bool {
+        self.stream.unwrap_or(false)
+    }
+
+    fn model(&self) -> Result<ModelId, MapperError> {
+        ModelId::from_str_and_provider(InferenceProvider::Cohere, &self.model)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: MessageContent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+/// Message content, either plain text or a list of content blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    /// Blocks we don't map, e.g. thinking and images.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub id: String,
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+    pub message: ResponseMessage,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResponseMessage {
+    #[serde(default)]
+    pub content: Vec<ContentBlock>,
+    /// Citations of the request's documents, which OpenAI has no equivalent
+    /// for so are dropped when mapping.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FinishReason {
+    Complete,
+    StopSequence,
+    MaxTokens,
+    ToolCall,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// The tokens the request is billed for, which exclude the prompt
+    /// template Cohere adds to the request.
+    #[serde(default)]
+    pub billed_units: Option<BilledUnits>,
+    /// All tokens processed, including the prompt template.
+    #[serde(default)]
+    pub tokens: Option<BilledUnits>,
+}
+
+impl Usage {
+    /// The billed tokens, or all tokens if Cohere didn't report billing.
+    #[must_use]
+    pub fn counted(self) -> Option<BilledUnits> {
+        self.billed_units.or(self.tokens)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BilledUnits {
+    #[serde(default)]
+    pub input_tokens: Option<f64>,
+    #[serde(default)]
+    pub output_tokens: Option<f64>,
+}
+
+/// Events in a chat stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ChatStreamEvent {
+    MessageStart {
+        #[serde(default)]
+        id: Option<String>,
+    },
+    ContentDelta {
+        delta: ContentDelta,
+    },
+    MessageEnd {
+        delta: MessageEndDelta,
+    },
+    /// Events for features we don't map, e.g. citations and tool calls, and
+    /// events which carry nothing, e.g. `content-start`.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentDelta {
+    pub message: ContentDeltaMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentDeltaMessage {
+    pub content: ContentDeltaText,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentDeltaText {
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageEndDelta {
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
@@ -0,0 +1,117 @@
+//! The Cohere v2 embed API.
+//!
+//! See: <https://docs.cohere.com/reference/embed>
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endpoints::{
+        AiRequest, Endpoint,
+        cohere::{CohereError, chat::BilledUnits},
+    },
+    error::mapper::MapperError,
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Embed;
+
+impl Endpoint for Embed {
+    const PATH: &'static str = "v2/embed";
+    type RequestBody = EmbedRequest;
+    type ResponseBody = EmbedResponse;
+    // embeddings are never streamed
+    type StreamResponseBody = EmbedResponse;
+    type ErrorResponseBody = CohereError;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbedRequest {
+    pub model: String,
+    pub texts: Vec<String>,
+    /// Required by v3 and later models.
+    pub input_type: InputType,
+    /// The formats to return embeddings in, we only request floats.
+    pub embedding_types: Vec<EmbeddingType>,
+    /// Only supported by v4 and later models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dimension: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncate: Option<Truncate>,
+}
+
+impl Default for EmbedRequest {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            texts: Vec::new(),
+            input_type: InputType::default(),
+            embedding_types: vec![EmbeddingType::Float],
+            output_dimension: None,
+            truncate: None,
+        }
+    }
+}
+
+impl AiRequest for EmbedRequest {
+    fn is_stream(&self) -> bool {
+        false
+    }
+
+    fn model(&self) -> Result<ModelId, MapperError> {
+        ModelId::from_str_and_provider(InferenceProvider::Cohere, &self.model)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum InputType {
+    /// OpenAI embeddings don't distinguish between documents and queries, so
+    /// we embed everything as a document.
+    #[default]
+    SearchDocument,
+    SearchQuery,
+    Classification,
+    Clustering,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingType {
+    Float,
+    Int8,
+    Uint8,
+    Binary,
+    Ubinary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Truncate {
+    None,
+    Start,
+    End,
+}
+
+/// Embeddings are grouped by type, rather than listed per input as OpenAI
+/// does.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbedResponse {
+    pub id: String,
+    pub embeddings: EmbeddingsByType,
+    #[serde(default)]
+    pub meta: Option<EmbedMeta>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingsByType {
+    #[serde(default)]
+    pub float: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbedMeta {
+    #[serde(default)]
+    pub billed_units: Option<BilledUnits>,
+}
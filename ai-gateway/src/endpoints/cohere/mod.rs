@@ -0,0 +1,47 @@
+pub mod chat;
+pub mod embed;
+
+use super::{Endpoint, EndpointType};
+pub use crate::endpoints::cohere::{chat::Chat, embed::Embed};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter)]
+pub enum Cohere {
+    Chat(Chat),
+    Embed(Embed),
+}
+
+impl Cohere {
+    #[must_use]
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Chat(_) => Chat::PATH,
+            Self::Embed(_) => Embed::PATH,
+        }
+    }
+
+    #[must_use]
+    pub fn chat() -> Self {
+        Self::Chat(Chat)
+    }
+
+    #[must_use]
+    pub fn embed() -> Self {
+        Self::Embed(Embed)
+    }
+
+    #[must_use]
+    pub fn endpoint_type(&self) -> EndpointType {
+        match self {
+            Self::Chat(_) => EndpointType::Chat,
+            Self::Embed(_) => EndpointType::Embeddings,
+        }
+    }
+}
+
+/// Cohere errors only carry a message, the kind of error is given by the
+/// status code.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct CohereError {
+    #[serde(default)]
+    pub message: String,
+}
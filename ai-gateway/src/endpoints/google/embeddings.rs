@@ -0,0 +1,19 @@
+use async_openai::types::CreateEmbeddingResponse;
+
+use crate::endpoints::{
+    google::generate_contents::GeminiApiError,
+    openai::OpenAICompatibleEmbeddingRequest,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Embeddings;
+
+impl crate::endpoints::Endpoint for Embeddings {
+    // https://ai.google.dev/gemini-api/docs/openai#embeddings
+    const PATH: &'static str = "v1beta/openai/embeddings";
+    type RequestBody = OpenAICompatibleEmbeddingRequest;
+    type ResponseBody = CreateEmbeddingResponse;
+    // embeddings are never streamed
+    type StreamResponseBody = CreateEmbeddingResponse;
+    type ErrorResponseBody = GeminiApiError;
+}
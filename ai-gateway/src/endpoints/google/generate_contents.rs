@@ -1,17 +1,301 @@
-use async_openai::types::{
-    CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+//! Gemini's native `generateContent` API.
+//!
+//! See: <https://ai.google.dev/api/generate-content>
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endpoints::{AiRequest, Endpoint},
+    error::mapper::MapperError,
+    types::{model_id::ModelId, provider::InferenceProvider},
 };
 
-use crate::endpoints::openai::OpenAICompatibleChatCompletionRequest;
+const RETRY_INFO_TYPE: &str = "type.googleapis.com/google.rpc.RetryInfo";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct GenerateContents;
 
-impl crate::endpoints::Endpoint for GenerateContents {
-    // https://ai.google.dev/gemini-api/docs/openai
-    const PATH: &'static str = "v1beta/openai/chat/completions";
-    type RequestBody = OpenAICompatibleChatCompletionRequest;
-    type ResponseBody = CreateChatCompletionResponse;
-    type StreamResponseBody = CreateChatCompletionStreamResponse;
-    type ErrorResponseBody = async_openai::error::WrappedError;
+impl Endpoint for GenerateContents {
+    const PATH: &'static str = "v1beta/models/{model}:generateContent";
+    type RequestBody = GenerateContentRequest;
+    type ResponseBody = GenerateContentResponse;
+    // streams are requested with `alt=sse`, so each event is a response
+    type StreamResponseBody = GenerateContentResponse;
+    type ErrorResponseBody = GeminiApiError;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateContentRequest {
+    /// Sent in the path rather than the body, see
+    /// [`Google::path`](super::Google::path).
+    #[serde(skip)]
+    pub model: String,
+    /// Streams are requested with a different method rather than a field.
+    #[serde(skip)]
+    pub stream: bool,
+    pub contents: Vec<Content>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<Content>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GenerationConfig>,
+}
+
+impl AiRequest for GenerateContentRequest {
+    fn is_stream(&self) -> bool {
+        self.stream
+    }
+
+    fn model(&self) -> Result<ModelId, MapperError> {
+        ModelId::from_str_and_provider(
+            InferenceProvider::GoogleGemini,
+            &self.model,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Content {
+    /// Unset for the system instruction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    #[serde(default)]
+    pub parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Model,
+}
+
+/// A part of a message. Gemini sets exactly one of the data fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Part {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<Blob>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<FileData>,
+    /// Set on the parts with the model's thoughts, which OpenAI doesn't
+    /// return so aren't mapped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought: Option<bool>,
+}
+
+impl Part {
+    #[must_use]
+    pub fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..Default::default()
+        }
+    }
+}
+
+/// Base64 encoded data, e.g. an image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Blob {
+    pub mime_type: String,
+    pub data: String,
+}
+
+/// Data Gemini fetches itself, e.g. an image URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub file_uri: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// `application/json` for JSON responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    /// A JSON schema the response must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_json_schema: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateContentResponse {
+    #[serde(default)]
+    pub candidates: Vec<Candidate>,
+    #[serde(default)]
+    pub usage_metadata: Option<UsageMetadata>,
+    #[serde(default)]
+    pub model_version: Option<String>,
+    #[serde(default)]
+    pub response_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    /// Unset if the candidate was blocked before any content was generated.
+    #[serde(default)]
+    pub content: Option<Content>,
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+    #[serde(default)]
+    pub index: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FinishReason {
+    Stop,
+    MaxTokens,
+    Safety,
+    Recitation,
+    Blocklist,
+    ProhibitedContent,
+    Spii,
+    /// Reasons we don't distinguish, e.g. `OTHER` and malformed tool calls.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    /// Tokens spent thinking, which Gemini bills as output tokens.
+    #[serde(default)]
+    pub thoughts_token_count: u32,
+    #[serde(default)]
+    pub cached_content_token_count: Option<u32>,
+}
+
+/// Errors from the Gemini API.
+///
+/// Errors from the native API are in the Google API format, sometimes
+/// wrapped in an array. Errors from the OpenAI compatible API are mostly
+/// OpenAI shaped, except for some, notably rate limits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GeminiApiError {
+    Google(GoogleErrorResponse),
+    GoogleList(Vec<GoogleErrorResponse>),
+    OpenAI(async_openai::error::WrappedError),
+}
+
+impl GeminiApiError {
+    /// How long to wait before retrying, from the `google.rpc.RetryInfo`
+    /// error detail.
+    #[must_use]
+    pub fn retry_delay(&self) -> Option<Duration> {
+        let error = match self {
+            Self::Google(response) => &response.error,
+            Self::GoogleList(responses) => &responses.first()?.error,
+            Self::OpenAI(_) => return None,
+        };
+        error
+            .details
+            .iter()
+            .filter(|detail| {
+                detail.get("@type").and_then(serde_json::Value::as_str)
+                    == Some(RETRY_INFO_TYPE)
+            })
+            .find_map(|detail| {
+                detail
+                    .get("retryDelay")
+                    .and_then(serde_json::Value::as_str)
+                    .and_then(parse_proto_duration)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoogleErrorResponse {
+    pub error: GoogleError,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoogleError {
+    pub code: u16,
+    pub message: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<serde_json::Value>,
+}
+
+/// Parses the JSON form of a `google.protobuf.Duration`, e.g. `"37.5s"`.
+fn parse_proto_duration(duration: &str) -> Option<Duration> {
+    let seconds = duration.strip_suffix('s')?.parse::<f64>().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn retry_delay_is_read_from_retry_info() {
+        let body = json!([{
+            "error": {
+                "code": 429,
+                "message": "Resource has been exhausted.",
+                "status": "RESOURCE_EXHAUSTED",
+                "details": [
+                    {
+                        "@type": "type.googleapis.com/google.rpc.QuotaFailure",
+                        "violations": []
+                    },
+                    {
+                        "@type": RETRY_INFO_TYPE,
+                        "retryDelay": "37s"
+                    }
+                ]
+            }
+        }]);
+        let error: GeminiApiError = serde_json::from_value(body).unwrap();
+        assert!(matches!(error, GeminiApiError::GoogleList(_)));
+        assert_eq!(error.retry_delay(), Some(Duration::from_secs(37)));
+    }
+
+    #[test]
+    fn openai_errors_have_no_retry_delay() {
+        let body = json!({
+            "error": {
+                "message": "Invalid model",
+                "type": "invalid_request_error",
+                "param": null,
+                "code": "invalid_model"
+            }
+        });
+        let error: GeminiApiError = serde_json::from_value(body).unwrap();
+        assert!(matches!(error, GeminiApiError::OpenAI(_)));
+        assert_eq!(error.retry_delay(), None);
+    }
 }
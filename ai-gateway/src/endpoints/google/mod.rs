@@ -1,18 +1,34 @@
+pub(crate) mod embeddings;
 pub(crate) mod generate_contents;
 
 use super::{Endpoint, EndpointType};
-pub(crate) use crate::endpoints::google::generate_contents::GenerateContents;
+pub(crate) use crate::endpoints::google::{
+    embeddings::Embeddings, generate_contents::GenerateContents,
+};
+use crate::types::model_id::ModelId;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter)]
 pub enum Google {
     GenerateContents(GenerateContents),
+    Embeddings(Embeddings),
 }
 
 impl Google {
+    /// The native API takes the model in the path, and streams with
+    /// `streamGenerateContent`, sent as SSE events with `alt=sse`.
     #[must_use]
-    pub fn path(&self) -> &str {
+    pub fn path(&self, model_id: &ModelId, is_stream: bool) -> String {
         match self {
-            Self::GenerateContents(_) => GenerateContents::PATH,
+            Self::GenerateContents(_) => {
+                if is_stream {
+                    format!(
+                        "v1beta/models/{model_id}:streamGenerateContent?alt=sse"
+                    )
+                } else {
+                    format!("v1beta/models/{model_id}:generateContent")
+                }
+            }
+            Self::Embeddings(_) => Embeddings::PATH.to_string(),
         }
     }
 
@@ -21,10 +37,16 @@ impl Google {
         Self::GenerateContents(GenerateContents)
     }
 
+    #[must_use]
+    pub fn embeddings() -> Self {
+        Self::Embeddings(Embeddings)
+    }
+
     #[must_use]
     pub fn endpoint_type(&self) -> EndpointType {
         match self {
             Self::GenerateContents(_) => EndpointType::Chat,
+            Self::Embeddings(_) => EndpointType::Embeddings,
         }
     }
 }
@@ -37,6 +59,7 @@ impl TryFrom<&str> for Google {
             GenerateContents::PATH => {
                 Ok(Self::GenerateContents(GenerateContents))
             }
+            Embeddings::PATH => Ok(Self::Embeddings(Embeddings)),
             path => {
                 tracing::warn!(path = %path, "unsupported Google path");
                 Err(crate::error::invalid_req::InvalidRequestError::NotFound(
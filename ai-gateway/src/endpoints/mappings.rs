@@ -1,8 +1,12 @@
 use crate::endpoints::{
-    anthropic::Anthropic, bedrock::Bedrock, google::Google, ollama::Ollama,
-    openai::OpenAI,
+    anthropic::Anthropic, azure::Azure, bedrock::Bedrock, cohere::Cohere,
+    google::Google, ollama::Ollama, openai::OpenAI, vertex::Vertex,
 };
 
+// Embeddings, image generation and audio requests are only mapped to providers
+// which serve them, see `ApiEndpoint::mapped`, so other providers map them to
+// their chat endpoint.
+
 impl From<Anthropic> for OpenAI {
     fn from(value: Anthropic) -> Self {
         match value {
@@ -14,7 +18,12 @@ impl From<Anthropic> for OpenAI {
 impl From<OpenAI> for Anthropic {
     fn from(value: OpenAI) -> Self {
         match value {
-            OpenAI::ChatCompletions(_) => Self::messages(),
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::Embeddings(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::messages(),
         }
     }
 }
@@ -23,6 +32,7 @@ impl From<Google> for OpenAI {
     fn from(value: Google) -> Self {
         match value {
             Google::GenerateContents(_) => Self::chat_completions(),
+            Google::Embeddings(_) => Self::embeddings(),
         }
     }
 }
@@ -30,7 +40,12 @@ impl From<Google> for OpenAI {
 impl From<OpenAI> for Google {
     fn from(value: OpenAI) -> Self {
         match value {
-            OpenAI::ChatCompletions(_) => Self::generate_contents(),
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::generate_contents(),
+            OpenAI::Embeddings(_) => Self::embeddings(),
         }
     }
 }
@@ -38,7 +53,12 @@ impl From<OpenAI> for Google {
 impl From<OpenAI> for Ollama {
     fn from(value: OpenAI) -> Self {
         match value {
-            OpenAI::ChatCompletions(_) => Self::chat_completions(),
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::Embeddings(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::chat_completions(),
         }
     }
 }
@@ -53,7 +73,76 @@ impl From<Ollama> for OpenAI {
 impl From<OpenAI> for Bedrock {
     fn from(value: OpenAI) -> Self {
         match value {
-            OpenAI::ChatCompletions(_) => Self::converse(),
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::Embeddings(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::converse(),
+        }
+    }
+}
+
+impl From<OpenAI> for Cohere {
+    fn from(value: OpenAI) -> Self {
+        match value {
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::chat(),
+            OpenAI::Embeddings(_) => Self::embed(),
+        }
+    }
+}
+
+impl From<Cohere> for OpenAI {
+    fn from(value: Cohere) -> Self {
+        match value {
+            Cohere::Chat(_) => Self::chat_completions(),
+            Cohere::Embed(_) => Self::embeddings(),
+        }
+    }
+}
+
+impl From<OpenAI> for Azure {
+    fn from(value: OpenAI) -> Self {
+        match value {
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::Embeddings(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::chat_completions(),
+        }
+    }
+}
+
+impl From<Azure> for OpenAI {
+    fn from(value: Azure) -> Self {
+        match value {
+            Azure::ChatCompletions(_) => Self::chat_completions(),
+        }
+    }
+}
+
+impl From<OpenAI> for Vertex {
+    fn from(value: OpenAI) -> Self {
+        match value {
+            OpenAI::ChatCompletions(_)
+            | OpenAI::Responses(_)
+            | OpenAI::Embeddings(_)
+            | OpenAI::ImageGenerations(_)
+            | OpenAI::AudioTranscriptions(_)
+            | OpenAI::AudioSpeech(_) => Self::chat_completions(),
+        }
+    }
+}
+
+impl From<Vertex> for OpenAI {
+    fn from(value: Vertex) -> Self {
+        match value {
+            Vertex::ChatCompletions(_) => Self::chat_completions(),
         }
     }
 }
@@ -1,16 +1,20 @@
 pub mod anthropic;
+pub mod azure;
 pub(crate) mod bedrock;
+pub mod cohere;
 pub mod google;
 pub mod mappings;
 pub mod ollama;
 pub mod openai;
+pub mod vertex;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    config::api_translation::ApiTranslation,
     endpoints::{
-        anthropic::Anthropic, bedrock::Bedrock, google::Google, ollama::Ollama,
-        openai::OpenAI,
+        anthropic::Anthropic, azure::Azure, bedrock::Bedrock, cohere::Cohere,
+        google::Google, ollama::Ollama, openai::OpenAI, vertex::Vertex,
     },
     error::{
         internal::InternalError, invalid_req::InvalidRequestError,
@@ -57,6 +61,11 @@ macro_rules! define_endpoints {
 
 define_endpoints! {
     (ChatCompletions, "chat/completions"),
+    (Responses, "responses"),
+    (Embeddings, "embeddings"),
+    (ImageGenerations, "images/generations"),
+    (AudioTranscriptions, "audio/transcriptions"),
+    (AudioSpeech, "audio/speech"),
 }
 
 pub trait AiRequest {
@@ -71,6 +80,9 @@ pub enum ApiEndpoint {
     Google(Google),
     Ollama(Ollama),
     Bedrock(Bedrock),
+    Cohere(Cohere),
+    Azure(Azure),
+    Vertex(Vertex),
     OpenAICompatible {
         provider: InferenceProvider,
         openai_endpoint: OpenAI,
@@ -84,10 +96,73 @@ impl ApiEndpoint {
         Some(Self::OpenAI(OpenAI::try_from(&endpoint_route).ok()?))
     }
 
+    /// The endpoint a direct proxy request is for, if it is one we monitor.
+    ///
+    /// Direct proxies forward requests as is, so this is only used for
+    /// provider health and to detect streaming requests.
+    #[must_use]
+    pub fn for_direct_proxy(
+        provider: &InferenceProvider,
+        path: &str,
+    ) -> Option<Self> {
+        match provider {
+            InferenceProvider::Anthropic
+                if path == anthropic::Messages::PATH =>
+            {
+                Some(Self::Anthropic(Anthropic::messages()))
+            }
+            _ => None,
+        }
+    }
+
     pub fn mapped(
         source_endpoint: ApiEndpoint,
         target_provider: &InferenceProvider,
+        api_translation: Option<ApiTranslation>,
     ) -> Result<Self, InvalidRequestError> {
+        let source_endpoint = match (source_endpoint, api_translation) {
+            // only OpenAI serves the responses API natively, so other
+            // providers serve it with their chat completions API
+            (Self::OpenAI(OpenAI::Responses(_)), api_translation)
+                if api_translation == Some(ApiTranslation::ResponsesToChat)
+                    || *target_provider != InferenceProvider::OpenAI =>
+            {
+                Self::OpenAI(OpenAI::chat_completions())
+            }
+            (
+                Self::OpenAI(OpenAI::ChatCompletions(_)),
+                Some(ApiTranslation::ChatToResponses),
+            ) if *target_provider == InferenceProvider::OpenAI => {
+                return Ok(Self::OpenAI(OpenAI::responses()));
+            }
+            // only OpenAI generates images and audio
+            (
+                Self::OpenAI(
+                    OpenAI::ImageGenerations(_)
+                    | OpenAI::AudioTranscriptions(_)
+                    | OpenAI::AudioSpeech(_),
+                ),
+                _,
+            ) if *target_provider != InferenceProvider::OpenAI => {
+                return Err(InvalidRequestError::UnsupportedProvider(
+                    target_provider.clone(),
+                ));
+            }
+            // only OpenAI, Cohere and Gemini serve embeddings
+            (Self::OpenAI(OpenAI::Embeddings(_)), _)
+                if !matches!(
+                    target_provider,
+                    InferenceProvider::OpenAI
+                        | InferenceProvider::Cohere
+                        | InferenceProvider::GoogleGemini
+                ) =>
+            {
+                return Err(InvalidRequestError::UnsupportedProvider(
+                    target_provider.clone(),
+                ));
+            }
+            (source_endpoint, _) => source_endpoint,
+        };
         match (source_endpoint, target_provider) {
             (Self::OpenAI(source), InferenceProvider::Anthropic) => {
                 Ok(Self::Anthropic(Anthropic::from(source)))
@@ -104,12 +179,26 @@ impl ApiEndpoint {
             (Self::OpenAI(source), InferenceProvider::Bedrock) => {
                 Ok(Self::Bedrock(Bedrock::from(source)))
             }
-            (Self::OpenAI(source), InferenceProvider::Named(name)) => {
-                Ok(Self::OpenAICompatible {
-                    provider: InferenceProvider::Named(name.clone()),
-                    openai_endpoint: source,
-                })
+            (Self::OpenAI(source), InferenceProvider::Cohere) => {
+                Ok(Self::Cohere(Cohere::from(source)))
+            }
+            (Self::OpenAI(source), InferenceProvider::AzureOpenAI) => {
+                Ok(Self::Azure(Azure::from(source)))
+            }
+            (Self::OpenAI(source), InferenceProvider::VertexAI) => {
+                Ok(Self::Vertex(Vertex::from(source)))
             }
+            (
+                Self::OpenAI(source),
+                InferenceProvider::Mistral
+                | InferenceProvider::Groq
+                | InferenceProvider::XAI
+                | InferenceProvider::DeepSeek
+                | InferenceProvider::Named(_),
+            ) => Ok(Self::OpenAICompatible {
+                provider: target_provider.clone(),
+                openai_endpoint: source,
+            }),
             _ => Err(InvalidRequestError::UnsupportedProvider(
                 target_provider.clone(),
             )),
@@ -124,6 +213,9 @@ impl ApiEndpoint {
             Self::Google(_) => InferenceProvider::GoogleGemini,
             Self::Ollama(_) => InferenceProvider::Ollama,
             Self::Bedrock(_) => InferenceProvider::Bedrock,
+            Self::Cohere(_) => InferenceProvider::Cohere,
+            Self::Azure(_) => InferenceProvider::AzureOpenAI,
+            Self::Vertex(_) => InferenceProvider::VertexAI,
             Self::OpenAICompatible { provider, .. } => provider.clone(),
         }
     }
@@ -139,8 +231,9 @@ impl ApiEndpoint {
                 openai_endpoint, ..
             } => Ok(openai_endpoint.path().to_string()),
             Self::Anthropic(anthropic) => Ok(anthropic.path().to_string()),
-            Self::Google(google) => Ok(google.path().to_string()),
+            Self::Vertex(vertex) => Ok(vertex.path().to_string()),
             Self::Ollama(ollama) => Ok(ollama.path().to_string()),
+            Self::Cohere(cohere) => Ok(cohere.path().to_string()),
             Self::Bedrock(bedrock) => {
                 if let Some(model_id) = model_id {
                     Ok(bedrock.path(model_id, is_stream))
@@ -149,6 +242,22 @@ impl ApiEndpoint {
                     Err(InternalError::Internal)
                 }
             }
+            Self::Google(google) => {
+                if let Some(model_id) = model_id {
+                    Ok(google.path(model_id, is_stream))
+                } else {
+                    tracing::error!("Google path requires model id");
+                    Err(InternalError::Internal)
+                }
+            }
+            Self::Azure(azure) => {
+                if let Some(model_id) = model_id {
+                    Ok(azure.path(model_id))
+                } else {
+                    tracing::error!("Azure path requires model id");
+                    Err(InternalError::Internal)
+                }
+            }
         }
     }
 
@@ -163,6 +272,9 @@ impl ApiEndpoint {
             Self::Google(google) => google.endpoint_type(),
             Self::Ollama(ollama) => ollama.endpoint_type(),
             Self::Bedrock(bedrock) => bedrock.endpoint_type(),
+            Self::Cohere(cohere) => cohere.endpoint_type(),
+            Self::Azure(azure) => azure.endpoint_type(),
+            Self::Vertex(vertex) => vertex.endpoint_type(),
         }
     }
 }
@@ -171,6 +283,30 @@ impl ApiEndpoint {
 #[serde(rename_all = "kebab-case")]
 pub enum EndpointType {
     Chat,
+    /// The OpenAI responses API. Routers without a balance config for it
+    /// balance these requests with their chat config.
+    Responses,
+    Embeddings,
     Image,
-    Audio,
+    /// `multipart/form-data` uploads, see
+    /// [`multipart`](crate::types::multipart).
+    AudioTranscription,
+    /// Responds with the raw audio rather than JSON.
+    AudioSpeech,
+}
+
+impl EndpointType {
+    /// The endpoint type whose balancer serves requests of this type when a
+    /// router has no balance config for it.
+    #[must_use]
+    pub fn fallback(self) -> Option<Self> {
+        match self {
+            Self::Responses => Some(Self::Chat),
+            Self::Chat
+            | Self::Embeddings
+            | Self::Image
+            | Self::AudioTranscription
+            | Self::AudioSpeech => None,
+        }
+    }
 }
@@ -0,0 +1,75 @@
+//! The OpenAI audio APIs.
+//!
+//! Transcription requests are `multipart/form-data` uploads, see
+//! [`multipart`](crate::types::multipart), and speech responses are the raw
+//! audio, so neither body is JSON. Requests are forwarded as is apart from
+//! the model, so these types only cover the fields we read.
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endpoints::{AiRequest, Endpoint},
+    error::mapper::MapperError,
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AudioTranscriptions;
+
+impl Endpoint for AudioTranscriptions {
+    const PATH: &'static str = "v1/audio/transcriptions";
+    /// The multipart form, with the audio in its `file` part.
+    type RequestBody = Bytes;
+    type ResponseBody = TranscriptionResponse;
+    // streamed transcriptions are forwarded as is
+    type StreamResponseBody = TranscriptionResponse;
+    type ErrorResponseBody = async_openai::error::WrappedError;
+}
+
+/// The `json` and `verbose_json` response formats. The `text`, `srt` and
+/// `vtt` formats respond with plain text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionResponse {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Duration of the audio in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AudioSpeech;
+
+impl Endpoint for AudioSpeech {
+    const PATH: &'static str = "v1/audio/speech";
+    type RequestBody = CreateSpeechRequest;
+    /// The audio, in the requested format.
+    type ResponseBody = Bytes;
+    type StreamResponseBody = Bytes;
+    type ErrorResponseBody = async_openai::error::WrappedError;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CreateSpeechRequest {
+    pub model: String,
+    pub input: String,
+    pub voice: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// `mp3`, `opus`, `aac`, `flac`, `wav` or `pcm`, defaulting to `mp3`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+}
+
+impl AiRequest for CreateSpeechRequest {
+    fn is_stream(&self) -> bool {
+        false
+    }
+
+    fn model(&self) -> Result<ModelId, MapperError> {
+        ModelId::from_str_and_provider(InferenceProvider::OpenAI, &self.model)
+    }
+}
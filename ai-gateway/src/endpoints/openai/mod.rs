@@ -1,7 +1,17 @@
+pub mod audio;
 pub mod chat_completions;
+pub mod embeddings;
+pub mod images;
+pub mod responses;
 
 use super::EndpointType;
-pub use crate::endpoints::openai::chat_completions::ChatCompletions;
+pub use crate::endpoints::openai::{
+    audio::{AudioSpeech, AudioTranscriptions},
+    chat_completions::ChatCompletions,
+    embeddings::Embeddings,
+    images::ImageGenerations,
+    responses::Responses,
+};
 use crate::{
     endpoints::{Endpoint, EndpointRoute},
     error::invalid_req::InvalidRequestError,
@@ -10,6 +20,11 @@ use crate::{
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter)]
 pub enum OpenAI {
     ChatCompletions(ChatCompletions),
+    Responses(Responses),
+    Embeddings(Embeddings),
+    ImageGenerations(ImageGenerations),
+    AudioTranscriptions(AudioTranscriptions),
+    AudioSpeech(AudioSpeech),
 }
 
 impl OpenAI {
@@ -17,6 +32,11 @@ impl OpenAI {
     pub fn path(&self) -> &str {
         match self {
             Self::ChatCompletions(_) => ChatCompletions::PATH,
+            Self::Responses(_) => Responses::PATH,
+            Self::Embeddings(_) => Embeddings::PATH,
+            Self::ImageGenerations(_) => ImageGenerations::PATH,
+            Self::AudioTranscriptions(_) => AudioTranscriptions::PATH,
+            Self::AudioSpeech(_) => AudioSpeech::PATH,
         }
     }
 
@@ -25,10 +45,40 @@ impl OpenAI {
         Self::ChatCompletions(ChatCompletions)
     }
 
+    #[must_use]
+    pub fn responses() -> Self {
+        Self::Responses(Responses)
+    }
+
+    #[must_use]
+    pub fn embeddings() -> Self {
+        Self::Embeddings(Embeddings)
+    }
+
+    #[must_use]
+    pub fn image_generations() -> Self {
+        Self::ImageGenerations(ImageGenerations)
+    }
+
+    #[must_use]
+    pub fn audio_transcriptions() -> Self {
+        Self::AudioTranscriptions(AudioTranscriptions)
+    }
+
+    #[must_use]
+    pub fn audio_speech() -> Self {
+        Self::AudioSpeech(AudioSpeech)
+    }
+
     #[must_use]
     pub fn endpoint_type(&self) -> EndpointType {
         match self {
             Self::ChatCompletions(_) => EndpointType::Chat,
+            Self::Responses(_) => EndpointType::Responses,
+            Self::Embeddings(_) => EndpointType::Embeddings,
+            Self::ImageGenerations(_) => EndpointType::Image,
+            Self::AudioTranscriptions(_) => EndpointType::AudioTranscription,
+            Self::AudioSpeech(_) => EndpointType::AudioSpeech,
         }
     }
 }
@@ -41,6 +91,15 @@ impl TryFrom<&EndpointRoute> for OpenAI {
             EndpointRoute::ChatCompletions => {
                 Ok(Self::ChatCompletions(ChatCompletions))
             }
+            EndpointRoute::Responses => Ok(Self::Responses(Responses)),
+            EndpointRoute::Embeddings => Ok(Self::Embeddings(Embeddings)),
+            EndpointRoute::ImageGenerations => {
+                Ok(Self::ImageGenerations(ImageGenerations))
+            }
+            EndpointRoute::AudioTranscriptions => {
+                Ok(Self::AudioTranscriptions(AudioTranscriptions))
+            }
+            EndpointRoute::AudioSpeech => Ok(Self::AudioSpeech(AudioSpeech)),
         }
     }
 }
@@ -84,3 +143,31 @@ impl super::AiRequest for OpenAICompatibleChatCompletionRequest {
         )
     }
 }
+
+#[derive(
+    Clone, serde::Serialize, Default, Debug, serde::Deserialize, PartialEq,
+)]
+pub struct OpenAICompatibleEmbeddingRequest {
+    #[serde(skip)]
+    pub(crate) provider: crate::types::provider::InferenceProvider,
+    #[serde(flatten)]
+    pub(crate) inner: async_openai::types::CreateEmbeddingRequest,
+}
+
+impl super::AiRequest for OpenAICompatibleEmbeddingRequest {
+    fn is_stream(&self) -> bool {
+        false
+    }
+
+    fn model(
+        &self,
+    ) -> Result<
+        crate::types::model_id::ModelId,
+        crate::error::mapper::MapperError,
+    > {
+        crate::types::model_id::ModelId::from_str_and_provider(
+            self.provider.clone(),
+            &self.inner.model,
+        )
+    }
+}
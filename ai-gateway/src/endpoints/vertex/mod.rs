@@ -0,0 +1,57 @@
+use super::{Endpoint, EndpointType};
+use crate::endpoints::{
+    google::generate_contents::GeminiApiError,
+    openai::OpenAICompatibleChatCompletionRequest,
+};
+
+/// The location requests are sent to when none is configured.
+pub(crate) const DEFAULT_VERTEX_LOCATION: &str = "us-central1";
+
+/// Vertex AI serves Gemini through the same OpenAI compatible API as the
+/// Gemini API, but under a path for the project and location.
+///
+/// See: <https://cloud.google.com/vertex-ai/generative-ai/docs/multimodal/call-vertex-using-openai-library>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter)]
+pub enum Vertex {
+    ChatCompletions(ChatCompletions),
+}
+
+impl Vertex {
+    /// The path relative to [`project_path`].
+    #[must_use]
+    pub fn path(&self) -> &str {
+        match self {
+            Self::ChatCompletions(_) => ChatCompletions::PATH,
+        }
+    }
+
+    #[must_use]
+    pub fn chat_completions() -> Self {
+        Self::ChatCompletions(ChatCompletions)
+    }
+
+    #[must_use]
+    pub fn endpoint_type(&self) -> EndpointType {
+        match self {
+            Self::ChatCompletions(_) => EndpointType::Chat,
+        }
+    }
+}
+
+/// The path of the OpenAI compatible API for `project` in `location`.
+#[must_use]
+pub fn project_path(project: &str, location: &str) -> String {
+    format!("v1/projects/{project}/locations/{location}/endpoints/openapi/")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChatCompletions;
+
+impl Endpoint for ChatCompletions {
+    const PATH: &'static str = "chat/completions";
+    type RequestBody = OpenAICompatibleChatCompletionRequest;
+    type ResponseBody = async_openai::types::CreateChatCompletionResponse;
+    type StreamResponseBody =
+        async_openai::types::CreateChatCompletionStreamResponse;
+    type ErrorResponseBody = GeminiApiError;
+}
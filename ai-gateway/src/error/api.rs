@@ -1,6 +1,10 @@
+use std::time::Duration;
+
 use axum_core::response::IntoResponse;
+use chrono::{DateTime, Utc};
 use displaydoc::Display;
-use http::StatusCode;
+use http::{HeaderValue, StatusCode};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use utoipa::ToSchema;
@@ -10,13 +14,28 @@ use super::{
     auth::{AuthError, AuthErrorMetric},
     internal::{InternalError, InternalErrorMetric},
     invalid_req::{InvalidRequestError, InvalidRequestErrorMetric},
+    mapper::MapperError,
 };
 use crate::{
+    discover::monitor::backoff::retry_after_header,
     error::stream::{StreamError, StreamErrorMetric},
     middleware::mapper::openai::SERVER_ERROR_TYPE,
-    types::json::Json,
+    types::{json::Json, provider::InferenceProvider},
 };
 
+/// What a provider took too long to do, see
+/// [`TimeoutsConfig`](crate::config::dispatcher::TimeoutsConfig).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, strum::AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum TimeoutKind {
+    /// accept a connection
+    Connect,
+    /// respond
+    Request,
+    /// send the next event of its stream
+    StreamIdle,
+}
+
 /// Common API errors
 #[derive(Debug, Error, Display, strum::AsRefStr)]
 pub enum ApiError {
@@ -30,8 +49,38 @@ pub enum ApiError {
     StreamError(#[from] StreamError),
     /// Service panicked: {0}
     Panic(String),
+    /// All providers are at their maximum concurrent requests
+    ProvidersSaturated,
+    /// No provider is ready to serve the request
+    NoProvidersReady,
+    /// Router is at its maximum concurrent requests
+    RouterSaturated,
+    /// Provider {provider} did not {kind} within the configured timeout
+    ProviderTimeout {
+        provider: InferenceProvider,
+        kind: TimeoutKind,
+    },
+    /// Provider {0} is failing too many requests and is temporarily disabled
+    CircuitOpen(InferenceProvider),
+    /// Provider {provider} is rate limited, retry after {retry_after:?}
+    ProviderBackingOff {
+        provider: InferenceProvider,
+        retry_after: Duration,
+    },
+    /// Spend limit of {limit} USD reached, the budget resets at {resets_at}
+    BudgetExceeded {
+        limit: Decimal,
+        resets_at: DateTime<Utc>,
+    },
+    /// Rate limits are temporarily unavailable
+    RateLimitUnavailable,
 }
 
+/// Seconds clients should wait before retrying a request that was rejected
+/// because all providers, or the router, were saturated, or because rate
+/// limits were unavailable.
+const PROVIDERS_SATURATED_RETRY_AFTER_SECS: u64 = 1;
+
 impl From<dynamic_router::router::Error> for ApiError {
     fn from(error: dynamic_router::router::Error) -> Self {
         match error {
@@ -48,6 +97,28 @@ impl From<dynamic_router::router::Error> for ApiError {
     }
 }
 
+impl From<MapperError> for ApiError {
+    fn from(error: MapperError) -> Self {
+        match error {
+            MapperError::ImageMappingInvalid(reason) => {
+                Self::InvalidRequest(InvalidRequestError::InvalidImage(reason))
+            }
+            MapperError::ImageTooLarge { size, max } => {
+                Self::InvalidRequest(InvalidRequestError::ImageTooLarge {
+                    size,
+                    max,
+                })
+            }
+            error @ MapperError::StructuredOutputStreaming(_) => {
+                Self::InvalidRequest(InvalidRequestError::Unsupported(
+                    error.to_string(),
+                ))
+            }
+            error => Self::Internal(InternalError::MapperError(error)),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct ErrorResponse {
     pub error: ErrorDetails,
@@ -84,6 +155,153 @@ impl IntoResponse for ApiError {
                 )
                     .into_response()
             }
+            ApiError::ProvidersSaturated => {
+                tracing::warn!("all providers saturated, shedding request");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(
+                        http::header::RETRY_AFTER,
+                        HeaderValue::from(PROVIDERS_SATURATED_RETRY_AFTER_SECS),
+                    )],
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::NoProvidersReady => {
+                tracing::warn!("no provider ready, shedding request");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(
+                        http::header::RETRY_AFTER,
+                        HeaderValue::from(PROVIDERS_SATURATED_RETRY_AFTER_SECS),
+                    )],
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::RouterSaturated => {
+                tracing::warn!("router saturated, shedding request");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(
+                        http::header::RETRY_AFTER,
+                        HeaderValue::from(PROVIDERS_SATURATED_RETRY_AFTER_SECS),
+                    )],
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::ProviderTimeout { ref provider, kind } => {
+                tracing::warn!(provider = %provider, kind = kind.as_ref(), "provider timed out");
+                (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::CircuitOpen(ref provider) => {
+                tracing::debug!(provider = %provider, "circuit open");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::ProviderBackingOff {
+                ref provider,
+                retry_after,
+            } => {
+                tracing::debug!(
+                    provider = %provider,
+                    retry_after = ?retry_after,
+                    "provider is backing off"
+                );
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(
+                        http::header::RETRY_AFTER,
+                        retry_after_header(retry_after),
+                    )],
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some("rate_limit_exceeded".to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::RateLimitUnavailable => {
+                tracing::warn!("rate limits unavailable, rejecting request");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(
+                        http::header::RETRY_AFTER,
+                        HeaderValue::from(PROVIDERS_SATURATED_RETRY_AFTER_SECS),
+                    )],
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            ApiError::BudgetExceeded { .. } => {
+                tracing::debug!("budget exceeded, rejecting request");
+                (
+                    StatusCode::PAYMENT_REQUIRED,
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some("insufficient_quota".to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
         }
     }
 }
@@ -102,6 +320,22 @@ pub enum ApiErrorMetric {
     StreamError(#[from] StreamErrorMetric),
     /// Panic
     Panic,
+    /// Providers saturated
+    ProvidersSaturated,
+    /// No providers ready
+    NoProvidersReady,
+    /// Router saturated
+    RouterSaturated,
+    /// Provider timeout
+    ProviderTimeout,
+    /// Circuit open
+    CircuitOpen,
+    /// Provider backing off
+    ProviderBackingOff,
+    /// Budget exceeded
+    BudgetExceeded,
+    /// Rate limit unavailable
+    RateLimitUnavailable,
 }
 
 impl From<&ApiError> for ApiErrorMetric {
@@ -127,6 +361,14 @@ impl From<&ApiError> for ApiErrorMetric {
                 _ => Self::StreamError(StreamErrorMetric::from(error)),
             },
             ApiError::Panic(_error) => Self::Panic,
+            ApiError::ProvidersSaturated => Self::ProvidersSaturated,
+            ApiError::NoProvidersReady => Self::NoProvidersReady,
+            ApiError::RouterSaturated => Self::RouterSaturated,
+            ApiError::ProviderTimeout { .. } => Self::ProviderTimeout,
+            ApiError::CircuitOpen(_) => Self::CircuitOpen,
+            ApiError::ProviderBackingOff { .. } => Self::ProviderBackingOff,
+            ApiError::BudgetExceeded { .. } => Self::BudgetExceeded,
+            ApiError::RateLimitUnavailable => Self::RateLimitUnavailable,
         }
     }
 }
@@ -151,6 +393,14 @@ impl ErrorMetric for ApiErrorMetric {
                 format!("StreamError:{}", error.as_ref())
             }
             Self::Panic => String::from("Panic"),
+            Self::ProvidersSaturated => String::from("ProvidersSaturated"),
+            Self::NoProvidersReady => String::from("NoProvidersReady"),
+            Self::RouterSaturated => String::from("RouterSaturated"),
+            Self::ProviderTimeout => String::from("ProviderTimeout"),
+            Self::CircuitOpen => String::from("CircuitOpen"),
+            Self::ProviderBackingOff => String::from("ProviderBackingOff"),
+            Self::BudgetExceeded => String::from("BudgetExceeded"),
+            Self::RateLimitUnavailable => String::from("RateLimitUnavailable"),
         }
     }
 }
@@ -6,7 +6,10 @@ use thiserror::Error;
 use super::api::ErrorResponse;
 use crate::{
     error::api::ErrorDetails,
-    middleware::mapper::openai::INVALID_REQUEST_ERROR_TYPE, types::json::Json,
+    middleware::mapper::openai::{
+        INVALID_REQUEST_ERROR_TYPE, SERVER_ERROR_TYPE,
+    },
+    types::{json::Json, provider::InferenceProvider},
 };
 
 #[derive(Debug, strum::AsRefStr, Error, Display)]
@@ -19,6 +22,8 @@ pub enum AuthError {
     ProviderKeyNotFound,
     /// Router not found
     RouterNotFound,
+    /// Failed to refresh the access token for {0}
+    ProviderTokenRefresh(InferenceProvider),
 }
 
 impl IntoResponse for AuthError {
@@ -72,6 +77,20 @@ impl IntoResponse for AuthError {
                 }),
             )
                 .into_response(),
+            // the gateway's own credential is at fault rather than the
+            // caller's, so this isn't reported as unauthorized
+            error @ Self::ProviderTokenRefresh(_) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorResponse {
+                    error: ErrorDetails {
+                        message: error.to_string(),
+                        r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                        param: None,
+                        code: Some("provider_token_refresh_failed".to_string()),
+                    },
+                }),
+            )
+                .into_response(),
         }
     }
 }
@@ -89,6 +108,8 @@ pub enum AuthErrorMetric {
     ProviderKeyNotFound,
     /// Router not found
     RouterNotFound,
+    /// Provider token refresh failed
+    ProviderTokenRefresh,
 }
 
 impl From<&AuthError> for AuthErrorMetric {
@@ -100,6 +121,7 @@ impl From<&AuthError> for AuthErrorMetric {
             AuthError::InvalidCredentials => Self::InvalidCredentials,
             AuthError::ProviderKeyNotFound => Self::ProviderKeyNotFound,
             AuthError::RouterNotFound => Self::RouterNotFound,
+            AuthError::ProviderTokenRefresh(_) => Self::ProviderTokenRefresh,
         }
     }
 }
@@ -3,7 +3,10 @@ use telemetry::TelemetryError;
 use thiserror::Error;
 
 use crate::{
-    config::validation::ModelMappingValidationError,
+    config::validation::{
+        ConfigValidationErrors, ModelMappingValidationError,
+        RouterValidationError,
+    },
     types::{provider::InferenceProvider, router::RouterId},
 };
 
@@ -24,6 +27,8 @@ pub enum InitError {
     OAuthConfig(url::ParseError),
     /// Failed to create reqwest client: {0}
     CreateReqwestClient(reqwest::Error),
+    /// Invalid Google service account key: {0}
+    InvalidServiceAccount(String),
     /// Failed to create balancer: {0}
     CreateBalancer(tower::BoxError),
     /// Provider error: {0}
@@ -54,8 +59,10 @@ pub enum InitError {
     RateLimitChannelsNotInitialized(RouterId),
     /// Failed to build websocket request: {0}
     WebsocketRequestBuild(#[from] http::Error),
-    /// Invalid router id: {0}
-    InvalidRouterId(String),
+    /// Invalid router config: {0}
+    InvalidRouterConfig(#[from] RouterValidationError),
+    /// Invalid config: {0}
+    InvalidConfig(#[from] ConfigValidationErrors),
     /// Cache not configured
     CacheNotConfigured,
     /// Minio not configured
@@ -68,10 +75,14 @@ pub enum InitError {
     ProviderNotSupported(InferenceProvider),
     /// Router rx not configured
     RouterRxNotConfigured,
+    /// Router tx not configured
+    RouterTxNotConfigured,
     /// Store not configured: {0}
     StoreNotConfigured(&'static str),
     /// Router api keys not initialized
     RouterApiKeysNotInitialized,
     /// Invalid organization id: {0}
     InvalidOrganizationId(String),
+    /// Failed to build gRPC reflection service: {0}
+    GrpcReflection(tonic_reflection::server::Error),
 }
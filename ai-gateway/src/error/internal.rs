@@ -79,6 +79,8 @@ pub enum InternalError {
     PromptError(#[from] crate::error::prompts::PromptError),
     /// Failed to complete prompt task: {0}
     PromptTaskError(tokio::task::JoinError),
+    /// Truncated response from provider: {0}
+    TruncatedResponse(String),
 }
 
 impl IntoResponse for InternalError {
@@ -158,6 +160,8 @@ pub enum InternalErrorMetric {
     PoolError,
     /// Prompt error
     PromptError,
+    /// Truncated response from provider
+    TruncatedResponse,
 }
 
 impl From<&InternalError> for InternalErrorMetric {
@@ -197,6 +201,7 @@ impl From<&InternalError> for InternalErrorMetric {
             InternalError::RedisError(_) => Self::RedisError,
             InternalError::PoolError(_) => Self::PoolError,
             InternalError::PromptError(_) => Self::PromptError,
+            InternalError::TruncatedResponse(_) => Self::TruncatedResponse,
             InternalError::DynamicRouterDiscoveryError(_) => {
                 Self::DynamicRouterDiscoveryError
             }
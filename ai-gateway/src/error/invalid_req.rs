@@ -20,6 +20,8 @@ pub struct TooManyRequestsError {
     /// Number of seconds in which the API will become available again after
     /// its rate limit has been exceeded
     pub retry_after: u64,
+    /// Number of seconds until the rate limit is fully replenished
+    pub ratelimit_reset: u64,
 }
 
 /// User errors
@@ -51,10 +53,54 @@ pub enum InvalidRequestError {
     InvalidCacheConfig,
     /// Too many requests: {0}
     TooManyRequests(TooManyRequestsError),
+    /// Too many tokens: over the token per minute budget
+    #[displaydoc(
+        "Too many tokens: the request needs an estimated {estimated_tokens} \
+         of the {tokens_per_minute} tokens per minute budget. {limits}"
+    )]
+    TokenBudgetExceeded {
+        estimated_tokens: u32,
+        tokens_per_minute: u32,
+        limits: TooManyRequestsError,
+    },
+    /// Too many requests for the models matching `{glob}`. {limits}
+    ModelRateLimitExceeded {
+        glob: String,
+        limits: TooManyRequestsError,
+    },
+    /// Request is larger than the token per minute budget
+    #[displaydoc(
+        "The request needs an estimated {estimated_tokens} tokens, more than \
+         the budget of {tokens_per_minute} tokens per minute"
+    )]
+    PromptExceedsTokenBudget {
+        estimated_tokens: u32,
+        tokens_per_minute: u32,
+    },
     /// Invalid request header: {0}
     InvalidRequestHeader(http::header::ToStrError),
     /// Invalid prompt inputs: {0}
     InvalidPromptInputs(String),
+    /// Model not allowed by this router: {0}
+    ModelNotAllowed(String),
+    /// Invalid image: {0}
+    InvalidImage(String),
+    /// Image is {size} bytes, larger than the {max} byte limit
+    ImageTooLarge { size: usize, max: usize },
+    /// Request body is larger than the {limit} byte limit
+    RequestBodyTooLarge { limit: u64 },
+    /// Unsupported request: {0}
+    Unsupported(String),
+    /// Cannot count tokens for {model}, supported models: {supported}
+    UnsupportedTokenizerModel { model: String, supported: String },
+    /// Provider {provider} is not in this router, allowed: {allowed}
+    ProviderOverrideNotAllowed { provider: String, allowed: String },
+    /// Invalid priority `{0}`, expected one of: high, normal, low
+    InvalidPriority(String),
+    /// A request with this idempotency key is still in progress
+    IdempotencyKeyInProgress,
+    /// This idempotency key was already used for a different request
+    IdempotencyKeyReused,
 }
 
 impl IntoResponse for InvalidRequestError {
@@ -86,7 +132,57 @@ impl IntoResponse for InvalidRequestError {
                 }),
             )
                 .into_response(),
-            Self::TooManyRequests(error) => {
+            Self::ImageTooLarge { .. } | Self::RequestBodyTooLarge { .. } => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorResponse {
+                    error: ErrorDetails {
+                        message,
+                        r#type: Some(INVALID_REQUEST_ERROR_TYPE.to_string()),
+                        param: None,
+                        code: None,
+                    },
+                }),
+            )
+                .into_response(),
+            Self::IdempotencyKeyInProgress => (
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: ErrorDetails {
+                        message,
+                        r#type: Some(INVALID_REQUEST_ERROR_TYPE.to_string()),
+                        param: None,
+                        code: None,
+                    },
+                }),
+            )
+                .into_response(),
+            Self::IdempotencyKeyReused => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse {
+                    error: ErrorDetails {
+                        message,
+                        r#type: Some(INVALID_REQUEST_ERROR_TYPE.to_string()),
+                        param: None,
+                        code: None,
+                    },
+                }),
+            )
+                .into_response(),
+            Self::UnsupportedTokenizerModel { .. } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse {
+                    error: ErrorDetails {
+                        message,
+                        r#type: Some(INVALID_REQUEST_ERROR_TYPE.to_string()),
+                        param: Some("model".to_string()),
+                        code: None,
+                    },
+                }),
+            )
+                .into_response(),
+            Self::TooManyRequests(error)
+            | Self::TokenBudgetExceeded { limits: error, .. }
+            | Self::ModelRateLimitExceeded { limits: error, .. } => {
                 let mut headers = HeaderMap::new();
                 headers.insert(
                     "retry-after",
@@ -104,6 +200,10 @@ impl IntoResponse for InvalidRequestError {
                     "x-ratelimit-remaining",
                     error.ratelimit_remaining.to_string().parse().unwrap(),
                 );
+                headers.insert(
+                    "x-ratelimit-reset",
+                    error.ratelimit_reset.to_string().parse().unwrap(),
+                );
                 (
                     StatusCode::TOO_MANY_REQUESTS,
                     headers,
@@ -171,6 +271,17 @@ impl From<&InvalidRequestError> for InvalidRequestErrorMetric {
             | InvalidRequestError::UnsupportedEndpoint(_)
             | InvalidRequestError::InvalidCacheConfig
             | InvalidRequestError::InvalidPromptInputs(_)
+            | InvalidRequestError::ModelNotAllowed(_)
+            | InvalidRequestError::InvalidImage(_)
+            | InvalidRequestError::ImageTooLarge { .. }
+            | InvalidRequestError::RequestBodyTooLarge { .. }
+            | InvalidRequestError::Unsupported(_)
+            | InvalidRequestError::UnsupportedTokenizerModel { .. }
+            | InvalidRequestError::ProviderOverrideNotAllowed { .. }
+            | InvalidRequestError::InvalidPriority(_)
+            | InvalidRequestError::IdempotencyKeyInProgress
+            | InvalidRequestError::IdempotencyKeyReused
+            | InvalidRequestError::PromptExceedsTokenBudget { .. }
             | InvalidRequestError::MissingModelId
             | InvalidRequestError::InvalidModelId => Self::InvalidRequest,
             InvalidRequestError::InvalidUrl(_) => Self::InvalidUrl,
@@ -178,7 +289,11 @@ impl From<&InvalidRequestError> for InvalidRequestErrorMetric {
                 Self::InvalidRequestBody
             }
             InvalidRequestError::Provider4xxError(_) => Self::Provider4xxError,
-            InvalidRequestError::TooManyRequests(_) => Self::TooManyRequests,
+            InvalidRequestError::TooManyRequests(_)
+            | InvalidRequestError::TokenBudgetExceeded { .. }
+            | InvalidRequestError::ModelRateLimitExceeded { .. } => {
+                Self::TooManyRequests
+            }
         }
     }
 }
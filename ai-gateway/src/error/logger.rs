@@ -20,4 +20,8 @@ pub enum LoggerError {
     NoAuthContextSet,
     /// Unexpected response: {0}
     UnexpectedResponse(String),
+    /// Failed to upload the batch of bodies this request's were in
+    BatchUploadFailed,
+    /// Invalid redaction pattern: {0}
+    InvalidRedactionPattern(#[from] regex::Error),
 }
@@ -30,8 +30,12 @@ pub enum MapperError {
     ToolMappingInvalid(String),
     /// Image mapping invalid: {0}
     ImageMappingInvalid(String),
+    /// Image is {size} bytes, larger than the {max} byte limit
+    ImageTooLarge { size: usize, max: usize },
     /// Failed to map Bedrock message: {0}
     FailedToMapBedrockMessage(BoxError),
+    /// Structured outputs can't be streamed from {0}
+    StructuredOutputStreaming(InferenceProvider),
 }
 
 /// Error types that can occur when mapping requests between providers.
@@ -59,8 +63,12 @@ pub enum MapperErrorMetric {
     ToolMappingInvalid,
     /// Image mapping invalid
     ImageMappingInvalid,
+    /// Image too large
+    ImageTooLarge,
     /// Failed to map Bedrock message
     FailedToMapBedrockMessage,
+    /// Structured outputs can't be streamed
+    StructuredOutputStreaming,
 }
 
 impl From<&MapperError> for MapperErrorMetric {
@@ -77,9 +85,13 @@ impl From<&MapperError> for MapperErrorMetric {
             MapperError::ProviderNotSupported(_) => Self::ProviderNotSupported,
             MapperError::ToolMappingInvalid(_) => Self::ToolMappingInvalid,
             MapperError::ImageMappingInvalid(_) => Self::ImageMappingInvalid,
+            MapperError::ImageTooLarge { .. } => Self::ImageTooLarge,
             MapperError::FailedToMapBedrockMessage(_) => {
                 Self::FailedToMapBedrockMessage
             }
+            MapperError::StructuredOutputStreaming(_) => {
+                Self::StructuredOutputStreaming
+            }
         }
     }
 }
@@ -13,6 +13,10 @@ pub enum RuntimeError {
     Internal(#[from] InternalError),
     /// Failed to serve: {0}
     Serve(std::io::Error),
+    /// Failed to serve gRPC: {0}
+    GrpcServe(tonic::transport::Error),
+    /// Failed to register signal handler: {0}
+    Signal(std::io::Error),
     /// Join tokio task: {0}
     Join(#[from] tokio::task::JoinError),
     /// Telemetry: {0}
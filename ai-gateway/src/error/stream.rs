@@ -1,4 +1,5 @@
 use axum_core::response::{IntoResponse, Response};
+use bytes::Bytes;
 use displaydoc::Display;
 use http::StatusCode;
 use thiserror::Error;
@@ -18,6 +19,10 @@ pub enum StreamError {
     StreamError(#[from] Box<reqwest_eventsource::Error>),
     /// Body error: {0}
     BodyError(axum_core::Error),
+    /// No event received from the provider for {0:?}
+    IdleTimeout(std::time::Duration),
+    /// Invalid event stream from the provider: {0}
+    EventStream(String),
 }
 
 impl StreamError {
@@ -37,9 +42,27 @@ impl StreamError {
                 | reqwest_eventsource::Error::InvalidContentType(_, _)
                 | reqwest_eventsource::Error::StreamEnded => false,
             },
-            StreamError::BodyError(_error) => false,
+            StreamError::BodyError(_error)
+            | StreamError::IdleTimeout(_duration)
+            | StreamError::EventStream(_error) => false,
         }
     }
+
+    /// The event a stream ends with when it fails after the response has
+    /// started, since its status code was already sent.
+    #[must_use]
+    pub fn error_event(&self) -> Bytes {
+        let response = ErrorResponse {
+            error: ErrorDetails {
+                message: self.to_string(),
+                r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                param: None,
+                code: None,
+            },
+        };
+        let data = serde_json::to_string(&response).unwrap_or_default();
+        Bytes::from(format!("event: error\ndata: {data}\n\n"))
+    }
 }
 
 impl IntoResponse for StreamError {
@@ -123,6 +146,36 @@ impl IntoResponse for StreamError {
                 }),
             )
                 .into_response(),
+            Self::EventStream(_error) => {
+                tracing::error!(error = %self, "invalid event stream");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
+            Self::IdleTimeout(_duration) => {
+                tracing::warn!(error = %self, "provider stream went idle");
+                (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    Json(ErrorResponse {
+                        error: ErrorDetails {
+                            message: self.to_string(),
+                            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+                            param: None,
+                            code: None,
+                        },
+                    }),
+                )
+                    .into_response()
+            }
         }
     }
 }
@@ -136,6 +189,10 @@ pub enum StreamErrorMetric {
     StreamError,
     /// Body error
     BodyError,
+    /// Idle timeout
+    IdleTimeout,
+    /// Event stream
+    EventStream,
 }
 
 impl From<&StreamError> for StreamErrorMetric {
@@ -143,6 +200,8 @@ impl From<&StreamError> for StreamErrorMetric {
         match error {
             StreamError::StreamError(_) => Self::StreamError,
             StreamError::BodyError(_) => Self::BodyError,
+            StreamError::IdleTimeout(_) => Self::IdleTimeout,
+            StreamError::EventStream(_) => Self::EventStream,
         }
     }
 }
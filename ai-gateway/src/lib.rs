@@ -15,5 +15,6 @@ pub(crate) mod router;
 pub mod store;
 #[cfg(feature = "testing")]
 pub mod tests;
+pub mod tokenizer;
 pub mod types;
 pub mod utils;
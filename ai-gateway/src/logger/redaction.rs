@@ -0,0 +1,252 @@
+//! Redaction of request and response bodies before they are uploaded for
+//! logging.
+use bytes::Bytes;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::redaction::RedactionConfig;
+
+/// Keys whose values are message content, across the request and response
+/// formats of the providers we support.
+const CONTENT_KEYS: &[&str] = &[
+    "arguments",
+    "content",
+    "contents",
+    "input",
+    "instructions",
+    "message",
+    "output",
+    "parts",
+    "preamble",
+    "prompt",
+    "reasoning_content",
+    "system",
+    "systemInstruction",
+    "text",
+    "texts",
+    "thinking",
+];
+
+/// Keys which describe the structure of message content rather than being
+/// content themselves, so are kept for analytics.
+const STRUCTURAL_KEYS: &[&str] = &[
+    "finish_reason",
+    "id",
+    "media_type",
+    "mimeType",
+    "mime_type",
+    "model",
+    "name",
+    "object",
+    "role",
+    "status",
+    "stop_reason",
+    "tool_call_id",
+    "tool_use_id",
+    "type",
+];
+
+const SSE_DATA_PREFIX: &str = "data: ";
+
+pub struct Redactor {
+    message_content: bool,
+    pattern: Option<Regex>,
+    placeholder: String,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Result<Self, regex::Error> {
+        let pattern = if config.patterns.is_empty() {
+            None
+        } else {
+            let pattern = config
+                .patterns
+                .iter()
+                .map(|pattern| format!("(?:{})", pattern.regex()))
+                .collect::<Vec<_>>()
+                .join("|");
+            Some(Regex::new(&pattern)?)
+        };
+        Ok(Self {
+            message_content: config.message_content,
+            pattern,
+            placeholder: config.placeholder().to_string(),
+        })
+    }
+
+    /// Redacts a JSON body, or a body of server-sent events with JSON data
+    /// for streamed responses. Patterns are still applied to other text
+    /// bodies.
+    #[must_use]
+    pub fn redact(&self, body: Bytes) -> Bytes {
+        if body.is_empty() {
+            return body;
+        }
+        if let Ok(mut value) = serde_json::from_slice::<Value>(&body) {
+            self.redact_value(&mut value, false);
+            return self.serialize(&value);
+        }
+        let Ok(text) = std::str::from_utf8(&body) else {
+            return body;
+        };
+        let redacted = text
+            .split('\n')
+            .map(|line| {
+                if let Some(data) = line.strip_prefix(SSE_DATA_PREFIX)
+                    && let Ok(mut value) = serde_json::from_str::<Value>(data)
+                {
+                    self.redact_value(&mut value, false);
+                    let data = self.serialize(&value);
+                    format!(
+                        "{SSE_DATA_PREFIX}{}",
+                        String::from_utf8_lossy(&data)
+                    )
+                } else {
+                    self.redact_str(line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Bytes::from(redacted)
+    }
+
+    fn redact_value(&self, value: &mut Value, is_content: bool) {
+        match value {
+            Value::String(string) => {
+                if is_content {
+                    string.clone_from(&self.placeholder);
+                } else {
+                    *string = self.redact_str(string);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.redact_value(item, is_content);
+                }
+            }
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let key = key.as_str();
+                    let is_content = if STRUCTURAL_KEYS.contains(&key) {
+                        false
+                    } else {
+                        is_content
+                            || (self.message_content
+                                && CONTENT_KEYS.contains(&key))
+                    };
+                    self.redact_value(value, is_content);
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+
+    fn redact_str(&self, string: &str) -> String {
+        match &self.pattern {
+            Some(pattern) => pattern
+                .replace_all(string, self.placeholder.as_str())
+                .into_owned(),
+            None => string.to_string(),
+        }
+    }
+
+    /// Falls back to the placeholder rather than logging an unredacted body.
+    fn serialize(&self, value: &Value) -> Bytes {
+        serde_json::to_vec(value)
+            .map_or_else(|_| Bytes::from(self.placeholder.clone()), Bytes::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::redaction::RedactionPattern;
+
+    fn redact(config: &RedactionConfig, body: &Value) -> Value {
+        let redactor = Redactor::new(config).unwrap();
+        let body = Bytes::from(serde_json::to_vec(body).unwrap());
+        serde_json::from_slice(&redactor.redact(body)).unwrap()
+    }
+
+    #[test]
+    fn message_content_is_replaced() {
+        let config = RedactionConfig {
+            message_content: true,
+            ..Default::default()
+        };
+        let request = json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                { "role": "system", "content": "You are helpful." },
+                {
+                    "role": "user",
+                    "content": [{ "type": "text", "text": "Hi, I'm Ada." }]
+                }
+            ],
+            "temperature": 0.5
+        });
+        assert_eq!(
+            redact(&config, &request),
+            json!({
+                "model": "openai/gpt-4o-mini",
+                "messages": [
+                    { "role": "system", "content": "[REDACTED]" },
+                    {
+                        "role": "user",
+                        "content": [{ "type": "text", "text": "[REDACTED]" }]
+                    }
+                ],
+                "temperature": 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn patterns_are_replaced_everywhere() {
+        let config = RedactionConfig {
+            patterns: vec![
+                RedactionPattern::Email,
+                RedactionPattern::CreditCard,
+            ],
+            placeholder: Some("***".to_string()),
+            ..Default::default()
+        };
+        let request = json!({
+            "messages": [{
+                "role": "user",
+                "content": "I'm ada@example.com, card 4242 4242 4242 4242"
+            }],
+            "user": "ada@example.com"
+        });
+        assert_eq!(
+            redact(&config, &request),
+            json!({
+                "messages": [{
+                    "role": "user",
+                    "content": "I'm ***, card ***"
+                }],
+                "user": "***"
+            })
+        );
+    }
+
+    #[test]
+    fn streamed_responses_are_redacted() {
+        let config = RedactionConfig {
+            message_content: true,
+            ..Default::default()
+        };
+        let redactor = Redactor::new(&config).unwrap();
+        let body = Bytes::from_static(
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\ndata: [DONE]\n\n",
+        );
+        assert_eq!(
+            redactor.redact(body),
+            "data: {\"choices\":[{\"delta\":{\"content\":\"[REDACTED]\"}}]}\n\\
+             \
+             ndata: [DONE]\n\n"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use base64::Engine;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use http::{HeaderMap, StatusCode};
@@ -7,6 +8,7 @@ use http_body_util::BodyExt;
 use indexmap::IndexMap;
 use opentelemetry::KeyValue;
 use reqwest::Client;
+use serde::Serialize;
 use tokio::{sync::oneshot, time::Instant};
 use typed_builder::TypedBuilder;
 use url::Url;
@@ -14,16 +16,26 @@ use uuid::Uuid;
 
 use crate::{
     app_state::AppState,
-    config::DeploymentTarget,
+    config::{
+        DeploymentTarget, minio::AudioStorage, redaction::RedactionConfig,
+        request_logging::RequestLogging,
+    },
+    endpoints::{Endpoint, openai::AudioSpeech},
     error::{init::InitError, logger::LoggerError},
+    logger::redaction::Redactor,
     metrics::tfft::TFFTFuture,
+    middleware::{
+        mapper::prompt_caching::CacheUsage,
+        usage::{ResponseUsage, Usage},
+    },
     store::minio::MinioClient,
     types::{
         body::BodyReader,
-        extensions::{AuthContext, MapperContext},
+        extensions::{AuthContext, CanaryArm, EvaluationScore, MapperContext},
         logger::{
             HeliconeLogMetadata, Log, LogMessage, RequestLog, ResponseLog,
         },
+        multipart::MultipartMetadata,
         provider::InferenceProvider,
         router::RouterId,
     },
@@ -48,6 +60,25 @@ impl JawnClient {
     }
 }
 
+/// Property recording which arm of a canary router served the request.
+const CANARY_ARM_PROPERTY: &str = "Helicone-Canary-Arm";
+/// Property attached to logs of requests a client pinned to a provider.
+const PROVIDER_OVERRIDE_PROPERTY: &str = "Helicone-Provider-Override";
+/// Property recording the score a judge model gave the response.
+const EVALUATION_SCORE_PROPERTY: &str = "Helicone-Evaluation-Score";
+/// Property attached to logs whose usage was estimated, since the provider
+/// didn't report it.
+const USAGE_ESTIMATED_PROPERTY: &str = "Helicone-Usage-Estimated";
+
+/// Logged in place of the audio of a speech response.
+#[derive(Debug, Serialize)]
+struct SpeechMetadata {
+    /// Size of the audio in bytes.
+    audio_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audio_prefix: Option<String>,
+}
+
 #[derive(Debug, TypedBuilder)]
 pub struct LoggerService {
     app_state: AppState,
@@ -55,7 +86,11 @@ pub struct LoggerService {
     start_time: DateTime<Utc>,
     start_instant: Instant,
     response_body: BodyReader,
+    /// Empty for multipart uploads, which are streamed to the provider and
+    /// logged from their `multipart_metadata` instead.
     request_body: Bytes,
+    #[builder(default)]
+    multipart_metadata: Option<oneshot::Receiver<MultipartMetadata>>,
     target_url: Url,
     request_headers: HeaderMap,
     response_status: StatusCode,
@@ -64,6 +99,16 @@ pub struct LoggerService {
     router_id: Option<RouterId>,
     deployment_target: DeploymentTarget,
     tfft_rx: oneshot::Receiver<()>,
+    #[builder(default)]
+    canary_arm: Option<CanaryArm>,
+    #[builder(default)]
+    is_provider_override: bool,
+    #[builder(default)]
+    evaluation_score: Option<EvaluationScore>,
+    #[builder(default)]
+    logging: RequestLogging,
+    #[builder(default)]
+    redaction: RedactionConfig,
 }
 
 impl LoggerService {
@@ -79,37 +124,113 @@ impl LoggerService {
             .inspect_err(|_| tracing::error!("infallible errored"))
             .expect("infallible never errors")
             .to_bytes();
+        let cache_usage = if self.provider == InferenceProvider::Anthropic {
+            CacheUsage::from_logged_response(&response_body)
+        } else {
+            None
+        };
+        let is_stream = self.mapper_ctx.is_stream;
+        let reported_usage =
+            ResponseUsage::from_body(&response_body, is_stream).usage;
+        let (usage, is_usage_estimated) = match reported_usage {
+            Some(usage) => (Some(usage), false),
+            None if is_stream && self.response_status.is_success() => {
+                let usage =
+                    Usage::estimate_stream(&self.request_body, &response_body);
+                tracing::debug!(usage = ?usage, "estimated usage of stream");
+                (Some(usage), true)
+            }
+            None => (None, false),
+        };
         let tfft_duration = tfft_duration.unwrap_or_else(|_| {
             tracing::error!("Failed to get TFFT signal");
             Duration::from_secs(0)
         });
         tracing::trace!(tfft_duration = ?tfft_duration, "tfft_duration");
-        let req_body_len = self.request_body.len();
+        // multipart bodies (e.g. audio uploads) are binary, so we log a
+        // summary of the form fields rather than the raw body
+        let multipart_metadata = match self.multipart_metadata.take() {
+            Some(multipart_metadata) => multipart_metadata.await.ok(),
+            None => None,
+        }
+        .map(|mut metadata| {
+            metadata.set_duration_from_response(&response_body);
+            metadata
+        });
+        let req_body_len = multipart_metadata
+            .as_ref()
+            .map_or(self.request_body.len(), |metadata| metadata.body_size);
         let resp_body_len = response_body.len();
-        let request_id = Uuid::new_v4();
-        let s3_client = match self.app_state.config().deployment_target {
-            DeploymentTarget::Cloud => {
-                MinioClient::cloud(&self.app_state.0.minio)
-            }
-            DeploymentTarget::Sidecar => {
-                MinioClient::sidecar(&self.app_state.0.jawn_http_client)
-            }
+        let audio_storage = self.app_state.config().minio.audio;
+        let request_body = if let Some(metadata) = &multipart_metadata {
+            tracing::trace!(metadata = ?metadata, "logging multipart request");
+            Bytes::from(
+                serde_json::to_vec(metadata)
+                    .map_err(|_| LoggerError::InvalidLogMessage)?,
+            )
+        } else {
+            self.request_body
         };
-        s3_client
-            .log_bodies(
-                &self.app_state,
-                &self.auth_ctx,
-                request_id,
-                self.request_body,
-                response_body,
+        // as are speech responses, which are the raw audio
+        let response_body = if self.response_status.is_success()
+            && self.target_url.path().ends_with(AudioSpeech::PATH)
+        {
+            let audio_prefix = match audio_storage {
+                AudioStorage::Skip => None,
+                AudioStorage::Truncate { max_bytes } => {
+                    let len = response_body.len().min(max_bytes);
+                    Some(
+                        base64::engine::general_purpose::STANDARD
+                            .encode(&response_body[..len]),
+                    )
+                }
+            };
+            let metadata = SpeechMetadata {
+                audio_size: response_body.len(),
+                audio_prefix,
+            };
+            tracing::trace!(metadata = ?metadata, "logging speech response");
+            Bytes::from(
+                serde_json::to_vec(&metadata)
+                    .map_err(|_| LoggerError::InvalidLogMessage)?,
             )
-            .await?;
+        } else {
+            response_body
+        };
+        // redact before the bodies are signed and uploaded, so that nothing
+        // sensitive leaves the process
+        let (request_body, response_body) = if self.redaction.is_enabled() {
+            let redactor = Redactor::new(&self.redaction)?;
+            (
+                redactor.redact(request_body),
+                redactor.redact(response_body),
+            )
+        } else {
+            (request_body, response_body)
+        };
+        let request_id = Uuid::new_v4();
+        let body_location = if self.logging.is_full() {
+            MinioClient::for_deployment_target(&self.app_state)
+                .log_bodies(
+                    &self.app_state,
+                    &self.auth_ctx,
+                    request_id,
+                    request_body,
+                    response_body,
+                )
+                .await?
+        } else {
+            tracing::trace!("skipping body upload for metadata only logging");
+            None
+        };
 
         let model = self
             .mapper_ctx
             .model
             .as_ref()
-            .map_or_else(|| "unknown".to_string(), ToString::to_string);
+            .map(ToString::to_string)
+            .or_else(|| multipart_metadata.and_then(|m| m.model))
+            .unwrap_or_else(|| "unknown".to_string());
         let attributes = [
             KeyValue::new("provider", self.provider.to_string()),
             KeyValue::new("model", model),
@@ -132,16 +253,47 @@ impl LoggerService {
             InferenceProvider::GoogleGemini => "GOOGLE".to_string(),
             provider => provider.to_string().to_uppercase(),
         };
+        let mut properties = IndexMap::new();
+        if let Some(canary_arm) = self.canary_arm {
+            properties.insert(
+                CANARY_ARM_PROPERTY.to_string(),
+                canary_arm.as_str().to_string(),
+            );
+        }
+        if self.is_provider_override {
+            properties.insert(
+                PROVIDER_OVERRIDE_PROPERTY.to_string(),
+                "true".to_string(),
+            );
+        }
+        if is_usage_estimated {
+            properties.insert(
+                USAGE_ESTIMATED_PROPERTY.to_string(),
+                "true".to_string(),
+            );
+        }
+        if let Some(EvaluationScore(mut score_rx)) = self.evaluation_score {
+            // the sender is dropped without a score if the evaluation fails
+            if let Ok(score) = score_rx.wait_for(Option::is_some).await
+                && let Some(score) = *score
+            {
+                properties.insert(
+                    EVALUATION_SCORE_PROPERTY.to_string(),
+                    score.to_string(),
+                );
+            }
+        }
         let request_log = RequestLog::builder()
             .id(request_id)
             .user_id(self.auth_ctx.user_id)
-            .properties(IndexMap::new())
+            .properties(properties)
             .target_url(self.target_url)
             .provider(provider)
             .body_size(req_body_len as f64)
             .path(req_path)
             .request_created_at(self.start_time)
             .is_stream(false)
+            .body_location(body_location)
             .build();
         let response_log = ResponseLog::builder()
             .id(request_id)
@@ -149,6 +301,14 @@ impl LoggerService {
             .body_size(resp_body_len as f64)
             .response_created_at(Utc::now())
             .delay_ms(tfft_duration.as_millis() as f64)
+            .cache_creation_input_tokens(
+                cache_usage.map(|usage| usage.creation_input_tokens),
+            )
+            .cache_read_input_tokens(
+                cache_usage.map(|usage| usage.read_input_tokens),
+            )
+            .prompt_tokens(usage.map(|usage| usage.prompt_tokens))
+            .completion_tokens(usage.map(|usage| usage.completion_tokens))
             .build();
         let log = Log::new(request_log, response_log);
         let log_message = LogMessage::builder()
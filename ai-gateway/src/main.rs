@@ -4,14 +4,18 @@ use ai_gateway::{
     app::App,
     config::{Config, DeploymentTarget},
     control_plane::websocket::ControlPlaneClient,
-    discover::monitor::{
-        health::provider::HealthMonitor, rate_limit::RateLimitMonitor,
+    discover::{
+        monitor::{
+            credentials::CredentialMonitor, health::provider::HealthMonitor,
+            probe::ProbeMonitor, rate_limit::RateLimitMonitor,
+        },
+        router::reload::ConfigWatcher,
     },
     error::{init::InitError, runtime::RuntimeError},
     metrics::system::SystemMetrics,
     middleware::rate_limit,
     store::db_listener::DatabaseListener,
-    utils::meltdown::TaggedService,
+    utils::{grpc_health::GrpcHealthServer, meltdown::TaggedService},
 };
 use clap::Parser;
 use meltdown::Meltdown;
@@ -39,11 +43,11 @@ pub struct Args {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
-    let config = load_and_validate_config()?;
+    let (config, config_path) = load_and_validate_config()?;
     let (logger_provider, tracer_provider, metrics_provider) =
         init_telemetry(&config)?;
 
-    run_app(config).await?;
+    run_app(config, config_path).await?;
 
     shutdown_telemetry(logger_provider, &tracer_provider, metrics_provider);
 
@@ -52,10 +56,11 @@ async fn main() -> Result<(), RuntimeError> {
     Ok(())
 }
 
-fn load_and_validate_config() -> Result<Config, RuntimeError> {
+fn load_and_validate_config() -> Result<(Config, Option<PathBuf>), RuntimeError>
+{
     dotenvy::dotenv().ok();
     let args = Args::parse();
-    let mut config = match Config::try_read(args.config) {
+    let mut config = match Config::try_read(args.config.clone()) {
         Ok(config) => config,
         Err(error) => {
             eprintln!("failed to read config: {error}");
@@ -72,7 +77,7 @@ fn load_and_validate_config() -> Result<Config, RuntimeError> {
         tracing::error!(error = %e, "configuration validation failed");
     })?;
 
-    Ok(config)
+    Ok((config, args.config))
 }
 
 fn init_telemetry(
@@ -99,7 +104,10 @@ fn init_telemetry(
     Ok((logger_provider, tracer_provider, metrics_provider))
 }
 
-async fn run_app(config: Config) -> Result<(), RuntimeError> {
+async fn run_app(
+    config: Config,
+    config_path: Option<PathBuf>,
+) -> Result<(), RuntimeError> {
     // 5 mins
     const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 5);
     let mut shutting_down = false;
@@ -110,13 +118,13 @@ async fn run_app(config: Config) -> Result<(), RuntimeError> {
     let rate_limit_monitor = RateLimitMonitor::new(app.state.clone());
     let control_plane_state = app.state.0.control_plane_state.clone();
 
+    // routers can be rate limited in memory without a global rate limit, and
+    // in the cloud routers are only known once they're discovered
     let rate_limiting_cleanup_service =
-        config.global.rate_limit.as_ref().map(|_| {
-            rate_limit::cleanup::GarbageCollector::new(
-                app.state.clone(),
-                CLEANUP_INTERVAL,
-            )
-        });
+        rate_limit::cleanup::GarbageCollector::new(
+            app.state.clone(),
+            CLEANUP_INTERVAL,
+        );
 
     let mut tasks = vec![
         "shutdown-signals",
@@ -124,6 +132,7 @@ async fn run_app(config: Config) -> Result<(), RuntimeError> {
         "provider-health-monitor",
         "provider-rate-limit-monitor",
         "system-metrics",
+        "rate-limiting-cleanup",
     ];
     let mut meltdown = Meltdown::new().register(TaggedService::new(
         "shutdown-signals",
@@ -134,7 +143,8 @@ async fn run_app(config: Config) -> Result<(), RuntimeError> {
         meltdown = meltdown.register(TaggedService::new(
             "control-plane-client",
             ControlPlaneClient::connect(control_plane_state, helicone_config)
-                .await?,
+                .await?
+                .with_app_state(app.state.clone()),
         ));
         tasks.push("control-plane-client");
     }
@@ -153,6 +163,40 @@ async fn run_app(config: Config) -> Result<(), RuntimeError> {
         tasks.push("database-listener");
     }
 
+    if app.state.0.config.deployment_target == DeploymentTarget::Sidecar
+        && app.state.0.config.discover.monitor.credentials.is_some()
+    {
+        meltdown = meltdown.register(TaggedService::new(
+            "provider-credential-monitor",
+            CredentialMonitor::new(app.state.clone()),
+        ));
+        tasks.push("provider-credential-monitor");
+    }
+
+    if app.state.0.config.discover.monitor.probes.is_some() {
+        meltdown = meltdown.register(TaggedService::new(
+            "provider-probe-monitor",
+            ProbeMonitor::new(app.state.clone()),
+        ));
+        tasks.push("provider-probe-monitor");
+    }
+
+    if app.state.0.config.deployment_target == DeploymentTarget::Sidecar {
+        meltdown = meltdown.register(TaggedService::new(
+            "config-watcher",
+            ConfigWatcher::new(app.state.clone(), config_path),
+        ));
+        tasks.push("config-watcher");
+    }
+
+    if app.state.0.config.server.grpc_health_port.is_some() {
+        meltdown = meltdown.register(TaggedService::new(
+            "grpc-health",
+            GrpcHealthServer::new(app.state.clone()),
+        ));
+        tasks.push("grpc-health");
+    }
+
     meltdown = meltdown
         .register(TaggedService::new("gateway", app))
         .register(TaggedService::new(
@@ -163,15 +207,11 @@ async fn run_app(config: Config) -> Result<(), RuntimeError> {
             "provider-rate-limit-monitor",
             rate_limit_monitor,
         ))
-        .register(TaggedService::new("system-metrics", SystemMetrics));
-
-    if let Some(rate_limiting_cleanup_service) = rate_limiting_cleanup_service {
-        meltdown = meltdown.register(TaggedService::new(
+        .register(TaggedService::new("system-metrics", SystemMetrics))
+        .register(TaggedService::new(
             "rate-limiting-cleanup",
             rate_limiting_cleanup_service,
         ));
-        tasks.push("rate-limiting-cleanup");
-    }
 
     info!(tasks = ?tasks, "starting services");
 
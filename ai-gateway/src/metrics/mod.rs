@@ -14,11 +14,20 @@ pub use self::rolling_counter::RollingCounter;
 pub struct Metrics {
     pub error_count: Counter<u64>,
     pub provider_health: Gauge<u64>,
+    pub provider_credential_health: Gauge<u64>,
+    pub credential_failures: Counter<u64>,
     pub auth_attempts: Counter<u64>,
     pub auth_rejections: Counter<u64>,
     pub request_count: Counter<u64>,
     pub response_count: Counter<u64>,
     pub tfft_duration: Histogram<f64>,
+    pub config_reloads: Counter<u64>,
+    pub provider_in_flight: Gauge<u64>,
+    pub provider_timeouts: Counter<u64>,
+    pub shadow_requests: Counter<u64>,
+    pub evaluations: Counter<u64>,
+    pub rate_limit_queue_depth: Gauge<u64>,
+    pub rate_limit_queue_wait: Histogram<f64>,
     pub cache: CacheMetrics,
 }
 
@@ -40,6 +49,18 @@ impl Metrics {
             .u64_gauge("provider_health")
             .with_description("Upstream provider health")
             .build();
+        let provider_credential_health = meter
+            .u64_gauge("provider_credential_health")
+            .with_description(
+                "Whether the upstream provider credential is valid",
+            )
+            .build();
+        let credential_failures = meter
+            .u64_counter("credential_failures")
+            .with_description(
+                "Number of provider credentials rejected by the provider",
+            )
+            .build();
         let auth_attempts = meter
             .u64_counter("auth_attempts")
             .with_description("Number of authentication attempts")
@@ -61,6 +82,39 @@ impl Metrics {
             .with_unit("ms")
             .with_description("Time to first token duration")
             .build();
+        let config_reloads = meter
+            .u64_counter("config_reloads")
+            .with_description("Number of router config reloads")
+            .build();
+        let provider_in_flight = meter
+            .u64_gauge("provider_in_flight_requests")
+            .with_description(
+                "In-flight requests for providers with a concurrency limit",
+            )
+            .build();
+        let provider_timeouts = meter
+            .u64_counter("provider_timeouts")
+            .with_description(
+                "Number of requests to providers which timed out, by kind",
+            )
+            .build();
+        let shadow_requests = meter
+            .u64_counter("shadow_requests")
+            .with_description("Number of requests mirrored to shadow providers")
+            .build();
+        let evaluations = meter
+            .u64_counter("evaluations")
+            .with_description("Number of responses scored by a judge model")
+            .build();
+        let rate_limit_queue_depth = meter
+            .u64_gauge("rate_limit_queue_depth")
+            .with_description("Requests waiting in a rate limit queue")
+            .build();
+        let rate_limit_queue_wait = meter
+            .f64_histogram("rate_limit_queue_wait")
+            .with_unit("ms")
+            .with_description("Time requests waited in a rate limit queue")
+            .build();
         let cache_hits = meter
             .u64_counter("cache_hits")
             .with_description("Number of cache hits")
@@ -81,11 +135,20 @@ impl Metrics {
         Self {
             error_count,
             provider_health,
+            provider_credential_health,
+            credential_failures,
             auth_attempts,
             auth_rejections,
             request_count,
             response_count,
             tfft_duration,
+            config_reloads,
+            provider_in_flight,
+            provider_timeouts,
+            shadow_requests,
+            evaluations,
+            rate_limit_queue_depth,
+            rate_limit_queue_wait,
             cache,
         }
     }
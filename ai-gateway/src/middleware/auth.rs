@@ -2,6 +2,7 @@ use axum_core::response::IntoResponse;
 use futures::future::BoxFuture;
 use http::Request;
 use tower_http::auth::AsyncAuthorizeRequest;
+use tracing::Instrument;
 
 use crate::{
     app_state::AppState,
@@ -67,13 +68,13 @@ impl AuthService {
                                 Err(AuthError::RouterNotFound)
                             }
                         }
-                        RequestKind::UnifiedApi | RequestKind::DirectProxy => {
-                            Ok(AuthContext {
-                                api_key: Secret::from(api_key_without_bearer),
-                                user_id: key.owner_id.as_str().try_into()?,
-                                org_id: key.organization_id,
-                            })
-                        }
+                        RequestKind::UnifiedApi
+                        | RequestKind::DirectProxy
+                        | RequestKind::Admin => Ok(AuthContext {
+                            api_key: Secret::from(api_key_without_bearer),
+                            user_id: key.owner_id.as_str().try_into()?,
+                            org_id: key.organization_id,
+                        }),
                     }
                 } else {
                     Err(AuthError::InvalidCredentials)
@@ -83,15 +84,13 @@ impl AuthService {
                 let config =
                     &app_state.0.control_plane_state.read().await.config;
                 let key = config.get_key_from_hash(&computed_hash);
+                // keys are attributed to the organization that owns them,
+                // so that e.g. its own provider keys are used
                 if let Some(key) = key {
                     Ok(AuthContext {
                         api_key: Secret::from(api_key_without_bearer),
                         user_id: key.owner_id.as_str().try_into()?,
-                        org_id: config
-                            .auth
-                            .organization_id
-                            .as_str()
-                            .try_into()?,
+                        org_id: key.organization_id,
                     })
                 } else {
                     Err(AuthError::InvalidCredentials)
@@ -112,53 +111,64 @@ where
         Result<Request<B>, http::Response<Self::ResponseBody>>,
     >;
 
-    #[tracing::instrument(skip_all)]
     fn authorize(&mut self, mut request: Request<B>) -> Self::Future {
         let app_state = self.app_state.clone();
-        Box::pin(async move {
-            if app_state.0.config.helicone.is_auth_disabled() {
-                tracing::trace!("auth middleware: auth disabled");
-                return Ok(request);
-            }
-            tracing::trace!("auth middleware");
-            let Some(api_key) = request
-                .headers()
-                .get("authorization")
-                .and_then(|h| h.to_str().ok())
-            else {
-                return Err(
-                    AuthError::MissingAuthorizationHeader.into_response()
-                );
-            };
-            app_state.0.metrics.auth_attempts.add(1, &[]);
+        let span = tracing::info_span!(
+            "authorize",
+            router_id = tracing::field::Empty,
+        );
+        if let Some(router_id) = request.extensions().get::<RouterId>() {
+            span.record("router_id", tracing::field::display(router_id));
+        }
+        Box::pin(
+            async move {
+                if app_state.0.config.helicone.is_auth_disabled() {
+                    tracing::trace!("auth middleware: auth disabled");
+                    return Ok(request);
+                }
+                tracing::trace!("auth middleware");
+                let Some(api_key) = request
+                    .headers()
+                    .get("authorization")
+                    .and_then(|h| h.to_str().ok())
+                else {
+                    return Err(
+                        AuthError::MissingAuthorizationHeader.into_response()
+                    );
+                };
+                app_state.0.metrics.auth_attempts.add(1, &[]);
 
-            let request_kind = request.extensions().get::<RequestKind>();
-            let router_id = request.extensions().get::<RouterId>();
+                let request_kind = request.extensions().get::<RequestKind>();
+                let router_id = request.extensions().get::<RouterId>();
 
-            match Self::authenticate_request_inner(
-                app_state.clone(),
-                api_key,
-                request_kind,
-                router_id,
-            )
-            .await
-            {
-                Ok(auth_ctx) => {
-                    request.extensions_mut().insert(auth_ctx);
-                    Ok(request)
-                }
-                Err(e) => {
-                    match &e {
-                        AuthError::MissingAuthorizationHeader
-                        | AuthError::InvalidCredentials
-                        | AuthError::ProviderKeyNotFound
-                        | AuthError::RouterNotFound => {
-                            app_state.0.metrics.auth_rejections.add(1, &[]);
+                match Self::authenticate_request_inner(
+                    app_state.clone(),
+                    api_key,
+                    request_kind,
+                    router_id,
+                )
+                .await
+                {
+                    Ok(auth_ctx) => {
+                        request.extensions_mut().insert(auth_ctx);
+                        Ok(request)
+                    }
+                    Err(e) => {
+                        match &e {
+                            AuthError::MissingAuthorizationHeader
+                            | AuthError::InvalidCredentials
+                            | AuthError::ProviderKeyNotFound
+                            | AuthError::RouterNotFound => {
+                                app_state.0.metrics.auth_rejections.add(1, &[]);
+                            }
+                            // only raised when dispatching to a provider
+                            AuthError::ProviderTokenRefresh(_) => {}
                         }
+                        Err(e.into_response())
                     }
-                    Err(e.into_response())
                 }
             }
-        })
+            .instrument(span),
+        )
     }
 }
@@ -0,0 +1,137 @@
+//! Rejects request bodies larger than the configured limits with a 413, see
+//! [`BodyLimitConfig`].
+//!
+//! Requests declaring a larger `content-length` are rejected before their
+//! body is read. Other bodies are limited as they're read, so the layers
+//! buffering them, e.g. the cache and the mapper, never buffer more than the
+//! limit. Whatever error they then fail with, the response is replaced with
+//! the 413.
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use axum_core::{body::Body, response::IntoResponse};
+use futures::future::BoxFuture;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+
+use crate::{
+    app_state::AppState,
+    config::{body_limit::BodyLimitConfig, router::RouterConfig},
+    error::{api::ApiError, invalid_req::InvalidRequestError},
+    types::{request::Request, response::Response},
+};
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    limits: Option<BodyLimitConfig>,
+}
+
+impl Layer {
+    /// Applied to every request, with the default limits unless configured.
+    #[must_use]
+    pub fn global(app_state: &AppState) -> Self {
+        Self {
+            limits: Some(
+                app_state.config().global.body_limit.unwrap_or_default(),
+            ),
+        }
+    }
+
+    #[must_use]
+    pub fn unified_api(app_state: &AppState) -> Self {
+        Self {
+            limits: app_state.config().unified_api.body_limit,
+        }
+    }
+
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            limits: router_config.body_limit,
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            limits: self.limits,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    limits: Option<BodyLimitConfig>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(limits) = self.limits else {
+            return Box::pin(inner.call(req));
+        };
+        let limit = limits.limit_for(req.headers());
+        let content_length = req
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > limit) {
+            return Box::pin(std::future::ready(Ok(too_large(limit))));
+        }
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let req = req.map(|body| {
+            let exceeded = exceeded.clone();
+            let max = usize::try_from(limit).unwrap_or(usize::MAX);
+            Body::new(Limited::new(body, max).map_err(move |error| {
+                if error.is::<LengthLimitError>() {
+                    exceeded.store(true, Ordering::Relaxed);
+                }
+                error
+            }))
+        });
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            // the body is read before the request is sent to a provider, so
+            // nothing was sent
+            if exceeded.load(Ordering::Relaxed) {
+                return Ok(too_large(limit));
+            }
+            Ok(response)
+        })
+    }
+}
+
+fn too_large(limit: u64) -> Response {
+    tracing::debug!(limit, "request body too large");
+    ApiError::from(InvalidRequestError::RequestBodyTooLarge { limit })
+        .into_response()
+}
@@ -0,0 +1,267 @@
+//! Enforces the spend budget of each organization, see
+//! [`BudgetConfig`](crate::config::budget::BudgetConfig).
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+};
+
+use axum_core::{body::Body, response::IntoResponse};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use http::{HeaderName, HeaderValue};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    app_state::AppState,
+    error::api::ApiError,
+    middleware::{
+        cache::{
+            CACHE_COALESCED_HEADER_VALUE, CACHE_HIT_HEADER,
+            CACHE_HIT_HEADER_VALUE,
+        },
+        usage::{ResponseUsage, UsageBody, is_event_stream},
+    },
+    types::{
+        extensions::AuthContext, org::OrgId, request::Request,
+        response::Response,
+    },
+};
+
+/// Set once an organization has spent the configured fraction of its budget,
+/// to the percentage spent.
+const BUDGET_WARNING_HEADER: HeaderName =
+    HeaderName::from_static("helicone-budget-warning");
+
+#[derive(Debug)]
+struct PeriodSpend {
+    period_start: DateTime<Utc>,
+    spend: Decimal,
+}
+
+/// The spend of each organization in the current budget period, shared by
+/// every router and the unified API.
+#[derive(Debug, Clone, Default)]
+pub struct SpendTracker(Arc<Mutex<HashMap<OrgId, PeriodSpend>>>);
+
+impl SpendTracker {
+    fn spend(&self, org_id: &OrgId, period_start: DateTime<Utc>) -> Decimal {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(org_id)
+            .filter(|spend| spend.period_start == period_start)
+            .map_or(Decimal::ZERO, |spend| spend.spend)
+    }
+
+    fn record(
+        &self,
+        org_id: OrgId,
+        period_start: DateTime<Utc>,
+        cost: Decimal,
+    ) {
+        let mut spend = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let spend = spend.entry(org_id).or_insert(PeriodSpend {
+            period_start,
+            spend: Decimal::ZERO,
+        });
+        // spend resets at the start of each period
+        if spend.period_start != period_start {
+            *spend = PeriodSpend {
+                period_start,
+                spend: Decimal::ZERO,
+            };
+        }
+        spend.spend += cost;
+    }
+}
+
+/// An organization's spend in the current budget period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OrgSpend {
+    pub spend: Decimal,
+    pub limit: Decimal,
+    pub period_start: DateTime<Utc>,
+    /// When spend resets.
+    pub period_end: DateTime<Utc>,
+}
+
+/// The organization's spend against its limit, or `None` if no budget is
+/// configured.
+pub async fn org_spend(
+    app_state: &AppState,
+    org_id: &OrgId,
+) -> Option<OrgSpend> {
+    let config = app_state.config().budget.as_ref()?;
+    let period_start = config.period.start(Utc::now());
+    let limit = app_state
+        .0
+        .control_plane_state
+        .read()
+        .await
+        .org_spend_limit(org_id)
+        .unwrap_or(config.limit);
+    Some(OrgSpend {
+        spend: app_state.0.spend.spend(org_id, period_start),
+        limit,
+        period_start,
+        period_end: config.period.end(period_start),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    /// Is `Some` if a budget is configured.
+    app_state: Option<AppState>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn new(app_state: &AppState) -> Self {
+        Self {
+            app_state: app_state
+                .config()
+                .budget
+                .is_some()
+                .then(|| app_state.clone()),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            app_state: self.app_state.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    app_state: Option<AppState>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        // budgets are per organization, so only apply to authenticated
+        // requests
+        let (Some(app_state), Some(org_id)) = (
+            self.app_state.clone(),
+            req.extensions()
+                .get::<AuthContext>()
+                .map(|auth_ctx| auth_ctx.org_id),
+        ) else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let Some(OrgSpend {
+                spend,
+                limit,
+                period_end,
+                ..
+            }) = org_spend(&app_state, &org_id).await
+            else {
+                return inner.call(req).await;
+            };
+            if spend >= limit {
+                tracing::debug!(
+                    org_id = %org_id,
+                    spend = %spend,
+                    limit = %limit,
+                    "budget exceeded"
+                );
+                return Ok(ApiError::BudgetExceeded {
+                    limit,
+                    resets_at: period_end,
+                }
+                .into_response());
+            }
+            let mut response = inner.call(req).await?;
+            let warning_threshold = app_state
+                .config()
+                .budget
+                .as_ref()
+                .map_or(Decimal::ONE, |config| config.warning_threshold);
+            if spend >= limit * warning_threshold {
+                let percent_spent = (spend / limit * Decimal::ONE_HUNDRED)
+                    .round_dp(0)
+                    .normalize();
+                if let Ok(value) =
+                    HeaderValue::from_str(&format!("{percent_spent}%"))
+                {
+                    response.headers_mut().insert(BUDGET_WARNING_HEADER, value);
+                }
+            }
+
+            // cached and coalesced responses are free, since only the
+            // request that called the provider is charged
+            let is_cache_hit = response
+                .headers()
+                .get(CACHE_HIT_HEADER)
+                .is_some_and(|value| {
+                    *value == CACHE_HIT_HEADER_VALUE
+                        || *value == CACHE_COALESCED_HEADER_VALUE
+                });
+            if is_cache_hit || !response.status().is_success() {
+                return Ok(response);
+            }
+            let is_stream = is_event_stream(&response);
+            Ok(response.map(|body| {
+                Body::new(UsageBody::new(body, is_stream, move |usage| {
+                    record_spend(&app_state, org_id, usage);
+                }))
+            }))
+        })
+    }
+}
+
+/// Records the cost of a response once it completes.
+fn record_spend(
+    app_state: &AppState,
+    org_id: OrgId,
+    response_usage: ResponseUsage,
+) {
+    let Some(config) = app_state.config().budget.as_ref() else {
+        return;
+    };
+    let ResponseUsage { model, usage } = response_usage;
+    let usage = usage.unwrap_or_default();
+    let price = config.price(model.as_deref().unwrap_or_default());
+    let cost = price.cost(usage.prompt_tokens, usage.completion_tokens);
+    tracing::trace!(
+        org_id = %org_id,
+        model = ?model,
+        cost = %cost,
+        "recording spend"
+    );
+    app_state
+        .0
+        .spend
+        .record(org_id, config.period.start(Utc::now()), cost);
+}
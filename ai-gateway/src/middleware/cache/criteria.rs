@@ -0,0 +1,284 @@
+//! Which successful responses are worth caching, see
+//! [`CacheConfig::cacheable_finish_reasons`].
+//!
+//! Chat completions in the `OpenAI` and Anthropic formats, streamed or not,
+//! are checked for their finish reasons and content. Other responses, e.g.
+//! embeddings or audio, are cached as long as they aren't errors.
+use displaydoc::Display;
+use serde_json::Value;
+
+use crate::config::cache::CacheConfig;
+
+/// Why a response wasn't cached.
+#[derive(Debug, Display, PartialEq, Eq)]
+pub(super) enum Rejection {
+    /// the response is an error
+    Error,
+    /// finish reason `{0}` is not cacheable
+    FinishReason(String),
+    /// the response has no content
+    Empty,
+}
+
+#[derive(Debug)]
+pub(super) struct Criteria {
+    finish_reasons: Vec<String>,
+    cache_empty: bool,
+}
+
+impl Criteria {
+    pub(super) fn new(config: &CacheConfig) -> Self {
+        Self {
+            finish_reasons: config.cacheable_finish_reasons.clone(),
+            cache_empty: config.cache_empty_completions,
+        }
+    }
+
+    /// Checks the body of a successful response against the criteria.
+    pub(super) fn check(
+        &self,
+        body: &[u8],
+        is_stream: bool,
+    ) -> Result<(), Rejection> {
+        let completion = if is_stream {
+            Completion::from_events(body)
+        } else {
+            match serde_json::from_slice::<Value>(body) {
+                Ok(body) => Completion::from_body(&body),
+                // e.g. audio
+                Err(_) => return Ok(()),
+            }
+        };
+        if completion.is_error {
+            return Err(Rejection::Error);
+        }
+        if let Some(reason) = completion
+            .finish_reasons
+            .into_iter()
+            .find(|reason| !self.finish_reasons.contains(reason))
+        {
+            return Err(Rejection::FinishReason(reason));
+        }
+        if completion.is_empty == Some(true) && !self.cache_empty {
+            return Err(Rejection::Empty);
+        }
+        Ok(())
+    }
+}
+
+/// What a response says about how it completed.
+#[derive(Debug, Default)]
+struct Completion {
+    is_error: bool,
+    finish_reasons: Vec<String>,
+    /// `None` for responses that aren't completions.
+    is_empty: Option<bool>,
+}
+
+impl Completion {
+    fn from_body(body: &Value) -> Self {
+        let mut completion = Self {
+            is_error: body.get("error").is_some_and(|error| !error.is_null()),
+            ..Default::default()
+        };
+        if let Some(choices) = body.get("choices").and_then(Value::as_array) {
+            // `OpenAI` chat and text completions
+            completion.is_empty = Some(true);
+            for choice in choices {
+                completion.add_finish_reason(choice.get("finish_reason"));
+                let message = choice.get("message");
+                if has_content(
+                    message.and_then(|message| message.get("content")),
+                ) || has_content(
+                    message.and_then(|message| message.get("tool_calls")),
+                ) || has_content(choice.get("text"))
+                {
+                    completion.is_empty = Some(false);
+                }
+            }
+        } else if body.get("type").and_then(Value::as_str) == Some("message") {
+            // Anthropic messages
+            completion.add_finish_reason(body.get("stop_reason"));
+            completion.is_empty = Some(!has_content(body.get("content")));
+        } else if let Some(data) = body.get("data") {
+            // embeddings and images
+            completion.is_empty = Some(!has_content(Some(data)));
+        }
+        completion
+    }
+
+    fn from_events(body: &[u8]) -> Self {
+        let mut completion = Self::default();
+        let events = body
+            .split(|b| *b == b'\n')
+            .filter_map(|line| line.strip_prefix(b"data:"))
+            .filter_map(|data| {
+                serde_json::from_slice::<Value>(data.trim_ascii()).ok()
+            });
+        for event in events {
+            if event.get("error").is_some_and(|error| !error.is_null()) {
+                completion.is_error = true;
+            }
+            if let Some(choices) =
+                event.get("choices").and_then(Value::as_array)
+            {
+                // `OpenAI` chunks
+                completion.is_empty.get_or_insert(true);
+                for choice in choices {
+                    completion.add_finish_reason(choice.get("finish_reason"));
+                    let delta = choice.get("delta");
+                    if has_content(delta.and_then(|delta| delta.get("content")))
+                        || has_content(
+                            delta.and_then(|delta| delta.get("tool_calls")),
+                        )
+                        || has_content(choice.get("text"))
+                    {
+                        completion.is_empty = Some(false);
+                    }
+                }
+            } else if let Some(kind) = event.get("type").and_then(Value::as_str)
+            {
+                // Anthropic events
+                let delta = event.get("delta");
+                match kind {
+                    "message_start" => {
+                        completion.is_empty.get_or_insert(true);
+                    }
+                    "content_block_delta" => {
+                        let text = delta.and_then(|delta| {
+                            delta
+                                .get("text")
+                                .or_else(|| delta.get("partial_json"))
+                        });
+                        if has_content(text) {
+                            completion.is_empty = Some(false);
+                        }
+                    }
+                    "message_delta" => completion.add_finish_reason(
+                        delta.and_then(|delta| delta.get("stop_reason")),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+        completion
+    }
+
+    fn add_finish_reason(&mut self, reason: Option<&Value>) {
+        if let Some(reason) = reason.and_then(Value::as_str) {
+            self.finish_reasons.push(reason.to_string());
+        }
+    }
+}
+
+fn has_content(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::String(text)) => !text.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Object(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn criteria() -> Criteria {
+        Criteria::new(&CacheConfig::default())
+    }
+
+    fn check(body: &Value) -> Result<(), Rejection> {
+        criteria().check(body.to_string().as_bytes(), false)
+    }
+
+    fn chat_completion(content: &str, finish_reason: &str) -> Value {
+        json!({
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": finish_reason,
+            }],
+        })
+    }
+
+    #[test]
+    fn complete_chat_completions_are_cacheable() {
+        assert_eq!(check(&chat_completion("Hello!", "stop")), Ok(()));
+        let tool_call = json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{ "id": "call_1", "type": "function" }],
+                },
+                "finish_reason": "tool_calls",
+            }],
+        });
+        assert_eq!(check(&tool_call), Ok(()));
+    }
+
+    #[test]
+    fn truncated_and_empty_completions_are_not_cacheable() {
+        assert_eq!(
+            check(&chat_completion("Hello", "length")),
+            Err(Rejection::FinishReason("length".to_string()))
+        );
+        assert_eq!(check(&chat_completion("", "stop")), Err(Rejection::Empty));
+        assert_eq!(
+            check(&json!({ "error": { "message": "overloaded" } })),
+            Err(Rejection::Error)
+        );
+    }
+
+    #[test]
+    fn anthropic_messages_are_checked() {
+        let message = |stop_reason: &str| {
+            json!({
+                "type": "message",
+                "content": [{ "type": "text", "text": "Hello!" }],
+                "stop_reason": stop_reason,
+            })
+        };
+        assert_eq!(check(&message("end_turn")), Ok(()));
+        assert_eq!(
+            check(&message("max_tokens")),
+            Err(Rejection::FinishReason("max_tokens".to_string()))
+        );
+    }
+
+    #[test]
+    fn streams_are_checked_by_their_events() {
+        let stream = |finish_reason: &str| {
+            [
+                json!({ "choices": [{ "delta": { "content": "Hi" } }] }),
+                json!({
+                    "choices": [{ "delta": {}, "finish_reason": finish_reason }]
+                }),
+            ]
+            .iter()
+            .map(|event| format!("data: {event}\n\n"))
+            .chain(["data: [DONE]\n\n".to_string()])
+            .collect::<String>()
+        };
+        let criteria = criteria();
+        assert_eq!(criteria.check(stream("stop").as_bytes(), true), Ok(()));
+        assert_eq!(
+            criteria.check(stream("length").as_bytes(), true),
+            Err(Rejection::FinishReason("length".to_string()))
+        );
+    }
+
+    #[test]
+    fn other_responses_are_cacheable() {
+        let embeddings = json!({
+            "object": "list",
+            "data": [{ "object": "embedding", "embedding": [0.1, 0.2] }],
+        });
+        assert_eq!(check(&embeddings), Ok(()));
+        assert_eq!(criteria().check(b"\x00\x01audio", false), Ok(()));
+    }
+}
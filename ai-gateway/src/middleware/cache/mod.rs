@@ -1,4 +1,8 @@
+mod criteria;
 pub mod optional;
 mod service;
 
 pub use optional::{Layer as CacheLayer, Service as CacheService};
+pub(crate) use service::{
+    CACHE_COALESCED_HEADER_VALUE, CACHE_HIT_HEADER, CACHE_HIT_HEADER_VALUE,
+};
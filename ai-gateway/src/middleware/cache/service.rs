@@ -1,12 +1,13 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, hash_map::Entry},
     convert::Infallible,
     hash::{Hash, Hasher},
     str::FromStr,
-    sync::Arc,
+    sync::{Arc, Mutex, PoisonError},
     task::{Context, Poll},
 };
 
+use axum_core::response::IntoResponse;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use futures::{StreamExt, future::BoxFuture, stream::FuturesUnordered};
@@ -18,9 +19,12 @@ use http_cache_semantics::{
 };
 use opentelemetry::KeyValue;
 use rustc_hash::FxHasher;
+use serde::Deserialize;
+use tokio::sync::watch;
 use tracing::Instrument;
 use url::Url;
 
+use super::criteria::Criteria;
 use crate::{
     app_state::AppState,
     cache::CacheClient,
@@ -34,10 +38,13 @@ use crate::{
     },
     logger::service::LoggerService,
     metrics::tfft::TFFTFuture,
+    middleware::usage::is_event_stream,
+    router::{direct, router_details::RouteType},
     types::{
         body::BodyReader,
-        extensions::{AuthContext, MapperContext},
+        extensions::{AuthContext, MapperContext, TargetModel},
         model_id::ModelId,
+        multipart,
         provider::InferenceProvider,
         request::Request,
         response::Response,
@@ -45,11 +52,19 @@ use crate::{
     },
 };
 
-const CACHE_HIT_HEADER: HeaderName = HeaderName::from_static("helicone-cache");
+pub(crate) const CACHE_HIT_HEADER: HeaderName =
+    HeaderName::from_static("helicone-cache");
 const CACHE_BUCKET_IDX: HeaderName =
     HeaderName::from_static("helicone-cache-bucket-idx");
-const CACHE_HIT_HEADER_VALUE: HeaderValue = HeaderValue::from_static("HIT");
+pub(crate) const CACHE_HIT_HEADER_VALUE: HeaderValue =
+    HeaderValue::from_static("HIT");
 const CACHE_MISS_HEADER_VALUE: HeaderValue = HeaderValue::from_static("MISS");
+pub(crate) const CACHE_COALESCED_HEADER_VALUE: HeaderValue =
+    HeaderValue::from_static("COALESCED");
+
+/// Upstream calls for cache misses that are still in flight, keyed by the
+/// hash of the request, excluding its bucket.
+type InFlight = Arc<Mutex<HashMap<u64, watch::Receiver<Option<Coalesced>>>>>;
 
 #[derive(Debug)]
 struct CacheContext {
@@ -60,6 +75,12 @@ struct CacheContext {
     buckets: Option<u8>,
     seed: Option<String>,
     options: Option<CacheOptions>,
+    /// Only set by config, not by request headers.
+    max_cached_body_bytes: Option<usize>,
+    /// See [`CacheConfig::key_fields`]. Only set by config.
+    key_fields: Option<Arc<[String]>>,
+    /// Which responses are cached. Only set by config.
+    criteria: Option<Arc<Criteria>>,
 }
 
 impl CacheContext {
@@ -84,6 +105,14 @@ impl CacheContext {
             buckets: other.buckets.or(self.buckets),
             seed: other.seed.clone().or_else(|| self.seed.clone()),
             options: other.options.or(self.options),
+            max_cached_body_bytes: other
+                .max_cached_body_bytes
+                .or(self.max_cached_body_bytes),
+            key_fields: other
+                .key_fields
+                .clone()
+                .or_else(|| self.key_fields.clone()),
+            criteria: other.criteria.clone().or_else(|| self.criteria.clone()),
         }
     }
 }
@@ -93,6 +122,7 @@ pub struct CacheLayer {
     app_state: AppState,
     backend: CacheClient,
     context: Arc<CacheContext>,
+    in_flight: InFlight,
 }
 
 impl CacheLayer {
@@ -114,11 +144,15 @@ impl CacheLayer {
                 shared: false,
                 ..Default::default()
             }),
+            max_cached_body_bytes: Some(config.max_cached_body_bytes),
+            key_fields: config.key_fields.clone().map(Arc::from),
+            criteria: Some(Arc::new(Criteria::new(&config))),
         };
         Ok(Self {
             app_state,
             backend,
             context: Arc::new(context),
+            in_flight: InFlight::default(),
         })
     }
 
@@ -163,6 +197,7 @@ impl<S> tower::Layer<S> for CacheLayer {
             app_state: self.app_state.clone(),
             backend: self.backend.clone(),
             context: Arc::clone(&self.context),
+            in_flight: Arc::clone(&self.in_flight),
         }
     }
 }
@@ -173,6 +208,7 @@ pub struct CacheService<S> {
     app_state: AppState,
     backend: CacheClient,
     context: Arc<CacheContext>,
+    in_flight: InFlight,
 }
 
 impl<S> tower::Service<Request> for CacheService<S>
@@ -196,24 +232,30 @@ where
             .map_err(|_| ApiError::Internal(InternalError::Internal))
     }
 
-    #[tracing::instrument(name = "cache", skip_all)]
     fn call(&mut self, req: Request) -> Self::Future {
-        tracing::trace!("cache middleware");
+        // `cache_result` is recorded once the lookup completes
+        let span =
+            tracing::info_span!("cache", cache_result = tracing::field::Empty);
         // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
         let mut this = self.clone();
         std::mem::swap(self, &mut this);
-        Box::pin(async move {
-            let merged_ctx = this.context.merge(&get_cache_ctx(&req)?);
-            let backend = this.backend.clone();
-            make_request(
-                &mut this.inner,
-                &this.app_state,
-                req,
-                &backend,
-                merged_ctx,
-            )
-            .await
-        })
+        Box::pin(
+            async move {
+                tracing::trace!("cache middleware");
+                let merged_ctx = this.context.merge(&get_cache_ctx(&req)?);
+                let backend = this.backend.clone();
+                make_request(
+                    &mut this.inner,
+                    &this.app_state,
+                    req,
+                    &backend,
+                    &this.in_flight,
+                    merged_ctx,
+                )
+                .await
+            }
+            .instrument(span),
+        )
     }
 }
 
@@ -278,15 +320,12 @@ async fn check_cache(
                     )?;
 
                 let app_state_cloned = app_state.clone();
-                // TODO(eng-2160): make cache service agnostic to which endpoint
-                // is used
-                let deserialized_body = serde_json::from_slice::<
-                    async_openai::types::CreateChatCompletionRequest,
-                >(&req_body_bytes)
-                .map_err(|e| InternalError::Deserialize {
-                    ty: "async_openai::types::CreateChatCompletionRequest",
-                    error: e,
-                });
+                let deserialized_body =
+                    serde_json::from_slice::<CachedRequest>(&req_body_bytes)
+                        .map_err(|e| InternalError::Deserialize {
+                            ty: std::any::type_name::<CachedRequest>(),
+                            error: e,
+                        });
                 tokio::spawn(
                     async move {
                         let Ok(deserialized_body) = deserialized_body else {
@@ -383,6 +422,15 @@ async fn check_cache(
     }
 }
 
+/// The fields logged for a cache hit, which every endpoint we cache (chat
+/// completions and embeddings) has in common.
+#[derive(Deserialize)]
+struct CachedRequest {
+    model: String,
+    #[serde(default)]
+    stream: Option<bool>,
+}
+
 enum CacheCheckResult {
     Fresh(Response),
     Stale,
@@ -402,7 +450,7 @@ async fn handle_response_for_cache_miss(
     resp: Response,
     bucket: u8,
     now: std::time::SystemTime,
-) -> Result<Response, ApiError> {
+) -> Result<Miss, ApiError> {
     let cacheable_resp =
         CacheableResponse::new(ctx, resp.headers(), resp.status());
     let cache_options = ctx.options.unwrap_or_default();
@@ -415,16 +463,47 @@ async fn handle_response_for_cache_miss(
             is_storable = policy.is_storable(),
             "got response that is not storable"
         );
-        return Ok(resp);
+        // error responses are small enough to buffer and share
+        if resp.status().is_success() {
+            return Ok(Miss::Unshared(resp));
+        }
+        return Ok(Miss::Shared(resp));
     }
     tracing::trace!("caching storable response");
     let url = get_url(&req)?;
+    let is_stream = is_event_stream(&resp);
     let (parts, body) = resp.into_parts();
     let body_bytes = body
         .collect()
         .await
         .map_err(InternalError::CollectBodyError)?
         .to_bytes();
+    if ctx
+        .max_cached_body_bytes
+        .is_some_and(|max_bytes| body_bytes.len() > max_bytes)
+    {
+        tracing::debug!(
+            body_bytes = body_bytes.len(),
+            "response body too large to cache"
+        );
+        let mut resp = Response::from_parts(parts, body_bytes.into());
+        resp.headers_mut().extend([
+            (CACHE_HIT_HEADER, CACHE_MISS_HEADER_VALUE),
+            (CACHE_BUCKET_IDX, bucket_header_value(bucket)),
+        ]);
+        return Ok(Miss::Shared(resp));
+    }
+    if let Some(criteria) = &ctx.criteria
+        && let Err(rejection) = criteria.check(&body_bytes, is_stream)
+    {
+        tracing::debug!(reason = %rejection, "response not cached");
+        let mut resp = Response::from_parts(parts, body_bytes.into());
+        resp.headers_mut().extend([
+            (CACHE_HIT_HEADER, CACHE_MISS_HEADER_VALUE),
+            (CACHE_BUCKET_IDX, bucket_header_value(bucket)),
+        ]);
+        return Ok(Miss::Shared(resp));
+    }
 
     let http_resp = HttpResponse {
         body: body_bytes.clone().into(),
@@ -447,6 +526,7 @@ async fn handle_response_for_cache_miss(
             (CACHE_BUCKET_IDX, bucket_header_value(bucket)),
         ],
     )
+    .map(Miss::Shared)
     .map_err(Into::into)
 }
 
@@ -456,6 +536,7 @@ async fn make_request<S>(
     app_state: &AppState,
     mut req: Request,
     cache: &CacheClient,
+    in_flight: &InFlight,
     ctx: CacheContext,
 ) -> Result<Response, ApiError>
 where
@@ -463,8 +544,14 @@ where
         + Send
         + 'static,
 {
-    // just call inner service if caching is disabled
-    if ctx.enabled.is_none_or(|enabled| !enabled) {
+    // just call inner service if caching is disabled.
+    // multipart uploads (e.g. audio transcriptions) are never cached so that
+    // we don't buffer potentially large files just to compute a cache key.
+    if ctx.enabled.is_none_or(|enabled| !enabled)
+        || multipart::is_multipart(req.headers())
+        || is_unknown_passthrough(&req)
+    {
+        tracing::Span::current().record("cache_result", "bypass");
         return inner.call(req).await.map_err(|e| {
             tracing::error!(error = %e, "encountered infallible error");
             ApiError::Internal(InternalError::Internal)
@@ -488,11 +575,16 @@ where
         .map_err(InternalError::CollectBodyError)?
         .to_bytes();
     let buckets = ctx.buckets.unwrap_or(DEFAULT_BUCKETS);
-    let now = std::time::SystemTime::now();
+    let now = std::time::SystemTime::from(app_state.0.clock.now());
 
     // Try each bucket in parallel
     let mut futures = FuturesUnordered::new();
-    let hasher = get_hasher(&parts, &body_bytes, ctx.seed.as_deref());
+    let hasher = get_hasher(
+        &parts,
+        &body_bytes,
+        ctx.seed.as_deref(),
+        ctx.key_fields.as_deref(),
+    );
     // fairly sample different buckets
     let mut bucket_indices: Vec<u8> = (0..buckets).collect();
     {
@@ -520,6 +612,7 @@ where
         match result {
             Ok((bucket, _key, CacheCheckResult::Fresh(mut resp))) => {
                 record_cache_hit(app_state, bucket, &parts.uri);
+                tracing::Span::current().record("cache_result", "hit");
                 resp.headers_mut().extend([
                     (CACHE_HIT_HEADER, CACHE_HIT_HEADER_VALUE),
                     (CACHE_BUCKET_IDX, bucket_header_value(bucket)),
@@ -538,16 +631,33 @@ where
         }
     }
 
-    // Try stale hits
-    if let Some((bucket, key)) = stale_hits.into_iter().next() {
+    let (bucket, key) =
+        if let Some((bucket, key)) = stale_hits.into_iter().next() {
+            // Try stale hits
+            tracing::Span::current().record("cache_result", "stale");
+            (bucket, key)
+        } else {
+            // Complete miss - pick a bucket and make the request
+            let bucket = empty_buckets
+                .first()
+                .copied()
+                .unwrap_or_else(|| rand::random::<u8>() % buckets);
+            let mut cloned_hasher = hasher.clone();
+            bucket.hash(&mut cloned_hasher);
+            let key = cloned_hasher.finish().to_string();
+            record_cache_miss(app_state, &parts.uri, bucket);
+            tracing::Span::current().record("cache_result", "miss");
+            (bucket, key)
+        };
+
+    let upstream = async {
         let req = Request::from_parts(parts.clone(), body_bytes.clone().into());
         let resp = inner.call(req).await.map_err(|e| {
             tracing::error!(error = %e, "encountered infallible error");
             ApiError::Internal(InternalError::Internal)
         })?;
-        let req_for_cache =
-            Request::from_parts(parts, body_bytes.clone().into());
-        return handle_response_for_cache_miss(
+        let req_for_cache = Request::from_parts(parts, body_bytes.into());
+        handle_response_for_cache_miss(
             cache,
             &ctx,
             key,
@@ -556,39 +666,130 @@ where
             bucket,
             now,
         )
-        .await;
+        .await
+    };
+    single_flight(in_flight, hasher.finish(), upstream).await
+}
+
+/// The response to a request which missed the cache.
+#[derive(Debug)]
+enum Miss {
+    /// A response which may be buffered to share it with coalesced requests.
+    Shared(Response),
+    /// A successful response which can't be cached, so is passed through
+    /// rather than buffered to share it.
+    Unshared(Response),
+}
+
+impl Miss {
+    fn into_inner(self) -> Response {
+        match self {
+            Self::Shared(resp) | Self::Unshared(resp) => resp,
+        }
     }
+}
 
-    // Complete miss - pick a bucket and make the request
-    let bucket = empty_buckets
-        .first()
-        .copied()
-        .unwrap_or_else(|| rand::random::<u8>() % buckets);
-    let mut cloned_hasher = hasher.clone();
-    bucket.hash(&mut cloned_hasher);
-    let key = cloned_hasher.finish().to_string();
-    record_cache_miss(app_state, &parts.uri, bucket);
-
-    let req = Request::from_parts(parts.clone(), body_bytes.clone().into());
-    let resp = inner.call(req).await.map_err(|e| {
-        tracing::error!(error = %e, "encountered infallible error");
-        ApiError::Internal(InternalError::Internal)
-    })?;
-
-    let req_for_cache = Request::from_parts(parts, body_bytes.into());
-    handle_response_for_cache_miss(
-        cache,
-        &ctx,
-        key,
-        req_for_cache,
-        resp,
-        bucket,
-        now,
-    )
-    .await
+/// A response shared with every request coalesced into the same upstream
+/// call.
+#[derive(Debug, Clone)]
+enum Coalesced {
+    /// The leading request's response, or its error rendered as one.
+    Response(http::response::Parts, Bytes),
+    /// The leading request's response wasn't shared, see [`Miss::Unshared`].
+    Unshared,
+}
+
+/// Removes the in flight entry once the leading request completes or is
+/// dropped.
+struct InFlightGuard<'a> {
+    in_flight: &'a InFlight,
+    key: u64,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.key);
+    }
 }
 
-fn get_hasher(parts: &Parts, body: &Bytes, seed: Option<&str>) -> FxHasher {
+/// Make at most one upstream call at a time for identical requests.
+///
+/// The first request to miss the cache makes the upstream call, while
+/// identical requests that arrive before it completes wait for its response
+/// rather than calling the provider themselves. Error responses, including
+/// errors from the upstream call rendered as responses, are shared the same
+/// way, but never cached. Waiting requests only call the provider themselves
+/// if the leading request's response can't be cached, so isn't buffered to
+/// share it, or the leading request is dropped before it completes.
+async fn single_flight<F>(
+    in_flight: &InFlight,
+    key: u64,
+    upstream: F,
+) -> Result<Response, ApiError>
+where
+    F: Future<Output = Result<Miss, ApiError>>,
+{
+    let (tx, rx) = watch::channel(None);
+    let existing = match in_flight
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(key)
+    {
+        Entry::Occupied(entry) => Some(entry.get().clone()),
+        Entry::Vacant(entry) => {
+            entry.insert(rx);
+            None
+        }
+    };
+
+    if let Some(mut rx) = existing {
+        let coalesced =
+            rx.wait_for(Option::is_some).await.map(|c| c.clone()).ok();
+        return match coalesced.flatten() {
+            Some(Coalesced::Response(parts, body)) => {
+                tracing::Span::current().record("cache_result", "coalesced");
+                let mut resp = Response::from_parts(parts, body.into());
+                resp.headers_mut()
+                    .insert(CACHE_HIT_HEADER, CACHE_COALESCED_HEADER_VALUE);
+                Ok(resp)
+            }
+            Some(Coalesced::Unshared) | None => {
+                tracing::debug!(
+                    "in flight response not shared, calling upstream directly"
+                );
+                upstream.await.map(Miss::into_inner)
+            }
+        };
+    }
+
+    let _guard = InFlightGuard { in_flight, key };
+    let resp = match upstream.await {
+        Ok(Miss::Shared(resp)) => resp,
+        Ok(Miss::Unshared(resp)) => {
+            tx.send_replace(Some(Coalesced::Unshared));
+            return Ok(resp);
+        }
+        Err(e) => e.into_response(),
+    };
+    let (parts, body) = resp.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(InternalError::CollectBodyError)?
+        .to_bytes();
+    tx.send_replace(Some(Coalesced::Response(parts.clone(), body.clone())));
+    Ok(Response::from_parts(parts, body.into()))
+}
+
+fn get_hasher(
+    parts: &Parts,
+    body: &Bytes,
+    seed: Option<&str>,
+    key_fields: Option<&[String]>,
+) -> FxHasher {
     let mut hasher = FxHasher::default();
     if let Some(s) = seed {
         s.hash(&mut hasher);
@@ -596,10 +797,37 @@ fn get_hasher(parts: &Parts, body: &Bytes, seed: Option<&str>) -> FxHasher {
     if let Some(pq) = parts.uri.path_and_query() {
         pq.hash(&mut hasher);
     }
-    body.hash(&mut hasher);
+    // requests sent to different models by a model weighted router must not
+    // share cached responses
+    if let Some(TargetModel(model)) = parts.extensions.get() {
+        model.hash(&mut hasher);
+    }
+    match key_fields.and_then(|fields| significant_fields(body, fields)) {
+        Some(fields) => fields.hash(&mut hasher),
+        None => body.hash(&mut hasher),
+    }
     hasher
 }
 
+/// The serialized values of `key_fields` and `stream`, or `None` if the body
+/// isn't a JSON object, in which case all of it is significant.
+fn significant_fields<'a>(
+    body: &Bytes,
+    key_fields: &'a [String],
+) -> Option<Vec<(&'a str, Option<String>)>> {
+    let serde_json::Value::Object(body) = serde_json::from_slice(body).ok()?
+    else {
+        return None;
+    };
+    let field = |name: &'a str| {
+        (name, body.get(name).map(serde_json::Value::to_string))
+    };
+    let mut fields: Vec<_> =
+        key_fields.iter().map(|name| field(name.as_str())).collect();
+    fields.push(field("stream"));
+    Some(fields)
+}
+
 fn record_cache_hit(app_state: &AppState, bucket: u8, uri: &http::Uri) {
     let attributes = &[
         KeyValue::new("bucket", bucket.to_string()),
@@ -618,6 +846,16 @@ fn record_cache_miss(app_state: &AppState, uri: &http::Uri, bucket: u8) {
     app_state.0.metrics.cache.misses.add(1, attributes);
 }
 
+/// Passthrough requests to arbitrary provider paths, e.g. `/openai/v1/models`,
+/// aren't inference requests, so their responses aren't ours to cache.
+fn is_unknown_passthrough(req: &Request) -> bool {
+    matches!(
+        req.extensions().get::<RouteType>(),
+        Some(RouteType::DirectProxy { provider, path })
+            if !direct::is_known_path(provider, path)
+    )
+}
+
 fn get_cache_ctx(req: &Request) -> Result<CacheContext, InvalidRequestError> {
     let headers = req.headers();
     let enabled = headers
@@ -641,6 +879,9 @@ fn get_cache_ctx(req: &Request) -> Result<CacheContext, InvalidRequestError> {
         buckets,
         seed,
         options: None,
+        max_cached_body_bytes: None,
+        key_fields: None,
+        criteria: None,
     })
 }
 
@@ -785,3 +1026,57 @@ impl ResponseLike for CacheableResponse {
         &self.resp_headers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum_core::body::Body;
+
+    use super::*;
+
+    /// Calls `single_flight` for the same key from a leading request, then a
+    /// waiting one, returning their responses.
+    async fn coalesce(
+        leader: Result<Miss, ApiError>,
+        follower: Result<Miss, ApiError>,
+    ) -> (Response, Response) {
+        let in_flight = InFlight::default();
+        let leader = single_flight(&in_flight, 0, async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            leader
+        });
+        let follower = single_flight(&in_flight, 0, async { follower });
+        let (leader, follower) = tokio::join!(leader, follower);
+        (leader.unwrap(), follower.unwrap())
+    }
+
+    #[tokio::test]
+    async fn waiting_requests_get_the_leading_requests_error() {
+        let (leader, follower) = coalesce(
+            Err(ApiError::RouterSaturated),
+            Ok(Miss::Shared(Response::new(Body::empty()))),
+        )
+        .await;
+        assert_eq!(leader.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(follower.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            follower.headers()[CACHE_HIT_HEADER],
+            CACHE_COALESCED_HEADER_VALUE
+        );
+    }
+
+    #[tokio::test]
+    async fn waiting_requests_call_upstream_for_unshared_responses() {
+        let mut own = Response::new(Body::empty());
+        *own.status_mut() = StatusCode::ACCEPTED;
+        let (leader, follower) = coalesce(
+            Ok(Miss::Unshared(Response::new(Body::empty()))),
+            Ok(Miss::Shared(own)),
+        )
+        .await;
+        assert_eq!(leader.status(), StatusCode::OK);
+        assert_eq!(follower.status(), StatusCode::ACCEPTED);
+        assert!(follower.headers().get(CACHE_HIT_HEADER).is_none());
+    }
+}
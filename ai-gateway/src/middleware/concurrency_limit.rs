@@ -0,0 +1,347 @@
+//! Caps the number of concurrent requests through a router, see
+//! [`ConcurrencyLimitConfig`].
+//!
+//! Requests waiting for a slot are admitted by self-clocked weighted fair
+//! queuing: each queued request is tagged with a virtual finish time that
+//! advances by the inverse of its [`PriorityClass`]'s weight, and freed slots
+//! go to the request with the earliest tag. Higher priority classes are
+//! admitted first in proportion to their weight, while lower ones still get
+//! their share rather than starving.
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError, atomic::AtomicUsize},
+    task::{Context, Poll},
+};
+
+use axum_core::{body::Body, response::IntoResponse};
+use futures::future::BoxFuture;
+use http::HeaderName;
+use tokio::sync::oneshot;
+
+use super::stream_limit::{PermitBody, QueuedGuard};
+use crate::{
+    config::{
+        concurrency_limit::{ConcurrencyLimitConfig, PriorityClass},
+        router::RouterConfig,
+    },
+    error::{api::ApiError, invalid_req::InvalidRequestError},
+    types::{request::Request, response::Response},
+};
+
+pub(crate) const PRIORITY_HEADER: HeaderName =
+    HeaderName::from_static("helicone-priority");
+
+/// The virtual time a request of weight 1 takes, larger weights take
+/// proportionally less.
+const VIRTUAL_COST: u64 = 1 << 32;
+
+#[derive(Debug)]
+struct Waiter {
+    finish: u64,
+    /// Breaks ties between equal finish times in arrival order.
+    seq: u64,
+    tx: oneshot::Sender<Permit>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        (self.finish, self.seq) == (other.finish, other.seq)
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.finish, self.seq).cmp(&(other.finish, other.seq))
+    }
+}
+
+#[derive(Debug)]
+struct Scheduler {
+    /// Free slots, which are only handed out directly while nothing is
+    /// queued.
+    available: usize,
+    /// The finish time of the last admitted request.
+    virtual_time: u64,
+    /// The finish time of the last request queued in each class, indexed by
+    /// [`PriorityClass`].
+    last_finish: [u64; 3],
+    seq: u64,
+    queue: BinaryHeap<Reverse<Waiter>>,
+}
+
+#[derive(Debug)]
+struct ConcurrencyLimiter {
+    config: ConcurrencyLimitConfig,
+    scheduler: Arc<Mutex<Scheduler>>,
+    queued: AtomicUsize,
+}
+
+/// A slot in a router, which is handed to the next queued request once
+/// dropped.
+#[derive(Debug)]
+struct Permit {
+    scheduler: Option<Arc<Mutex<Scheduler>>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let Some(scheduler) = self.scheduler.take() else {
+            return;
+        };
+        let mut guard =
+            scheduler.lock().unwrap_or_else(PoisonError::into_inner);
+        while let Some(Reverse(waiter)) = guard.queue.pop() {
+            guard.virtual_time = guard.virtual_time.max(waiter.finish);
+            let permit = Permit {
+                scheduler: Some(scheduler.clone()),
+            };
+            match waiter.tx.send(permit) {
+                Ok(()) => return,
+                // the waiter gave up, so the slot goes to the next one
+                Err(mut permit) => permit.scheduler = None,
+            }
+        }
+        guard.available += 1;
+    }
+}
+
+fn class_index(class: PriorityClass) -> usize {
+    match class {
+        PriorityClass::High => 0,
+        PriorityClass::Normal => 1,
+        PriorityClass::Low => 2,
+    }
+}
+
+impl ConcurrencyLimiter {
+    fn new(config: ConcurrencyLimitConfig) -> Self {
+        let scheduler = Scheduler {
+            available: config.limit.get(),
+            virtual_time: 0,
+            last_finish: [0; 3],
+            seq: 0,
+            queue: BinaryHeap::new(),
+        };
+        Self {
+            config,
+            scheduler: Arc::new(Mutex::new(scheduler)),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    fn try_acquire(&self) -> Option<Permit> {
+        let mut scheduler = self
+            .scheduler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        scheduler.available = scheduler.available.checked_sub(1)?;
+        Some(Permit {
+            scheduler: Some(self.scheduler.clone()),
+        })
+    }
+
+    /// Queues for a slot in the given class, unless one has been freed since
+    /// [`Self::try_acquire`].
+    fn enqueue(
+        &self,
+        class: PriorityClass,
+    ) -> Result<Permit, oneshot::Receiver<Permit>> {
+        let mut scheduler = self
+            .scheduler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(available) = scheduler.available.checked_sub(1) {
+            scheduler.available = available;
+            return Ok(Permit {
+                scheduler: Some(self.scheduler.clone()),
+            });
+        }
+        let weight =
+            u64::from(self.config.priority_weights.weight(class).get());
+        let index = class_index(class);
+        let start = scheduler.virtual_time.max(scheduler.last_finish[index]);
+        let finish = start.saturating_add(VIRTUAL_COST / weight);
+        scheduler.last_finish[index] = finish;
+        scheduler.seq += 1;
+        let seq = scheduler.seq;
+        let (tx, rx) = oneshot::channel();
+        scheduler.queue.push(Reverse(Waiter { finish, seq, tx }));
+        Err(rx)
+    }
+
+    /// Admit a request, waiting in the queue if the router is at its limit.
+    async fn acquire(&self, class: PriorityClass) -> Result<Permit, ApiError> {
+        if let Some(permit) = self.try_acquire() {
+            return Ok(permit);
+        }
+
+        let queued = QueuedGuard::new(&self.queued);
+        if queued.position > self.config.queue_depth {
+            tracing::debug!("router queue full, shedding request");
+            return Err(ApiError::RouterSaturated);
+        }
+        let mut rx = match self.enqueue(class) {
+            Ok(permit) => return Ok(permit),
+            Err(rx) => rx,
+        };
+        tracing::debug!(
+            position = queued.position,
+            priority = %class,
+            "queueing request"
+        );
+        match tokio::time::timeout(self.config.queue_timeout, &mut rx).await {
+            Ok(Ok(permit)) => Ok(permit),
+            Ok(Err(_)) => Err(ApiError::RouterSaturated),
+            Err(_) => {
+                // a slot may have been handed over as the wait timed out
+                rx.close();
+                rx.try_recv().map_err(|_| {
+                    tracing::debug!("timed out waiting for router slot");
+                    ApiError::RouterSaturated
+                })
+            }
+        }
+    }
+}
+
+fn priority(req: &Request) -> Result<PriorityClass, InvalidRequestError> {
+    let Some(value) = req.headers().get(PRIORITY_HEADER) else {
+        return Ok(PriorityClass::default());
+    };
+    let value = value
+        .to_str()
+        .map_err(InvalidRequestError::InvalidRequestHeader)?;
+    PriorityClass::from_str(value.trim())
+        .map_err(|_| InvalidRequestError::InvalidPriority(value.to_string()))
+}
+
+/// Built once per router, so that the limit is shared by every endpoint type
+/// the router serves.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            limiter: router_config
+                .max_concurrent_requests
+                .clone()
+                .map(|config| Arc::new(ConcurrencyLimiter::new(config))),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(limiter) = self.limiter.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let class = match priority(&req) {
+                Ok(class) => class,
+                Err(e) => return Ok(e.into_response()),
+            };
+            let permit = match limiter.acquire(class).await {
+                Ok(permit) => permit,
+                Err(e) => return Ok(e.into_response()),
+            };
+            let response = inner.call(req).await?;
+            // streams hold their slot until the stream completes
+            Ok(response.map(|body| {
+                Body::new(PermitBody {
+                    inner: body,
+                    _permit: permit,
+                })
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, time::Duration};
+
+    use super::*;
+    use crate::config::concurrency_limit::PriorityWeights;
+
+    #[test]
+    fn queued_requests_are_admitted_by_weighted_fair_queuing() {
+        use PriorityClass::{High, Low};
+
+        let limiter = ConcurrencyLimiter::new(ConcurrencyLimitConfig {
+            limit: NonZeroUsize::MIN,
+            queue_depth: 10,
+            queue_timeout: Duration::from_secs(1),
+            priority_weights: PriorityWeights::default(),
+        });
+        let mut permit = limiter.try_acquire().unwrap();
+        let mut queued = [Low, Low, Low, Low, High, High, High, High]
+            .into_iter()
+            .map(|class| (class, limiter.enqueue(class).unwrap_err()))
+            .collect::<Vec<_>>();
+
+        let mut admitted = Vec::new();
+        for _ in 0..queued.len() {
+            drop(permit);
+            let (class, next) = queued
+                .iter_mut()
+                .find_map(|(class, rx)| Some((*class, rx.try_recv().ok()?)))
+                .unwrap();
+            admitted.push(class);
+            permit = next;
+        }
+        // high priority requests go first, but low priority ones still get
+        // their share of slots
+        assert_eq!(admitted, [High, High, High, Low, High, Low, Low, Low]);
+    }
+}
@@ -0,0 +1,205 @@
+//! Answers CORS preflights and adds CORS headers to responses, see
+//! [`CorsConfig`].
+//!
+//! Routers may override the global config, so the config is picked per
+//! request from its `/router/{id}` path, before requests are routed.
+use std::{
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use http::{HeaderName, HeaderValue, Method};
+use tower::Layer as _;
+use tower_http::cors::{
+    AllowHeaders, AllowMethods, AllowOrigin, CorsLayer, ExposeHeaders,
+};
+
+use crate::{
+    app_state::AppState,
+    config::cors::{CORS_WILDCARD, CorsConfig},
+    types::{request::Request, response::Response, router::RouterId},
+};
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    app_state: AppState,
+    global: CorsLayer,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn new(app_state: &AppState) -> Self {
+        Self {
+            app_state: app_state.clone(),
+            global: cors_layer(&app_state.config().cors),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            app_state: self.app_state.clone(),
+            global: self.global.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    app_state: AppState,
+    global: CorsLayer,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, inner);
+        let app_state = self.app_state.clone();
+        let global = self.global.clone();
+        let router_id = router_id(req.uri().path());
+        Box::pin(async move {
+            let router_cors = match router_id {
+                Some(router_id) => app_state
+                    .0
+                    .router_configs
+                    .read()
+                    .await
+                    .get(&router_id)
+                    .and_then(|router_config| router_config.cors.clone()),
+                None => None,
+            };
+            let layer = router_cors.map_or(global, |cors| cors_layer(&cors));
+            tower::Service::call(&mut layer.layer(inner), req).await
+        })
+    }
+}
+
+fn router_id(path: &str) -> Option<RouterId> {
+    let id = path.strip_prefix("/router/")?.split('/').next()?;
+    Some(RouterId::Named(id.into()))
+}
+
+fn is_wildcard(values: &[String]) -> bool {
+    values.iter().any(|value| value == CORS_WILDCARD)
+}
+
+/// Parses each value, skipping invalid ones, which config validation
+/// reports.
+fn parse<T: FromStr>(values: &[String]) -> Vec<T> {
+    values
+        .iter()
+        .filter_map(|value| T::from_str(value).ok())
+        .collect()
+}
+
+fn allow_origin(origins: &[String]) -> AllowOrigin {
+    if is_wildcard(origins) {
+        return AllowOrigin::any();
+    }
+    if !origins.iter().any(|origin| origin.contains(CORS_WILDCARD)) {
+        return AllowOrigin::list(parse::<HeaderValue>(origins));
+    }
+    let patterns = origins.to_vec();
+    AllowOrigin::predicate(move |origin, _| {
+        origin.to_str().is_ok_and(|origin| {
+            patterns
+                .iter()
+                .any(|pattern| origin_matches(pattern, origin))
+        })
+    })
+}
+
+/// Whether `origin` matches `pattern`, in which a `*` matches any subdomain,
+/// e.g. `https://*.example.com` matches `https://app.example.com` but not
+/// `https://example.com`.
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    let Some((prefix, suffix)) = pattern.split_once(CORS_WILDCARD) else {
+        return pattern == origin;
+    };
+    origin.len() > prefix.len() + suffix.len()
+        && origin.starts_with(prefix)
+        && origin.ends_with(suffix)
+        && origin[prefix.len()..origin.len() - suffix.len()]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let allow_methods = if is_wildcard(&config.allowed_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(parse::<Method>(&config.allowed_methods))
+    };
+    let allow_headers = if is_wildcard(&config.allowed_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(parse::<HeaderName>(&config.allowed_headers))
+    };
+    let layer = CorsLayer::new()
+        .allow_origin(allow_origin(&config.allowed_origins))
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .expose_headers(ExposeHeaders::list(parse::<HeaderName>(
+            &config.exposed_headers,
+        )))
+        .allow_credentials(config.allow_credentials);
+    match config.max_age {
+        Some(max_age) => layer.max_age(max_age),
+        None => layer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_origins_match_subdomains_only() {
+        let pattern = "https://*.example.com";
+
+        assert!(origin_matches(pattern, "https://app.example.com"));
+        assert!(origin_matches(pattern, "https://eu.app.example.com"));
+        assert!(!origin_matches(pattern, "https://example.com"));
+        assert!(!origin_matches(pattern, "http://app.example.com"));
+        assert!(!origin_matches(pattern, "https://app.example.com.evil.com"));
+        assert!(!origin_matches(pattern, "https://evil.com/.example.com"));
+        assert!(origin_matches(
+            "https://app.example.com",
+            "https://app.example.com"
+        ));
+    }
+
+    #[test]
+    fn routers_are_found_by_path() {
+        assert_eq!(
+            router_id("/router/my-router/chat/completions"),
+            Some(RouterId::Named("my-router".into()))
+        );
+        assert_eq!(router_id("/ai/chat/completions"), None);
+        assert_eq!(router_id("/openai/v1/chat/completions"), None);
+    }
+}
@@ -0,0 +1,293 @@
+//! Scores a sample of a router's completions with a judge model, see
+//! [`EvaluationConfig`].
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum_core::body::Body;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use http_body_util::BodyExt;
+use opentelemetry::KeyValue;
+use rust_decimal::prelude::ToPrimitive;
+use serde_json::json;
+use tokio::sync::watch;
+use tower::ServiceExt;
+use tracing::Instrument;
+
+use crate::{
+    app_state::AppState,
+    config::{
+        evaluation::EvaluationConfig, request_logging::RequestLogging,
+        router::RouterConfig,
+    },
+    dispatcher::{Dispatcher, DispatcherService},
+    endpoints::{ApiEndpoint, EndpointType},
+    error::{api::ApiError, init::InitError, internal::InternalError},
+    metrics::Metrics,
+    types::{
+        extensions::{EvaluationScore, RequestContext},
+        request::{Request, is_stream},
+        response::Response,
+        router::RouterId,
+    },
+};
+
+/// Upper bound on how long the request log waits for a score.
+const JUDGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct Evaluator {
+    config: Arc<EvaluationConfig>,
+    sample_rate: f64,
+    dispatcher: DispatcherService,
+    /// The router's config with request logging turned off, so that judge
+    /// requests aren't logged as if they were sent by the user.
+    judge_router_config: Arc<RouterConfig>,
+    metrics: Metrics,
+}
+
+impl Evaluator {
+    fn sample(&self) -> bool {
+        rand::random::<f64>() < self.sample_rate
+    }
+
+    /// Ask the judge to score the response in the background, sending the
+    /// score to the request log once it is available.
+    fn judge(
+        &self,
+        judge_req: http::Request<()>,
+        request_body: &Bytes,
+        response_body: &Bytes,
+        score_tx: watch::Sender<Option<f64>>,
+    ) {
+        let Evaluator {
+            config,
+            dispatcher,
+            judge_router_config,
+            metrics,
+            ..
+        } = self.clone();
+        let prompt = config.render_prompt(
+            &String::from_utf8_lossy(request_body),
+            &String::from_utf8_lossy(response_body),
+        );
+        let body = json!({
+            "model": config.model,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt,
+                }
+            ]
+        });
+        let mut judge_req = judge_req.map(|()| Body::from(body.to_string()));
+        judge_req.extensions_mut().insert(Arc::new(RequestContext {
+            router_config: Some(judge_router_config),
+            auth_context: None,
+        }));
+        let span =
+            tracing::info_span!("evaluation", provider = %config.provider);
+        tokio::spawn(
+            async move {
+                let score = tokio::time::timeout(
+                    JUDGE_TIMEOUT,
+                    judge_score(dispatcher, judge_req),
+                )
+                .await
+                .ok()
+                .flatten();
+                let outcome = if let Some(score) = score {
+                    tracing::debug!(score, "evaluation succeeded");
+                    score_tx.send_replace(Some(score));
+                    "success"
+                } else {
+                    tracing::warn!("evaluation failed");
+                    "error"
+                };
+                metrics.evaluations.add(
+                    1,
+                    &[
+                        KeyValue::new("provider", config.provider.to_string()),
+                        KeyValue::new("status", outcome),
+                    ],
+                );
+            }
+            .instrument(span),
+        );
+    }
+}
+
+async fn judge_score(
+    dispatcher: DispatcherService,
+    req: Request,
+) -> Option<f64> {
+    let response = match dispatcher.oneshot(req).await {
+        Ok(response) => response,
+        Err(e) => match e {},
+    };
+    if !response.status().is_success() {
+        tracing::debug!(status = %response.status(), "judge request failed");
+        return None;
+    }
+    let body = response.into_body().collect().await.ok()?.to_bytes();
+    let body = serde_json::from_slice::<serde_json::Value>(&body).ok()?;
+    let content = body.pointer("/choices/0/message/content")?.as_str()?;
+    parse_score(content)
+}
+
+/// The first number in the judge's reply.
+fn parse_score(content: &str) -> Option<f64> {
+    content
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find_map(|token| token.parse::<f64>().ok())
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    evaluator: Option<Evaluator>,
+}
+
+impl Layer {
+    pub async fn for_router(
+        app_state: &AppState,
+        router_id: &RouterId,
+        router_config: &Arc<RouterConfig>,
+    ) -> Result<Self, InitError> {
+        let Some(evaluation_config) = &router_config.evaluation else {
+            return Ok(Self { evaluator: None });
+        };
+        let dispatcher = Dispatcher::new_without_rate_limit_events(
+            app_state.clone(),
+            router_id,
+            router_config,
+            evaluation_config.provider.clone(),
+        )
+        .await?;
+        let sample_rate = evaluation_config.sample_rate.to_f64().unwrap_or(0.0);
+        let judge_router_config = Arc::new(RouterConfig {
+            logging: RequestLogging::Off,
+            evaluation: None,
+            ..RouterConfig::clone(router_config)
+        });
+        Ok(Self {
+            evaluator: Some(Evaluator {
+                config: Arc::new(evaluation_config.clone()),
+                sample_rate,
+                dispatcher,
+                judge_router_config,
+                metrics: app_state.0.metrics.clone(),
+            }),
+        })
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            evaluator: self.evaluator.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    evaluator: Option<Evaluator>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let is_chat =
+            req.extensions()
+                .get::<ApiEndpoint>()
+                .is_some_and(|endpoint| {
+                    endpoint.endpoint_type() == EndpointType::Chat
+                });
+        let Some(evaluator) = self
+            .evaluator
+            .clone()
+            .filter(|evaluator| is_chat && evaluator.sample())
+        else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            if is_stream(&body) {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            }
+
+            let mut judge_req = http::Request::new(());
+            *judge_req.method_mut() = parts.method.clone();
+            *judge_req.uri_mut() = parts.uri.clone();
+            *judge_req.headers_mut() = parts.headers.clone();
+            *judge_req.extensions_mut() = parts.extensions.clone();
+            let (score_tx, score_rx) = watch::channel(None);
+            parts.extensions.insert(EvaluationScore(score_rx));
+
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(body.clone())))
+                .await?;
+            if !response.status().is_success() {
+                return Ok(response);
+            }
+            let (response_parts, response_body) = response.into_parts();
+            let response_body = response_body
+                .collect()
+                .await
+                .map_err(InternalError::CollectBodyError)?
+                .to_bytes();
+            evaluator.judge(judge_req, &body, &response_body, score_tx);
+            Ok(Response::from_parts(
+                response_parts,
+                Body::from(response_body),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_score_finds_first_number() {
+        assert_eq!(parse_score("8"), Some(8.0));
+        assert_eq!(parse_score("Score: 7.5/10"), Some(7.5));
+        assert_eq!(parse_score("no score"), None);
+    }
+}
@@ -0,0 +1,485 @@
+//! Replays responses to clients which retry a request with the same
+//! `Idempotency-Key` header, see [`IdempotencyConfig`].
+//!
+//! The first request with a key claims it and is sent as usual. Its response
+//! is stored once the body completes, if it succeeded, and replayed to
+//! requests with the same key and organization until the window passes.
+//! Requests with a key which is still claimed are rejected with a 409, and
+//! requests reusing a key for a different request with a 422. Keys of
+//! requests which fail are released, so that the client can retry them.
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+};
+
+use axum_core::{body::Body, response::IntoResponse};
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use http_body::{Frame, SizeHint};
+use http_body_util::BodyExt;
+use pin_project_lite::pin_project;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_state::AppState,
+    config::idempotency::IdempotencyConfig,
+    error::{
+        api::ApiError, internal::InternalError,
+        invalid_req::InvalidRequestError,
+    },
+    types::{
+        extensions::AuthContext, org::OrgId, request::Request,
+        response::Response,
+    },
+};
+
+const IDEMPOTENCY_KEY_HEADER: HeaderName =
+    HeaderName::from_static("idempotency-key");
+/// Set on replayed responses.
+pub const IDEMPOTENT_REPLAYED_HEADER: HeaderName =
+    HeaderName::from_static("idempotent-replayed");
+
+/// Keys are scoped to the organization of the request, or shared by every
+/// client if auth is disabled.
+type Key = (Option<OrgId>, String);
+/// Identifies the request a key was claimed for.
+type Fingerprint = [u8; 32];
+
+#[derive(Debug, Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl IntoResponse for StoredResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response.headers_mut().insert(
+            IDEMPOTENT_REPLAYED_HEADER,
+            HeaderValue::from_static("true"),
+        );
+        response
+    }
+}
+
+#[derive(Debug)]
+enum Entry {
+    InProgress {
+        fingerprint: Fingerprint,
+    },
+    Completed {
+        fingerprint: Fingerprint,
+        response: StoredResponse,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+impl Entry {
+    fn fingerprint(&self) -> &Fingerprint {
+        match self {
+            Self::InProgress { fingerprint }
+            | Self::Completed { fingerprint, .. } => fingerprint,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_key: HashMap<Key, Entry>,
+    /// Completed entries in the order they expire, which is the order they
+    /// completed in since the window is the same for all of them.
+    expiries: VecDeque<(DateTime<Utc>, Key)>,
+}
+
+impl Entries {
+    fn remove_expired(&mut self, now: DateTime<Utc>) {
+        while self
+            .expiries
+            .front()
+            .is_some_and(|(expires_at, _)| *expires_at <= now)
+        {
+            let Some((expires_at, key)) = self.expiries.pop_front() else {
+                break;
+            };
+            // unless the key was claimed again since
+            if matches!(
+                self.by_key.get(&key),
+                Some(Entry::Completed { expires_at: completed_expiry, .. })
+                    if *completed_expiry == expires_at
+            ) {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+}
+
+enum Claim {
+    Claimed,
+    Replay(StoredResponse),
+    InProgress,
+    Reused,
+}
+
+/// The responses stored for idempotency keys, shared by every router and the
+/// unified API.
+#[derive(Debug, Clone, Default)]
+pub struct IdempotencyStore(Arc<Mutex<Entries>>);
+
+impl IdempotencyStore {
+    fn claim(
+        &self,
+        key: &Key,
+        fingerprint: Fingerprint,
+        now: DateTime<Utc>,
+    ) -> Claim {
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.remove_expired(now);
+        match entries.by_key.get(key) {
+            None => {
+                entries
+                    .by_key
+                    .insert(key.clone(), Entry::InProgress { fingerprint });
+                Claim::Claimed
+            }
+            Some(entry) if *entry.fingerprint() != fingerprint => Claim::Reused,
+            Some(Entry::InProgress { .. }) => Claim::InProgress,
+            Some(Entry::Completed { response, .. }) => {
+                Claim::Replay(response.clone())
+            }
+        }
+    }
+
+    fn complete(
+        &self,
+        key: Key,
+        fingerprint: Fingerprint,
+        response: StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) {
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.expiries.push_back((expires_at, key.clone()));
+        entries.by_key.insert(
+            key,
+            Entry::Completed {
+                fingerprint,
+                response,
+                expires_at,
+            },
+        );
+    }
+
+    fn release(&self, key: &Key) {
+        let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if matches!(entries.by_key.get(key), Some(Entry::InProgress { .. })) {
+            entries.by_key.remove(key);
+        }
+    }
+}
+
+/// A claimed key, released when dropped unless its response was stored.
+struct ClaimedKey {
+    app_state: AppState,
+    window: std::time::Duration,
+    key: Option<Key>,
+    fingerprint: Fingerprint,
+}
+
+impl ClaimedKey {
+    fn complete(mut self, status: StatusCode, headers: HeaderMap, body: Bytes) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let now = self.app_state.0.clock.now();
+        let expires_at = chrono::Duration::from_std(self.window)
+            .ok()
+            .and_then(|window| now.checked_add_signed(window))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        self.app_state.0.idempotency.complete(
+            key,
+            self.fingerprint,
+            StoredResponse {
+                status,
+                headers,
+                body,
+            },
+            expires_at,
+        );
+    }
+}
+
+impl Drop for ClaimedKey {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.app_state.0.idempotency.release(&key);
+        }
+    }
+}
+
+fn fingerprint(req: &http::request::Parts, body: &[u8]) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method.as_str());
+    hasher.update(req.uri.path());
+    hasher.update(body);
+    hasher.finalize().into()
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    /// Is `Some` if idempotency keys are honored.
+    app_state: Option<AppState>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn new(app_state: &AppState) -> Self {
+        Self {
+            app_state: app_state
+                .config()
+                .idempotency
+                .is_some()
+                .then(|| app_state.clone()),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            app_state: self.app_state.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    app_state: Option<AppState>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let (Some(app_state), Some(idempotency_key)) = (
+            self.app_state.clone(),
+            req.headers()
+                .get(IDEMPOTENCY_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string),
+        ) else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let Some(config) = app_state.config().idempotency.clone() else {
+                return inner.call(req).await;
+            };
+            let org_id = req
+                .extensions()
+                .get::<AuthContext>()
+                .map(|auth_ctx| auth_ctx.org_id);
+            let (parts, body) = req.into_parts();
+            let body = match body.collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) => {
+                    return Ok(ApiError::from(
+                        InternalError::RequestBodyError(Box::new(e)),
+                    )
+                    .into_response());
+                }
+            };
+            let key = (org_id, idempotency_key);
+            let fingerprint = fingerprint(&parts, &body);
+            let now = app_state.0.clock.now();
+            match app_state.0.idempotency.claim(&key, fingerprint, now) {
+                Claim::Claimed => {}
+                Claim::Replay(response) => {
+                    tracing::debug!("replaying response for idempotency key");
+                    return Ok(response.into_response());
+                }
+                Claim::InProgress => {
+                    return Ok(ApiError::from(
+                        InvalidRequestError::IdempotencyKeyInProgress,
+                    )
+                    .into_response());
+                }
+                Claim::Reused => {
+                    return Ok(ApiError::from(
+                        InvalidRequestError::IdempotencyKeyReused,
+                    )
+                    .into_response());
+                }
+            }
+            let claimed = ClaimedKey {
+                app_state: app_state.clone(),
+                window: config.window,
+                key: Some(key),
+                fingerprint,
+            };
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await?;
+            // failed requests can be retried, so their key is released
+            if !response.status().is_success() {
+                return Ok(response);
+            }
+            let status = response.status();
+            let headers = response.headers().clone();
+            Ok(response.map(|body| {
+                Body::new(StoringBody {
+                    inner: body,
+                    frames: Vec::new(),
+                    size: 0,
+                    max_size: config.max_stored_body_bytes,
+                    pending: Some((claimed, status, headers)),
+                })
+            }))
+        })
+    }
+}
+
+pin_project! {
+    /// Passes a response body through, storing it for its idempotency key
+    /// once it completes.
+    struct StoringBody {
+        #[pin]
+        inner: Body,
+        frames: Vec<Bytes>,
+        size: usize,
+        max_size: usize,
+        // dropping the claimed key releases it, e.g. if the client
+        // disconnects before the body completes
+        pending: Option<(ClaimedKey, StatusCode, HeaderMap)>,
+    }
+}
+
+impl http_body::Body for StoringBody {
+    type Data = Bytes;
+    type Error = axum_core::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = std::task::ready!(this.inner.as_mut().poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref()
+                    && this.pending.is_some()
+                {
+                    *this.size += data.len();
+                    this.frames.push(data.clone());
+                    if *this.size > *this.max_size {
+                        tracing::debug!(
+                            "response too large to store for idempotency key"
+                        );
+                        this.pending.take();
+                        this.frames.clear();
+                    }
+                }
+            }
+            Some(Err(_)) => {
+                this.pending.take();
+            }
+            None => {}
+        }
+        if (frame.is_none() || this.inner.is_end_stream())
+            && let Some((claimed, status, headers)) = this.pending.take()
+        {
+            let body = this
+                .frames
+                .drain(..)
+                .fold(BytesMut::new(), |mut body, frame| {
+                    body.extend_from_slice(&frame);
+                    body
+                })
+                .freeze();
+            claimed.complete(status, headers, body);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored() -> StoredResponse {
+        StoredResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"{}"),
+        }
+    }
+
+    #[test]
+    fn keys_are_replayed_until_they_expire() {
+        let store = IdempotencyStore::default();
+        let key = (None, "key".to_string());
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::seconds(60);
+
+        assert!(matches!(store.claim(&key, [0; 32], now), Claim::Claimed));
+        assert!(matches!(store.claim(&key, [0; 32], now), Claim::InProgress));
+        assert!(matches!(store.claim(&key, [1; 32], now), Claim::Reused));
+
+        store.complete(key.clone(), [0; 32], stored(), expires_at);
+        assert!(matches!(store.claim(&key, [0; 32], now), Claim::Replay(_)));
+        assert!(matches!(
+            store.claim(&key, [0; 32], expires_at),
+            Claim::Claimed
+        ));
+        // expiring the first response leaves the new claim in place
+        assert!(matches!(
+            store.claim(&key, [0; 32], expires_at),
+            Claim::InProgress
+        ));
+    }
+
+    #[test]
+    fn released_keys_can_be_claimed_again() {
+        let store = IdempotencyStore::default();
+        let key = (None, "key".to_string());
+        let now = Utc::now();
+
+        assert!(matches!(store.claim(&key, [0; 32], now), Claim::Claimed));
+        store.release(&key);
+        assert!(matches!(store.claim(&key, [1; 32], now), Claim::Claimed));
+    }
+}
@@ -0,0 +1,290 @@
+//! Validates that the completions of `json_object` requests are valid JSON,
+//! see [`JsonRepairMode`].
+//!
+//! Repairs are lightweight: markdown code fences and any text around the
+//! outermost JSON object or array are stripped. Responses with a repaired
+//! completion have the `helicone-json-repaired: true` header.
+use std::task::{Context, Poll};
+
+use axum_core::body::Body;
+use futures::future::BoxFuture;
+use http::{HeaderName, HeaderValue, header};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{
+    config::{json_repair::JsonRepairMode, router::RouterConfig},
+    error::{api::ApiError, internal::InternalError},
+    types::{
+        request::{Request, is_stream},
+        response::Response,
+    },
+};
+
+pub(crate) const JSON_REPAIRED_HEADER: HeaderName =
+    HeaderName::from_static("helicone-json-repaired");
+
+#[derive(Debug, Clone, Copy)]
+pub struct Layer {
+    mode: JsonRepairMode,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            mode: router_config.json_repair,
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            mode: self.mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    mode: JsonRepairMode,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        if self.mode.is_off() {
+            return Box::pin(inner.call(req));
+        }
+        let mode = self.mode;
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            if !wants_json_object(&body) {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            }
+
+            let retry_req = (mode == JsonRepairMode::Retry).then(|| {
+                Request::from_parts(parts.clone(), body.clone().into())
+            });
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await?;
+            let (response, Some(completion)) =
+                split_completion(response).await?
+            else {
+                return Ok(response);
+            };
+            if has_valid_contents(&completion) {
+                return Ok(with_completion(response, &completion));
+            }
+            let Some(retry_req) = retry_req else {
+                return Ok(repaired(response, completion));
+            };
+
+            tracing::debug!("completion is not valid JSON, retrying");
+            let response = inner.ready().await?.call(retry_req).await?;
+            let (response, Some(completion)) =
+                split_completion(response).await?
+            else {
+                return Ok(response);
+            };
+            Ok(repaired(response, completion))
+        })
+    }
+}
+
+/// Whether the request asks for a JSON object, rather than streaming.
+fn wants_json_object(body: &[u8]) -> bool {
+    if is_stream(body) {
+        return false;
+    }
+    serde_json::from_slice::<Value>(body).is_ok_and(|body| {
+        body.pointer("/response_format/type")
+            .and_then(Value::as_str)
+            .is_some_and(|ty| ty == "json_object")
+    })
+}
+
+/// Splits off the chat completion of a successful response, so that the
+/// response can be rebuilt with its completion repaired. Other responses are
+/// returned as they are.
+async fn split_completion(
+    response: Response,
+) -> Result<(Response, Option<Value>), ApiError> {
+    if !response.status().is_success() {
+        return Ok((response, None));
+    }
+    let (parts, body) = response.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(InternalError::CollectBodyError)?
+        .to_bytes();
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(completion) => {
+            Ok((Response::from_parts(parts, Body::empty()), Some(completion)))
+        }
+        Err(_) => Ok((Response::from_parts(parts, Body::from(body)), None)),
+    }
+}
+
+fn with_completion(response: Response, completion: &Value) -> Response {
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(completion.to_string()))
+}
+
+/// The message contents of each choice.
+fn contents(completion: &mut Value) -> impl Iterator<Item = &mut Value> {
+    completion
+        .get_mut("choices")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|choice| choice.pointer_mut("/message/content"))
+}
+
+fn is_valid_json(content: &Value) -> bool {
+    content
+        .as_str()
+        .is_none_or(|content| serde_json::from_str::<Value>(content).is_ok())
+}
+
+fn has_valid_contents(completion: &Value) -> bool {
+    completion
+        .get("choices")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|choice| choice.pointer("/message/content"))
+        .all(is_valid_json)
+}
+
+/// Repairs the contents that aren't valid JSON where possible, leaving the
+/// others as they are.
+fn repaired(response: Response, mut completion: Value) -> Response {
+    let mut was_repaired = false;
+    for content in contents(&mut completion) {
+        if is_valid_json(content) {
+            continue;
+        }
+        if let Some(repaired) = content.as_str().and_then(repair) {
+            *content = Value::String(repaired);
+            was_repaired = true;
+        }
+    }
+    let mut response = with_completion(response, &completion);
+    if was_repaired {
+        tracing::debug!("repaired JSON completion");
+        response
+            .headers_mut()
+            .insert(JSON_REPAIRED_HEADER, HeaderValue::from_static("true"));
+    }
+    response
+}
+
+/// The JSON value in `text`, without markdown code fences or text around it.
+fn repair(text: &str) -> Option<String> {
+    let text = strip_code_fence(text.trim());
+    if serde_json::from_str::<Value>(text).is_ok() {
+        return Some(text.to_string());
+    }
+    let start = text.find(['{', '['])?;
+    let close = if text[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    let end = text.rfind(close)?;
+    let candidate = text.get(start..=end)?;
+    serde_json::from_str::<Value>(candidate)
+        .is_ok()
+        .then(|| candidate.to_string())
+}
+
+/// Strips a code fence, e.g. ` ```json ... ``` `, around the whole text.
+fn strip_code_fence(text: &str) -> &str {
+    let Some(fenced) = text.strip_prefix("```") else {
+        return text;
+    };
+    // the language tag, if any, is on the same line as the opening fence
+    let fenced = fenced.split_once('\n').map_or("", |(_, rest)| rest);
+    fenced
+        .trim_end()
+        .strip_suffix("```")
+        .unwrap_or(fenced)
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fenced_json_is_unwrapped() {
+        assert_eq!(
+            repair("```json\n{\"city\": \"Paris\"}\n```").as_deref(),
+            Some("{\"city\": \"Paris\"}")
+        );
+        assert_eq!(repair("```\n[1, 2]\n```\n").as_deref(), Some("[1, 2]"));
+    }
+
+    #[test]
+    fn text_around_json_is_trimmed() {
+        assert_eq!(
+            repair("Here you go: {\"a\": {\"b\": 1}} Let me know!").as_deref(),
+            Some("{\"a\": {\"b\": 1}}")
+        );
+    }
+
+    #[test]
+    fn unrepairable_json_is_left_alone() {
+        assert_eq!(repair("{\"a\": "), None);
+        assert_eq!(repair("no JSON here"), None);
+    }
+
+    #[test]
+    fn only_json_object_requests_are_checked() {
+        assert!(wants_json_object(
+            br#"{"response_format": {"type": "json_object"}}"#
+        ));
+        assert!(!wants_json_object(
+            br#"{"response_format": {"type": "json_object"}, "stream": true}"#
+        ));
+        assert!(!wants_json_object(br#"{"messages": []}"#));
+    }
+}
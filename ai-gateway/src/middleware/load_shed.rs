@@ -0,0 +1,145 @@
+//! Rejects requests with a `503` when the inner service has not become ready
+//! within [`MAX_SATURATED_WAIT`], e.g. when every provider in a load balanced
+//! router stays at its `max-concurrent-requests` limit.
+use std::{
+    future::{Ready, ready},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::response::IntoResponse;
+use futures::future::Either;
+use indexmap::IndexSet;
+use tokio::time::Sleep;
+
+use crate::{
+    dispatcher::concurrency::{MAX_SATURATED_WAIT, ProviderConcurrencyLimits},
+    error::api::ApiError,
+    types::{
+        provider::InferenceProvider, request::Request, response::Response,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    enabled: bool,
+    concurrency_limits: ProviderConcurrencyLimits,
+    providers: Arc<IndexSet<InferenceProvider>>,
+}
+
+impl Layer {
+    /// Only sheds load if one of the router's `providers` has a concurrency
+    /// limit, otherwise requests should wait for a provider to become ready.
+    #[must_use]
+    pub fn new(
+        concurrency_limits: ProviderConcurrencyLimits,
+        providers: IndexSet<InferenceProvider>,
+    ) -> Self {
+        let enabled = providers
+            .iter()
+            .any(|provider| concurrency_limits.is_limited(provider));
+        Self {
+            enabled,
+            concurrency_limits,
+            providers: Arc::new(providers),
+        }
+    }
+
+    /// For when we statically know that load shedding is disabled.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            concurrency_limits: ProviderConcurrencyLimits::default(),
+            providers: Arc::default(),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            enabled: self.enabled,
+            concurrency_limits: self.concurrency_limits.clone(),
+            providers: self.providers.clone(),
+            deadline: None,
+            is_ready: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Service<S> {
+    inner: S,
+    enabled: bool,
+    concurrency_limits: ProviderConcurrencyLimits,
+    providers: Arc<IndexSet<InferenceProvider>>,
+    /// Set once the inner service is pending, and cleared once it is ready
+    /// again. Requests are shed from when it elapses until then.
+    deadline: Option<Pin<Box<Sleep>>>,
+    /// Whether the inner service reported ready on the last `poll_ready`.
+    is_ready: bool,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response, S::Error>>, S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        match self.inner.poll_ready(cx) {
+            Poll::Ready(Ok(())) => {
+                self.deadline = None;
+                self.is_ready = true;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => {
+                self.deadline = None;
+                Poll::Ready(Err(e))
+            }
+            Poll::Pending if self.enabled => {
+                // the inner service wakes us once a provider frees a slot,
+                // and the deadline if none does in time
+                let deadline = self.deadline.get_or_insert_with(|| {
+                    Box::pin(tokio::time::sleep(MAX_SATURATED_WAIT))
+                });
+                std::task::ready!(deadline.as_mut().poll(cx));
+                self.is_ready = false;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        if self.enabled && !std::mem::take(&mut self.is_ready) {
+            // the inner service is also pending while providers are being
+            // discovered or are unhealthy, so we only blame the concurrency
+            // limits when every provider is at its limit
+            let all_saturated = self
+                .providers
+                .iter()
+                .all(|provider| self.concurrency_limits.is_saturated(provider));
+            let error = if all_saturated {
+                ApiError::ProvidersSaturated
+            } else {
+                ApiError::NoProvidersReady
+            };
+            // return a response rather than an error so that the buffer
+            // in front of us does not turn it into an internal error
+            let response = error.into_response();
+            return Either::Left(ready(Ok(response)));
+        }
+        Either::Right(self.inner.call(req))
+    }
+}
@@ -29,6 +29,106 @@ impl AnthropicConverter {
     }
 }
 
+/// Anthropic rejects images larger than this once decoded.
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+const SUPPORTED_IMAGE_TYPES: [&str; 4] =
+    ["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+/// Maps an `OpenAI` image URL to an Anthropic image source.
+///
+/// Anthropic needs the image data and its media type, so only base64 `data:`
+/// URLs are supported. We don't fetch remote URLs on the client's behalf.
+fn image_source(
+    url: &str,
+) -> Result<anthropic_ai_sdk::types::message::ImageSource, MapperError> {
+    let invalid =
+        |reason: &str| MapperError::ImageMappingInvalid(reason.into());
+    let Some(data_url) = url.strip_prefix("data:") else {
+        return Err(invalid(
+            "remote image URLs are not supported by Anthropic, send the image \
+             as a base64 encoded `data:` URL instead",
+        ));
+    };
+    let (metadata, data) = data_url
+        .split_once(',')
+        .ok_or_else(|| invalid("malformed `data:` URL"))?;
+    let media_type = metadata
+        .strip_suffix(";base64")
+        .ok_or_else(|| invalid("image `data:` URLs must be base64 encoded"))?;
+    let media_type = if media_type.is_empty() {
+        mime_from_data_uri(url).map_or("", |mime| mime.mime_type())
+    } else {
+        media_type
+    };
+    if !SUPPORTED_IMAGE_TYPES.contains(&media_type) {
+        return Err(MapperError::ImageMappingInvalid(format!(
+            "unsupported image type `{media_type}`, expected one of {}",
+            SUPPORTED_IMAGE_TYPES.join(", ")
+        )));
+    }
+    let padding = data.bytes().rev().take_while(|b| *b == b'=').count();
+    let size = (data.len() / 4 * 3).saturating_sub(padding);
+    if size > MAX_IMAGE_BYTES {
+        return Err(MapperError::ImageTooLarge {
+            size,
+            max: MAX_IMAGE_BYTES,
+        });
+    }
+    Ok(anthropic_ai_sdk::types::message::ImageSource {
+        type_: "base64".to_string(),
+        media_type: media_type.to_string(),
+        data: data.to_string(),
+    })
+}
+
+/// The tool Anthropic is forced to call in place of OpenAI's
+/// `response_format`, since it has no native structured outputs. Its input is
+/// unwrapped back into the message content of responses.
+const STRUCTURED_OUTPUT_TOOL: &str = "structured_output";
+
+/// Maps an OpenAI `response_format` to the tool standing in for it, if the
+/// format isn't plain text.
+fn structured_output_tool(
+    response_format: async_openai::types::ResponseFormat,
+) -> Option<anthropic_ai_sdk::types::message::Tool> {
+    use async_openai::types::ResponseFormat;
+    let (description, input_schema) = match response_format {
+        ResponseFormat::Text => return None,
+        ResponseFormat::JsonObject => (
+            "Respond with a JSON object.".to_string(),
+            serde_json::json!({ "type": "object" }),
+        ),
+        ResponseFormat::JsonSchema { json_schema } => (
+            json_schema.description.unwrap_or_else(|| {
+                format!("Respond with a `{}` JSON object.", json_schema.name)
+            }),
+            json_schema
+                .schema
+                .unwrap_or_else(|| serde_json::json!({ "type": "object" })),
+        ),
+    };
+    Some(anthropic_ai_sdk::types::message::Tool {
+        name: STRUCTURED_OUTPUT_TOOL.to_string(),
+        description: Some(description),
+        input_schema,
+    })
+}
+
+fn finish_reason(
+    stop_reason: Option<&anthropic_ai_sdk::types::message::StopReason>,
+) -> Option<async_openai::types::FinishReason> {
+    use anthropic_ai_sdk::types::message::StopReason;
+    use async_openai::types::FinishReason;
+    match stop_reason? {
+        StopReason::EndTurn | StopReason::StopSequence => {
+            Some(FinishReason::Stop)
+        }
+        StopReason::MaxTokens => Some(FinishReason::Length),
+        StopReason::ToolUse => Some(FinishReason::ToolCalls),
+        StopReason::Refusal => Some(FinishReason::ContentFilter),
+    }
+}
+
 impl
     TryConvert<
         async_openai::types::CreateChatCompletionRequest,
@@ -121,6 +221,30 @@ impl
             None => None,
         };
 
+        let (tools, tool_choice) =
+            match value.response_format.and_then(structured_output_tool) {
+                Some(_) if stream.is_some_and(|stream| stream) => {
+                    return Err(MapperError::StructuredOutputStreaming(
+                        InferenceProvider::Anthropic,
+                    ));
+                }
+                Some(structured_output_tool) => {
+                    // the model may still call the client's tools rather than
+                    // responding
+                    let tool_choice = if tools.is_some() {
+                        anthropic::ToolChoice::Any
+                    } else {
+                        anthropic::ToolChoice::Tool {
+                            name: STRUCTURED_OUTPUT_TOOL.to_string(),
+                        }
+                    };
+                    let mut tools = tools.unwrap_or_default();
+                    tools.push(structured_output_tool);
+                    (Some(tools), Some(tool_choice))
+                }
+                None => (tools, tool_choice),
+            };
+
         let mut mapped_messages = Vec::with_capacity(value.messages.len());
         for message in value.messages {
             match message {
@@ -136,32 +260,15 @@ impl
                             let mapped_content_blocks = content.into_iter().filter_map(|part| {
                                 match part {
                                     openai::ChatCompletionRequestUserMessageContentPart::Text(text) => {
-                                        Some(anthropic::ContentBlock::Text { text: text.text })
+                                        Some(Ok(anthropic::ContentBlock::Text { text: text.text }))
                                     },
                                     openai::ChatCompletionRequestUserMessageContentPart::ImageUrl(image) => {
-                                        let mapped_image = if image.image_url.url.starts_with("http") {
-                                            anthropic::ImageSource {
-                                                type_: "url".to_string(),
-                                                media_type: String::new(),
-                                                data: image.image_url.url,
-                                            }
-                                        } else {
-                                            let mime = mime_from_data_uri(&image.image_url.url)?;
-                                            let (_, b64) = image.image_url.url.split_once(',')?;
-                                            anthropic::ImageSource {
-                                                type_: "base64".to_string(),
-                                                media_type: mime.mime_type().to_string(),
-                                                data: b64.to_string(),
-                                            }
-                                        };
-                                        Some(anthropic::ContentBlock::Image { source: mapped_image })
-                                    },
-                                    openai::ChatCompletionRequestUserMessageContentPart::InputAudio(_audio) => {                                         // Anthropic API does not support audio
-                                        // Anthropic does not support audio
-                                        None
+                                        Some(image_source(&image.image_url.url).map(|source| anthropic::ContentBlock::Image { source }))
                                     },
+                                    // Anthropic does not support audio
+                                    openai::ChatCompletionRequestUserMessageContentPart::InputAudio(_audio) => None,
                                 }
-                            }).collect();
+                            }).collect::<Result<_, _>>()?;
                             anthropic::MessageContent::Blocks { content: mapped_content_blocks }
                         },
                     };
@@ -239,29 +346,42 @@ impl
                     }
                 }
                 openai::ChatCompletionRequestMessage::Tool(message) => {
-                    tracing::info!(message = ?message, "tool message");
-                    let mapped_content = match message.content {
-                        openai::ChatCompletionRequestToolMessageContent::Text(content) => {
-                            let block = anthropic::ContentBlock::ToolResult {
-                                tool_use_id: message.tool_call_id,
-                                content,
-                            };
-                            anthropic::MessageContent::Blocks { content: vec![block] }
-                        },
+                    let content = match message.content {
+                        openai::ChatCompletionRequestToolMessageContent::Text(content) => content,
                         openai::ChatCompletionRequestToolMessageContent::Array(content) => {
-                            let mapped_content_blocks = content.into_iter().map(|part| {
+                            content.into_iter().map(|part| {
                                 match part {
-                                    openai::ChatCompletionRequestToolMessageContentPart::Text(text) => {
-                                        anthropic::ContentBlock::ToolResult { tool_use_id: message.tool_call_id.clone(), content: text.text }
-                                    },
+                                    openai::ChatCompletionRequestToolMessageContentPart::Text(text) => text.text,
                                 }
-                            }).collect();
-                            anthropic::MessageContent::Blocks { content: mapped_content_blocks }
+                            }).collect::<Vec<_>>().join("\n")
                         },
                     };
+                    let block = anthropic::ContentBlock::ToolResult {
+                        tool_use_id: message.tool_call_id,
+                        content,
+                    };
+                    // the results of parallel tool calls are sent as
+                    // consecutive tool messages, while anthropic expects them
+                    // in a single user message
+                    if let Some(anthropic::Message {
+                        role: anthropic::Role::User,
+                        content: anthropic::MessageContent::Blocks { content },
+                    }) = mapped_messages.last_mut()
+                        && content.iter().all(|block| {
+                            matches!(
+                                block,
+                                anthropic::ContentBlock::ToolResult { .. }
+                            )
+                        })
+                    {
+                        content.push(block);
+                        continue;
+                    }
                     let mapped_message = anthropic::Message {
                         role: anthropic::Role::User,
-                        content: mapped_content,
+                        content: anthropic::MessageContent::Blocks {
+                            content: vec![block],
+                        },
                     };
                     mapped_messages.push(mapped_message);
                 }
@@ -337,41 +457,51 @@ impl
             completion_tokens_details: None,
         };
 
+        let finish_reason = finish_reason(value.stop_reason.as_ref());
         let mut tool_calls: Vec<openai::ChatCompletionMessageToolCall> =
             Vec::new();
-        let mut content = None;
+        let mut content: Option<String> = None;
+        let mut structured_output: Option<String> = None;
         for anthropic_content in value.content {
             match anthropic_content {
+                anthropic::ContentBlock::ToolUse { name, input, .. }
+                    if name == STRUCTURED_OUTPUT_TOOL =>
+                {
+                    structured_output = Some(serde_json::to_string(&input)?);
+                }
                 anthropic::ContentBlock::ToolUse { id, name, input } => {
                     tool_calls.push(openai::ChatCompletionMessageToolCall {
-                        id: id.clone(),
+                        id,
                         r#type: openai::ChatCompletionToolType::Function,
                         function: openai::FunctionCall {
-                            name: name.clone(),
+                            name,
                             arguments: serde_json::to_string(&input)?,
                         },
                     });
                 }
-                anthropic::ContentBlock::ToolResult {
-                    tool_use_id,
-                    content,
-                } => tool_calls.push(openai::ChatCompletionMessageToolCall {
-                    id: tool_use_id.clone(),
-                    r#type: openai::ChatCompletionToolType::Function,
-                    function: openai::FunctionCall {
-                        name: tool_use_id.clone(),
-                        arguments: serde_json::to_string(&content)?,
-                    },
-                }),
-
                 anthropic::ContentBlock::Text { text, .. } => {
-                    content = Some(text.clone());
+                    content.get_or_insert_default().push_str(&text);
                 }
-                anthropic::ContentBlock::Image { .. }
+                // tool results are only ever sent by the client
+                anthropic::ContentBlock::ToolResult { .. }
+                | anthropic::ContentBlock::Image { .. }
                 | anthropic::ContentBlock::Thinking { .. }
                 | anthropic::ContentBlock::RedactedThinking { .. } => {}
             }
         }
+        // the structured output is the response rather than a tool call, and
+        // replaces any text the model wrote before calling the tool
+        let finish_reason = match structured_output {
+            Some(structured_output) => {
+                content = Some(structured_output);
+                if tool_calls.is_empty() {
+                    Some(openai::FinishReason::Stop)
+                } else {
+                    finish_reason
+                }
+            }
+            None => finish_reason,
+        };
         let tool_calls = if tool_calls.is_empty() {
             None
         } else {
@@ -391,7 +521,7 @@ impl
         let choice = openai::ChatChoice {
             index: 0,
             message,
-            finish_reason: None,
+            finish_reason,
             logprobs: None,
         };
 
@@ -417,7 +547,6 @@ impl
 {
     type Error = MapperError;
 
-    #[allow(clippy::too_many_lines)]
     fn try_convert_chunk(
         &self,
         value: anthropic_ai_sdk::types::message::StreamEvent,
@@ -425,306 +554,300 @@ impl
         Option<async_openai::types::CreateChatCompletionStreamResponse>,
         Self::Error,
     > {
-        use anthropic_ai_sdk::types::message as anthropic;
-        use async_openai::types as openai;
+        chat_completion_chunk(value)
+    }
+}
 
-        const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
-        // TODO: These placeholder values for id, model, and created should be
-        // replaced by actual values from the MessageStart event,
-        // propagated by the stream handling logic.
-        const PLACEHOLDER_STREAM_ID: &str = "anthropic-stream-id";
-        const PLACEHOLDER_MODEL_NAME: &str = "anthropic-model";
-        const DEFAULT_CREATED_TIMESTAMP: u32 = 0;
+/// Converts a single stream event, see
+/// [`ChatCompletionStream`](super::anthropic_stream::ChatCompletionStream)
+/// for the state carried across the events of a stream.
+#[allow(clippy::too_many_lines)]
+pub(super) fn chat_completion_chunk(
+    value: anthropic_ai_sdk::types::message::StreamEvent,
+) -> std::result::Result<
+    Option<async_openai::types::CreateChatCompletionStreamResponse>,
+    MapperError,
+> {
+    use anthropic_ai_sdk::types::message as anthropic;
+    use async_openai::types as openai;
 
-        #[allow(deprecated)]
-        match value {
-            anthropic::StreamEvent::MessageStart { message } => {
-                let mut current_text_content = String::new();
-                let mut tool_calls = Vec::new();
-
-                for (idx, content_block) in message.content.iter().enumerate() {
-                    match content_block {
-                        anthropic::ContentBlock::Text { text, .. } => {
-                            current_text_content.push_str(text);
-                        }
-                        anthropic::ContentBlock::ToolUse {
-                            id,
-                            name,
-                            input,
-                        } => {
-                            tool_calls.push(
-                                openai::ChatCompletionMessageToolCallChunk {
-                                    index: u32::try_from(idx).unwrap_or(0),
-                                    id: Some(id.clone()),
-                                    r#type: Some(openai::ChatCompletionToolType::Function),
-                                    function: Some(openai::FunctionCallStream {
-                                        name: Some(name.clone()),
-                                        arguments: Some(
-                                            serde_json::to_string(input)
-                                                .map_err(MapperError::SerdeError)?,
-                                        ),
-                                    }),
-                                }
-                            );
-                        }
-                        anthropic::ContentBlock::ToolResult {
-                            tool_use_id: _,
-                            content,
-                        } => {
-                            current_text_content.push('\n');
-                            current_text_content.push_str(content);
-                        }
-                        _ => {}
-                    }
-                }
+    const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
+    // the id and model are only sent with the `message_start` event, so the
+    // chunks of other events are filled in by `ChatCompletionStream`
+    const PLACEHOLDER_STREAM_ID: &str = "anthropic-stream-id";
+    const PLACEHOLDER_MODEL_NAME: &str = "anthropic-model";
+    const DEFAULT_CREATED_TIMESTAMP: u32 = 0;
 
-                let finish_reason = match message.stop_reason {
-                    Some(
-                        anthropic::StopReason::EndTurn
-                        | anthropic::StopReason::StopSequence,
-                    ) => Some(openai::FinishReason::Stop),
-                    Some(anthropic::StopReason::MaxTokens) => {
-                        Some(openai::FinishReason::Length)
-                    }
-                    Some(anthropic::StopReason::ToolUse) => {
-                        Some(openai::FinishReason::ToolCalls)
-                    }
-                    Some(anthropic::StopReason::Refusal) => {
-                        Some(openai::FinishReason::ContentFilter)
-                    }
-                    None => None,
-                };
-
-                let refusal_content = if matches!(
-                    message.stop_reason,
-                    Some(anthropic::StopReason::Refusal)
-                ) {
-                    message.stop_sequence.clone() // stop_sequence is Option<String>
-                } else {
-                    None
-                };
-
-                let choice = openai::ChatChoiceStream {
-                    index: 0,
-                    delta: openai::ChatCompletionStreamResponseDelta {
-                        role: Some(match message.role {
-                            anthropic::Role::User => openai::Role::User,
-                            anthropic::Role::Assistant => {
-                                openai::Role::Assistant
-                            }
-                        }),
-                        content: Some(current_text_content),
-                        tool_calls: Some(tool_calls),
-                        refusal: refusal_content,
-                        function_call: None,
-                    },
-                    finish_reason,
-                    logprobs: None,
-                };
-                Ok(Some(openai::CreateChatCompletionStreamResponse {
-                    id: message.id,
-                    choices: vec![choice],
-                    created: DEFAULT_CREATED_TIMESTAMP, /* Or use message.
-                                                         * usage if there's a
-                                                         * timestamp */
-                    model: message.model,
-                    object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
-                    system_fingerprint: None,
-                    service_tier: None,
-                    usage: Some(openai::CompletionUsage {
-                        // Anthropic provides full usage at MessageStart
-                        prompt_tokens: message.usage.input_tokens,
-                        completion_tokens: message.usage.output_tokens,
-                        total_tokens: message.usage.input_tokens
-                            + message.usage.output_tokens,
-                        prompt_tokens_details: None,
-                        completion_tokens_details: None,
-                    }),
-                }))
-            }
-            anthropic::StreamEvent::ContentBlockStart {
-                index,
-                content_block,
-            } => {
+    #[allow(deprecated)]
+    match value {
+        anthropic::StreamEvent::MessageStart { message } => {
+            let mut current_text_content = String::new();
+            let mut tool_calls = Vec::new();
+
+            for (idx, content_block) in message.content.iter().enumerate() {
                 match content_block {
+                    anthropic::ContentBlock::Text { text, .. } => {
+                        current_text_content.push_str(text);
+                    }
                     anthropic::ContentBlock::ToolUse { id, name, input } => {
-                        let tool_call_chunk =
+                        tool_calls.push(
                             openai::ChatCompletionMessageToolCallChunk {
-                                index: u32::try_from(index).unwrap_or(0),
-                                id: Some(id),
+                                index: u32::try_from(idx).unwrap_or(0),
+                                id: Some(id.clone()),
                                 r#type: Some(
                                     openai::ChatCompletionToolType::Function,
                                 ),
                                 function: Some(openai::FunctionCallStream {
-                                    name: Some(name),
+                                    name: Some(name.clone()),
                                     arguments: Some(
-                                        serde_json::to_string(&input)
+                                        serde_json::to_string(input)
                                             .map_err(MapperError::SerdeError)?,
                                     ),
                                 }),
-                            };
-                        let choice = openai::ChatChoiceStream {
-                            index: 0,
-                            delta: openai::ChatCompletionStreamResponseDelta {
-                                role: None,
-                                content: None,
-                                tool_calls: Some(vec![tool_call_chunk]),
-                                refusal: None,
-                                function_call: None,
-                            },
-                            finish_reason: None,
-                            logprobs: None,
-                        };
-                        Ok(Some(openai::CreateChatCompletionStreamResponse {
-                            id: PLACEHOLDER_STREAM_ID.to_string(), /* TODO: Use actual stream ID */
-                            choices: vec![choice],
-                            created: DEFAULT_CREATED_TIMESTAMP,
-                            model: PLACEHOLDER_MODEL_NAME.to_string(),
-                            object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
-                            system_fingerprint: None,
-                            service_tier: None,
-                            usage: None,
-                        }))
-                    }
-                    _ => Ok(None), // Text start, etc., content comes in delta
-                }
-            }
-            anthropic::StreamEvent::ContentBlockDelta { index, delta } => {
-                match delta {
-                    anthropic::ContentBlockDelta::TextDelta { text } => {
-                        let choice = openai::ChatChoiceStream {
-                            index: u32::try_from(index).unwrap_or(0),
-                            delta: openai::ChatCompletionStreamResponseDelta {
-                                role: None,
-                                content: Some(text),
-                                tool_calls: None,
-                                refusal: None,
-                                function_call: None,
                             },
-                            finish_reason: None,
-                            logprobs: None,
-                        };
-                        Ok(Some(openai::CreateChatCompletionStreamResponse {
-                            id: PLACEHOLDER_STREAM_ID.to_string(), /* TODO: Use actual stream ID */
-                            choices: vec![choice],
-                            created: DEFAULT_CREATED_TIMESTAMP, /* TODO: Use actual created timestamp */
-                            model: PLACEHOLDER_MODEL_NAME.to_string(), /* TODO: Use actual model name */
-                            object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
-                            system_fingerprint: None,
-                            service_tier: None,
-                            usage: None,
-                        }))
+                        );
                     }
-                    anthropic::ContentBlockDelta::InputJsonDelta {
-                        partial_json,
+                    anthropic::ContentBlock::ToolResult {
+                        tool_use_id: _,
+                        content,
                     } => {
-                        let tool_call_chunk =
-                            openai::ChatCompletionMessageToolCallChunk {
-                                index: u32::try_from(index).unwrap_or(0),
-                                id: None, /* ID would have been sent with ContentBlockStart for this tool */
-                                r#type: Some(
-                                    openai::ChatCompletionToolType::Function,
-                                ), // Assuming function
-                                function: Some(openai::FunctionCallStream {
-                                    name: None, /* Name would have been sent
-                                                 * with ContentBlockStart */
-                                    arguments: Some(partial_json),
-                                }),
-                            };
-                        let choice = openai::ChatChoiceStream {
-                            index: u32::try_from(index).unwrap_or(0),
-                            delta: openai::ChatCompletionStreamResponseDelta {
-                                role: None,
-                                content: None,
-                                tool_calls: Some(vec![tool_call_chunk]),
-                                refusal: None,
-                                function_call: None,
-                            },
-                            finish_reason: None,
-                            logprobs: None,
-                        };
-                        Ok(Some(openai::CreateChatCompletionStreamResponse {
-                            id: PLACEHOLDER_STREAM_ID.to_string(), /* TODO: Use actual stream ID */
-                            choices: vec![choice],
-                            created: DEFAULT_CREATED_TIMESTAMP, /* TODO: Use actual created timestamp */
-                            model: PLACEHOLDER_MODEL_NAME.to_string(), /* TODO: Use actual model name */
-                            object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
-                            system_fingerprint: None,
-                            service_tier: None,
-                            usage: None,
-                        }))
+                        current_text_content.push('\n');
+                        current_text_content.push_str(content);
                     }
-                    anthropic::ContentBlockDelta::ThinkingDelta { .. }
-                    | anthropic::ContentBlockDelta::SignatureDelta { .. } => {
-                        Ok(None)
-                    } // No direct OpenAI mapping for these deltas
+                    _ => {}
                 }
             }
-            anthropic::StreamEvent::ContentBlockStop { index: _ }
-            | anthropic::StreamEvent::MessageStop
-            | anthropic::StreamEvent::Ping => Ok(None), /* Usually no */
-            // separate OpenAI
-            // chunk for this
-            anthropic::StreamEvent::MessageDelta { delta, usage } => {
-                let finish_reason = match delta.stop_reason {
-                    Some(
-                        anthropic::StopReason::EndTurn
-                        | anthropic::StopReason::StopSequence,
-                    ) => Some(openai::FinishReason::Stop),
-                    Some(anthropic::StopReason::MaxTokens) => {
-                        Some(openai::FinishReason::Length)
-                    }
-                    Some(anthropic::StopReason::ToolUse) => {
-                        Some(openai::FinishReason::ToolCalls)
-                    }
-                    Some(anthropic::StopReason::Refusal) => {
-                        Some(openai::FinishReason::ContentFilter)
-                    }
-                    None => None,
-                };
-
-                let completion_usage = openai::CompletionUsage {
-                    prompt_tokens: usage.as_ref().map_or(0, |u| u.input_tokens),
-                    completion_tokens: usage
-                        .as_ref()
-                        .map_or(0, |u| u.output_tokens),
-                    total_tokens: usage
-                        .as_ref()
-                        .map_or(0, |u| u.input_tokens + u.output_tokens),
+
+            let finish_reason = finish_reason(message.stop_reason.as_ref());
+            let tool_calls = if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls)
+            };
+
+            let refusal_content = if matches!(
+                message.stop_reason,
+                Some(anthropic::StopReason::Refusal)
+            ) {
+                message.stop_sequence.clone() // stop_sequence is Option<String>
+            } else {
+                None
+            };
+
+            let choice = openai::ChatChoiceStream {
+                index: 0,
+                delta: openai::ChatCompletionStreamResponseDelta {
+                    role: Some(match message.role {
+                        anthropic::Role::User => openai::Role::User,
+                        anthropic::Role::Assistant => openai::Role::Assistant,
+                    }),
+                    content: Some(current_text_content),
+                    tool_calls,
+                    refusal: refusal_content,
+                    function_call: None,
+                },
+                finish_reason,
+                logprobs: None,
+            };
+            Ok(Some(openai::CreateChatCompletionStreamResponse {
+                id: message.id,
+                choices: vec![choice],
+                created: DEFAULT_CREATED_TIMESTAMP,
+                model: message.model,
+                object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+                system_fingerprint: None,
+                service_tier: None,
+                usage: Some(openai::CompletionUsage {
+                    // Anthropic provides full usage at MessageStart
+                    prompt_tokens: message.usage.input_tokens,
+                    completion_tokens: message.usage.output_tokens,
+                    total_tokens: message.usage.input_tokens
+                        + message.usage.output_tokens,
                     prompt_tokens_details: None,
                     completion_tokens_details: None,
-                };
-
-                let choice = openai::ChatChoiceStream {
-                    index: 0,
-                    delta: openai::ChatCompletionStreamResponseDelta {
-                        role: None,
-                        content: None,
-                        tool_calls: None,
-                        refusal: delta.stop_sequence, /* Or map to a specific
-                                                       * refusal field if
-                                                       * applicable */
-                        function_call: None,
-                    },
-                    finish_reason,
-                    logprobs: None,
-                };
-                Ok(Some(openai::CreateChatCompletionStreamResponse {
-                    id: PLACEHOLDER_STREAM_ID.to_string(), /* TODO: Use actual stream ID */
-                    choices: vec![choice],
-                    created: DEFAULT_CREATED_TIMESTAMP, /* TODO: Use actual created timestamp */
-                    model: PLACEHOLDER_MODEL_NAME.to_string(), /* TODO: Use actual model name */
-                    object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
-                    system_fingerprint: None,
-                    service_tier: None,
-                    usage: Some(completion_usage),
-                }))
+                }),
+            }))
+        }
+        anthropic::StreamEvent::ContentBlockStart {
+            index,
+            content_block,
+        } => {
+            match content_block {
+                anthropic::ContentBlock::ToolUse { id, name, input } => {
+                    // the input of streamed tool calls is always empty
+                    // and sent as json deltas instead, which clients
+                    // append to the arguments sent here
+                    let arguments = if input
+                        .as_object()
+                        .is_some_and(serde_json::Map::is_empty)
+                    {
+                        String::new()
+                    } else {
+                        serde_json::to_string(&input)
+                            .map_err(MapperError::SerdeError)?
+                    };
+                    // stream conversion is stateless, so tool calls are
+                    // indexed by their content block, which is also sent
+                    // with each of their json deltas, and reindexed by
+                    // `ChatCompletionStream`
+                    let tool_call_chunk =
+                        openai::ChatCompletionMessageToolCallChunk {
+                            index: u32::try_from(index).unwrap_or(0),
+                            id: Some(id),
+                            r#type: Some(
+                                openai::ChatCompletionToolType::Function,
+                            ),
+                            function: Some(openai::FunctionCallStream {
+                                name: Some(name),
+                                arguments: Some(arguments),
+                            }),
+                        };
+                    let choice = openai::ChatChoiceStream {
+                        index: 0,
+                        delta: openai::ChatCompletionStreamResponseDelta {
+                            role: None,
+                            content: None,
+                            tool_calls: Some(vec![tool_call_chunk]),
+                            refusal: None,
+                            function_call: None,
+                        },
+                        finish_reason: None,
+                        logprobs: None,
+                    };
+                    Ok(Some(openai::CreateChatCompletionStreamResponse {
+                        id: PLACEHOLDER_STREAM_ID.to_string(),
+                        choices: vec![choice],
+                        created: DEFAULT_CREATED_TIMESTAMP,
+                        model: PLACEHOLDER_MODEL_NAME.to_string(),
+                        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+                        system_fingerprint: None,
+                        service_tier: None,
+                        usage: None,
+                    }))
+                }
+                _ => Ok(None), // Text start, etc., content comes in delta
             }
-            anthropic::StreamEvent::Error { error } => {
-                tracing::warn!(error = ?error, "error in stream event");
-                Ok(None)
+        }
+        anthropic::StreamEvent::ContentBlockDelta { index, delta } => {
+            match delta {
+                anthropic::ContentBlockDelta::TextDelta { text } => {
+                    let choice = openai::ChatChoiceStream {
+                        index: 0,
+                        delta: openai::ChatCompletionStreamResponseDelta {
+                            role: None,
+                            content: Some(text),
+                            tool_calls: None,
+                            refusal: None,
+                            function_call: None,
+                        },
+                        finish_reason: None,
+                        logprobs: None,
+                    };
+                    Ok(Some(openai::CreateChatCompletionStreamResponse {
+                        id: PLACEHOLDER_STREAM_ID.to_string(),
+                        choices: vec![choice],
+                        created: DEFAULT_CREATED_TIMESTAMP,
+                        model: PLACEHOLDER_MODEL_NAME.to_string(),
+                        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+                        system_fingerprint: None,
+                        service_tier: None,
+                        usage: None,
+                    }))
+                }
+                anthropic::ContentBlockDelta::InputJsonDelta {
+                    partial_json,
+                } => {
+                    let tool_call_chunk =
+                        openai::ChatCompletionMessageToolCallChunk {
+                            index: u32::try_from(index).unwrap_or(0),
+                            id: None, /* ID would have been sent with
+                                       * ContentBlockStart for this tool */
+                            r#type: Some(
+                                openai::ChatCompletionToolType::Function,
+                            ), // Assuming function
+                            function: Some(openai::FunctionCallStream {
+                                name: None, /* Name would have been sent
+                                             * with ContentBlockStart */
+                                arguments: Some(partial_json),
+                            }),
+                        };
+                    let choice = openai::ChatChoiceStream {
+                        index: 0,
+                        delta: openai::ChatCompletionStreamResponseDelta {
+                            role: None,
+                            content: None,
+                            tool_calls: Some(vec![tool_call_chunk]),
+                            refusal: None,
+                            function_call: None,
+                        },
+                        finish_reason: None,
+                        logprobs: None,
+                    };
+                    Ok(Some(openai::CreateChatCompletionStreamResponse {
+                        id: PLACEHOLDER_STREAM_ID.to_string(),
+                        choices: vec![choice],
+                        created: DEFAULT_CREATED_TIMESTAMP,
+                        model: PLACEHOLDER_MODEL_NAME.to_string(),
+                        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+                        system_fingerprint: None,
+                        service_tier: None,
+                        usage: None,
+                    }))
+                }
+                anthropic::ContentBlockDelta::ThinkingDelta { .. }
+                | anthropic::ContentBlockDelta::SignatureDelta { .. } => {
+                    Ok(None)
+                } // No direct OpenAI mapping for these deltas
             }
         }
+        anthropic::StreamEvent::ContentBlockStop { index: _ }
+        | anthropic::StreamEvent::MessageStop
+        | anthropic::StreamEvent::Ping => Ok(None), /* Usually no */
+        // separate OpenAI
+        // chunk for this
+        anthropic::StreamEvent::MessageDelta { delta, usage } => {
+            let finish_reason = finish_reason(delta.stop_reason.as_ref());
+
+            let completion_usage = openai::CompletionUsage {
+                prompt_tokens: usage.as_ref().map_or(0, |u| u.input_tokens),
+                completion_tokens: usage
+                    .as_ref()
+                    .map_or(0, |u| u.output_tokens),
+                total_tokens: usage
+                    .as_ref()
+                    .map_or(0, |u| u.input_tokens + u.output_tokens),
+                prompt_tokens_details: None,
+                completion_tokens_details: None,
+            };
+
+            let choice = openai::ChatChoiceStream {
+                index: 0,
+                delta: openai::ChatCompletionStreamResponseDelta {
+                    role: None,
+                    content: None,
+                    tool_calls: None,
+                    refusal: None,
+                    function_call: None,
+                },
+                finish_reason,
+                logprobs: None,
+            };
+            Ok(Some(openai::CreateChatCompletionStreamResponse {
+                id: PLACEHOLDER_STREAM_ID.to_string(),
+                choices: vec![choice],
+                created: DEFAULT_CREATED_TIMESTAMP,
+                model: PLACEHOLDER_MODEL_NAME.to_string(),
+                object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+                system_fingerprint: None,
+                service_tier: None,
+                usage: Some(completion_usage),
+            }))
+        }
+        anthropic::StreamEvent::Error { error } => {
+            // sent as an error chunk by `ChatCompletionStream`
+            tracing::warn!(error = ?error, "error in stream event");
+            Ok(None)
+        }
     }
 }
 
@@ -809,3 +932,560 @@ impl
         Ok(error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anthropic_ai_sdk::types::message as anthropic;
+    use async_openai::types as openai;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        app::App,
+        config::Config,
+        middleware::mapper::openai::OpenAIConverter,
+        tests::TestDefault,
+        types::provider::{ProviderKeyMap, ProviderKeys},
+    };
+
+    async fn converters() -> (AnthropicConverter, OpenAIConverter) {
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+        let app =
+            App::with_provider_keys(Config::test_default(), provider_keys)
+                .await
+                .expect("failed to create app");
+        (
+            AnthropicConverter::new(ModelMapper::new(app.state.clone())),
+            OpenAIConverter::new(ModelMapper::new(app.state)),
+        )
+    }
+
+    fn tool_calling_request(
+        tool_choice: serde_json::Value,
+    ) -> serde_json::Value {
+        json!({
+            "model": "openai/gpt-4o-mini",
+            "tool_choice": tool_choice,
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Get the weather in a city",
+                    "parameters": {
+                        "type": "object",
+                        "properties": { "city": { "type": "string" } },
+                        "required": ["city"]
+                    }
+                }
+            }],
+            "messages": [
+                { "role": "user", "content": "Weather in Paris and Oslo?" },
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [
+                        {
+                            "id": "call_paris",
+                            "type": "function",
+                            "function": {
+                                "name": "get_weather",
+                                "arguments": "{\"city\":\"Paris\"}"
+                            }
+                        },
+                        {
+                            "id": "call_oslo",
+                            "type": "function",
+                            "function": {
+                                "name": "get_weather",
+                                "arguments": "{\"city\":\"Oslo\"}"
+                            }
+                        }
+                    ]
+                },
+                {
+                    "role": "tool",
+                    "tool_call_id": "call_paris",
+                    "content": "sunny"
+                },
+                {
+                    "role": "tool",
+                    "tool_call_id": "call_oslo",
+                    "content": "snowy"
+                },
+                { "role": "user", "content": "Thanks!" }
+            ]
+        })
+    }
+
+    #[tokio::test]
+    async fn tool_calling_requests_round_trip() {
+        let (anthropic_converter, openai_converter) = converters().await;
+        for (tool_choice, anthropic_tool_choice) in [
+            (json!("auto"), json!({ "type": "auto" })),
+            (json!("none"), json!({ "type": "none" })),
+            (json!("required"), json!({ "type": "any" })),
+            (
+                json!({
+                    "type": "function",
+                    "function": { "name": "get_weather" }
+                }),
+                json!({ "type": "tool", "name": "get_weather" }),
+            ),
+        ] {
+            let request: openai::CreateChatCompletionRequest =
+                serde_json::from_value(tool_calling_request(tool_choice))
+                    .unwrap();
+            let original = serde_json::to_value(&request).unwrap();
+
+            let mut mapped: anthropic::CreateMessageParams =
+                anthropic_converter.try_convert(request).unwrap();
+            let mapped_json = serde_json::to_value(&mapped).unwrap();
+            assert_eq!(mapped_json["tool_choice"], anthropic_tool_choice);
+            assert_eq!(
+                mapped_json["tools"][0]["input_schema"],
+                original["tools"][0]["function"]["parameters"]
+            );
+            let messages = mapped_json["messages"].as_array().unwrap();
+            assert_eq!(messages.len(), 4);
+            assert_eq!(
+                messages[1]["content"],
+                json!([
+                    {
+                        "type": "tool_use",
+                        "id": "call_paris",
+                        "name": "get_weather",
+                        "input": { "city": "Paris" }
+                    },
+                    {
+                        "type": "tool_use",
+                        "id": "call_oslo",
+                        "name": "get_weather",
+                        "input": { "city": "Oslo" }
+                    }
+                ])
+            );
+            // the results of parallel tool calls share a message
+            assert_eq!(messages[2]["role"], "user");
+            assert_eq!(
+                messages[2]["content"],
+                json!([
+                    {
+                        "type": "tool_result",
+                        "tool_use_id": "call_paris",
+                        "content": "sunny"
+                    },
+                    {
+                        "type": "tool_result",
+                        "tool_use_id": "call_oslo",
+                        "content": "snowy"
+                    }
+                ])
+            );
+
+            mapped.model = "anthropic/claude-3-5-haiku".to_string();
+            let round_tripped: openai::CreateChatCompletionRequest =
+                openai_converter.try_convert(mapped).unwrap();
+            let round_tripped = serde_json::to_value(&round_tripped).unwrap();
+            assert_eq!(round_tripped["tool_choice"], original["tool_choice"]);
+            assert_eq!(round_tripped["tools"], original["tools"]);
+            for idx in 1..=3 {
+                assert_eq!(
+                    round_tripped["messages"][idx], original["messages"][idx],
+                    "message {idx} should round trip"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_use_responses_round_trip() {
+        let (anthropic_converter, openai_converter) = converters().await;
+        let response: anthropic::CreateMessageResponse =
+            serde_json::from_value(json!({
+                "id": "msg_123",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-5-haiku-20241022",
+                "content": [
+                    { "type": "text", "text": "Let me check both cities." },
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_paris",
+                        "name": "get_weather",
+                        "input": { "city": "Paris" }
+                    },
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_oslo",
+                        "name": "get_weather",
+                        "input": { "city": "Oslo" }
+                    }
+                ],
+                "stop_reason": "tool_use",
+                "stop_sequence": null,
+                "usage": { "input_tokens": 10, "output_tokens": 20 }
+            }))
+            .unwrap();
+        let original = serde_json::to_value(&response).unwrap();
+
+        let mapped: openai::CreateChatCompletionResponse =
+            anthropic_converter.try_convert(response).unwrap();
+        let mapped_json = serde_json::to_value(&mapped).unwrap();
+        let choice = &mapped_json["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(choice["message"]["content"], "Let me check both cities.");
+        assert_eq!(
+            choice["message"]["tool_calls"],
+            json!([
+                {
+                    "id": "toolu_paris",
+                    "type": "function",
+                    "function": {
+                        "name": "get_weather",
+                        "arguments": "{\"city\":\"Paris\"}"
+                    }
+                },
+                {
+                    "id": "toolu_oslo",
+                    "type": "function",
+                    "function": {
+                        "name": "get_weather",
+                        "arguments": "{\"city\":\"Oslo\"}"
+                    }
+                }
+            ])
+        );
+
+        let round_tripped: anthropic::CreateMessageResponse =
+            openai_converter.try_convert(mapped).unwrap();
+        let round_tripped = serde_json::to_value(&round_tripped).unwrap();
+        assert_eq!(round_tripped["content"], original["content"]);
+        assert_eq!(round_tripped["stop_reason"], "tool_use");
+    }
+
+    #[tokio::test]
+    async fn streamed_tool_calls_are_translated_chunk_by_chunk() {
+        let (anthropic_converter, _) = converters().await;
+        let events = [
+            json!({
+                "type": "message_start",
+                "message": {
+                    "id": "msg_123",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "claude-3-5-haiku-20241022",
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": { "input_tokens": 10, "output_tokens": 1 }
+                }
+            }),
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": "Checking." }
+            }),
+            json!({ "type": "content_block_stop", "index": 0 }),
+            json!({
+                "type": "content_block_start",
+                "index": 1,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_paris",
+                    "name": "get_weather",
+                    "input": {}
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "{\"ci" }
+            }),
+            json!({
+                "type": "content_block_start",
+                "index": 2,
+                "content_block": {
+                    "type": "tool_use",
+                    "id": "toolu_oslo",
+                    "name": "get_weather",
+                    "input": {}
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": {
+                    "type": "input_json_delta",
+                    "partial_json": "ty\": \"Paris\"}"
+                }
+            }),
+            json!({
+                "type": "content_block_delta",
+                "index": 2,
+                "delta": {
+                    "type": "input_json_delta",
+                    "partial_json": "{\"city\": \"Oslo\"}"
+                }
+            }),
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "tool_use", "stop_sequence": null },
+                "usage": { "input_tokens": 10, "output_tokens": 30 }
+            }),
+            json!({ "type": "message_stop" }),
+        ];
+
+        // accumulate the tool calls the way openai clients do
+        let mut tool_calls: HashMap<u32, (String, String, String)> =
+            HashMap::new();
+        let mut finish_reason = None;
+        for event in events {
+            let event: anthropic::StreamEvent =
+                serde_json::from_value(event).unwrap();
+            let Some(chunk) =
+                anthropic_converter.try_convert_chunk(event).unwrap()
+            else {
+                continue;
+            };
+            let chunk = serde_json::to_value(&chunk).unwrap();
+            let choice = &chunk["choices"][0];
+            assert_eq!(choice["index"], 0);
+            if !choice["finish_reason"].is_null() {
+                finish_reason = Some(choice["finish_reason"].clone());
+            }
+            let Some(chunks) = choice["delta"]["tool_calls"].as_array() else {
+                continue;
+            };
+            for tool_call_chunk in chunks {
+                let index =
+                    u32::try_from(tool_call_chunk["index"].as_u64().unwrap())
+                        .unwrap();
+                let tool_call = tool_calls.entry(index).or_default();
+                let function = &tool_call_chunk["function"];
+                if let Some(id) = tool_call_chunk["id"].as_str() {
+                    tool_call.0.push_str(id);
+                }
+                if let Some(name) = function["name"].as_str() {
+                    tool_call.1.push_str(name);
+                }
+                if let Some(arguments) = function["arguments"].as_str() {
+                    tool_call.2.push_str(arguments);
+                }
+            }
+        }
+
+        assert_eq!(finish_reason, Some(json!("tool_calls")));
+        let mut tool_calls = tool_calls.into_values().collect::<Vec<_>>();
+        tool_calls.sort();
+        assert_eq!(tool_calls.len(), 2);
+        for ((id, name, arguments), city) in
+            tool_calls.iter().zip(["Oslo", "Paris"])
+        {
+            assert_eq!(id, &format!("toolu_{}", city.to_lowercase()));
+            assert_eq!(name, "get_weather");
+            let arguments: serde_json::Value =
+                serde_json::from_str(arguments).unwrap();
+            assert_eq!(arguments, json!({ "city": city }));
+        }
+    }
+
+    /// The 8 byte PNG signature.
+    const PNG: &str = "iVBORw0KGgo=";
+
+    fn image_request(url: &str) -> openai::CreateChatCompletionRequest {
+        serde_json::from_value(json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image_url", "image_url": { "url": url } }
+                ]
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn image_requests_round_trip() {
+        let (anthropic_converter, openai_converter) = converters().await;
+        for url in [
+            format!("data:image/png;base64,{PNG}"),
+            // the media type is sniffed when it's missing
+            format!("data:;base64,{PNG}"),
+        ] {
+            let mut mapped: anthropic::CreateMessageParams =
+                anthropic_converter
+                    .try_convert(image_request(&url))
+                    .unwrap();
+            let mapped_json = serde_json::to_value(&mapped).unwrap();
+            assert_eq!(
+                mapped_json["messages"][0]["content"][1],
+                json!({
+                    "type": "image",
+                    "source": {
+                        "type": "base64",
+                        "media_type": "image/png",
+                        "data": PNG
+                    }
+                })
+            );
+
+            mapped.model = "anthropic/claude-3-5-haiku".to_string();
+            let round_tripped: openai::CreateChatCompletionRequest =
+                openai_converter.try_convert(mapped).unwrap();
+            let round_tripped = serde_json::to_value(&round_tripped).unwrap();
+            assert_eq!(
+                round_tripped["messages"][0]["content"][1]["image_url"]["url"],
+                format!("data:image/png;base64,{PNG}")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unsupported_images_are_rejected() {
+        let (anthropic_converter, _) = converters().await;
+        for url in [
+            "https://example.com/cat.png".to_string(),
+            format!("data:image/png,{PNG}"),
+            format!("data:image/tiff;base64,{PNG}"),
+        ] {
+            let result = anthropic_converter.try_convert(image_request(&url));
+            assert!(
+                matches!(result, Err(MapperError::ImageMappingInvalid(_))),
+                "{url} should be rejected"
+            );
+        }
+
+        let oversized = "A".repeat((MAX_IMAGE_BYTES / 3 + 1) * 4);
+        let result = anthropic_converter.try_convert(image_request(&format!(
+            "data:image/png;base64,{oversized}"
+        )));
+        assert!(matches!(
+            result,
+            Err(MapperError::ImageTooLarge {
+                max: MAX_IMAGE_BYTES,
+                ..
+            })
+        ));
+    }
+
+    fn structured_output_request(
+        response_format: serde_json::Value,
+        stream: bool,
+    ) -> openai::CreateChatCompletionRequest {
+        serde_json::from_value(json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [{ "role": "user", "content": "Weather in Paris?" }],
+            "response_format": response_format,
+            "stream": stream
+        }))
+        .unwrap()
+    }
+
+    fn weather_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string" },
+                "temperature": { "type": "number" }
+            },
+            "required": ["city", "temperature"],
+            "additionalProperties": false
+        })
+    }
+
+    #[tokio::test]
+    async fn response_formats_are_mapped_to_a_forced_tool() {
+        let (anthropic_converter, _) = converters().await;
+        for (response_format, tool) in [
+            (
+                json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": "weather",
+                        "description": "The weather in a city",
+                        "schema": weather_schema(),
+                        "strict": true
+                    }
+                }),
+                json!({
+                    "name": STRUCTURED_OUTPUT_TOOL,
+                    "description": "The weather in a city",
+                    "input_schema": weather_schema()
+                }),
+            ),
+            (
+                json!({ "type": "json_object" }),
+                json!({
+                    "name": STRUCTURED_OUTPUT_TOOL,
+                    "description": "Respond with a JSON object.",
+                    "input_schema": { "type": "object" }
+                }),
+            ),
+        ] {
+            let mapped: anthropic::CreateMessageParams = anthropic_converter
+                .try_convert(structured_output_request(response_format, false))
+                .unwrap();
+            let mapped = serde_json::to_value(&mapped).unwrap();
+            assert_eq!(mapped["tools"], json!([tool]));
+            assert_eq!(
+                mapped["tool_choice"],
+                json!({ "type": "tool", "name": STRUCTURED_OUTPUT_TOOL })
+            );
+        }
+
+        let mapped: anthropic::CreateMessageParams = anthropic_converter
+            .try_convert(structured_output_request(
+                json!({ "type": "text" }),
+                false,
+            ))
+            .unwrap();
+        assert!(mapped.tools.is_none());
+        assert!(mapped.tool_choice.is_none());
+    }
+
+    #[tokio::test]
+    async fn streamed_structured_outputs_are_rejected() {
+        let (anthropic_converter, _) = converters().await;
+        let result = anthropic_converter.try_convert(
+            structured_output_request(json!({ "type": "json_object" }), true),
+        );
+        assert!(matches!(
+            result,
+            Err(MapperError::StructuredOutputStreaming(
+                InferenceProvider::Anthropic
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn structured_outputs_are_unwrapped_into_the_message_content() {
+        let (anthropic_converter, _) = converters().await;
+        let stub: serde_json::Value = serde_json::from_str(include_str!(
+            "../../../stubs/anthropic/messages_structured_output.json"
+        ))
+        .unwrap();
+        let response: anthropic::CreateMessageResponse =
+            serde_json::from_value(stub["response"]["jsonBody"].clone())
+                .unwrap();
+
+        let mapped: openai::CreateChatCompletionResponse =
+            anthropic_converter.try_convert(response).unwrap();
+        let mapped = serde_json::to_value(&mapped).unwrap();
+        let choice = &mapped["choices"][0];
+        assert_eq!(choice["finish_reason"], "stop");
+        assert!(choice["message"]["tool_calls"].is_null());
+        let content: serde_json::Value = serde_json::from_str(
+            choice["message"]["content"].as_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(content, json!({ "city": "Paris", "temperature": 21 }));
+    }
+}
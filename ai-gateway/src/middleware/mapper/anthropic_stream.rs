@@ -0,0 +1,366 @@
+//! Translates Anthropic message streams into `OpenAI` chat completion chunks.
+//!
+//! Events are converted one at a time by [`chat_completion_chunk`], but a
+//! spec-faithful chunk sequence needs state carried across events: every
+//! chunk has the id and model sent with `message_start`, usage is only sent in
+//! a final chunk without choices when the client asked for it with
+//! `stream_options.include_usage`, and the stream ends with `data: [DONE]`.
+//! Tool calls are also indexed from zero in the order they're streamed, while
+//! Anthropic indexes them by their content block, which counts text blocks
+//! too.
+//!
+//! Anthropic `error` events are sent as an `OpenAI` error chunk, the way
+//! `OpenAI` reports errors mid-stream, rather than truncating the stream.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anthropic_ai_sdk::types::message::{ContentBlock, StreamEvent};
+use async_openai::{
+    error::{ApiError as OpenAIApiError, WrappedError},
+    types::{
+        ChatCompletionStreamOptions, CompletionUsage,
+        CreateChatCompletionStreamResponse,
+    },
+};
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{anthropic::chat_completion_chunk, openai::SERVER_ERROR_TYPE};
+use crate::error::{api::ApiError, internal::InternalError};
+
+const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
+/// The data of the event terminating `OpenAI` streams.
+const DONE: &str = "[DONE]";
+
+#[derive(Deserialize)]
+struct StreamOptionsField {
+    #[serde(default)]
+    stream_options: Option<ChatCompletionStreamOptions>,
+}
+
+/// Whether an `OpenAI` chat completion request asked for usage to be sent at
+/// the end of the stream.
+pub(super) fn wants_usage(req_body: &[u8]) -> bool {
+    serde_json::from_slice::<StreamOptionsField>(req_body).is_ok_and(
+        |request| {
+            request
+                .stream_options
+                .is_some_and(|options| options.include_usage)
+        },
+    )
+}
+
+#[derive(Debug)]
+pub(super) struct ChatCompletionStream {
+    include_usage: bool,
+    id: String,
+    model: String,
+    /// Anthropic doesn't send when the message was created, so this is when
+    /// the stream started.
+    created: u32,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    /// The content block index of each tool call streamed so far, the
+    /// position of which is the tool call's `OpenAI` index.
+    tool_call_blocks: Vec<usize>,
+}
+
+impl ChatCompletionStream {
+    #[must_use]
+    pub(super) fn new(include_usage: bool) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                u32::try_from(elapsed.as_secs()).unwrap_or(u32::MAX)
+            });
+        Self {
+            include_usage,
+            id: String::new(),
+            model: String::new(),
+            created,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            tool_call_blocks: Vec::new(),
+        }
+    }
+
+    /// Translates the data of an Anthropic SSE event into the data of the
+    /// `OpenAI` SSE events it maps to, if any.
+    pub(super) fn translate(
+        &mut self,
+        data: &[u8],
+    ) -> Result<Vec<Bytes>, ApiError> {
+        let event: StreamEvent = serde_json::from_slice(data).map_err(|e| {
+            InternalError::Deserialize {
+                ty: "StreamEvent",
+                error: e,
+            }
+        })?;
+        match &event {
+            StreamEvent::MessageStart { message } => {
+                self.id.clone_from(&message.id);
+                self.model.clone_from(&message.model);
+            }
+            StreamEvent::ContentBlockStart {
+                index,
+                content_block: ContentBlock::ToolUse { .. },
+            } => {
+                self.tool_call_blocks.push(*index);
+            }
+            StreamEvent::Error { error } => {
+                tracing::warn!(error = ?error, "error in stream event");
+                return Ok(vec![error_chunk(error)?]);
+            }
+            _ => {}
+        }
+        let is_message_stop = matches!(event, StreamEvent::MessageStop);
+
+        let mut chunks = Vec::new();
+        if let Some(mut chunk) =
+            chat_completion_chunk(event).map_err(InternalError::MapperError)?
+        {
+            // Anthropic reports running totals, with input tokens at the
+            // start of the message and output tokens at its end
+            if let Some(usage) = chunk.usage.take() {
+                self.prompt_tokens =
+                    self.prompt_tokens.max(usage.prompt_tokens);
+                self.completion_tokens =
+                    self.completion_tokens.max(usage.completion_tokens);
+            }
+            self.reindex_tool_calls(&mut chunk);
+            chunks.push(self.serialize(chunk)?);
+        }
+        if is_message_stop {
+            if self.include_usage {
+                let chunk = CreateChatCompletionStreamResponse {
+                    id: String::new(),
+                    choices: Vec::new(),
+                    created: 0,
+                    model: String::new(),
+                    service_tier: None,
+                    system_fingerprint: None,
+                    object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+                    usage: Some(CompletionUsage {
+                        prompt_tokens: self.prompt_tokens,
+                        completion_tokens: self.completion_tokens,
+                        total_tokens: self.prompt_tokens
+                            + self.completion_tokens,
+                        prompt_tokens_details: None,
+                        completion_tokens_details: None,
+                    }),
+                };
+                chunks.push(self.serialize(chunk)?);
+            }
+            chunks.push(Bytes::from_static(DONE.as_bytes()));
+        }
+        Ok(chunks)
+    }
+
+    /// Replaces the content block index the tool calls of a chunk were
+    /// converted with by their position among the message's tool calls.
+    fn reindex_tool_calls(
+        &self,
+        chunk: &mut CreateChatCompletionStreamResponse,
+    ) {
+        let tool_calls = chunk
+            .choices
+            .iter_mut()
+            .filter_map(|choice| choice.delta.tool_calls.as_mut())
+            .flatten();
+        for tool_call in tool_calls {
+            if let Some(position) =
+                usize::try_from(tool_call.index).ok().and_then(|block| {
+                    self.tool_call_blocks.iter().position(|b| *b == block)
+                })
+            {
+                tool_call.index = u32::try_from(position).unwrap_or(u32::MAX);
+            }
+        }
+    }
+
+    fn serialize(
+        &self,
+        mut chunk: CreateChatCompletionStreamResponse,
+    ) -> Result<Bytes, InternalError> {
+        chunk.id.clone_from(&self.id);
+        chunk.model.clone_from(&self.model);
+        chunk.created = self.created;
+        let chunk = serde_json::to_vec(&chunk).map_err(|e| {
+            InternalError::Serialize {
+                ty: "CreateChatCompletionStreamResponse",
+                error: e,
+            }
+        })?;
+        Ok(Bytes::from(chunk))
+    }
+}
+
+/// Maps an Anthropic stream error, e.g. `overloaded_error`, to an `OpenAI`
+/// error chunk with the Anthropic error type as its code.
+fn error_chunk(error: &impl serde::Serialize) -> Result<Bytes, InternalError> {
+    let serialize_error = |e| InternalError::Serialize {
+        ty: "WrappedError",
+        error: e,
+    };
+    let error = serde_json::to_value(error).map_err(serialize_error)?;
+    let field = |name: &str| {
+        error
+            .get(name)
+            .and_then(Value::as_str)
+            .map(ToString::to_string)
+    };
+    let code = field("type");
+    let chunk = WrappedError {
+        error: OpenAIApiError {
+            message: field("message")
+                .or_else(|| code.clone())
+                .unwrap_or_else(|| SERVER_ERROR_TYPE.to_string()),
+            r#type: Some(SERVER_ERROR_TYPE.to_string()),
+            param: None,
+            code,
+        },
+    };
+    let chunk = serde_json::to_vec(&chunk).map_err(serialize_error)?;
+    Ok(Bytes::from(chunk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Translates an Anthropic SSE stream, returning the translated stream.
+    fn translate(input: &str, include_usage: bool) -> String {
+        let mut stream = ChatCompletionStream::new(include_usage);
+        stream.created = 1_750_000_000;
+        let mut output = String::new();
+        for data in input.lines().filter_map(|line| line.strip_prefix("data: "))
+        {
+            for chunk in stream.translate(data.as_bytes()).unwrap() {
+                output.push_str("data: ");
+                output.push_str(std::str::from_utf8(&chunk).unwrap());
+                output.push_str("\n\n");
+            }
+        }
+        output
+    }
+
+    /// Nulls are insignificant in chunks, so they're removed before
+    /// comparing.
+    fn without_nulls(value: Value) -> Value {
+        match value {
+            Value::Object(object) => object
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+            Value::Array(array) => {
+                array.into_iter().map(without_nulls).collect()
+            }
+            value => value,
+        }
+    }
+
+    /// Compares a translated stream with its golden file event by event.
+    fn assert_golden(translated: &str, golden: &str) {
+        let events = |stream: &str| {
+            stream
+                .lines()
+                .filter_map(|line| line.strip_prefix("data: "))
+                .map(|data| {
+                    if data == DONE {
+                        Value::String(DONE.to_string())
+                    } else {
+                        without_nulls(serde_json::from_str(data).unwrap())
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let (translated, golden) = (events(translated), events(golden));
+        assert_eq!(translated.len(), golden.len(), "{translated:#?}");
+        for (translated, golden) in translated.iter().zip(&golden) {
+            assert_eq!(translated, golden);
+        }
+    }
+
+    #[test]
+    fn text_stream_with_usage() {
+        let translated = translate(
+            include_str!("../../../tests/golden/anthropic_stream/text.sse"),
+            true,
+        );
+        assert_golden(
+            &translated,
+            include_str!("../../../tests/golden/anthropic_stream/text.golden"),
+        );
+    }
+
+    #[test]
+    fn max_tokens_stream() {
+        let translated = translate(
+            include_str!(
+                "../../../tests/golden/anthropic_stream/max_tokens.sse"
+            ),
+            false,
+        );
+        assert_golden(
+            &translated,
+            include_str!(
+                "../../../tests/golden/anthropic_stream/max_tokens.golden"
+            ),
+        );
+    }
+
+    #[test]
+    fn tool_use_stream() {
+        let translated = translate(
+            include_str!("../../../tests/golden/anthropic_stream/tool_use.sse"),
+            true,
+        );
+        assert_golden(
+            &translated,
+            include_str!(
+                "../../../tests/golden/anthropic_stream/tool_use.golden"
+            ),
+        );
+    }
+
+    #[test]
+    fn parallel_tool_use_stream() {
+        let translated = translate(
+            include_str!(
+                "../../../tests/golden/anthropic_stream/parallel_tool_use.sse"
+            ),
+            false,
+        );
+        assert_golden(
+            &translated,
+            include_str!(
+                "../../../tests/golden/anthropic_stream/parallel_tool_use.\
+                 golden"
+            ),
+        );
+    }
+
+    #[test]
+    fn error_mid_stream() {
+        let translated = translate(
+            include_str!("../../../tests/golden/anthropic_stream/error.sse"),
+            false,
+        );
+        assert_golden(
+            &translated,
+            include_str!("../../../tests/golden/anthropic_stream/error.golden"),
+        );
+    }
+
+    #[test]
+    fn usage_is_only_sent_when_requested() {
+        assert!(wants_usage(
+            br#"{"stream": true, "stream_options": {"include_usage": true}}"#
+        ));
+        assert!(!wants_usage(
+            br#"{"stream": true, "stream_options": {"include_usage": false}}"#
+        ));
+        assert!(!wants_usage(br#"{"stream": true}"#));
+    }
+}
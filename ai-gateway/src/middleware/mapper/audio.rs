@@ -0,0 +1,67 @@
+//! Transcription requests are `multipart/form-data` uploads rather than JSON,
+//! so only the `model` field is mapped and the audio is forwarded untouched.
+use std::str::FromStr;
+
+use bytes::Bytes;
+use http::response::Parts;
+
+use super::{EndpointConverter, model::ModelMapper};
+use crate::{
+    error::{api::ApiError, internal::InternalError, mapper::MapperError},
+    types::{
+        extensions::MapperContext, model_id::ModelId, multipart,
+        provider::InferenceProvider,
+    },
+};
+
+/// Passes transcription requests through to OpenAI, only mapping the model.
+pub struct TranscriptionConverter {
+    model_mapper: ModelMapper,
+}
+
+impl TranscriptionConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+}
+
+impl EndpointConverter for TranscriptionConverter {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        let boundary = multipart::boundary_from_body(&req_body_bytes)
+            .ok_or(InternalError::MapperError(MapperError::InvalidRequest))?;
+        let source_model = multipart::field(&req_body_bytes, boundary, "model")
+            .ok_or(InternalError::MapperError(MapperError::InvalidRequest))?;
+        let source_model = ModelId::from_str(&source_model)
+            .map_err(InternalError::MapperError)?;
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::OpenAI)
+            .map_err(InternalError::MapperError)?;
+        let body = multipart::replace_field(
+            &req_body_bytes,
+            boundary,
+            "model",
+            &target_model.to_string(),
+        )
+        .ok_or(InternalError::MapperError(MapperError::InvalidRequest))?;
+
+        let mapper_ctx = MapperContext {
+            is_stream: false,
+            model: Some(target_model),
+        };
+        Ok((body, mapper_ctx))
+    }
+
+    fn convert_resp_body(
+        &self,
+        _resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        _is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        Ok(Some(resp_body_bytes))
+    }
+}
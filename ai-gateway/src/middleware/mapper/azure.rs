@@ -0,0 +1,108 @@
+use std::str::FromStr;
+
+use http::response::Parts;
+
+use super::{TryConvertStreamData, model::ModelMapper};
+use crate::{
+    error::mapper::MapperError,
+    middleware::mapper::{TryConvert, TryConvertError},
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+pub struct AzureConverter {
+    model_mapper: ModelMapper,
+}
+
+impl AzureConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+}
+
+impl
+    TryConvert<
+        async_openai::types::CreateChatCompletionRequest,
+        async_openai::types::CreateChatCompletionRequest,
+    > for AzureConverter
+{
+    type Error = MapperError;
+    fn try_convert(
+        &self,
+        mut value: async_openai::types::CreateChatCompletionRequest,
+    ) -> Result<async_openai::types::CreateChatCompletionRequest, Self::Error>
+    {
+        let source_model = match ModelId::from_str(&value.model)? {
+            // Azure serves OpenAI's models under the same names
+            ModelId::ModelIdWithVersion {
+                provider: InferenceProvider::OpenAI,
+                id,
+            } => ModelId::ModelIdWithVersion {
+                provider: InferenceProvider::AzureOpenAI,
+                id,
+            },
+            source_model => source_model,
+        };
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::AzureOpenAI)?;
+        // the deployment is part of the request path, which is built from the
+        // model in the request body
+        let deployment = self.model_mapper.azure_deployment(&target_model);
+        tracing::trace!(source_model = ?source_model, target_model = ?target_model, deployment = %deployment, "mapped model");
+        value.model = deployment;
+        Ok(value)
+    }
+}
+
+impl
+    TryConvert<
+        async_openai::types::CreateChatCompletionResponse,
+        async_openai::types::CreateChatCompletionResponse,
+    > for AzureConverter
+{
+    type Error = MapperError;
+    fn try_convert(
+        &self,
+        value: async_openai::types::CreateChatCompletionResponse,
+    ) -> Result<async_openai::types::CreateChatCompletionResponse, Self::Error>
+    {
+        Ok(value)
+    }
+}
+
+impl
+    TryConvertStreamData<
+        async_openai::types::CreateChatCompletionStreamResponse,
+        async_openai::types::CreateChatCompletionStreamResponse,
+    > for AzureConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: async_openai::types::CreateChatCompletionStreamResponse,
+    ) -> Result<
+        Option<async_openai::types::CreateChatCompletionStreamResponse>,
+        Self::Error,
+    > {
+        Ok(Some(value))
+    }
+}
+
+impl
+    TryConvertError<
+        async_openai::error::WrappedError,
+        async_openai::error::WrappedError,
+    > for AzureConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_error(
+        &self,
+        _resp_parts: &Parts,
+        value: async_openai::error::WrappedError,
+    ) -> Result<async_openai::error::WrappedError, Self::Error> {
+        Ok(value)
+    }
+}
@@ -10,6 +10,7 @@ use super::{
     MapperError, TryConvert, TryConvertStreamData, model::ModelMapper,
 };
 use crate::{
+    endpoints::bedrock::converse::ConverseStreamEvent,
     middleware::mapper::{DEFAULT_MAX_TOKENS, TryConvertError},
     types::{model_id::ModelId, provider::InferenceProvider},
 };
@@ -448,194 +449,119 @@ impl
 
 impl
     TryConvertStreamData<
-        aws_sdk_bedrockruntime::types::ConverseStreamOutput,
+        ConverseStreamEvent,
         CreateChatCompletionStreamResponse,
     > for BedrockConverter
 {
     type Error = MapperError;
 
-    #[allow(clippy::too_many_lines)]
     fn try_convert_chunk(
         &self,
-        value: aws_sdk_bedrockruntime::types::ConverseStreamOutput,
-    ) -> Result<
-        std::option::Option<CreateChatCompletionStreamResponse>,
-        Self::Error,
-    > {
+        value: ConverseStreamEvent,
+    ) -> Result<Option<CreateChatCompletionStreamResponse>, Self::Error> {
         use async_openai::types as openai;
-        use aws_sdk_bedrockruntime::types as bedrock;
         const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
-        // TODO: These placeholder values for id, model, and created should be
-        // replaced by actual values from the MessageStart event,
-        // propagated by the stream handling logic.
+        // Bedrock doesn't send an id, model or timestamp in the stream
         const PLACEHOLDER_STREAM_ID: &str = "bedrock-stream-id";
         const PLACEHOLDER_MODEL_NAME: &str = "bedrock-model";
         const DEFAULT_CREATED_TIMESTAMP: u32 = 0;
 
-        #[allow(deprecated)]
-        let mut choices = Vec::new();
-        let mut completion_usage: openai::CompletionUsage =
-            openai::CompletionUsage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-                total_tokens: 0,
-                prompt_tokens_details: None,
-                completion_tokens_details: None,
-            };
+        let mut delta = openai::ChatCompletionStreamResponseDelta {
+            role: None,
+            content: None,
+            tool_calls: None,
+            refusal: None,
+            #[allow(deprecated)]
+            function_call: None,
+        };
+        let mut finish_reason = None;
+        let mut usage = None;
         match value {
-            bedrock::ConverseStreamOutput::MessageStart(message) => {
-                let choice = openai::ChatChoiceStream {
-                    index: 0,
-                    delta: openai::ChatCompletionStreamResponseDelta {
-                        role: Some(match message.role {
-                            bedrock::ConversationRole::Assistant => {
-                                openai::Role::Assistant
-                            }
-                            bedrock::ConversationRole::User => {
-                                openai::Role::User
-                            }
-                            _ => openai::Role::System,
-                        }),
-                        content: None,
-                        tool_calls: None,
-                        refusal: None,
-                        #[allow(deprecated)]
-                        function_call: None,
-                    },
-                    finish_reason: None,
-                    logprobs: None,
+            ConverseStreamEvent::MessageStart(_) => {
+                delta.role = Some(openai::Role::Assistant);
+            }
+            ConverseStreamEvent::ContentBlockStart(event) => {
+                let Some(tool_use) = event.start.tool_use else {
+                    return Ok(None);
                 };
-
-                choices.push(choice);
+                delta.tool_calls =
+                    Some(vec![openai::ChatCompletionMessageToolCallChunk {
+                        index: event.content_block_index,
+                        id: Some(tool_use.tool_use_id),
+                        r#type: Some(openai::ChatCompletionToolType::Function),
+                        function: Some(openai::FunctionCallStream {
+                            name: Some(tool_use.name),
+                            arguments: Some(String::new()),
+                        }),
+                    }]);
             }
-            bedrock::ConverseStreamOutput::ContentBlockStart(
-                content_block_start,
-            ) => {
-                if let Some(bedrock::ContentBlockStart::ToolUse(tool_use)) =
-                    content_block_start.start
-                {
-                    let tool_call_chunk =
+            ConverseStreamEvent::ContentBlockDelta(event) => {
+                if let Some(text) = event.delta.text {
+                    delta.content = Some(text);
+                } else if let Some(tool_use) = event.delta.tool_use {
+                    // the id and name were sent with the block start
+                    delta.tool_calls = Some(vec![
                         openai::ChatCompletionMessageToolCallChunk {
-                            index: content_block_start
-                                .content_block_index
-                                .try_into()
-                                .unwrap_or(0),
-                            id: Some(tool_use.tool_use_id),
+                            index: event.content_block_index,
+                            id: None,
                             r#type: Some(
                                 openai::ChatCompletionToolType::Function,
                             ),
                             function: Some(openai::FunctionCallStream {
-                                name: Some(tool_use.name),
-                                arguments: Some(String::new()),
+                                name: None,
+                                arguments: Some(tool_use.input),
                             }),
-                        };
-                    let choice = openai::ChatChoiceStream {
-                        index: 0,
-                        delta: openai::ChatCompletionStreamResponseDelta {
-                            role: None,
-                            content: None,
-                            tool_calls: Some(vec![tool_call_chunk]),
-                            refusal: None,
-                            #[allow(deprecated)]
-                            function_call: None,
                         },
-                        finish_reason: None,
-                        logprobs: None,
-                    };
-
-                    choices.push(choice);
+                    ]);
+                } else {
+                    return Ok(None);
                 }
             }
-            bedrock::ConverseStreamOutput::ContentBlockDelta(
-                content_block_delta_event,
-            ) => {
-                match content_block_delta_event.delta {
-                    Some(bedrock::ContentBlockDelta::Text(text)) => {
-                        let choice = openai::ChatChoiceStream {
-                            index: u32::try_from(
-                                content_block_delta_event.content_block_index,
-                            )
-                            .unwrap_or(0),
-                            delta: openai::ChatCompletionStreamResponseDelta {
-                                role: None,
-                                content: Some(text),
-                                tool_calls: None,
-                                refusal: None,
-                                #[allow(deprecated)]
-                                function_call: None,
-                            },
-                            finish_reason: None,
-                            logprobs: None,
-                        };
-                        choices.push(choice);
-                    }
-                    Some(bedrock::ContentBlockDelta::ToolUse(tool_use)) => {
-                        let tool_call_chunk =
-                            openai::ChatCompletionMessageToolCallChunk {
-                                index: u32::try_from(
-                                    content_block_delta_event
-                                        .content_block_index,
-                                )
-                                .unwrap_or(0),
-                                id: None, /* ID would have been sent with ContentBlockStart for this tool */
-                                r#type: Some(
-                                    openai::ChatCompletionToolType::Function,
-                                ), // Assuming function
-                                function: Some(openai::FunctionCallStream {
-                                    name: None, /* Name would have been sent
-                                                 * with ContentBlockStart */
-                                    arguments: Some(tool_use.input),
-                                }),
-                            };
-                        let choice = openai::ChatChoiceStream {
-                            index: 0,
-                            delta: openai::ChatCompletionStreamResponseDelta {
-                                role: None,
-                                content: None,
-                                tool_calls: Some(vec![tool_call_chunk]),
-                                refusal: None,
-                                #[allow(deprecated)]
-                                function_call: None,
-                            },
-                            finish_reason: None,
-                            logprobs: None,
-                        };
-
-                        choices.push(choice);
+            ConverseStreamEvent::ContentBlockStop(_) => return Ok(None),
+            ConverseStreamEvent::MessageStop(event) => {
+                finish_reason = Some(match event.stop_reason.as_str() {
+                    "max_tokens" => openai::FinishReason::Length,
+                    "tool_use" => openai::FinishReason::ToolCalls,
+                    "guardrail_intervened" | "content_filtered" => {
+                        openai::FinishReason::ContentFilter
                     }
-                    _ => {}
-                }
+                    _ => openai::FinishReason::Stop,
+                });
             }
-
-            bedrock::ConverseStreamOutput::Metadata(metadata) => {
-                if let Some(usage) = metadata.usage {
-                    completion_usage.prompt_tokens =
-                        u32::try_from(usage.input_tokens).unwrap_or(0);
-                    completion_usage.completion_tokens =
-                        u32::try_from(usage.output_tokens).unwrap_or(0);
-                    completion_usage.total_tokens =
-                        u32::try_from(usage.total_tokens).unwrap_or(0);
-                }
+            ConverseStreamEvent::Metadata(event) => {
+                let Some(token_usage) = event.usage else {
+                    return Ok(None);
+                };
+                usage = Some(openai::CompletionUsage {
+                    prompt_tokens: token_usage.input_tokens,
+                    completion_tokens: token_usage.output_tokens,
+                    total_tokens: token_usage.total_tokens,
+                    prompt_tokens_details: None,
+                    completion_tokens_details: None,
+                });
             }
-            bedrock::ConverseStreamOutput::ContentBlockStop(_)
-            | bedrock::ConverseStreamOutput::MessageStop(_)
-            | _ => {}
         }
 
+        // usage is sent in a final chunk without choices, as OpenAI does
+        let choices = if usage.is_some() {
+            Vec::new()
+        } else {
+            vec![openai::ChatChoiceStream {
+                index: 0,
+                delta,
+                finish_reason,
+                logprobs: None,
+            }]
+        };
         Ok(Some(CreateChatCompletionStreamResponse {
-            id: PLACEHOLDER_STREAM_ID.to_string(), /* TODO: Use actual
-                                                    * stream
-                                                    * ID */
+            id: PLACEHOLDER_STREAM_ID.to_string(),
             choices,
-            created: DEFAULT_CREATED_TIMESTAMP, /* TODO: Use actual
-                                                 * created
-                                                 * timestamp */
-            model: PLACEHOLDER_MODEL_NAME.to_string(), /* TODO: Use actual
-                                                        * model name */
+            created: DEFAULT_CREATED_TIMESTAMP,
+            model: PLACEHOLDER_MODEL_NAME.to_string(),
             object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
             system_fingerprint: None,
             service_tier: None,
-            usage: Some(completion_usage),
+            usage,
         }))
     }
 }
@@ -0,0 +1,404 @@
+use std::str::FromStr;
+
+use async_openai::types::{
+    CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+    CreateEmbeddingRequest, CreateEmbeddingResponse,
+};
+use http::response::Parts;
+
+use super::{TryConvert, TryConvertStreamData};
+use crate::{
+    endpoints::cohere::{
+        CohereError,
+        chat::{
+            BilledUnits, ChatMessage, ChatRequest, ChatResponse, ChatRole,
+            ChatStreamEvent, ContentBlock, FinishReason, MessageContent, Usage,
+        },
+        embed::{EmbedRequest, EmbedResponse},
+    },
+    error::mapper::MapperError,
+    middleware::mapper::{TryConvertError, model::ModelMapper},
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+const CHAT_COMPLETION_OBJECT: &str = "chat.completion";
+const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
+const PLACEHOLDER_STREAM_ID: &str = "cohere-stream-id";
+
+pub struct CohereConverter {
+    model_mapper: ModelMapper,
+}
+
+impl CohereConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+
+    fn map_model(&self, model: &str) -> Result<String, MapperError> {
+        let source_model = ModelId::from_str(model)?;
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::Cohere)?;
+        tracing::trace!(source_model = ?source_model, target_model = ?target_model, "mapped model");
+        Ok(target_model.to_string())
+    }
+}
+
+impl TryConvert<async_openai::types::CreateChatCompletionRequest, ChatRequest>
+    for CohereConverter
+{
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: async_openai::types::CreateChatCompletionRequest,
+    ) -> Result<ChatRequest, Self::Error> {
+        use async_openai::types as openai;
+        let model = self.map_model(&value.model)?;
+
+        let mut messages = Vec::with_capacity(value.messages.len());
+        for message in value.messages {
+            let (role, content) = match message {
+                openai::ChatCompletionRequestMessage::Developer(message) => {
+                    let content = match message.content {
+                        openai::ChatCompletionRequestDeveloperMessageContent::Text(text) => text,
+                        openai::ChatCompletionRequestDeveloperMessageContent::Array(parts) => parts
+                            .into_iter()
+                            .map(|part| part.text)
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    };
+                    (ChatRole::System, content)
+                }
+                openai::ChatCompletionRequestMessage::System(message) => {
+                    let content = match message.content {
+                        openai::ChatCompletionRequestSystemMessageContent::Text(text) => text,
+                        openai::ChatCompletionRequestSystemMessageContent::Array(parts) => parts
+                            .into_iter()
+                            .map(|part| match part {
+                                openai::ChatCompletionRequestSystemMessageContentPart::Text(text) => text.text,
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    };
+                    (ChatRole::System, content)
+                }
+                // tools aren't mapped, so neither are their results
+                openai::ChatCompletionRequestMessage::Tool(_)
+                | openai::ChatCompletionRequestMessage::Function(_) => {
+                    continue;
+                }
+                openai::ChatCompletionRequestMessage::User(message) => {
+                    let content = match message.content {
+                        openai::ChatCompletionRequestUserMessageContent::Text(
+                            text,
+                        ) => text,
+                        openai::ChatCompletionRequestUserMessageContent::Array(
+                            parts,
+                        ) => parts
+                            .into_iter()
+                            .filter_map(|part| match part {
+                                openai::ChatCompletionRequestUserMessageContentPart::Text(text) => Some(text.text),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    };
+                    (ChatRole::User, content)
+                }
+                openai::ChatCompletionRequestMessage::Assistant(message) => {
+                    let content = match message.content {
+                        Some(openai::ChatCompletionRequestAssistantMessageContent::Text(text)) => text,
+                        Some(openai::ChatCompletionRequestAssistantMessageContent::Array(parts)) => parts
+                            .into_iter()
+                            .map(|part| match part {
+                                openai::ChatCompletionRequestAssistantMessageContentPart::Text(text) => text.text,
+                                openai::ChatCompletionRequestAssistantMessageContentPart::Refusal(refusal) => refusal.refusal,
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        None => continue,
+                    };
+                    (ChatRole::Assistant, content)
+                }
+            };
+            messages.push(ChatMessage {
+                role,
+                content: MessageContent::Text(content),
+            });
+        }
+        if messages.is_empty() {
+            return Err(MapperError::InvalidRequest);
+        }
+
+        let stop_sequences = match value.stop {
+            Some(openai::Stop::String(stop)) => vec![stop],
+            Some(openai::Stop::StringArray(stops)) => stops,
+            None => Vec::new(),
+        };
+        #[allow(deprecated)]
+        let max_tokens = value.max_completion_tokens.or(value.max_tokens);
+
+        Ok(ChatRequest {
+            model,
+            messages,
+            documents: Vec::new(),
+            stream: value.stream,
+            temperature: value.temperature,
+            max_tokens,
+            p: value.top_p,
+            seed: value.seed,
+            stop_sequences,
+            frequency_penalty: value.frequency_penalty,
+            presence_penalty: value.presence_penalty,
+        })
+    }
+}
+
+impl TryConvert<ChatResponse, CreateChatCompletionResponse>
+    for CohereConverter
+{
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: ChatResponse,
+    ) -> Result<CreateChatCompletionResponse, Self::Error> {
+        use async_openai::types as openai;
+        let usage = value.usage.and_then(Usage::counted).map(completion_usage);
+        // citations have no OpenAI equivalent, so only the text is kept
+        let content = value
+            .message
+            .content
+            .into_iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text),
+                ContentBlock::Other => None,
+            })
+            .collect::<String>();
+
+        #[allow(deprecated)]
+        let message = openai::ChatCompletionResponseMessage {
+            content: Some(content),
+            refusal: None,
+            tool_calls: None,
+            role: openai::Role::Assistant,
+            function_call: None,
+            audio: None,
+        };
+        let choice = openai::ChatChoice {
+            index: 0,
+            message,
+            finish_reason: value.finish_reason.map(finish_reason),
+            logprobs: None,
+        };
+
+        Ok(CreateChatCompletionResponse {
+            choices: vec![choice],
+            id: value.id,
+            created: 0,
+            // cohere doesn't echo the model in the response
+            model: String::new(),
+            object: CHAT_COMPLETION_OBJECT.to_string(),
+            usage,
+            service_tier: None,
+            system_fingerprint: None,
+        })
+    }
+}
+
+impl TryConvertStreamData<ChatStreamEvent, CreateChatCompletionStreamResponse>
+    for CohereConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: ChatStreamEvent,
+    ) -> Result<Option<CreateChatCompletionStreamResponse>, Self::Error> {
+        use async_openai::types as openai;
+        let (id, delta, finish, usage) = match value {
+            ChatStreamEvent::MessageStart { id } => (
+                id.unwrap_or_else(|| PLACEHOLDER_STREAM_ID.to_string()),
+                openai::ChatCompletionStreamResponseDelta {
+                    role: Some(openai::Role::Assistant),
+                    content: None,
+                    tool_calls: None,
+                    refusal: None,
+                    #[allow(deprecated)]
+                    function_call: None,
+                },
+                None,
+                None,
+            ),
+            ChatStreamEvent::ContentDelta { delta } => (
+                PLACEHOLDER_STREAM_ID.to_string(),
+                openai::ChatCompletionStreamResponseDelta {
+                    role: None,
+                    content: Some(delta.message.content.text),
+                    tool_calls: None,
+                    refusal: None,
+                    #[allow(deprecated)]
+                    function_call: None,
+                },
+                None,
+                None,
+            ),
+            ChatStreamEvent::MessageEnd { delta } => (
+                PLACEHOLDER_STREAM_ID.to_string(),
+                openai::ChatCompletionStreamResponseDelta {
+                    role: None,
+                    content: None,
+                    tool_calls: None,
+                    refusal: None,
+                    #[allow(deprecated)]
+                    function_call: None,
+                },
+                delta.finish_reason.map(finish_reason),
+                delta.usage.and_then(Usage::counted).map(completion_usage),
+            ),
+            ChatStreamEvent::Other => return Ok(None),
+        };
+
+        let choice = openai::ChatChoiceStream {
+            index: 0,
+            delta,
+            finish_reason: finish,
+            logprobs: None,
+        };
+        Ok(Some(CreateChatCompletionStreamResponse {
+            id,
+            choices: vec![choice],
+            created: 0,
+            model: String::new(),
+            object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+            system_fingerprint: None,
+            service_tier: None,
+            usage,
+        }))
+    }
+}
+
+impl TryConvert<CreateEmbeddingRequest, EmbedRequest> for CohereConverter {
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: CreateEmbeddingRequest,
+    ) -> Result<EmbedRequest, Self::Error> {
+        use async_openai::types as openai;
+        let model = self.map_model(&value.model)?;
+        let texts = match value.input {
+            openai::EmbeddingInput::String(text) => vec![text],
+            openai::EmbeddingInput::StringArray(texts) => texts,
+            // token ids are specific to OpenAI's tokenizer
+            openai::EmbeddingInput::IntegerArray(_)
+            | openai::EmbeddingInput::ArrayOfIntegerArray(_) => {
+                return Err(MapperError::InvalidRequest);
+            }
+        };
+        Ok(EmbedRequest {
+            model,
+            texts,
+            output_dimension: value.dimensions,
+            ..Default::default()
+        })
+    }
+}
+
+impl TryConvert<EmbedResponse, CreateEmbeddingResponse> for CohereConverter {
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: EmbedResponse,
+    ) -> Result<CreateEmbeddingResponse, Self::Error> {
+        use async_openai::types as openai;
+        let prompt_tokens = value
+            .meta
+            .and_then(|meta| meta.billed_units)
+            .and_then(|units| units.input_tokens)
+            .map_or(0, tokens);
+        let data = value
+            .embeddings
+            .float
+            .into_iter()
+            .zip(0..)
+            .map(|(embedding, index)| openai::Embedding {
+                index,
+                object: "embedding".to_string(),
+                embedding,
+            })
+            .collect();
+        Ok(CreateEmbeddingResponse {
+            object: "list".to_string(),
+            model: String::new(),
+            data,
+            usage: openai::EmbeddingUsage {
+                prompt_tokens,
+                total_tokens: prompt_tokens,
+            },
+        })
+    }
+}
+
+// embeddings are never streamed, so this is never called
+impl TryConvertStreamData<EmbedResponse, CreateEmbeddingResponse>
+    for CohereConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: EmbedResponse,
+    ) -> Result<Option<CreateEmbeddingResponse>, Self::Error> {
+        self.try_convert(value).map(Some)
+    }
+}
+
+impl TryConvertError<CohereError, async_openai::error::WrappedError>
+    for CohereConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_error(
+        &self,
+        resp_parts: &Parts,
+        value: CohereError,
+    ) -> Result<async_openai::error::WrappedError, Self::Error> {
+        let message = (!value.message.is_empty()).then_some(value.message);
+        Ok(super::openai_error_from_status(resp_parts.status, message))
+    }
+}
+
+fn finish_reason(reason: FinishReason) -> async_openai::types::FinishReason {
+    use async_openai::types as openai;
+    match reason {
+        FinishReason::Complete
+        | FinishReason::StopSequence
+        | FinishReason::Error => openai::FinishReason::Stop,
+        FinishReason::MaxTokens => openai::FinishReason::Length,
+        FinishReason::ToolCall => openai::FinishReason::ToolCalls,
+    }
+}
+
+fn completion_usage(
+    units: BilledUnits,
+) -> async_openai::types::CompletionUsage {
+    let prompt_tokens = units.input_tokens.map_or(0, tokens);
+    let completion_tokens = units.output_tokens.map_or(0, tokens);
+    async_openai::types::CompletionUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        prompt_tokens_details: None,
+        completion_tokens_details: None,
+    }
+}
+
+/// Cohere reports billed units as floats, though they are always whole.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn tokens(units: f64) -> u32 {
+    units.max(0.0) as u32
+}
@@ -0,0 +1,169 @@
+//! Handles the differences between DeepSeek's chat completions API and
+//! OpenAI's.
+//!
+//! DeepSeek is otherwise OpenAI compatible, so this wraps the OpenAI
+//! compatible converter. Reasoning models return their chain of thought in
+//! each choice's `reasoning_content`, which the OpenAI response types would
+//! drop, so it is carried over to the converted response.
+use bytes::Bytes;
+use http::response::Parts;
+use serde_json::Value;
+
+use super::{
+    EndpointConverter,
+    groq::{contains, deserialize, serialize},
+};
+use crate::{error::api::ApiError, types::extensions::MapperContext};
+
+const REASONING_CONTENT: &str = "reasoning_content";
+const REASONING_CONTENT_KEY: &[u8] = b"\"reasoning_content\"";
+
+/// Wraps the converter for requests to DeepSeek.
+pub struct DeepSeekConverter<C> {
+    inner: C,
+}
+
+impl<C> DeepSeekConverter<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: EndpointConverter> EndpointConverter for DeepSeekConverter<C> {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        self.inner.convert_req_body(req_body_bytes)
+    }
+
+    fn convert_resp_body(
+        &self,
+        resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        if !resp_parts.status.is_success()
+            || !contains(&resp_body_bytes, REASONING_CONTENT_KEY)
+        {
+            return self.inner.convert_resp_body(
+                resp_parts,
+                resp_body_bytes,
+                is_stream,
+            );
+        }
+        let reasoning =
+            reasoning_by_choice(&deserialize(&resp_body_bytes)?, is_stream);
+        let Some(converted) = self.inner.convert_resp_body(
+            resp_parts,
+            resp_body_bytes,
+            is_stream,
+        )?
+        else {
+            return Ok(None);
+        };
+        if reasoning.is_empty() {
+            return Ok(Some(converted));
+        }
+        let mut converted_value = deserialize(&converted)?;
+        restore_reasoning(&mut converted_value, reasoning, is_stream);
+        Ok(Some(serialize(&converted_value)?))
+    }
+}
+
+/// Chunks carry their choices' content in `delta` rather than `message`.
+fn message_field(is_stream: bool) -> &'static str {
+    if is_stream { "delta" } else { "message" }
+}
+
+/// The `reasoning_content` of each choice that has any, by choice index.
+fn reasoning_by_choice(response: &Value, is_stream: bool) -> Vec<(u64, Value)> {
+    let Some(choices) = response.get("choices").and_then(Value::as_array)
+    else {
+        return Vec::new();
+    };
+    choices
+        .iter()
+        .filter_map(|choice| {
+            let index = choice.get("index")?.as_u64()?;
+            let reasoning = choice
+                .get(message_field(is_stream))?
+                .get(REASONING_CONTENT)?;
+            (!reasoning.is_null()).then(|| (index, reasoning.clone()))
+        })
+        .collect()
+}
+
+fn restore_reasoning(
+    response: &mut Value,
+    reasoning: Vec<(u64, Value)>,
+    is_stream: bool,
+) {
+    let Some(choices) =
+        response.get_mut("choices").and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    for (index, reasoning) in reasoning {
+        let message = choices
+            .iter_mut()
+            .find(|choice| {
+                choice.get("index").and_then(Value::as_u64) == Some(index)
+            })
+            .and_then(|choice| choice.get_mut(message_field(is_stream)))
+            .and_then(Value::as_object_mut);
+        if let Some(message) = message {
+            message.insert(REASONING_CONTENT.to_string(), reasoning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reasoning_content_is_restored() {
+        let response = json!({
+            "id": "930c60df-bf64-41c9-a88e-3ec75f81e00e",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "Hello!",
+                    "reasoning_content": "The user greeted me."
+                },
+                "finish_reason": "stop"
+            }]
+        });
+        let reasoning = reasoning_by_choice(&response, false);
+
+        let mut converted = json!({
+            "id": "930c60df-bf64-41c9-a88e-3ec75f81e00e",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "Hello!" },
+                "finish_reason": "stop"
+            }]
+        });
+        restore_reasoning(&mut converted, reasoning, false);
+
+        assert_eq!(
+            converted["choices"][0]["message"]["reasoning_content"],
+            "The user greeted me."
+        );
+    }
+
+    #[test]
+    fn null_reasoning_content_is_skipped() {
+        let chunk = json!({
+            "choices": [{
+                "index": 0,
+                "delta": { "content": "Hi", "reasoning_content": null }
+            }]
+        });
+        assert!(reasoning_by_choice(&chunk, true).is_empty());
+    }
+}
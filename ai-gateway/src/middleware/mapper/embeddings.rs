@@ -0,0 +1,111 @@
+//! OpenAI can return embeddings base64 encoded, as the little endian bytes of
+//! each `f32`, which is more compact than a JSON array of floats. Other
+//! providers only return floats, so when the client asks for base64 we
+//! request floats and encode the mapped response ourselves.
+use async_openai::types::EncodingFormat;
+use base64::Engine;
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::groq::{deserialize, serialize};
+use crate::error::internal::InternalError;
+
+#[derive(Deserialize)]
+struct EncodingFormatField {
+    #[serde(default)]
+    encoding_format: Option<EncodingFormat>,
+}
+
+/// Whether an OpenAI embeddings request asked for base64 encoded embeddings.
+pub(super) fn wants_base64(req_body: &[u8]) -> bool {
+    serde_json::from_slice::<EncodingFormatField>(req_body).is_ok_and(
+        |request| {
+            matches!(request.encoding_format, Some(EncodingFormat::Base64))
+        },
+    )
+}
+
+/// Replaces each float embedding in an OpenAI embeddings response with its
+/// base64 encoding.
+pub(super) fn encode_base64(resp_body: &[u8]) -> Result<Bytes, InternalError> {
+    let mut response = deserialize(resp_body)?;
+    let Some(data) = response.get_mut("data").and_then(Value::as_array_mut)
+    else {
+        return serialize(&response);
+    };
+    for embedding in
+        data.iter_mut().filter_map(|item| item.get_mut("embedding"))
+    {
+        let Some(floats) = embedding.as_array() else {
+            continue;
+        };
+        let bytes = floats
+            .iter()
+            .filter_map(Value::as_f64)
+            .flat_map(|float| {
+                #[allow(clippy::cast_possible_truncation)]
+                let float = float as f32;
+                float.to_le_bytes()
+            })
+            .collect::<Vec<_>>();
+        *embedding = Value::String(
+            base64::engine::general_purpose::STANDARD.encode(bytes),
+        );
+    }
+    serialize(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn only_base64_requests_are_encoded() {
+        let base64 = json!({
+            "model": "gemini/text-embedding-004",
+            "input": "Hello, world!",
+            "encoding_format": "base64"
+        });
+        let float = json!({
+            "model": "gemini/text-embedding-004",
+            "input": "Hello, world!",
+            "encoding_format": "float"
+        });
+        let default = json!({
+            "model": "gemini/text-embedding-004",
+            "input": "Hello, world!"
+        });
+        assert!(wants_base64(&serde_json::to_vec(&base64).unwrap()));
+        assert!(!wants_base64(&serde_json::to_vec(&float).unwrap()));
+        assert!(!wants_base64(&serde_json::to_vec(&default).unwrap()));
+    }
+
+    #[test]
+    fn embeddings_are_encoded_as_little_endian_floats() {
+        let response = json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 0, "embedding": [0.5, -1.0] }
+            ],
+            "model": "text-embedding-004",
+            "usage": { "prompt_tokens": 4, "total_tokens": 4 }
+        });
+        let encoded =
+            encode_base64(&serde_json::to_vec(&response).unwrap()).unwrap();
+        let encoded: Value = serde_json::from_slice(&encoded).unwrap();
+
+        let embedding = encoded["data"][0]["embedding"].as_str().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(embedding)
+            .unwrap();
+        let floats = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(floats, vec![0.5, -1.0]);
+        assert_eq!(encoded["usage"], response["usage"]);
+    }
+}
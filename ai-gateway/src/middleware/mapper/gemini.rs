@@ -0,0 +1,449 @@
+use std::str::FromStr;
+
+use async_openai::types::{
+    CreateChatCompletionRequest, CreateChatCompletionResponse,
+    CreateChatCompletionStreamResponse,
+};
+use http::response::Parts;
+
+use super::{
+    TryConvert, TryConvertError, TryConvertStreamData, mime_from_data_uri,
+    model::ModelMapper, openai_compatible::openai_error_from_gemini,
+};
+use crate::{
+    endpoints::google::generate_contents::{
+        Blob, Candidate, Content, FileData, FinishReason, GeminiApiError,
+        GenerateContentRequest, GenerateContentResponse, GenerationConfig,
+        Part, Role, UsageMetadata,
+    },
+    error::mapper::MapperError,
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+const CHAT_COMPLETION_OBJECT: &str = "chat.completion";
+const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
+const PLACEHOLDER_STREAM_ID: &str = "gemini-stream-id";
+const JSON_MIME_TYPE: &str = "application/json";
+
+pub struct GeminiConverter {
+    model_mapper: ModelMapper,
+}
+
+impl GeminiConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+
+    fn map_model(&self, model: &str) -> Result<String, MapperError> {
+        let source_model = ModelId::from_str(model)?;
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::GoogleGemini)?;
+        tracing::trace!(source_model = ?source_model, target_model = ?target_model, "mapped model");
+        Ok(target_model.to_string())
+    }
+}
+
+impl TryConvert<CreateChatCompletionRequest, GenerateContentRequest>
+    for GeminiConverter
+{
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: CreateChatCompletionRequest,
+    ) -> Result<GenerateContentRequest, Self::Error> {
+        use async_openai::types as openai;
+        let model = self.map_model(&value.model)?;
+        let (system_instruction, contents) = contents(value.messages)?;
+
+        let stop_sequences = match value.stop {
+            Some(openai::Stop::String(stop)) => vec![stop],
+            Some(openai::Stop::StringArray(stops)) => stops,
+            None => Vec::new(),
+        };
+        #[allow(deprecated)]
+        let max_output_tokens =
+            value.max_completion_tokens.or(value.max_tokens);
+        let (response_mime_type, response_json_schema) =
+            match value.response_format {
+                None | Some(openai::ResponseFormat::Text) => (None, None),
+                Some(openai::ResponseFormat::JsonObject) => {
+                    (Some(JSON_MIME_TYPE.to_string()), None)
+                }
+                Some(openai::ResponseFormat::JsonSchema { json_schema }) => {
+                    (Some(JSON_MIME_TYPE.to_string()), json_schema.schema)
+                }
+            };
+
+        Ok(GenerateContentRequest {
+            model,
+            stream: value.stream.unwrap_or(false),
+            contents,
+            system_instruction,
+            generation_config: Some(GenerationConfig {
+                stop_sequences,
+                candidate_count: value.n,
+                max_output_tokens,
+                temperature: value.temperature,
+                top_p: value.top_p,
+                seed: value.seed,
+                presence_penalty: value.presence_penalty,
+                frequency_penalty: value.frequency_penalty,
+                response_mime_type,
+                response_json_schema,
+            }),
+        })
+    }
+}
+
+/// Gemini's system instruction and the contents of the conversation.
+fn contents(
+    messages: Vec<async_openai::types::ChatCompletionRequestMessage>,
+) -> Result<(Option<Content>, Vec<Content>), MapperError> {
+    use async_openai::types as openai;
+    // system messages may appear anywhere, but Gemini only takes a single
+    // system instruction
+    let mut system_parts = Vec::new();
+    let mut contents = Vec::with_capacity(messages.len());
+    for message in messages {
+        let (role, parts) = match message {
+            openai::ChatCompletionRequestMessage::Developer(message) => {
+                let text = match message.content {
+                    openai::ChatCompletionRequestDeveloperMessageContent::Text(text) => text,
+                    openai::ChatCompletionRequestDeveloperMessageContent::Array(parts) => parts
+                        .into_iter()
+                        .map(|part| part.text)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                };
+                system_parts.push(Part::text(text));
+                continue;
+            }
+            openai::ChatCompletionRequestMessage::System(message) => {
+                let text = match message.content {
+                    openai::ChatCompletionRequestSystemMessageContent::Text(text) => text,
+                    openai::ChatCompletionRequestSystemMessageContent::Array(parts) => parts
+                        .into_iter()
+                        .map(|part| match part {
+                            openai::ChatCompletionRequestSystemMessageContentPart::Text(text) => text.text,
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                };
+                system_parts.push(Part::text(text));
+                continue;
+            }
+            // tools aren't mapped, so neither are their results
+            openai::ChatCompletionRequestMessage::Tool(_)
+            | openai::ChatCompletionRequestMessage::Function(_) => {
+                continue;
+            }
+            openai::ChatCompletionRequestMessage::User(message) => {
+                let parts = match message.content {
+                    openai::ChatCompletionRequestUserMessageContent::Text(
+                        text,
+                    ) => vec![Part::text(text)],
+                    openai::ChatCompletionRequestUserMessageContent::Array(
+                        parts,
+                    ) => parts
+                        .into_iter()
+                        .filter_map(|part| match part {
+                            openai::ChatCompletionRequestUserMessageContentPart::Text(text) => Some(Part::text(text.text)),
+                            openai::ChatCompletionRequestUserMessageContentPart::ImageUrl(image) => Some(image_part(image.image_url.url)),
+                            _ => None,
+                        })
+                        .collect(),
+                };
+                (Role::User, parts)
+            }
+            openai::ChatCompletionRequestMessage::Assistant(message) => {
+                let text = match message.content {
+                    Some(openai::ChatCompletionRequestAssistantMessageContent::Text(text)) => text,
+                    Some(openai::ChatCompletionRequestAssistantMessageContent::Array(parts)) => parts
+                        .into_iter()
+                        .map(|part| match part {
+                            openai::ChatCompletionRequestAssistantMessageContentPart::Text(text) => text.text,
+                            openai::ChatCompletionRequestAssistantMessageContentPart::Refusal(refusal) => refusal.refusal,
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    None => continue,
+                };
+                (Role::Model, vec![Part::text(text)])
+            }
+        };
+        contents.push(Content {
+            role: Some(role),
+            parts,
+        });
+    }
+    if contents.is_empty() {
+        return Err(MapperError::InvalidRequest);
+    }
+    let system_instruction = (!system_parts.is_empty()).then(|| Content {
+        role: None,
+        parts: system_parts,
+    });
+    Ok((system_instruction, contents))
+}
+
+/// Images in `data:` URLs are sent inline, others are fetched by Gemini.
+fn image_part(url: String) -> Part {
+    let inline_data = url.strip_prefix("data:").and_then(|data_url| {
+        let (metadata, data) = data_url.split_once(',')?;
+        let mime_type = metadata.strip_suffix(";base64")?;
+        let mime_type = if mime_type.is_empty() {
+            mime_from_data_uri(&url)?.mime_type()
+        } else {
+            mime_type
+        };
+        Some(Blob {
+            mime_type: mime_type.to_string(),
+            data: data.to_string(),
+        })
+    });
+    match inline_data {
+        Some(inline_data) => Part {
+            inline_data: Some(inline_data),
+            ..Default::default()
+        },
+        None => Part {
+            file_data: Some(FileData {
+                mime_type: None,
+                file_uri: url,
+            }),
+            ..Default::default()
+        },
+    }
+}
+
+impl TryConvert<GenerateContentResponse, CreateChatCompletionResponse>
+    for GeminiConverter
+{
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: GenerateContentResponse,
+    ) -> Result<CreateChatCompletionResponse, Self::Error> {
+        use async_openai::types as openai;
+        let choices = value
+            .candidates
+            .into_iter()
+            .zip(0..)
+            .map(|(candidate, position)| {
+                let index = candidate.index.unwrap_or(position);
+                let finish_reason = candidate.finish_reason.map(finish_reason);
+                #[allow(deprecated)]
+                let message = openai::ChatCompletionResponseMessage {
+                    content: Some(candidate_text(candidate)),
+                    refusal: None,
+                    tool_calls: None,
+                    role: openai::Role::Assistant,
+                    function_call: None,
+                    audio: None,
+                };
+                openai::ChatChoice {
+                    index,
+                    message,
+                    finish_reason,
+                    logprobs: None,
+                }
+            })
+            .collect();
+
+        Ok(CreateChatCompletionResponse {
+            choices,
+            id: value.response_id.unwrap_or_default(),
+            created: 0,
+            model: value.model_version.unwrap_or_default(),
+            object: CHAT_COMPLETION_OBJECT.to_string(),
+            usage: value.usage_metadata.map(completion_usage),
+            service_tier: None,
+            system_fingerprint: None,
+        })
+    }
+}
+
+impl
+    TryConvertStreamData<
+        GenerateContentResponse,
+        CreateChatCompletionStreamResponse,
+    > for GeminiConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: GenerateContentResponse,
+    ) -> Result<Option<CreateChatCompletionStreamResponse>, Self::Error> {
+        use async_openai::types as openai;
+        // every event carries the usage so far, so it's only sent with the
+        // last one
+        let mut is_finished = false;
+        let choices = value
+            .candidates
+            .into_iter()
+            .zip(0..)
+            .map(|(candidate, position)| {
+                let index = candidate.index.unwrap_or(position);
+                let finish_reason = candidate.finish_reason.map(finish_reason);
+                is_finished |= finish_reason.is_some();
+                openai::ChatChoiceStream {
+                    index,
+                    delta: openai::ChatCompletionStreamResponseDelta {
+                        role: Some(openai::Role::Assistant),
+                        content: Some(candidate_text(candidate)),
+                        tool_calls: None,
+                        refusal: None,
+                        #[allow(deprecated)]
+                        function_call: None,
+                    },
+                    finish_reason,
+                    logprobs: None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if choices.is_empty() {
+            return Ok(None);
+        }
+        let usage = value
+            .usage_metadata
+            .filter(|_| is_finished)
+            .map(completion_usage);
+
+        Ok(Some(CreateChatCompletionStreamResponse {
+            id: value
+                .response_id
+                .unwrap_or_else(|| PLACEHOLDER_STREAM_ID.to_string()),
+            choices,
+            created: 0,
+            model: value.model_version.unwrap_or_default(),
+            object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+            system_fingerprint: None,
+            service_tier: None,
+            usage,
+        }))
+    }
+}
+
+impl TryConvertError<GeminiApiError, async_openai::error::WrappedError>
+    for GeminiConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_error(
+        &self,
+        resp_parts: &Parts,
+        value: GeminiApiError,
+    ) -> Result<async_openai::error::WrappedError, Self::Error> {
+        Ok(openai_error_from_gemini(resp_parts, value))
+    }
+}
+
+/// The candidate's text, without the model's thoughts.
+fn candidate_text(candidate: Candidate) -> String {
+    candidate
+        .content
+        .into_iter()
+        .flat_map(|content| content.parts)
+        .filter(|part| part.thought != Some(true))
+        .filter_map(|part| part.text)
+        .collect()
+}
+
+fn finish_reason(reason: FinishReason) -> async_openai::types::FinishReason {
+    use async_openai::types as openai;
+    match reason {
+        FinishReason::Stop | FinishReason::Other => openai::FinishReason::Stop,
+        FinishReason::MaxTokens => openai::FinishReason::Length,
+        FinishReason::Safety
+        | FinishReason::Recitation
+        | FinishReason::Blocklist
+        | FinishReason::ProhibitedContent
+        | FinishReason::Spii => openai::FinishReason::ContentFilter,
+    }
+}
+
+fn completion_usage(
+    usage: UsageMetadata,
+) -> async_openai::types::CompletionUsage {
+    use async_openai::types as openai;
+    // OpenAI counts reasoning tokens as completion tokens
+    let completion_tokens =
+        usage.candidates_token_count + usage.thoughts_token_count;
+    openai::CompletionUsage {
+        prompt_tokens: usage.prompt_token_count,
+        completion_tokens,
+        total_tokens: usage.prompt_token_count + completion_tokens,
+        prompt_tokens_details: usage.cached_content_token_count.map(
+            |cached_tokens| openai::PromptTokensDetails {
+                audio_tokens: None,
+                cached_tokens: Some(cached_tokens),
+            },
+        ),
+        completion_tokens_details: (usage.thoughts_token_count > 0).then(
+            || openai::CompletionTokensDetails {
+                accepted_prediction_tokens: None,
+                audio_tokens: None,
+                reasoning_tokens: Some(usage.thoughts_token_count),
+                rejected_prediction_tokens: None,
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn thoughts_are_dropped_from_the_response() {
+        let response: GenerateContentResponse = serde_json::from_value(json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        { "text": "Let me think.", "thought": true },
+                        { "text": "Hello!" }
+                    ]
+                },
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 4,
+                "candidatesTokenCount": 2,
+                "thoughtsTokenCount": 3,
+                "totalTokenCount": 9
+            }
+        }))
+        .unwrap();
+        let candidate = response.candidates[0].clone();
+        assert_eq!(candidate_text(candidate), "Hello!");
+        let usage = completion_usage(response.usage_metadata.unwrap());
+        assert_eq!(usage.completion_tokens, 5);
+        assert_eq!(usage.total_tokens, 9);
+    }
+
+    #[test]
+    fn data_url_images_are_sent_inline() {
+        let part = image_part("data:image/png;base64,iVBORw0KGgo=".to_string());
+        assert_eq!(
+            part.inline_data,
+            Some(Blob {
+                mime_type: "image/png".to_string(),
+                data: "iVBORw0KGgo=".to_string(),
+            })
+        );
+        let part = image_part("https://example.com/cat.png".to_string());
+        assert_eq!(
+            part.file_data.unwrap().file_uri,
+            "https://example.com/cat.png"
+        );
+    }
+}
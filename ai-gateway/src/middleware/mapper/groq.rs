@@ -0,0 +1,159 @@
+//! Handles the differences between Groq's chat completions API and OpenAI's.
+//!
+//! Groq is otherwise OpenAI compatible, so this wraps the OpenAI compatible
+//! converter. Streamed responses report usage in the final chunk's `x_groq`
+//! extension rather than in `usage`, which the OpenAI chunk type would drop,
+//! so it is moved to where OpenAI puts it.
+use bytes::Bytes;
+use http::response::Parts;
+use serde_json::{Map, Value};
+
+use super::EndpointConverter;
+use crate::{
+    error::{api::ApiError, internal::InternalError},
+    types::extensions::MapperContext,
+};
+
+const X_GROQ: &[u8] = b"\"x_groq\"";
+const USAGE_FIELDS: [&str; 3] =
+    ["prompt_tokens", "completion_tokens", "total_tokens"];
+
+/// Wraps the converter for requests to Groq.
+pub struct GroqConverter<C> {
+    inner: C,
+}
+
+impl<C> GroqConverter<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: EndpointConverter> EndpointConverter for GroqConverter<C> {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        self.inner.convert_req_body(req_body_bytes)
+    }
+
+    fn convert_resp_body(
+        &self,
+        resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        let resp_body_bytes = if is_stream
+            && resp_parts.status.is_success()
+            && contains(&resp_body_bytes, X_GROQ)
+        {
+            let mut chunk = deserialize(&resp_body_bytes)?;
+            if hoist_usage(&mut chunk) {
+                serialize(&chunk)?
+            } else {
+                resp_body_bytes
+            }
+        } else {
+            resp_body_bytes
+        };
+        self.inner
+            .convert_resp_body(resp_parts, resp_body_bytes, is_stream)
+    }
+}
+
+/// Copies the token counts from `x_groq.usage` to `usage`.
+///
+/// Returns `false` if the chunk has no Groq usage, or already has `usage`.
+fn hoist_usage(chunk: &mut Value) -> bool {
+    let Some(chunk) = chunk.as_object_mut() else {
+        return false;
+    };
+    if chunk.get("usage").is_some_and(|usage| !usage.is_null()) {
+        return false;
+    }
+    let Some(Value::Object(groq_usage)) =
+        chunk.get("x_groq").and_then(|x_groq| x_groq.get("usage"))
+    else {
+        return false;
+    };
+    let usage = USAGE_FIELDS
+        .iter()
+        .filter_map(|field| {
+            groq_usage
+                .get(*field)
+                .map(|count| ((*field).to_string(), count.clone()))
+        })
+        .collect::<Map<_, _>>();
+    chunk.insert("usage".to_string(), Value::Object(usage));
+    true
+}
+
+pub(super) fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}
+
+pub(super) fn deserialize(bytes: &[u8]) -> Result<Value, InternalError> {
+    serde_json::from_slice(bytes).map_err(|e| InternalError::Deserialize {
+        ty: std::any::type_name::<Value>(),
+        error: e,
+    })
+}
+
+pub(super) fn serialize(value: &Value) -> Result<Bytes, InternalError> {
+    serde_json::to_vec(value).map(Bytes::from).map_err(|e| {
+        InternalError::Serialize {
+            ty: std::any::type_name::<Value>(),
+            error: e,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn groq_usage_is_hoisted() {
+        let mut chunk = json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion.chunk",
+            "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }],
+            "x_groq": {
+                "id": "req_123",
+                "usage": {
+                    "queue_time": 0.01,
+                    "prompt_tokens": 10,
+                    "prompt_time": 0.002,
+                    "completion_tokens": 3,
+                    "completion_time": 0.004,
+                    "total_tokens": 13,
+                    "total_time": 0.006
+                }
+            }
+        });
+        assert!(hoist_usage(&mut chunk));
+        assert_eq!(
+            chunk["usage"],
+            json!({
+                "prompt_tokens": 10,
+                "completion_tokens": 3,
+                "total_tokens": 13
+            })
+        );
+        // usage which is already present is kept
+        assert!(!hoist_usage(&mut chunk));
+    }
+
+    #[test]
+    fn chunks_without_groq_usage_are_unchanged() {
+        let mut chunk = json!({
+            "id": "chatcmpl-123",
+            "choices": [{ "index": 0, "delta": { "content": "Hi" } }],
+            "x_groq": { "id": "req_123" }
+        });
+        assert!(!hoist_usage(&mut chunk));
+        assert!(chunk.get("usage").is_none());
+    }
+}
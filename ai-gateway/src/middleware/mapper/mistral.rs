@@ -0,0 +1,270 @@
+//! Handles the differences between Mistral's chat completions API and
+//! OpenAI's.
+//!
+//! Mistral is otherwise OpenAI compatible, so this wraps the OpenAI
+//! compatible converter and adjusts the raw request and response bodies:
+//!
+//! - tool call ids must be 9 alphanumeric characters, so ids generated by other
+//!   providers are shortened
+//! - `tool_choice: "required"` is spelled `"any"`
+//! - `seed` is spelled `random_seed`
+//! - the Mistral specific `safe_prompt` option, which the OpenAI request type
+//!   drops, is copied from the source request
+//! - tool calls in responses may omit their `type`
+//! - errors are not wrapped in an `error` object
+use bytes::Bytes;
+use http::response::Parts;
+use serde_json::{Value, json};
+
+use super::EndpointConverter;
+use crate::{
+    error::{api::ApiError, internal::InternalError},
+    types::extensions::MapperContext,
+};
+
+const SAFE_PROMPT: &str = "safe_prompt";
+const TOOL_CALL_ID_LEN: usize = 9;
+const TOOL_CALL_ID_ALPHABET: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Wraps the converter for requests to Mistral.
+pub struct MistralConverter<C> {
+    inner: C,
+}
+
+impl<C> MistralConverter<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: EndpointConverter> EndpointConverter for MistralConverter<C> {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        let safe_prompt = if contains(&req_body_bytes, SAFE_PROMPT.as_bytes()) {
+            deserialize(&req_body_bytes)?.get(SAFE_PROMPT).cloned()
+        } else {
+            None
+        };
+        let (target_bytes, mapper_ctx) =
+            self.inner.convert_req_body(req_body_bytes)?;
+        let mut target = deserialize(&target_bytes)?;
+        adapt_request(&mut target);
+        if let Some(safe_prompt) = safe_prompt
+            && let Some(target) = target.as_object_mut()
+        {
+            target.insert(SAFE_PROMPT.to_string(), safe_prompt);
+        }
+        Ok((serialize(&target)?, mapper_ctx))
+    }
+
+    fn convert_resp_body(
+        &self,
+        resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        let is_error = resp_parts.status.is_client_error()
+            || resp_parts.status.is_server_error();
+        let resp_body_bytes = if is_error && !is_stream {
+            let mut error = deserialize(&resp_body_bytes)?;
+            if wrap_error(&mut error) {
+                serialize(&error)?
+            } else {
+                resp_body_bytes
+            }
+        } else if !is_stream && contains(&resp_body_bytes, b"\"tool_calls\"") {
+            let mut response = deserialize(&resp_body_bytes)?;
+            fill_tool_call_types(&mut response);
+            serialize(&response)?
+        } else {
+            resp_body_bytes
+        };
+        self.inner
+            .convert_resp_body(resp_parts, resp_body_bytes, is_stream)
+    }
+}
+
+fn adapt_request(request: &mut Value) {
+    if let Some(tool_choice) = request.get_mut("tool_choice")
+        && tool_choice == "required"
+    {
+        *tool_choice = json!("any");
+    }
+    if let Some(request) = request.as_object_mut()
+        && let Some(seed) = request.remove("seed")
+    {
+        request.insert("random_seed".to_string(), seed);
+    }
+
+    let messages = request.get_mut("messages").and_then(Value::as_array_mut);
+    for message in messages.into_iter().flatten() {
+        if let Some(Value::String(id)) = message.get_mut("tool_call_id") {
+            *id = mistral_tool_call_id(id);
+        }
+        let tool_calls =
+            message.get_mut("tool_calls").and_then(Value::as_array_mut);
+        for tool_call in tool_calls.into_iter().flatten() {
+            if let Some(Value::String(id)) = tool_call.get_mut("id") {
+                *id = mistral_tool_call_id(id);
+            }
+        }
+    }
+}
+
+/// Maps a tool call id to one Mistral accepts.
+///
+/// The mapping is deterministic so that an assistant's tool calls still
+/// match the tool messages answering them.
+fn mistral_tool_call_id(id: &str) -> String {
+    if id.len() == TOOL_CALL_ID_LEN
+        && id.bytes().all(|byte| byte.is_ascii_alphanumeric())
+    {
+        return id.to_string();
+    }
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in id.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    let base = TOOL_CALL_ID_ALPHABET.len() as u64;
+    (0..TOOL_CALL_ID_LEN)
+        .map(|_| {
+            #[allow(clippy::cast_possible_truncation)]
+            let index = (hash % base) as usize;
+            hash /= base;
+            char::from(TOOL_CALL_ID_ALPHABET[index])
+        })
+        .collect()
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}
+
+/// Adds the `type` Mistral may leave out of tool calls, which OpenAI
+/// requires.
+fn fill_tool_call_types(response: &mut Value) {
+    let choices = response.get_mut("choices").and_then(Value::as_array_mut);
+    for choice in choices.into_iter().flatten() {
+        let tool_calls = choice
+            .pointer_mut("/message/tool_calls")
+            .and_then(Value::as_array_mut);
+        for tool_call in tool_calls.into_iter().flatten() {
+            if let Some(tool_call) = tool_call.as_object_mut() {
+                tool_call.entry("type").or_insert_with(|| json!("function"));
+            }
+        }
+    }
+}
+
+/// Wraps a Mistral error in an `error` object, as OpenAI does.
+///
+/// Returns `false` if the error is already wrapped.
+fn wrap_error(error: &mut Value) -> bool {
+    if error.get("error").is_some() {
+        return false;
+    }
+    let message = match error.get("message") {
+        Some(Value::String(message)) => json!(message),
+        // validation errors have a list of details as the message
+        Some(message) => json!(message.to_string()),
+        None => json!(error.to_string()),
+    };
+    *error = json!({
+        "error": {
+            "message": message,
+            "type": error.get("type").cloned().unwrap_or(Value::Null),
+            "param": error.get("param").cloned().unwrap_or(Value::Null),
+            "code": error
+                .get("code")
+                .map(|code| match code {
+                    Value::Null | Value::String(_) => code.clone(),
+                    code => json!(code.to_string()),
+                })
+                .unwrap_or(Value::Null),
+        }
+    });
+    true
+}
+
+fn deserialize(bytes: &[u8]) -> Result<Value, InternalError> {
+    serde_json::from_slice(bytes).map_err(|e| InternalError::Deserialize {
+        ty: std::any::type_name::<Value>(),
+        error: e,
+    })
+}
+
+fn serialize(value: &Value) -> Result<Bytes, InternalError> {
+    serde_json::to_vec(value).map(Bytes::from).map_err(|e| {
+        InternalError::Serialize {
+            ty: std::any::type_name::<Value>(),
+            error: e,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_call_ids_are_shortened_consistently() {
+        let mut request = json!({
+            "model": "mistral-large",
+            "tool_choice": "required",
+            "seed": 42,
+            "messages": [
+                {
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_abc123XYZ456def",
+                        "type": "function",
+                        "function": { "name": "get_weather", "arguments": "{}" }
+                    }]
+                },
+                {
+                    "role": "tool",
+                    "tool_call_id": "call_abc123XYZ456def",
+                    "content": "sunny"
+                },
+                {
+                    "role": "tool",
+                    "tool_call_id": "D681PevKs",
+                    "content": "cloudy"
+                }
+            ]
+        });
+        adapt_request(&mut request);
+
+        assert_eq!(request["tool_choice"], "any");
+        assert_eq!(request["random_seed"], 42);
+        assert!(request.get("seed").is_none());
+        let id = request["messages"][0]["tool_calls"][0]["id"]
+            .as_str()
+            .unwrap();
+        assert_eq!(id.len(), TOOL_CALL_ID_LEN);
+        assert!(id.bytes().all(|byte| byte.is_ascii_alphanumeric()));
+        assert_eq!(request["messages"][1]["tool_call_id"], id);
+        // ids which are already valid are kept
+        assert_eq!(request["messages"][2]["tool_call_id"], "D681PevKs");
+    }
+
+    #[test]
+    fn errors_are_wrapped() {
+        let mut error = json!({
+            "object": "error",
+            "message": "Invalid model: mistral-huge",
+            "type": "invalid_model",
+            "param": null,
+            "code": "1500"
+        });
+        assert!(wrap_error(&mut error));
+        assert_eq!(error["error"]["message"], "Invalid model: mistral-huge");
+        assert_eq!(error["error"]["type"], "invalid_model");
+        assert!(!wrap_error(&mut error));
+    }
+}
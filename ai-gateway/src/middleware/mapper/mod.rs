@@ -1,11 +1,25 @@
 pub mod anthropic;
+mod anthropic_stream;
+pub mod audio;
+pub mod azure;
 mod bedrock;
+pub mod cohere;
+pub mod deepseek;
+mod embeddings;
+pub mod gemini;
+pub mod groq;
+pub mod mistral;
 pub mod model;
 pub mod ollama;
 pub mod openai;
 pub mod openai_compatible;
+mod params;
+pub mod prompt_caching;
 pub mod registry;
+pub mod responses;
 pub mod service;
+mod stream_usage;
+pub mod vertex;
 
 use async_openai::error::WrappedError;
 use base64::Engine;
@@ -136,7 +150,7 @@ where
         let target_request: T::RequestBody = self
             .converter
             .try_convert(source_request)
-            .map_err(|e| InternalError::MapperError(e.into()))?;
+            .map_err(|e| ApiError::from(Into::<MapperError>::into(e)))?;
         let model = target_request.model().map_err(InternalError::MapperError).inspect_err(|e| {
             tracing::error!(?e, "failed to get model from request");
         })?;
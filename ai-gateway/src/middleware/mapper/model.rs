@@ -85,6 +85,9 @@ impl ModelMapper {
 
     /// Map a model to a new model name for a target provider.
     ///
+    /// If the source model is an alias, see
+    /// [`ModelAliasConfig`](crate::config::model_alias::ModelAliasConfig),
+    /// return the model configured for the target provider.
     /// If the source model is offered by the target provider, return the source
     /// model name. Otherwise, use the model mapping from router config.
     /// If that doesn't have a mapping, use the default model mapping from the
@@ -101,6 +104,14 @@ impl ModelMapper {
         if let Some(model_id) = self.model_id.clone() {
             return Ok(model_id);
         }
+        if let Some(target_model) = self
+            .app_state
+            .config()
+            .model_aliases
+            .resolve(source_model, target_provider)
+        {
+            return target_model;
+        }
         let models_offered_by_target_provider =
             self.provider_models.0.get(target_provider).ok_or_else(|| {
                 MapperError::NoProviderConfig(target_provider.clone())
@@ -153,4 +164,20 @@ impl ModelMapper {
 
         Ok(target_model)
     }
+
+    /// The Azure deployment serving `model`.
+    ///
+    /// Models without a configured deployment are assumed to be deployed
+    /// under their own name.
+    #[must_use]
+    pub fn azure_deployment(&self, model: &ModelId) -> String {
+        let model = model.to_string();
+        self.app_state
+            .config()
+            .providers
+            .get(&InferenceProvider::AzureOpenAI)
+            .and_then(|config| config.deployments.get(&model))
+            .cloned()
+            .unwrap_or(model)
+    }
 }
@@ -148,10 +148,10 @@ impl
             }));
         }
         for message in value.messages {
-            let mapped_message: openai::ChatCompletionRequestMessage =
-                match message.role {
-                    anthropic::Role::Assistant => {
-                        let mapped_content: openai::ChatCompletionRequestAssistantMessageContent = match message.content {
+            match message.role {
+                anthropic::Role::Assistant => {
+                    let mut tool_calls = Vec::new();
+                    let mapped_content: openai::ChatCompletionRequestAssistantMessageContent = match message.content {
                         anthropic::MessageContent::Text { content } => {
                             openai::ChatCompletionRequestAssistantMessageContent::Text(content)
                         }
@@ -163,32 +163,57 @@ impl
                                             text
                                         }))
                                     },
+                                    anthropic::ContentBlock::ToolUse { id, name, input } => {
+                                        tool_calls.push(openai::ChatCompletionMessageToolCall {
+                                            id,
+                                            r#type: openai::ChatCompletionToolType::Function,
+                                            function: openai::FunctionCall {
+                                                name,
+                                                arguments: input.to_string(),
+                                            },
+                                        });
+                                        None
+                                    },
                                     anthropic::ContentBlock::Image { .. } |
-                                    anthropic::ContentBlock::ToolUse { .. } |
                                     anthropic::ContentBlock::ToolResult { .. } |
                                     anthropic::ContentBlock::Thinking { .. } |
                                     anthropic::ContentBlock::RedactedThinking { .. } => {
                                         None
                                     }
                                 }
-                            }).collect();
+                            }).collect::<Vec<_>>();
                             openai::ChatCompletionRequestAssistantMessageContent::Array(blocks)
                         }
                     };
-                        #[allow(deprecated)]
+                    // messages which only call tools have no content
+                    let content = match mapped_content {
+                        openai::ChatCompletionRequestAssistantMessageContent::Array(blocks)
+                            if blocks.is_empty() && !tool_calls.is_empty() =>
+                        {
+                            None
+                        }
+                        mapped_content => Some(mapped_content),
+                    };
+                    #[allow(deprecated)]
+                    messages.push(
                         openai::ChatCompletionRequestMessage::Assistant(
                             openai::ChatCompletionRequestAssistantMessage {
-                                content: Some(mapped_content),
-                                tool_calls: None,
+                                content,
+                                tool_calls: if tool_calls.is_empty() {
+                                    None
+                                } else {
+                                    Some(tool_calls)
+                                },
                                 refusal: None,
                                 name: None,
                                 audio: None,
                                 function_call: None,
                             },
-                        )
-                    }
-                    anthropic::Role::User => {
-                        let content: openai::ChatCompletionRequestUserMessageContent  = match message.content {
+                        ),
+                    );
+                }
+                anthropic::Role::User => {
+                    let content: openai::ChatCompletionRequestUserMessageContent = match message.content {
                         anthropic::MessageContent::Text { content } => {
                             openai::ChatCompletionRequestUserMessageContent::Text(content)
                         }
@@ -202,33 +227,46 @@ impl
                                     },
                                     anthropic::ContentBlock::Image { source } => {
                                         let image_url = openai::ImageUrl {
-                                            url: source.data,
+                                            url: image_url(source),
                                             detail: None,
                                         };
                                         Some(openai::ChatCompletionRequestUserMessageContentPart::ImageUrl(openai::ChatCompletionRequestMessageContentPartImage {
                                             image_url,
                                         }))
                                     },
+                                    // tool results are sent as tool messages,
+                                    // which must directly follow the assistant
+                                    // message that called the tools
+                                    anthropic::ContentBlock::ToolResult { tool_use_id, content } => {
+                                        messages.push(openai::ChatCompletionRequestMessage::Tool(
+                                            openai::ChatCompletionRequestToolMessage {
+                                                content: openai::ChatCompletionRequestToolMessageContent::Text(content),
+                                                tool_call_id: tool_use_id,
+                                            },
+                                        ));
+                                        None
+                                    },
                                     anthropic::ContentBlock::ToolUse { .. } |
-                                    anthropic::ContentBlock::ToolResult { .. } |
                                     anthropic::ContentBlock::Thinking { .. } |
                                     anthropic::ContentBlock::RedactedThinking { .. } => {
                                         None
                                     }
                                 }
-                            }).collect();
+                            }).collect::<Vec<_>>();
+                            if blocks.is_empty() {
+                                continue;
+                            }
                             openai::ChatCompletionRequestUserMessageContent::Array(blocks)
                         }
                     };
-                        openai::ChatCompletionRequestMessage::User(
-                            openai::ChatCompletionRequestUserMessage {
-                                content,
-                                name: None,
-                            },
-                        )
-                    }
-                };
-            messages.push(mapped_message);
+                    messages.push(openai::ChatCompletionRequestMessage::User(
+                        openai::ChatCompletionRequestUserMessage {
+                            content,
+                            name: None,
+                        },
+                    ));
+                }
+            }
         }
 
         #[allow(deprecated)]
@@ -309,10 +347,15 @@ impl
         let stop_reason = if openai_message.message.refusal.is_some() {
             Some(anthropic::StopReason::Refusal)
         } else {
-            None
+            openai_message.finish_reason.map(stop_reason)
         };
         let mut content: Vec<anthropic::ContentBlock> = Vec::new();
 
+        // anthropic responses explain the tool calls before making them
+        if let Some(text) = openai_message.message.content {
+            let text = anthropic::ContentBlock::Text { text };
+            content.push(text);
+        }
         if let Some(tool_uses) = openai_message.message.tool_calls {
             for tool_use in tool_uses {
                 if let Ok(input) =
@@ -327,10 +370,6 @@ impl
                 }
             }
         }
-        if let Some(text) = openai_message.message.content {
-            let text = anthropic::ContentBlock::Text { text };
-            content.push(text);
-        }
 
         Ok(anthropic::CreateMessageResponse {
             content,
@@ -400,21 +439,13 @@ impl
                     ) {
                         let input_str =
                             func.arguments.as_deref().unwrap_or("{}");
-                        if let Ok(input_json) = serde_json::from_str(input_str)
-                        {
-                            let tool_use_block =
-                                anthropic::ContentBlock::ToolUse {
-                                    id: id.clone(),
-                                    name: name.clone(),
-                                    input: input_json,
-                                };
-                            content_blocks.push(tool_use_block);
-                        }
-                        // Handle error or default for bad JSON
+                        // default to empty input for bad JSON
+                        let input = serde_json::from_str(input_str)
+                            .unwrap_or_else(|_| serde_json::json!({}));
                         let tool_use_block = anthropic::ContentBlock::ToolUse {
                             id: id.clone(),
                             name: name.clone(),
-                            input: serde_json::json!({}),
+                            input,
                         };
                         content_blocks.push(tool_use_block);
                     }
@@ -447,19 +478,7 @@ impl
 
         // Priority 2: MessageDelta Event (for finish_reason)
         if let Some(finish_reason) = first_choice.finish_reason {
-            let anthropic_stop_reason = match finish_reason {
-                openai::FinishReason::Stop => anthropic::StopReason::EndTurn,
-                openai::FinishReason::Length => {
-                    anthropic::StopReason::MaxTokens
-                }
-                openai::FinishReason::ToolCalls
-                | openai::FinishReason::FunctionCall => {
-                    anthropic::StopReason::ToolUse
-                }
-                openai::FinishReason::ContentFilter => {
-                    anthropic::StopReason::Refusal
-                }
-            };
+            let anthropic_stop_reason = stop_reason(finish_reason);
 
             let stream_usage = value.usage.map_or_else(
                 || anthropic::StreamUsage {
@@ -650,6 +669,31 @@ impl
     }
 }
 
+/// Anthropic image sources are either a URL or the base64 encoded image,
+/// which `OpenAI` expects as a `data:` URL.
+fn image_url(source: anthropic_ai_sdk::types::message::ImageSource) -> String {
+    if source.type_ == "base64" {
+        format!("data:{};base64,{}", source.media_type, source.data)
+    } else {
+        source.data
+    }
+}
+
+fn stop_reason(
+    finish_reason: async_openai::types::FinishReason,
+) -> anthropic_ai_sdk::types::message::StopReason {
+    use anthropic_ai_sdk::types::message::StopReason;
+    use async_openai::types::FinishReason;
+    match finish_reason {
+        FinishReason::Stop => StopReason::EndTurn,
+        FinishReason::Length => StopReason::MaxTokens,
+        FinishReason::ToolCalls | FinishReason::FunctionCall => {
+            StopReason::ToolUse
+        }
+        FinishReason::ContentFilter => StopReason::Refusal,
+    }
+}
+
 pub(super) fn get_error_type(status_code: StatusCode) -> String {
     if status_code == StatusCode::TOO_MANY_REQUESTS {
         "tokens".to_string()
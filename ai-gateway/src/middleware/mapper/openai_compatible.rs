@@ -4,7 +4,13 @@ use http::response::Parts;
 
 use super::{TryConvertStreamData, model::ModelMapper};
 use crate::{
-    endpoints::openai::OpenAICompatibleChatCompletionRequest,
+    endpoints::{
+        google::generate_contents::GeminiApiError,
+        openai::{
+            OpenAICompatibleChatCompletionRequest,
+            OpenAICompatibleEmbeddingRequest,
+        },
+    },
     error::mapper::MapperError,
     middleware::mapper::{TryConvert, TryConvertError},
     types::{model_id::ModelId, provider::InferenceProvider},
@@ -84,6 +90,66 @@ impl
     }
 }
 
+impl
+    TryConvert<
+        async_openai::types::CreateEmbeddingRequest,
+        OpenAICompatibleEmbeddingRequest,
+    > for OpenAICompatibleConverter
+{
+    type Error = MapperError;
+    fn try_convert(
+        &self,
+        mut value: async_openai::types::CreateEmbeddingRequest,
+    ) -> Result<OpenAICompatibleEmbeddingRequest, Self::Error> {
+        let source_model = ModelId::from_str(&value.model)?;
+        let target_model =
+            self.model_mapper.map_model(&source_model, &self.provider)?;
+        tracing::trace!(source_model = ?source_model, target_model = ?target_model, "mapped model");
+        value.model = target_model.to_string();
+        // we always request floats, base64 encoding is applied to the mapped
+        // response if the client asked for it
+        value.encoding_format = None;
+
+        Ok(OpenAICompatibleEmbeddingRequest {
+            provider: self.provider.clone(),
+            inner: value,
+        })
+    }
+}
+
+impl
+    TryConvert<
+        async_openai::types::CreateEmbeddingResponse,
+        async_openai::types::CreateEmbeddingResponse,
+    > for OpenAICompatibleConverter
+{
+    type Error = MapperError;
+    fn try_convert(
+        &self,
+        value: async_openai::types::CreateEmbeddingResponse,
+    ) -> Result<async_openai::types::CreateEmbeddingResponse, Self::Error> {
+        Ok(value)
+    }
+}
+
+// embeddings are never streamed, so this is never called
+impl
+    TryConvertStreamData<
+        async_openai::types::CreateEmbeddingResponse,
+        async_openai::types::CreateEmbeddingResponse,
+    > for OpenAICompatibleConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: async_openai::types::CreateEmbeddingResponse,
+    ) -> Result<Option<async_openai::types::CreateEmbeddingResponse>, Self::Error>
+    {
+        Ok(Some(value))
+    }
+}
+
 impl
     TryConvertError<
         async_openai::error::WrappedError,
@@ -100,3 +166,36 @@ impl
         Ok(value)
     }
 }
+
+impl TryConvertError<GeminiApiError, async_openai::error::WrappedError>
+    for OpenAICompatibleConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_error(
+        &self,
+        resp_parts: &Parts,
+        value: GeminiApiError,
+    ) -> Result<async_openai::error::WrappedError, Self::Error> {
+        Ok(openai_error_from_gemini(resp_parts, value))
+    }
+}
+
+/// Gemini errors are either already OpenAI shaped, or Google API errors
+/// whose message we keep.
+pub(super) fn openai_error_from_gemini(
+    resp_parts: &Parts,
+    value: GeminiApiError,
+) -> async_openai::error::WrappedError {
+    let google_error = match value {
+        GeminiApiError::OpenAI(error) => return error,
+        GeminiApiError::Google(response) => Some(response.error),
+        GeminiApiError::GoogleList(responses) => {
+            responses.into_iter().next().map(|response| response.error)
+        }
+    };
+    super::openai_error_from_status(
+        resp_parts.status,
+        google_error.map(|error| error.message),
+    )
+}
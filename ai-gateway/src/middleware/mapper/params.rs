@@ -0,0 +1,373 @@
+//! Adjusts the parameters of mapped chat requests to what the target model
+//! supports, so that e.g. a request written for `gpt-4o` can be sent to
+//! `o3-mini` without being rejected.
+//!
+//! What each model supports is looked up in [`CAPABILITIES`]:
+//!
+//! - OpenAI reasoning models take `max_completion_tokens` rather than
+//!   `max_tokens`, and reject sampling parameters such as `temperature`.
+//! - Anthropic models with extended thinking get a `thinking` block with a
+//!   budget derived from the unified `reasoning_effort`, which Anthropic
+//!   doesn't accept as is.
+//! - Models without reasoning support don't get `reasoning_effort`.
+//!
+//! Every adjustment is listed in the `helicone-params-adjusted` response
+//! header, e.g. `max_tokens->max_completion_tokens, -temperature`, where a
+//! `-` prefix means the parameter was dropped. Routers can opt out with
+//! [`RouterConfig::strict_params`](crate::config::router::RouterConfig::strict_params).
+use std::fmt;
+
+use bytes::Bytes;
+use http::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    endpoints::{
+        ApiEndpoint, anthropic::Anthropic, azure::Azure, openai::OpenAI,
+    },
+    error::{api::ApiError, internal::InternalError},
+    types::model_id::ModelId,
+};
+
+pub(crate) const PARAMS_ADJUSTED_HEADER: HeaderName =
+    HeaderName::from_static("helicone-params-adjusted");
+
+/// The smallest thinking budget Anthropic accepts.
+const MIN_THINKING_BUDGET: u64 = 1024;
+
+/// Parameters OpenAI reasoning models reject.
+const UNSUPPORTED_REASONING_PARAMS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "presence_penalty",
+    "frequency_penalty",
+    "logprobs",
+    "top_logprobs",
+    "logit_bias",
+];
+
+/// Parameters Anthropic rejects alongside extended thinking.
+const UNSUPPORTED_THINKING_PARAMS: &[&str] = &["temperature", "top_p", "top_k"];
+
+/// The request formats parameters are adjusted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    OpenAI,
+    Anthropic,
+}
+
+/// What a family of models supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Capabilities {
+    reasoning: bool,
+}
+
+const REASONING: Capabilities = Capabilities { reasoning: true };
+
+/// Model name prefixes per request format. Models which aren't listed have
+/// no reasoning support.
+const CAPABILITIES: &[(Format, &str, Capabilities)] = &[
+    (Format::OpenAI, "o1", REASONING),
+    (Format::OpenAI, "o3", REASONING),
+    (Format::OpenAI, "o4", REASONING),
+    (Format::OpenAI, "gpt-5", REASONING),
+    (Format::Anthropic, "claude-3-7-sonnet", REASONING),
+    (Format::Anthropic, "claude-sonnet-4", REASONING),
+    (Format::Anthropic, "claude-opus-4", REASONING),
+];
+
+fn capabilities(format: Format, model: &ModelId) -> Capabilities {
+    let model = model.to_string();
+    // some providers prefix models with their publisher, e.g. `openai/o3`
+    let name = model.rsplit('/').next().unwrap_or(&model);
+    CAPABILITIES
+        .iter()
+        .find(|(f, prefix, _)| *f == format && name.starts_with(prefix))
+        .map_or(Capabilities { reasoning: false }, |(_, _, caps)| *caps)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Adjustment {
+    Renamed {
+        from: &'static str,
+        to: &'static str,
+    },
+    Dropped(&'static str),
+}
+
+impl fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Renamed { from, to } => write!(f, "{from}->{to}"),
+            Self::Dropped(param) => write!(f, "-{param}"),
+        }
+    }
+}
+
+/// The unified parameters of the request before it was mapped.
+#[derive(Debug, Default, Deserialize)]
+struct SourceParams {
+    reasoning_effort: Option<String>,
+}
+
+/// Adjusts the mapped `target_bytes` for `model`, returning them along with
+/// the value of the [`PARAMS_ADJUSTED_HEADER`] if anything was adjusted.
+pub(crate) fn normalize(
+    source_bytes: &[u8],
+    target_endpoint: &ApiEndpoint,
+    model: Option<&ModelId>,
+    target_bytes: Bytes,
+) -> Result<(Bytes, Option<HeaderValue>), ApiError> {
+    let format = match target_endpoint {
+        ApiEndpoint::OpenAI(OpenAI::ChatCompletions(_))
+        | ApiEndpoint::Azure(Azure::ChatCompletions(_)) => Format::OpenAI,
+        ApiEndpoint::Anthropic(Anthropic::Messages(_)) => Format::Anthropic,
+        _ => return Ok((target_bytes, None)),
+    };
+    let Some(model) = model else {
+        return Ok((target_bytes, None));
+    };
+    let Ok(Value::Object(mut target)) = serde_json::from_slice(&target_bytes)
+    else {
+        return Ok((target_bytes, None));
+    };
+
+    let capabilities = capabilities(format, model);
+    let adjustments = match format {
+        Format::OpenAI => normalize_openai(&mut target, capabilities),
+        Format::Anthropic => {
+            let source: SourceParams =
+                serde_json::from_slice(source_bytes).unwrap_or_default();
+            normalize_anthropic(
+                &mut target,
+                capabilities,
+                source.reasoning_effort.as_deref(),
+            )
+        }
+    };
+    if adjustments.is_empty() {
+        return Ok((target_bytes, None));
+    }
+
+    tracing::debug!(?adjustments, %model, "adjusted request parameters");
+    let header = adjustments
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let body =
+        serde_json::to_vec(&target).map_err(|e| InternalError::Serialize {
+            ty: std::any::type_name::<Value>(),
+            error: e,
+        })?;
+    Ok((Bytes::from(body), HeaderValue::from_str(&header).ok()))
+}
+
+fn normalize_openai(
+    target: &mut Map<String, Value>,
+    capabilities: Capabilities,
+) -> Vec<Adjustment> {
+    let mut adjustments = Vec::new();
+    if !capabilities.reasoning {
+        if remove(target, "reasoning_effort") {
+            adjustments.push(Adjustment::Dropped("reasoning_effort"));
+        }
+        return adjustments;
+    }
+
+    if let Some(max_tokens) = target.remove("max_tokens")
+        && !max_tokens.is_null()
+    {
+        if target
+            .get("max_completion_tokens")
+            .is_none_or(Value::is_null)
+        {
+            target.insert("max_completion_tokens".to_string(), max_tokens);
+            adjustments.push(Adjustment::Renamed {
+                from: "max_tokens",
+                to: "max_completion_tokens",
+            });
+        } else {
+            adjustments.push(Adjustment::Dropped("max_tokens"));
+        }
+    }
+    for param in UNSUPPORTED_REASONING_PARAMS {
+        if remove(target, param) {
+            adjustments.push(Adjustment::Dropped(param));
+        }
+    }
+    adjustments
+}
+
+fn normalize_anthropic(
+    target: &mut Map<String, Value>,
+    capabilities: Capabilities,
+    reasoning_effort: Option<&str>,
+) -> Vec<Adjustment> {
+    let Some(reasoning_effort) = reasoning_effort else {
+        return Vec::new();
+    };
+    let max_tokens = target.get("max_tokens").and_then(Value::as_u64);
+    let budget_tokens = max_tokens
+        .filter(|_| capabilities.reasoning)
+        .and_then(|max_tokens| thinking_budget(reasoning_effort, max_tokens));
+    let Some(budget_tokens) = budget_tokens else {
+        return vec![Adjustment::Dropped("reasoning_effort")];
+    };
+
+    target.insert(
+        "thinking".to_string(),
+        json!({ "type": "enabled", "budget_tokens": budget_tokens }),
+    );
+    let mut adjustments = vec![Adjustment::Renamed {
+        from: "reasoning_effort",
+        to: "thinking.budget_tokens",
+    }];
+    for param in UNSUPPORTED_THINKING_PARAMS {
+        if remove(target, param) {
+            adjustments.push(Adjustment::Dropped(param));
+        }
+    }
+    adjustments
+}
+
+/// The share of `max_tokens` spent thinking, the inverse of how Anthropic
+/// thinking budgets are mapped to a `reasoning_effort`. `None` if the budget
+/// wouldn't leave room for a response.
+fn thinking_budget(reasoning_effort: &str, max_tokens: u64) -> Option<u64> {
+    let budget = match reasoning_effort {
+        "minimal" | "low" => max_tokens / 4,
+        "medium" => max_tokens / 2,
+        "high" => max_tokens / 5 * 4,
+        _ => return None,
+    };
+    let budget = budget.max(MIN_THINKING_BUDGET);
+    (budget < max_tokens).then_some(budget)
+}
+
+/// Removes `param`, returning whether it was set.
+fn remove(target: &mut Map<String, Value>, param: &str) -> bool {
+    target.remove(param).is_some_and(|value| !value.is_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::endpoints::{
+        anthropic::Messages, openai::ChatCompletions as OpenAIChatCompletions,
+    };
+
+    fn normalized(
+        source: &Value,
+        target_endpoint: &ApiEndpoint,
+        model: &str,
+        target: &Value,
+    ) -> (Value, Option<HeaderValue>) {
+        let model = ModelId::from_str(model).unwrap();
+        let (body, header) = normalize(
+            &serde_json::to_vec(source).unwrap(),
+            target_endpoint,
+            Some(&model),
+            Bytes::from(serde_json::to_vec(target).unwrap()),
+        )
+        .unwrap();
+        (serde_json::from_slice(&body).unwrap(), header)
+    }
+
+    #[test]
+    fn openai_reasoning_models_get_max_completion_tokens() {
+        let request = json!({
+            "model": "o3-mini",
+            "max_tokens": 100,
+            "temperature": 0.5,
+            "messages": [],
+        });
+        let (body, header) = normalized(
+            &request,
+            &ApiEndpoint::OpenAI(OpenAI::ChatCompletions(
+                OpenAIChatCompletions,
+            )),
+            "openai/o3-mini",
+            &request,
+        );
+        assert_eq!(
+            body,
+            json!({
+                "model": "o3-mini",
+                "max_completion_tokens": 100,
+                "messages": [],
+            })
+        );
+        assert_eq!(
+            header.unwrap(),
+            "max_tokens->max_completion_tokens, -temperature"
+        );
+    }
+
+    #[test]
+    fn other_openai_models_are_left_alone() {
+        let request = json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 100,
+            "temperature": 0.5,
+            "messages": [],
+        });
+        let (body, header) = normalized(
+            &request,
+            &ApiEndpoint::OpenAI(OpenAI::ChatCompletions(
+                OpenAIChatCompletions,
+            )),
+            "openai/gpt-4o-mini",
+            &request,
+        );
+        assert_eq!(body, request);
+        assert!(header.is_none());
+    }
+
+    #[test]
+    fn reasoning_effort_is_mapped_to_a_thinking_budget() {
+        let source = json!({
+            "model": "anthropic/claude-3-7-sonnet",
+            "reasoning_effort": "medium",
+            "messages": [],
+        });
+        let target = json!({
+            "model": "claude-3-7-sonnet-latest",
+            "max_tokens": 4000,
+            "temperature": 0.5,
+            "messages": [],
+        });
+        let (body, header) = normalized(
+            &source,
+            &ApiEndpoint::Anthropic(Anthropic::Messages(Messages)),
+            "anthropic/claude-3-7-sonnet-latest",
+            &target,
+        );
+        assert_eq!(
+            body["thinking"],
+            json!({ "type": "enabled", "budget_tokens": 2000 })
+        );
+        assert!(body.get("temperature").is_none());
+        assert_eq!(
+            header.unwrap(),
+            "reasoning_effort->thinking.budget_tokens, -temperature"
+        );
+    }
+
+    #[test]
+    fn reasoning_effort_is_dropped_without_room_to_think() {
+        let source = json!({ "reasoning_effort": "high" });
+        let target = json!({ "max_tokens": 1000, "messages": [] });
+        let (body, header) = normalized(
+            &source,
+            &ApiEndpoint::Anthropic(Anthropic::Messages(Messages)),
+            "anthropic/claude-sonnet-4-0",
+            &target,
+        );
+        assert_eq!(body, target);
+        assert_eq!(header.unwrap(), "-reasoning_effort");
+    }
+}
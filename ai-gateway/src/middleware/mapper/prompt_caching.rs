@@ -0,0 +1,438 @@
+//! Preserves Anthropic prompt caching breakpoints when mapping chat
+//! completions requests to the messages API.
+//!
+//! Clients mark breakpoints with a `cache_control` field on chat completions
+//! content parts, messages or tools. Our request types have no such field, so
+//! the breakpoints would be dropped by the typed conversion, and are instead
+//! read from the raw request and re-applied to the mapped request by matching
+//! the marked content. Converters for other providers drop the field as usual.
+//!
+//! Clients which don't mark breakpoints themselves can send the
+//! `helicone-prompt-cache: auto` header to cache the system prompt and the
+//! last large user message. Other messages API fields can be sent in an
+//! `anthropic` object of the chat completions request, which is merged into
+//! the mapped request.
+//!
+//! The cache usage Anthropic reports is added to the mapped response's usage,
+//! see [`CacheUsage`].
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, response::Parts};
+use rustc_hash::FxHashMap as HashMap;
+use serde_json::{Value, json};
+
+use super::EndpointConverter;
+use crate::{
+    error::{api::ApiError, internal::InternalError},
+    types::extensions::MapperContext,
+};
+
+const CACHE_CONTROL: &str = "cache_control";
+
+/// Fields of the chat completions request which are merged into the mapped
+/// messages API request.
+const VENDOR_EXTENSION: &str = "anthropic";
+
+/// Set to `auto` to add breakpoints to requests mapped to Anthropic.
+const PROMPT_CACHE_HEADER: HeaderName =
+    HeaderName::from_static("helicone-prompt-cache");
+
+/// Anthropic doesn't cache prompts shorter than 1024 tokens, which is roughly
+/// this many characters.
+const MIN_CACHEABLE_CHARS: usize = 4096;
+
+/// Wraps the converter for requests to Anthropic.
+pub struct CacheControlConverter<C> {
+    inner: C,
+}
+
+impl<C> CacheControlConverter<C> {
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+impl<C: EndpointConverter> EndpointConverter for CacheControlConverter<C> {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        let request = serde_json::from_slice::<Value>(&req_body_bytes).ok();
+        let breakpoints = request
+            .as_ref()
+            .map(Breakpoints::from_request)
+            .unwrap_or_default();
+        let vendor_extension =
+            match request.as_ref().and_then(|r| r.get(VENDOR_EXTENSION)) {
+                Some(Value::Object(fields)) => Some(fields.clone()),
+                _ => None,
+            };
+        let (target_bytes, mapper_ctx) =
+            self.inner.convert_req_body(req_body_bytes)?;
+        if breakpoints.is_empty() && vendor_extension.is_none() {
+            return Ok((target_bytes, mapper_ctx));
+        }
+
+        let mut target: Value =
+            serde_json::from_slice(&target_bytes).map_err(|e| {
+                InternalError::Deserialize {
+                    ty: std::any::type_name::<Value>(),
+                    error: e,
+                }
+            })?;
+        breakpoints.apply(&mut target);
+        if let Some(fields) = vendor_extension
+            && let Some(target) = target.as_object_mut()
+        {
+            target.extend(fields);
+        }
+        Ok((to_bytes(&target)?, mapper_ctx))
+    }
+
+    fn convert_resp_body(
+        &self,
+        resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        let cache_usage = CacheUsage::from_response(&resp_body_bytes);
+        let converted = self.inner.convert_resp_body(
+            resp_parts,
+            resp_body_bytes,
+            is_stream,
+        )?;
+        match (converted, cache_usage) {
+            (Some(converted), Some(cache_usage)) => {
+                cache_usage.add_to_usage(&converted).map(Some)
+            }
+            (converted, _) => Ok(converted),
+        }
+    }
+}
+
+/// Whether the client asked for breakpoints to be added automatically.
+pub(crate) fn wants_automatic_breakpoints(headers: &HeaderMap) -> bool {
+    headers
+        .get(PROMPT_CACHE_HEADER)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"auto"))
+}
+
+/// Marks the system prompt and the last large user message of a mapped
+/// messages API request as breakpoints, unless the client already marked any.
+pub(crate) fn add_automatic_breakpoints(
+    target_bytes: Bytes,
+) -> Result<Bytes, ApiError> {
+    let Ok(mut target) = serde_json::from_slice::<Value>(&target_bytes) else {
+        return Ok(target_bytes);
+    };
+    if has_breakpoint(&target) {
+        return Ok(target_bytes);
+    }
+    let ephemeral = json!({ "type": "ephemeral" });
+
+    if let Some(system) = target.get_mut("system") {
+        match system {
+            Value::String(text) if !text.is_empty() => {
+                *system = json!([text_block(text, ephemeral.clone())]);
+            }
+            Value::Array(blocks) => {
+                if let Some(block) = blocks.last_mut() {
+                    block[CACHE_CONTROL] = ephemeral.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let messages = target.get_mut("messages").and_then(Value::as_array_mut);
+    let last_large_user_message = messages.and_then(|messages| {
+        messages.iter_mut().rev().find(|message| {
+            message.get("role").and_then(Value::as_str) == Some("user")
+                && message_text_len(message) >= MIN_CACHEABLE_CHARS
+        })
+    });
+    if let Some(message) = last_large_user_message
+        && let Some(content) = message.get_mut("content")
+    {
+        match content {
+            Value::String(text) => {
+                *content = json!([text_block(text, ephemeral)]);
+            }
+            Value::Array(blocks) => {
+                if let Some(block) = blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| block_text(block).is_some())
+                {
+                    block[CACHE_CONTROL] = ephemeral;
+                }
+            }
+            _ => {}
+        }
+    }
+    to_bytes(&target)
+}
+
+fn has_breakpoint(value: &Value) -> bool {
+    match value {
+        Value::Object(fields) => fields
+            .iter()
+            .any(|(key, value)| key == CACHE_CONTROL || has_breakpoint(value)),
+        Value::Array(values) => values.iter().any(has_breakpoint),
+        _ => false,
+    }
+}
+
+fn message_text_len(message: &Value) -> usize {
+    match message.get("content") {
+        Some(Value::String(text)) => text.len(),
+        Some(Value::Array(blocks)) => {
+            blocks.iter().filter_map(block_text).map(str::len).sum()
+        }
+        _ => 0,
+    }
+}
+
+fn to_bytes(value: &Value) -> Result<Bytes, ApiError> {
+    let bytes =
+        serde_json::to_vec(value).map_err(|e| InternalError::Serialize {
+            ty: std::any::type_name::<Value>(),
+            error: e,
+        })?;
+    Ok(Bytes::from(bytes))
+}
+
+/// The prompt tokens Anthropic wrote to or read from its cache, which it
+/// reports separately from `input_tokens`.
+///
+/// Mapped responses include them in `prompt_tokens`, as OpenAI does, report
+/// the tokens read in `prompt_tokens_details.cached_tokens` and also keep
+/// Anthropic's fields as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub creation_input_tokens: u64,
+    pub read_input_tokens: u64,
+}
+
+impl CacheUsage {
+    /// The cache usage of a messages API response or stream event, if it
+    /// reports any.
+    #[must_use]
+    pub fn from_response(body: &[u8]) -> Option<Self> {
+        let response = serde_json::from_slice::<Value>(body).ok()?;
+        let usage = response
+            .get("usage")
+            .or_else(|| response.pointer("/message/usage"))?;
+        let creation = usage
+            .get("cache_creation_input_tokens")
+            .and_then(Value::as_u64);
+        let read = usage.get("cache_read_input_tokens").and_then(Value::as_u64);
+        if creation.is_none() && read.is_none() {
+            return None;
+        }
+        Some(Self {
+            creation_input_tokens: creation.unwrap_or_default(),
+            read_input_tokens: read.unwrap_or_default(),
+        })
+    }
+
+    /// Like [`CacheUsage::from_response`], for a logged response body, which
+    /// for streams is every event. Anthropic's usage is cumulative, so the
+    /// last event which reports any is used.
+    #[must_use]
+    pub fn from_logged_response(body: &[u8]) -> Option<Self> {
+        if let Some(cache_usage) = Self::from_response(body) {
+            return Some(cache_usage);
+        }
+        std::str::from_utf8(body)
+            .ok()?
+            .lines()
+            .rev()
+            .find_map(|line| {
+                let data = line.strip_prefix("data:").unwrap_or(line).trim();
+                Self::from_response(data.as_bytes())
+            })
+    }
+
+    fn add_to_usage(self, mapped_bytes: &Bytes) -> Result<Bytes, ApiError> {
+        let Ok(mut mapped) = serde_json::from_slice::<Value>(mapped_bytes)
+        else {
+            return Ok(mapped_bytes.clone());
+        };
+        let Some(usage) =
+            mapped.get_mut("usage").and_then(Value::as_object_mut)
+        else {
+            return Ok(mapped_bytes.clone());
+        };
+        let cached = self.creation_input_tokens + self.read_input_tokens;
+        for field in ["prompt_tokens", "total_tokens"] {
+            if let Some(tokens) = usage.get(field).and_then(Value::as_u64) {
+                usage.insert(field.to_string(), json!(tokens + cached));
+            }
+        }
+        usage.insert(
+            "prompt_tokens_details".to_string(),
+            json!({ "cached_tokens": self.read_input_tokens }),
+        );
+        usage.insert(
+            "cache_creation_input_tokens".to_string(),
+            json!(self.creation_input_tokens),
+        );
+        usage.insert(
+            "cache_read_input_tokens".to_string(),
+            json!(self.read_input_tokens),
+        );
+        to_bytes(&mapped)
+    }
+}
+
+/// The `cache_control` values in a chat completions request.
+#[derive(Debug, Default)]
+struct Breakpoints {
+    system: Option<Value>,
+    /// Marked text, in request order.
+    content: Vec<(String, Value)>,
+    /// Marked tools, by function name.
+    tools: HashMap<String, Value>,
+}
+
+impl Breakpoints {
+    fn from_request(request: &Value) -> Self {
+        let mut breakpoints = Self::default();
+        let messages = request.get("messages").and_then(Value::as_array);
+        for message in messages.into_iter().flatten() {
+            let message_breakpoint = message.get(CACHE_CONTROL);
+            let role = message.get("role").and_then(Value::as_str);
+            // the system prompt is a single block once mapped
+            if matches!(role, Some("system" | "developer")) {
+                let part_breakpoint = message
+                    .get("content")
+                    .and_then(Value::as_array)
+                    .and_then(|parts| {
+                        parts.iter().rev().find_map(|p| p.get(CACHE_CONTROL))
+                    });
+                if let Some(breakpoint) = part_breakpoint.or(message_breakpoint)
+                {
+                    breakpoints.system = Some(breakpoint.clone());
+                }
+                continue;
+            }
+            match message.get("content") {
+                Some(Value::String(text)) => {
+                    if let Some(breakpoint) = message_breakpoint {
+                        breakpoints
+                            .content
+                            .push((text.clone(), breakpoint.clone()));
+                    }
+                }
+                Some(Value::Array(parts)) => {
+                    for (index, part) in parts.iter().enumerate() {
+                        // a breakpoint on the message applies to its last
+                        // part, as it does for Anthropic
+                        let breakpoint =
+                            part.get(CACHE_CONTROL).or_else(|| {
+                                message_breakpoint
+                                    .filter(|_| index == parts.len() - 1)
+                            });
+                        let text = part.get("text").and_then(Value::as_str);
+                        if let (Some(text), Some(breakpoint)) =
+                            (text, breakpoint)
+                        {
+                            breakpoints
+                                .content
+                                .push((text.to_string(), breakpoint.clone()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let tools = request.get("tools").and_then(Value::as_array);
+        for tool in tools.into_iter().flatten() {
+            let name = tool.pointer("/function/name").and_then(Value::as_str);
+            if let (Some(name), Some(breakpoint)) =
+                (name, tool.get(CACHE_CONTROL))
+            {
+                breakpoints
+                    .tools
+                    .insert(name.to_string(), breakpoint.clone());
+            }
+        }
+        breakpoints
+    }
+
+    fn is_empty(&self) -> bool {
+        self.system.is_none()
+            && self.content.is_empty()
+            && self.tools.is_empty()
+    }
+
+    /// Adds the breakpoints to a mapped messages API request.
+    fn apply(self, target: &mut Value) {
+        if let Some(breakpoint) = self.system
+            && let Some(system) = target.get_mut("system")
+            && let Value::String(text) = system
+        {
+            *system = json!([text_block(text, breakpoint)]);
+        }
+
+        let mut content = self.content.into_iter().peekable();
+        let messages = target.get_mut("messages").and_then(Value::as_array_mut);
+        for message in messages.into_iter().flatten() {
+            let Some(message_content) = message.get_mut("content") else {
+                continue;
+            };
+            match message_content {
+                Value::String(text) => {
+                    if let Some(breakpoint) =
+                        content.next_if(|(marked, _)| marked == text)
+                    {
+                        *message_content =
+                            json!([text_block(text, breakpoint.1)]);
+                    }
+                }
+                Value::Array(blocks) => {
+                    for block in blocks {
+                        let Some(text) = block_text(block) else {
+                            continue;
+                        };
+                        if let Some((_, breakpoint)) =
+                            content.next_if(|(marked, _)| marked == text)
+                        {
+                            block[CACHE_CONTROL] = breakpoint;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let tools = target.get_mut("tools").and_then(Value::as_array_mut);
+        for tool in tools.into_iter().flatten() {
+            let breakpoint = tool
+                .get("name")
+                .and_then(Value::as_str)
+                .and_then(|name| self.tools.get(name));
+            if let Some(breakpoint) = breakpoint {
+                tool[CACHE_CONTROL] = breakpoint.clone();
+            }
+        }
+    }
+}
+
+fn text_block(text: &str, breakpoint: Value) -> Value {
+    json!({
+        "type": "text",
+        "text": text,
+        "cache_control": breakpoint,
+    })
+}
+
+/// The text of a mapped content block which a breakpoint may be matched to.
+fn block_text(block: &Value) -> Option<&str> {
+    match block.get("type").and_then(Value::as_str) {
+        Some("text") => block.get("text").and_then(Value::as_str),
+        Some("tool_result") => block.get("content").and_then(Value::as_str),
+        _ => None,
+    }
+}
@@ -3,16 +3,33 @@ use std::sync::Arc;
 use rustc_hash::FxHashMap as HashMap;
 
 use super::{
-    EndpointConverter, TypedEndpointConverter, anthropic::AnthropicConverter,
-    model::ModelMapper, openai::OpenAIConverter,
+    EndpointConverter, TypedEndpointConverter,
+    anthropic::AnthropicConverter,
+    audio::TranscriptionConverter,
+    azure::AzureConverter,
+    deepseek::DeepSeekConverter,
+    groq::GroqConverter,
+    mistral::MistralConverter,
+    model::ModelMapper,
+    openai::OpenAIConverter,
     openai_compatible::OpenAICompatibleConverter,
+    prompt_caching::CacheControlConverter,
+    responses::{
+        OpenAIPassthroughConverter, ResponsesConverter,
+        ResponsesToChatConverter,
+    },
+    vertex::VertexConverter,
 };
 use crate::{
     endpoints::{
-        self, ApiEndpoint, anthropic::Anthropic, bedrock::Bedrock,
-        google::Google, ollama::Ollama, openai::OpenAI,
+        self, ApiEndpoint, anthropic::Anthropic, azure::Azure,
+        bedrock::Bedrock, cohere::Cohere, google::Google, ollama::Ollama,
+        openai::OpenAI, vertex::Vertex,
+    },
+    middleware::mapper::{
+        bedrock::BedrockConverter, cohere::CohereConverter,
+        gemini::GeminiConverter, ollama::OllamaConverter,
     },
-    middleware::mapper::{bedrock::BedrockConverter, ollama::OllamaConverter},
     types::provider::InferenceProvider,
 };
 
@@ -63,7 +80,7 @@ struct EndpointConverterRegistryInner {
     /// we'll want to add another level here.
     converters: HashMap<
         RegistryKey,
-        Box<dyn EndpointConverter + Send + Sync + 'static>,
+        Arc<dyn EndpointConverter + Send + Sync + 'static>,
     >,
 }
 
@@ -86,26 +103,25 @@ impl EndpointConverterRegistryInner {
             ApiEndpoint::OpenAI(OpenAI::chat_completions()),
             ApiEndpoint::Anthropic(Anthropic::messages()),
         );
-        let converter =
-            TypedEndpointConverter::<
-                endpoints::openai::ChatCompletions,
-                endpoints::anthropic::Messages,
-                AnthropicConverter,
-            >::new(AnthropicConverter::new(model_mapper.clone()));
+        let converter = CacheControlConverter::new(TypedEndpointConverter::<
+            endpoints::openai::ChatCompletions,
+            endpoints::anthropic::Messages,
+            AnthropicConverter,
+        >::new(
+            AnthropicConverter::new(model_mapper.clone()),
+        ));
         registry.register_converter(key, converter);
 
         let key = RegistryKey::new(
             ApiEndpoint::OpenAI(OpenAI::chat_completions()),
             ApiEndpoint::Google(Google::generate_contents()),
         );
-        let converter = TypedEndpointConverter::<
-            endpoints::openai::ChatCompletions,
-            endpoints::google::GenerateContents,
-            OpenAICompatibleConverter,
-        >::new(OpenAICompatibleConverter::new(
-            InferenceProvider::GoogleGemini,
-            model_mapper.clone(),
-        ));
+        let converter =
+            TypedEndpointConverter::<
+                endpoints::openai::ChatCompletions,
+                endpoints::google::GenerateContents,
+                GeminiConverter,
+            >::new(GeminiConverter::new(model_mapper.clone()));
         registry.register_converter(key, converter);
 
         let key = RegistryKey::new(
@@ -146,61 +162,103 @@ impl EndpointConverterRegistryInner {
 
         registry.register_converter(key, converter);
 
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::chat_completions()),
+            ApiEndpoint::Cohere(Cohere::chat()),
+        );
+        let converter =
+            TypedEndpointConverter::<
+                endpoints::openai::ChatCompletions,
+                endpoints::cohere::Chat,
+                CohereConverter,
+            >::new(CohereConverter::new(model_mapper.clone()));
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::chat_completions()),
+            ApiEndpoint::Azure(Azure::chat_completions()),
+        );
+        let converter =
+            TypedEndpointConverter::<
+                endpoints::openai::ChatCompletions,
+                endpoints::azure::ChatCompletions,
+                AzureConverter,
+            >::new(AzureConverter::new(model_mapper.clone()));
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::chat_completions()),
+            ApiEndpoint::Vertex(Vertex::chat_completions()),
+        );
+        let converter =
+            TypedEndpointConverter::<
+                endpoints::openai::ChatCompletions,
+                endpoints::vertex::ChatCompletions,
+                VertexConverter,
+            >::new(VertexConverter::new(model_mapper.clone()));
+        registry.register_converter(key, converter);
+
         let key = RegistryKey::new(
             ApiEndpoint::OpenAI(OpenAI::chat_completions()),
             ApiEndpoint::OpenAICompatible {
-                provider: InferenceProvider::Named("mistral".into()),
+                provider: InferenceProvider::Mistral,
                 openai_endpoint: OpenAI::chat_completions(),
             },
         );
-        let converter = TypedEndpointConverter::<
+        let converter = MistralConverter::new(TypedEndpointConverter::<
             endpoints::openai::ChatCompletions,
             endpoints::openai::OpenAICompatibleChatCompletions,
             OpenAICompatibleConverter,
-        >::new(OpenAICompatibleConverter::new(
-            InferenceProvider::Named("mistral".into()),
-            model_mapper.clone(),
+        >::new(
+            OpenAICompatibleConverter::new(
+                InferenceProvider::Mistral,
+                model_mapper.clone(),
+            ),
         ));
         registry.register_converter(key, converter);
 
         let key = RegistryKey::new(
             ApiEndpoint::OpenAI(OpenAI::chat_completions()),
             ApiEndpoint::OpenAICompatible {
-                provider: InferenceProvider::Named("groq".into()),
+                provider: InferenceProvider::Groq,
                 openai_endpoint: OpenAI::chat_completions(),
             },
         );
-        let converter = TypedEndpointConverter::<
+        let converter = GroqConverter::new(TypedEndpointConverter::<
             endpoints::openai::ChatCompletions,
             endpoints::openai::OpenAICompatibleChatCompletions,
             OpenAICompatibleConverter,
-        >::new(OpenAICompatibleConverter::new(
-            InferenceProvider::Named("groq".into()),
-            model_mapper.clone(),
+        >::new(
+            OpenAICompatibleConverter::new(
+                InferenceProvider::Groq,
+                model_mapper.clone(),
+            ),
         ));
         registry.register_converter(key, converter);
 
         let key = RegistryKey::new(
             ApiEndpoint::OpenAI(OpenAI::chat_completions()),
             ApiEndpoint::OpenAICompatible {
-                provider: InferenceProvider::Named("deepseek".into()),
+                provider: InferenceProvider::DeepSeek,
                 openai_endpoint: OpenAI::chat_completions(),
             },
         );
-        let converter = TypedEndpointConverter::<
+        let converter = DeepSeekConverter::new(TypedEndpointConverter::<
             endpoints::openai::ChatCompletions,
             endpoints::openai::OpenAICompatibleChatCompletions,
             OpenAICompatibleConverter,
-        >::new(OpenAICompatibleConverter::new(
-            InferenceProvider::Named("deepseek".into()),
-            model_mapper.clone(),
+        >::new(
+            OpenAICompatibleConverter::new(
+                InferenceProvider::DeepSeek,
+                model_mapper.clone(),
+            ),
         ));
         registry.register_converter(key, converter);
 
         let key = RegistryKey::new(
             ApiEndpoint::OpenAI(OpenAI::chat_completions()),
             ApiEndpoint::OpenAICompatible {
-                provider: InferenceProvider::Named("xai".into()),
+                provider: InferenceProvider::XAI,
                 openai_endpoint: OpenAI::chat_completions(),
             },
         );
@@ -209,7 +267,7 @@ impl EndpointConverterRegistryInner {
             endpoints::openai::OpenAICompatibleChatCompletions,
             OpenAICompatibleConverter,
         >::new(OpenAICompatibleConverter::new(
-            InferenceProvider::Named("xai".into()),
+            InferenceProvider::XAI,
             model_mapper.clone(),
         ));
         registry.register_converter(key, converter);
@@ -231,6 +289,103 @@ impl EndpointConverterRegistryInner {
         ));
         registry.register_converter(key, converter);
 
+        // responses API requests can be served by any provider we can serve
+        // chat completions requests with
+        let chat_converters = registry
+            .converters
+            .iter()
+            .filter(|(key, _)| {
+                key.source_endpoint
+                    == ApiEndpoint::OpenAI(OpenAI::chat_completions())
+            })
+            .map(|(key, converter)| {
+                (key.target_endpoint.clone(), converter.clone())
+            })
+            .collect::<Vec<_>>();
+        for (target_endpoint, chat_converter) in chat_converters {
+            let key = RegistryKey::new(
+                ApiEndpoint::OpenAI(OpenAI::responses()),
+                target_endpoint,
+            );
+            registry.register_converter(
+                key,
+                ResponsesToChatConverter::new(chat_converter),
+            );
+        }
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::chat_completions()),
+            ApiEndpoint::OpenAI(OpenAI::responses()),
+        );
+        let converter =
+            TypedEndpointConverter::<
+                endpoints::openai::ChatCompletions,
+                endpoints::openai::Responses,
+                ResponsesConverter,
+            >::new(ResponsesConverter::new(model_mapper.clone()));
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::responses()),
+            ApiEndpoint::OpenAI(OpenAI::responses()),
+        );
+        let converter = OpenAIPassthroughConverter::new(model_mapper.clone());
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::embeddings()),
+            ApiEndpoint::OpenAI(OpenAI::embeddings()),
+        );
+        let converter = OpenAIPassthroughConverter::new(model_mapper.clone());
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::image_generations()),
+            ApiEndpoint::OpenAI(OpenAI::image_generations()),
+        );
+        let converter = OpenAIPassthroughConverter::new(model_mapper.clone());
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::audio_transcriptions()),
+            ApiEndpoint::OpenAI(OpenAI::audio_transcriptions()),
+        );
+        let converter = TranscriptionConverter::new(model_mapper.clone());
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::audio_speech()),
+            ApiEndpoint::OpenAI(OpenAI::audio_speech()),
+        );
+        let converter = OpenAIPassthroughConverter::new(model_mapper.clone());
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::embeddings()),
+            ApiEndpoint::Cohere(Cohere::embed()),
+        );
+        let converter =
+            TypedEndpointConverter::<
+                endpoints::openai::Embeddings,
+                endpoints::cohere::Embed,
+                CohereConverter,
+            >::new(CohereConverter::new(model_mapper.clone()));
+        registry.register_converter(key, converter);
+
+        let key = RegistryKey::new(
+            ApiEndpoint::OpenAI(OpenAI::embeddings()),
+            ApiEndpoint::Google(Google::embeddings()),
+        );
+        let converter = TypedEndpointConverter::<
+            endpoints::openai::Embeddings,
+            endpoints::google::Embeddings,
+            OpenAICompatibleConverter,
+        >::new(OpenAICompatibleConverter::new(
+            InferenceProvider::GoogleGemini,
+            model_mapper.clone(),
+        ));
+        registry.register_converter(key, converter);
+
         registry
     }
 
@@ -238,6 +393,6 @@ impl EndpointConverterRegistryInner {
     where
         C: EndpointConverter + Send + Sync + 'static,
     {
-        self.converters.insert(key, Box::new(converter));
+        self.converters.insert(key, Arc::new(converter));
     }
 }
@@ -0,0 +1,1065 @@
+//! Conversions between the chat completions and responses APIs, see
+//! [`ApiTranslation`](crate::config::api_translation::ApiTranslation).
+use std::{str::FromStr, sync::Arc};
+
+use async_openai::types as openai;
+use bytes::{BufMut, Bytes, BytesMut};
+use http::response::Parts;
+
+use super::{
+    EndpointConverter, TryConvert, TryConvertError, TryConvertStreamData,
+    anthropic::OPENAI_CHAT_COMPLETION_OBJECT, model::ModelMapper,
+};
+use crate::{
+    endpoints::openai::responses::{
+        CreateResponseRequest, RESPONSE_OBJECT, ResponseContentPart,
+        ResponseFunctionCall, ResponseInput, ResponseInputContent,
+        ResponseInputItem, ResponseInputMessage, ResponseInputTokensDetails,
+        ResponseNamedToolChoice, ResponseObject, ResponseOutputContent,
+        ResponseOutputItem, ResponseOutputTokensDetails, ResponseRole,
+        ResponseStatus, ResponseStreamEvent, ResponseTool, ResponseToolChoice,
+        ResponseToolChoiceMode, ResponseToolItem, ResponseUsage,
+    },
+    error::{
+        api::ApiError, internal::InternalError,
+        invalid_req::InvalidRequestError, mapper::MapperError,
+    },
+    types::{
+        extensions::MapperContext, model_id::ModelId,
+        provider::InferenceProvider,
+    },
+};
+
+const CHAT_COMPLETION_CHUNK_OBJECT: &str = "chat.completion.chunk";
+
+/// Converts chat completions requests to responses API requests, for
+/// serving chat completions clients with the responses API.
+pub struct ResponsesConverter {
+    model_mapper: ModelMapper,
+}
+
+impl ResponsesConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+}
+
+impl TryConvert<openai::CreateChatCompletionRequest, CreateResponseRequest>
+    for ResponsesConverter
+{
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: openai::CreateChatCompletionRequest,
+    ) -> Result<CreateResponseRequest, Self::Error> {
+        let source_model = ModelId::from_str(&value.model)?;
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::OpenAI)?;
+        tracing::trace!(source_model = ?source_model, target_model = ?target_model, "mapped model");
+
+        let input = value
+            .messages
+            .into_iter()
+            .flat_map(input_items_from_chat_message)
+            .collect();
+        #[allow(deprecated)]
+        let max_output_tokens =
+            value.max_completion_tokens.or(value.max_tokens);
+        let tools = value.tools.map(|tools| {
+            tools
+                .into_iter()
+                .map(|tool| ResponseTool::Function {
+                    name: tool.function.name,
+                    description: tool.function.description,
+                    parameters: tool.function.parameters,
+                    strict: tool.function.strict,
+                })
+                .collect()
+        });
+        let tool_choice =
+            value.tool_choice.map(|tool_choice| match tool_choice {
+                openai::ChatCompletionToolChoiceOption::None => {
+                    ResponseToolChoice::Mode(ResponseToolChoiceMode::None)
+                }
+                openai::ChatCompletionToolChoiceOption::Auto => {
+                    ResponseToolChoice::Mode(ResponseToolChoiceMode::Auto)
+                }
+                openai::ChatCompletionToolChoiceOption::Required => {
+                    ResponseToolChoice::Mode(ResponseToolChoiceMode::Required)
+                }
+                openai::ChatCompletionToolChoiceOption::Named(tool) => {
+                    ResponseToolChoice::Named(
+                        ResponseNamedToolChoice::Function {
+                            name: tool.function.name,
+                        },
+                    )
+                }
+            });
+
+        Ok(CreateResponseRequest {
+            model: target_model.to_string(),
+            input: ResponseInput::Items(input),
+            instructions: None,
+            max_output_tokens,
+            temperature: value.temperature,
+            top_p: value.top_p,
+            stream: value.stream,
+            user: value.user,
+            tools,
+            tool_choice,
+            parallel_tool_calls: value.parallel_tool_calls,
+        })
+    }
+}
+
+impl TryConvert<ResponseObject, openai::CreateChatCompletionResponse>
+    for ResponsesConverter
+{
+    type Error = MapperError;
+
+    fn try_convert(
+        &self,
+        value: ResponseObject,
+    ) -> Result<openai::CreateChatCompletionResponse, Self::Error> {
+        let mut content: Option<String> = None;
+        let mut refusal: Option<String> = None;
+        let mut tool_calls = Vec::new();
+        for item in value.output {
+            match item {
+                ResponseOutputItem::Message {
+                    content: output, ..
+                } => {
+                    for part in output {
+                        match part {
+                            ResponseOutputContent::OutputText {
+                                text, ..
+                            } => {
+                                content
+                                    .get_or_insert_with(String::new)
+                                    .push_str(&text);
+                            }
+                            ResponseOutputContent::Refusal {
+                                refusal: text,
+                            } => {
+                                refusal
+                                    .get_or_insert_with(String::new)
+                                    .push_str(&text);
+                            }
+                        }
+                    }
+                }
+                ResponseOutputItem::FunctionCall(call) => {
+                    tool_calls.push(chat_tool_call(call));
+                }
+                ResponseOutputItem::Other => {}
+            }
+        }
+        let finish_reason = if tool_calls.is_empty() {
+            finish_reason(value.status)
+        } else {
+            openai::FinishReason::ToolCalls
+        };
+        #[allow(deprecated)]
+        let choice = openai::ChatChoice {
+            index: 0,
+            message: openai::ChatCompletionResponseMessage {
+                content,
+                refusal,
+                tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                role: openai::Role::Assistant,
+                function_call: None,
+                audio: None,
+            },
+            finish_reason: Some(finish_reason),
+            logprobs: None,
+        };
+        Ok(openai::CreateChatCompletionResponse {
+            choices: vec![choice],
+            id: value.id,
+            created: value.created_at,
+            model: value.model,
+            object: OPENAI_CHAT_COMPLETION_OBJECT.to_string(),
+            usage: value.usage.map(completion_usage),
+            service_tier: None,
+            system_fingerprint: None,
+        })
+    }
+}
+
+impl
+    TryConvertStreamData<
+        ResponseStreamEvent,
+        openai::CreateChatCompletionStreamResponse,
+    > for ResponsesConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: ResponseStreamEvent,
+    ) -> Result<Option<openai::CreateChatCompletionStreamResponse>, Self::Error>
+    {
+        // the model is only known once the response completes, and tool
+        // calls are indexed by their output item since events don't carry
+        // the index of the call
+        match value {
+            ResponseStreamEvent::OutputTextDelta { item_id, delta, .. } => {
+                Ok(Some(chat_chunk(
+                    item_id,
+                    String::new(),
+                    0,
+                    chat_delta(Some(delta), None),
+                    None,
+                    None,
+                )))
+            }
+            ResponseStreamEvent::OutputItemAdded {
+                output_index,
+                item: ResponseOutputItem::FunctionCall(call),
+            } => {
+                let tool_call = openai::ChatCompletionMessageToolCallChunk {
+                    index: output_index,
+                    id: Some(call.call_id),
+                    r#type: Some(openai::ChatCompletionToolType::Function),
+                    function: Some(openai::FunctionCallStream {
+                        name: Some(call.name),
+                        arguments: Some(call.arguments),
+                    }),
+                };
+                Ok(Some(chat_chunk(
+                    call.id.unwrap_or_default(),
+                    String::new(),
+                    0,
+                    chat_delta(None, Some(vec![tool_call])),
+                    None,
+                    None,
+                )))
+            }
+            ResponseStreamEvent::FunctionCallArgumentsDelta {
+                item_id,
+                output_index,
+                delta,
+            } => {
+                let tool_call = openai::ChatCompletionMessageToolCallChunk {
+                    index: output_index,
+                    id: None,
+                    r#type: None,
+                    function: Some(openai::FunctionCallStream {
+                        name: None,
+                        arguments: Some(delta),
+                    }),
+                };
+                Ok(Some(chat_chunk(
+                    item_id,
+                    String::new(),
+                    0,
+                    chat_delta(None, Some(vec![tool_call])),
+                    None,
+                    None,
+                )))
+            }
+            ResponseStreamEvent::Completed { response }
+            | ResponseStreamEvent::Incomplete { response }
+            | ResponseStreamEvent::Failed { response } => {
+                let has_tool_calls = response.output.iter().any(|item| {
+                    matches!(item, ResponseOutputItem::FunctionCall(_))
+                });
+                let finish_reason = if has_tool_calls {
+                    openai::FinishReason::ToolCalls
+                } else {
+                    finish_reason(response.status)
+                };
+                Ok(Some(chat_chunk(
+                    response.id,
+                    response.model,
+                    response.created_at,
+                    chat_delta(None, None),
+                    Some(finish_reason),
+                    response.usage.map(completion_usage),
+                )))
+            }
+            ResponseStreamEvent::Created { .. }
+            | ResponseStreamEvent::InProgress { .. }
+            | ResponseStreamEvent::OutputItemAdded { .. }
+            | ResponseStreamEvent::OutputItemDone { .. }
+            | ResponseStreamEvent::ContentPartAdded { .. }
+            | ResponseStreamEvent::ContentPartDone { .. }
+            | ResponseStreamEvent::OutputTextDone { .. }
+            | ResponseStreamEvent::FunctionCallArgumentsDone { .. }
+            | ResponseStreamEvent::Error { .. }
+            | ResponseStreamEvent::Other => Ok(None),
+        }
+    }
+}
+
+impl
+    TryConvertError<
+        async_openai::error::WrappedError,
+        async_openai::error::WrappedError,
+    > for ResponsesConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_error(
+        &self,
+        _resp_parts: &Parts,
+        value: async_openai::error::WrappedError,
+    ) -> Result<async_openai::error::WrappedError, Self::Error> {
+        Ok(value)
+    }
+}
+
+/// Serves responses API requests with the chat completions API by wrapping
+/// the chat completions converter for the target provider.
+///
+/// Stream conversion is stateless, so the `response.completed` event carries
+/// the usage of the response but not its accumulated output, and only the
+/// events that carry output (`response.created`, text and function call
+/// argument deltas, and function call items) precede it.
+pub struct ResponsesToChatConverter {
+    chat: Arc<dyn EndpointConverter + Send + Sync>,
+}
+
+impl ResponsesToChatConverter {
+    #[must_use]
+    pub fn new(chat: Arc<dyn EndpointConverter + Send + Sync>) -> Self {
+        Self { chat }
+    }
+}
+
+impl EndpointConverter for ResponsesToChatConverter {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        let request: CreateResponseRequest =
+            serde_json::from_slice(&req_body_bytes)
+                .map_err(InvalidRequestError::InvalidRequestBody)?;
+        let chat_request = chat_request_from_responses(request);
+        self.chat.convert_req_body(serialize(&chat_request)?)
+    }
+
+    fn convert_resp_body(
+        &self,
+        resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        let is_error = resp_parts.status.is_client_error()
+            || resp_parts.status.is_server_error();
+        let Some(chat_bytes) = self.chat.convert_resp_body(
+            resp_parts,
+            resp_body_bytes,
+            is_stream,
+        )?
+        else {
+            return Ok(None);
+        };
+        if is_stream {
+            let chunk: openai::CreateChatCompletionStreamResponse =
+                deserialize(&chat_bytes)?;
+            serialize_events(&stream_events_from_chat_chunk(chunk))
+        } else if is_error {
+            // both APIs share the same error shape
+            Ok(Some(chat_bytes))
+        } else {
+            let response: openai::CreateChatCompletionResponse =
+                deserialize(&chat_bytes)?;
+            serialize(&response_from_chat_response(response)).map(Some)
+        }
+    }
+}
+
+/// Passes requests through to OpenAI, only mapping the model.
+///
+/// Used for APIs where the request may carry features our types don't model
+/// (e.g. responses API tools), or where there is nothing else to map.
+pub struct OpenAIPassthroughConverter {
+    model_mapper: ModelMapper,
+}
+
+impl OpenAIPassthroughConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+}
+
+impl EndpointConverter for OpenAIPassthroughConverter {
+    fn convert_req_body(
+        &self,
+        req_body_bytes: Bytes,
+    ) -> Result<(Bytes, MapperContext), ApiError> {
+        // the request is kept as a `Value` so that features without an
+        // equivalent in our types (e.g. tools) are preserved
+        let mut request: serde_json::Value =
+            serde_json::from_slice(&req_body_bytes)
+                .map_err(InvalidRequestError::InvalidRequestBody)?;
+        let is_stream = request
+            .get("stream")
+            .and_then(serde_json::Value::as_bool)
+            .unwrap_or(false);
+        let source_model = request
+            .get("model")
+            .and_then(serde_json::Value::as_str)
+            .ok_or(InternalError::MapperError(MapperError::InvalidRequest))?;
+        let source_model = ModelId::from_str(source_model)
+            .map_err(InternalError::MapperError)?;
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::OpenAI)
+            .map_err(InternalError::MapperError)?;
+        request["model"] = serde_json::Value::String(target_model.to_string());
+
+        let mapper_ctx = MapperContext {
+            is_stream,
+            model: Some(target_model),
+        };
+        Ok((serialize(&request)?, mapper_ctx))
+    }
+
+    fn convert_resp_body(
+        &self,
+        _resp_parts: Parts,
+        resp_body_bytes: Bytes,
+        _is_stream: bool,
+    ) -> Result<Option<Bytes>, ApiError> {
+        Ok(Some(resp_body_bytes))
+    }
+}
+
+fn chat_request_from_responses(
+    value: CreateResponseRequest,
+) -> openai::CreateChatCompletionRequest {
+    let mut messages = Vec::new();
+    if let Some(instructions) = value.instructions {
+        messages.push(developer_message(instructions));
+    }
+    match value.input {
+        ResponseInput::Text(text) => messages.push(user_message(text)),
+        ResponseInput::Items(items) => {
+            for item in items {
+                match item {
+                    ResponseInputItem::Message(message) => {
+                        let text = message.content.text();
+                        messages.push(match message.role {
+                            ResponseRole::User => user_message(text),
+                            ResponseRole::Assistant => assistant_message(text),
+                            ResponseRole::System => system_message(text),
+                            ResponseRole::Developer => developer_message(text),
+                        });
+                    }
+                    ResponseInputItem::Tool(
+                        ResponseToolItem::FunctionCall(call),
+                    ) => push_tool_call(&mut messages, chat_tool_call(call)),
+                    ResponseInputItem::Tool(
+                        ResponseToolItem::FunctionCallOutput {
+                            call_id,
+                            output,
+                        },
+                    ) => messages.push(tool_message(call_id, output)),
+                }
+            }
+        }
+    }
+    let stream_options = if value.stream.unwrap_or(false) {
+        Some(openai::ChatCompletionStreamOptions {
+            include_usage: true,
+        })
+    } else {
+        None
+    };
+    let tools = value
+        .tools
+        .map(|tools| {
+            tools
+                .into_iter()
+                .filter_map(|tool| match tool {
+                    ResponseTool::Function {
+                        name,
+                        description,
+                        parameters,
+                        strict,
+                    } => Some(openai::ChatCompletionTool {
+                        r#type: openai::ChatCompletionToolType::Function,
+                        function: openai::FunctionObject {
+                            name,
+                            description,
+                            parameters,
+                            strict,
+                        },
+                    }),
+                    ResponseTool::Other => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|tools| !tools.is_empty());
+    let tool_choice = value.tool_choice.map(|tool_choice| match tool_choice {
+        ResponseToolChoice::Mode(ResponseToolChoiceMode::None) => {
+            openai::ChatCompletionToolChoiceOption::None
+        }
+        ResponseToolChoice::Mode(ResponseToolChoiceMode::Auto) => {
+            openai::ChatCompletionToolChoiceOption::Auto
+        }
+        ResponseToolChoice::Mode(ResponseToolChoiceMode::Required) => {
+            openai::ChatCompletionToolChoiceOption::Required
+        }
+        ResponseToolChoice::Named(ResponseNamedToolChoice::Function {
+            name,
+        }) => openai::ChatCompletionToolChoiceOption::Named(
+            openai::ChatCompletionNamedToolChoice {
+                r#type: openai::ChatCompletionToolType::Function,
+                function: openai::FunctionName { name },
+            },
+        ),
+    });
+
+    #[allow(deprecated)]
+    openai::CreateChatCompletionRequest {
+        messages,
+        model: value.model,
+        store: None,
+        reasoning_effort: None,
+        metadata: None,
+        parallel_tool_calls: value.parallel_tool_calls,
+        stop: None,
+        stream: value.stream,
+        stream_options,
+        temperature: value.temperature,
+        top_p: value.top_p,
+        tools,
+        tool_choice,
+        user: value.user,
+        max_completion_tokens: value.max_output_tokens,
+        max_tokens: None,
+        frequency_penalty: None,
+        logit_bias: None,
+        logprobs: None,
+        n: None,
+        modalities: None,
+        presence_penalty: None,
+        prediction: None,
+        response_format: None,
+        seed: None,
+        service_tier: None,
+        top_logprobs: None,
+        audio: None,
+        function_call: None,
+        functions: None,
+        web_search_options: None,
+    }
+}
+
+fn response_from_chat_response(
+    mut value: openai::CreateChatCompletionResponse,
+) -> ResponseObject {
+    let choice = (!value.choices.is_empty()).then(|| value.choices.remove(0));
+    let status = response_status(
+        choice.as_ref().and_then(|choice| choice.finish_reason),
+    );
+    let mut content = Vec::new();
+    let mut tool_calls = Vec::new();
+    if let Some(message) = choice.map(|choice| choice.message) {
+        if let Some(text) = message.content {
+            content.push(ResponseOutputContent::OutputText {
+                text,
+                annotations: Vec::new(),
+            });
+        }
+        if let Some(refusal) = message.refusal {
+            content.push(ResponseOutputContent::Refusal { refusal });
+        }
+        tool_calls = message.tool_calls.unwrap_or_default();
+    }
+
+    let mut output = Vec::new();
+    if !content.is_empty() || tool_calls.is_empty() {
+        output.push(ResponseOutputItem::Message {
+            id: message_id(&value.id),
+            role: ResponseRole::Assistant,
+            status,
+            content,
+        });
+    }
+    output.extend(tool_calls.into_iter().map(|tool_call| {
+        ResponseOutputItem::FunctionCall(ResponseFunctionCall {
+            id: Some(function_call_id(&tool_call.id)),
+            call_id: tool_call.id,
+            name: tool_call.function.name,
+            arguments: tool_call.function.arguments,
+            status: Some(ResponseStatus::Completed),
+        })
+    }));
+
+    ResponseObject {
+        output,
+        id: value.id,
+        object: RESPONSE_OBJECT.to_string(),
+        created_at: value.created,
+        model: value.model,
+        status,
+        usage: value.usage.map(response_usage),
+    }
+}
+
+/// The events for a chat completions chunk. Function call items follow the
+/// message item, in the order of their tool call index.
+fn stream_events_from_chat_chunk(
+    value: openai::CreateChatCompletionStreamResponse,
+) -> Vec<ResponseStreamEvent> {
+    let mut events = Vec::new();
+    let choice = value.choices.first();
+    // the role is only sent with the first chunk
+    if choice.is_some_and(|choice| choice.delta.role.is_some()) {
+        events.push(ResponseStreamEvent::Created {
+            response: ResponseObject {
+                id: value.id.clone(),
+                object: RESPONSE_OBJECT.to_string(),
+                created_at: value.created,
+                model: value.model.clone(),
+                status: ResponseStatus::InProgress,
+                output: Vec::new(),
+                usage: None,
+            },
+        });
+    }
+
+    if let Some(delta) = choice
+        .and_then(|choice| choice.delta.content.clone())
+        .filter(|delta| !delta.is_empty())
+    {
+        events.push(ResponseStreamEvent::OutputTextDelta {
+            item_id: message_id(&value.id),
+            output_index: 0,
+            content_index: 0,
+            delta,
+        });
+    }
+
+    let tool_calls = choice
+        .and_then(|choice| choice.delta.tool_calls.as_ref())
+        .into_iter()
+        .flatten();
+    for tool_call in tool_calls {
+        let item_id =
+            function_call_id(&format!("{}_{}", value.id, tool_call.index));
+        let output_index = tool_call.index + 1;
+        let function = tool_call.function.as_ref();
+        // only the first chunk of each tool call carries its id
+        if let Some(call_id) = &tool_call.id {
+            events.push(ResponseStreamEvent::OutputItemAdded {
+                output_index,
+                item: ResponseOutputItem::FunctionCall(ResponseFunctionCall {
+                    id: Some(item_id.clone()),
+                    call_id: call_id.clone(),
+                    name: function
+                        .and_then(|function| function.name.clone())
+                        .unwrap_or_default(),
+                    arguments: String::new(),
+                    status: Some(ResponseStatus::InProgress),
+                }),
+            });
+        }
+        if let Some(delta) = function
+            .and_then(|function| function.arguments.clone())
+            .filter(|delta| !delta.is_empty())
+        {
+            events.push(ResponseStreamEvent::FunctionCallArgumentsDelta {
+                item_id,
+                output_index,
+                delta,
+            });
+        }
+    }
+
+    let finish_reason = choice.and_then(|choice| choice.finish_reason);
+    // with `include_usage`, usage is sent in a final chunk without choices,
+    // otherwise providers send it alongside the finish reason
+    if let Some(usage) = value.usage
+        && (choice.is_none() || finish_reason.is_some())
+    {
+        let status = response_status(finish_reason);
+        let response = ResponseObject {
+            id: value.id,
+            object: RESPONSE_OBJECT.to_string(),
+            created_at: value.created,
+            model: value.model,
+            status,
+            output: Vec::new(),
+            usage: Some(response_usage(usage)),
+        };
+        events.push(if status == ResponseStatus::Incomplete {
+            ResponseStreamEvent::Incomplete { response }
+        } else {
+            ResponseStreamEvent::Completed { response }
+        });
+    }
+    events
+}
+
+fn input_items_from_chat_message(
+    message: openai::ChatCompletionRequestMessage,
+) -> Vec<ResponseInputItem> {
+    let (role, text) = match message {
+        openai::ChatCompletionRequestMessage::System(message) => {
+            let text = match message.content {
+                openai::ChatCompletionRequestSystemMessageContent::Text(text) => text,
+                openai::ChatCompletionRequestSystemMessageContent::Array(parts) => parts
+                    .into_iter()
+                    .map(|part| match part {
+                        openai::ChatCompletionRequestSystemMessageContentPart::Text(part) => part.text,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            (ResponseRole::System, text)
+        }
+        openai::ChatCompletionRequestMessage::Developer(message) => {
+            let text = match message.content {
+                openai::ChatCompletionRequestDeveloperMessageContent::Text(
+                    text,
+                ) => text,
+                openai::ChatCompletionRequestDeveloperMessageContent::Array(
+                    parts,
+                ) => parts
+                    .into_iter()
+                    .map(|part| part.text)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            (ResponseRole::Developer, text)
+        }
+        openai::ChatCompletionRequestMessage::User(message) => {
+            let content = match message.content {
+                openai::ChatCompletionRequestUserMessageContent::Text(text) => {
+                    ResponseInputContent::Text(text)
+                }
+                openai::ChatCompletionRequestUserMessageContent::Array(parts) => {
+                    ResponseInputContent::Parts(
+                        parts
+                            .into_iter()
+                            .filter_map(|part| match part {
+                                openai::ChatCompletionRequestUserMessageContentPart::Text(part) => {
+                                    Some(ResponseContentPart::InputText { text: part.text })
+                                }
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                }
+            };
+            return vec![ResponseInputItem::Message(ResponseInputMessage {
+                role: ResponseRole::User,
+                content,
+            })];
+        }
+        openai::ChatCompletionRequestMessage::Assistant(message) => {
+            let tool_calls = message.tool_calls.unwrap_or_default();
+            let text = message.content.map(|content| match content {
+                openai::ChatCompletionRequestAssistantMessageContent::Text(text) => text,
+                openai::ChatCompletionRequestAssistantMessageContent::Array(parts) => parts
+                    .into_iter()
+                    .map(|part| match part {
+                        openai::ChatCompletionRequestAssistantMessageContentPart::Text(part) => part.text,
+                        openai::ChatCompletionRequestAssistantMessageContentPart::Refusal(part) => part.refusal,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            });
+            let message = text.map(|text| {
+                ResponseInputItem::Message(ResponseInputMessage {
+                    role: ResponseRole::Assistant,
+                    content: ResponseInputContent::Text(text),
+                })
+            });
+            let calls = tool_calls.into_iter().map(|call| {
+                ResponseInputItem::Tool(ResponseToolItem::FunctionCall(
+                    ResponseFunctionCall {
+                        id: None,
+                        call_id: call.id,
+                        name: call.function.name,
+                        arguments: call.function.arguments,
+                        status: None,
+                    },
+                ))
+            });
+            return message.into_iter().chain(calls).collect();
+        }
+        openai::ChatCompletionRequestMessage::Tool(message) => {
+            let output = match message.content {
+                openai::ChatCompletionRequestToolMessageContent::Text(text) => text,
+                openai::ChatCompletionRequestToolMessageContent::Array(parts) => parts
+                    .into_iter()
+                    .map(|part| match part {
+                        openai::ChatCompletionRequestToolMessageContentPart::Text(part) => part.text,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            return vec![ResponseInputItem::Tool(
+                ResponseToolItem::FunctionCallOutput {
+                    call_id: message.tool_call_id,
+                    output,
+                },
+            )];
+        }
+        // deprecated function messages have no equivalent in the responses
+        // API
+        openai::ChatCompletionRequestMessage::Function(_) => return Vec::new(),
+    };
+    vec![ResponseInputItem::Message(ResponseInputMessage {
+        role,
+        content: ResponseInputContent::Text(text),
+    })]
+}
+
+/// Adds a tool call to the assistant message it follows, since the
+/// responses API has an item for each call of a turn.
+fn push_tool_call(
+    messages: &mut Vec<openai::ChatCompletionRequestMessage>,
+    tool_call: openai::ChatCompletionMessageToolCall,
+) {
+    if let Some(openai::ChatCompletionRequestMessage::Assistant(message)) =
+        messages.last_mut()
+    {
+        message
+            .tool_calls
+            .get_or_insert_with(Vec::new)
+            .push(tool_call);
+        return;
+    }
+    #[allow(deprecated)]
+    messages.push(openai::ChatCompletionRequestMessage::Assistant(
+        openai::ChatCompletionRequestAssistantMessage {
+            content: None,
+            tool_calls: Some(vec![tool_call]),
+            refusal: None,
+            name: None,
+            audio: None,
+            function_call: None,
+        },
+    ));
+}
+
+fn tool_message(
+    call_id: String,
+    output: String,
+) -> openai::ChatCompletionRequestMessage {
+    openai::ChatCompletionRequestMessage::Tool(
+        openai::ChatCompletionRequestToolMessage {
+            content: openai::ChatCompletionRequestToolMessageContent::Text(
+                output,
+            ),
+            tool_call_id: call_id,
+        },
+    )
+}
+
+fn chat_tool_call(
+    call: ResponseFunctionCall,
+) -> openai::ChatCompletionMessageToolCall {
+    openai::ChatCompletionMessageToolCall {
+        id: call.call_id,
+        r#type: openai::ChatCompletionToolType::Function,
+        function: openai::FunctionCall {
+            name: call.name,
+            arguments: call.arguments,
+        },
+    }
+}
+
+fn user_message(text: String) -> openai::ChatCompletionRequestMessage {
+    openai::ChatCompletionRequestMessage::User(
+        openai::ChatCompletionRequestUserMessage {
+            content: openai::ChatCompletionRequestUserMessageContent::Text(
+                text,
+            ),
+            name: None,
+        },
+    )
+}
+
+fn system_message(text: String) -> openai::ChatCompletionRequestMessage {
+    openai::ChatCompletionRequestMessage::System(
+        openai::ChatCompletionRequestSystemMessage {
+            content: openai::ChatCompletionRequestSystemMessageContent::Text(
+                text,
+            ),
+            name: None,
+        },
+    )
+}
+
+fn developer_message(text: String) -> openai::ChatCompletionRequestMessage {
+    openai::ChatCompletionRequestMessage::Developer(
+        openai::ChatCompletionRequestDeveloperMessage {
+            content: openai::ChatCompletionRequestDeveloperMessageContent::Text(
+                text,
+            ),
+            name: None,
+        },
+    )
+}
+
+fn assistant_message(text: String) -> openai::ChatCompletionRequestMessage {
+    #[allow(deprecated)]
+    openai::ChatCompletionRequestMessage::Assistant(
+        openai::ChatCompletionRequestAssistantMessage {
+            content: Some(
+                openai::ChatCompletionRequestAssistantMessageContent::Text(
+                    text,
+                ),
+            ),
+            tool_calls: None,
+            refusal: None,
+            name: None,
+            audio: None,
+            function_call: None,
+        },
+    )
+}
+
+fn chat_delta(
+    content: Option<String>,
+    tool_calls: Option<Vec<openai::ChatCompletionMessageToolCallChunk>>,
+) -> openai::ChatCompletionStreamResponseDelta {
+    #[allow(deprecated)]
+    openai::ChatCompletionStreamResponseDelta {
+        role: None,
+        content,
+        tool_calls,
+        refusal: None,
+        function_call: None,
+    }
+}
+
+fn chat_chunk(
+    id: String,
+    model: String,
+    created: u32,
+    delta: openai::ChatCompletionStreamResponseDelta,
+    finish_reason: Option<openai::FinishReason>,
+    usage: Option<openai::CompletionUsage>,
+) -> openai::CreateChatCompletionStreamResponse {
+    let choice = openai::ChatChoiceStream {
+        index: 0,
+        delta,
+        finish_reason,
+        logprobs: None,
+    };
+    openai::CreateChatCompletionStreamResponse {
+        id,
+        choices: vec![choice],
+        created,
+        model,
+        object: CHAT_COMPLETION_CHUNK_OBJECT.to_string(),
+        system_fingerprint: None,
+        service_tier: None,
+        usage,
+    }
+}
+
+fn response_status(
+    finish_reason: Option<openai::FinishReason>,
+) -> ResponseStatus {
+    match finish_reason {
+        Some(openai::FinishReason::Length) => ResponseStatus::Incomplete,
+        _ => ResponseStatus::Completed,
+    }
+}
+
+fn finish_reason(status: ResponseStatus) -> openai::FinishReason {
+    match status {
+        ResponseStatus::Incomplete => openai::FinishReason::Length,
+        ResponseStatus::Completed
+        | ResponseStatus::InProgress
+        | ResponseStatus::Failed => openai::FinishReason::Stop,
+    }
+}
+
+fn message_id(response_id: &str) -> String {
+    format!("msg_{response_id}")
+}
+
+fn function_call_id(call_id: &str) -> String {
+    format!("fc_{call_id}")
+}
+
+fn completion_usage(usage: ResponseUsage) -> openai::CompletionUsage {
+    openai::CompletionUsage {
+        prompt_tokens: usage.input_tokens,
+        completion_tokens: usage.output_tokens,
+        total_tokens: usage.total_tokens,
+        prompt_tokens_details: usage.input_tokens_details.map(|details| {
+            openai::PromptTokensDetails {
+                audio_tokens: None,
+                cached_tokens: Some(details.cached_tokens),
+            }
+        }),
+        completion_tokens_details: usage.output_tokens_details.map(|details| {
+            openai::CompletionTokensDetails {
+                accepted_prediction_tokens: None,
+                audio_tokens: None,
+                reasoning_tokens: Some(details.reasoning_tokens),
+                rejected_prediction_tokens: None,
+            }
+        }),
+    }
+}
+
+fn response_usage(usage: openai::CompletionUsage) -> ResponseUsage {
+    ResponseUsage {
+        input_tokens: usage.prompt_tokens,
+        output_tokens: usage.completion_tokens,
+        total_tokens: usage.total_tokens,
+        input_tokens_details: usage
+            .prompt_tokens_details
+            .and_then(|details| details.cached_tokens)
+            .map(|cached_tokens| ResponseInputTokensDetails { cached_tokens }),
+        output_tokens_details: usage
+            .completion_tokens_details
+            .and_then(|details| details.reasoning_tokens)
+            .map(|reasoning_tokens| ResponseOutputTokensDetails {
+                reasoning_tokens,
+            }),
+    }
+}
+
+fn deserialize<T: serde::de::DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<T, ApiError> {
+    serde_json::from_slice(bytes).map_err(|e| {
+        ApiError::Internal(InternalError::Deserialize {
+            ty: std::any::type_name::<T>(),
+            error: e,
+        })
+    })
+}
+
+fn serialize<T: serde::Serialize>(value: &T) -> Result<Bytes, ApiError> {
+    serde_json::to_vec(value).map(Bytes::from).map_err(|e| {
+        ApiError::Internal(InternalError::Serialize {
+            ty: std::any::type_name::<T>(),
+            error: e,
+        })
+    })
+}
+
+/// Serializes stream events as the data of a single SSE event. The mapper
+/// frames each converted chunk as one event, so the events after the first
+/// are framed here.
+fn serialize_events(
+    events: &[ResponseStreamEvent],
+) -> Result<Option<Bytes>, ApiError> {
+    let mut data = BytesMut::new();
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            data.put("\n\ndata: ".as_bytes());
+        }
+        data.put(serialize(event)?);
+    }
+    Ok((!data.is_empty()).then(|| data.freeze()))
+}
@@ -3,21 +3,29 @@ use std::{
     task::{Context, Poll},
 };
 
-use bytes::{BufMut, BytesMut};
-use futures::{TryStreamExt, future::BoxFuture};
-use http::uri::PathAndQuery;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{StreamExt, TryStreamExt, future::BoxFuture};
+use http::{HeaderValue, uri::PathAndQuery};
 use tracing::{Instrument, info_span};
 
 use crate::{
-    endpoints::ApiEndpoint,
+    config::api_translation::ApiTranslation,
+    endpoints::{ApiEndpoint, openai::OpenAI},
     error::{
         api::ApiError, internal::InternalError, mapper::MapperError,
         stream::StreamError,
     },
-    middleware::mapper::registry::EndpointConverterRegistry,
+    middleware::mapper::{
+        anthropic_stream::{self, ChatCompletionStream},
+        embeddings, params, prompt_caching,
+        registry::EndpointConverterRegistry,
+        stream_usage,
+    },
     types::{
-        extensions::MapperContext, provider::InferenceProvider,
-        request::Request, response::Response,
+        extensions::{DryRun, MapperContext},
+        provider::InferenceProvider,
+        request::Request,
+        response::Response,
     },
 };
 
@@ -25,16 +33,23 @@ use crate::{
 pub struct Service<S> {
     inner: S,
     endpoint_converter_registry: EndpointConverterRegistry,
+    api_translation: Option<ApiTranslation>,
+    /// See [`params`].
+    normalize_params: bool,
 }
 
 impl<S> Service<S> {
     pub fn new(
         inner: S,
         endpoint_converter_registry: EndpointConverterRegistry,
+        api_translation: Option<ApiTranslation>,
+        normalize_params: bool,
     ) -> Self {
         Self {
             inner,
             endpoint_converter_registry,
+            api_translation,
+            normalize_params,
         }
     }
 }
@@ -67,6 +82,8 @@ where
         // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
         let mut inner = self.inner.clone();
         let converter_registry = self.endpoint_converter_registry.clone();
+        let api_translation = self.api_translation;
+        let normalize_params = self.normalize_params;
         std::mem::swap(&mut self.inner, &mut inner);
         Box::pin(async move {
             let target_provider = req
@@ -90,42 +107,55 @@ where
                 InternalError::ExtensionNotFound("ApiEndpoint"),
             ))?;
             let source_endpoint_cloned = source_endpoint.clone();
-            let target_endpoint =
-                ApiEndpoint::mapped(source_endpoint, &target_provider)?;
+            let target_endpoint = ApiEndpoint::mapped(
+                source_endpoint,
+                &target_provider,
+                api_translation,
+            )?;
             let target_endpoint_cloned = target_endpoint.clone();
             // serialization/deserialization should be done on a dedicated
             // thread
             let converter_registry_cloned = converter_registry.clone();
             let source_endpoint_for_req = source_endpoint_cloned.clone();
             let target_endpoint_for_req = target_endpoint_cloned.clone();
-            let req = tokio::task::spawn_blocking(move || async move {
-                map_request(
-                    converter_registry_cloned,
-                    source_endpoint_for_req,
-                    target_endpoint_for_req,
-                    &extracted_path_and_query,
-                    req,
-                )
-                .instrument(info_span!("map_request"))
+            let (req, encode_embeddings, chat_stream, params_adjusted) =
+                tokio::task::spawn_blocking(move || async move {
+                    map_request(
+                        converter_registry_cloned,
+                        source_endpoint_for_req,
+                        target_endpoint_for_req,
+                        &extracted_path_and_query,
+                        req,
+                        normalize_params,
+                    )
+                    .instrument(info_span!("map_request"))
+                    .await
+                })
                 .await
-            })
-            .await
-            .map_err(InternalError::MappingTaskError)?
-            .await?;
+                .map_err(InternalError::MappingTaskError)?
+                .await?;
             let response = inner.call(req).await?;
-            let response = tokio::task::spawn_blocking(move || async move {
-                map_response(
-                    converter_registry,
-                    target_endpoint_cloned,
-                    source_endpoint_cloned,
-                    response,
-                )
+            let mut response =
+                tokio::task::spawn_blocking(move || async move {
+                    map_response(
+                        converter_registry,
+                        target_endpoint_cloned,
+                        source_endpoint_cloned,
+                        response,
+                        encode_embeddings,
+                        chat_stream,
+                    )
+                    .await
+                })
+                .instrument(info_span!("map_response"))
                 .await
-            })
-            .instrument(info_span!("map_response"))
-            .await
-            .map_err(InternalError::MappingTaskError)?
-            .await?;
+                .map_err(InternalError::MappingTaskError)?
+                .await?;
+            if let Some(params_adjusted) = params_adjusted {
+                response
+                    .headers_mut()
+                    .insert(params::PARAMS_ADJUSTED_HEADER, params_adjusted);
+            }
             Ok(response)
         })
     }
@@ -137,7 +167,16 @@ async fn map_request(
     target_endpoint: ApiEndpoint,
     target_path_and_query: &PathAndQuery,
     req: Request,
-) -> Result<Request, ApiError> {
+    normalize_params: bool,
+) -> Result<
+    (
+        Request,
+        bool,
+        Option<ChatCompletionStream>,
+        Option<HeaderValue>,
+    ),
+    ApiError,
+> {
     use http_body_util::BodyExt;
     let (parts, body) = req.into_parts();
     let body = body
@@ -154,13 +193,57 @@ async fn map_request(
             )
         })?;
 
-    let (body, mapper_ctx) = converter.convert_req_body(body)?;
+    // providers other than OpenAI only return float embeddings
+    let encode_embeddings =
+        matches!(source_endpoint, ApiEndpoint::OpenAI(OpenAI::Embeddings(_)))
+            && !matches!(target_endpoint, ApiEndpoint::OpenAI(_))
+            && embeddings::wants_base64(&body);
+    // Anthropic streams need state carried across events to translate them
+    let chat_stream =
+        if matches!(
+            source_endpoint,
+            ApiEndpoint::OpenAI(OpenAI::ChatCompletions(_))
+        ) && matches!(target_endpoint, ApiEndpoint::Anthropic(_))
+        {
+            let include_usage = anthropic_stream::wants_usage(&body);
+            Some(ChatCompletionStream::new(include_usage))
+        } else {
+            None
+        };
+    let (target_body, mapper_ctx) = converter.convert_req_body(body.clone())?;
+    let (body, params_adjusted) = if normalize_params {
+        params::normalize(
+            &body,
+            &target_endpoint,
+            mapper_ctx.model.as_ref(),
+            target_body,
+        )?
+    } else {
+        (target_body, None)
+    };
+    let body = if matches!(target_endpoint, ApiEndpoint::Anthropic(_))
+        && prompt_caching::wants_automatic_breakpoints(&parts.headers)
+    {
+        prompt_caching::add_automatic_breakpoints(body)?
+    } else {
+        body
+    };
+    let body = if mapper_ctx.is_stream
+        && stream_usage::accepts_stream_options(&target_endpoint)
+    {
+        stream_usage::include_usage(body)?
+    } else {
+        body
+    };
     let base_path = target_endpoint
         .path(mapper_ctx.model.as_ref(), mapper_ctx.is_stream)?;
 
     let target_path_and_query =
         if let Some(query_params) = target_path_and_query.query() {
-            format!("{base_path}?{query_params}")
+            // the target path may already have a query, e.g. Gemini's
+            // `alt=sse`
+            let separator = if base_path.contains('?') { '&' } else { '?' };
+            format!("{base_path}{separator}{query_params}")
         } else {
             base_path
         };
@@ -178,7 +261,7 @@ async fn map_request(
     req.extensions_mut().insert(target_path_and_query);
     req.extensions_mut().insert(mapper_ctx);
     req.extensions_mut().insert(target_endpoint);
-    Ok(req)
+    Ok((req, encode_embeddings, chat_stream, params_adjusted))
 }
 
 async fn map_response(
@@ -186,12 +269,18 @@ async fn map_response(
     source_endpoint: ApiEndpoint,
     target_endpoint: ApiEndpoint,
     resp: http::Response<crate::types::body::Body>,
+    encode_embeddings: bool,
+    chat_stream: Option<ChatCompletionStream>,
 ) -> Result<Response, ApiError> {
     let mapper_ctx = resp
         .extensions()
         .get::<MapperContext>()
         .ok_or(InternalError::ExtensionNotFound("MapperContext"))?;
     let is_stream = mapper_ctx.is_stream;
+    // synthetic responses are already in the format clients expect
+    if resp.extensions().get::<DryRun>().is_some() {
+        return Ok(resp);
+    }
     let (parts, body) = resp.into_parts();
 
     let converter = converter_registry
@@ -203,7 +292,35 @@ async fn map_response(
             )
         })?;
 
-    if is_stream {
+    if is_stream && let Some(mut chat_stream) = chat_stream {
+        tracing::trace!(
+            source_endpoint = ?target_endpoint,
+            target_endpoint = ?source_endpoint,
+            "mapped streaming response"
+        );
+        let mapped_stream = body
+            .into_data_stream()
+            .map_err(body_error)
+            .map(move |bytes| -> Result<Option<Bytes>, ApiError> {
+                let chunks = chat_stream.translate(&bytes?)?;
+                if chunks.is_empty() {
+                    return Ok(None);
+                }
+                let mut new_bytes = BytesMut::new();
+                for chunk in chunks {
+                    new_bytes.put("data: ".as_bytes());
+                    new_bytes.put(chunk);
+                    new_bytes.put("\n\n".as_bytes());
+                }
+                Ok(Some(new_bytes.freeze()))
+            })
+            .try_filter_map(|data| std::future::ready(Ok(data)))
+            .map(end_with_error_event);
+        let final_body = axum_core::body::Body::new(
+            reqwest::Body::wrap_stream(mapped_stream),
+        );
+        Ok(Response::from_parts(parts, final_body))
+    } else if is_stream {
         tracing::trace!(
             source_endpoint = ?target_endpoint,
             target_endpoint = ?source_endpoint,
@@ -215,7 +332,7 @@ async fn map_response(
         // SSE event in this branch
         let mapped_stream = body
             .into_data_stream()
-            .map_err(|e| ApiError::StreamError(StreamError::BodyError(e)))
+            .map_err(body_error)
             .try_filter_map({
                 let captured_registry = converter_registry.clone();
                 let resp_parts = parts.clone();
@@ -252,7 +369,8 @@ async fn map_response(
                         }
                     }
                 }
-            });
+            })
+            .map(end_with_error_event);
         let final_body = axum_core::body::Body::new(
             reqwest::Body::wrap_stream(mapped_stream),
         );
@@ -270,6 +388,12 @@ async fn map_response(
             .convert_resp_body(parts.clone(), body_bytes, is_stream)?
             .ok_or(MapperError::EmptyResponseBody)
             .map_err(InternalError::MapperError)?;
+        let mapped_body_bytes =
+            if encode_embeddings && parts.status.is_success() {
+                embeddings::encode_base64(&mapped_body_bytes)?
+            } else {
+                mapped_body_bytes
+            };
         let final_body = axum_core::body::Body::from(mapped_body_bytes);
         let new_resp = Response::from_parts(parts, final_body);
         tracing::trace!(
@@ -281,16 +405,56 @@ async fn map_response(
     }
 }
 
+/// The error the dispatcher's response body failed with, which is boxed into
+/// a body error for each body it's wrapped in.
+fn body_error(error: axum_core::Error) -> ApiError {
+    let mut error = error.into_inner();
+    loop {
+        error = match error.downcast::<ApiError>() {
+            Ok(error) => return *error,
+            Err(error) => match error.downcast::<axum_core::Error>() {
+                Ok(error) => error.into_inner(),
+                Err(error) => {
+                    return ApiError::StreamError(StreamError::BodyError(
+                        axum_core::Error::new(error),
+                    ));
+                }
+            },
+        };
+    }
+}
+
+/// Ends a stream which went idle with an error event rather than dropping
+/// the connection, so that clients know why it ended.
+fn end_with_error_event(
+    chunk: Result<Bytes, ApiError>,
+) -> Result<Bytes, ApiError> {
+    match chunk {
+        Err(ApiError::StreamError(error @ StreamError::IdleTimeout(_))) => {
+            Ok(error.error_event())
+        }
+        chunk => chunk,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Layer {
     endpoint_converter_registry: EndpointConverterRegistry,
+    api_translation: Option<ApiTranslation>,
+    normalize_params: bool,
 }
 
 impl Layer {
     #[must_use]
-    pub fn new(endpoint_converter_registry: EndpointConverterRegistry) -> Self {
+    pub fn new(
+        endpoint_converter_registry: EndpointConverterRegistry,
+        api_translation: Option<ApiTranslation>,
+        normalize_params: bool,
+    ) -> Self {
         Self {
             endpoint_converter_registry,
+            api_translation,
+            normalize_params,
         }
     }
 }
@@ -299,6 +463,11 @@ impl<S> tower::Layer<S> for Layer {
     type Service = Service<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        Service::new(inner, self.endpoint_converter_registry.clone())
+        Service::new(
+            inner,
+            self.endpoint_converter_registry.clone(),
+            self.api_translation,
+            self.normalize_params,
+        )
     }
 }
@@ -0,0 +1,91 @@
+//! Asks providers with `OpenAI` compatible APIs to report the usage of
+//! streamed chat completions.
+//!
+//! These providers only send usage, in a final chunk without choices, when
+//! it's requested with `stream_options.include_usage`, without which neither
+//! budgets nor the logged request could account for the tokens of a stream.
+//! The option is added to every stream sent to them, so clients may receive
+//! the usage chunk without having asked for it. The usage of streams from
+//! providers which still don't report it is estimated when they're logged,
+//! see [`Usage::estimate_stream`](crate::middleware::usage::Usage::estimate_stream).
+use bytes::Bytes;
+use serde_json::{Value, json};
+
+use crate::{
+    endpoints::{ApiEndpoint, azure::Azure, openai::OpenAI},
+    error::{api::ApiError, internal::InternalError},
+    types::provider::InferenceProvider,
+};
+
+/// Whether `target_endpoint` accepts `stream_options`.
+///
+/// Mistral rejects unknown fields, but always reports the usage of streams.
+pub(super) fn accepts_stream_options(target_endpoint: &ApiEndpoint) -> bool {
+    match target_endpoint {
+        ApiEndpoint::OpenAI(OpenAI::ChatCompletions(_))
+        | ApiEndpoint::Azure(Azure::ChatCompletions(_)) => true,
+        ApiEndpoint::OpenAICompatible {
+            provider,
+            openai_endpoint: OpenAI::ChatCompletions(_),
+        } => *provider != InferenceProvider::Mistral,
+        _ => false,
+    }
+}
+
+/// Sets `stream_options.include_usage` on a streamed chat completions
+/// request, leaving other requests as they are.
+pub(super) fn include_usage(target_bytes: Bytes) -> Result<Bytes, ApiError> {
+    let Ok(mut target) = serde_json::from_slice::<Value>(&target_bytes) else {
+        return Ok(target_bytes);
+    };
+    let is_stream = target.get("stream").and_then(Value::as_bool);
+    let includes_usage = target
+        .pointer("/stream_options/include_usage")
+        .and_then(Value::as_bool);
+    if is_stream != Some(true) || includes_usage == Some(true) {
+        return Ok(target_bytes);
+    }
+    match target.get_mut("stream_options") {
+        Some(Value::Object(options)) => {
+            options.insert("include_usage".to_string(), Value::Bool(true));
+        }
+        _ => target["stream_options"] = json!({ "include_usage": true }),
+    }
+    let target_bytes =
+        serde_json::to_vec(&target).map_err(|e| InternalError::Serialize {
+            ty: "serde_json::Value",
+            error: e,
+        })?;
+    Ok(Bytes::from(target_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_options(request: &Value) -> Value {
+        let body = Bytes::from(serde_json::to_vec(request).unwrap());
+        let body = include_usage(body).unwrap();
+        serde_json::from_slice::<Value>(&body).unwrap()["stream_options"]
+            .clone()
+    }
+
+    #[test]
+    fn usage_is_requested_for_streams_only() {
+        assert_eq!(
+            stream_options(&json!({ "model": "gpt-4o", "stream": true })),
+            json!({ "include_usage": true })
+        );
+        assert_eq!(
+            stream_options(&json!({
+                "stream": true,
+                "stream_options": { "include_usage": false }
+            })),
+            json!({ "include_usage": true })
+        );
+        assert_eq!(
+            stream_options(&json!({ "model": "gpt-4o", "stream": false })),
+            Value::Null
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::str::FromStr;
+
+use http::response::Parts;
+
+use super::{
+    TryConvertStreamData, model::ModelMapper,
+    openai_compatible::openai_error_from_gemini,
+};
+use crate::{
+    endpoints::{
+        google::generate_contents::GeminiApiError,
+        openai::OpenAICompatibleChatCompletionRequest,
+    },
+    error::mapper::MapperError,
+    middleware::mapper::{TryConvert, TryConvertError},
+    types::{model_id::ModelId, provider::InferenceProvider},
+};
+
+/// Vertex AI's OpenAI compatible endpoint serves models from several
+/// publishers, so model names are qualified by their publisher.
+const GOOGLE_PUBLISHER: &str = "google";
+
+pub struct VertexConverter {
+    model_mapper: ModelMapper,
+}
+
+impl VertexConverter {
+    #[must_use]
+    pub fn new(model_mapper: ModelMapper) -> Self {
+        Self { model_mapper }
+    }
+}
+
+impl
+    TryConvert<
+        async_openai::types::CreateChatCompletionRequest,
+        OpenAICompatibleChatCompletionRequest,
+    > for VertexConverter
+{
+    type Error = MapperError;
+    fn try_convert(
+        &self,
+        mut value: async_openai::types::CreateChatCompletionRequest,
+    ) -> Result<OpenAICompatibleChatCompletionRequest, Self::Error> {
+        let source_model = match ModelId::from_str(&value.model)? {
+            // Vertex serves Gemini's models under the same names
+            ModelId::ModelIdWithVersion {
+                provider: InferenceProvider::GoogleGemini,
+                id,
+            } => ModelId::ModelIdWithVersion {
+                provider: InferenceProvider::VertexAI,
+                id,
+            },
+            source_model => source_model,
+        };
+        let target_model = self
+            .model_mapper
+            .map_model(&source_model, &InferenceProvider::VertexAI)?;
+        tracing::trace!(source_model = ?source_model, target_model = ?target_model, "mapped model");
+        value.model = format!("{GOOGLE_PUBLISHER}/{target_model}");
+
+        Ok(OpenAICompatibleChatCompletionRequest {
+            provider: InferenceProvider::VertexAI,
+            inner: value,
+        })
+    }
+}
+
+impl
+    TryConvert<
+        async_openai::types::CreateChatCompletionResponse,
+        async_openai::types::CreateChatCompletionResponse,
+    > for VertexConverter
+{
+    type Error = MapperError;
+    fn try_convert(
+        &self,
+        value: async_openai::types::CreateChatCompletionResponse,
+    ) -> Result<async_openai::types::CreateChatCompletionResponse, Self::Error>
+    {
+        Ok(value)
+    }
+}
+
+impl
+    TryConvertStreamData<
+        async_openai::types::CreateChatCompletionStreamResponse,
+        async_openai::types::CreateChatCompletionStreamResponse,
+    > for VertexConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_chunk(
+        &self,
+        value: async_openai::types::CreateChatCompletionStreamResponse,
+    ) -> Result<
+        Option<async_openai::types::CreateChatCompletionStreamResponse>,
+        Self::Error,
+    > {
+        Ok(Some(value))
+    }
+}
+
+impl TryConvertError<GeminiApiError, async_openai::error::WrappedError>
+    for VertexConverter
+{
+    type Error = MapperError;
+
+    fn try_convert_error(
+        &self,
+        resp_parts: &Parts,
+        value: GeminiApiError,
+    ) -> Result<async_openai::error::WrappedError, Self::Error> {
+        Ok(openai_error_from_gemini(resp_parts, value))
+    }
+}
@@ -1,8 +1,26 @@
 pub mod add_extension;
 pub mod auth;
+pub mod body_limit;
+pub mod budget;
 pub mod cache;
+pub mod concurrency_limit;
+pub mod cors;
+pub mod evaluation;
+pub mod idempotency;
+pub mod json_repair;
+pub mod load_shed;
 pub mod mapper;
+pub mod model_access;
+pub mod model_alias;
 pub mod prompts;
+pub mod provider_override;
 pub mod rate_limit;
 pub mod request_context;
 pub mod response_headers;
+pub mod routing_rules;
+pub mod shadow;
+pub mod stream_buffer;
+pub mod stream_limit;
+pub mod target_model;
+pub mod transform;
+pub mod usage;
@@ -0,0 +1,182 @@
+//! Rejects requests for models a router doesn't allow, see
+//! [`RouterConfig::allowed_models`].
+//!
+//! This runs before the cache, so that a disallowed model is never served,
+//! even from a response cached before the router's config changed.
+use std::{
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use futures::future::BoxFuture;
+use http_body_util::BodyExt;
+use serde::Deserialize;
+
+use crate::{
+    config::router::RouterConfig,
+    error::{
+        api::ApiError, internal::InternalError,
+        invalid_req::InvalidRequestError,
+    },
+    types::{
+        extensions::TargetModel,
+        model_id::{ModelId, ModelIdWithoutVersion},
+        multipart,
+        request::Request,
+        response::Response,
+    },
+};
+
+/// The models a router allows, also used to list the router's models.
+#[derive(Debug)]
+pub(crate) struct ModelAccess {
+    allowed: Vec<ModelIdWithoutVersion>,
+    denied: Vec<ModelIdWithoutVersion>,
+}
+
+impl ModelAccess {
+    /// `None` if the router allows every model.
+    pub(crate) fn for_router(router_config: &RouterConfig) -> Option<Self> {
+        if router_config.allowed_models.is_empty()
+            && router_config.denied_models.is_empty()
+        {
+            return None;
+        }
+        let without_version = |models: &[ModelId]| {
+            models
+                .iter()
+                .cloned()
+                .map(ModelIdWithoutVersion::from)
+                .collect()
+        };
+        Some(Self {
+            allowed: without_version(&router_config.allowed_models),
+            denied: without_version(&router_config.denied_models),
+        })
+    }
+
+    pub(crate) fn is_allowed(&self, model: &ModelId) -> bool {
+        let model = ModelIdWithoutVersion::from(model.clone());
+        (self.allowed.is_empty() || self.allowed.contains(&model))
+            && !self.denied.contains(&model)
+    }
+}
+
+/// The only field we need from the request body.
+#[derive(Debug, Deserialize)]
+struct RequestModel {
+    model: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    access: Option<Arc<ModelAccess>>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            access: ModelAccess::for_router(router_config).map(Arc::new),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            access: self.access.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    access: Option<Arc<ModelAccess>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(access) = self.access.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        // model weighted routers have already chosen the model
+        if let Some(TargetModel(model)) = req.extensions().get::<TargetModel>()
+        {
+            if access.is_allowed(model) {
+                return Box::pin(inner.call(req));
+            }
+            let model = match model.inference_provider() {
+                Some(provider) => format!("{provider}/{model}"),
+                None => model.to_string(),
+            };
+            tracing::debug!(model = %model, "model not allowed");
+            let error = InvalidRequestError::ModelNotAllowed(model);
+            return Box::pin(async move { Err(error.into()) });
+        }
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            let model =
+                if let Some(boundary) = multipart::boundary(&parts.headers) {
+                    multipart::field(&body, &boundary, "model")
+                } else {
+                    serde_json::from_slice::<RequestModel>(&body)
+                        .ok()
+                        .map(|request| request.model)
+                };
+            // without a model we can't tell, so only routers with an allow
+            // list reject the request
+            let is_allowed = match model
+                .as_deref()
+                .map(ModelId::from_str)
+                .and_then(Result::ok)
+            {
+                Some(model_id) => access.is_allowed(&model_id),
+                None => access.allowed.is_empty(),
+            };
+            if !is_allowed {
+                tracing::debug!(model = ?model, "model not allowed");
+                return Err(InvalidRequestError::ModelNotAllowed(
+                    model.unwrap_or_default(),
+                )
+                .into());
+            }
+            inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await
+        })
+    }
+}
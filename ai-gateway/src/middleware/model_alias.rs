@@ -0,0 +1,221 @@
+//! Rewrites the model requested through a router's aliases, see
+//! [`RouterConfig::model_aliases`].
+//!
+//! This runs before the model is checked against the router's allowed models,
+//! before the cache and before a provider is selected, so everything
+//! downstream, including logging and billing, only sees the rewritten model.
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use bytes::Bytes;
+use futures::{TryStreamExt, future::BoxFuture};
+use http_body_util::BodyExt;
+use serde_json::Value;
+
+use crate::{
+    config::router::RouterConfig,
+    error::{api::ApiError, internal::InternalError},
+    types::{
+        request::{Request, is_stream},
+        response::Response,
+    },
+};
+
+#[derive(Debug)]
+struct Aliases {
+    models: HashMap<String, String>,
+    preserve_in_response: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    aliases: Option<Arc<Aliases>>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        if router_config.model_aliases.is_empty() {
+            return Self { aliases: None };
+        }
+        Self {
+            aliases: Some(Arc::new(Aliases {
+                models: router_config.model_aliases.clone(),
+                preserve_in_response: router_config.preserve_alias_in_response,
+            })),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            aliases: self.aliases.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    aliases: Option<Arc<Aliases>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(aliases) = self.aliases.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            let mut request: Value = match serde_json::from_slice(&body) {
+                Ok(request) => request,
+                // e.g. multipart audio requests
+                Err(_) => {
+                    return inner
+                        .call(Request::from_parts(parts, Body::from(body)))
+                        .await;
+                }
+            };
+            let Some((alias, model)) = request
+                .get("model")
+                .and_then(Value::as_str)
+                .and_then(|alias| aliases.models.get_key_value(alias))
+            else {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            };
+            let alias = alias.clone();
+            tracing::debug!(
+                alias = %alias,
+                model = %model,
+                "rewrote model alias"
+            );
+            request["model"] = Value::String(model.clone());
+            let body = serde_json::to_vec(&request).map_err(|e| {
+                InternalError::Serialize {
+                    ty: "serde_json::Value",
+                    error: e,
+                }
+            })?;
+            let is_stream = is_stream(&body);
+
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await?;
+            if !aliases.preserve_in_response {
+                return Ok(response);
+            }
+            echo_alias(response, alias, is_stream).await
+        })
+    }
+}
+
+/// Replaces the `model` field of the response, or of each streamed event,
+/// with the alias the client requested.
+async fn echo_alias(
+    response: Response,
+    alias: String,
+    is_stream: bool,
+) -> Result<Response, ApiError> {
+    let (mut parts, body) = response.into_parts();
+    // the length of the body changes
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    if is_stream {
+        // every frame is a complete SSE event
+        let events = body
+            .into_data_stream()
+            .map_ok(move |event| event_with_model(&event, &alias));
+        return Ok(Response::from_parts(parts, Body::from_stream(events)));
+    }
+    let body = body
+        .collect()
+        .await
+        .map_err(InternalError::CollectBodyError)?
+        .to_bytes();
+    let body = with_model(&body, &alias).map_or(body, Bytes::from);
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn event_with_model(event: &Bytes, alias: &str) -> Bytes {
+    let Ok(event) = std::str::from_utf8(event) else {
+        return event.clone();
+    };
+    let mut rewritten = String::with_capacity(event.len());
+    for line in event.split_inclusive('\n') {
+        let data = line.strip_prefix("data:").map(str::trim);
+        match data.and_then(|data| with_model(data.as_bytes(), alias)) {
+            Some(data) => {
+                rewritten.push_str("data: ");
+                rewritten.push_str(&String::from_utf8_lossy(&data));
+                rewritten.push_str(&line[line.trim_end().len()..]);
+            }
+            None => rewritten.push_str(line),
+        }
+    }
+    Bytes::from(rewritten)
+}
+
+/// The JSON object with its `model` field replaced, or `None` if it doesn't
+/// have one.
+fn with_model(json: &[u8], model: &str) -> Option<Vec<u8>> {
+    let mut value: Value = serde_json::from_slice(json).ok()?;
+    let field = value.get_mut("model").filter(|field| field.is_string())?;
+    *field = Value::String(model.to_string());
+    serde_json::to_vec(&value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamed_events_echo_the_alias() {
+        let event = Bytes::from(
+            "data: {\"id\":\"1\",\"model\":\"gpt-4o-mini\"}\n\ndata: \
+             [DONE]\n\n",
+        );
+
+        let rewritten = event_with_model(&event, "fast");
+
+        assert_eq!(
+            rewritten,
+            "data: {\"id\":\"1\",\"model\":\"fast\"}\n\ndata: [DONE]\n\n"
+        );
+    }
+}
@@ -20,6 +20,7 @@ use crate::{
     store::minio::MinioClient,
     types::{
         extensions::{AuthContext, PromptContext},
+        multipart,
         request::Request,
         response::{JawnResponse, Response},
     },
@@ -81,6 +82,11 @@ where
         let mut inner = self.inner.clone();
         let app_state = self.app_state.clone();
         std::mem::swap(&mut self.inner, &mut inner);
+        // prompts are only referenced from JSON bodies, and multipart bodies
+        // (e.g. audio uploads) aren't JSON
+        if multipart::is_multipart(req.headers()) {
+            return Box::pin(inner.call(req));
+        }
         Box::pin(async move {
             let req = tokio::task::spawn_blocking(move || async move {
                 build_prompt_request(app_state, req)
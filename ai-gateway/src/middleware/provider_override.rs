@@ -0,0 +1,205 @@
+//! Lets clients pin a request to one of a router's providers with the
+//! `helicone-provider` header, bypassing the router's load balancing, see
+//! [`RouterConfig::allow_provider_override`].
+//!
+//! Only providers in the router's configured pool can be pinned, requests for
+//! any other provider are rejected with the allowed set. The header is ignored
+//! by routers which don't allow overrides.
+//!
+//! Requests routed to a provider by the router's
+//! [`routing_rules`](super::routing_rules) are pinned the same way, unless
+//! the client pinned them to another provider.
+use std::{
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::response::IntoResponse;
+use futures::future::BoxFuture;
+use http::HeaderName;
+use indexmap::IndexMap;
+use tower::ServiceExt;
+
+use crate::{
+    app_state::AppState,
+    config::{balance::BalanceConfigInner, router::RouterConfig},
+    dispatcher::{Dispatcher, DispatcherService},
+    error::{api::ApiError, init::InitError, invalid_req::InvalidRequestError},
+    types::{
+        extensions::{ProviderOverride, RoutedProvider},
+        provider::InferenceProvider,
+        request::Request,
+        response::Response,
+        router::RouterId,
+    },
+};
+
+pub(crate) const PROVIDER_OVERRIDE_HEADER: HeaderName =
+    HeaderName::from_static("helicone-provider");
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    dispatchers: Option<Arc<IndexMap<InferenceProvider, DispatcherService>>>,
+    allow_override: bool,
+}
+
+impl Layer {
+    /// Creates a dispatcher for each provider of the balance config, if the
+    /// router allows overrides or routes requests to providers.
+    pub async fn for_balance_config(
+        app_state: &AppState,
+        router_id: &RouterId,
+        router_config: &Arc<RouterConfig>,
+        balance_config: &BalanceConfigInner,
+    ) -> Result<Self, InitError> {
+        let allow_override = router_config.allow_provider_override;
+        let routes_to_providers =
+            router_config.routing_rules.as_ref().is_some_and(|rules| {
+                rules.targets().any(|target| target.provider.is_some())
+            });
+        if !allow_override && !routes_to_providers {
+            return Ok(Self {
+                dispatchers: None,
+                allow_override,
+            });
+        }
+        let mut dispatchers = IndexMap::new();
+        for provider in balance_config.providers() {
+            let dispatcher = Dispatcher::new(
+                app_state.clone(),
+                router_id,
+                router_config,
+                provider.clone(),
+            )
+            .await?;
+            dispatchers.insert(provider, dispatcher);
+        }
+        Ok(Self {
+            dispatchers: Some(Arc::new(dispatchers)),
+            allow_override,
+        })
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            dispatchers: self.dispatchers.clone(),
+            allow_override: self.allow_override,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    dispatchers: Option<Arc<IndexMap<InferenceProvider, DispatcherService>>>,
+    allow_override: bool,
+}
+
+impl<S> Service<S> {
+    /// The dispatcher for the provider the client pinned the request to, if
+    /// any.
+    fn pinned_dispatcher(
+        &self,
+        req: &Request,
+    ) -> Result<Option<(InferenceProvider, DispatcherService)>, ApiError> {
+        let (Some(dispatchers), Some(value), true) = (
+            &self.dispatchers,
+            req.headers().get(PROVIDER_OVERRIDE_HEADER),
+            self.allow_override,
+        ) else {
+            return Ok(None);
+        };
+        let value = value
+            .to_str()
+            .map_err(InvalidRequestError::InvalidRequestHeader)?;
+        let Ok(provider) = InferenceProvider::from_str(value.trim());
+        let Some(dispatcher) = dispatchers.get(&provider) else {
+            return Err(InvalidRequestError::ProviderOverrideNotAllowed {
+                provider: provider.to_string(),
+                allowed: dispatchers
+                    .keys()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }
+            .into());
+        };
+        Ok(Some((provider, dispatcher.clone())))
+    }
+
+    /// The dispatcher for the provider the router's routing rules sent the
+    /// request to, if it's one of this balancer's providers.
+    fn routed_dispatcher(
+        &self,
+        req: &Request,
+    ) -> Option<(InferenceProvider, DispatcherService)> {
+        let RoutedProvider(provider) = req.extensions().get()?;
+        let dispatcher = self.dispatchers.as_ref()?.get(provider)?;
+        Some((provider.clone(), dispatcher.clone()))
+    }
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let (provider, dispatcher) = match self.pinned_dispatcher(&req) {
+            Ok(Some(pinned)) => pinned,
+            Ok(None) => {
+                let Some((provider, dispatcher)) = self.routed_dispatcher(&req)
+                else {
+                    return Box::pin(inner.call(req));
+                };
+                tracing::debug!(provider = %provider, "provider routed by rules");
+                return Box::pin(async move {
+                    match dispatcher.oneshot(req).await {
+                        Ok(response) => Ok(response),
+                        Err(e) => match e {},
+                    }
+                });
+            }
+            // returned as a response since errors below the router's buffer
+            // would otherwise surface as internal errors
+            Err(e) => {
+                return Box::pin(std::future::ready(Ok(e.into_response())));
+            }
+        };
+        tracing::debug!(provider = %provider, "provider overridden by client");
+        req.extensions_mut()
+            .insert(ProviderOverride(provider.clone()));
+        Box::pin(async move {
+            let mut response = match dispatcher.oneshot(req).await {
+                Ok(response) => response,
+                Err(e) => match e {},
+            };
+            response.extensions_mut().insert(ProviderOverride(provider));
+            Ok(response)
+        })
+    }
+}
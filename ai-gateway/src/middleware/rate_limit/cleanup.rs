@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use chrono::Utc;
 use futures::future::BoxFuture;
 use meltdown::Token;
 use tracing::{error, info};
@@ -29,18 +30,12 @@ impl meltdown::Service for GarbageCollector {
         let cleanup_interval = self.cleanup_interval;
         Box::pin(async move {
             tokio::task::spawn_blocking(move || async move {
-                let global_rate_limit = app_state.0.global_rate_limit.clone();
+                let rate_limits = app_state.0.in_memory_rate_limits.clone();
                 loop {
                     tokio::select! {
                         () = tokio::time::sleep(cleanup_interval) => {
-                            if let Some(global_rate_limit) = global_rate_limit.as_ref() {
-                                global_rate_limit.limiter().retain_recent();
-                            }
-
-                            let router_limits = app_state.0.router_rate_limits.read().await;
-                            for rate_limit_config in router_limits.values() {
-                                rate_limit_config.limiter().retain_recent();
-                            }
+                            let now = Utc::now().timestamp_micros();
+                            rate_limits.retain_recent(now);
                         }
                         () = &mut token => {
                             info!(name = "rate-limiting-cleanup-task", "task shutting down");
@@ -1,37 +1,95 @@
-use http::Request;
-use tower_governor::{GovernorError, key_extractor::KeyExtractor};
-
 use crate::{
-    error::internal::InternalError,
-    types::{extensions::AuthContext, router::RouterId, user::UserId},
+    control_plane::types::hash_key, error::internal::InternalError,
+    types::extensions::AuthContext,
 };
 
-#[derive(Debug, Clone)]
-pub struct RateLimitKeyExtractor;
+/// The authenticated caller of a request, which rate limits are keyed on.
+pub fn get_auth_context<T>(
+    req: &http::Request<T>,
+) -> Result<&AuthContext, InternalError> {
+    req.extensions()
+        .get::<AuthContext>()
+        .ok_or(InternalError::ExtensionNotFound("AuthContext"))
+}
 
-impl KeyExtractor for RateLimitKeyExtractor {
-    type Key = UserId;
-    fn extract<T>(&self, req: &Request<T>) -> Result<Self::Key, GovernorError> {
-        get_user_id(req).map_err(|_| GovernorError::UnableToExtractKey)
-    }
+/// Keys on the hash of the caller's API key, so that API keys aren't stored
+/// in plain text, falling back to their organization for requests without
+/// one.
+///
+/// Keys are namespaced by the kind of limit, its scope, e.g. a router, and
+/// the organization, so that limits sharing a store are independent.
+#[must_use]
+pub fn per_api_key_rl_key(scope: &str, ctx: &AuthContext) -> String {
+    api_key_rl_key("per-api-key", scope, ctx)
 }
 
-fn get_user_id<T>(req: &Request<T>) -> Result<UserId, InternalError> {
-    let Some(ctx) = req.extensions().get::<AuthContext>() else {
-        return Err(InternalError::ExtensionNotFound("AuthContext"));
-    };
+#[must_use]
+pub fn tokens_per_api_key_rl_key(scope: &str, ctx: &AuthContext) -> String {
+    api_key_rl_key("tokens-per-api-key", scope, ctx)
+}
 
-    Ok(ctx.user_id)
+/// Keys on the glob rather than the model, so that all the models matching a
+/// glob share its limit.
+#[must_use]
+pub fn per_model_rl_key(scope: &str, ctx: &AuthContext, glob: &str) -> String {
+    format!("{}:{glob}", api_key_rl_key("per-model", scope, ctx))
 }
 
-pub fn get_redis_rl_key<T>(
-    req: &Request<T>,
-    router_id: Option<&RouterId>,
-) -> Result<String, InternalError> {
-    let user_id = get_user_id(req)?;
-    if let Some(router_id) = router_id {
-        Ok(format!("rl:per-api-key:{router_id}:{user_id}"))
+fn api_key_rl_key(limit: &str, scope: &str, ctx: &AuthContext) -> String {
+    let api_key = ctx.api_key.expose();
+    if api_key.is_empty() {
+        format!("rl:{limit}:{scope}:{}", ctx.org_id)
     } else {
-        Ok(format!("rl:per-api-key:GLOBAL:{user_id}"))
+        format!("rl:{limit}:{scope}:{}:{}", ctx.org_id, hash_key(api_key))
+    }
+}
+
+#[must_use]
+pub fn per_org_rl_key(scope: &str, ctx: &AuthContext) -> String {
+    format!("rl:per-org:{scope}:{}", ctx.org_id)
+}
+
+#[must_use]
+pub fn per_user_rl_key(scope: &str, ctx: &AuthContext) -> String {
+    format!("rl:per-user:{scope}:{}:{}", ctx.org_id, ctx.user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::types::{org::OrgId, secret::Secret, user::UserId};
+
+    fn ctx(api_key: &str, org_id: OrgId) -> AuthContext {
+        AuthContext {
+            api_key: Secret::from(api_key.to_string()),
+            user_id: UserId::new(Uuid::new_v4()),
+            org_id,
+        }
+    }
+
+    #[test]
+    fn keys_are_namespaced_by_limit_scope_and_org() {
+        let org_id = OrgId::new(Uuid::new_v4());
+        let auth_ctx = ctx("sk-helicone-test-key", org_id);
+        let key = per_api_key_rl_key("my-router", &auth_ctx);
+        assert!(
+            key.starts_with(&format!("rl:per-api-key:my-router:{org_id}:"))
+        );
+        assert!(!key.contains("sk-helicone-test-key"));
+
+        assert_ne!(key, per_api_key_rl_key("other-router", &auth_ctx));
+        assert_ne!(key, tokens_per_api_key_rl_key("my-router", &auth_ctx));
+        assert_ne!(
+            per_model_rl_key("my-router", &auth_ctx, "openai/o3*"),
+            per_model_rl_key("my-router", &auth_ctx, "*")
+        );
+        assert_ne!(
+            per_org_rl_key("my-router", &auth_ctx),
+            per_user_rl_key("my-router", &auth_ctx)
+        );
+        let other_org = ctx("sk-helicone-test-key", OrgId::new(Uuid::new_v4()));
+        assert_ne!(key, per_api_key_rl_key("my-router", &other_org));
     }
 }
@@ -1,6 +1,8 @@
 pub mod cleanup;
 pub mod extractor;
-pub mod redis_service;
+mod per_model;
+mod queue;
 pub mod service;
+pub mod store;
 
 pub use self::service::{Layer, Service};
@@ -0,0 +1,150 @@
+//! Rate limits scoped to the models matching a glob, see
+//! [`LimitsConfig::per_model`](crate::config::rate_limit::LimitsConfig::per_model).
+//!
+//! Globs match the `model` of a request's body as sent, e.g.
+//! `openai/o3-mini`, with `*` matching any number of characters.
+use std::cmp::Reverse;
+
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use super::store::Gcra;
+use crate::config::rate_limit::ModelLimitConfig;
+
+#[derive(Debug)]
+pub(super) struct ModelLimit {
+    pub(super) glob: String,
+    pub(super) gcra: Gcra,
+}
+
+/// The per model limits, ordered most specific first.
+#[derive(Debug, Default)]
+pub(super) struct ModelLimits(Vec<ModelLimit>);
+
+impl ModelLimits {
+    pub(super) fn new(config: &IndexMap<String, ModelLimitConfig>) -> Self {
+        let mut limits = config
+            .iter()
+            .map(|(glob, limit)| ModelLimit {
+                glob: glob.clone(),
+                gcra: Gcra::per_minute(limit.rpm),
+            })
+            .collect::<Vec<_>>();
+        // stable, so that equally specific globs keep their config order
+        limits.sort_by_key(|limit| Reverse(specificity(&limit.glob)));
+        Self(limits)
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The most specific limit matching the model, if any.
+    pub(super) fn for_model(&self, model: &str) -> Option<&ModelLimit> {
+        self.0.iter().find(|limit| matches(&limit.glob, model))
+    }
+}
+
+/// The only field we need from the request body.
+#[derive(Debug, Deserialize)]
+struct RequestModel {
+    model: String,
+}
+
+/// The model of a JSON request body, if it has one.
+pub(super) fn request_model(body: &[u8]) -> Option<String> {
+    serde_json::from_slice::<RequestModel>(body)
+        .ok()
+        .map(|request| request.model)
+}
+
+/// Exact models are more specific than any glob, and globs with more literal
+/// characters are more specific than those with fewer.
+fn specificity(glob: &str) -> (bool, usize) {
+    let literals = glob.chars().filter(|c| *c != '*').count();
+    (!glob.contains('*'), literals)
+}
+
+fn matches(glob: &str, model: &str) -> bool {
+    let mut parts = glob.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = model.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    if parts.peek().is_none() {
+        // no wildcards
+        return rest.is_empty();
+    }
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    fn limits(globs: &[&str]) -> ModelLimits {
+        let config = globs
+            .iter()
+            .enumerate()
+            .map(|(i, glob)| {
+                let rpm = NonZeroU32::new(u32::try_from(i + 1).unwrap());
+                ((*glob).to_string(), ModelLimitConfig { rpm: rpm.unwrap() })
+            })
+            .collect();
+        ModelLimits::new(&config)
+    }
+
+    fn glob_for(limits: &ModelLimits, model: &str) -> Option<String> {
+        limits.for_model(model).map(|limit| limit.glob.clone())
+    }
+
+    #[test]
+    fn globs_match_models() {
+        assert!(matches("*", "openai/gpt-4o-mini"));
+        assert!(matches("openai/o3*", "openai/o3-mini"));
+        assert!(matches("openai/o3*", "openai/o3"));
+        assert!(!matches("openai/o3*", "anthropic/o3"));
+        assert!(matches("*/claude-*-sonnet", "anthropic/claude-3-7-sonnet"));
+        assert!(!matches("*/claude-*-sonnet", "anthropic/claude-3-7-opus"));
+        assert!(matches("openai/gpt-4o", "openai/gpt-4o"));
+        assert!(!matches("openai/gpt-4o", "openai/gpt-4o-mini"));
+    }
+
+    #[test]
+    fn most_specific_limit_applies() {
+        let limits =
+            limits(&["*", "openai/*", "openai/o3*", "openai/o3-mini", "*o3*"]);
+        assert_eq!(
+            glob_for(&limits, "openai/o3-mini").as_deref(),
+            Some("openai/o3-mini")
+        );
+        assert_eq!(
+            glob_for(&limits, "openai/o3-pro").as_deref(),
+            Some("openai/o3*")
+        );
+        assert_eq!(
+            glob_for(&limits, "openai/gpt-4o").as_deref(),
+            Some("openai/*")
+        );
+        assert_eq!(glob_for(&limits, "azure/o3").as_deref(), Some("*o3*"));
+        assert_eq!(glob_for(&limits, "anthropic/claude").as_deref(), Some("*"));
+        assert!(
+            limits(&["openai/*"])
+                .for_model("anthropic/claude")
+                .is_none()
+        );
+    }
+}
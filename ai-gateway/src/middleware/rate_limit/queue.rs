@@ -0,0 +1,229 @@
+//! Parks requests over their rate limits until they're allowed, see
+//! [`QueueConfig`].
+//!
+//! A queued request waits for its API key's requests queued before it, then
+//! for as long as its limits say it must, after which they're checked again.
+//! Nothing has been sent for a queued request yet, so streaming requests are
+//! queued like any other.
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use opentelemetry::KeyValue;
+use rustc_hash::FxHashMap as HashMap;
+use tokio::sync::OwnedMutexGuard;
+use tokio_util::sync::CancellationToken;
+
+use super::extractor::per_api_key_rl_key;
+use crate::{
+    app_state::AppState, config::rate_limit::QueueConfig, metrics::Metrics,
+    types::extensions::AuthContext,
+};
+
+/// Taken by each queued request of an API key in turn, in the order they
+/// arrived, since tokio's mutexes are fair.
+type Turn = Arc<tokio::sync::Mutex<()>>;
+
+#[derive(Debug, Default)]
+struct Waiting {
+    depth: usize,
+    /// The turns of the API keys with requests queued.
+    turns: HashMap<String, Turn>,
+}
+
+#[derive(Debug)]
+pub(super) struct Queue {
+    scope: String,
+    config: QueueConfig,
+    waiting: Mutex<Waiting>,
+    metrics: Metrics,
+    shutdown: CancellationToken,
+}
+
+impl Queue {
+    pub(super) fn new(
+        app_state: &AppState,
+        scope: String,
+        config: QueueConfig,
+    ) -> Self {
+        Self {
+            scope,
+            config,
+            waiting: Mutex::default(),
+            metrics: app_state.0.metrics.clone(),
+            shutdown: app_state.0.shutdown.clone(),
+        }
+    }
+
+    /// Queues a request from `ctx`, or returns `None` if the queue is full.
+    pub(super) fn join(&self, ctx: &AuthContext) -> Option<Ticket<'_>> {
+        let caller = per_api_key_rl_key(&self.scope, ctx);
+        let mut waiting =
+            self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        if waiting.depth >= self.config.max_queue_depth {
+            return None;
+        }
+        waiting.depth += 1;
+        let turn = waiting.turns.entry(caller.clone()).or_default().clone();
+        self.record_depth(waiting.depth);
+        Some(Ticket {
+            queue: self,
+            caller,
+            turn: Some(turn),
+            guard: None,
+            queued_at: Instant::now(),
+        })
+    }
+
+    fn record_depth(&self, depth: usize) {
+        self.metrics.rate_limit_queue_depth.record(
+            u64::try_from(depth).unwrap_or(u64::MAX),
+            &[KeyValue::new("scope", self.scope.clone())],
+        );
+    }
+}
+
+/// A queued request, which leaves the queue on drop.
+#[derive(Debug)]
+pub(super) struct Ticket<'a> {
+    queue: &'a Queue,
+    caller: String,
+    turn: Option<Turn>,
+    /// Held once it's the request's turn.
+    guard: Option<OwnedMutexGuard<()>>,
+    queued_at: Instant,
+}
+
+impl Ticket<'_> {
+    /// Waits for the API key's requests queued before this one, then until
+    /// `retry_after` has passed, returning `false` if the request would wait
+    /// longer than `max-wait` or the gateway is shutting down.
+    pub(super) async fn wait(&mut self, retry_after: Duration) -> bool {
+        let queue = self.queue;
+        let deadline = self.queued_at + queue.config.max_wait;
+        let allowed_at = Instant::now() + retry_after;
+        // no point waiting for limits that won't allow the request in time
+        if allowed_at > deadline {
+            return false;
+        }
+        let wait = async {
+            if self.guard.is_none()
+                && let Some(turn) = &self.turn
+            {
+                self.guard = Some(turn.clone().lock_owned().await);
+            }
+            tokio::time::sleep_until(allowed_at.into()).await;
+        };
+        tokio::select! {
+            biased;
+            () = queue.shutdown.cancelled() => false,
+            () = tokio::time::sleep_until(deadline.into()) => false,
+            () = wait => true,
+        }
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        let queue = self.queue;
+        queue.metrics.rate_limit_queue_wait.record(
+            self.queued_at.elapsed().as_secs_f64() * 1000.0,
+            &[KeyValue::new("scope", queue.scope.clone())],
+        );
+        let mut waiting =
+            queue.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        waiting.depth = waiting.depth.saturating_sub(1);
+        // turns are only kept for API keys with requests queued, i.e. held by
+        // more than the map
+        if let Some(turn) = self.turn.take()
+            && Arc::strong_count(&turn) == 2
+        {
+            waiting.turns.remove(&self.caller);
+        }
+        queue.record_depth(waiting.depth);
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        app::App,
+        config::Config,
+        tests::TestDefault,
+        types::{
+            org::OrgId,
+            provider::{ProviderKeyMap, ProviderKeys},
+            secret::Secret,
+            user::UserId,
+        },
+    };
+
+    async fn app_state() -> AppState {
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+        App::with_provider_keys(Config::test_default(), provider_keys)
+            .await
+            .expect("failed to create app")
+            .state
+    }
+
+    fn ctx() -> AuthContext {
+        AuthContext {
+            api_key: Secret::from("sk-helicone-test-key".to_string()),
+            user_id: UserId::new(Uuid::new_v4()),
+            org_id: OrgId::new(Uuid::new_v4()),
+        }
+    }
+
+    #[tokio::test]
+    async fn full_queues_reject_requests() {
+        let app_state = app_state().await;
+        let config = QueueConfig {
+            max_wait: Duration::from_secs(1),
+            max_queue_depth: 1,
+        };
+        let queue = Queue::new(&app_state, "GLOBAL".to_string(), config);
+        let ctx = ctx();
+        let ticket = queue.join(&ctx).unwrap();
+        assert!(queue.join(&ctx).is_none());
+        drop(ticket);
+        assert!(queue.join(&ctx).is_some());
+        // the API key's turn is dropped with its last queued request
+        let waiting = queue.waiting.lock().unwrap();
+        assert_eq!(waiting.depth, 0);
+        assert!(waiting.turns.is_empty());
+    }
+
+    #[tokio::test]
+    async fn queued_requests_are_rejected_on_shutdown() {
+        let app_state = app_state().await;
+        let config = QueueConfig {
+            max_wait: Duration::from_secs(10),
+            max_queue_depth: 10,
+        };
+        let queue = Queue::new(&app_state, "GLOBAL".to_string(), config);
+        let ctx = ctx();
+        let mut ticket = queue.join(&ctx).unwrap();
+        // waits that would outlast `max-wait` are rejected right away
+        assert!(!ticket.wait(Duration::from_secs(11)).await);
+        assert!(ticket.wait(Duration::from_millis(10)).await);
+
+        let mut next = queue.join(&ctx).unwrap();
+        let started = Instant::now();
+        tokio::spawn({
+            let shutdown = app_state.0.shutdown.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                shutdown.cancel();
+            }
+        });
+        // waiting for its turn, which the first request still holds
+        assert!(!next.wait(Duration::ZERO).await);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -1,330 +1,803 @@
 use std::{
-    future::Future,
-    pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
-use governor::middleware::StateInformationMiddleware;
-use http::Response;
-use tower_governor::GovernorLayer;
+use axum_core::body::Body;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use http::HeaderValue;
+use http_body_util::BodyExt;
 
-use super::extractor::RateLimitKeyExtractor;
+use super::{
+    extractor::{
+        get_auth_context, per_api_key_rl_key, per_model_rl_key, per_org_rl_key,
+        per_user_rl_key, tokens_per_api_key_rl_key,
+    },
+    per_model::{ModelLimits, request_model},
+    queue::Queue,
+    store::{Decision, Gcra, GcraStore, RedisStore},
+};
 use crate::{
     app_state::AppState,
     config::{
+        DeploymentTarget,
         rate_limit::{
-            LimitsConfig, RateLimitConfig, RateLimitStore, RateLimiterConfig,
+            LimitsConfig, QueueConfig, RateLimitConfig, RateLimitStore,
         },
+        redis::UnavailablePolicy,
         router::RouterConfig,
     },
-    error::init::InitError,
-    middleware::rate_limit::redis_service::{
-        RedisRateLimitLayer, RedisRateLimitService,
+    error::{
+        api::ApiError,
+        init::InitError,
+        internal::InternalError,
+        invalid_req::{InvalidRequestError, TooManyRequestsError},
+    },
+    middleware::usage::{Usage, UsageBody, is_event_stream},
+    tokenizer::estimate_input_tokens,
+    types::{
+        extensions::AuthContext, request::Request, response::Response,
+        router::RouterId,
     },
-    types::router::RouterId,
 };
 
-pub type OptionalGovernorLayer =
-    Option<GovernorLayer<RateLimitKeyExtractor, StateInformationMiddleware>>;
-pub type GovernorService<S> = tower_governor::governor::Governor<
-    RateLimitKeyExtractor,
-    StateInformationMiddleware,
-    S,
->;
-
-#[derive(Clone)]
-pub enum InnerLayer {
-    None,
-    InMemory(GovernorLayer<RateLimitKeyExtractor, StateInformationMiddleware>),
-    Redis(RedisRateLimitLayer),
+/// When the request arrived, in microseconds.
+fn request_time(req: &Request) -> i64 {
+    req.extensions()
+        .get::<DateTime<Utc>>()
+        .copied()
+        .unwrap_or_else(|| {
+            tracing::warn!(
+                "did not find expected DateTime<Utc> in req extensions"
+            );
+            Utc::now()
+        })
+        .timestamp_micros()
 }
 
-#[derive(Clone)]
-pub struct Layer {
-    inner: InnerLayer,
+/// Rounds up, so that retrying after the returned number of seconds
+/// succeeds.
+fn whole_seconds(duration: Duration) -> u64 {
+    duration
+        .as_millis()
+        .div_ceil(1000)
+        .try_into()
+        .unwrap_or(u64::MAX)
 }
 
-impl Layer {
-    /// Create a new rate limit layer to be applied globally.
-    pub fn global(app_state: &AppState) -> Result<Self, InitError> {
-        if let Some(rate_limit_config) = &app_state.config().global.rate_limit {
-            let store_config =
-                app_state.config().rate_limit_store.as_ref().ok_or(
-                    InitError::InvalidRateLimitConfig("store not configured"),
-                )?;
-            if let RateLimitStore::Redis(redis_config) = &store_config {
-                Ok(Self::new_redis_inner(
-                    rate_limit_config.limits.clone(),
-                    redis_config.host_url.expose().clone(),
-                ))
-            } else {
-                Ok(Self::new_in_memory_inner(
-                    app_state.0.global_rate_limit.clone(),
-                ))
-            }
-        } else {
-            Ok(Self {
-                inner: InnerLayer::None,
-            })
-        }
+fn too_many_requests(decision: &Decision) -> TooManyRequestsError {
+    TooManyRequestsError {
+        ratelimit_limit: u64::from(decision.limit),
+        ratelimit_remaining: 0,
+        retry_after: decision.retry_after.map_or(0, whole_seconds),
+        ratelimit_reset: whole_seconds(decision.reset),
     }
+}
 
-    /// Create a new rate limit layer to be applied to all requests to the
-    /// unified api.
-    pub fn unified_api(app_state: &AppState) -> Result<Self, InitError> {
-        if let Some(rate_limit_config) =
-            &app_state.config().unified_api.rate_limit
-        {
-            let store_config =
-                app_state.config().rate_limit_store.as_ref().ok_or(
-                    InitError::InvalidRateLimitConfig("store not configured"),
-                )?;
-            if let RateLimitStore::Redis(redis_config) = &store_config {
-                Ok(Self::new_redis_inner(
-                    rate_limit_config.limits.clone(),
-                    redis_config.host_url.expose().clone(),
-                ))
-            } else {
-                Ok(Self::new_in_memory_inner(
-                    app_state.0.global_rate_limit.clone(),
-                ))
-            }
-        } else {
-            Ok(Self {
-                inner: InnerLayer::None,
-            })
-        }
+/// The decision with the fewest requests remaining, or the first that
+/// limited the request.
+fn most_restrictive(decision: Decision, other: Decision) -> Decision {
+    if decision.retry_after.is_none()
+        && (other.retry_after.is_some() || other.remaining < decision.remaining)
+    {
+        other
+    } else {
+        decision
     }
+}
 
-    #[must_use]
-    fn new_redis_inner(rl: LimitsConfig, url: url::Url) -> Self {
-        if let Ok(layer) = RedisRateLimitLayer::new(Arc::new(rl), url, None) {
-            Self {
-                inner: InnerLayer::Redis(layer),
+/// Which of its limits a request is over.
+#[derive(Debug)]
+enum Limit {
+    Requests,
+    /// The model limit with the glob.
+    Model(String),
+    /// The token limit, with the request's estimated tokens.
+    Tokens {
+        estimated_tokens: u32,
+    },
+}
+
+/// A request over one of its limits.
+#[derive(Debug)]
+struct Limited {
+    decision: Decision,
+    limit: Limit,
+}
+
+impl Limited {
+    fn retry_after(&self) -> Duration {
+        self.decision.retry_after.unwrap_or_default()
+    }
+}
+
+impl From<Limited> for ApiError {
+    fn from(limited: Limited) -> Self {
+        let limits = too_many_requests(&limited.decision);
+        match limited.limit {
+            Limit::Requests => InvalidRequestError::TooManyRequests(limits),
+            Limit::Model(glob) => {
+                InvalidRequestError::ModelRateLimitExceeded { glob, limits }
             }
-        } else {
-            Self {
-                inner: InnerLayer::None,
+            Limit::Tokens { estimated_tokens } => {
+                InvalidRequestError::TokenBudgetExceeded {
+                    estimated_tokens,
+                    tokens_per_minute: limited.decision.limit,
+                    limits,
+                }
             }
         }
+        .into()
     }
+}
 
-    #[must_use]
-    fn new_in_memory_inner(rl: Option<Arc<RateLimiterConfig>>) -> Self {
-        if let Some(rl) = rl {
-            Self {
-                inner: InnerLayer::InMemory(GovernorLayer { config: rl }),
-            }
-        } else {
-            Self {
-                inner: InnerLayer::None,
-            }
+#[derive(Debug)]
+enum Outcome {
+    Allowed {
+        /// The decision with the fewest requests remaining, if any limits
+        /// were checked.
+        decision: Option<Decision>,
+        /// The estimated tokens debited, if the request has a token limit.
+        tokens: Option<(TokenDebit, Decision)>,
+    },
+    Limited(Limited),
+}
+
+impl Outcome {
+    fn allowed(decision: Option<Decision>) -> Self {
+        Self::Allowed {
+            decision,
+            tokens: None,
         }
     }
+}
 
-    /// For when we statically know that rate limiting is disabled.
-    #[must_use]
-    pub fn disabled() -> Self {
-        Self {
-            inner: InnerLayer::None,
+/// A cost taken from a limit while checking a request.
+#[derive(Debug)]
+struct Charge {
+    store: Arc<dyn GcraStore>,
+    key: String,
+    gcra: Gcra,
+    cost: u32,
+}
+
+/// The costs taken from a request's limits as they're checked, which are
+/// refunded if a later limit rejects it, so that only allowed requests count
+/// against their limits.
+#[derive(Debug, Default)]
+struct Charges(Vec<Charge>);
+
+impl Charges {
+    /// Checks a request costing `cost` cells against the limit for `key`,
+    /// recording the cost if it's allowed.
+    async fn check(
+        &mut self,
+        store: &Arc<dyn GcraStore>,
+        key: String,
+        gcra: Gcra,
+        cost: u32,
+        now: i64,
+    ) -> Result<Decision, InternalError> {
+        let decision = store.check(&key, gcra, cost, now).await?;
+        if decision.retry_after.is_none() {
+            self.0.push(Charge {
+                store: store.clone(),
+                key,
+                gcra,
+                cost,
+            });
         }
+        Ok(decision)
     }
 
-    pub async fn per_router(
+    async fn refund(self, now: i64) {
+        for charge in self.0 {
+            let cost = -i64::from(charge.cost);
+            if let Err(e) = charge
+                .store
+                .adjust(&charge.key, charge.gcra, cost, now)
+                .await
+            {
+                tracing::error!(error = %e, "failed to refund rate limit");
+            }
+        }
+    }
+}
+
+/// Enforces the limits of one scope, e.g. a router, whose keys are namespaced
+/// so that scopes sharing a store are limited independently.
+#[derive(Debug)]
+pub struct RateLimiter {
+    scope: String,
+    store: Arc<dyn GcraStore>,
+    per_api_key: Gcra,
+    per_org: Option<Gcra>,
+    per_model: ModelLimits,
+    tokens_per_api_key: Option<Gcra>,
+    queue: Option<Queue>,
+    on_unavailable: UnavailablePolicy,
+}
+
+impl RateLimiter {
+    fn new(
         app_state: &AppState,
-        router_id: RouterId,
-        router_config: &RouterConfig,
+        scope: String,
+        store: Option<&RateLimitStore>,
+        limits: &LimitsConfig,
+        queue: Option<&QueueConfig>,
     ) -> Result<Self, InitError> {
-        match &router_config.rate_limit {
-            None => Ok(Self {
-                inner: InnerLayer::None,
+        let store = store
+            .or(app_state.config().rate_limit_store.as_ref())
+            .ok_or(InitError::InvalidRateLimitConfig("store not configured"))?;
+        let (store, on_unavailable): (Arc<dyn GcraStore>, _) = match store {
+            RateLimitStore::Redis(redis_config) => (
+                Arc::new(RedisStore::new(redis_config)?),
+                redis_config.on_unavailable,
+            ),
+            // in memory limits are always available
+            RateLimitStore::InMemory => (
+                app_state.0.in_memory_rate_limits.clone(),
+                UnavailablePolicy::FailOpen,
+            ),
+        };
+        Ok(Self {
+            store,
+            per_api_key: Gcra::from(&limits.per_api_key),
+            per_org: limits.per_org.as_ref().map(Gcra::from),
+            tokens_per_api_key: limits.tokens_per_minute.map(Gcra::per_minute),
+            per_model: ModelLimits::new(&limits.per_model),
+            queue: queue.map(|queue| {
+                Queue::new(app_state, scope.clone(), queue.clone())
             }),
-            Some(RateLimitConfig { store, limits }) => {
-                let ratelimit_store = store
-                    .as_ref()
-                    .or_else(|| app_state.config().rate_limit_store.as_ref())
-                    .ok_or(InitError::InvalidRateLimitConfig(
-                        "store not configured",
-                    ))?;
-
-                if let RateLimitStore::Redis(redis_config) = ratelimit_store
-                    && let Ok(layer) = RedisRateLimitLayer::new(
-                        Arc::new(limits.clone()),
-                        redis_config.host_url.expose().clone(),
-                        Some(router_id.clone()),
-                    )
-                {
-                    return Ok(Self {
-                        inner: InnerLayer::Redis(layer),
-                    });
+            scope,
+            on_unavailable,
+        })
+    }
+
+    /// Applies the [`UnavailablePolicy`] when the store can't be reached,
+    /// returning `None` if requests should be let through without limits.
+    fn or_unavailable<T>(
+        &self,
+        result: Result<T, InternalError>,
+    ) -> Result<Option<T>, ApiError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                tracing::error!(
+                    error = %e,
+                    scope = %self.scope,
+                    "rate limit store unavailable"
+                );
+                match self.on_unavailable {
+                    UnavailablePolicy::FailOpen => Ok(None),
+                    UnavailablePolicy::FailClosed => {
+                        Err(ApiError::RateLimitUnavailable)
+                    }
                 }
-                let rl = Arc::new(crate::config::rate_limit::limiter_config(
-                    limits,
-                )?);
-                add_rate_limit_to_app_state(app_state, router_id, rl.clone())
-                    .await;
-
-                Ok(Self {
-                    inner: InnerLayer::InMemory(GovernorLayer {
-                        config: rl.clone(),
-                    }),
-                })
             }
         }
     }
+
+    /// Checks a request from `ctx` arriving at `now` against each limit,
+    /// returning the decision with the fewest requests remaining, if the
+    /// store is available.
+    async fn check(
+        &self,
+        ctx: &AuthContext,
+        now: i64,
+        charges: &mut Charges,
+    ) -> Result<Option<Decision>, ApiError> {
+        let key = per_api_key_rl_key(&self.scope, ctx);
+        let Some(mut decision) = self.or_unavailable(
+            charges
+                .check(&self.store, key, self.per_api_key, 1, now)
+                .await,
+        )?
+        else {
+            return Ok(None);
+        };
+        if decision.retry_after.is_none()
+            && let Some(per_org) = self.per_org
+        {
+            let key = per_org_rl_key(&self.scope, ctx);
+            let org_decision = self.or_unavailable(
+                charges.check(&self.store, key, per_org, 1, now).await,
+            )?;
+            if let Some(org_decision) = org_decision {
+                decision = most_restrictive(decision, org_decision);
+            }
+        }
+        Ok(Some(decision))
+    }
+
+    /// Checks a request from `ctx` against the most specific limit matching
+    /// the model in its body, returning the request with its body buffered,
+    /// and the decision if a limit matched and the store is available.
+    async fn check_model(
+        &self,
+        req: Request,
+        ctx: &AuthContext,
+        now: i64,
+        charges: &mut Charges,
+    ) -> Result<(Request, Outcome), ApiError> {
+        let (parts, body) = req.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+            .to_bytes();
+        let limit = request_model(&body)
+            .and_then(|model| self.per_model.for_model(&model));
+        let req = Request::from_parts(parts, Body::from(body));
+        let Some(limit) = limit else {
+            return Ok((req, Outcome::allowed(None)));
+        };
+        let key = per_model_rl_key(&self.scope, ctx, &limit.glob);
+        let Some(decision) = self.or_unavailable(
+            charges.check(&self.store, key, limit.gcra, 1, now).await,
+        )?
+        else {
+            return Ok((req, Outcome::allowed(None)));
+        };
+        if decision.retry_after.is_some() {
+            tracing::debug!(glob = %limit.glob, "model rate limit exceeded");
+            let limited = Limited {
+                decision,
+                limit: Limit::Model(limit.glob.clone()),
+            };
+            return Ok((req, Outcome::Limited(limited)));
+        }
+        Ok((req, Outcome::allowed(Some(decision))))
+    }
+
+    /// Debits the estimated input tokens of a request from the token limit
+    /// of `ctx`, returning the request with its body buffered, and the debit
+    /// if the store is available, or the limit if the request is over it.
+    async fn debit_tokens(
+        &self,
+        req: Request,
+        ctx: &AuthContext,
+        gcra: Gcra,
+        now: i64,
+        charges: &mut Charges,
+    ) -> Result<
+        (Request, Result<Option<(TokenDebit, Decision)>, Limited>),
+        ApiError,
+    > {
+        let (parts, body) = req.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+            .to_bytes();
+        let estimate =
+            u32::try_from(estimate_input_tokens(&body)).unwrap_or(u32::MAX);
+        // such requests would never be allowed, so retrying is pointless
+        if estimate > gcra.capacity() {
+            return Err(InvalidRequestError::PromptExceedsTokenBudget {
+                estimated_tokens: estimate,
+                tokens_per_minute: gcra.capacity(),
+            }
+            .into());
+        }
+        let req = Request::from_parts(parts, Body::from(body));
+        let key = tokens_per_api_key_rl_key(&self.scope, ctx);
+        let Some(decision) = self.or_unavailable(
+            charges
+                .check(&self.store, key.clone(), gcra, estimate, now)
+                .await,
+        )?
+        else {
+            return Ok((req, Ok(None)));
+        };
+        if decision.retry_after.is_some() {
+            tracing::debug!(estimate, "token rate limit exceeded");
+            let limited = Limited {
+                decision,
+                limit: Limit::Tokens {
+                    estimated_tokens: estimate,
+                },
+            };
+            return Ok((req, Err(limited)));
+        }
+        let debit = TokenDebit {
+            store: self.store.clone(),
+            key,
+            gcra,
+            estimate,
+        };
+        Ok((req, Ok(Some((debit, decision)))))
+    }
 }
 
-async fn add_rate_limit_to_app_state(
-    app_state: &AppState,
-    router_id: RouterId,
-    rl_config: Arc<RateLimiterConfig>,
-) {
-    let mut write_guard = app_state.0.router_rate_limits.write().await;
-    write_guard.insert(router_id, rl_config);
+const CONTROL_PLANE_SCOPE: &str = "CONTROL-PLANE";
+
+/// Enforces the rate limits the control plane sets for organizations and
+/// their users in sidecar deployments.
+///
+/// The limits are read from the sidecar's copy of the control plane config,
+/// which is refreshed whenever the control plane pushes an update, and kept
+/// in the in memory store, so they're enforced without a round trip to the
+/// control plane. They limit the gateway's callers, independently of the
+/// rate limit monitors reacting to providers limiting the gateway.
+#[derive(Debug)]
+struct ControlPlaneLimiter {
+    app_state: AppState,
 }
 
-impl<S> tower::layer::Layer<S> for Layer {
-    type Service = Service<S>;
+impl ControlPlaneLimiter {
+    /// Checks a request from `ctx` arriving at `now` against the limits of
+    /// its user and organization, returning the decision with the fewest
+    /// requests remaining, if the control plane set any limits.
+    async fn check(
+        &self,
+        ctx: &AuthContext,
+        now: i64,
+        charges: &mut Charges,
+    ) -> Result<Option<Decision>, ApiError> {
+        let (per_user, per_org) = {
+            let state = self.app_state.0.control_plane_state.read().await;
+            (
+                state.rate_limit(ctx.org_id, Some(ctx.user_id)),
+                state.rate_limit(ctx.org_id, None),
+            )
+        };
+        let limits = [
+            (per_user, per_user_rl_key(CONTROL_PLANE_SCOPE, ctx)),
+            (per_org, per_org_rl_key(CONTROL_PLANE_SCOPE, ctx)),
+        ];
+        let store: Arc<dyn GcraStore> =
+            self.app_state.0.in_memory_rate_limits.clone();
+        let mut decision: Option<Decision> = None;
+        for (gcra, key) in limits {
+            let Some(gcra) = gcra else {
+                continue;
+            };
+            if decision.is_some_and(|decision| decision.retry_after.is_some()) {
+                break;
+            }
+            let next = charges.check(&store, key, gcra, 1, now).await?;
+            decision = Some(
+                decision
+                    .map_or(next, |decision| most_restrictive(decision, next)),
+            );
+        }
+        Ok(decision)
+    }
+}
 
-    fn layer(&self, service: S) -> Self::Service {
-        match &self.inner {
-            InnerLayer::InMemory(inner) => Service::InMemory {
-                service: inner.layer(service),
-            },
-            InnerLayer::Redis(inner) => Service::Redis {
-                service: inner.layer(service),
-            },
-            InnerLayer::None => Service::Disabled { service },
+/// The estimated tokens debited for a request, which are reconciled with the
+/// tokens it actually used once its response completes.
+#[derive(Debug)]
+struct TokenDebit {
+    store: Arc<dyn GcraStore>,
+    key: String,
+    gcra: Gcra,
+    estimate: u32,
+}
+
+impl TokenDebit {
+    fn reconcile_on_completion(self, response: Response) -> Response {
+        // failed requests are assumed not to have used any tokens
+        if !response.status().is_success() {
+            tokio::spawn(self.reconcile(Some(Usage::default())));
+            return response;
+        }
+        let is_stream = is_event_stream(&response);
+        response.map(|body| {
+            Body::new(UsageBody::new(body, is_stream, move |usage| {
+                tokio::spawn(self.reconcile(usage.usage));
+            }))
+        })
+    }
+
+    /// Debits the tokens used beyond the estimate, or credits back those
+    /// that weren't, keeping the estimate if the response didn't report its
+    /// usage, e.g. streams that didn't ask for it.
+    async fn reconcile(self, usage: Option<Usage>) {
+        let Some(usage) = usage else {
+            return;
+        };
+        let cost = i64::try_from(usage.total_tokens())
+            .unwrap_or(i64::MAX)
+            .saturating_sub(i64::from(self.estimate));
+        if cost == 0 {
+            return;
+        }
+        tracing::trace!(estimate = self.estimate, cost, "reconciling tokens");
+        let now = Utc::now().timestamp_micros();
+        if let Err(e) = self.store.adjust(&self.key, self.gcra, cost, now).await
+        {
+            tracing::error!(error = %e, "failed to reconcile token usage");
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Service<S> {
-    Disabled { service: S },
-    InMemory { service: GovernorService<S> },
-    Redis { service: RedisRateLimitService<S> },
+/// Checks a request against the control plane's limits, then the limiter's,
+/// returning the request, whose body is buffered if its model or tokens were
+/// needed.
+///
+/// A request only counts against its limits if it passes all of them, so
+/// rejected requests, including those retried from the queue, don't use up
+/// the limits checked before the one they're over.
+async fn check_limits(
+    limiters: (Option<&RateLimiter>, Option<&ControlPlaneLimiter>),
+    req: Request,
+    ctx: &AuthContext,
+    now: i64,
+) -> Result<(Request, Outcome), ApiError> {
+    let mut charges = Charges::default();
+    let result = check_each_limit(limiters, req, ctx, now, &mut charges).await;
+    if !matches!(result, Ok((_, Outcome::Allowed { .. }))) {
+        charges.refund(now).await;
+    }
+    result
 }
 
-pin_project_lite::pin_project! {
-    #[derive(Debug)]
-    #[project = EnumProj]
-    pub enum ResponseFuture<InMemoryFuture, RedisFuture, DisabledFuture> {
-        InMemory { #[pin] future: InMemoryFuture },
-        Redis { #[pin] future: RedisFuture },
-        Disabled { #[pin] future: DisabledFuture },
+async fn check_each_limit(
+    (limiter, control_plane): (
+        Option<&RateLimiter>,
+        Option<&ControlPlaneLimiter>,
+    ),
+    mut req: Request,
+    ctx: &AuthContext,
+    now: i64,
+    charges: &mut Charges,
+) -> Result<(Request, Outcome), ApiError> {
+    let mut decision = match control_plane {
+        Some(control_plane) => control_plane.check(ctx, now, charges).await?,
+        None => None,
+    };
+    if let Some(limiter) = limiter
+        && decision.is_none_or(|decision| decision.retry_after.is_none())
+        && let Some(limiter_decision) = limiter.check(ctx, now, charges).await?
+    {
+        decision = Some(decision.map_or(limiter_decision, |decision| {
+            most_restrictive(decision, limiter_decision)
+        }));
+    }
+    let Some(mut decision) = decision else {
+        return Ok((req, Outcome::allowed(None)));
+    };
+    if decision.retry_after.is_some() {
+        let limited = Limited {
+            decision,
+            limit: Limit::Requests,
+        };
+        return Ok((req, Outcome::Limited(limited)));
+    }
+    let Some(limiter) = limiter else {
+        return Ok((req, Outcome::allowed(Some(decision))));
+    };
+    // both the limits above and the model's must pass
+    if !limiter.per_model.is_empty() {
+        let outcome;
+        (req, outcome) = limiter.check_model(req, ctx, now, charges).await?;
+        match outcome {
+            Outcome::Allowed {
+                decision: Some(model),
+                ..
+            } => decision = most_restrictive(decision, model),
+            Outcome::Allowed { decision: None, .. } => {}
+            limited @ Outcome::Limited(_) => return Ok((req, limited)),
+        }
     }
+    let Some(gcra) = limiter.tokens_per_api_key else {
+        return Ok((req, Outcome::allowed(Some(decision))));
+    };
+    let (req, tokens) =
+        limiter.debit_tokens(req, ctx, gcra, now, charges).await?;
+    let outcome = match tokens {
+        Ok(tokens) => Outcome::Allowed {
+            decision: Some(decision),
+            tokens,
+        },
+        Err(limited) => Outcome::Limited(limited),
+    };
+    Ok((req, outcome))
 }
 
-// add a second to the retry after header to prevent rounding errors
-fn increment_retry_after_header<ResponseBody>(
-    res: &mut http::Response<ResponseBody>,
-) {
-    if let Some(retry_after) = res.headers().get("retry-after") {
-        if let Some(retry_after_value) = retry_after
-            .to_str()
-            .ok()
-            .and_then(|s| s.parse::<u64>().ok())
-        {
-            let new_retry_after = retry_after_value + 1;
-            res.headers_mut().insert(
-                "retry-after",
-                new_retry_after.to_string().parse().unwrap(),
-            );
-            res.headers_mut().insert(
-                "x-ratelimit-after",
-                new_retry_after.to_string().parse().unwrap(),
-            );
+#[derive(Debug, Clone)]
+pub struct Layer {
+    limiter: Option<Arc<RateLimiter>>,
+    control_plane: Option<Arc<ControlPlaneLimiter>>,
+}
+
+impl Layer {
+    /// Create a new rate limit layer to be applied globally, which in sidecar
+    /// deployments also enforces the limits set by the control plane.
+    pub fn global(app_state: &AppState) -> Result<Self, InitError> {
+        let mut layer = Self::new(
+            app_state,
+            "GLOBAL",
+            app_state.config().global.rate_limit.as_ref(),
+        )?;
+        if app_state.config().deployment_target == DeploymentTarget::Sidecar {
+            layer.control_plane = Some(Arc::new(ControlPlaneLimiter {
+                app_state: app_state.clone(),
+            }));
+        }
+        Ok(layer)
+    }
+
+    /// Create a new rate limit layer to be applied to all requests to the
+    /// unified api.
+    pub fn unified_api(app_state: &AppState) -> Result<Self, InitError> {
+        Self::new(
+            app_state,
+            "UNIFIED-API",
+            app_state.config().unified_api.rate_limit.as_ref(),
+        )
+    }
+
+    pub fn per_router(
+        app_state: &AppState,
+        router_id: &RouterId,
+        router_config: &RouterConfig,
+    ) -> Result<Self, InitError> {
+        Self::new(
+            app_state,
+            &router_id.to_string(),
+            router_config.rate_limit.as_ref(),
+        )
+    }
+
+    fn new(
+        app_state: &AppState,
+        scope: &str,
+        config: Option<&RateLimitConfig>,
+    ) -> Result<Self, InitError> {
+        let Some(RateLimitConfig {
+            store,
+            limits,
+            queue,
+        }) = config
+        else {
+            return Ok(Self::disabled());
+        };
+        let limiter = RateLimiter::new(
+            app_state,
+            scope.to_string(),
+            store.as_ref(),
+            limits,
+            queue.as_ref(),
+        )?;
+        Ok(Self {
+            limiter: Some(Arc::new(limiter)),
+            control_plane: None,
+        })
+    }
+
+    /// For when we statically know that rate limiting is disabled.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            limiter: None,
+            control_plane: None,
         }
     }
 }
 
-impl<InMemoryFuture, RedisFuture, DisabledFuture, ResponseBody, Error> Future
-    for ResponseFuture<InMemoryFuture, RedisFuture, DisabledFuture>
-where
-    InMemoryFuture: Future<Output = Result<Response<ResponseBody>, Error>>,
-    RedisFuture: Future<Output = Result<Response<ResponseBody>, Error>>,
-    DisabledFuture: Future<Output = Result<Response<ResponseBody>, Error>>,
-{
-    type Output = Result<Response<ResponseBody>, Error>;
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.project() {
-            EnumProj::InMemory { future } => {
-                let result = std::task::ready!(future.poll(cx));
-                if let Ok(mut res) = result {
-                    increment_retry_after_header(&mut res);
-                    Poll::Ready(Ok(res))
-                } else {
-                    Poll::Ready(result)
-                }
-            }
-            EnumProj::Redis { future } => future.poll(cx),
-            EnumProj::Disabled { future } => future.poll(cx),
+impl<S> tower::layer::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            limiter: self.limiter.clone(),
+            control_plane: self.control_plane.clone(),
         }
     }
 }
 
-impl<S, Request, ResponseBody> tower::Service<Request> for Service<S>
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    limiter: Option<Arc<RateLimiter>>,
+    control_plane: Option<Arc<ControlPlaneLimiter>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
 where
-    S: tower::Service<Request, Response = Response<ResponseBody>>,
-    GovernorService<S>: tower::Service<
-            Request,
-            Response = Response<ResponseBody>,
-            Error = S::Error,
-        >,
-    RedisRateLimitService<S>: tower::Service<
-            Request,
-            Response = Response<ResponseBody>,
-            Error = S::Error,
-        >,
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
 {
-    type Response = Response<ResponseBody>;
-    type Error = S::Error;
-    type Future = ResponseFuture<
-        <GovernorService<S> as tower::Service<Request>>::Future,
-        <RedisRateLimitService<S> as tower::Service<Request>>::Future,
-        S::Future,
-    >;
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
+    #[inline]
     fn poll_ready(
         &mut self,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), Self::Error>> {
-        match self {
-            Service::InMemory { service } => match service.poll_ready(cx) {
-                Poll::Ready(Ok(())) => {
-                    tracing::trace!("in memory rate limit ready");
-                    Poll::Ready(Ok(()))
-                }
-                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
-                Poll::Pending => Poll::Pending,
-            },
-            Service::Redis { service } => service.poll_ready(cx),
-            Service::Disabled { service } => service.poll_ready(cx),
-        }
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
     }
 
     #[tracing::instrument(name = "opt_rate_limit", skip_all)]
-    fn call(&mut self, request: Request) -> Self::Future {
-        match self {
-            Service::InMemory { service } => {
-                tracing::trace!(kind = "in_memory", "rate limit middleware");
-                ResponseFuture::InMemory {
-                    future: service.call(request),
-                }
-            }
-            Service::Redis { service } => {
-                tracing::trace!(kind = "redis", "rate limit middleware");
-                ResponseFuture::Redis {
-                    future: service.call(request),
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        if self.limiter.is_none() && self.control_plane.is_none() {
+            return Box::pin(inner.call(req));
+        }
+        let limiter = self.limiter.clone();
+        let control_plane = self.control_plane.clone();
+        // extracted up front since requests aren't `Sync`
+        let ctx = get_auth_context(&req).cloned();
+        let mut now = request_time(&req);
+        Box::pin(async move {
+            let ctx = ctx?;
+            let limiters = (limiter.as_deref(), control_plane.as_deref());
+            let (mut req, outcome) =
+                check_limits(limiters, req, &ctx, now).await?;
+            let (decision, tokens) = match outcome {
+                Outcome::Allowed { decision, tokens } => (decision, tokens),
+                Outcome::Limited(mut limited) => {
+                    let ticket = limiter
+                        .as_ref()
+                        .and_then(|limiter| limiter.queue.as_ref())
+                        .and_then(|queue| queue.join(&ctx));
+                    let Some(mut ticket) = ticket else {
+                        tracing::debug!("rate limit exceeded");
+                        return Err(limited.into());
+                    };
+                    loop {
+                        if !ticket.wait(limited.retry_after()).await {
+                            tracing::debug!("rate limit exceeded while queued");
+                            return Err(limited.into());
+                        }
+                        now = Utc::now().timestamp_micros();
+                        let outcome;
+                        (req, outcome) =
+                            check_limits(limiters, req, &ctx, now).await?;
+                        match outcome {
+                            Outcome::Allowed { decision, tokens } => {
+                                break (decision, tokens);
+                            }
+                            Outcome::Limited(next) => limited = next,
+                        }
+                    }
                 }
+            };
+            let Some(decision) = decision else {
+                return inner.call(req).await;
+            };
+            let mut response = inner.call(req).await?;
+            let headers = response.headers_mut();
+            headers
+                .insert("x-ratelimit-limit", HeaderValue::from(decision.limit));
+            headers.insert(
+                "x-ratelimit-remaining",
+                HeaderValue::from(decision.remaining),
+            );
+            headers.insert(
+                "x-ratelimit-reset",
+                HeaderValue::from(whole_seconds(decision.reset)),
+            );
+            if let Some((debit, decision)) = tokens {
+                headers.insert(
+                    "x-ratelimit-limit-tokens",
+                    HeaderValue::from(decision.limit),
+                );
+                headers.insert(
+                    "x-ratelimit-remaining-tokens",
+                    HeaderValue::from(decision.remaining),
+                );
+                headers.insert(
+                    "x-ratelimit-reset-tokens",
+                    HeaderValue::from(whole_seconds(decision.reset)),
+                );
+                response = debit.reconcile_on_completion(response);
             }
-            Service::Disabled { service } => ResponseFuture::Disabled {
-                future: service.call(request),
-            },
-        }
+            Ok(response)
+        })
     }
 }
 
@@ -333,6 +806,8 @@ mod tests {
     use std::{num::NonZeroU32, time::Duration};
 
     use compact_str::CompactString;
+    use indexmap::IndexMap;
+    use uuid::Uuid;
 
     use super::*;
     use crate::{
@@ -340,12 +815,20 @@ mod tests {
         config::{
             Config,
             rate_limit::{
-                GcraConfig, LimitsConfig, RateLimitConfig, RateLimitStore,
+                GcraConfig, LimitsConfig, ModelLimitConfig, RateLimitConfig,
+                RateLimitStore,
             },
             router::RouterConfig,
         },
+        middleware::rate_limit::store::InMemoryStore,
         tests::TestDefault,
-        types::router::RouterId,
+        types::{
+            org::OrgId,
+            provider::{ProviderKeyMap, ProviderKeys},
+            router::RouterId,
+            secret::Secret,
+            user::UserId,
+        },
     };
 
     async fn create_test_app_state(
@@ -353,7 +836,9 @@ mod tests {
     ) -> AppState {
         let mut config = Config::test_default();
         config.global.rate_limit = Some(rate_limit_config);
-        let app = crate::app::App::new(config)
+        let provider_keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::test_default());
+        let app = crate::app::App::with_provider_keys(config, provider_keys)
             .await
             .expect("failed to create app");
         app.state
@@ -365,6 +850,9 @@ mod tests {
                 capacity: NonZeroU32::new(10).unwrap(),
                 refill_frequency: Duration::from_secs(1),
             },
+            per_org: None,
+            tokens_per_minute: None,
+            per_model: IndexMap::new(),
         }
     }
 
@@ -382,18 +870,18 @@ mod tests {
         let app_state = create_test_app_state(RateLimitConfig {
             store: None,
             limits: create_test_limits(),
+            queue: None,
         })
         .await;
         let router_config = create_router_config(None);
 
         let result = Layer::per_router(
             &app_state,
-            RouterId::Named(CompactString::new("my-router")),
+            &RouterId::Named(CompactString::new("my-router")),
             &router_config,
-        )
-        .await;
+        );
         assert!(result.is_ok());
-        assert!(matches!(result.unwrap().inner, InnerLayer::None));
+        assert!(result.unwrap().limiter.is_none());
     }
 
     #[tokio::test]
@@ -401,21 +889,22 @@ mod tests {
         let app_state = create_test_app_state(RateLimitConfig {
             store: Some(RateLimitStore::InMemory),
             limits: create_test_limits(),
+            queue: None,
         })
         .await;
         let router_config = create_router_config(Some(RateLimitConfig {
             store: Some(RateLimitStore::InMemory),
             limits: create_test_limits(),
+            queue: None,
         }));
 
         let result = Layer::per_router(
             &app_state,
-            RouterId::Named(CompactString::new("my-router")),
+            &RouterId::Named(CompactString::new("my-router")),
             &router_config,
-        )
-        .await;
+        );
         assert!(result.is_ok());
-        assert!(matches!(result.unwrap().inner, InnerLayer::InMemory(_)));
+        assert!(result.unwrap().limiter.is_some());
     }
 
     #[tokio::test]
@@ -423,20 +912,215 @@ mod tests {
         let app_state = create_test_app_state(RateLimitConfig {
             store: None,
             limits: create_test_limits(),
+            queue: None,
         })
         .await;
         let router_config = create_router_config(Some(RateLimitConfig {
             store: Some(RateLimitStore::InMemory),
             limits: create_test_limits(),
+            queue: None,
         }));
 
         let result = Layer::per_router(
             &app_state,
-            RouterId::Named(CompactString::new("my-router")),
+            &RouterId::Named(CompactString::new("my-router")),
             &router_config,
-        )
-        .await;
+        );
         assert!(result.is_ok());
-        assert!(matches!(result.unwrap().inner, InnerLayer::InMemory(_)));
+        assert!(result.unwrap().limiter.is_some());
+    }
+
+    const CHAT_BODY: &str = "{\"model\":\"openai/gpt-4o\",\"messages\":\
+                             [{\"role\":\"user\",\"content\":\"Hi there!\"}]}";
+
+    fn chat_request() -> Request {
+        Request::new(Body::from(CHAT_BODY))
+    }
+
+    async fn in_memory_limiter(limits: &LimitsConfig) -> RateLimiter {
+        let app_state = create_test_app_state(RateLimitConfig {
+            store: Some(RateLimitStore::InMemory),
+            limits: create_test_limits(),
+            queue: None,
+        })
+        .await;
+        RateLimiter::new(
+            &app_state,
+            "GLOBAL".to_string(),
+            Some(&RateLimitStore::InMemory),
+            limits,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn test_ctx() -> AuthContext {
+        AuthContext {
+            api_key: Secret::from("sk-helicone-test-key".to_string()),
+            user_id: UserId::new(Uuid::new_v4()),
+            org_id: OrgId::new(Uuid::new_v4()),
+        }
+    }
+
+    /// The requests `ctx` has remaining under its per api key limit.
+    async fn remaining_per_api_key(
+        limiter: &RateLimiter,
+        ctx: &AuthContext,
+        now: i64,
+    ) -> u32 {
+        let key = per_api_key_rl_key("GLOBAL", ctx);
+        let decision = limiter
+            .store
+            .check(&key, limiter.per_api_key, 0, now)
+            .await
+            .unwrap();
+        decision.remaining
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_dont_use_up_earlier_limits() {
+        let mut limits = create_test_limits();
+        limits.per_model.insert(
+            "openai/*".to_string(),
+            ModelLimitConfig {
+                rpm: NonZeroU32::new(1).unwrap(),
+            },
+        );
+        let limiter = in_memory_limiter(&limits).await;
+        let ctx = test_ctx();
+        let limiters = (Some(&limiter), None);
+        let now = Utc::now().timestamp_micros();
+
+        let (_, outcome) = check_limits(limiters, chat_request(), &ctx, now)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, Outcome::Allowed { .. }));
+        // over the model's limit, after passing the api key's
+        let (_, outcome) = check_limits(limiters, chat_request(), &ctx, now)
+            .await
+            .unwrap();
+        assert!(matches!(
+            outcome,
+            Outcome::Limited(Limited {
+                limit: Limit::Model(_),
+                ..
+            })
+        ));
+        assert_eq!(remaining_per_api_key(&limiter, &ctx, now).await, 9);
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_token_limit_can_be_queued() {
+        let estimate =
+            u32::try_from(estimate_input_tokens(CHAT_BODY.as_bytes())).unwrap();
+        let mut limits = create_test_limits();
+        // room for one request's tokens, but not two
+        limits.tokens_per_minute = NonZeroU32::new(estimate * 3 / 2);
+        let limiter = in_memory_limiter(&limits).await;
+        let ctx = test_ctx();
+        let limiters = (Some(&limiter), None);
+        let now = Utc::now().timestamp_micros();
+
+        let (_, outcome) = check_limits(limiters, chat_request(), &ctx, now)
+            .await
+            .unwrap();
+        assert!(matches!(
+            outcome,
+            Outcome::Allowed {
+                tokens: Some(_),
+                ..
+            }
+        ));
+        // rejections which would be retried from the queue
+        for _ in 0..3 {
+            let (_, outcome) =
+                check_limits(limiters, chat_request(), &ctx, now)
+                    .await
+                    .unwrap();
+            assert!(matches!(
+                outcome,
+                Outcome::Limited(Limited {
+                    limit: Limit::Tokens { .. },
+                    ..
+                })
+            ));
+        }
+        assert_eq!(remaining_per_api_key(&limiter, &ctx, now).await, 9);
+    }
+
+    fn stream_response(frames: Vec<String>) -> Response {
+        let frames = frames
+            .into_iter()
+            .map(|frame| Ok::<_, std::convert::Infallible>(frame.into_bytes()));
+        let mut response =
+            Response::new(Body::from_stream(futures::stream::iter(frames)));
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        response
+    }
+
+    /// Debits an estimate of 100 tokens from a limit of 1000 per minute,
+    /// reconciles it with the response, and returns the tokens remaining.
+    async fn remaining_after_reconciling(response: Response) -> u32 {
+        let store = Arc::new(InMemoryStore::default());
+        let gcra = Gcra::per_minute(NonZeroU32::new(1000).unwrap());
+        let now = Utc::now().timestamp_micros();
+        let decision = store.check("key", gcra, 100, now).await.unwrap();
+        assert_eq!(decision.remaining, 900);
+        let debit = TokenDebit {
+            store: store.clone(),
+            key: "key".to_string(),
+            gcra,
+            estimate: 100,
+        };
+        let response = debit.reconcile_on_completion(response);
+        response.into_body().collect().await.unwrap();
+        // tokens are reconciled in the background once the body completes
+        tokio::task::yield_now().await;
+        store.check("key", gcra, 0, now).await.unwrap().remaining
+    }
+
+    #[tokio::test]
+    async fn tokens_are_reconciled_with_usage_in_the_last_chunk() {
+        let response = stream_response(vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n"
+                .to_string(),
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":100,\"\
+             completion_tokens\":150}}\n\n"
+                .to_string(),
+            "data: [DONE]\n\n".to_string(),
+        ]);
+        // 150 more tokens were used than estimated
+        assert_eq!(remaining_after_reconciling(response).await, 750);
+    }
+
+    #[tokio::test]
+    async fn unused_tokens_are_credited_back() {
+        let response = stream_response(vec![
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":60,\"\
+             completion_tokens\":10}}\n\n"
+                .to_string(),
+            "data: [DONE]\n\n".to_string(),
+        ]);
+        assert_eq!(remaining_after_reconciling(response).await, 930);
+    }
+
+    #[tokio::test]
+    async fn estimate_is_kept_without_usage() {
+        let response = stream_response(vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n"
+                .to_string(),
+            "data: [DONE]\n\n".to_string(),
+        ]);
+        assert_eq!(remaining_after_reconciling(response).await, 900);
+    }
+
+    #[tokio::test]
+    async fn failed_requests_are_credited_back() {
+        let mut response = stream_response(Vec::new());
+        *response.status_mut() = http::StatusCode::SERVICE_UNAVAILABLE;
+        assert_eq!(remaining_after_reconciling(response).await, 1000);
     }
 }
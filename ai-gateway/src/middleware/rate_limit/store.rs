@@ -0,0 +1,413 @@
+//! Rate limits use the generic cell rate algorithm (GCRA), which only needs
+//! to store one timestamp per key: the theoretical arrival time (TAT) of the
+//! next request if requests were evenly spaced. A request is allowed if it
+//! arrives no more than the burst window before that time.
+//!
+//! Where TATs are kept is abstracted by [`GcraStore`], so limits can be
+//! enforced per instance with [`InMemoryStore`] or across instances with
+//! [`RedisStore`].
+use std::{
+    num::NonZeroU32,
+    sync::{LazyLock, Mutex, PoisonError},
+    time::Duration,
+};
+
+use r2d2::Pool;
+use redis::{Client, Script};
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{
+    config::{
+        rate_limit::{GcraConfig, default_refill_frequency},
+        redis::RedisConfig,
+    },
+    error::{init::InitError, internal::InternalError},
+};
+
+/// A GCRA limit, with times in microseconds so that limits refilling
+/// thousands of cells a second, e.g. tokens per minute, stay precise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gcra {
+    /// The time it takes to refill one cell, e.g. a request or a token.
+    interval: i64,
+    capacity: u32,
+}
+
+impl From<&GcraConfig> for Gcra {
+    fn from(config: &GcraConfig) -> Self {
+        let interval = config
+            .refill_frequency
+            .checked_div(config.capacity.get())
+            .filter(|interval| !interval.is_zero())
+            .unwrap_or_else(|| {
+                tracing::warn!(
+                    "fill_frequency is too small for capacity, using default \
+                     fill frequency"
+                );
+                default_refill_frequency()
+            });
+        Self {
+            interval: i64::try_from(interval.as_micros())
+                .unwrap_or(i64::MAX)
+                .max(1),
+            capacity: config.capacity.get(),
+        }
+    }
+}
+
+impl Gcra {
+    /// A limit of `capacity` cells per minute, with bursts of up to the whole
+    /// minute's cells.
+    #[must_use]
+    pub fn per_minute(capacity: NonZeroU32) -> Self {
+        Self {
+            interval: (MICROS_PER_MINUTE / i64::from(capacity.get())).max(1),
+            capacity: capacity.get(),
+        }
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Checks a request costing `cost` cells arriving at `now` against the
+    /// stored `tat`, returning the decision and the TAT to store if the
+    /// request is allowed.
+    #[must_use]
+    pub fn check(
+        &self,
+        tat: Option<i64>,
+        now: i64,
+        cost: u32,
+    ) -> (Decision, Option<i64>) {
+        let window = self.interval.saturating_mul(i64::from(self.capacity));
+        let tat = tat.unwrap_or(now).max(now);
+        let new_tat =
+            tat.saturating_add(self.interval.saturating_mul(i64::from(cost)));
+        let allowed_at = new_tat - window;
+        if allowed_at <= now {
+            let used = (new_tat - now)
+                .unsigned_abs()
+                .div_ceil(self.interval.unsigned_abs());
+            let remaining = u64::from(self.capacity).saturating_sub(used);
+            let decision = Decision {
+                limit: self.capacity,
+                remaining: u32::try_from(remaining).unwrap_or(0),
+                retry_after: None,
+                reset: micros(new_tat - now),
+            };
+            (decision, Some(new_tat))
+        } else {
+            let decision = Decision {
+                limit: self.capacity,
+                remaining: 0,
+                retry_after: Some(micros(allowed_at - now)),
+                reset: micros(tat - now),
+            };
+            (decision, None)
+        }
+    }
+
+    /// Debits `cost` more cells from the stored `tat` at `now`, or credits
+    /// them back if negative, returning the TAT to store, if any.
+    ///
+    /// Unlike [`Gcra::check`], debits are never refused, so a limit can be
+    /// overdrawn, in which case later requests wait until it's paid back.
+    #[must_use]
+    pub fn adjust(&self, tat: Option<i64>, now: i64, cost: i64) -> Option<i64> {
+        let tat = tat.unwrap_or(now).max(now);
+        let new_tat = tat.saturating_add(self.interval.saturating_mul(cost));
+        // a TAT in the past is equivalent to a full limit
+        (new_tat > now).then_some(new_tat)
+    }
+}
+
+const MICROS_PER_MINUTE: i64 = 60_000_000;
+
+fn micros(micros: i64) -> Duration {
+    Duration::from_micros(u64::try_from(micros).unwrap_or(0))
+}
+
+/// The outcome of checking a request against a [`Gcra`] limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub limit: u32,
+    /// Cells, e.g. requests, left before the limit is exceeded.
+    pub remaining: u32,
+    /// If the request was limited, when the next request will be allowed.
+    pub retry_after: Option<Duration>,
+    /// When the limit will be fully refilled.
+    pub reset: Duration,
+}
+
+/// Stores the TATs of rate limit keys.
+#[async_trait::async_trait]
+pub trait GcraStore: std::fmt::Debug + Send + Sync {
+    /// Checks a request costing `cost` cells arriving at `now` against the
+    /// limit for `key`, recording it if it's allowed.
+    async fn check(
+        &self,
+        key: &str,
+        gcra: Gcra,
+        cost: u32,
+        now: i64,
+    ) -> Result<Decision, InternalError>;
+
+    /// Debits `cost` more cells from the limit for `key`, or credits them
+    /// back if negative, e.g. once the actual cost of a request is known.
+    async fn adjust(
+        &self,
+        key: &str,
+        gcra: Gcra,
+        cost: i64,
+        now: i64,
+    ) -> Result<(), InternalError>;
+}
+
+/// Rate limits for a single instance, shared by all of its limiters with keys
+/// namespaced by scope.
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    tats: Mutex<HashMap<String, i64>>,
+}
+
+impl InMemoryStore {
+    /// Removes keys whose limits have fully refilled, since a missing key is
+    /// equivalent.
+    pub fn retain_recent(&self, now: i64) {
+        let mut tats = self.tats.lock().unwrap_or_else(PoisonError::into_inner);
+        tats.retain(|_, tat| *tat > now);
+    }
+}
+
+#[async_trait::async_trait]
+impl GcraStore for InMemoryStore {
+    async fn check(
+        &self,
+        key: &str,
+        gcra: Gcra,
+        cost: u32,
+        now: i64,
+    ) -> Result<Decision, InternalError> {
+        let mut tats = self.tats.lock().unwrap_or_else(PoisonError::into_inner);
+        let (decision, new_tat) = gcra.check(tats.get(key).copied(), now, cost);
+        if let Some(new_tat) = new_tat {
+            tats.insert(key.to_string(), new_tat);
+        }
+        Ok(decision)
+    }
+
+    async fn adjust(
+        &self,
+        key: &str,
+        gcra: Gcra,
+        cost: i64,
+        now: i64,
+    ) -> Result<(), InternalError> {
+        let mut tats = self.tats.lock().unwrap_or_else(PoisonError::into_inner);
+        match gcra.adjust(tats.get(key).copied(), now, cost) {
+            Some(new_tat) => tats.insert(key.to_string(), new_tat),
+            None => tats.remove(key),
+        };
+        Ok(())
+    }
+}
+
+/// Checks a request against a GCRA limit, following [`Gcra::check`], and
+/// returns the TAT it read so that the decision can be made the same way.
+///
+/// `KEYS[1]` is the limit's key, and `ARGV` is `now`, `interval`, `capacity`
+/// and `cost`. TATs are formatted with `%d`, since Lua numbers are floats.
+const CHECK_SCRIPT: &str = r"
+local now = tonumber(ARGV[1])
+local interval = tonumber(ARGV[2])
+local capacity = tonumber(ARGV[3])
+local cost = tonumber(ARGV[4])
+local tat = tonumber(redis.call('GET', KEYS[1]))
+local new_tat = math.max(tat or now, now) + interval * cost
+if new_tat - interval * capacity <= now then
+    local ttl = math.ceil((new_tat - now) / 1000) + 1000
+    redis.call('SET', KEYS[1], string.format('%d', new_tat), 'PX', ttl)
+end
+return tat
+";
+
+/// Debits or credits a GCRA limit, following [`Gcra::adjust`].
+///
+/// `KEYS[1]` is the limit's key, and `ARGV` is `now`, `interval` and `cost`.
+const ADJUST_SCRIPT: &str = r"
+local now = tonumber(ARGV[1])
+local interval = tonumber(ARGV[2])
+local cost = tonumber(ARGV[3])
+local tat = tonumber(redis.call('GET', KEYS[1]))
+local new_tat = math.max(tat or now, now) + interval * cost
+if new_tat > now then
+    local ttl = math.ceil((new_tat - now) / 1000) + 1000
+    redis.call('SET', KEYS[1], string.format('%d', new_tat), 'PX', ttl)
+else
+    redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+static CHECK: LazyLock<Script> = LazyLock::new(|| Script::new(CHECK_SCRIPT));
+static ADJUST: LazyLock<Script> = LazyLock::new(|| Script::new(ADJUST_SCRIPT));
+
+/// Rate limits shared by all instances using the same Redis, e.g. replicas
+/// of a deployment, which would otherwise each allow the whole limit.
+///
+/// Decisions are made by Lua scripts, so that concurrent requests from
+/// different instances can't both be allowed the last cell of a limit.
+#[derive(Debug, Clone)]
+pub struct RedisStore {
+    pool: Pool<Client>,
+}
+
+impl RedisStore {
+    /// Connections are made lazily, so that Redis being unreachable is
+    /// handled per request by the store's
+    /// [`UnavailablePolicy`](crate::config::redis::UnavailablePolicy).
+    pub fn new(config: &RedisConfig) -> Result<Self, InitError> {
+        let client = Client::open(config.host_url.expose().clone())?;
+        let pool = Pool::builder()
+            .connection_timeout(config.connection_timeout)
+            .build_unchecked(client);
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl GcraStore for RedisStore {
+    async fn check(
+        &self,
+        key: &str,
+        gcra: Gcra,
+        cost: u32,
+        now: i64,
+    ) -> Result<Decision, InternalError> {
+        let mut conn = self.pool.get().map_err(InternalError::PoolError)?;
+        let tat: Option<i64> = CHECK
+            .key(key)
+            .arg(now)
+            .arg(gcra.interval)
+            .arg(gcra.capacity)
+            .arg(cost)
+            .invoke(&mut *conn)
+            .map_err(InternalError::RedisError)?;
+        let (decision, _) = gcra.check(tat, now, cost);
+        Ok(decision)
+    }
+
+    async fn adjust(
+        &self,
+        key: &str,
+        gcra: Gcra,
+        cost: i64,
+        now: i64,
+    ) -> Result<(), InternalError> {
+        let mut conn = self.pool.get().map_err(InternalError::PoolError)?;
+        let _: i64 = ADJUST
+            .key(key)
+            .arg(now)
+            .arg(gcra.interval)
+            .arg(cost)
+            .invoke(&mut *conn)
+            .map_err(InternalError::RedisError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000;
+
+    fn gcra() -> Gcra {
+        Gcra::from(&GcraConfig {
+            refill_frequency: Duration::from_secs(1),
+            capacity: NonZeroU32::new(4).unwrap(),
+        })
+    }
+
+    #[test]
+    fn bursts_up_to_capacity_are_allowed() {
+        let gcra = gcra();
+        let mut tat = None;
+        for remaining in (0..4).rev() {
+            let (decision, new_tat) = gcra.check(tat, 0, 1);
+            assert_eq!(decision.remaining, remaining);
+            assert_eq!(decision.retry_after, None);
+            assert!(new_tat.is_some());
+            tat = new_tat;
+        }
+        assert_eq!(tat, Some(SECOND));
+
+        let (decision, new_tat) = gcra.check(tat, 0, 1);
+        assert_eq!(new_tat, None);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after, Some(Duration::from_millis(250)));
+        assert_eq!(decision.reset, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn limits_refill_over_time() {
+        let gcra = gcra();
+        // exhausted at time 0
+        let tat = Some(SECOND);
+        let (decision, new_tat) = gcra.check(tat, SECOND / 4, 1);
+        assert_eq!(decision.retry_after, None);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(new_tat, Some(SECOND + SECOND / 4));
+
+        let (decision, new_tat) = gcra.check(Some(SECOND), 2 * SECOND, 1);
+        assert_eq!(decision.remaining, 3);
+        assert_eq!(decision.reset, Duration::from_millis(250));
+        assert_eq!(new_tat, Some(2 * SECOND + SECOND / 4));
+    }
+
+    #[test]
+    fn requests_can_cost_many_cells() {
+        let gcra = Gcra::per_minute(NonZeroU32::new(1000).unwrap());
+        let (decision, tat) = gcra.check(None, 0, 600);
+        assert_eq!(decision.remaining, 400);
+        assert_eq!(decision.reset, Duration::from_secs(36));
+
+        let (decision, new_tat) = gcra.check(tat, 0, 600);
+        assert_eq!(new_tat, None);
+        assert_eq!(decision.retry_after, Some(Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn adjustments_debit_and_credit_cells() {
+        let gcra = Gcra::per_minute(NonZeroU32::new(1000).unwrap());
+        let (_, tat) = gcra.check(None, 0, 600);
+        // overdrawn by 100 tokens, which take 6s to pay back
+        let tat = gcra.adjust(tat, 0, 500);
+        let (decision, new_tat) = gcra.check(tat, 0, 0);
+        assert_eq!(new_tat, None);
+        assert_eq!(decision.retry_after, Some(Duration::from_secs(6)));
+        assert!(gcra.check(tat, 6 * SECOND, 1).0.retry_after.is_some());
+        assert!(gcra.check(tat, 7 * SECOND, 1).0.retry_after.is_none());
+
+        // crediting more than was debited is the same as a full limit
+        assert_eq!(gcra.adjust(tat, 0, -2000), None);
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_forgets_refilled_limits() {
+        let store = InMemoryStore::default();
+        let gcra = gcra();
+        for _ in 0..4 {
+            store.check("key", gcra, 1, 0).await.unwrap();
+        }
+        let decision = store.check("key", gcra, 1, 0).await.unwrap();
+        assert!(decision.retry_after.is_some());
+
+        store.retain_recent(SECOND - 1);
+        assert_eq!(store.tats.lock().unwrap().len(), 1);
+        store.retain_recent(SECOND);
+        assert!(store.tats.lock().unwrap().is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
@@ -10,21 +11,27 @@ use pin_project_lite::pin_project;
 
 use crate::{
     config::response_headers::ResponseHeadersConfig,
-    types::{extensions::ProviderRequestId, provider::InferenceProvider},
+    types::{
+        extensions::{CanaryArm, ProviderOverride, ProviderRequestId},
+        provider::InferenceProvider,
+    },
 };
 
 #[derive(Debug, Clone)]
 pub struct ResponseHeaderService<S> {
-    config: ResponseHeadersConfig,
+    config: Arc<ResponseHeadersConfig>,
     inner: S,
 }
 
 impl<S> ResponseHeaderService<S> {
-    pub const fn new(
+    pub fn new(
         config: ResponseHeadersConfig,
         inner: S,
     ) -> ResponseHeaderService<S> {
-        ResponseHeaderService { config, inner }
+        ResponseHeaderService {
+            config: Arc::new(config),
+            inner,
+        }
     }
 }
 
@@ -50,33 +57,36 @@ where
 
     fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
         ResponseFuture {
-            config: self.config,
+            config: self.config.clone(),
             inner: self.inner.call(req),
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct ResponseHeaderLayer(ResponseHeadersConfig);
+pub struct ResponseHeaderLayer(Arc<ResponseHeadersConfig>);
 
 impl ResponseHeaderLayer {
     #[must_use]
-    pub const fn new(config: ResponseHeadersConfig) -> Self {
-        Self(config)
+    pub fn new(config: ResponseHeadersConfig) -> Self {
+        Self(Arc::new(config))
     }
 }
 
 impl<S> tower::Layer<S> for ResponseHeaderLayer {
     type Service = ResponseHeaderService<S>;
 
-    fn layer(&self, service: S) -> ResponseHeaderService<S> {
-        ResponseHeaderService::new(self.0, service)
+    fn layer(&self, inner: S) -> ResponseHeaderService<S> {
+        ResponseHeaderService {
+            config: self.0.clone(),
+            inner,
+        }
     }
 }
 
 pin_project! {
     pub struct ResponseFuture<F> {
-        config: ResponseHeadersConfig,
+        config: Arc<ResponseHeadersConfig>,
         #[pin]
         inner: F,
     }
@@ -119,6 +129,25 @@ where
                     .insert("helicone-provider-req-id", provider_request_id.0);
             }
         }
+
+        if this.config.canary_arm {
+            let canary_arm = response.extensions().get::<CanaryArm>().copied();
+            if let Some(canary_arm) = canary_arm {
+                response.headers_mut().insert(
+                    "helicone-canary-arm",
+                    http::HeaderValue::from_static(canary_arm.as_str()),
+                );
+            }
+        }
+
+        if this.config.provider_override
+            && response.extensions().get::<ProviderOverride>().is_some()
+        {
+            response.headers_mut().insert(
+                "helicone-provider-override",
+                http::HeaderValue::from_static("true"),
+            );
+        }
         Poll::Ready(Ok(response))
     }
 }
@@ -154,6 +183,9 @@ mod tests {
         let config = ResponseHeadersConfig {
             provider: false,
             provider_request_id: false,
+            canary_arm: false,
+            provider_override: false,
+            ..Default::default()
         };
 
         let mut service = ResponseHeaderService::new(
@@ -181,6 +213,9 @@ mod tests {
         let config = ResponseHeadersConfig {
             provider: true,
             provider_request_id: false,
+            canary_arm: false,
+            provider_override: false,
+            ..Default::default()
         };
 
         let mut service = ResponseHeaderService::new(
@@ -210,6 +245,9 @@ mod tests {
         let config = ResponseHeadersConfig {
             provider: false,
             provider_request_id: true,
+            canary_arm: false,
+            provider_override: false,
+            ..Default::default()
         };
 
         let mut service = ResponseHeaderService::new(
@@ -239,6 +277,9 @@ mod tests {
         let config = ResponseHeadersConfig {
             provider: true,
             provider_request_id: true,
+            canary_arm: false,
+            provider_override: false,
+            ..Default::default()
         };
 
         let mut service = ResponseHeaderService::new(
@@ -274,6 +315,9 @@ mod tests {
         let config = ResponseHeadersConfig {
             provider: true,
             provider_request_id: false,
+            canary_arm: false,
+            provider_override: false,
+            ..Default::default()
         };
 
         let mut service = ResponseHeaderService::new(
@@ -293,6 +337,9 @@ mod tests {
         let config = ResponseHeadersConfig {
             provider: false,
             provider_request_id: true,
+            canary_arm: false,
+            provider_override: false,
+            ..Default::default()
         };
 
         let mut service = ResponseHeaderService::new(
@@ -306,4 +353,55 @@ mod tests {
 
         assert!(!response.headers().contains_key("helicone-provider-req-id"));
     }
+
+    #[tokio::test]
+    async fn test_canary_arm_header_enabled() {
+        let config = ResponseHeadersConfig::default();
+
+        let mut service = ResponseHeaderService::new(
+            config,
+            create_mock_service(|| {
+                let mut response = Response::new("test".to_string());
+                response.extensions_mut().insert(CanaryArm::Canary);
+                response
+            }),
+        );
+
+        let request = Request::new(());
+        let response =
+            service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(
+            response.headers().get("helicone-canary-arm").unwrap(),
+            "canary"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_override_header_enabled() {
+        let config = ResponseHeadersConfig::default();
+
+        let mut service = ResponseHeaderService::new(
+            config,
+            create_mock_service(|| {
+                let mut response = Response::new("test".to_string());
+                response
+                    .extensions_mut()
+                    .insert(ProviderOverride(InferenceProvider::Anthropic));
+                response
+            }),
+        );
+
+        let request = Request::new(());
+        let response =
+            service.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(
+            response
+                .headers()
+                .get("helicone-provider-override")
+                .unwrap(),
+            "true"
+        );
+    }
 }
@@ -0,0 +1,251 @@
+//! Rewrites the model requested, or picks the provider, based on the
+//! request's content, see [`RouterConfig::routing_rules`].
+//!
+//! Like model aliases, this runs before the model is checked against the
+//! router's allowed models, before the cache and before a provider is
+//! selected. Requests routed to a provider are pinned to it by the
+//! [`provider_override`](super::provider_override) layer.
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use futures::future::BoxFuture;
+use http_body_util::BodyExt;
+use serde_json::Value;
+
+use crate::{
+    config::{
+        router::RouterConfig,
+        routing_rules::{RoutingRulesConfig, RoutingTarget, RuleConditions},
+    },
+    error::{api::ApiError, internal::InternalError},
+    tokenizer::estimate_input_tokens,
+    types::{extensions::RoutedProvider, request::Request, response::Response},
+};
+
+/// What routing rules can match a request on.
+#[derive(Debug, Clone, Copy, Default)]
+struct Features {
+    /// Only estimated if a rule needs it.
+    input_tokens: Option<usize>,
+    has_tools: bool,
+    messages: usize,
+}
+
+impl Features {
+    fn of(request: &Value, body: &[u8], count_tokens: bool) -> Self {
+        Self {
+            input_tokens: count_tokens.then(|| estimate_input_tokens(body)),
+            has_tools: request
+                .get("tools")
+                .and_then(Value::as_array)
+                .is_some_and(|tools| !tools.is_empty()),
+            messages: request
+                .get("messages")
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len),
+        }
+    }
+
+    fn satisfy(&self, when: &RuleConditions) -> bool {
+        let tokens = self.input_tokens.unwrap_or_default();
+        when.min_input_tokens.is_none_or(|min| tokens >= min)
+            && when.max_input_tokens.is_none_or(|max| tokens <= max)
+            && when.has_tools.is_none_or(|has| has == self.has_tools)
+            && when.min_messages.is_none_or(|min| self.messages >= min)
+            && when.max_messages.is_none_or(|max| self.messages <= max)
+    }
+}
+
+/// The target of the first rule the request satisfies, or the default.
+fn route<'a>(
+    config: &'a RoutingRulesConfig,
+    features: &Features,
+) -> Option<&'a RoutingTarget> {
+    config
+        .rules
+        .iter()
+        .find(|rule| features.satisfy(&rule.when))
+        .map(|rule| &rule.target)
+        .or(config.default.as_ref())
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    config: Option<Arc<RoutingRulesConfig>>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            config: router_config.routing_rules.clone().map(Arc::new),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    config: Option<Arc<RoutingRulesConfig>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(config) = self.config.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            let mut request: Value = match serde_json::from_slice(&body) {
+                Ok(request) => request,
+                // e.g. multipart audio requests
+                Err(_) => {
+                    return inner
+                        .call(Request::from_parts(parts, Body::from(body)))
+                        .await;
+                }
+            };
+            let features =
+                Features::of(&request, &body, config.counts_tokens());
+            let Some(target) = route(&config, &features) else {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            };
+            tracing::debug!(
+                model = ?target.model,
+                provider = ?target.provider,
+                input_tokens = ?features.input_tokens,
+                "routed request by routing rules"
+            );
+            if let Some(provider) = &target.provider {
+                parts.extensions.insert(RoutedProvider(provider.clone()));
+            }
+            let Some(model) = &target.model else {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            };
+            request["model"] = Value::String(model.clone());
+            let body = serde_json::to_vec(&request).map_err(|e| {
+                InternalError::Serialize {
+                    ty: "serde_json::Value",
+                    error: e,
+                }
+            })?;
+            inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::routing_rules::RoutingRule;
+
+    fn target(model: &str) -> RoutingTarget {
+        RoutingTarget {
+            model: Some(model.to_string()),
+            provider: None,
+        }
+    }
+
+    #[test]
+    fn first_satisfied_rule_applies() {
+        let config = RoutingRulesConfig {
+            rules: vec![
+                RoutingRule {
+                    when: RuleConditions {
+                        has_tools: Some(true),
+                        ..Default::default()
+                    },
+                    target: target("openai/gpt-4o"),
+                },
+                RoutingRule {
+                    when: RuleConditions {
+                        max_input_tokens: Some(50),
+                        max_messages: Some(2),
+                        ..Default::default()
+                    },
+                    target: target("openai/gpt-4o-mini"),
+                },
+            ],
+            default: Some(target("openai/o3")),
+        };
+        let routed_model = |request: Value| {
+            let body = serde_json::to_vec(&request).unwrap();
+            let features = Features::of(&request, &body, true);
+            route(&config, &features).and_then(|target| target.model.clone())
+        };
+        let message = json!({ "role": "user", "content": "Hi" });
+
+        assert_eq!(
+            routed_model(json!({ "messages": [message] })).as_deref(),
+            Some("openai/gpt-4o-mini")
+        );
+        assert_eq!(
+            routed_model(json!({
+                "messages": [message],
+                "tools": [{ "type": "function" }],
+            }))
+            .as_deref(),
+            Some("openai/gpt-4o")
+        );
+        // every condition of a rule must hold
+        assert_eq!(
+            routed_model(json!({ "messages": [message, message, message] }))
+                .as_deref(),
+            Some("openai/o3")
+        );
+        let long = json!({ "role": "user", "content": "word ".repeat(100) });
+        assert_eq!(
+            routed_model(json!({ "messages": [long] })).as_deref(),
+            Some("openai/o3")
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Mirrors a sample of a router's requests to a shadow provider, see
+//! [`ShadowConfig`](crate::config::shadow::ShadowConfig).
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use futures::future::BoxFuture;
+use http_body_util::BodyExt;
+use opentelemetry::KeyValue;
+use rust_decimal::prelude::ToPrimitive;
+use tower::ServiceExt;
+use tracing::Instrument;
+
+use crate::{
+    app_state::AppState,
+    config::{request_logging::RequestLogging, router::RouterConfig},
+    dispatcher::{Dispatcher, DispatcherService},
+    error::{api::ApiError, init::InitError, internal::InternalError},
+    metrics::Metrics,
+    types::{
+        extensions::RequestContext,
+        provider::InferenceProvider,
+        request::{Request, is_stream},
+        response::Response,
+        router::RouterId,
+    },
+};
+
+#[derive(Debug, Clone)]
+struct Shadow {
+    provider: InferenceProvider,
+    sample_rate: f64,
+    dispatcher: DispatcherService,
+    /// The router's config with request logging turned off, so that shadow
+    /// requests aren't logged as if they were sent by the user.
+    shadow_router_config: Arc<RouterConfig>,
+    metrics: Metrics,
+}
+
+impl Shadow {
+    fn sample(&self) -> bool {
+        rand::random::<f64>() < self.sample_rate
+    }
+
+    /// Send the request to the shadow provider in the background. The
+    /// outcome is only recorded in metrics.
+    fn mirror(&self, mut req: Request) {
+        let Shadow {
+            provider,
+            dispatcher,
+            shadow_router_config,
+            metrics,
+            ..
+        } = self.clone();
+        let auth_context = req
+            .extensions()
+            .get::<Arc<RequestContext>>()
+            .and_then(|req_ctx| req_ctx.auth_context.clone());
+        req.extensions_mut().insert(Arc::new(RequestContext {
+            router_config: Some(shadow_router_config),
+            auth_context,
+        }));
+        let span = tracing::info_span!("shadow", provider = %provider);
+        tokio::spawn(
+            async move {
+                let response = match dispatcher.oneshot(req).await {
+                    Ok(response) => response,
+                    Err(e) => match e {},
+                };
+                let status = response.status();
+                // drive the body to completion so that streamed errors count
+                let result = response.into_body().collect().await;
+                let outcome = if status.is_success() && result.is_ok() {
+                    tracing::debug!(status = %status, "shadow succeeded");
+                    "success"
+                } else {
+                    tracing::warn!(status = %status, "shadow failed");
+                    "error"
+                };
+                metrics.shadow_requests.add(
+                    1,
+                    &[
+                        KeyValue::new("provider", provider.to_string()),
+                        KeyValue::new("status", outcome),
+                    ],
+                );
+            }
+            .instrument(span),
+        );
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    shadow: Option<Shadow>,
+}
+
+impl Layer {
+    pub async fn for_router(
+        app_state: &AppState,
+        router_id: &RouterId,
+        router_config: &Arc<RouterConfig>,
+    ) -> Result<Self, InitError> {
+        let Some(shadow_config) = &router_config.shadow else {
+            return Ok(Self { shadow: None });
+        };
+        // shadow traffic shouldn't take the provider out of the router's
+        // rotation
+        let dispatcher = Dispatcher::new_without_rate_limit_events(
+            app_state.clone(),
+            router_id,
+            router_config,
+            shadow_config.provider.clone(),
+        )
+        .await?;
+        let sample_rate = shadow_config.sample_rate.to_f64().unwrap_or(0.0);
+        let shadow_router_config = Arc::new(RouterConfig {
+            logging: RequestLogging::Off,
+            shadow: None,
+            ..RouterConfig::clone(router_config)
+        });
+        Ok(Self {
+            shadow: Some(Shadow {
+                provider: shadow_config.provider.clone(),
+                sample_rate,
+                dispatcher,
+                shadow_router_config,
+                metrics: app_state.0.metrics.clone(),
+            }),
+        })
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            shadow: self.shadow.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    shadow: Option<Shadow>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(shadow) = self.shadow.clone().filter(Shadow::sample) else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            if !is_stream(&body) {
+                let mut shadow_req = Request::new(Body::from(body.clone()));
+                *shadow_req.method_mut() = parts.method.clone();
+                *shadow_req.uri_mut() = parts.uri.clone();
+                *shadow_req.headers_mut() = parts.headers.clone();
+                *shadow_req.extensions_mut() = parts.extensions.clone();
+                shadow.mirror(shadow_req);
+            }
+            inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await
+        })
+    }
+}
@@ -0,0 +1,105 @@
+//! Buffers streaming responses until the stream completes, see
+//! [`StreamingMode::BufferValidate`].
+use std::task::{Context, Poll};
+
+use axum_core::body::Body;
+use futures::future::BoxFuture;
+use http_body_util::BodyExt;
+
+use crate::{
+    config::{router::RouterConfig, streaming::StreamingMode},
+    error::{api::ApiError, internal::InternalError, stream::StreamError},
+    types::{
+        request::{Request, is_stream},
+        response::Response,
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Layer {
+    mode: StreamingMode,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            mode: router_config.streaming_mode,
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            mode: self.mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    mode: StreamingMode,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        if self.mode.is_passthrough() {
+            return Box::pin(inner.call(req));
+        }
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            let is_stream = is_stream(&body);
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await?;
+            if !is_stream || !response.status().is_success() {
+                return Ok(response);
+            }
+
+            // every frame is already a complete SSE event, so once the
+            // stream has completed the events can be sent to the client as
+            // a single frame
+            let (parts, body) = response.into_parts();
+            let events = match body.collect().await {
+                Ok(events) => events.to_bytes(),
+                Err(e) => {
+                    tracing::debug!(error = %e, "buffered stream failed");
+                    return Err(StreamError::BodyError(e).into());
+                }
+            };
+            Ok(Response::from_parts(parts, Body::from(events)))
+        })
+    }
+}
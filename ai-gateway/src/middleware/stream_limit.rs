@@ -0,0 +1,226 @@
+//! Caps the number of concurrent streaming requests through a router, see
+//! [`StreamLimitConfig`].
+use std::{
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use axum_core::{body::Body, response::IntoResponse};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use http_body::{Body as _, Frame, SizeHint};
+use http_body_util::BodyExt;
+use pin_project_lite::pin_project;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    config::{router::RouterConfig, stream_limit::StreamLimitConfig},
+    error::{
+        api::ApiError,
+        internal::InternalError,
+        invalid_req::{InvalidRequestError, TooManyRequestsError},
+    },
+    types::{
+        request::{Request, is_stream},
+        response::Response,
+    },
+};
+
+#[derive(Debug)]
+struct StreamLimiter {
+    config: StreamLimitConfig,
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+impl StreamLimiter {
+    fn new(config: StreamLimitConfig) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(config.limit.get())),
+            config,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Admit a stream, waiting in the queue if the router is at its limit.
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit, ApiError> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let queued = QueuedGuard::new(&self.queued);
+        if queued.position > self.config.queue_depth {
+            tracing::debug!("stream queue full, rejecting stream");
+            return Err(self.too_many_streams());
+        }
+        tracing::debug!(position = queued.position, "queueing stream");
+        tokio::time::timeout(
+            self.config.queue_timeout,
+            self.semaphore.clone().acquire_owned(),
+        )
+        .await
+        .map_err(|_| {
+            tracing::debug!("timed out waiting for stream slot");
+            self.too_many_streams()
+        })?
+        .map_err(|_| ApiError::Internal(InternalError::Internal))
+    }
+
+    fn too_many_streams(&self) -> ApiError {
+        ApiError::InvalidRequest(InvalidRequestError::TooManyRequests(
+            TooManyRequestsError {
+                ratelimit_limit: u64::try_from(self.config.limit.get())
+                    .unwrap_or(u64::MAX),
+                ratelimit_remaining: 0,
+                retry_after: self.config.queue_timeout.as_secs().max(1),
+                ratelimit_reset: self.config.queue_timeout.as_secs().max(1),
+            },
+        ))
+    }
+}
+
+/// Tracks a request waiting in the queue, leaving the queue on drop so that
+/// cancelled requests free up their place.
+pub(super) struct QueuedGuard<'a> {
+    queued: &'a AtomicUsize,
+    /// 1-indexed position in the queue.
+    pub(super) position: usize,
+}
+
+impl<'a> QueuedGuard<'a> {
+    pub(super) fn new(queued: &'a AtomicUsize) -> Self {
+        let position = queued.fetch_add(1, Ordering::AcqRel) + 1;
+        Self { queued, position }
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    limiter: Option<Arc<StreamLimiter>>,
+}
+
+impl Layer {
+    #[must_use]
+    pub fn for_router(router_config: &RouterConfig) -> Self {
+        Self {
+            limiter: router_config
+                .max_concurrent_streams
+                .clone()
+                .map(|config| Arc::new(StreamLimiter::new(config))),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    limiter: Option<Arc<StreamLimiter>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(limiter) = self.limiter.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            if !is_stream(&body) {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            }
+
+            let permit = match limiter.acquire().await {
+                Ok(permit) => permit,
+                Err(e) => return Ok(e.into_response()),
+            };
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await?;
+            Ok(response.map(|body| {
+                Body::new(PermitBody {
+                    inner: body,
+                    _permit: permit,
+                })
+            }))
+        })
+    }
+}
+
+pin_project! {
+    /// Holds a slot until the response body is dropped, i.e. once the
+    /// response completes or the client disconnects.
+    pub(super) struct PermitBody<P = OwnedSemaphorePermit> {
+        #[pin]
+        pub(super) inner: Body,
+        pub(super) _permit: P,
+    }
+}
+
+impl<P> http_body::Body for PermitBody<P> {
+    type Data = Bytes;
+    type Error = axum_core::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.project().inner.poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,99 @@
+//! Chooses the model a model weighted router sends each request to before the
+//! request is cached, see [`TargetModel`].
+//!
+//! Otherwise the model would only be chosen by the balancer, after the cache
+//! has already looked up the request, so a response from one model could be
+//! served for a request that the balancer would have sent to another, e.g. a
+//! canary model's responses could be served for the stable model.
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use rand::seq::IndexedRandom;
+use rust_decimal::prelude::ToPrimitive;
+
+use crate::{
+    config::balance::BalanceConfigInner,
+    types::{extensions::TargetModel, model_id::ModelId, request::Request},
+};
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    models: Option<Arc<[(ModelId, f64)]>>,
+}
+
+impl Layer {
+    /// Only chooses models for model weighted balance configs.
+    #[must_use]
+    pub fn for_balance_config(balance_config: &BalanceConfigInner) -> Self {
+        let models = match balance_config {
+            BalanceConfigInner::ModelWeighted { models } => Some(
+                models
+                    .iter()
+                    .map(|model| {
+                        (
+                            model.model.clone(),
+                            model.weight.to_f64().unwrap_or(0.0),
+                        )
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Self { models }
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            models: self.models.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    models: Option<Arc<[(ModelId, f64)]>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        if let Some(models) = &self.models {
+            // weights are validated on startup, but if sampling still fails
+            // the balancer chooses the model instead
+            match models
+                .choose_weighted(&mut rand::rng(), |(_, weight)| *weight)
+            {
+                Ok((model, _)) => {
+                    tracing::trace!(model = %model, "chose target model");
+                    req.extensions_mut().insert(TargetModel(model.clone()));
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to choose target model");
+                }
+            }
+        }
+        self.inner.call(req)
+    }
+}
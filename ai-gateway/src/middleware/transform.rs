@@ -0,0 +1,219 @@
+//! Applies a router's declarative request transforms, see [`TransformRule`].
+use std::{
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use futures::future::BoxFuture;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+
+use crate::{
+    config::{
+        router::RouterConfig, transform::TransformRule,
+        validation::RouterValidationError,
+    },
+    error::{api::ApiError, init::InitError, internal::InternalError},
+    types::{request::Request, response::Response},
+};
+
+#[derive(Debug)]
+enum HeaderTransform {
+    Set(HeaderName, HeaderValue),
+    Remove(HeaderName),
+}
+
+#[derive(Debug)]
+struct Transforms {
+    headers: Vec<HeaderTransform>,
+    body: Vec<TransformRule>,
+}
+
+impl Transforms {
+    fn new(rules: &[TransformRule]) -> Result<Self, InitError> {
+        let invalid_header = |name: &String| {
+            InitError::InvalidRouterConfig(
+                RouterValidationError::InvalidTransformHeader {
+                    name: name.clone(),
+                },
+            )
+        };
+        let mut headers = Vec::new();
+        for rule in rules {
+            match rule {
+                TransformRule::SetHeader { name, value } => {
+                    headers.push(HeaderTransform::Set(
+                        HeaderName::from_str(name)
+                            .map_err(|_| invalid_header(name))?,
+                        HeaderValue::from_str(value)
+                            .map_err(|_| invalid_header(name))?,
+                    ));
+                }
+                TransformRule::RemoveHeader { name } => {
+                    headers.push(HeaderTransform::Remove(
+                        HeaderName::from_str(name)
+                            .map_err(|_| invalid_header(name))?,
+                    ));
+                }
+                TransformRule::PrependSystemMessage { .. }
+                | TransformRule::AppendSystemMessage { .. }
+                | TransformRule::DefaultParam { .. } => {}
+            }
+        }
+        let body = rules
+            .iter()
+            .filter(|rule| rule.modifies_body())
+            .cloned()
+            .collect();
+        Ok(Self { headers, body })
+    }
+
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        for transform in &self.headers {
+            match transform {
+                HeaderTransform::Set(name, value) => {
+                    headers.insert(name.clone(), value.clone());
+                }
+                HeaderTransform::Remove(name) => {
+                    headers.remove(name);
+                }
+            }
+        }
+    }
+
+    fn apply_body(&self, body: &mut Value) {
+        let Some(object) = body.as_object_mut() else {
+            return;
+        };
+        // prepended messages keep their configured order
+        let mut prepended = 0;
+        for rule in &self.body {
+            match rule {
+                TransformRule::PrependSystemMessage { content } => {
+                    if let Some(messages) =
+                        object.get_mut("messages").and_then(Value::as_array_mut)
+                    {
+                        messages.insert(prepended, system_message(content));
+                        prepended += 1;
+                    }
+                }
+                TransformRule::AppendSystemMessage { content } => {
+                    if let Some(messages) =
+                        object.get_mut("messages").and_then(Value::as_array_mut)
+                    {
+                        messages.push(system_message(content));
+                    }
+                }
+                TransformRule::DefaultParam { param, value } => {
+                    if !object.contains_key(param) {
+                        object.insert(param.clone(), value.clone());
+                    }
+                }
+                TransformRule::SetHeader { .. }
+                | TransformRule::RemoveHeader { .. } => {}
+            }
+        }
+    }
+}
+
+fn system_message(content: &str) -> Value {
+    json!({
+        "role": "system",
+        "content": content,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    transforms: Option<Arc<Transforms>>,
+}
+
+impl Layer {
+    pub fn for_router(router_config: &RouterConfig) -> Result<Self, InitError> {
+        if router_config.transforms.is_empty() {
+            return Ok(Self { transforms: None });
+        }
+        let transforms = Transforms::new(&router_config.transforms)?;
+        Ok(Self {
+            transforms: Some(Arc::new(transforms)),
+        })
+    }
+}
+
+impl<S> tower::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            inner,
+            transforms: self.transforms.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Service<S> {
+    inner: S,
+    transforms: Option<Arc<Transforms>>,
+}
+
+impl<S> tower::Service<Request> for Service<S>
+where
+    S: tower::Service<Request, Response = Response, Error = ApiError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let Some(transforms) = self.transforms.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        transforms.apply_headers(req.headers_mut());
+        if transforms.body.is_empty() {
+            return Box::pin(inner.call(req));
+        }
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            // invalid bodies are rejected further down the stack
+            let Ok(mut json) = serde_json::from_slice::<Value>(&body) else {
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            };
+            transforms.apply_body(&mut json);
+            let body = serde_json::to_vec(&json).map_err(|error| {
+                InternalError::Serialize {
+                    ty: "request body",
+                    error,
+                }
+            })?;
+            inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await
+        })
+    }
+}
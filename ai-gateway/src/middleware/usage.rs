@@ -0,0 +1,375 @@
+//! Reads the token usage providers report in responses, which is only known
+//! once the response completes, e.g. from the last event of a stream.
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use bytes::{Bytes, BytesMut};
+use http_body::{Frame, SizeHint};
+use pin_project_lite::pin_project;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    tokenizer::{estimate_input_tokens, estimate_output_tokens},
+    types::response::Response,
+};
+
+#[derive(Debug, Deserialize)]
+struct UsageResponse {
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<Usage>,
+    /// Responses API stream events nest the response, e.g.
+    /// `response.completed`, and Anthropic's `message_start` the message.
+    #[serde(default, alias = "message")]
+    response: Option<Box<UsageResponse>>,
+}
+
+/// Token usage as reported by the `OpenAI` and Anthropic APIs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Usage {
+    #[serde(default, alias = "input_tokens")]
+    pub prompt_tokens: u64,
+    #[serde(default, alias = "output_tokens")]
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    #[must_use]
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Estimates the usage of a chat completions stream which didn't report
+    /// it, from the request and the content reassembled from the stream.
+    #[must_use]
+    pub fn estimate_stream(request_body: &[u8], response_body: &[u8]) -> Self {
+        let content = stream_content(response_body);
+        let to_u64 = |tokens| u64::try_from(tokens).unwrap_or(u64::MAX);
+        Self {
+            prompt_tokens: to_u64(estimate_input_tokens(request_body)),
+            completion_tokens: to_u64(estimate_output_tokens(
+                request_body,
+                &content,
+            )),
+        }
+    }
+}
+
+/// The content and tool call arguments streamed in the deltas of chat
+/// completion chunks, joined.
+fn stream_content(body: &[u8]) -> String {
+    let mut content = String::new();
+    let chunks = body
+        .split(|b| *b == b'\n')
+        .filter_map(|line| line.strip_prefix(b"data:"))
+        .filter_map(|data| {
+            serde_json::from_slice::<Value>(data.trim_ascii()).ok()
+        });
+    for chunk in chunks {
+        let deltas = chunk
+            .get("choices")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|choice| choice.get("delta"));
+        for delta in deltas {
+            if let Some(text) = delta.get("content").and_then(Value::as_str) {
+                content.push_str(text);
+            }
+            let arguments = delta
+                .get("tool_calls")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|tool_call| {
+                    tool_call.pointer("/function/arguments")?.as_str()
+                });
+            for argument in arguments {
+                content.push_str(argument);
+            }
+        }
+    }
+    content
+}
+
+/// What a response reported, if anything, e.g. streams only report usage
+/// when asked to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResponseUsage {
+    pub model: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+impl ResponseUsage {
+    /// Reads the usage of a complete response body.
+    #[must_use]
+    pub fn from_body(body: &[u8], is_stream: bool) -> Self {
+        let mut usage = Self::default();
+        if is_stream {
+            body.split(|b| *b == b'\n')
+                .filter_map(|line| line.strip_prefix(b"data:"))
+                .filter_map(|data| {
+                    serde_json::from_slice::<UsageResponse>(data.trim_ascii())
+                        .ok()
+                })
+                .for_each(|response| usage.add(response));
+        } else if let Ok(response) =
+            serde_json::from_slice::<UsageResponse>(body)
+        {
+            usage.add(response);
+        }
+        usage
+    }
+
+    fn add(&mut self, mut response: UsageResponse) {
+        if let Some(nested) = response.response.take() {
+            response = *nested;
+        }
+        self.model = response.model.or(self.model.take());
+        // streams usually report usage in the final event, but some report
+        // running totals as they go
+        if let Some(response_usage) = response.usage {
+            let usage = self.usage.get_or_insert_default();
+            usage.prompt_tokens =
+                usage.prompt_tokens.max(response_usage.prompt_tokens);
+            usage.completion_tokens = usage
+                .completion_tokens
+                .max(response_usage.completion_tokens);
+        }
+    }
+}
+
+/// Whether a response is a server-sent event stream.
+#[must_use]
+pub fn is_event_stream(response: &Response) -> bool {
+    response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.starts_with(mime::TEXT_EVENT_STREAM.essence_str())
+        })
+}
+
+type OnComplete = Box<dyn FnOnce(ResponseUsage) + Send>;
+
+pin_project! {
+    /// Passes a response body through, calling back with its usage once the
+    /// body completes.
+    pub struct UsageBody {
+        #[pin]
+        inner: Body,
+        is_stream: bool,
+        frames: Vec<Bytes>,
+        on_complete: Option<OnComplete>,
+    }
+}
+
+impl UsageBody {
+    pub fn new(
+        inner: Body,
+        is_stream: bool,
+        on_complete: impl FnOnce(ResponseUsage) + Send + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            is_stream,
+            frames: Vec::new(),
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+}
+
+impl http_body::Body for UsageBody {
+    type Data = Bytes;
+    type Error = axum_core::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = std::task::ready!(this.inner.as_mut().poll_frame(cx));
+        if let Some(Ok(frame)) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            this.frames.push(data.clone());
+        }
+        if (frame.is_none() || this.inner.is_end_stream())
+            && let Some(on_complete) = this.on_complete.take()
+        {
+            // events can be split across frames, so they're joined first
+            let body = if let [frame] = this.frames.as_slice() {
+                frame.clone()
+            } else {
+                this.frames
+                    .drain(..)
+                    .fold(BytesMut::new(), |mut body, frame| {
+                        body.extend_from_slice(&frame);
+                        body
+                    })
+                    .freeze()
+            };
+            on_complete(ResponseUsage::from_body(&body, *this.is_stream));
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use http_body_util::BodyExt;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn event(data: &Value) -> String {
+        format!("data: {data}\n\n")
+    }
+
+    /// Streams `frames` through a [`UsageBody`], returning the usage it
+    /// reported.
+    async fn stream_usage(frames: Vec<String>) -> ResponseUsage {
+        let frames = frames
+            .into_iter()
+            .map(|frame| Ok::<_, Infallible>(Bytes::from(frame)))
+            .collect::<Vec<_>>();
+        let reported = Arc::new(Mutex::new(None));
+        let body = UsageBody::new(
+            Body::from_stream(futures::stream::iter(frames)),
+            true,
+            {
+                let reported = reported.clone();
+                move |usage| *reported.lock().unwrap() = Some(usage)
+            },
+        );
+        body.collect().await.unwrap();
+        reported
+            .lock()
+            .unwrap()
+            .take()
+            .expect("usage wasn't reported")
+    }
+
+    #[tokio::test]
+    async fn usage_in_the_last_chunk_of_a_stream() {
+        let delta = json!({
+            "model": "gpt-4o",
+            "choices": [{ "delta": { "content": "Hi" } }],
+        });
+        let usage = event(&json!({
+            "model": "gpt-4o",
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 12,
+                "completion_tokens": 3,
+                "total_tokens": 15,
+            },
+        }));
+        // the usage chunk is split across frames
+        let (usage_start, usage_end) = usage.split_at(usage.len() / 2);
+        let usage = stream_usage(vec![
+            event(&delta),
+            usage_start.to_string(),
+            usage_end.to_string(),
+            "data: [DONE]\n\n".to_string(),
+        ])
+        .await;
+        assert_eq!(usage.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            usage.usage,
+            Some(Usage {
+                prompt_tokens: 12,
+                completion_tokens: 3,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn running_totals_of_anthropic_streams() {
+        let usage = stream_usage(vec![
+            event(&json!({
+                "type": "message_start",
+                "message": {
+                    "model": "claude-3-5-haiku",
+                    "usage": { "input_tokens": 20, "output_tokens": 1 },
+                },
+            })),
+            event(&json!({
+                "type": "message_delta",
+                "usage": { "output_tokens": 9 },
+            })),
+            event(&json!({ "type": "message_stop" })),
+        ])
+        .await;
+        assert_eq!(usage.model.as_deref(), Some("claude-3-5-haiku"));
+        assert_eq!(
+            usage.usage,
+            Some(Usage {
+                prompt_tokens: 20,
+                completion_tokens: 9,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_without_usage() {
+        let usage = stream_usage(vec![
+            event(&json!({ "choices": [{ "delta": { "content": "Hi" } }] })),
+            "data: [DONE]\n\n".to_string(),
+        ])
+        .await;
+        assert_eq!(usage.usage, None);
+    }
+
+    #[test]
+    fn usage_of_streams_without_usage_is_estimated() {
+        let request = json!({
+            "model": "openai/gpt-4o",
+            "messages": [{ "role": "user", "content": "Say hello" }],
+        });
+        let body = [
+            event(&json!({ "choices": [{ "delta": { "content": "Hello" } }] })),
+            event(
+                &json!({ "choices": [{ "delta": { "content": " world" } }] }),
+            ),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+        let usage = Usage::estimate_stream(
+            request.to_string().as_bytes(),
+            body.as_bytes(),
+        );
+        assert_eq!(usage.completion_tokens, 2);
+        assert!(usage.prompt_tokens > 0);
+    }
+
+    #[test]
+    fn usage_of_a_complete_response() {
+        let body = json!({
+            "model": "gpt-4o",
+            "usage": { "prompt_tokens": 5, "completion_tokens": 7 },
+        });
+        let usage =
+            ResponseUsage::from_body(body.to_string().as_bytes(), false);
+        assert_eq!(usage.usage.map(|usage| usage.total_tokens()), Some(12));
+    }
+}
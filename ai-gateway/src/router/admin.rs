@@ -0,0 +1,244 @@
+//! Serves `GET /admin/config`, which returns the effective runtime config,
+//! e.g. to verify that a hot reload or a router's overrides took effect.
+//!
+//! The response is `{ "config": ..., "provider-keys": ... }`, where `config`
+//! is the [`Config`](crate::config::Config) after defaults and merges with the
+//! routers currently being served, and `provider-keys` are the provider keys
+//! requests from the caller's organization are sent with. Secrets, including
+//! cache seeds, are masked.
+//!
+//! `GET /admin/providers/health` returns whether each provider of each router
+//! is currently in the router's pool, as `{ "routers": { router-id: {
+//! provider: ... } } }`. Providers removed by the health or rate limit
+//! monitors have a `removal` with its reason and, for rate limited
+//! providers, the `retry-after` deadline at which they're re-added. Removals
+//! since the gateway started are counted by reason.
+//!
+//! `GET /admin/spend` returns the caller's organization's spend in the
+//! current budget period, its limit, and when the period started and ends.
+//! It's only served when a budget is configured.
+//!
+//! Admin endpoints require an authenticated request, so they aren't served
+//! when auth is disabled.
+use bytes::Bytes;
+use http::{HeaderValue, Method, header::CONTENT_TYPE};
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    app_state::AppState,
+    discover::monitor::pool::ProviderPoolState,
+    error::{
+        api::ApiError, auth::AuthError, internal::InternalError,
+        invalid_req::InvalidRequestError,
+    },
+    middleware::budget::org_spend,
+    types::{
+        extensions::AuthContext,
+        provider::{InferenceProvider, ProviderKey},
+        request::Request,
+        response::Response,
+        router::RouterId,
+    },
+};
+
+/// The path of the config endpoint, relative to `/admin`.
+const CONFIG_PATH: &str = "config";
+/// The path of the provider health endpoint, relative to `/admin`.
+const PROVIDERS_HEALTH_PATH: &str = "providers/health";
+/// The path of the spend endpoint, relative to `/admin`.
+const SPEND_PATH: &str = "spend";
+/// Shown in place of secrets, as [`Secret`](crate::types::secret::Secret)s
+/// are serialized.
+const MASKED: &str = "*****";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ConfigResponse {
+    config: Value,
+    provider_keys: IndexMap<InferenceProvider, ProviderKey>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProvidersHealthResponse {
+    routers: IndexMap<RouterId, IndexMap<InferenceProvider, ProviderHealth>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProviderHealth {
+    in_pool: bool,
+    #[serde(flatten)]
+    state: ProviderPoolState,
+}
+
+/// Serves a request, with the path relative to `/admin`.
+pub async fn admin(
+    app_state: AppState,
+    req: Request,
+    path: &str,
+) -> Result<Response, ApiError> {
+    let Some(auth_ctx) = req.extensions().get::<AuthContext>() else {
+        return Err(AuthError::MissingAuthorizationHeader.into());
+    };
+    if req.method() != Method::GET {
+        return Err(not_found(&req));
+    }
+    match path.trim_end_matches('/') {
+        CONFIG_PATH => {
+            let response = ConfigResponse {
+                config: effective_config(&app_state).await?,
+                provider_keys: provider_keys(&app_state, auth_ctx).await,
+            };
+            json_response(&response, "ConfigResponse")
+        }
+        PROVIDERS_HEALTH_PATH => json_response(
+            &providers_health(&app_state).await,
+            "ProvidersHealthResponse",
+        ),
+        SPEND_PATH => {
+            let Some(spend) = org_spend(&app_state, &auth_ctx.org_id).await
+            else {
+                return Err(not_found(&req));
+            };
+            json_response(&spend, "OrgSpend")
+        }
+        _ => Err(not_found(&req)),
+    }
+}
+
+fn not_found(req: &Request) -> ApiError {
+    InvalidRequestError::NotFound(req.uri().path().to_string()).into()
+}
+
+fn json_response(
+    body: &impl Serialize,
+    ty: &'static str,
+) -> Result<Response, ApiError> {
+    let body = serde_json::to_vec(body)
+        .map_err(|e| InternalError::Serialize { ty, error: e })?;
+    let mut response = Response::new(Bytes::from(body).into());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(response)
+}
+
+/// The config with the routers currently being served, which differ from the
+/// configured ones once routers are hot reloaded.
+async fn effective_config(app_state: &AppState) -> Result<Value, ApiError> {
+    let serialize_error = |e| InternalError::Serialize {
+        ty: "Config",
+        error: e,
+    };
+    let mut config =
+        serde_json::to_value(app_state.config()).map_err(serialize_error)?;
+    config["routers"] =
+        serde_json::to_value(&*app_state.0.router_configs.read().await)
+            .map_err(serialize_error)?;
+    mask_cache_seeds(&mut config);
+    Ok(config)
+}
+
+/// Cache seeds are plain strings in the config, since they're also persisted
+/// with router configs, but anyone with a seed can read its cached responses.
+fn mask_cache_seeds(config: &mut Value) {
+    let mut caches = vec![
+        "/global/cache".to_string(),
+        "/unified-api/cache".to_string(),
+    ];
+    if let Some(routers) = config.get("routers").and_then(Value::as_object) {
+        for router_id in routers.keys() {
+            let router_id = router_id.replace('~', "~0").replace('/', "~1");
+            caches.push(format!("/routers/{router_id}/cache"));
+        }
+    }
+    for pointer in caches {
+        if let Some(seed) = config
+            .pointer_mut(&pointer)
+            .and_then(|cache| cache.get_mut("seed"))
+            .filter(|seed| !seed.is_null())
+        {
+            *seed = Value::String(MASKED.to_string());
+        }
+    }
+}
+
+/// The providers of the routers currently being served, sorted by router.
+async fn providers_health(app_state: &AppState) -> ProvidersHealthResponse {
+    let router_configs = app_state.0.router_configs.read().await;
+    let mut routers = router_configs.iter().collect::<Vec<_>>();
+    routers.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+    let pools = &app_state.0.provider_pools;
+    let routers = routers
+        .into_iter()
+        .map(|(router_id, router_config)| {
+            let providers = router_config
+                .load_balance
+                .providers()
+                .into_iter()
+                .map(|provider| {
+                    let state = pools.state(router_id, &provider);
+                    let health = ProviderHealth {
+                        in_pool: state.in_pool(),
+                        state,
+                    };
+                    (provider, health)
+                })
+                .collect();
+            (router_id.clone(), providers)
+        })
+        .collect();
+    ProvidersHealthResponse { routers }
+}
+
+async fn provider_keys(
+    app_state: &AppState,
+    auth_ctx: &AuthContext,
+) -> IndexMap<InferenceProvider, ProviderKey> {
+    let mut provider_keys = IndexMap::new();
+    for provider in app_state.config().providers.keys() {
+        if let Some(key) = app_state
+            .0
+            .provider_keys
+            .get_provider_key(provider, Some(&auth_ctx.org_id))
+            .await
+        {
+            provider_keys.insert(provider.clone(), key);
+        }
+    }
+    provider_keys
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn cache_seeds_are_masked() {
+        let mut config = json!({
+            "global": { "cache": { "buckets": 1, "seed": "global-seed" } },
+            "unified-api": {},
+            "routers": {
+                "my-router": { "cache": { "seed": "router-seed" } },
+                "other-router": { "cache": { "buckets": 2 } },
+            },
+        });
+        mask_cache_seeds(&mut config);
+        assert_eq!(
+            config,
+            json!({
+                "global": { "cache": { "buckets": 1, "seed": MASKED } },
+                "unified-api": {},
+                "routers": {
+                    "my-router": { "cache": { "seed": MASKED } },
+                    "other-router": { "cache": { "buckets": 2 } },
+                },
+            })
+        );
+    }
+}
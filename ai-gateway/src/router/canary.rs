@@ -0,0 +1,193 @@
+//! A balancer which shifts a percentage of traffic from a stable provider to a
+//! canary provider, see
+//! [`BalanceConfigInner::Canary`](crate::config::balance::BalanceConfigInner::Canary).
+use std::{
+    fmt,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use http::HeaderName;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use tower::{
+    Service,
+    discover::Discover,
+    ready_cache::{ReadyCache, error::Failed},
+};
+use tracing::{debug, trace};
+
+use crate::{
+    discover::provider::key::Key,
+    router::ready_set,
+    types::{
+        extensions::CanaryArm, provider::InferenceProvider, request::Request,
+        response::Response,
+    },
+};
+
+/// Assigns each request to an arm by hashing the value of a header into
+/// `[0, 1)`, so that every request with the same header value is sent to the
+/// same provider. If the provider for the assigned arm has been removed from
+/// discovery, the request is sent to the other arm.
+pub struct CanaryBalance<D>
+where
+    D: Discover<Key = Key>,
+{
+    discover: D,
+    stable: InferenceProvider,
+    canary: InferenceProvider,
+    /// Fraction of traffic sent to the canary provider, between 0 and 1.
+    fraction: f64,
+    header: HeaderName,
+    services: ReadyCache<Key, D::Service, Request>,
+}
+
+impl<D> fmt::Debug for CanaryBalance<D>
+where
+    D: Discover<Key = Key> + fmt::Debug,
+    D::Service: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CanaryBalance")
+            .field("discover", &self.discover)
+            .field("stable", &self.stable)
+            .field("canary", &self.canary)
+            .field("fraction", &self.fraction)
+            .field("header", &self.header)
+            .field("services", &self.services)
+            .finish()
+    }
+}
+
+impl<D> CanaryBalance<D>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request, Response = Response>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+{
+    pub fn new(
+        discover: D,
+        stable: InferenceProvider,
+        canary: InferenceProvider,
+        percentage: Decimal,
+        header: HeaderName,
+    ) -> Self {
+        let fraction =
+            (percentage / Decimal::ONE_HUNDRED).to_f64().unwrap_or(0.0);
+        Self {
+            discover,
+            stable,
+            canary,
+            fraction,
+            header,
+            services: ReadyCache::default(),
+        }
+    }
+
+    /// The bucket in `[0, 1)` for the request, drawn at random if the request
+    /// doesn't have the bucketing header.
+    #[allow(clippy::cast_precision_loss)]
+    fn bucket(&self, request: &Request) -> f64 {
+        let Some(value) = request.headers().get(&self.header) else {
+            return rand::random::<f64>();
+        };
+        // a fixed hash function and seed, so that requests keep their arm
+        // across restarts, toolchain upgrades and replicas
+        let hash = seahash::hash(value.as_bytes());
+        // use the top 53 bits so that every bucket is exactly representable
+        (hash >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    fn ready_index_for(&self, provider: &InferenceProvider) -> Option<usize> {
+        (0..self.services.ready_len()).find(|index| {
+            self.services
+                .get_ready_index(*index)
+                .is_some_and(|(key, _service)| key.provider == *provider)
+        })
+    }
+}
+
+impl<D> Service<Request> for CanaryBalance<D>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request, Response = Response>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+    <D::Service as Service<Request>>::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = tower::BoxError;
+    type Future = BoxFuture<'static, Result<Response, tower::BoxError>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        ready_set::update_pending_from_discover(
+            &mut self.discover,
+            &mut self.services,
+            cx,
+            |_| {},
+        )?;
+        ready_set::promote_pending_to_ready(&mut self.services, cx);
+
+        // the arm is only known once we see the request, so every ready
+        // service must still be ready. iterate in reverse since services
+        // which are no longer ready are swapped out for the last service.
+        for index in (0..self.services.ready_len()).rev() {
+            match self.services.check_ready_index(cx, index) {
+                Ok(true) => {}
+                Ok(false) => {
+                    trace!("canary service became unavailable");
+                }
+                Err(Failed(_, error)) => {
+                    debug!(%error, "endpoint failed");
+                }
+            }
+        }
+
+        if self.services.ready_len() == 0 {
+            // We have previously registered interest in updates from
+            // discover and pending services.
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let assigned = if self.bucket(&request) < self.fraction {
+            CanaryArm::Canary
+        } else {
+            CanaryArm::Stable
+        };
+        let provider_for = |arm| match arm {
+            CanaryArm::Stable => &self.stable,
+            CanaryArm::Canary => &self.canary,
+        };
+        let fallback = match assigned {
+            CanaryArm::Stable => CanaryArm::Canary,
+            CanaryArm::Canary => CanaryArm::Stable,
+        };
+        let (arm, index) =
+            if let Some(index) = self.ready_index_for(provider_for(assigned)) {
+                (assigned, index)
+            } else {
+                debug!(arm = assigned.as_str(), "canary arm unavailable");
+                let index = self
+                    .ready_index_for(provider_for(fallback))
+                    .expect("called before ready");
+                (fallback, index)
+            };
+        trace!(arm = arm.as_str(), "canary");
+
+        request.extensions_mut().insert(arm);
+        let future = self.services.call_ready_index(index, request);
+        Box::pin(async move {
+            let mut response = future.await.map_err(Into::into)?;
+            response.extensions_mut().insert(arm);
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,419 @@
+//! A balancer which sends requests with the same key to the same provider,
+//! see
+//! [`BalanceConfigInner::ConsistentHash`](crate::config::balance::BalanceConfigInner::ConsistentHash).
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use futures::future::{BoxFuture, TryFutureExt};
+use http::HeaderName;
+use http_body_util::BodyExt;
+use rust_decimal::prelude::ToPrimitive;
+use tower::{
+    Service,
+    buffer::Buffer,
+    discover::{Change, Discover},
+    ready_cache::{ReadyCache, error::Failed},
+};
+use tracing::{debug, trace};
+
+use crate::{
+    app_state::AppState,
+    config::{
+        balance::{HashKeySource, WeightedProvider},
+        router::RouterConfig,
+        validation::RouterValidationError,
+    },
+    discover::provider::key::Key,
+    error::{api::ApiError, init::InitError, internal::InternalError},
+    router::{ready_set, strategy::provider_discovery},
+    types::{
+        provider::InferenceProvider, request::Request, response::Response,
+        router::RouterId,
+    },
+};
+
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Points on the ring per provider. More points spread keys more evenly
+/// between providers.
+const VIRTUAL_NODES: u32 = 160;
+
+type BalanceFuture = BoxFuture<'static, Result<Response, tower::BoxError>>;
+
+/// The hashed key of a request, inserted by [`ConsistentHashRouter`] since
+/// the body can only be read asynchronously.
+#[derive(Debug, Clone, Copy)]
+struct RequestHashKey(u64);
+
+/// A fixed hash function and seed, so that keys keep their provider across
+/// restarts, toolchain upgrades and replicas.
+fn hash(bytes: &[u8]) -> u64 {
+    seahash::hash(bytes)
+}
+
+/// Maps hashes to providers, with each provider owning the arcs of the ring
+/// that end at one of its virtual nodes.
+#[derive(Debug, Default)]
+struct HashRing {
+    nodes: BTreeMap<u64, InferenceProvider>,
+}
+
+impl HashRing {
+    fn insert(&mut self, provider: &InferenceProvider) {
+        for node in 0..VIRTUAL_NODES {
+            let point = hash(format!("{provider}-{node}").as_bytes());
+            self.nodes.insert(point, provider.clone());
+        }
+    }
+
+    fn remove(&mut self, provider: &InferenceProvider) {
+        self.nodes
+            .retain(|_, node_provider| node_provider != provider);
+    }
+
+    /// Providers in the order they are reached walking clockwise from `key`.
+    fn walk(&self, key: u64) -> impl Iterator<Item = &InferenceProvider> {
+        self.nodes
+            .range(key..)
+            .chain(self.nodes.range(..key))
+            .map(|(_, provider)| provider)
+    }
+}
+
+pub struct ConsistentHashBalance<D>
+where
+    D: Discover<Key = Key>,
+{
+    discover: D,
+    ring: HashRing,
+    /// Weights for requests without a key.
+    weights: HashMap<InferenceProvider, f64>,
+    services: ReadyCache<Key, D::Service, Request>,
+}
+
+impl<D> fmt::Debug for ConsistentHashBalance<D>
+where
+    D: Discover<Key = Key> + fmt::Debug,
+    D::Service: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsistentHashBalance")
+            .field("discover", &self.discover)
+            .field("weights", &self.weights)
+            .field("services", &self.services)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> ConsistentHashBalance<D>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request, Response = Response>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+{
+    pub fn new(discover: D, providers: &[WeightedProvider]) -> Self {
+        let weights = providers
+            .iter()
+            .map(|target| {
+                (
+                    target.provider.clone(),
+                    target.weight.to_f64().unwrap_or(0.0),
+                )
+            })
+            .collect();
+        Self {
+            discover,
+            ring: HashRing::default(),
+            weights,
+            services: ReadyCache::default(),
+        }
+    }
+
+    fn ready_index_for(&self, provider: &InferenceProvider) -> Option<usize> {
+        (0..self.services.ready_len()).find(|index| {
+            self.services
+                .get_ready_index(*index)
+                .is_some_and(|(key, _service)| key.provider == *provider)
+        })
+    }
+
+    /// The first ready provider clockwise from the key on the ring.
+    fn hashed_index(&self, key: u64) -> Option<usize> {
+        self.ring
+            .walk(key)
+            .find_map(|provider| self.ready_index_for(provider))
+    }
+
+    fn weighted_index(&self) -> usize {
+        let weight = |index| {
+            self.services
+                .get_ready_index(index)
+                .and_then(|(key, _service)| self.weights.get(&key.provider))
+                .copied()
+                .unwrap_or(0.0)
+        };
+        let ready_len = self.services.ready_len();
+        let total: f64 = (0..ready_len).map(weight).sum();
+        let mut point = rand::random::<f64>() * total;
+        for index in 0..ready_len {
+            let weight = weight(index);
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        ready_len - 1
+    }
+}
+
+impl<D> Service<Request> for ConsistentHashBalance<D>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request, Response = Response>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+    <D::Service as Service<Request>>::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = tower::BoxError;
+    type Future = BalanceFuture;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        let ring = &mut self.ring;
+        ready_set::update_pending_from_discover(
+            &mut self.discover,
+            &mut self.services,
+            cx,
+            |change| match change {
+                Change::Insert(key, ()) => ring.insert(&key.provider),
+                Change::Remove(key) => ring.remove(&key.provider),
+            },
+        )?;
+        ready_set::promote_pending_to_ready(&mut self.services, cx);
+
+        // the provider is only known once we see the request, so every ready
+        // service must still be ready. iterate in reverse since services
+        // which are no longer ready are swapped out for the last service.
+        for index in (0..self.services.ready_len()).rev() {
+            match self.services.check_ready_index(cx, index) {
+                Ok(true) => {}
+                Ok(false) => {
+                    trace!("consistent hash service became unavailable");
+                }
+                Err(Failed(_, error)) => {
+                    debug!(%error, "endpoint failed");
+                }
+            }
+        }
+
+        if self.services.ready_len() == 0 {
+            // We have previously registered interest in updates from
+            // discover and pending services.
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let index = request
+            .extensions()
+            .get::<RequestHashKey>()
+            .and_then(|RequestHashKey(key)| self.hashed_index(*key))
+            .unwrap_or_else(|| {
+                trace!("no hash key, falling back to weighted selection");
+                self.weighted_index()
+            });
+        let future = self.services.call_ready_index(index, request);
+        Box::pin(future.map_err(Into::into))
+    }
+}
+
+/// Extracts the hash key from each request before it is balanced.
+#[derive(Clone)]
+pub struct ConsistentHashRouter {
+    inner: Buffer<Request, BalanceFuture>,
+    key: Arc<KeyExtractor>,
+}
+
+impl fmt::Debug for ConsistentHashRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsistentHashRouter")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+enum KeyExtractor {
+    Header(HeaderName),
+    JsonPointer(String),
+}
+
+impl ConsistentHashRouter {
+    pub async fn new(
+        app_state: AppState,
+        router_id: RouterId,
+        router_config: Arc<RouterConfig>,
+        providers: &[WeightedProvider],
+        key: &HashKeySource,
+    ) -> Result<Self, InitError> {
+        let key = match key {
+            HashKeySource::Header(name) => {
+                KeyExtractor::Header(HeaderName::from_str(name).map_err(
+                    |_| RouterValidationError::InvalidHashKeyHeader {
+                        name: name.clone(),
+                    },
+                )?)
+            }
+            HashKeySource::JsonPointer(pointer) => {
+                KeyExtractor::JsonPointer(pointer.clone())
+            }
+        };
+        let discovery =
+            provider_discovery(&app_state, &router_id, &router_config).await?;
+        let balance = ConsistentHashBalance::new(discovery, providers);
+        Ok(Self {
+            inner: Buffer::new(balance, CHANNEL_CAPACITY),
+            key: Arc::new(key),
+        })
+    }
+}
+
+impl Service<Request> for ConsistentHashRouter {
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Response, ApiError>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner
+            .poll_ready(cx)
+            .map_err(InternalError::PollReadyError)
+            .map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        let pointer = match &*self.key {
+            KeyExtractor::Header(name) => {
+                if let Some(value) = req.headers().get(name) {
+                    let key = RequestHashKey(hash(value.as_bytes()));
+                    req.extensions_mut().insert(key);
+                }
+                return Box::pin(inner.call(req).map_err(|e| {
+                    ApiError::from(InternalError::LoadBalancerError(e))
+                }));
+            }
+            KeyExtractor::JsonPointer(pointer) => pointer.clone(),
+        };
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            let key = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|json| json.pointer(&pointer).map(hash_value));
+            if let Some(key) = key {
+                parts.extensions.insert(RequestHashKey(key));
+            }
+            inner
+                .call(Request::from_parts(parts, Body::from(body)))
+                .await
+                .map_err(|e| InternalError::LoadBalancerError(e).into())
+        })
+    }
+}
+
+/// Strings are hashed without their quotes so that a header and a body field
+/// with the same value hash the same way.
+fn hash_value(value: &serde_json::Value) -> u64 {
+    match value {
+        serde_json::Value::String(value) => hash(value.as_bytes()),
+        value => hash(value.to_string().as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: u64 = 10_000;
+
+    fn ring(providers: &[InferenceProvider]) -> HashRing {
+        let mut ring = HashRing::default();
+        for provider in providers {
+            ring.insert(provider);
+        }
+        ring
+    }
+
+    fn assign(ring: &HashRing, key: u64) -> InferenceProvider {
+        ring.walk(hash(&key.to_le_bytes())).next().unwrap().clone()
+    }
+
+    #[test]
+    fn keys_are_spread_across_providers() {
+        let providers = [
+            InferenceProvider::OpenAI,
+            InferenceProvider::Anthropic,
+            InferenceProvider::GoogleGemini,
+        ];
+        let ring = ring(&providers);
+        let mut counts = HashMap::<InferenceProvider, u64>::new();
+        for key in 0..KEYS {
+            *counts.entry(assign(&ring, key)).or_default() += 1;
+        }
+
+        for provider in &providers {
+            let share = counts[provider] * 100 / KEYS;
+            assert!((23..=43).contains(&share), "{provider} got {share}%");
+        }
+    }
+
+    #[test]
+    fn removing_a_provider_only_remaps_its_keys() {
+        let mut ring = ring(&[
+            InferenceProvider::OpenAI,
+            InferenceProvider::Anthropic,
+            InferenceProvider::GoogleGemini,
+        ]);
+        let before =
+            (0..KEYS).map(|key| assign(&ring, key)).collect::<Vec<_>>();
+
+        ring.remove(&InferenceProvider::Anthropic);
+
+        for (key, provider) in (0..KEYS).zip(before) {
+            let after = assign(&ring, key);
+            if provider == InferenceProvider::Anthropic {
+                assert_ne!(after, InferenceProvider::Anthropic);
+            } else {
+                assert_eq!(after, provider);
+            }
+        }
+    }
+
+    #[test]
+    fn walk_wraps_around_the_ring() {
+        let ring = ring(&[InferenceProvider::OpenAI]);
+        assert_eq!(ring.walk(u64::MAX).count(), VIRTUAL_NODES as usize);
+    }
+}
@@ -8,6 +8,7 @@ use crate::{
     dispatcher::{
         Dispatcher, DispatcherService, service::DispatcherServiceWithoutMapper,
     },
+    endpoints::{ApiEndpoint, EndpointRoute},
     error::init::InitError,
     middleware::request_context,
     types::provider::InferenceProvider,
@@ -17,6 +18,19 @@ pub type DirectProxyService = request_context::Service<DispatcherService>;
 pub type DirectProxyServiceWithoutMapper =
     request_context::Service<DispatcherServiceWithoutMapper>;
 
+/// Whether a direct proxy path is one of the inference endpoints the gateway
+/// knows, e.g. `v1/chat/completions`, under any provider specific prefix.
+///
+/// Any other path, e.g. `v1/models`, is still forwarded to the provider with
+/// its credentials and logged, but is never cached.
+#[must_use]
+pub fn is_known_path(provider: &InferenceProvider, path: &str) -> bool {
+    ApiEndpoint::for_direct_proxy(provider, path).is_some()
+        || std::iter::once(path)
+            .chain(path.match_indices('/').map(|(i, _)| &path[i + 1..]))
+            .any(|route| EndpointRoute::from_path(route).is_some())
+}
+
 #[derive(Debug, Clone)]
 pub struct DirectProxies(Arc<HashMap<InferenceProvider, DirectProxyService>>);
 
@@ -85,3 +99,19 @@ impl std::ops::Deref for DirectProxiesWithoutMapper {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_paths_are_inference_endpoints() {
+        let openai = InferenceProvider::OpenAI;
+        assert!(is_known_path(&openai, "v1/chat/completions"));
+        assert!(is_known_path(&openai, "v1/embeddings"));
+        assert!(is_known_path(&InferenceProvider::Anthropic, "v1/messages"));
+        assert!(!is_known_path(&openai, "v1/models"));
+        assert!(!is_known_path(&openai, "v1/moderations"));
+        assert!(!is_known_path(&openai, "v1/fake_endpoint"));
+    }
+}
@@ -5,6 +5,7 @@ use std::{
 };
 
 use dynamic_router::router::DynamicRouter;
+use futures::future::BoxFuture;
 use pin_project_lite::pin_project;
 use tower::{
     Service as _, ServiceBuilder, buffer::BufferLayer, util::BoxCloneService,
@@ -22,30 +23,38 @@ use crate::{
         invalid_req::InvalidRequestError,
     },
     middleware::{
+        body_limit, budget,
         cache::{CacheLayer, CacheService},
+        idempotency,
         rate_limit::service::{
             Layer as RateLimitLayer, Service as RateLimitService,
         },
     },
     router::{
+        admin,
         direct::{DirectProxiesWithoutMapper, DirectProxyServiceWithoutMapper},
+        models,
         router_details::{RouteType, RouterDetailsLayer},
-        unified_api,
+        tokenize, unified_api,
+    },
+    types::{
+        extensions::AuthContext, provider::InferenceProvider, router::RouterId,
     },
-    types::{provider::InferenceProvider, router::RouterId},
     utils::handle_error::{ErrorHandler, ErrorHandlerLayer},
 };
 
 pub(crate) const MIDDLEWARE_BUFFER_SIZE: usize = 256;
 
-pub type UnifiedApiService =
-    RateLimitService<CacheService<ErrorHandler<unified_api::Service>>>;
+pub type UnifiedApiService = body_limit::Service<
+    RateLimitService<CacheService<ErrorHandler<unified_api::Service>>>,
+>;
 
 #[derive(Debug)]
 pub struct MetaRouter {
     dynamic_router: DynamicRouter<RouterDiscovery, axum_core::body::Body>,
     unified_api: UnifiedApiService,
     direct_proxies: DirectProxiesWithoutMapper,
+    app_state: AppState,
 }
 
 pub type MetaRouterService = BoxCloneService<
@@ -64,12 +73,18 @@ impl MetaRouter {
         }?;
         let service_stack = ServiceBuilder::new()
             .layer(ErrorHandlerLayer::new(app_state.clone()))
+            // before anything reads the body
+            .layer(body_limit::Layer::global(&app_state))
             .layer(RouterDetailsLayer::new())
             .layer(AsyncRequireAuthorizationLayer::new(
                 crate::middleware::auth::AuthService::new(app_state.clone()),
             ))
+            // replays are neither rate limited nor charged
+            .layer(idempotency::Layer::new(&app_state))
             .layer(RateLimitLayer::global(&app_state)?)
             .layer(CacheLayer::global(&app_state)?)
+            // after the global cache, since cached responses are free
+            .layer(budget::Layer::new(&app_state))
             .layer(ErrorHandlerLayer::new(app_state.clone()))
             .map_err(crate::error::internal::InternalError::BufferError)
             .layer(BufferLayer::new(MIDDLEWARE_BUFFER_SIZE))
@@ -87,6 +102,7 @@ impl MetaRouter {
         let dynamic_router = router_factory.call(Some(rx)).await?;
 
         let unified_api = ServiceBuilder::new()
+            .layer(body_limit::Layer::unified_api(&app_state))
             .layer(RateLimitLayer::unified_api(&app_state)?)
             .layer(CacheLayer::unified_api(&app_state)?)
             .layer(ErrorHandlerLayer::new(app_state.clone()))
@@ -98,6 +114,7 @@ impl MetaRouter {
             dynamic_router,
             unified_api,
             direct_proxies,
+            app_state,
         };
         Ok(meta_router)
     }
@@ -106,8 +123,12 @@ impl MetaRouter {
         let discovery_factory = RouterDiscoverFactory::new(app_state.clone());
         let mut router_factory =
             dynamic_router::router::make::MakeRouter::new(discovery_factory);
-        let dynamic_router = router_factory.call(None).await?;
+        // used to hot reload routers when the config changes
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+        app_state.set_router_tx(tx).await;
+        let dynamic_router = router_factory.call(Some(rx)).await?;
         let unified_api = ServiceBuilder::new()
+            .layer(body_limit::Layer::unified_api(&app_state))
             .layer(RateLimitLayer::unified_api(&app_state)?)
             .layer(CacheLayer::unified_api(&app_state)?)
             .layer(ErrorHandlerLayer::new(app_state.clone()))
@@ -118,6 +139,7 @@ impl MetaRouter {
             dynamic_router,
             unified_api,
             direct_proxies,
+            app_state,
         };
         Ok(meta_router)
     }
@@ -144,6 +166,24 @@ impl MetaRouter {
         rest: &str,
     ) -> ResponseFuture {
         tracing::trace!(api_path = rest, "received /ai request");
+        if models::is_model_list_request(&req, rest) {
+            let auth_ctx = req.extensions().get::<AuthContext>().cloned();
+            return ResponseFuture::Models {
+                future: Box::pin(models::list_models(
+                    self.app_state.clone(),
+                    None,
+                    auth_ctx,
+                )),
+            };
+        }
+        if tokenize::is_tokenize_request(&req, rest) {
+            return ResponseFuture::Tokenize {
+                future: Box::pin(tokenize::tokenize(
+                    self.app_state.clone(),
+                    req,
+                )),
+            };
+        }
         // assumes request is from OpenAI compatible client
         // and uses the model name to determine the provider.
         ResponseFuture::UnifiedApi {
@@ -217,6 +257,15 @@ impl tower::Service<crate::types::request::Request> for MetaRouter {
             Some(RouteType::DirectProxy { provider, .. }) => {
                 self.handle_direct_proxy_request(req, provider.clone())
             }
+            Some(RouteType::Admin { path }) => {
+                tracing::trace!(api_path = %path, "received /admin request");
+                let app_state = self.app_state.clone();
+                ResponseFuture::Admin {
+                    future: Box::pin(async move {
+                        admin::admin(app_state, req, &path).await
+                    }),
+                }
+            }
             None => {
                 tracing::debug!("no route type found");
                 ResponseFuture::Ready {
@@ -250,6 +299,18 @@ pin_project! {
             #[pin]
             future: <DirectProxyServiceWithoutMapper as tower::Service<crate::types::request::Request>>::Future,
         },
+        Models {
+            #[pin]
+            future: BoxFuture<'static, Result<crate::types::response::Response, ApiError>>,
+        },
+        Tokenize {
+            #[pin]
+            future: BoxFuture<'static, Result<crate::types::response::Response, ApiError>>,
+        },
+        Admin {
+            #[pin]
+            future: BoxFuture<'static, Result<crate::types::response::Response, ApiError>>,
+        },
     }
 }
 
@@ -269,6 +330,9 @@ impl std::future::Future for ResponseFuture {
             ResponseFutureProj::DirectProxy { future } => future
                 .poll(cx)
                 .map_err(|_| ApiError::Internal(InternalError::Internal)),
+            ResponseFutureProj::Models { future }
+            | ResponseFutureProj::Tokenize { future }
+            | ResponseFutureProj::Admin { future } => future.poll(cx),
         }
     }
 }
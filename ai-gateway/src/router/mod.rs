@@ -1,9 +1,16 @@
+pub mod admin;
+pub mod canary;
+pub mod consistent_hash;
 pub mod direct;
 pub mod latency;
 pub mod meta;
+pub mod models;
+pub mod priority;
+mod ready_set;
 pub mod router_details;
 pub mod service;
 pub mod strategy;
+pub mod tokenize;
 pub mod unified_api;
 
 pub(in crate::router) const FORCED_ROUTING_HEADER: http::HeaderName =
@@ -0,0 +1,158 @@
+//! Serves `GET /ai/models` and `GET /router/{id}/models`, so that OpenAI
+//! SDKs which list the available models on startup work against the gateway.
+//!
+//! Models are listed from the gateway's model registry, i.e. the `models` of
+//! each configured provider, as `{provider}/{model}`, the same names the
+//! unified API and routers accept. The unified API lists the models of every
+//! provider, while a router lists only the models of the providers it load
+//! balances across that it allows. In the cloud, providers the organization
+//! hasn't configured a key for are left out.
+//!
+//! Assembled lists are cached in-process for
+//! [`ModelListConfig::cache_ttl`](crate::config::model_list::ModelListConfig::cache_ttl).
+//!
+//! Requests to a provider's own models endpoint, e.g. `GET
+//! /openai/v1/models`, are proxied to the provider as is.
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http::{HeaderValue, Method, header::CONTENT_TYPE};
+use indexmap::IndexSet;
+use serde::Serialize;
+
+use crate::{
+    app_state::AppState,
+    config::{DeploymentTarget, router::RouterConfig},
+    error::{api::ApiError, internal::InternalError},
+    middleware::model_access::ModelAccess,
+    types::{
+        extensions::AuthContext, org::OrgId, provider::InferenceProvider,
+        request::Request, response::Response, router::RouterId,
+    },
+};
+
+/// The path of the models endpoint, relative to `/ai` or `/router/{id}`.
+const MODELS_PATH: &str = "models";
+
+/// Model lists are cached per router and, in the cloud, per organization.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModelListKey {
+    router_id: Option<RouterId>,
+    org_id: Option<OrgId>,
+}
+
+/// Serialized model lists, see [`ModelListKey`].
+pub type ModelListCache = moka::future::Cache<ModelListKey, Bytes>;
+
+/// An OpenAI compatible model list.
+#[derive(Debug, Serialize)]
+struct ModelList {
+    object: &'static str,
+    data: Vec<Model>,
+}
+
+#[derive(Debug, Serialize)]
+struct Model {
+    id: String,
+    object: &'static str,
+    created: u64,
+    owned_by: InferenceProvider,
+}
+
+/// Whether the request, with the path relative to `/ai` or `/router/{id}`,
+/// lists the available models.
+#[must_use]
+pub fn is_model_list_request(req: &Request, path: &str) -> bool {
+    req.method() == Method::GET && path.trim_end_matches('/') == MODELS_PATH
+}
+
+/// The models available through the unified API, or through the router if
+/// one is given.
+pub async fn list_models(
+    app_state: AppState,
+    router: Option<(RouterId, Arc<RouterConfig>)>,
+    auth_ctx: Option<AuthContext>,
+) -> Result<Response, ApiError> {
+    let org_id = auth_ctx.map(|auth_ctx| auth_ctx.org_id);
+    let key = ModelListKey {
+        router_id: router.as_ref().map(|(id, _)| id.clone()),
+        org_id,
+    };
+    let body = if let Some(body) = app_state.0.model_lists.get(&key).await {
+        body
+    } else {
+        let router_config = router.map(|(_, router_config)| router_config);
+        let model_list =
+            model_list(&app_state, router_config.as_deref(), org_id).await;
+        let body = serde_json::to_vec(&model_list).map_err(|e| {
+            InternalError::Serialize {
+                ty: "ModelList",
+                error: e,
+            }
+        })?;
+        let body = Bytes::from(body);
+        app_state.0.model_lists.insert(key, body.clone()).await;
+        body
+    };
+
+    let mut response = Response::new(body.into());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(response)
+}
+
+async fn model_list(
+    app_state: &AppState,
+    router_config: Option<&RouterConfig>,
+    org_id: Option<OrgId>,
+) -> ModelList {
+    let config = app_state.config();
+    let providers: IndexSet<InferenceProvider> = match router_config {
+        Some(router_config) => router_config.load_balance.providers(),
+        None => config.providers.keys().cloned().collect(),
+    };
+    let access = router_config.and_then(ModelAccess::for_router);
+
+    let mut data = Vec::new();
+    for provider in providers {
+        if !is_enabled(app_state, &provider, org_id.as_ref()).await {
+            continue;
+        }
+        let Some(provider_config) = config.providers.get(&provider) else {
+            continue;
+        };
+        let models = provider_config.models.iter().filter(|model| {
+            access
+                .as_ref()
+                .is_none_or(|access| access.is_allowed(model))
+        });
+        data.extend(models.map(|model| Model {
+            id: format!("{provider}/{model}"),
+            object: "model",
+            created: 0,
+            owned_by: provider.clone(),
+        }));
+    }
+    ModelList {
+        object: "list",
+        data,
+    }
+}
+
+/// In the cloud, only providers the organization has a key for can be used.
+async fn is_enabled(
+    app_state: &AppState,
+    provider: &InferenceProvider,
+    org_id: Option<&OrgId>,
+) -> bool {
+    match app_state.config().deployment_target {
+        DeploymentTarget::Sidecar => true,
+        DeploymentTarget::Cloud => app_state
+            .0
+            .provider_keys
+            .get_provider_key(provider, org_id)
+            .await
+            .is_some(),
+    }
+}
@@ -0,0 +1,371 @@
+//! A balancer which always sends requests to the highest priority provider
+//! that is available, see
+//! [`BalanceConfigInner::Priority`](crate::config::balance::BalanceConfigInner::Priority).
+//!
+//! When the router has retries configured, a request which gets a transient
+//! error from a provider is sent to the next provider in the chain instead of
+//! being retried against the same provider. Providers which are backing off
+//! after a rate limited response are skipped until their `Retry-After` has
+//! elapsed.
+use std::{
+    fmt,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum_core::body::Body;
+use futures::future::{BoxFuture, TryFutureExt};
+use http::StatusCode;
+use http_body_util::BodyExt;
+use tower::{
+    Service, ServiceExt,
+    buffer::Buffer,
+    discover::Discover,
+    ready_cache::{ReadyCache, error::Failed},
+};
+use tracing::{debug, trace, warn};
+
+use crate::{
+    app_state::AppState,
+    config::router::RouterConfig,
+    discover::{
+        monitor::backoff::{ProviderBackoffs, retry_after_header},
+        provider::key::Key,
+    },
+    error::{api::ApiError, init::InitError, internal::InternalError},
+    router::{ready_set, strategy::provider_discovery},
+    types::{
+        extensions::PriorityFailover, provider::InferenceProvider,
+        request::Request, response::Response, router::RouterId,
+    },
+};
+
+const CHANNEL_CAPACITY: usize = 16;
+
+type BalanceFuture = BoxFuture<'static, Result<Response, tower::BoxError>>;
+
+type InnerService = Buffer<Request, BalanceFuture>;
+
+/// Unlike the other balancers, there is no randomness in the selection: a
+/// lower priority provider only receives requests while every provider before
+/// it is removed from discovery or not ready.
+pub struct PriorityBalance<D>
+where
+    D: Discover<Key = Key>,
+{
+    discover: D,
+    /// Providers in descending order of priority.
+    priorities: Vec<InferenceProvider>,
+    services: ReadyCache<Key, D::Service, Request>,
+    ready_index: Option<usize>,
+    router_id: RouterId,
+    backoffs: ProviderBackoffs,
+}
+
+impl<D> fmt::Debug for PriorityBalance<D>
+where
+    D: Discover<Key = Key> + fmt::Debug,
+    D::Service: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriorityBalance")
+            .field("discover", &self.discover)
+            .field("priorities", &self.priorities)
+            .field("services", &self.services)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> PriorityBalance<D>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request, Response = Response>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+{
+    pub fn new(
+        discover: D,
+        priorities: Vec<InferenceProvider>,
+        router_id: RouterId,
+        backoffs: ProviderBackoffs,
+    ) -> Self {
+        Self {
+            discover,
+            priorities,
+            services: ReadyCache::default(),
+            ready_index: None,
+            router_id,
+            backoffs,
+        }
+    }
+
+    /// The index of the ready service with the highest priority, skipping
+    /// any `excluded` providers.
+    fn priority_index(&self, excluded: &[InferenceProvider]) -> Option<usize> {
+        (0..self.services.ready_len())
+            .filter_map(|index| {
+                let (key, _service) = self.services.get_ready_index(index)?;
+                if excluded.contains(&key.provider) {
+                    return None;
+                }
+                let priority = self
+                    .priorities
+                    .iter()
+                    .position(|provider| *provider == key.provider)?;
+                Some((priority, index))
+            })
+            .min()
+            .map(|(_, index)| index)
+    }
+}
+
+impl<D> Service<Request> for PriorityBalance<D>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request, Response = Response>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+    <D::Service as Service<Request>>::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = tower::BoxError;
+    type Future = BalanceFuture;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        ready_set::update_pending_from_discover(
+            &mut self.discover,
+            &mut self.services,
+            cx,
+            |_| {},
+        )?;
+        ready_set::promote_pending_to_ready(&mut self.services, cx);
+
+        // always select again rather than reusing a previous selection, so
+        // that traffic moves back as soon as a higher priority provider is
+        // re-added
+        self.ready_index = None;
+        loop {
+            let Some(index) = self.priority_index(&[]) else {
+                // We have previously registered interest in updates from
+                // discover and pending services.
+                return Poll::Pending;
+            };
+            match self.services.check_ready_index(cx, index) {
+                Ok(true) => {
+                    self.ready_index = Some(index);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(false) => {
+                    trace!("priority service became unavailable");
+                }
+                Err(Failed(_, error)) => {
+                    debug!(%error, "endpoint failed");
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let ready_index = self.ready_index.take().expect("called before ready");
+        // prefer the highest priority provider which is not backing off and,
+        // when failing over, has not already failed this request
+        let attempted = request
+            .extensions()
+            .get::<PriorityFailover>()
+            .map(|failover| failover.attempted.as_slice())
+            .unwrap_or_default();
+        let backing_off = self.priorities.iter().filter(|provider| {
+            self.backoffs.remaining(&self.router_id, provider).is_some()
+        });
+        let excluded = attempted
+            .iter()
+            .chain(backing_off)
+            .cloned()
+            .collect::<Vec<_>>();
+        let index = if excluded.is_empty() {
+            ready_index
+        } else {
+            self.priority_index(&excluded)
+                .or_else(|| self.priority_index(attempted))
+                .unwrap_or(ready_index)
+        };
+        let provider = self
+            .services
+            .get_ready_index(index)
+            .map(|(key, _service)| key.provider.clone());
+        let future = self.services.call_ready_index(index, request);
+        Box::pin(async move {
+            let mut response = future.await.map_err(Into::into)?;
+            if let Some(provider) = provider
+                && response.extensions().get::<InferenceProvider>().is_none()
+            {
+                response.extensions_mut().insert(provider);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Wraps a [`PriorityBalance`] and fails over down the chain on transient
+/// errors.
+#[derive(Clone)]
+pub struct PriorityRouter {
+    inner: InnerService,
+    /// The maximum number of times a request may fail over to a lower
+    /// priority provider, derived from the router's retry config.
+    max_failovers: usize,
+    router_id: RouterId,
+    backoffs: ProviderBackoffs,
+}
+
+impl fmt::Debug for PriorityRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriorityRouter")
+            .field("max_failovers", &self.max_failovers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PriorityRouter {
+    pub async fn new(
+        app_state: AppState,
+        router_id: RouterId,
+        router_config: Arc<RouterConfig>,
+        priorities: Vec<InferenceProvider>,
+    ) -> Result<Self, InitError> {
+        let discovery =
+            provider_discovery(&app_state, &router_id, &router_config).await?;
+        let max_failovers = router_config
+            .retries
+            .as_ref()
+            .map_or(0, |retries| usize::from(retries.max_retries()))
+            .min(priorities.len().saturating_sub(1));
+        let backoffs = app_state.0.provider_backoffs.clone();
+        let balance = PriorityBalance::new(
+            discovery,
+            priorities,
+            router_id.clone(),
+            backoffs.clone(),
+        );
+        let inner = Buffer::new(balance, CHANNEL_CAPACITY);
+        Ok(Self {
+            inner,
+            max_failovers,
+            router_id,
+            backoffs,
+        })
+    }
+}
+
+impl Service<Request> for PriorityRouter {
+    type Response = Response;
+    type Error = ApiError;
+    type Future = BoxFuture<'static, Result<Response, ApiError>>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner
+            .poll_ready(cx)
+            .map_err(InternalError::PollReadyError)
+            .map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // see: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let inner = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, inner);
+        if self.max_failovers == 0 {
+            return Box::pin(inner.call(req).map_err(|e| {
+                ApiError::from(InternalError::LoadBalancerError(e))
+            }));
+        }
+        let max_failovers = self.max_failovers;
+        let router_id = self.router_id.clone();
+        let backoffs = self.backoffs.clone();
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+                .to_bytes();
+            let mut failover = PriorityFailover::default();
+            loop {
+                let mut req = Request::new(Body::from(body.clone()));
+                *req.method_mut() = parts.method.clone();
+                *req.uri_mut() = parts.uri.clone();
+                *req.headers_mut() = parts.headers.clone();
+                *req.extensions_mut() = parts.extensions.clone();
+                req.extensions_mut().insert(failover.clone());
+                if !failover.attempted.is_empty() {
+                    inner
+                        .ready()
+                        .await
+                        .map_err(InternalError::PollReadyError)?;
+                }
+                let response = inner
+                    .call(req)
+                    .await
+                    .map_err(InternalError::LoadBalancerError)?;
+                let status = response.status();
+                if failover.attempted.len() == max_failovers
+                    || !is_transient(status)
+                {
+                    return Ok(with_min_retry_after(
+                        response, &failover, &router_id, &backoffs,
+                    ));
+                }
+                let Some(provider) =
+                    response.extensions().get::<InferenceProvider>().cloned()
+                else {
+                    return Ok(response);
+                };
+                if failover.attempted.contains(&provider) {
+                    // every other provider in the chain is unavailable
+                    return Ok(with_min_retry_after(
+                        response, &failover, &router_id, &backoffs,
+                    ));
+                }
+                warn!(
+                    provider = %provider,
+                    status = %status,
+                    "transient error from provider, failing over",
+                );
+                failover.attempted.push(provider);
+            }
+        })
+    }
+}
+
+/// When every provider the request was sent to is rate limited, clients
+/// should retry once the first of them has stopped backing off.
+fn with_min_retry_after(
+    mut response: Response,
+    failover: &PriorityFailover,
+    router_id: &RouterId,
+    backoffs: &ProviderBackoffs,
+) -> Response {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return response;
+    }
+    let providers = failover
+        .attempted
+        .iter()
+        .chain(response.extensions().get::<InferenceProvider>());
+    if let Some(retry_after) = backoffs.min_remaining(router_id, providers) {
+        response
+            .headers_mut()
+            .insert(http::header::RETRY_AFTER, retry_after_header(retry_after));
+    }
+    response
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
@@ -0,0 +1,66 @@
+//! Keeps the [`ReadyCache`] of a balancer which picks a provider for each
+//! request itself, such as the priority, canary and consistent hash
+//! balancers, in sync with discovery.
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tower::{
+    Service,
+    discover::{Change, Discover},
+    ready_cache::ReadyCache,
+};
+use tracing::{debug, trace};
+
+use crate::{discover::provider::key::Key, types::request::Request};
+
+/// Applies every change from `discover` to `services`, calling `on_change`
+/// with each one so that balancers can keep their own state in sync.
+pub(super) fn update_pending_from_discover<D>(
+    discover: &mut D,
+    services: &mut ReadyCache<Key, D::Service, Request>,
+    cx: &mut Context<'_>,
+    mut on_change: impl FnMut(Change<&Key, ()>),
+) -> Result<(), tower::BoxError>
+where
+    D: Discover<Key = Key> + Unpin,
+    D::Error: Into<tower::BoxError>,
+    D::Service: Service<Request>,
+    <D::Service as Service<Request>>::Error: Into<tower::BoxError>,
+{
+    loop {
+        match Pin::new(&mut *discover).poll_discover(cx) {
+            Poll::Pending | Poll::Ready(None) => return Ok(()),
+            Poll::Ready(Some(change)) => match change.map_err(Into::into)? {
+                Change::Remove(key) => {
+                    trace!(key = ?key, "remove");
+                    on_change(Change::Remove(&key));
+                    services.evict(&key);
+                }
+                Change::Insert(key, service) => {
+                    trace!(key = ?key, "insert");
+                    on_change(Change::Insert(&key, ()));
+                    services.push(key, service);
+                }
+            },
+        }
+    }
+}
+
+pub(super) fn promote_pending_to_ready<S>(
+    services: &mut ReadyCache<Key, S, Request>,
+    cx: &mut Context<'_>,
+) where
+    S: Service<Request>,
+    S::Error: Into<tower::BoxError>,
+{
+    loop {
+        match services.poll_pending(cx) {
+            Poll::Ready(Ok(())) | Poll::Pending => break,
+            Poll::Ready(Err(error)) => {
+                debug!(%error, "dropping failed endpoint");
+            }
+        }
+    }
+}
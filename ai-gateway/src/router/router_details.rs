@@ -9,6 +9,7 @@ use http::uri::PathAndQuery;
 use regex::Regex;
 
 use crate::{
+    endpoints::ApiEndpoint,
     error::{
         api::ApiError, internal::InternalError,
         invalid_req::InvalidRequestError,
@@ -26,6 +27,7 @@ use crate::{
 /// Unified regex that matches all three routing patterns:
 /// - `/router/{id}[/path][?query]` - Router pattern
 /// - `/ai[/path][?query]` - Unified API pattern
+/// - `/admin[/path][?query]` - Admin pattern
 /// - `/{provider}[/path][?query]` - Direct proxy pattern
 const UNIFIED_URL_REGEX: &str =
     r"^/(?P<first_segment>[^/?]+)(?P<rest>/[^?]*)?(?P<query>\?.*)?$";
@@ -74,6 +76,9 @@ pub enum RouteType {
         provider: InferenceProvider,
         path: CompactString,
     },
+    Admin {
+        path: CompactString,
+    },
 }
 
 impl<S> RouterDetailsService<S> {
@@ -120,6 +125,10 @@ impl<S> RouterDetailsService<S> {
                 Ok(RouteType::UnifiedApi {
                     path: rest_path.trim_start_matches('/').into(),
                 })
+            } else if first_segment == "admin" {
+                Ok(RouteType::Admin {
+                    path: rest_path.trim_start_matches('/').into(),
+                })
             } else {
                 let Ok(provider) = InferenceProvider::from_str(first_segment);
                 Ok(RouteType::DirectProxy {
@@ -230,7 +239,7 @@ where
                     req.extensions_mut().insert(extracted_path_and_query);
                     req.extensions_mut().insert(RequestKind::UnifiedApi);
                 }
-                RouteType::DirectProxy { path, .. } => {
+                RouteType::DirectProxy { provider, path } => {
                     let extracted_path_and_query =
                         match extract_path_and_query(path, req.uri().query()) {
                             Ok(p) => p,
@@ -240,16 +249,23 @@ where
                         };
                     req.extensions_mut().insert(extracted_path_and_query);
                     req.extensions_mut().insert(RequestKind::DirectProxy);
+                    if let Some(api_endpoint) =
+                        ApiEndpoint::for_direct_proxy(provider, path)
+                    {
+                        req.extensions_mut().insert(api_endpoint);
+                    }
                     // for the passthrough endpoints, we don't want to
-                    // collect/deserialize the request
-                    // body, and thus we must assume the request is not a stream
-                    // request and cannot support streaming.
+                    // collect/deserialize the request body here, so the
+                    // dispatcher detects streams for the endpoints we know
                     let mapper_ctx = MapperContext {
                         is_stream: false,
                         model: None,
                     };
                     req.extensions_mut().insert(mapper_ctx);
                 }
+                RouteType::Admin { .. } => {
+                    req.extensions_mut().insert(RequestKind::Admin);
+                }
             }
             req.extensions_mut().insert(route_type);
         }
@@ -7,6 +7,7 @@ use std::{
 };
 
 use axum_core::response::IntoResponse;
+use futures::future::BoxFuture;
 use http::uri::PathAndQuery;
 use pin_project_lite::pin_project;
 use rustc_hash::FxHashMap as HashMap;
@@ -21,10 +22,16 @@ use crate::{
         invalid_req::InvalidRequestError,
     },
     middleware::{
-        cache::CacheLayer, prompts::PromptLayer, rate_limit, request_context,
+        body_limit, cache::CacheLayer, concurrency_limit, evaluation,
+        json_repair, load_shed, model_access, model_alias,
+        prompts::PromptLayer, provider_override, rate_limit, request_context,
+        routing_rules, shadow, stream_buffer, stream_limit, target_model,
+        transform,
     },
-    router::{meta::MIDDLEWARE_BUFFER_SIZE, strategy::RoutingStrategyService},
-    types::router::RouterId,
+    router::{
+        meta::MIDDLEWARE_BUFFER_SIZE, models, strategy::RoutingStrategyService,
+    },
+    types::{extensions::AuthContext, router::RouterId},
     utils::handle_error::ErrorHandlerLayer,
 };
 
@@ -39,6 +46,9 @@ type InnerRouterService = BoxCloneService<
 #[derive(Debug)]
 pub struct Router {
     inner: HashMap<EndpointType, InnerRouterService>,
+    id: RouterId,
+    router_config: Arc<RouterConfig>,
+    app_state: AppState,
 }
 
 impl Router {
@@ -50,16 +60,33 @@ impl Router {
         router_config.validate()?;
 
         let mut inner = HashMap::default();
-        let rl_layer = rate_limit::Layer::per_router(
-            &app_state,
-            id.clone(),
-            &router_config,
-        )
-        .await?;
+        let rl_layer =
+            rate_limit::Layer::per_router(&app_state, &id, &router_config)?;
+        let body_limit_layer = body_limit::Layer::for_router(&router_config);
         let prompt_layer = PromptLayer::new(&app_state)?;
+        let transform_layer = transform::Layer::for_router(&router_config)?;
         let cache_layer = CacheLayer::for_router(&app_state, &router_config)?;
+        let model_alias_layer = model_alias::Layer::for_router(&router_config);
+        let routing_rules_layer =
+            routing_rules::Layer::for_router(&router_config);
+        let model_access_layer =
+            model_access::Layer::for_router(&router_config);
+        let concurrency_limit_layer =
+            concurrency_limit::Layer::for_router(&router_config);
         let request_context_layer =
             request_context::Layer::for_router(router_config.clone());
+        let stream_limit_layer =
+            stream_limit::Layer::for_router(&router_config);
+        let stream_buffer_layer =
+            stream_buffer::Layer::for_router(&router_config);
+        let json_repair_layer = json_repair::Layer::for_router(&router_config);
+        let shadow_layer =
+            shadow::Layer::for_router(&app_state, &id, &router_config).await?;
+        let evaluation_layer =
+            evaluation::Layer::for_router(&app_state, &id, &router_config)
+                .await?;
+        // the rebuilt balancers start with every provider
+        app_state.0.provider_pools.reset(&id);
         for (endpoint_type, balance_config) in
             router_config.load_balance.as_ref()
         {
@@ -70,15 +97,52 @@ impl Router {
                 balance_config,
             )
             .await?;
+            // created after the routing strategy, which sets up the router's
+            // rate limit channels that dispatchers report to
+            let provider_override_layer =
+                provider_override::Layer::for_balance_config(
+                    &app_state,
+                    &id,
+                    &router_config,
+                    balance_config,
+                )
+                .await?;
+            let load_shed_layer = load_shed::Layer::new(
+                app_state.0.provider_concurrency_limits.clone(),
+                balance_config.providers(),
+            );
             let service_stack = ServiceBuilder::new()
                 .layer(ErrorHandlerLayer::new(app_state.clone()))
+                .layer(body_limit_layer.clone())
                 .layer(prompt_layer.clone())
+                // transforms apply before caching so that cache keys reflect
+                // the transformed request
+                .layer(transform_layer.clone())
+                // aliases are rewritten before the model is checked, cached
+                // or mapped
+                .layer(model_alias_layer.clone())
+                // routing rules see the model the alias resolved to
+                .layer(routing_rules_layer.clone())
+                // and so does the model chosen by model weighted routers
+                .layer(target_model::Layer::for_balance_config(balance_config))
+                // disallowed models never reach the cache or a provider
+                .layer(model_access_layer.clone())
                 .layer(cache_layer.clone())
+                // cache hits are served without taking a slot
+                .layer(concurrency_limit_layer.clone())
                 .layer(ErrorHandlerLayer::new(app_state.clone()))
                 .layer(rl_layer.clone())
+                .layer(stream_limit_layer.clone())
+                .layer(stream_buffer_layer)
+                .layer(json_repair_layer)
                 .map_err(|e| ApiError::from(InternalError::BufferError(e)))
                 .layer(buffer::BufferLayer::new(MIDDLEWARE_BUFFER_SIZE))
                 .layer(request_context_layer.clone())
+                .layer(shadow_layer.clone())
+                .layer(evaluation_layer.clone())
+                // pinned and routed requests bypass the balancer entirely
+                .layer(provider_override_layer)
+                .layer(load_shed_layer)
                 .service(routing_strategy);
 
             inner.insert(*endpoint_type, BoxCloneService::new(service_stack));
@@ -86,7 +150,12 @@ impl Router {
 
         tracing::info!(id = %id, "router created");
 
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            id,
+            router_config,
+            app_state,
+        })
     }
 }
 
@@ -131,9 +200,26 @@ impl tower::Service<crate::types::request::Request> for Router {
             };
         };
 
+        if models::is_model_list_request(&req, extracted_path_and_query.path())
+        {
+            let auth_ctx = req.extensions().get::<AuthContext>().cloned();
+            return ResponseFuture::Models {
+                future: Box::pin(models::list_models(
+                    self.app_state.clone(),
+                    Some((self.id.clone(), self.router_config.clone())),
+                    auth_ctx,
+                )),
+            };
+        }
+
         let api_endpoint = ApiEndpoint::new(extracted_path_and_query.path());
         if let Some(api_endpoint) = api_endpoint {
             let endpoint_type = api_endpoint.endpoint_type();
+            let endpoint_type = if self.inner.contains_key(&endpoint_type) {
+                endpoint_type
+            } else {
+                endpoint_type.fallback().unwrap_or(endpoint_type)
+            };
             if let Some(balancer) = self.inner.get_mut(&endpoint_type) {
                 req.extensions_mut().insert(api_endpoint);
                 ResponseFuture::Inner {
@@ -173,6 +259,10 @@ pin_project! {
             #[pin]
             future: <InnerRouterService as tower::Service<crate::types::request::Request>>::Future,
         },
+        Models {
+            #[pin]
+            future: BoxFuture<'static, Result<crate::types::response::Response, ApiError>>,
+        },
     }
 }
 
@@ -191,6 +281,10 @@ impl Future for ResponseFuture {
                     Err(e) => match e {},
                 }
             }
+            ResponseFutureProj::Models { future } => {
+                Poll::Ready(Ok(futures::ready!(future.poll(cx))
+                    .unwrap_or_else(IntoResponse::into_response)))
+            }
         }
     }
 }
@@ -1,24 +1,43 @@
 use std::{
+    hash::{Hash, Hasher},
+    str::FromStr,
     sync::Arc,
     task::{Context, Poll},
 };
 
 use futures::{Future, ready};
+use http::HeaderName;
 use pin_project_lite::pin_project;
-use tokio::sync::mpsc::channel;
-use tower::{Service, balance::p2c::Balance, load::PeakEwmaDiscover};
+use rust_decimal::Decimal;
+use tokio::sync::mpsc::{Receiver, channel};
+use tower::{
+    Service, balance::p2c::Balance, discover::Change, load::PeakEwmaDiscover,
+};
 use weighted_balance::{balance::WeightedBalance, weight::WeightedDiscover};
 
 use crate::{
     app_state::AppState,
-    config::{balance::BalanceConfigInner, router::RouterConfig},
+    config::{
+        balance::BalanceConfigInner, router::RouterConfig,
+        validation::RouterValidationError,
+    },
     discover::{
         dispatcher::{DispatcherDiscovery, factory::DispatcherDiscoverFactory},
         model, provider,
     },
+    dispatcher::DispatcherService,
     error::{api::ApiError, init::InitError, internal::InternalError},
-    router::latency::LatencyRouter,
-    types::{request::Request, response::Response, router::RouterId},
+    router::{
+        canary::CanaryBalance, consistent_hash::ConsistentHashRouter,
+        latency::LatencyRouter, priority::PriorityRouter,
+    },
+    types::{
+        extensions::{AuthContext, TargetModel},
+        provider::InferenceProvider,
+        request::Request,
+        response::Response,
+        router::RouterId,
+    },
 };
 
 const CHANNEL_CAPACITY: usize = 16;
@@ -41,7 +60,9 @@ pub enum RoutingStrategyService {
     /// Strategy:
     /// 1. receive request
     /// 2. according to configured weighted distribution, randomly sample a
-    ///    single provider from the set of providers.
+    ///    single provider from the set of providers. if `sticky` is enabled,
+    ///    the provider is instead chosen consistently for the requesting user.
+    ///    if a `seed` is configured, the sampling is reproducible.
     /// 3. if the provider does not have requested model, map it to a model
     ///    offered by the target provider.
     /// 4. send request
@@ -56,7 +77,9 @@ pub enum RoutingStrategyService {
     /// Strategy:
     /// 1. receive request
     /// 2. according to configured weighted distribution, randomly sample a
-    ///    single (provider, model) from the set of (provider, model) pairs.
+    ///    single (provider, model) from the set of (provider, model) pairs. the
+    ///    model is sampled before the request is cached, see [`TargetModel`],
+    ///    and is only resampled here if it isn't available.
     /// 3. send request
     WeightedModel(
         WeightedBalance<
@@ -72,6 +95,36 @@ pub enum RoutingStrategyService {
     /// 3. pick the lowest latency provider that serves the requested model
     /// 4. send request
     ModelLatency(LatencyRouter),
+    /// Strategy:
+    /// 1. receive request
+    /// 2. pick the first provider in the configured order which has not been
+    ///    removed for being unhealthy or rate limited
+    /// 3. if the provider does not have requested model, map it to a model
+    ///    offered by the target provider.
+    /// 4. send request
+    /// 5. if retries are configured and the provider returns a transient error,
+    ///    send the request to the next provider in the order
+    Priority(PriorityRouter),
+    /// Strategy:
+    /// 1. receive request
+    /// 2. hash the configured header into a bucket, or pick a random bucket if
+    ///    the header is missing
+    /// 3. send the request to the canary provider if the bucket falls under the
+    ///    configured percentage, otherwise to the stable provider
+    /// 4. if the provider does not have requested model, map it to a model
+    ///    offered by the target provider.
+    /// 5. send request
+    Canary(CanaryBalance<DispatcherDiscovery<provider::key::Key>>),
+    /// Strategy:
+    /// 1. receive request
+    /// 2. extract the configured key from a header or the request body and hash
+    ///    it onto a ring of providers
+    /// 3. pick the first available provider clockwise on the ring, or a
+    ///    weighted random provider if the key couldn't be extracted
+    /// 4. if the provider does not have requested model, map it to a model
+    ///    offered by the target provider.
+    /// 5. send request
+    ConsistentHash(ConsistentHashRouter),
 }
 
 impl RoutingStrategyService {
@@ -82,9 +135,15 @@ impl RoutingStrategyService {
         balance_config: &BalanceConfigInner,
     ) -> Result<RoutingStrategyService, InitError> {
         match balance_config {
-            BalanceConfigInner::ProviderWeighted { .. } => {
-                Self::provider_weighted(app_state, router_id, router_config)
-                    .await
+            BalanceConfigInner::ProviderWeighted { sticky, seed, .. } => {
+                Self::provider_weighted(
+                    app_state,
+                    router_id,
+                    router_config,
+                    *sticky,
+                    *seed,
+                )
+                .await
             }
             BalanceConfigInner::BalancedLatency { .. } => {
                 Self::provider_latency(app_state, router_id, router_config)
@@ -98,6 +157,51 @@ impl RoutingStrategyService {
                     .await
                     .map(Self::ModelLatency)
             }
+            BalanceConfigInner::Priority { providers } => {
+                tracing::debug!("creating priority routing strategy");
+                PriorityRouter::new(
+                    app_state,
+                    router_id,
+                    router_config,
+                    providers.iter().cloned().collect(),
+                )
+                .await
+                .map(Self::Priority)
+            }
+            BalanceConfigInner::Canary {
+                stable,
+                canary,
+                percentage,
+                header,
+            } => {
+                let header = HeaderName::from_str(header).map_err(|_| {
+                    RouterValidationError::InvalidCanaryHeader {
+                        name: header.clone(),
+                    }
+                })?;
+                Self::canary(
+                    app_state,
+                    router_id,
+                    router_config,
+                    stable.clone(),
+                    canary.clone(),
+                    *percentage,
+                    header,
+                )
+                .await
+            }
+            BalanceConfigInner::ConsistentHash { providers, key } => {
+                tracing::debug!("creating consistent hash routing strategy");
+                ConsistentHashRouter::new(
+                    app_state,
+                    router_id,
+                    router_config,
+                    &providers.iter().cloned().collect::<Vec<_>>(),
+                    key,
+                )
+                .await
+                .map(Self::ConsistentHash)
+            }
         }
     }
 
@@ -105,6 +209,8 @@ impl RoutingStrategyService {
         app_state: AppState,
         router_id: RouterId,
         router_config: Arc<RouterConfig>,
+        sticky: bool,
+        seed: Option<u64>,
     ) -> Result<RoutingStrategyService, InitError> {
         tracing::debug!("creating provider weighted routing strategy");
         let (change_tx, change_rx) = channel(CHANNEL_CAPACITY);
@@ -136,7 +242,13 @@ impl RoutingStrategyService {
             .await;
         let mut balance_factory =
             weighted_balance::balance::make::MakeBalance::new(discover_factory);
-        let balance = balance_factory.call(change_rx).await?;
+        let mut balance = balance_factory.call(change_rx).await?;
+        if sticky {
+            balance = balance.with_sticky_key(user_sticky_key);
+        }
+        if let Some(seed) = seed {
+            balance = balance.with_seed(seed);
+        }
         let provider_balancer =
             RoutingStrategyService::WeightedProvider(balance);
 
@@ -178,7 +290,10 @@ impl RoutingStrategyService {
             .await;
         let mut balance_factory =
             weighted_balance::balance::make::MakeBalance::new(discover_factory);
-        let balance = balance_factory.call(change_rx).await?;
+        let balance = balance_factory
+            .call(change_rx)
+            .await?
+            .with_preference(is_target_model);
         let provider_balancer = RoutingStrategyService::WeightedModel(balance);
 
         Ok(provider_balancer)
@@ -190,33 +305,10 @@ impl RoutingStrategyService {
         router_config: Arc<RouterConfig>,
     ) -> Result<RoutingStrategyService, InitError> {
         tracing::debug!("creating provider latency routing strategy");
-        let (change_tx, change_rx) = channel(CHANNEL_CAPACITY);
-        let (rate_limit_tx, rate_limit_rx) = channel(CHANNEL_CAPACITY);
-        let discover_factory = DispatcherDiscoverFactory::new(
-            app_state.clone(),
-            router_id.clone(),
-            router_config.clone(),
-        );
-        app_state
-            .add_provider_latency_router_health_monitor(
-                router_id.clone(),
-                router_config.clone(),
-                change_tx.clone(),
-            )
-            .await;
-        app_state
-            .add_rate_limit_tx(router_id.clone(), rate_limit_tx)
-            .await;
-        app_state
-            .add_rate_limit_rx(router_id.clone(), rate_limit_rx)
-            .await;
-        app_state
-            .add_provider_latency_router_rate_limit_monitor(
-                router_id.clone(),
-                router_config,
-                change_tx,
-            )
-            .await;
+        let change_rx =
+            provider_monitors(&app_state, &router_id, &router_config).await;
+        let discover_factory =
+            DispatcherDiscoverFactory::new(app_state, router_id, router_config);
         let mut balance_factory =
             tower::balance::p2c::MakeBalance::new(discover_factory);
         let balance = balance_factory.call(change_rx).await?;
@@ -225,6 +317,94 @@ impl RoutingStrategyService {
 
         Ok(provider_balancer)
     }
+
+    async fn canary(
+        app_state: AppState,
+        router_id: RouterId,
+        router_config: Arc<RouterConfig>,
+        stable: InferenceProvider,
+        canary: InferenceProvider,
+        percentage: Decimal,
+        header: HeaderName,
+    ) -> Result<RoutingStrategyService, InitError> {
+        tracing::debug!("creating canary routing strategy");
+        let discovery =
+            provider_discovery(&app_state, &router_id, &router_config).await?;
+        let balance =
+            CanaryBalance::new(discovery, stable, canary, percentage, header);
+
+        Ok(RoutingStrategyService::Canary(balance))
+    }
+}
+
+/// Registers the health and rate limit monitors which remove and re-add the
+/// providers of a router keyed by provider, returning the channel they send
+/// their changes on.
+async fn provider_monitors(
+    app_state: &AppState,
+    router_id: &RouterId,
+    router_config: &Arc<RouterConfig>,
+) -> Receiver<Change<provider::key::Key, DispatcherService>> {
+    let (change_tx, change_rx) = channel(CHANNEL_CAPACITY);
+    let (rate_limit_tx, rate_limit_rx) = channel(CHANNEL_CAPACITY);
+    app_state
+        .add_provider_latency_router_health_monitor(
+            router_id.clone(),
+            router_config.clone(),
+            change_tx.clone(),
+        )
+        .await;
+    app_state
+        .add_rate_limit_tx(router_id.clone(), rate_limit_tx)
+        .await;
+    app_state
+        .add_rate_limit_rx(router_id.clone(), rate_limit_rx)
+        .await;
+    app_state
+        .add_provider_latency_router_rate_limit_monitor(
+            router_id.clone(),
+            router_config.clone(),
+            change_tx,
+        )
+        .await;
+    change_rx
+}
+
+/// Discovers the providers of a router whose balancer picks a provider for
+/// each request itself, see [`ready_set`](super::ready_set).
+///
+/// Providers are removed and re-added by the same monitors as the provider
+/// latency strategy, since both are keyed by provider.
+pub(super) async fn provider_discovery(
+    app_state: &AppState,
+    router_id: &RouterId,
+    router_config: &Arc<RouterConfig>,
+) -> Result<DispatcherDiscovery<provider::key::Key>, InitError> {
+    let change_rx =
+        provider_monitors(app_state, router_id, router_config).await;
+    DispatcherDiscovery::new(app_state, router_id, router_config, change_rx)
+        .await
+}
+
+/// Pins requests to a provider by the authenticated user, if any.
+fn user_sticky_key(req: &Request) -> Option<u64> {
+    let auth_context = req.extensions().get::<AuthContext>()?;
+    // a fixed hash function and seed, so that users keep their provider
+    // across restarts, toolchain upgrades and replicas
+    let mut hasher = seahash::SeaHasher::new();
+    auth_context.user_id.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Sends requests to the model chosen for them before they were cached, if
+/// it's available.
+fn is_target_model(
+    req: &Request,
+    key: &model::weighted_key::WeightedKey,
+) -> bool {
+    req.extensions()
+        .get::<TargetModel>()
+        .is_some_and(|TargetModel(model)| *model == key.model_id)
 }
 
 impl tower::Service<Request> for RoutingStrategyService {
@@ -247,14 +427,24 @@ impl tower::Service<Request> for RoutingStrategyService {
             RoutingStrategyService::WeightedModel(inner) => {
                 inner.poll_ready(cx)
             }
+            RoutingStrategyService::Canary(inner) => inner.poll_ready(cx),
             RoutingStrategyService::ModelLatency(inner) => {
                 return inner.poll_ready(cx);
             }
+            RoutingStrategyService::Priority(inner) => {
+                return inner.poll_ready(cx);
+            }
+            RoutingStrategyService::ConsistentHash(inner) => {
+                return inner.poll_ready(cx);
+            }
         }
         .map_err(InternalError::PollReadyError)
         .map_err(Into::into)
     }
 
+    /// Most strategies select a provider within `call`, so the selected
+    /// dispatcher's span is a child of this one.
+    #[tracing::instrument(name = "balancer", skip_all)]
     fn call(&mut self, req: Request) -> Self::Future {
         match self {
             RoutingStrategyService::ProviderLatencyPeakEwmaP2C(inner) => {
@@ -277,6 +467,19 @@ impl tower::Service<Request> for RoutingStrategyService {
                     future: inner.call(req),
                 }
             }
+            RoutingStrategyService::Priority(inner) => {
+                ResponseFuture::Priority {
+                    future: inner.call(req),
+                }
+            }
+            RoutingStrategyService::Canary(inner) => ResponseFuture::Canary {
+                future: inner.call(req),
+            },
+            RoutingStrategyService::ConsistentHash(inner) => {
+                ResponseFuture::ConsistentHash {
+                    future: inner.call(req),
+                }
+            }
         }
     }
 }
@@ -312,6 +515,22 @@ pin_project! {
             #[pin]
             future: <LatencyRouter as tower::Service<Request>>::Future,
         },
+        Priority {
+            #[pin]
+            future: <PriorityRouter as tower::Service<Request>>::Future,
+        },
+        Canary {
+            #[pin]
+            future: <
+                CanaryBalance<DispatcherDiscovery<provider::key::Key>> as tower::Service<
+                    Request,
+                >
+            >::Future,
+        },
+        ConsistentHash {
+            #[pin]
+            future: <ConsistentHashRouter as tower::Service<Request>>::Future,
+        },
     }
 }
 
@@ -330,7 +549,8 @@ impl Future for ResponseFuture {
                     .map_err(Into::into)
             )),
             EnumProj::ProviderWeighted { future }
-            | EnumProj::ModelWeighted { future } => Poll::Ready(ready!(
+            | EnumProj::ModelWeighted { future }
+            | EnumProj::Canary { future } => Poll::Ready(ready!(
                 future
                     .poll(cx)
                     .map_err(InternalError::LoadBalancerError)
@@ -339,6 +559,10 @@ impl Future for ResponseFuture {
             EnumProj::ModelLatency { future } => {
                 Poll::Ready(ready!(future.poll(cx)))
             }
+            EnumProj::Priority { future }
+            | EnumProj::ConsistentHash { future } => {
+                Poll::Ready(ready!(future.poll(cx)))
+            }
         }
     }
 }
@@ -0,0 +1,88 @@
+//! Serves `POST /ai/tokenize`, which counts the input tokens of a prompt
+//! without sending it, see [`crate::tokenizer`].
+//!
+//! The request is `{ "model": "{provider}/{model}" }` with either chat
+//! `messages` in the OpenAI format or raw `text`, and the response is
+//! `{ "model": ..., "input_tokens": ... }`.
+use bytes::Bytes;
+use http::{HeaderValue, Method, header::CONTENT_TYPE};
+use http_body_util::BodyExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    error::{
+        api::ApiError, internal::InternalError,
+        invalid_req::InvalidRequestError,
+    },
+    tokenizer::{self, TokenizeInput},
+    types::{
+        extensions::AuthContext, model_id::ModelId, request::Request,
+        response::Response,
+    },
+};
+
+/// The path of the tokenize endpoint, relative to `/ai`.
+const TOKENIZE_PATH: &str = "tokenize";
+
+#[derive(Debug, Deserialize)]
+struct TokenizeRequest {
+    model: String,
+    #[serde(flatten)]
+    input: TokenizeInput,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenizeResponse {
+    model: String,
+    input_tokens: usize,
+}
+
+/// Whether the request, with the path relative to `/ai`, counts tokens.
+#[must_use]
+pub fn is_tokenize_request(req: &Request, path: &str) -> bool {
+    req.method() == Method::POST && path.trim_end_matches('/') == TOKENIZE_PATH
+}
+
+pub async fn tokenize(
+    app_state: AppState,
+    req: Request,
+) -> Result<Response, ApiError> {
+    let auth_ctx = req.extensions().get::<AuthContext>().cloned();
+    let body = req
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| InternalError::RequestBodyError(Box::new(e)))?
+        .to_bytes();
+    let request: TokenizeRequest = serde_json::from_slice(&body)
+        .map_err(InvalidRequestError::InvalidRequestBody)?;
+    let model = request.model.parse::<ModelId>().map_err(|_| {
+        InvalidRequestError::UnsupportedTokenizerModel {
+            model: request.model.clone(),
+            supported: tokenizer::supported_model_families().join(", "),
+        }
+    })?;
+
+    let input_tokens = tokenizer::count_input_tokens(
+        &app_state,
+        &model,
+        &request.input,
+        auth_ctx.as_ref(),
+    )
+    .await?;
+    let body = serde_json::to_vec(&TokenizeResponse {
+        model: request.model,
+        input_tokens,
+    })
+    .map_err(|e| InternalError::Serialize {
+        ty: "TokenizeResponse",
+        error: e,
+    })?;
+
+    let mut response = Response::new(Bytes::from(body).into());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(response)
+}
@@ -74,6 +74,7 @@ pin_project! {
         DetermineProvider {
             collected_body: Option<Bytes>,
             parts: Option<http::request::Parts>,
+            unified_api: UnifiedApi,
         },
         InitProxy {
             request: Option<Request>,
@@ -110,8 +111,37 @@ impl ResponseFuture {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum UnifiedApi {
     ChatCompletions(),
+    Responses(),
+    Embeddings(),
+    ImageGenerations(),
+}
+
+impl UnifiedApi {
+    /// The model requested, which determines the provider.
+    fn model(self, body: &[u8]) -> Result<String, InvalidRequestError> {
+        match self {
+            Self::ChatCompletions() => serde_json::from_slice::<
+                async_openai::types::CreateChatCompletionRequest,
+            >(body)
+            .map(|request| request.model),
+            Self::Responses() => serde_json::from_slice::<
+                crate::endpoints::openai::responses::CreateResponseRequest,
+            >(body)
+            .map(|request| request.model),
+            Self::Embeddings() => serde_json::from_slice::<
+                async_openai::types::CreateEmbeddingRequest,
+            >(body)
+            .map(|request| request.model),
+            Self::ImageGenerations() => serde_json::from_slice::<
+                crate::endpoints::openai::images::CreateImageRequest,
+            >(body)
+            .map(|request| request.model),
+        }
+        .map_err(InvalidRequestError::InvalidRequestBody)
+    }
 }
 
 impl TryFrom<&str> for UnifiedApi {
@@ -119,6 +149,9 @@ impl TryFrom<&str> for UnifiedApi {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "chat/completions" => Ok(Self::ChatCompletions()),
+            "responses" => Ok(Self::Responses()),
+            "embeddings" => Ok(Self::Embeddings()),
+            "images/generations" => Ok(Self::ImageGenerations()),
             _ => {
                 Err(InvalidRequestError::UnsupportedEndpoint(value.to_string()))
             }
@@ -176,27 +209,40 @@ impl Future for ResponseFuture {
                                 OpenAI::chat_completions(),
                             ));
                         }
+                        UnifiedApi::Responses() => {
+                            parts.extensions.insert(ApiEndpoint::OpenAI(
+                                OpenAI::responses(),
+                            ));
+                        }
+                        UnifiedApi::Embeddings() => {
+                            parts.extensions.insert(ApiEndpoint::OpenAI(
+                                OpenAI::embeddings(),
+                            ));
+                        }
+                        UnifiedApi::ImageGenerations() => {
+                            parts.extensions.insert(ApiEndpoint::OpenAI(
+                                OpenAI::image_generations(),
+                            ));
+                        }
                     }
 
                     this.state.set(State::DetermineProvider {
                         collected_body: Some(collected.to_bytes()),
                         parts: Some(parts),
+                        unified_api,
                     });
                 }
                 StateProj::DetermineProvider {
                     collected_body,
                     parts,
+                    unified_api,
                 } => {
                     let body = collected_body
                         .take()
                         .expect("future polled after completion");
-                    let deserialized_body = serde_json::from_slice::<
-                        async_openai::types::CreateChatCompletionRequest,
-                    >(&body)
-                    .map_err(InvalidRequestError::InvalidRequestBody)?;
-                    let source_model =
-                        ModelId::from_str(&deserialized_body.model)
-                            .map_err(InternalError::MapperError)?;
+                    let model = unified_api.model(&body)?;
+                    let source_model = ModelId::from_str(&model)
+                        .map_err(InternalError::MapperError)?;
                     let mut parts =
                         parts.take().expect("future polled after completion");
                     let provider = match source_model {
@@ -192,7 +192,8 @@ impl DatabaseListener {
                 } => {
                     debug!("Router configuration updated");
                     match op {
-                        Op::Insert => {
+                        // an update rebuilds the router in place
+                        Op::Insert | Op::Update => {
                             let organization_id = OrgId::try_from(organization_id.as_str()).map_err(|e| {
                                 error!(error = %e, "failed to convert organization id to OrgId");
                                 RuntimeError::Internal(crate::error::internal::InternalError::Internal)
@@ -211,8 +212,8 @@ impl DatabaseListener {
                             debug!("router removed");
                             Ok(())
                         }
-                        _ => {
-                            debug!("skipping router insert");
+                        Op::Truncate => {
+                            debug!("skipping router truncate");
                             Ok(())
                         }
                     }
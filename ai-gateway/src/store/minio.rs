@@ -12,10 +12,14 @@ use uuid::Uuid;
 
 use crate::{
     app_state::AppState,
-    config::minio::Config,
+    config::{DeploymentTarget, minio::Config},
     error::{init::InitError, logger::LoggerError, prompts::PromptError},
     logger::service::JawnClient,
-    types::{extensions::AuthContext, logger::S3Log, response::JawnResponse},
+    types::{
+        extensions::AuthContext,
+        logger::{BodyLocation, S3Log},
+        response::JawnResponse,
+    },
 };
 
 const DEFAULT_MINIO_TIMEOUT: Duration = Duration::from_secs(10);
@@ -113,6 +117,19 @@ impl<'a> MinioClient<'a> {
         Self::SignedByJawn(jawn_client)
     }
 
+    /// The client for the app's deployment target.
+    #[must_use]
+    pub fn for_deployment_target(app_state: &'a AppState) -> Self {
+        match app_state.config().deployment_target {
+            DeploymentTarget::Cloud => Self::cloud(&app_state.0.minio),
+            DeploymentTarget::Sidecar => {
+                Self::sidecar(&app_state.0.jawn_http_client)
+            }
+        }
+    }
+
+    /// Uploads the request and response bodies, returning where they are if
+    /// they were uploaded in a batch.
     #[tracing::instrument(skip_all)]
     pub async fn log_bodies(
         &self,
@@ -121,8 +138,43 @@ impl<'a> MinioClient<'a> {
         request_id: Uuid,
         request_body: Bytes,
         response_body: Bytes,
+    ) -> Result<Option<BodyLocation>, LoggerError> {
+        // bodies can be several MB, e.g. generated images, so they're
+        // converted without copying where possible and serialized once
+        let request_body = String::from_utf8(Vec::from(request_body))?;
+        let response_body = String::from_utf8(Vec::from(response_body))?;
+        let s3_log =
+            serde_json::to_vec(&S3Log::new(request_body, response_body))
+                .map_err(|e| {
+                    tracing::error!(error = %e, "failed to serialize s3 log");
+                    LoggerError::InvalidLogMessage
+                })?;
+
+        if let Some(batcher) = &app_state.0.body_batcher {
+            let location = batcher.add(app_state, auth_ctx, s3_log).await?;
+            return Ok(Some(location));
+        }
+        self.upload(
+            app_state,
+            auth_ctx,
+            request_id,
+            s3_log,
+            mime::APPLICATION_JSON.essence_str(),
+        )
+        .await?;
+        Ok(None)
+    }
+
+    /// Uploads an object in place of the bodies of request `request_id`.
+    pub(crate) async fn upload(
+        &self,
+        app_state: &AppState,
+        auth_ctx: &AuthContext,
+        request_id: Uuid,
+        body: Vec<u8>,
+        content_type: &str,
     ) -> Result<(), LoggerError> {
-        let (signed_url, s3_log) = match self {
+        let signed_url = match self {
             Self::SelfSigned(minio) => {
                 let object_path = format!(
                     "organizations/{}/requests/{}/raw_request_response_body",
@@ -131,12 +183,8 @@ impl<'a> MinioClient<'a> {
                 );
                 let action = minio.put_object(&object_path);
                 let signed_url = action.sign(PUT_OBJECT_SIGN_DURATION);
-                let request_body = String::from_utf8(request_body.to_vec())?;
-                let response_body = String::from_utf8(response_body.to_vec())?;
-
                 tracing::trace!("got signed url for self hosted minio");
-                let s3_log = S3Log::new(request_body, response_body);
-                (signed_url, s3_log)
+                signed_url
             }
             Self::SignedByJawn(client) => {
                 let signed_request_url =
@@ -145,19 +193,11 @@ impl<'a> MinioClient<'a> {
                         .helicone
                         .base_url
                         .join("/v1/router/control-plane/sign-s3-url")?;
-                let request_body = String::from_utf8(request_body.to_vec())?;
-                let response_body = String::from_utf8(response_body.to_vec())?;
-
-                let s3_log = S3Log::new(request_body, response_body);
-                let bytes = serde_json::to_vec(&s3_log).map_err(|e| {
-                    tracing::error!(error = %e, "failed to serialize s3 log");
-                    LoggerError::InvalidLogMessage
-                })?;
 
                 let signed_url = client
                   .request_client
                   .post(signed_request_url)
-                  .json(&SignedUrlRequest { request_id, payload_size: bytes.len() })
+                  .json(&SignedUrlRequest { request_id, payload_size: body.len() })
                   .header(
                     "authorization",
                     format!("Bearer {}", auth_ctx.api_key.expose()),
@@ -183,7 +223,7 @@ impl<'a> MinioClient<'a> {
                 })?;
                 tracing::trace!("got signed url for sidecar");
 
-                (signed_url.url, s3_log)
+                signed_url.url
             }
         };
 
@@ -192,7 +232,8 @@ impl<'a> MinioClient<'a> {
             .minio
             .client
             .put(signed_url)
-            .json(&s3_log)
+            .header(http::header::CONTENT_TYPE, content_type)
+            .body(body)
             .send()
             .await
             .map_err(|e| {
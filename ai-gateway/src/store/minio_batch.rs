@@ -0,0 +1,209 @@
+//! Batches request bodies into fewer uploads, see [`BatchingConfig`].
+//!
+//! Each request's bodies are a line of the batch's newline delimited JSON,
+//! which is uploaded in place of the bodies of a request with the batch's id.
+//! Requests wait for their batch to be uploaded before they're logged, so
+//! that their log can point to the [`BodyLocation`] of their bodies.
+use std::{
+    sync::{Arc, OnceLock, Weak},
+    time::Duration,
+};
+
+use rustc_hash::FxHashMap as HashMap;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{Instant, MissedTickBehavior},
+};
+use uuid::Uuid;
+
+use crate::{
+    app_state::{AppState, InnerAppState},
+    config::minio::BatchingConfig,
+    error::logger::LoggerError,
+    store::minio::MinioClient,
+    types::{extensions::AuthContext, logger::BodyLocation, org::OrgId},
+};
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+type Uploaded = oneshot::Sender<Result<BodyLocation, LoggerError>>;
+
+#[derive(Debug)]
+enum Command {
+    Add {
+        auth_ctx: AuthContext,
+        s3_log: Vec<u8>,
+        uploaded: Uploaded,
+    },
+    /// Uploads pending batches, and every batch after without waiting for it
+    /// to fill, e.g. on shutdown.
+    Flush,
+}
+
+#[derive(Debug)]
+pub struct BodyBatcher {
+    config: BatchingConfig,
+    /// Started by the first request, since batches are uploaded with the
+    /// app's state.
+    tx: OnceLock<mpsc::UnboundedSender<Command>>,
+}
+
+impl BodyBatcher {
+    #[must_use]
+    pub fn new(config: BatchingConfig) -> Self {
+        Self {
+            config,
+            tx: OnceLock::new(),
+        }
+    }
+
+    /// Adds serialized bodies to their organization's batch, returning their
+    /// location once the batch is uploaded.
+    pub async fn add(
+        &self,
+        app_state: &AppState,
+        auth_ctx: &AuthContext,
+        s3_log: Vec<u8>,
+    ) -> Result<BodyLocation, LoggerError> {
+        let tx = self.tx.get_or_init(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            // a weak reference, so that the batcher stops with the app
+            let app_state = Arc::downgrade(&app_state.0);
+            tokio::spawn(run(self.config, app_state, rx));
+            tx
+        });
+        let (uploaded, uploaded_rx) = oneshot::channel();
+        tx.send(Command::Add {
+            auth_ctx: auth_ctx.clone(),
+            s3_log,
+            uploaded,
+        })
+        .map_err(|_| LoggerError::BatchUploadFailed)?;
+        uploaded_rx
+            .await
+            .unwrap_or(Err(LoggerError::BatchUploadFailed))
+    }
+
+    /// Uploads pending batches without waiting for them to fill.
+    pub fn flush(&self) {
+        if let Some(tx) = self.tx.get() {
+            let _ = tx.send(Command::Flush);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Batch {
+    id: Uuid,
+    started: Instant,
+    /// The first request's, which signs the upload in sidecar deployments.
+    auth_ctx: AuthContext,
+    lines: Vec<u8>,
+    uploaded: Vec<Uploaded>,
+}
+
+impl Batch {
+    fn new(auth_ctx: AuthContext) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            started: Instant::now(),
+            auth_ctx,
+            lines: Vec::new(),
+            uploaded: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, s3_log: &[u8], uploaded: Uploaded) {
+        self.lines.extend_from_slice(s3_log);
+        self.lines.push(b'\n');
+        self.uploaded.push(uploaded);
+    }
+
+    async fn upload(self, app_state: AppState) {
+        tracing::debug!(
+            batch_id = %self.id,
+            len = self.uploaded.len(),
+            "uploading batch of bodies"
+        );
+        let result = MinioClient::for_deployment_target(&app_state)
+            .upload(
+                &app_state,
+                &self.auth_ctx,
+                self.id,
+                self.lines,
+                NDJSON_CONTENT_TYPE,
+            )
+            .await;
+        if let Err(e) = &result {
+            tracing::error!(error = %e, "failed to upload batch of bodies");
+        }
+        for (line, uploaded) in self.uploaded.into_iter().enumerate() {
+            let location = match &result {
+                Ok(()) => Ok(BodyLocation {
+                    batch_id: self.id,
+                    line,
+                }),
+                Err(_) => Err(LoggerError::BatchUploadFailed),
+            };
+            let _ = uploaded.send(location);
+        }
+    }
+}
+
+async fn run(
+    config: BatchingConfig,
+    app_state: Weak<InnerAppState>,
+    mut rx: mpsc::UnboundedReceiver<Command>,
+) {
+    let upload = |batch: Batch| {
+        // requests are failed by dropping their senders if the app stopped
+        if let Some(app_state) = app_state.upgrade() {
+            tokio::spawn(batch.upload(AppState(app_state)));
+        }
+    };
+    let mut batches: HashMap<OrgId, Batch> = HashMap::default();
+    let mut flushing = false;
+    // batches are uploaded within 1.5x of their max wait
+    let mut ticker = tokio::time::interval(
+        (config.max_wait / 2).max(Duration::from_millis(1)),
+    );
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(Command::Add { auth_ctx, s3_log, uploaded }) => {
+                    let org_id = auth_ctx.org_id;
+                    let batch = batches
+                        .entry(org_id)
+                        .or_insert_with(|| Batch::new(auth_ctx));
+                    batch.push(&s3_log, uploaded);
+                    if (flushing
+                        || batch.uploaded.len() >= config.max_logs.get())
+                        && let Some(batch) = batches.remove(&org_id)
+                    {
+                        upload(batch);
+                    }
+                }
+                Some(Command::Flush) => {
+                    flushing = true;
+                    batches.drain().for_each(|(_, batch)| upload(batch));
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                let expired = batches
+                    .iter()
+                    .filter(|(_, batch)| {
+                        batch.started.elapsed() >= config.max_wait
+                    })
+                    .map(|(org_id, _)| *org_id)
+                    .collect::<Vec<_>>();
+                for org_id in expired {
+                    if let Some(batch) = batches.remove(&org_id) {
+                        upload(batch);
+                    }
+                }
+            }
+        }
+    }
+}
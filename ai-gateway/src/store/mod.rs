@@ -4,6 +4,7 @@ use crate::{config::database::DatabaseConfig, error::init::InitError};
 
 pub mod db_listener;
 pub mod minio;
+pub mod minio_batch;
 pub mod router;
 
 pub async fn connect(config: &DatabaseConfig) -> Result<PgPool, InitError> {
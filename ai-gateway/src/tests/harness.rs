@@ -10,9 +10,12 @@ use tower::MakeService as _;
 use super::mock::{Mock, MockArgs};
 use crate::{
     app::{App, AppFactory, AppResponse},
-    config::Config,
+    config::{Config, DeploymentTarget},
     control_plane::{self, types::Key},
-    types::request::Request,
+    types::{
+        provider::{ProviderKeyMap, ProviderKeys},
+        request::Request,
+    },
 };
 
 pub const MOCK_SERVER_PORT: u16 = 8111;
@@ -84,10 +87,23 @@ impl Harness {
         control_plane_config: control_plane::types::Config,
     ) -> Self {
         let mock = Mock::new(&mut config, mock_args).await;
-        let app = App::new(config).await.expect("failed to create app");
+        let provider_keys = match config.deployment_target {
+            DeploymentTarget::Sidecar => ProviderKeys::Sidecar(
+                ProviderKeyMap::test_keys(&config.providers),
+            ),
+            DeploymentTarget::Cloud => ProviderKeys::new(&config),
+        };
+        let app = App::with_provider_keys(config, provider_keys)
+            .await
+            .expect("failed to create app");
         let app_factory = AppFactory::new(app.state.clone(), app);
-        app_factory.state.0.control_plane_state.write().await.config =
-            control_plane_config;
+        app_factory
+            .state
+            .0
+            .control_plane_state
+            .write()
+            .await
+            .set_config(control_plane_config);
         let socket_addr =
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0);
         Self {
@@ -30,6 +30,18 @@ pub struct MockArgs {
     pub global_bedrock_latency: Option<u64>,
     #[builder(setter(strip_option), default = None)]
     pub global_mistral_latency: Option<u64>,
+    #[builder(setter(strip_option), default = None)]
+    pub global_cohere_latency: Option<u64>,
+    #[builder(setter(strip_option), default = None)]
+    pub global_azure_latency: Option<u64>,
+    #[builder(setter(strip_option), default = None)]
+    pub global_groq_latency: Option<u64>,
+    #[builder(setter(strip_option), default = None)]
+    pub global_vertex_latency: Option<u64>,
+    #[builder(setter(strip_option), default = None)]
+    pub global_deepseek_latency: Option<u64>,
+    #[builder(setter(strip_option), default = None)]
+    pub global_xai_latency: Option<u64>,
 
     #[builder(setter(strip_option), default = None)]
     pub openai_port: Option<u16>,
@@ -47,6 +59,18 @@ pub struct MockArgs {
     pub jawn_port: Option<u16>,
     #[builder(setter(strip_option), default = None)]
     pub mistral_port: Option<u16>,
+    #[builder(setter(strip_option), default = None)]
+    pub cohere_port: Option<u16>,
+    #[builder(setter(strip_option), default = None)]
+    pub azure_port: Option<u16>,
+    #[builder(setter(strip_option), default = None)]
+    pub groq_port: Option<u16>,
+    #[builder(setter(strip_option), default = None)]
+    pub vertex_port: Option<u16>,
+    #[builder(setter(strip_option), default = None)]
+    pub deepseek_port: Option<u16>,
+    #[builder(setter(strip_option), default = None)]
+    pub xai_port: Option<u16>,
 
     /// Map of stub id to the expectations on the number of times it should be
     /// called.
@@ -65,6 +89,12 @@ pub struct Mock {
     pub minio_mock: Stubr,
     pub jawn_mock: Stubr,
     pub mistral_mock: Stubr,
+    pub cohere_mock: Stubr,
+    pub azure_mock: Stubr,
+    pub groq_mock: Stubr,
+    pub vertex_mock: Stubr,
+    pub deepseek_mock: Stubr,
+    pub xai_mock: Stubr,
     args: MockArgs,
 }
 
@@ -153,10 +183,94 @@ impl Mock {
         .await;
         config
             .providers
-            .get_mut(&InferenceProvider::Named("mistral".into()))
+            .get_mut(&InferenceProvider::Mistral)
             .unwrap()
             .base_url = Url::parse(&mistral_mock.uri()).unwrap();
 
+        let cohere_mock = start_mock_for_test(
+            &get_stubs_path("cohere"),
+            args.global_cohere_latency,
+            args.stubs.as_ref(),
+            args.verify,
+            args.cohere_port,
+        )
+        .await;
+        config
+            .providers
+            .get_mut(&InferenceProvider::Cohere)
+            .unwrap()
+            .base_url = Url::parse(&cohere_mock.uri()).unwrap();
+
+        let azure_mock = start_mock_for_test(
+            &get_stubs_path("azure"),
+            args.global_azure_latency,
+            args.stubs.as_ref(),
+            args.verify,
+            args.azure_port,
+        )
+        .await;
+        config
+            .providers
+            .get_mut(&InferenceProvider::AzureOpenAI)
+            .unwrap()
+            .base_url = Url::parse(&azure_mock.uri()).unwrap();
+
+        let groq_mock = start_mock_for_test(
+            &get_stubs_path("groq"),
+            args.global_groq_latency,
+            args.stubs.as_ref(),
+            args.verify,
+            args.groq_port,
+        )
+        .await;
+        config
+            .providers
+            .get_mut(&InferenceProvider::Groq)
+            .unwrap()
+            .base_url = Url::parse(&groq_mock.uri()).unwrap();
+
+        let vertex_mock = start_mock_for_test(
+            &get_stubs_path("vertex"),
+            args.global_vertex_latency,
+            args.stubs.as_ref(),
+            args.verify,
+            args.vertex_port,
+        )
+        .await;
+        config
+            .providers
+            .get_mut(&InferenceProvider::VertexAI)
+            .unwrap()
+            .base_url = Url::parse(&vertex_mock.uri()).unwrap();
+
+        let deepseek_mock = start_mock_for_test(
+            &get_stubs_path("deepseek"),
+            args.global_deepseek_latency,
+            args.stubs.as_ref(),
+            args.verify,
+            args.deepseek_port,
+        )
+        .await;
+        config
+            .providers
+            .get_mut(&InferenceProvider::DeepSeek)
+            .unwrap()
+            .base_url = Url::parse(&deepseek_mock.uri()).unwrap();
+
+        let xai_mock = start_mock_for_test(
+            &get_stubs_path("xai"),
+            args.global_xai_latency,
+            args.stubs.as_ref(),
+            args.verify,
+            args.xai_port,
+        )
+        .await;
+        config
+            .providers
+            .get_mut(&InferenceProvider::XAI)
+            .unwrap()
+            .base_url = Url::parse(&xai_mock.uri()).unwrap();
+
         let minio_mock = start_mock_for_test(
             &get_stubs_path("minio"),
             None,
@@ -189,6 +303,12 @@ impl Mock {
             minio_mock,
             jawn_mock,
             mistral_mock,
+            cohere_mock,
+            azure_mock,
+            groq_mock,
+            vertex_mock,
+            deepseek_mock,
+            xai_mock,
             args,
         }
     }
@@ -273,6 +393,66 @@ impl Mock {
         )
         .await;
 
+        let cohere_mock = start_mock(
+            &get_stubs_path("cohere"),
+            args.global_cohere_latency,
+            args.stubs.as_ref(),
+            false,
+            false,
+            args.cohere_port,
+        )
+        .await;
+
+        let azure_mock = start_mock(
+            &get_stubs_path("azure"),
+            args.global_azure_latency,
+            args.stubs.as_ref(),
+            false,
+            false,
+            args.azure_port,
+        )
+        .await;
+
+        let groq_mock = start_mock(
+            &get_stubs_path("groq"),
+            args.global_groq_latency,
+            args.stubs.as_ref(),
+            false,
+            false,
+            args.groq_port,
+        )
+        .await;
+
+        let vertex_mock = start_mock(
+            &get_stubs_path("vertex"),
+            args.global_vertex_latency,
+            args.stubs.as_ref(),
+            false,
+            false,
+            args.vertex_port,
+        )
+        .await;
+
+        let deepseek_mock = start_mock(
+            &get_stubs_path("deepseek"),
+            args.global_deepseek_latency,
+            args.stubs.as_ref(),
+            false,
+            false,
+            args.deepseek_port,
+        )
+        .await;
+
+        let xai_mock = start_mock(
+            &get_stubs_path("xai"),
+            args.global_xai_latency,
+            args.stubs.as_ref(),
+            false,
+            false,
+            args.xai_port,
+        )
+        .await;
+
         Self {
             openai_mock,
             anthropic_mock,
@@ -282,6 +462,12 @@ impl Mock {
             minio_mock,
             jawn_mock,
             mistral_mock,
+            cohere_mock,
+            azure_mock,
+            groq_mock,
+            vertex_mock,
+            deepseek_mock,
+            xai_mock,
             args,
         }
     }
@@ -295,6 +481,12 @@ impl Mock {
         self.minio_mock.http_server.verify().await;
         self.jawn_mock.http_server.verify().await;
         self.mistral_mock.http_server.verify().await;
+        self.cohere_mock.http_server.verify().await;
+        self.azure_mock.http_server.verify().await;
+        self.groq_mock.http_server.verify().await;
+        self.vertex_mock.http_server.verify().await;
+        self.deepseek_mock.http_server.verify().await;
+        self.xai_mock.http_server.verify().await;
     }
 
     pub async fn reset(&self) {
@@ -306,6 +498,12 @@ impl Mock {
         self.minio_mock.http_server.reset().await;
         self.jawn_mock.http_server.reset().await;
         self.mistral_mock.http_server.reset().await;
+        self.cohere_mock.http_server.reset().await;
+        self.azure_mock.http_server.reset().await;
+        self.groq_mock.http_server.reset().await;
+        self.vertex_mock.http_server.reset().await;
+        self.deepseek_mock.http_server.reset().await;
+        self.xai_mock.http_server.reset().await;
     }
 
     pub async fn stubs(&self, stubs: HashMap<&'static str, Times>) {
@@ -354,6 +552,60 @@ impl Mock {
         )
         .await;
 
+        register_stubs_for_mock(
+            &self.cohere_mock,
+            &get_stubs_path("cohere"),
+            self.args.global_cohere_latency,
+            &stubs,
+            self.args.verify,
+        )
+        .await;
+
+        register_stubs_for_mock(
+            &self.azure_mock,
+            &get_stubs_path("azure"),
+            self.args.global_azure_latency,
+            &stubs,
+            self.args.verify,
+        )
+        .await;
+
+        register_stubs_for_mock(
+            &self.groq_mock,
+            &get_stubs_path("groq"),
+            self.args.global_groq_latency,
+            &stubs,
+            self.args.verify,
+        )
+        .await;
+
+        register_stubs_for_mock(
+            &self.vertex_mock,
+            &get_stubs_path("vertex"),
+            self.args.global_vertex_latency,
+            &stubs,
+            self.args.verify,
+        )
+        .await;
+
+        register_stubs_for_mock(
+            &self.deepseek_mock,
+            &get_stubs_path("deepseek"),
+            self.args.global_deepseek_latency,
+            &stubs,
+            self.args.verify,
+        )
+        .await;
+
+        register_stubs_for_mock(
+            &self.xai_mock,
+            &get_stubs_path("xai"),
+            self.args.global_xai_latency,
+            &stubs,
+            self.args.verify,
+        )
+        .await;
+
         register_stubs_for_mock(
             &self.minio_mock,
             &get_stubs_path("minio"),
@@ -1,6 +1,10 @@
 pub mod harness;
 pub mod mock;
+pub mod providers;
 
 pub trait TestDefault {
     fn test_default() -> Self;
 }
+
+/// Encodes an AWS event stream message, for mocking Bedrock streams.
+pub use crate::dispatcher::event_stream::encode_message as encode_event_stream_message;
@@ -0,0 +1,176 @@
+//! Fixtures shared by the tests of each provider.
+use std::{collections::HashMap, time::Duration};
+
+use axum_core::body::Body;
+use chrono::Utc;
+use compact_str::CompactString;
+use http::{Method, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+use super::{TestDefault, harness::Harness, mock::MockArgs};
+use crate::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    discover::monitor::rate_limit::RateLimitMonitor,
+    endpoints::EndpointType,
+    types::{provider::InferenceProvider, request::Request, router::RouterId},
+};
+
+/// Routes chat requests on `my-router` to `provider`, or splits them evenly
+/// between it and `split_with`.
+#[must_use]
+pub fn config(
+    provider: InferenceProvider,
+    split_with: Option<InferenceProvider>,
+) -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let providers = if let Some(split_with) = split_with {
+        nes![
+            WeightedProvider {
+                provider: split_with,
+                weight: Decimal::try_from(0.5).unwrap(),
+                schedule: None,
+            },
+            WeightedProvider {
+                provider,
+                weight: Decimal::try_from(0.5).unwrap(),
+                schedule: None,
+            },
+        ]
+    } else {
+        nes![WeightedProvider {
+            provider,
+            weight: Decimal::try_from(1.0).unwrap(),
+            schedule: None,
+        }]
+    };
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers,
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+/// A chat request to `my-router` for `model`.
+#[must_use]
+pub fn chat_request(model: &str, stream: bool) -> Request {
+    let request_body = Body::from(
+        serde_json::to_vec(&json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": stream
+        }))
+        .unwrap(),
+    );
+    http::Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+/// Moves the gateway's stopped clock past a rate limited provider's
+/// retry-after period and ramp up, a little at a time so that the rate limit
+/// monitor keeps up with it.
+pub async fn advance_past_readmission(harness: &Harness) {
+    let clock = &harness.app_factory.state.0.clock;
+    for _ in 0..40 {
+        clock.advance(Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// Checks that `provider`, split evenly with OpenAI, stops receiving
+/// requests once it rate limits the gateway, and receives them again after
+/// its retry-after period.
+///
+/// The stubs are the provider's rate limited and successful chat
+/// completions, e.g. `rate_limit:groq:chat_completion` and
+/// `success:groq:chat_completion`.
+pub async fn assert_rate_limit_removes_provider_from_lb_pool(
+    provider: InferenceProvider,
+    rate_limit_stub: &'static str,
+    success_stub: &'static str,
+) {
+    let num_requests = 20;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            (rate_limit_stub, 1.into()),
+            (
+                "success:openai:chat_completion",
+                (num_requests - 1..).into(),
+            ),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(provider, Some(InferenceProvider::OpenAI)))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    harness.app_factory.state.0.clock.set(Utc::now());
+
+    let rate_limit_monitor =
+        RateLimitMonitor::new(harness.app_factory.state.clone());
+    tokio::spawn(async move {
+        rate_limit_monitor.run_forever().await.unwrap();
+    });
+    // give the monitor time to pick up the router
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    for _ in 0..num_requests {
+        let response = harness
+            .call(chat_request("openai/gpt-4o-mini", false))
+            .await
+            .unwrap();
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+
+    advance_past_readmission(&harness).await;
+    harness.mock.verify().await;
+    harness.mock.reset().await;
+
+    harness
+        .mock
+        .stubs(HashMap::from([
+            (success_stub, (1..).into()),
+            ("success:openai:chat_completion", (1..).into()),
+        ]))
+        .await;
+    for _ in 0..num_requests {
+        let response = harness
+            .call(chat_request("openai/gpt-4o-mini", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+    harness.mock.verify().await;
+}
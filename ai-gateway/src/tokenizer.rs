@@ -0,0 +1,528 @@
+//! Counts the input tokens of a prompt before it's sent, e.g. to budget or
+//! truncate requests.
+//!
+//! OpenAI models are counted locally with their `tiktoken` encoding, and
+//! Anthropic models with Anthropic's [count tokens API], called with the same
+//! provider key the request itself would be sent with. Counts match the
+//! `prompt_tokens` / `input_tokens` the provider reports in the usage of the
+//! response, which is what the gateway accounts for.
+//!
+//! Where calling a provider isn't an option, [`count_tokens`] counts locally
+//! and approximates Anthropic models instead. Rate limits estimate tokens
+//! with [`estimate_input_tokens`], which doesn't approximate Anthropic models,
+//! so that their token budgets don't change.
+//!
+//! [count tokens API]: https://docs.anthropic.com/en/api/messages-count-tokens
+use std::{str::FromStr, sync::LazyLock};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tiktoken_rs::CoreBPE;
+
+use crate::{
+    app_state::AppState,
+    dispatcher::client::{Client, ProviderClient},
+    error::{
+        api::ApiError, internal::InternalError,
+        invalid_req::InvalidRequestError,
+    },
+    types::{
+        extensions::AuthContext, model_id::ModelId, provider::InferenceProvider,
+    },
+};
+
+/// Tokens every chat message is wrapped in, e.g. `<|start|>{role}\n`.
+const TOKENS_PER_MESSAGE: usize = 3;
+/// Tokens a message's `name` adds.
+const TOKENS_PER_NAME: usize = 1;
+/// Every reply is primed with `<|start|>assistant<|message|>`.
+const TOKENS_PER_REPLY: usize = 3;
+/// Anthropic's tokenizer isn't public, but counts about 10% more tokens than
+/// `cl100k_base` for English text, as a ratio of numerator and denominator.
+const ANTHROPIC_TOKENS_PER_CL100K_TOKEN: (usize, usize) = (11, 10);
+
+const ANTHROPIC_COUNT_TOKENS_PATH: &str = "v1/messages/count_tokens";
+
+static CL100K_BASE: LazyLock<CoreBPE> = LazyLock::new(|| {
+    tiktoken_rs::cl100k_base().expect("cl100k_base is bundled")
+});
+static O200K_BASE: LazyLock<CoreBPE> =
+    LazyLock::new(|| tiktoken_rs::o200k_base().expect("o200k_base is bundled"));
+
+/// What a prompt is tokenized as.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TokenizeInput {
+    /// Chat messages in the OpenAI format.
+    Messages { messages: Vec<Message> },
+    /// Raw text, counted without any chat formatting.
+    Text { text: String },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Message {
+    pub role: String,
+    #[serde(default)]
+    pub content: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Message {
+    /// The text of the message, with content parts other than text left out.
+    fn text(&self) -> String {
+        match &self.content {
+            Value::String(text) => text.clone(),
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The BPE encodings of OpenAI models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Cl100kBase,
+    O200kBase,
+}
+
+impl Encoding {
+    fn bpe(self) -> &'static CoreBPE {
+        match self {
+            Self::Cl100kBase => &CL100K_BASE,
+            Self::O200kBase => &O200K_BASE,
+        }
+    }
+}
+
+/// How the tokens of a model are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// Locally, with the model's encoding.
+    Bpe(Encoding),
+    /// By Anthropic's count tokens API.
+    Anthropic,
+}
+
+/// Model name prefixes per provider. More specific prefixes come first.
+const MODEL_FAMILIES: &[(InferenceProvider, &str, Tokenizer)] = &[
+    (
+        InferenceProvider::OpenAI,
+        "gpt-4o",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "gpt-4.1",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "gpt-4.5",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "gpt-5",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "o1",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "o3",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "o4",
+        Tokenizer::Bpe(Encoding::O200kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "gpt-4",
+        Tokenizer::Bpe(Encoding::Cl100kBase),
+    ),
+    (
+        InferenceProvider::OpenAI,
+        "gpt-3.5-turbo",
+        Tokenizer::Bpe(Encoding::Cl100kBase),
+    ),
+    (InferenceProvider::Anthropic, "claude", Tokenizer::Anthropic),
+];
+
+impl Tokenizer {
+    /// The tokenizer for a `{provider}/{model}` model, if it's in one of the
+    /// supported [`MODEL_FAMILIES`].
+    #[must_use]
+    pub fn for_model(model: &ModelId) -> Option<Self> {
+        let provider = model.inference_provider()?;
+        let name = model.to_string();
+        MODEL_FAMILIES
+            .iter()
+            .find(|(p, prefix, _)| *p == provider && name.starts_with(prefix))
+            .map(|(_, _, tokenizer)| *tokenizer)
+    }
+}
+
+/// The supported model families, e.g. for error messages.
+#[must_use]
+pub fn supported_model_families() -> Vec<String> {
+    MODEL_FAMILIES
+        .iter()
+        .map(|(provider, prefix, _)| format!("{provider}/{prefix}"))
+        .collect()
+}
+
+/// Counts the input tokens of `input` with an encoding, following OpenAI's
+/// accounting of chat messages.
+#[must_use]
+pub fn count_bpe_tokens(encoding: Encoding, input: &TokenizeInput) -> usize {
+    match input {
+        TokenizeInput::Text { text } => {
+            encoding.bpe().encode_with_special_tokens(text).len()
+        }
+        TokenizeInput::Messages { messages } => {
+            count_bpe_messages(encoding, messages)
+        }
+    }
+}
+
+fn count_bpe_messages(encoding: Encoding, messages: &[Message]) -> usize {
+    let bpe = encoding.bpe();
+    let count = |text: &str| bpe.encode_with_special_tokens(text).len();
+    let tokens: usize = messages
+        .iter()
+        .map(|message| {
+            TOKENS_PER_MESSAGE
+                + count(&message.role)
+                + count(&message.text())
+                + message
+                    .name
+                    .as_deref()
+                    .map_or(0, |name| TOKENS_PER_NAME + count(name))
+        })
+        .sum();
+    tokens + TOKENS_PER_REPLY
+}
+
+/// Counts the input tokens of chat `messages` for `model` locally.
+///
+/// OpenAI models are counted exactly, Anthropic models are approximated from
+/// `cl100k_base`, and other models are counted with `cl100k_base`. Use
+/// [`count_input_tokens`] for exact counts of Anthropic models.
+#[must_use]
+pub fn count_tokens(model: &ModelId, messages: &[Message]) -> usize {
+    match Tokenizer::for_model(model) {
+        Some(Tokenizer::Bpe(encoding)) => {
+            count_bpe_messages(encoding, messages)
+        }
+        Some(Tokenizer::Anthropic) => {
+            let (numerator, denominator) = ANTHROPIC_TOKENS_PER_CL100K_TOKEN;
+            let tokens = count_bpe_messages(Encoding::Cl100kBase, messages);
+            (tokens * numerator).div_ceil(denominator)
+        }
+        None => count_bpe_messages(Encoding::Cl100kBase, messages),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelField {
+    #[serde(default)]
+    model: Option<String>,
+}
+
+/// Estimates the input tokens of a request body locally, e.g. to rate limit
+/// tokens before the request is sent.
+///
+/// Models without an encoding, including Anthropic's, are approximated with
+/// `cl100k_base`, and bodies without chat messages, e.g. embeddings, are
+/// counted as text.
+#[must_use]
+pub fn estimate_input_tokens(body: &[u8]) -> usize {
+    let encoding = estimation_encoding(body);
+    let input = match serde_json::from_slice::<TokenizeInput>(body) {
+        Ok(input @ TokenizeInput::Messages { .. }) => input,
+        _ => TokenizeInput::Text {
+            text: String::from_utf8_lossy(body).into_owned(),
+        },
+    };
+    count_bpe_tokens(encoding, &input)
+}
+
+/// Estimates the tokens of `output` generated for a request with `body`,
+/// e.g. for streams which don't report their usage.
+///
+/// Encodings are chosen as in [`estimate_input_tokens`].
+#[must_use]
+pub fn estimate_output_tokens(body: &[u8], output: &str) -> usize {
+    let encoding = estimation_encoding(body);
+    encoding.bpe().encode_with_special_tokens(output).len()
+}
+
+/// The encoding of the model a request body is for, or `cl100k_base`.
+fn estimation_encoding(body: &[u8]) -> Encoding {
+    serde_json::from_slice::<ModelField>(body)
+        .ok()
+        .and_then(|field| field.model)
+        .and_then(|model| ModelId::from_str(&model).ok())
+        .and_then(|model| Tokenizer::for_model(&model))
+        .and_then(|tokenizer| match tokenizer {
+            Tokenizer::Bpe(encoding) => Some(encoding),
+            Tokenizer::Anthropic => None,
+        })
+        .unwrap_or(Encoding::Cl100kBase)
+}
+
+/// Counts the input tokens of `input` for `model`, as the provider would.
+///
+/// Models outside the supported families are rejected, see
+/// [`supported_model_families`].
+pub async fn count_input_tokens(
+    app_state: &AppState,
+    model: &ModelId,
+    input: &TokenizeInput,
+    auth_ctx: Option<&AuthContext>,
+) -> Result<usize, ApiError> {
+    let Some(tokenizer) = Tokenizer::for_model(model) else {
+        return Err(InvalidRequestError::UnsupportedTokenizerModel {
+            model: model.to_string(),
+            supported: supported_model_families().join(", "),
+        }
+        .into());
+    };
+    match tokenizer {
+        Tokenizer::Bpe(encoding) => Ok(count_bpe_tokens(encoding, input)),
+        Tokenizer::Anthropic => {
+            count_anthropic_tokens(app_state, model, input, auth_ctx).await
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicTokenCount {
+    input_tokens: usize,
+}
+
+async fn count_anthropic_tokens(
+    app_state: &AppState,
+    model: &ModelId,
+    input: &TokenizeInput,
+    auth_ctx: Option<&AuthContext>,
+) -> Result<usize, ApiError> {
+    let provider = InferenceProvider::Anthropic;
+    let url = app_state
+        .config()
+        .providers
+        .get(&provider)
+        .ok_or(InternalError::ProviderNotConfigured(provider.clone()))?
+        .base_url
+        .join(ANTHROPIC_COUNT_TOKENS_PATH)
+        .map_err(|_| InternalError::Internal)?;
+    let body = anthropic_count_tokens_body(model, input);
+    let body = Bytes::from(serde_json::to_vec(&body).map_err(|e| {
+        InternalError::Serialize {
+            ty: "AnthropicCountTokensRequest",
+            error: e,
+        }
+    })?);
+
+    let client = Client::new(app_state, provider.clone()).await.map_err(
+        |e| {
+            tracing::error!(error = %e, "failed to create anthropic client");
+            InternalError::Internal
+        },
+    )?;
+    let request_builder = client.as_ref().post(url).body(body.clone());
+    let request_builder = client
+        .authenticate(app_state, request_builder, &body, auth_ctx, provider)
+        .await?;
+    let response = request_builder
+        .send()
+        .await
+        .map_err(InternalError::ReqwestError)?;
+
+    let status = response.status();
+    if status.is_client_error() {
+        return Err(InvalidRequestError::Provider4xxError(status).into());
+    } else if !status.is_success() {
+        return Err(InternalError::Provider5xxError(status).into());
+    }
+    let response_bytes = response
+        .bytes()
+        .await
+        .map_err(InternalError::ReqwestError)?;
+    let count: AnthropicTokenCount = serde_json::from_slice(&response_bytes)
+        .map_err(|e| InternalError::Deserialize {
+            ty: "AnthropicTokenCount",
+            error: e,
+        })?;
+    Ok(count.input_tokens)
+}
+
+/// System messages are passed separately from the conversation to Anthropic.
+fn anthropic_count_tokens_body(
+    model: &ModelId,
+    input: &TokenizeInput,
+) -> Value {
+    let (system, messages): (Vec<_>, Vec<_>) = match input {
+        TokenizeInput::Text { text } => {
+            (Vec::new(), vec![json!({ "role": "user", "content": text })])
+        }
+        TokenizeInput::Messages { messages } => {
+            let (system, messages): (Vec<_>, Vec<_>) =
+                messages.iter().partition(|message| {
+                    message.role == "system" || message.role == "developer"
+                });
+            (
+                system.iter().map(|message| message.text()).collect(),
+                messages
+                    .iter()
+                    .map(|message| {
+                        json!({
+                            "role": message.role,
+                            "content": message.text(),
+                        })
+                    })
+                    .collect(),
+            )
+        }
+    };
+    let mut body = json!({
+        "model": model.to_string(),
+        "messages": messages,
+    });
+    if !system.is_empty() {
+        body["system"] = Value::String(system.join("\n"));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> TokenizeInput {
+        serde_json::from_value(json!({
+            "messages": [
+                { "role": "system", "content": "You are a helpful assistant." },
+                { "role": "user", "content": "Hello, world!" },
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn model_families_are_matched_by_prefix() {
+        let tokenizer = |model: &str| {
+            Tokenizer::for_model(&ModelId::from_str(model).unwrap())
+        };
+        assert_eq!(
+            tokenizer("openai/gpt-4o-mini"),
+            Some(Tokenizer::Bpe(Encoding::O200kBase))
+        );
+        assert_eq!(
+            tokenizer("openai/gpt-4-turbo"),
+            Some(Tokenizer::Bpe(Encoding::Cl100kBase))
+        );
+        assert_eq!(
+            tokenizer("anthropic/claude-3-5-haiku"),
+            Some(Tokenizer::Anthropic)
+        );
+        assert_eq!(tokenizer("openai/text-embedding-3-small"), None);
+    }
+
+    #[test]
+    fn chat_messages_are_counted_with_their_formatting() {
+        let text = TokenizeInput::Text {
+            text: "Hello, world!".to_string(),
+        };
+        assert_eq!(count_bpe_tokens(Encoding::O200kBase, &text), 4);
+        // (3 + 1 + 6) + (3 + 1 + 4) for the messages, 3 to prime the reply
+        assert_eq!(count_bpe_tokens(Encoding::O200kBase, &messages()), 21);
+    }
+
+    #[test]
+    fn known_strings_are_counted_per_encoding() {
+        let count = |encoding, text: &str| {
+            count_bpe_tokens(
+                encoding,
+                &TokenizeInput::Text {
+                    text: text.to_string(),
+                },
+            )
+        };
+        assert_eq!(count(Encoding::Cl100kBase, "hello world"), 2);
+        assert_eq!(count(Encoding::O200kBase, "hello world"), 2);
+        assert_eq!(count(Encoding::Cl100kBase, "tiktoken is great!"), 6);
+        assert_eq!(count(Encoding::Cl100kBase, "Hello, world!"), 4);
+        assert_eq!(count(Encoding::Cl100kBase, ""), 0);
+    }
+
+    #[test]
+    fn messages_are_counted_locally_per_model() {
+        let TokenizeInput::Messages { messages } = messages() else {
+            unreachable!("messages() returns messages");
+        };
+        let count = |model: &str| {
+            count_tokens(&ModelId::from_str(model).unwrap(), &messages)
+        };
+        assert_eq!(count("openai/gpt-4o"), 21);
+        let cl100k = count_bpe_messages(Encoding::Cl100kBase, &messages);
+        assert_eq!(count("openai/gpt-4"), cl100k);
+        // approximated as 10% more than cl100k_base
+        assert_eq!(
+            count("anthropic/claude-3-5-haiku"),
+            (cl100k * 11).div_ceil(10)
+        );
+    }
+
+    #[test]
+    fn request_bodies_are_estimated_locally() {
+        let chat = json!({
+            "model": "openai/gpt-4o",
+            "messages": [
+                { "role": "system", "content": "You are a helpful assistant." },
+                { "role": "user", "content": "Hello, world!" },
+            ],
+            "max_tokens": 100,
+        });
+        assert_eq!(estimate_input_tokens(chat.to_string().as_bytes()), 21);
+
+        // rate limit estimates of Anthropic models aren't approximated
+        let anthropic_chat = json!({
+            "model": "anthropic/claude-3-5-haiku",
+            "messages": chat["messages"],
+        });
+        assert_eq!(
+            estimate_input_tokens(anthropic_chat.to_string().as_bytes()),
+            count_bpe_tokens(Encoding::Cl100kBase, &messages())
+        );
+
+        let embeddings = json!({
+            "model": "openai/text-embedding-3-small",
+            "input": "Hello, world!",
+        });
+        let estimate = estimate_input_tokens(embeddings.to_string().as_bytes());
+        assert!(estimate > 4, "the whole body is counted: {estimate}");
+    }
+
+    #[test]
+    fn system_messages_are_separate_for_anthropic() {
+        let model = ModelId::from_str("anthropic/claude-3-5-haiku").unwrap();
+        let body = anthropic_count_tokens_body(&model, &messages());
+        assert_eq!(body["system"], "You are a helpful assistant.");
+        assert_eq!(
+            body["messages"],
+            json!([{ "role": "user", "content": "Hello, world!" }])
+        );
+    }
+}
@@ -2,7 +2,9 @@ use std::{collections::HashMap, sync::Arc};
 
 use derive_more::{AsRef, From, Into};
 
-use super::{model_id::ModelId, org::OrgId, user::UserId};
+use super::{
+    model_id::ModelId, org::OrgId, provider::InferenceProvider, user::UserId,
+};
 use crate::{config::router::RouterConfig, types::secret::Secret};
 
 #[derive(Debug, Clone, AsRef, From, Into)]
@@ -41,9 +43,68 @@ pub struct PromptContext {
     pub inputs: Option<HashMap<String, serde_json::Value>>,
 }
 
+/// Marks a synthetic response returned instead of calling the provider, see
+/// [`DispatcherConfig::dry_run`](crate::config::dispatcher::DispatcherConfig::dry_run).
+#[derive(Debug, Clone, Copy)]
+pub struct DryRun;
+
+/// The score a judge model gave the response to a request, see
+/// [`EvaluationConfig`](crate::config::evaluation::EvaluationConfig).
+///
+/// The score is only available some time after the response is returned, so
+/// the request log waits for it before being sent.
+#[derive(Debug, Clone)]
+pub struct EvaluationScore(pub tokio::sync::watch::Receiver<Option<f64>>);
+
+/// Marks a request sent by a priority router which fails over to the next
+/// provider in the chain rather than retrying the same provider, see
+/// [`BalanceConfigInner::Priority`](crate::config::balance::BalanceConfigInner::Priority).
+#[derive(Debug, Clone, Default)]
+pub struct PriorityFailover {
+    /// Providers which already returned a transient error for this request.
+    pub attempted: Vec<InferenceProvider>,
+}
+
+/// The arm of a canary router a request was sent to, see
+/// [`BalanceConfigInner::Canary`](crate::config::balance::BalanceConfigInner::Canary).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanaryArm {
+    Stable,
+    Canary,
+}
+
+impl CanaryArm {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stable => "stable",
+            Self::Canary => "canary",
+        }
+    }
+}
+
+/// The provider a client pinned a request to with the `helicone-provider`
+/// header, bypassing the router's load balancing, see
+/// [`RouterConfig::allow_provider_override`](crate::config::router::RouterConfig::allow_provider_override).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderOverride(pub InferenceProvider);
+
+/// The provider a router's routing rules send a request to, bypassing the
+/// router's load balancing, see
+/// [`RoutingTarget::provider`](crate::config::routing_rules::RoutingTarget::provider).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutedProvider(pub InferenceProvider);
+
+/// The model a model weighted router sends a request to, chosen before the
+/// request is cached so that responses from each model are cached separately,
+/// see [`BalanceConfigInner::ModelWeighted`](crate::config::balance::BalanceConfigInner::ModelWeighted).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetModel(pub ModelId);
+
 #[derive(Debug, Clone, Copy)]
 pub enum RequestKind {
     Router,
     UnifiedApi,
     DirectProxy,
+    Admin,
 }
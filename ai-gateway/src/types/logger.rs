@@ -25,6 +25,16 @@ impl S3Log {
     }
 }
 
+/// Where a request's bodies are when they were uploaded in a batch: the
+/// `line` of the newline delimited [`S3Log`]s uploaded in place of the bodies
+/// of request `batch_id`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyLocation {
+    pub batch_id: Uuid,
+    pub line: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct HeliconeLogMetadata {
@@ -127,6 +137,11 @@ pub struct RequestLog {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default)]
     pub experiment_row_index: Option<String>,
+    /// Set if the bodies were uploaded in a batch, otherwise they're stored
+    /// under the request's id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub body_location: Option<BodyLocation>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, TypedBuilder)]
@@ -140,6 +155,21 @@ pub struct ResponseLog {
     pub time_to_first_token: Option<f64>,
     pub response_created_at: DateTime<Utc>,
     pub delay_ms: f64,
+    /// Prompt tokens written to Anthropic's prompt cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub cache_creation_input_tokens: Option<u64>,
+    /// Prompt tokens read from Anthropic's prompt cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub cache_read_input_tokens: Option<u64>,
+    /// Reported by the provider, or estimated for streams without usage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub prompt_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub completion_tokens: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -4,6 +4,7 @@ pub mod extensions;
 pub mod json;
 pub mod logger;
 pub mod model_id;
+pub mod multipart;
 pub mod org;
 pub mod provider;
 pub mod rate_limit;
@@ -186,6 +186,58 @@ impl ModelId {
                     id: model_with_version,
                 })
             }
+            InferenceProvider::Cohere => {
+                let model_with_version = ModelIdWithVersion::from_str(s)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::Cohere,
+                    id: model_with_version,
+                })
+            }
+            InferenceProvider::AzureOpenAI => {
+                let model_with_version = ModelIdWithVersion::from_str(s)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::AzureOpenAI,
+                    id: model_with_version,
+                })
+            }
+            InferenceProvider::VertexAI => {
+                // the OpenAI compatible endpoint qualifies models by
+                // publisher, e.g. `google/gemini-2.0-flash`
+                let model = s.split_once('/').map_or(s, |(_, model)| model);
+                let model_with_version = ModelIdWithVersion::from_str(model)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::VertexAI,
+                    id: model_with_version,
+                })
+            }
+            InferenceProvider::Mistral => {
+                let model_with_version = ModelIdWithVersion::from_str(s)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::Mistral,
+                    id: model_with_version,
+                })
+            }
+            InferenceProvider::Groq => {
+                let model_with_version = ModelIdWithVersion::from_str(s)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::Groq,
+                    id: model_with_version,
+                })
+            }
+            InferenceProvider::XAI => {
+                let model_with_version = ModelIdWithVersion::from_str(s)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::XAI,
+                    id: model_with_version,
+                })
+            }
+            InferenceProvider::DeepSeek => {
+                let model_with_version = ModelIdWithVersion::from_str(s)?;
+                Ok(ModelId::ModelIdWithVersion {
+                    provider: InferenceProvider::DeepSeek,
+                    id: model_with_version,
+                })
+            }
             InferenceProvider::Named(name) => {
                 let model_with_version = ModelIdWithVersion::from_str(s)?;
                 Ok(ModelId::ModelIdWithVersion {
@@ -780,7 +832,7 @@ mod tests {
     fn groq_model_id_format_with_slash() {
         let groq_model_id_str = "meta-llama/llama-4-maverick-17b-128e-instruct";
         let result = ModelId::from_str_and_provider(
-            InferenceProvider::Named("groq".into()),
+            InferenceProvider::Groq,
             groq_model_id_str,
         )
         .unwrap();
@@ -795,14 +847,14 @@ mod tests {
                 version: Version::ImplicitLatest,
             }
         );
-        assert_eq!(provider, InferenceProvider::Named("groq".into()));
+        assert_eq!(provider, InferenceProvider::Groq);
     }
 
     #[test]
     fn groq_model_id_format_without_slash() {
         let groq_model_id_str = "deepseek-r1-distill-llama-70b";
         let result = ModelId::from_str_and_provider(
-            InferenceProvider::Named("groq".into()),
+            InferenceProvider::Groq,
             groq_model_id_str,
         )
         .unwrap();
@@ -816,7 +868,23 @@ mod tests {
                 version: Version::ImplicitLatest,
             }
         );
-        assert_eq!(provider, InferenceProvider::Named("groq".into()));
+        assert_eq!(provider, InferenceProvider::Groq);
+    }
+
+    #[test]
+    fn vertex_model_id_drops_publisher() {
+        for model in ["google/gemini-pro", "gemini-pro"] {
+            let result = ModelId::from_str_and_provider(
+                InferenceProvider::VertexAI,
+                model,
+            )
+            .unwrap();
+            let ModelId::ModelIdWithVersion { provider, id } = result else {
+                panic!("Expected ModelIdWithVersion with Vertex provider");
+            };
+            assert_eq!(id.model, "gemini-pro");
+            assert_eq!(provider, InferenceProvider::VertexAI);
+        }
     }
 
     #[test]
@@ -0,0 +1,538 @@
+//! Minimal support for `multipart/form-data` request bodies, e.g. the
+//! `audio/transcriptions` and `audio/translations` endpoints.
+//!
+//! We never need to materialize the uploaded file, we only walk the parts
+//! so that we can log a small JSON summary in place of the raw (binary)
+//! body, and map the `model` field without touching the rest of the body.
+use base64::Engine;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use http::HeaderMap;
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+/// Returns the multipart boundary if the request is `multipart/form-data`.
+#[must_use]
+pub fn boundary(headers: &HeaderMap) -> Option<String> {
+    let mime = headers
+        .get(http::header::CONTENT_TYPE)?
+        .to_str()
+        .ok()?
+        .parse::<mime::Mime>()
+        .ok()?;
+    if mime.type_() != mime::MULTIPART || mime.subtype() != mime::FORM_DATA {
+        return None;
+    }
+    mime.get_param(mime::BOUNDARY)
+        .map(|boundary| boundary.as_str().to_string())
+}
+
+#[must_use]
+pub fn is_multipart(headers: &HeaderMap) -> bool {
+    boundary(headers).is_some()
+}
+
+/// Metadata about a multipart request that is logged instead of the raw
+/// request body.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct MultipartMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_content_type: Option<String>,
+    /// Size of the uploaded file in bytes.
+    pub file_size: usize,
+    /// Duration of the audio in seconds, if reported by the provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// The start of the uploaded file, base64 encoded, if configured to be
+    /// logged, see [`AudioStorage`](crate::config::minio::AudioStorage).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_prefix: Option<String>,
+    /// Size of the whole body in bytes.
+    #[serde(skip)]
+    pub body_size: usize,
+}
+
+impl MultipartMetadata {
+    /// Parse the metadata out of a `multipart/form-data` body.
+    ///
+    /// Returns `None` if the request is not a multipart request.
+    #[must_use]
+    pub fn from_request(headers: &HeaderMap, body: &[u8]) -> Option<Self> {
+        let boundary = boundary(headers)?;
+        let mut reader = MetadataReader::new(&boundary, None);
+        reader.read(body);
+        Some(reader.finish())
+    }
+
+    /// Fill in the audio duration from the provider response, which is
+    /// included when `response_format=verbose_json` is requested.
+    pub fn set_duration_from_response(&mut self, response_body: &[u8]) {
+        self.duration =
+            serde_json::from_slice::<serde_json::Value>(response_body)
+                .ok()
+                .and_then(|value| {
+                    value.get("duration").and_then(serde_json::Value::as_f64)
+                });
+    }
+}
+
+/// Summarizes a multipart body as it is forwarded to the provider, so that
+/// the upload never has to be held in memory for the logger. Up to
+/// `file_prefix_bytes` of the uploaded file are included in the summary.
+///
+/// The metadata is sent once the returned stream is dropped, i.e. once the
+/// body has been forwarded or the upload was abandoned.
+pub fn tee<S, E>(
+    body: S,
+    boundary: &str,
+    file_prefix_bytes: Option<usize>,
+) -> (
+    impl Stream<Item = Result<Bytes, E>>,
+    oneshot::Receiver<MultipartMetadata>,
+)
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    let (tx, rx) = oneshot::channel();
+    let mut summary = Summary {
+        reader: Some(MetadataReader::new(boundary, file_prefix_bytes)),
+        tx: Some(tx),
+    };
+    let body = body.map(move |chunk| {
+        if let Ok(chunk) = &chunk
+            && let Some(reader) = summary.reader.as_mut()
+        {
+            reader.read(chunk);
+        }
+        chunk
+    });
+    (body, rx)
+}
+
+struct Summary {
+    reader: Option<MetadataReader>,
+    tx: Option<oneshot::Sender<MultipartMetadata>>,
+}
+
+impl Drop for Summary {
+    fn drop(&mut self) {
+        if let (Some(reader), Some(tx)) = (self.reader.take(), self.tx.take())
+            && tx.send(reader.finish()).is_err()
+        {
+            tracing::debug!("multipart metadata dropped before upload ended");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Content {
+    Model,
+    File,
+    /// Other fields, and the preamble before the first part.
+    Ignored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadState {
+    Content(Content),
+    Delimiter,
+    Headers,
+    Done,
+}
+
+/// Builds [`MultipartMetadata`] from a body that is read in chunks, holding
+/// on to no more of it than a part's headers, the `model` field and the
+/// file prefix.
+struct MetadataReader {
+    /// The CRLF before a delimiter is part of the delimiter rather than the
+    /// content of the previous part.
+    delimiter: Vec<u8>,
+    buffer: BytesMut,
+    state: ReadState,
+    metadata: MultipartMetadata,
+    model: Vec<u8>,
+    file_prefix_bytes: Option<usize>,
+    file_prefix: Option<Vec<u8>>,
+}
+
+impl MetadataReader {
+    fn new(boundary: &str, file_prefix_bytes: Option<usize>) -> Self {
+        Self {
+            delimiter: format!("\r\n--{boundary}").into_bytes(),
+            // so that the first delimiter matches too
+            buffer: BytesMut::from(&b"\r\n"[..]),
+            state: ReadState::Content(Content::Ignored),
+            metadata: MultipartMetadata::default(),
+            model: Vec::new(),
+            file_prefix_bytes,
+            file_prefix: None,
+        }
+    }
+
+    fn read(&mut self, chunk: &[u8]) {
+        self.metadata.body_size += chunk.len();
+        if self.state == ReadState::Done {
+            return;
+        }
+        self.buffer.extend_from_slice(chunk);
+        loop {
+            match self.state {
+                ReadState::Content(content) => {
+                    if let Some(end) = find(&self.buffer, &self.delimiter) {
+                        let part = self.buffer.split_to(end);
+                        self.read_content(content, &part);
+                        self.buffer.advance(self.delimiter.len());
+                        self.state = ReadState::Delimiter;
+                    } else {
+                        // the end of the buffer may be the start of a
+                        // delimiter
+                        let len = self
+                            .buffer
+                            .len()
+                            .saturating_sub(self.delimiter.len() - 1);
+                        let part = self.buffer.split_to(len);
+                        self.read_content(content, &part);
+                        return;
+                    }
+                }
+                ReadState::Delimiter => {
+                    if self.buffer.len() < 2 {
+                        return;
+                    }
+                    // closing delimiter
+                    if self.buffer.starts_with(b"--") {
+                        self.buffer.clear();
+                        self.state = ReadState::Done;
+                        return;
+                    }
+                    if self.buffer.starts_with(b"\r\n") {
+                        self.buffer.advance(2);
+                    }
+                    self.state = ReadState::Headers;
+                }
+                ReadState::Headers => {
+                    let Some(end) = find(&self.buffer, b"\r\n\r\n") else {
+                        return;
+                    };
+                    let headers = PartHeaders::parse(&self.buffer[..end]);
+                    self.buffer.advance(end + 4);
+                    self.state = ReadState::Content(self.start_part(headers));
+                }
+                ReadState::Done => return,
+            }
+        }
+    }
+
+    fn start_part(&mut self, headers: PartHeaders) -> Content {
+        if headers.is_file() {
+            self.metadata.file_name = headers.file_name;
+            self.metadata.file_content_type = headers.content_type;
+            self.metadata.file_size = 0;
+            self.file_prefix = self.file_prefix_bytes.map(|_| Vec::new());
+            Content::File
+        } else if headers.name.as_deref() == Some("model") {
+            self.model.clear();
+            Content::Model
+        } else {
+            Content::Ignored
+        }
+    }
+
+    fn read_content(&mut self, content: Content, part: &[u8]) {
+        match content {
+            Content::Model => self.model.extend_from_slice(part),
+            Content::File => {
+                self.metadata.file_size += part.len();
+                if let (Some(prefix), Some(max_bytes)) =
+                    (self.file_prefix.as_mut(), self.file_prefix_bytes)
+                {
+                    let len = part.len().min(max_bytes - prefix.len());
+                    prefix.extend_from_slice(&part[..len]);
+                }
+            }
+            Content::Ignored => {}
+        }
+    }
+
+    fn finish(mut self) -> MultipartMetadata {
+        // the body ended without a closing delimiter
+        if let ReadState::Content(content) = self.state {
+            let buffer = std::mem::take(&mut self.buffer);
+            let part = buffer.strip_suffix(b"\r\n").unwrap_or(&buffer);
+            self.read_content(content, part);
+        }
+        let mut metadata = self.metadata;
+        if !self.model.is_empty() {
+            metadata.model =
+                Some(String::from_utf8_lossy(&self.model).trim().to_string());
+        }
+        metadata.file_prefix = self.file_prefix.map(|prefix| {
+            base64::engine::general_purpose::STANDARD.encode(prefix)
+        });
+        metadata
+    }
+}
+
+/// Returns the boundary from the first delimiter of a multipart body, for
+/// when the request headers aren't available.
+#[must_use]
+pub fn boundary_from_body(body: &[u8]) -> Option<&str> {
+    let first_line = &body[..find(body, b"\r\n")?];
+    std::str::from_utf8(first_line.strip_prefix(b"--")?)
+        .ok()
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// Returns the value of a text field, e.g. the `model`.
+#[must_use]
+pub fn field(body: &[u8], boundary: &str, name: &str) -> Option<String> {
+    parts(body, boundary)
+        .find(|part| part.name.as_deref() == Some(name))
+        .map(|part| String::from_utf8_lossy(part.content).trim().to_string())
+}
+
+/// Replaces the value of a text field, leaving the rest of the body, including
+/// any files, untouched.
+///
+/// Returns `None` if the body has no such field.
+#[must_use]
+pub fn replace_field(
+    body: &[u8],
+    boundary: &str,
+    name: &str,
+    value: &str,
+) -> Option<Bytes> {
+    let part = parts(body, boundary)
+        .find(|part| part.name.as_deref() == Some(name))?;
+    // the content is a subslice of the body
+    let start = part.content.as_ptr() as usize - body.as_ptr() as usize;
+    let end = start + part.content.len();
+    let mut replaced = BytesMut::with_capacity(body.len() + value.len());
+    replaced.extend_from_slice(&body[..start]);
+    replaced.extend_from_slice(value.as_bytes());
+    replaced.extend_from_slice(&body[end..]);
+    Some(replaced.freeze())
+}
+
+#[derive(Debug, Default)]
+struct PartHeaders {
+    name: Option<String>,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl PartHeaders {
+    fn parse(part_headers: &[u8]) -> Self {
+        let part_headers = String::from_utf8_lossy(part_headers);
+        let mut headers = Self::default();
+        for line in part_headers.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            if key.trim().eq_ignore_ascii_case("content-disposition") {
+                headers.name = disposition_param(value, "name");
+                headers.file_name = disposition_param(value, "filename");
+            } else if key.trim().eq_ignore_ascii_case("content-type") {
+                headers.content_type = Some(value.trim().to_string());
+            }
+        }
+        headers
+    }
+
+    fn is_file(&self) -> bool {
+        self.file_name.is_some() || self.name.as_deref() == Some("file")
+    }
+}
+
+struct Part<'a> {
+    name: Option<String>,
+    content: &'a [u8],
+}
+
+fn parts<'a>(
+    body: &'a [u8],
+    boundary: &str,
+) -> impl Iterator<Item = Part<'a>> + 'a {
+    let delimiter = format!("--{boundary}").into_bytes();
+    let mut rest = find(body, &delimiter).map(|idx| &body[idx..]);
+    std::iter::from_fn(move || next_part(&mut rest, &delimiter)).filter_map(
+        |part| {
+            let header_end = find(part, b"\r\n\r\n")?;
+            let headers = PartHeaders::parse(&part[..header_end]);
+            let content = &part[header_end + 4..];
+            let content = content.strip_suffix(b"\r\n").unwrap_or(content);
+            Some(Part {
+                name: headers.name,
+                content,
+            })
+        },
+    )
+}
+
+/// Split the next part off `rest`, skipping the preamble and stopping at the
+/// closing delimiter.
+fn next_part<'a>(
+    rest: &mut Option<&'a [u8]>,
+    delimiter: &[u8],
+) -> Option<&'a [u8]> {
+    let current = rest.take()?.strip_prefix(delimiter)?;
+    // closing delimiter
+    if current.starts_with(b"--") {
+        return None;
+    }
+    let current = current.strip_prefix(b"\r\n").unwrap_or(current);
+    if let Some(next) = find(current, delimiter) {
+        *rest = Some(&current[next..]);
+        Some(&current[..next])
+    } else {
+        Some(current)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn disposition_param(value: &str, param: &str) -> Option<String> {
+    value.split(';').find_map(|segment| {
+        let (key, value) = segment.trim().split_once('=')?;
+        if key.trim().eq_ignore_ascii_case(param) {
+            Some(value.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static(
+                "multipart/form-data; boundary=test-boundary",
+            ),
+        );
+        headers
+    }
+
+    #[test]
+    fn parses_transcription_request() {
+        let body = b"--test-boundary\r\n\
+            Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+            whisper-1\r\n\
+            --test-boundary\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"audio.mp3\"\r\n\
+            Content-Type: audio/mpeg\r\n\r\n\
+            \x00\x01\x02\x03\xff\r\n\
+            --test-boundary--\r\n";
+        let metadata = MultipartMetadata::from_request(&headers(), body)
+            .expect("is multipart");
+        assert_eq!(metadata.model.as_deref(), Some("whisper-1"));
+        assert_eq!(metadata.file_name.as_deref(), Some("audio.mp3"));
+        assert_eq!(metadata.file_content_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(metadata.file_size, 5);
+    }
+
+    #[test]
+    fn replaces_model_field() {
+        let body = b"--test-boundary\r\n\
+            Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+            openai/whisper-1\r\n\
+            --test-boundary\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"audio.mp3\"\r\n\
+            Content-Type: audio/mpeg\r\n\r\n\
+            \x00\x01\x02\x03\xff\r\n\
+            --test-boundary--\r\n";
+        let boundary = boundary_from_body(body).expect("has a boundary");
+        assert_eq!(boundary, "test-boundary");
+        assert_eq!(
+            field(body, boundary, "model").as_deref(),
+            Some("openai/whisper-1")
+        );
+
+        let replaced = replace_field(body, boundary, "model", "whisper-1")
+            .expect("has a model field");
+        assert_eq!(
+            field(&replaced, boundary, "model").as_deref(),
+            Some("whisper-1")
+        );
+        let metadata = MultipartMetadata::from_request(&headers(), &replaced)
+            .expect("is multipart");
+        assert_eq!(metadata.file_size, 5);
+        assert!(replace_field(body, boundary, "language", "en").is_none());
+    }
+
+    #[tokio::test]
+    async fn tee_summarizes_body_as_it_is_forwarded() {
+        let body = b"preamble\r\n\
+            --test-boundary\r\n\
+            Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+            whisper-1\r\n\
+            --test-boundary\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"audio.mp3\"\r\n\
+            Content-Type: audio/mpeg\r\n\r\n\
+            \x00\x01\x02\r\n\x03\xff\r\n\
+            --test-boundary\r\n\
+            Content-Disposition: form-data; name=\"language\"\r\n\r\n\
+            en\r\n\
+            --test-boundary--\r\n";
+        // small enough that delimiters are split across chunks
+        let chunks = body
+            .chunks(3)
+            .map(|chunk| {
+                Ok::<_, std::convert::Infallible>(Bytes::copy_from_slice(chunk))
+            })
+            .collect::<Vec<_>>();
+        let (forwarded, metadata) =
+            tee(futures::stream::iter(chunks), "test-boundary", Some(2));
+        let forwarded = forwarded
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(forwarded, body);
+
+        let metadata = metadata.await.expect("sent once the body is dropped");
+        assert_eq!(metadata.model.as_deref(), Some("whisper-1"));
+        assert_eq!(metadata.file_name.as_deref(), Some("audio.mp3"));
+        assert_eq!(metadata.file_content_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(metadata.file_size, 7);
+        assert_eq!(metadata.file_prefix.as_deref(), Some("AAE="));
+        assert_eq!(metadata.body_size, body.len());
+    }
+
+    #[test]
+    fn non_multipart_is_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        assert!(!is_multipart(&headers));
+        assert!(MultipartMetadata::from_request(&headers, b"{}").is_none());
+    }
+
+    #[test]
+    fn duration_from_verbose_response() {
+        let mut metadata = MultipartMetadata::default();
+        metadata
+            .set_duration_from_response(br#"{"text":"hi","duration":8.47}"#);
+        assert!(
+            metadata
+                .duration
+                .is_some_and(|d| (d - 8.47).abs() < f64::EPSILON)
+        );
+    }
+}
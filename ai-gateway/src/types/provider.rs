@@ -76,6 +76,17 @@ pub enum InferenceProvider {
     Ollama,
     #[serde(rename = "gemini")]
     GoogleGemini,
+    Cohere,
+    Mistral,
+    Groq,
+    #[serde(rename = "xai")]
+    XAI,
+    #[serde(rename = "deepseek")]
+    DeepSeek,
+    #[serde(rename = "azure")]
+    AzureOpenAI,
+    #[serde(rename = "vertex")]
+    VertexAI,
     #[serde(untagged)]
     Named(CompactString),
 }
@@ -109,7 +120,28 @@ impl InferenceProvider {
                     .map(ApiEndpoint::Google)
                     .collect()
             }
-            InferenceProvider::Named(_) => {
+            InferenceProvider::Cohere => {
+                crate::endpoints::cohere::Cohere::iter()
+                    .map(ApiEndpoint::Cohere)
+                    .collect()
+            }
+            InferenceProvider::AzureOpenAI => {
+                crate::endpoints::azure::Azure::iter()
+                    .map(ApiEndpoint::Azure)
+                    .collect()
+            }
+            InferenceProvider::VertexAI => {
+                crate::endpoints::vertex::Vertex::iter()
+                    .map(ApiEndpoint::Vertex)
+                    .collect()
+            }
+            // Mistral, Groq, xAI and DeepSeek are OpenAI compatible, with
+            // small differences handled by their converters
+            InferenceProvider::Mistral
+            | InferenceProvider::Groq
+            | InferenceProvider::XAI
+            | InferenceProvider::DeepSeek
+            | InferenceProvider::Named(_) => {
                 crate::endpoints::openai::OpenAI::iter()
                     .map(|endpoint| ApiEndpoint::OpenAICompatible {
                         provider: self.clone(),
@@ -129,6 +161,13 @@ impl InferenceProvider {
             "AWS Bedrock" => Ok(InferenceProvider::Bedrock),
             "Ollama" => Ok(InferenceProvider::Ollama),
             "Google AI (Gemini)" => Ok(InferenceProvider::GoogleGemini),
+            "Cohere" => Ok(InferenceProvider::Cohere),
+            "Mistral" => Ok(InferenceProvider::Mistral),
+            "Groq" => Ok(InferenceProvider::Groq),
+            "xAI" => Ok(InferenceProvider::XAI),
+            "DeepSeek" => Ok(InferenceProvider::DeepSeek),
+            "Azure OpenAI" => Ok(InferenceProvider::AzureOpenAI),
+            "Google Vertex AI" => Ok(InferenceProvider::VertexAI),
             _ => Err(ProviderError::InvalidProviderName(provider_name.into())),
         }
     }
@@ -146,6 +185,13 @@ impl FromStr for InferenceProvider {
             "bedrock" => Ok(InferenceProvider::Bedrock),
             "ollama" => Ok(InferenceProvider::Ollama),
             "gemini" => Ok(InferenceProvider::GoogleGemini),
+            "cohere" => Ok(InferenceProvider::Cohere),
+            "mistral" => Ok(InferenceProvider::Mistral),
+            "groq" => Ok(InferenceProvider::Groq),
+            "xai" => Ok(InferenceProvider::XAI),
+            "deepseek" => Ok(InferenceProvider::DeepSeek),
+            "azure" => Ok(InferenceProvider::AzureOpenAI),
+            "vertex" => Ok(InferenceProvider::VertexAI),
             s => Ok(InferenceProvider::Named(s.into())),
         }
     }
@@ -160,6 +206,13 @@ impl AsRef<str> for InferenceProvider {
             InferenceProvider::Bedrock => "bedrock",
             InferenceProvider::Ollama => "ollama",
             InferenceProvider::GoogleGemini => "gemini",
+            InferenceProvider::Cohere => "cohere",
+            InferenceProvider::Mistral => "mistral",
+            InferenceProvider::Groq => "groq",
+            InferenceProvider::XAI => "xai",
+            InferenceProvider::DeepSeek => "deepseek",
+            InferenceProvider::AzureOpenAI => "azure",
+            InferenceProvider::VertexAI => "vertex",
         }
     }
 }
@@ -173,13 +226,23 @@ impl std::fmt::Display for InferenceProvider {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Serialized with its secrets masked, e.g. for `GET /admin/config`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ProviderKey {
     Secret(Secret<String>),
     AwsCredentials {
         access_key: Secret<String>,
         secret_key: Secret<String>,
+        /// Set for temporary credentials, e.g. from an assumed role.
+        session_token: Option<Secret<String>>,
     },
+    /// A Google service account key, as the JSON downloaded from the Google
+    /// Cloud console.
+    GoogleServiceAccount(Secret<String>),
+    /// Access tokens are fetched from the metadata server of the instance the
+    /// gateway runs on, e.g. with GKE workload identity.
+    GoogleWorkloadIdentity,
     NotRequired,
 }
 
@@ -200,25 +263,28 @@ impl ProviderKey {
             ProviderKey::AwsCredentials {
                 access_key,
                 secret_key,
+                ..
             } => (Some(access_key), Some(secret_key)),
             _ => (None, None),
         }
     }
 
+    #[must_use]
+    pub fn aws_session_token(&self) -> Option<&Secret<String>> {
+        match self {
+            ProviderKey::AwsCredentials { session_token, .. } => {
+                session_token.as_ref()
+            }
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn from_env(provider: &InferenceProvider) -> Option<Self> {
         if *provider == InferenceProvider::Bedrock {
-            if let (Ok(access_key), Ok(secret_key)) = (
-                std::env::var("AWS_ACCESS_KEY"),
-                std::env::var("AWS_SECRET_KEY"),
-            ) {
-                Some(ProviderKey::AwsCredentials {
-                    access_key: Secret::from(access_key),
-                    secret_key: Secret::from(secret_key),
-                })
-            } else {
-                None
-            }
+            aws_credentials_from_env().or_else(aws_credentials_from_profile)
+        } else if *provider == InferenceProvider::VertexAI {
+            google_credentials_from_env()
         } else {
             let provider_str = provider.to_string().to_uppercase();
             let env_var = format!("{provider_str}_API_KEY");
@@ -231,9 +297,93 @@ impl ProviderKey {
     }
 }
 
+/// Reads AWS credentials from the standard environment variables, falling
+/// back to the `AWS_ACCESS_KEY` and `AWS_SECRET_KEY` variables we've always
+/// supported.
+fn aws_credentials_from_env() -> Option<ProviderKey> {
+    let access_key = std::env::var("AWS_ACCESS_KEY_ID")
+        .or_else(|_| std::env::var("AWS_ACCESS_KEY"))
+        .ok()?;
+    let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY")
+        .or_else(|_| std::env::var("AWS_SECRET_KEY"))
+        .ok()?;
+    let session_token = std::env::var("AWS_SESSION_TOKEN").ok();
+    Some(ProviderKey::AwsCredentials {
+        access_key: Secret::from(access_key),
+        secret_key: Secret::from(secret_key),
+        session_token: session_token.map(Secret::from),
+    })
+}
+
+/// Reads the service account key at `GOOGLE_APPLICATION_CREDENTIALS`,
+/// falling back to workload identity when it isn't set, as Google's own
+/// clients do.
+fn google_credentials_from_env() -> Option<ProviderKey> {
+    let Ok(path) = std::env::var("GOOGLE_APPLICATION_CREDENTIALS") else {
+        return Some(ProviderKey::GoogleWorkloadIdentity);
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(key) => Some(ProviderKey::GoogleServiceAccount(Secret::from(key))),
+        Err(e) => {
+            tracing::warn!(path = %path, error = %e, "failed to read google service account key");
+            None
+        }
+    }
+}
+
+/// Reads AWS credentials for `AWS_PROFILE` (or the default profile) from the
+/// shared credentials file.
+fn aws_credentials_from_profile() -> Option<ProviderKey> {
+    let path =
+        std::env::var("AWS_SHARED_CREDENTIALS_FILE")
+            .ok()
+            .or_else(|| {
+                std::env::var("HOME")
+                    .ok()
+                    .map(|home| format!("{home}/.aws/credentials"))
+            })?;
+    let profile =
+        std::env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+    let contents = std::fs::read_to_string(path).ok()?;
+    parse_aws_credentials_file(&contents, &profile)
+}
+
+fn parse_aws_credentials_file(
+    contents: &str,
+    profile: &str,
+) -> Option<ProviderKey> {
+    let mut in_profile = false;
+    let mut values = HashMap::default();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) =
+            line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))
+        {
+            in_profile = section.trim() == profile;
+            continue;
+        }
+        if in_profile && let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    Some(ProviderKey::AwsCredentials {
+        access_key: Secret::from(values.remove("aws_access_key_id")?),
+        secret_key: Secret::from(values.remove("aws_secret_access_key")?),
+        session_token: values.remove("aws_session_token").map(Secret::from),
+    })
+}
+
 #[derive(Debug)]
 pub enum ProviderKeys {
-    Cloud(RwLock<HashMap<OrgId, ProviderKeyMap>>),
+    Cloud {
+        /// The keys each organization stored, for the organizations whose
+        /// keys have been fetched.
+        orgs: RwLock<HashMap<OrgId, ProviderKeyMap>>,
+        /// The platform's keys, used for organizations without their own.
+        platform: ProviderKeyMap,
+    },
     Sidecar(ProviderKeyMap),
 }
 
@@ -241,7 +391,10 @@ impl ProviderKeys {
     #[must_use]
     pub fn new(config: &Config) -> Self {
         if config.deployment_target == DeploymentTarget::Cloud {
-            Self::Cloud(RwLock::new(HashMap::default()))
+            Self::Cloud {
+                orgs: RwLock::new(HashMap::default()),
+                platform: ProviderKeyMap::from_env(&config.providers),
+            }
         } else {
             Self::Sidecar(ProviderKeyMap::from_env(&config.providers))
         }
@@ -252,8 +405,8 @@ impl ProviderKeys {
         provider_keys: HashMap<OrgId, ProviderKeyMap>,
     ) {
         match self {
-            ProviderKeys::Cloud(keys) => {
-                let mut keys = keys.write().await;
+            ProviderKeys::Cloud { orgs, .. } => {
+                let mut keys = orgs.write().await;
                 *keys = provider_keys;
             }
             ProviderKeys::Sidecar(_) => {}
@@ -266,32 +419,74 @@ impl ProviderKeys {
         provider_keys: ProviderKeyMap,
     ) {
         match self {
-            ProviderKeys::Cloud(keys) => {
-                let mut keys = keys.write().await;
+            ProviderKeys::Cloud { orgs, .. } => {
+                let mut keys = orgs.write().await;
                 keys.insert(org_id, provider_keys);
             }
             ProviderKeys::Sidecar(_) => {}
         }
     }
 
+    /// The organization's key for the provider in the cloud, or the
+    /// platform's key without an organization.
     pub async fn get_provider_key(
         &self,
         provider: &InferenceProvider,
         org_id: Option<&OrgId>,
     ) -> Option<ProviderKey> {
         match self {
-            ProviderKeys::Cloud(keys) => {
+            ProviderKeys::Cloud { orgs, platform } => {
                 if let Some(org_id) = org_id {
-                    let keys = keys.read().await;
+                    let keys = orgs.read().await;
                     let org_keys = keys.get(org_id);
                     org_keys.and_then(|keys| keys.get(provider)).cloned()
                 } else {
-                    None
+                    platform.get(provider).cloned()
                 }
             }
             ProviderKeys::Sidecar(keys) => keys.get(provider).cloned(),
         }
     }
+
+    /// The key to call the provider with on behalf of the organization: its
+    /// own key in the cloud, if it stored one, and the platform's otherwise.
+    ///
+    /// The organization's keys are fetched with `fetch_org_keys` the first
+    /// time they're needed, then cached until they're next set, including
+    /// when it has no key for the provider.
+    pub async fn resolve<F, Fut, E>(
+        &self,
+        provider: &InferenceProvider,
+        org_id: Option<&OrgId>,
+        fetch_org_keys: F,
+    ) -> Result<Option<ProviderKey>, E>
+    where
+        F: FnOnce(OrgId) -> Fut,
+        Fut: Future<Output = Result<ProviderKeyMap, E>>,
+    {
+        let ProviderKeys::Cloud { orgs, platform } = self else {
+            return Ok(self.get_provider_key(provider, org_id).await);
+        };
+        if let Some(org_id) = org_id {
+            let cached = orgs.read().await.get(org_id).cloned();
+            let org_keys = match cached {
+                Some(org_keys) => org_keys,
+                None => {
+                    let org_keys = fetch_org_keys(*org_id).await?;
+                    orgs.write().await.insert(*org_id, org_keys.clone());
+                    org_keys
+                }
+            };
+            let key = org_keys.get(provider).filter(|key| match key {
+                ProviderKey::Secret(key) => !key.expose().is_empty(),
+                _ => true,
+            });
+            if let Some(key) = key {
+                return Ok(Some(key.clone()));
+            }
+        }
+        Ok(platform.get(provider).cloned())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -331,10 +526,151 @@ impl ProviderKeyMap {
     }
 }
 
+/// A throwaway RSA key for signing test service account assertions.
+#[cfg(feature = "testing")]
+const TEST_GOOGLE_PRIVATE_KEY: &str =
+    include_str!("../tests/vertex_test_key.pem");
+
+#[cfg(feature = "testing")]
+impl ProviderKeyMap {
+    /// Placeholder keys for every provider in the given config that
+    /// requires one.
+    #[must_use]
+    pub fn test_keys(providers_config: &ProvidersConfig) -> Self {
+        let keys = providers_config
+            .keys()
+            .filter(|provider| **provider != InferenceProvider::Ollama)
+            .map(|provider| {
+                let key = if *provider == InferenceProvider::Bedrock {
+                    ProviderKey::AwsCredentials {
+                        access_key: Secret::from("test-access-key".to_string()),
+                        secret_key: Secret::from("test-secret-key".to_string()),
+                        session_token: None,
+                    }
+                } else if *provider == InferenceProvider::VertexAI {
+                    // tokens are fetched from the provider's (mock) server
+                    let token_uri = providers_config
+                        .get(provider)
+                        .and_then(|config| config.base_url.join("token").ok())
+                        .map(String::from)
+                        .unwrap_or_default();
+                    let service_account = serde_json::json!({
+                        "type": "service_account",
+                        "client_email": "test@test-project.iam.gserviceaccount.com",
+                        "private_key": TEST_GOOGLE_PRIVATE_KEY,
+                        "token_uri": token_uri,
+                    });
+                    ProviderKey::GoogleServiceAccount(Secret::from(
+                        service_account.to_string(),
+                    ))
+                } else {
+                    ProviderKey::Secret(Secret::from(format!(
+                        "test-{provider}-key"
+                    )))
+                };
+                (provider.clone(), key)
+            })
+            .collect();
+        Self(Arc::new(keys))
+    }
+}
+
+#[cfg(feature = "testing")]
+impl crate::tests::TestDefault for ProviderKeyMap {
+    fn test_default() -> Self {
+        Self::test_keys(&ProvidersConfig::default())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use uuid::Uuid;
+
     use super::*;
 
+    fn secret(key: &str) -> ProviderKey {
+        ProviderKey::Secret(Secret::from(key.to_string()))
+    }
+
+    fn exposed(key: Option<ProviderKey>) -> Option<String> {
+        match key? {
+            ProviderKey::Secret(key) => Some(key.expose().to_string()),
+            _ => None,
+        }
+    }
+
+    fn cloud_keys() -> ProviderKeys {
+        let platform = HashMap::from_iter([(
+            InferenceProvider::OpenAI,
+            secret("sk-platform-openai-key"),
+        )]);
+        ProviderKeys::Cloud {
+            orgs: RwLock::default(),
+            platform: ProviderKeyMap::from_db(platform),
+        }
+    }
+
+    /// Resolves the key for the provider, counting the fetches of the
+    /// organization's keys, which has only an Anthropic key.
+    async fn resolve_counting_fetches(
+        keys: &ProviderKeys,
+        provider: &InferenceProvider,
+        org_id: &OrgId,
+        fetches: &AtomicUsize,
+    ) -> Option<ProviderKey> {
+        keys.resolve(provider, Some(org_id), |_| async {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            let org_keys = HashMap::from_iter([(
+                InferenceProvider::Anthropic,
+                secret("sk-org-anthropic-key"),
+            )]);
+            Ok::<_, ()>(ProviderKeyMap::from_db(org_keys))
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cloud_orgs_use_their_own_key_or_the_platform_key() {
+        let keys = cloud_keys();
+        let org_id = OrgId::new(Uuid::new_v4());
+        let fetches = AtomicUsize::new(0);
+
+        let anthropic = InferenceProvider::Anthropic;
+        let key =
+            resolve_counting_fetches(&keys, &anthropic, &org_id, &fetches)
+                .await;
+        assert_eq!(exposed(key).as_deref(), Some("sk-org-anthropic-key"));
+        let openai = InferenceProvider::OpenAI;
+        for _ in 0..2 {
+            let key =
+                resolve_counting_fetches(&keys, &openai, &org_id, &fetches)
+                    .await;
+            assert_eq!(exposed(key).as_deref(), Some("sk-platform-openai-key"));
+        }
+        // the org's lack of an OpenAI key is cached along with its keys
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn sidecar_keys_are_never_fetched() {
+        let keys =
+            ProviderKeys::Sidecar(ProviderKeyMap::from_db(HashMap::from_iter(
+                [(InferenceProvider::OpenAI, secret("sk-sidecar-openai-key"))],
+            )));
+        let key = keys
+            .resolve(
+                &InferenceProvider::OpenAI,
+                Some(&OrgId::new(Uuid::new_v4())),
+                |_| async { Err(()) },
+            )
+            .await
+            .unwrap();
+        assert_eq!(exposed(key).as_deref(), Some("sk-sidecar-openai-key"));
+    }
+
     #[test]
     fn inference_provider_as_ref() {
         let named_provider = InferenceProvider::Named("test".into());
@@ -348,4 +684,30 @@ mod tests {
         let named_provider_str = named_provider.to_string();
         assert_eq!("test", named_provider_str);
     }
+
+    #[test]
+    fn aws_credentials_are_read_from_profile() {
+        let contents = "
+[default]
+aws_access_key_id = default-access-key
+aws_secret_access_key = default-secret-key
+
+[prod]
+aws_access_key_id = prod-access-key
+aws_secret_access_key = prod-secret-key
+aws_session_token = prod-session-token
+";
+        let key = parse_aws_credentials_file(contents, "prod").unwrap();
+        let (access_key, secret_key) = key.as_aws_credentials();
+        assert_eq!(access_key.unwrap().expose(), "prod-access-key");
+        assert_eq!(secret_key.unwrap().expose(), "prod-secret-key");
+        assert_eq!(
+            key.aws_session_token().unwrap().expose(),
+            "prod-session-token"
+        );
+
+        let key = parse_aws_credentials_file(contents, "default").unwrap();
+        assert!(key.aws_session_token().is_none());
+        assert!(parse_aws_credentials_file(contents, "missing").is_none());
+    }
 }
@@ -4,6 +4,7 @@ use std::{
     task::{Context, Poll},
 };
 
+use futures::future::BoxFuture;
 use pin_project_lite::pin_project;
 use rustc_hash::FxHashMap as HashMap;
 use tokio::sync::{
@@ -26,6 +27,9 @@ pub struct RateLimitEvent {
     pub api_endpoint: ApiEndpoint,
     pub model_id: Option<ModelId>,
     pub retry_after_seconds: Option<u64>,
+    /// Set when the provider wasn't rate limited, but reported the fraction
+    /// of its rate limits it has remaining.
+    pub remaining_capacity: Option<f64>,
 }
 
 impl RateLimitEvent {
@@ -38,6 +42,20 @@ impl RateLimitEvent {
             api_endpoint,
             model_id: None,
             retry_after_seconds,
+            remaining_capacity: None,
+        }
+    }
+
+    #[must_use]
+    pub fn remaining_capacity(
+        api_endpoint: ApiEndpoint,
+        remaining_capacity: f64,
+    ) -> Self {
+        Self {
+            api_endpoint,
+            model_id: None,
+            retry_after_seconds: None,
+            remaining_capacity: Some(remaining_capacity),
         }
     }
 
@@ -54,8 +72,10 @@ pin_project! {
     pub struct ProviderRestore<K> {
         pub key: Option<K>,
         pub api_endpoint: ApiEndpoint,
+        /// Completes once the provider should be re-added, see
+        /// [`Clock::sleep`](crate::utils::clock::Clock::sleep).
         #[pin]
-        pub timer: tokio::time::Sleep,
+        pub timer: BoxFuture<'static, ()>,
     }
 }
 
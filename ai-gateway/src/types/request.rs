@@ -1,3 +1,15 @@
 use axum_core::body::Body;
+use serde::Deserialize;
 
 pub type Request = http::Request<Body>;
+
+/// Returns `true` if the request body has `"stream": true` set.
+#[must_use]
+pub fn is_stream(body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct StreamFlag {
+        #[serde(default)]
+        stream: bool,
+    }
+    serde_json::from_slice::<StreamFlag>(body).is_ok_and(|flag| flag.stream)
+}
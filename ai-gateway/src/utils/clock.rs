@@ -0,0 +1,101 @@
+//! The current time, as seen by behaviour that depends on it, e.g. provider
+//! schedules, re-adding rate limited providers and cached responses
+//! expiring.
+//!
+//! Tests stop the clock and move it forward rather than waiting for the time
+//! to pass.
+#[cfg(any(test, feature = "testing"))]
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+#[cfg(any(test, feature = "testing"))]
+use tokio::sync::watch;
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    /// Set by tests to stop the clock at a given time, which is then only
+    /// moved by the tests.
+    #[cfg(any(test, feature = "testing"))]
+    mock: Arc<watch::Sender<Option<DateTime<Utc>>>>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            #[cfg(any(test, feature = "testing"))]
+            mock: Arc::new(watch::channel(None).0),
+        }
+    }
+}
+
+impl Clock {
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(now) = *self.mock.borrow() {
+            return now;
+        }
+        Utc::now()
+    }
+
+    /// Completes once `duration` has passed, by the clock as it is when
+    /// called.
+    #[must_use]
+    pub fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(start) = *self.mock.borrow() {
+            let deadline = chrono::Duration::from_std(duration)
+                .ok()
+                .and_then(|duration| start.checked_add_signed(duration))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            let mut mock = self.mock.subscribe();
+            return Box::pin(async move {
+                while mock.borrow_and_update().is_some_and(|now| now < deadline)
+                {
+                    // the clock can't move once every clone is dropped
+                    if mock.changed().await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    /// Stops the clock at `now`, for every clone of the clock.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.mock.send_replace(Some(now));
+    }
+
+    /// Moves the clock forward by `duration`, completing the sleeps that end
+    /// by then. Stops the clock first if it isn't already.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn advance(&self, duration: Duration) {
+        let now = self.now()
+            + chrono::Duration::from_std(duration)
+                .expect("duration out of range");
+        self.set(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sleeps_complete_once_the_clock_is_advanced_past_them() {
+        let clock = Clock::default();
+        clock.set(Utc::now());
+        let mut sleep = clock.sleep(Duration::from_secs(60));
+        let started = std::time::Instant::now();
+
+        clock.advance(Duration::from_secs(59));
+        assert!(futures::poll!(sleep.as_mut()).is_pending());
+        clock.advance(Duration::from_secs(1));
+        sleep.await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,132 @@
+//! The [gRPC health checking protocol], served alongside the HTTP listener
+//! for service meshes which only speak gRPC.
+//!
+//! - The `""` service reports liveness, and is serving for as long as the
+//!   gateway is up.
+//! - The [`READINESS_SERVICE`] reports whether the gateway is ready for
+//!   traffic, see [`is_ready`].
+//!
+//! Server reflection is enabled so that tools like `grpcurl` can discover the
+//! health service without the protobuf definitions.
+//!
+//! [gRPC health checking protocol]: https://github.com/grpc/grpc/blob/master/doc/health-checking.md
+use std::{net::SocketAddr, time::Duration};
+
+use futures::future::BoxFuture;
+use meltdown::Token;
+use tokio::{net::TcpListener, time};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic_health::{ServingStatus, server::HealthReporter};
+use tracing::{debug, info};
+
+use crate::{
+    app_state::AppState,
+    config::DeploymentTarget,
+    error::{init::InitError, runtime::RuntimeError},
+};
+
+/// The service name to check for readiness.
+pub const READINESS_SERVICE: &str = "readiness";
+const READINESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct GrpcHealthServer {
+    app_state: AppState,
+}
+
+impl GrpcHealthServer {
+    #[must_use]
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    /// Serve health checks on `listener` until `shutdown` completes.
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()> + Send,
+    ) -> Result<(), RuntimeError> {
+        let (mut reporter, health_service) =
+            tonic_health::server::health_reporter();
+        let reflection_service = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(
+                tonic_health::pb::FILE_DESCRIPTOR_SET,
+            )
+            .build_v1()
+            .map_err(InitError::GrpcReflection)?;
+        // report readiness before accepting connections so that the first
+        // check is accurate
+        set_readiness(&mut reporter, &self.app_state).await;
+
+        let server = Server::builder()
+            .add_service(health_service)
+            .add_service(reflection_service)
+            .serve_with_incoming_shutdown(
+                TcpListenerStream::new(listener),
+                shutdown,
+            );
+        tokio::select! {
+            result = server => result.map_err(RuntimeError::GrpcServe),
+            () = refresh_readiness(reporter, self.app_state) => Ok(()),
+        }
+    }
+}
+
+/// Whether the gateway is ready for traffic.
+///
+/// Without Helicone auth there is nothing to wait for. Otherwise, sidecars
+/// are ready once the control plane has sent their config, and cloud
+/// deployments are ready once the control plane has sent a heartbeat.
+pub async fn is_ready(app_state: &AppState) -> bool {
+    let config = app_state.config();
+    if !config.helicone.is_auth_enabled() {
+        return true;
+    }
+    let control_plane_state = app_state.0.control_plane_state.read().await;
+    match config.deployment_target {
+        DeploymentTarget::Sidecar => {
+            !control_plane_state.config.auth.organization_id.is_empty()
+        }
+        DeploymentTarget::Cloud => control_plane_state.last_heartbeat.is_some(),
+    }
+}
+
+async fn set_readiness(reporter: &mut HealthReporter, app_state: &AppState) {
+    let status = if is_ready(app_state).await {
+        ServingStatus::Serving
+    } else {
+        ServingStatus::NotServing
+    };
+    reporter.set_service_status(READINESS_SERVICE, status).await;
+}
+
+async fn refresh_readiness(mut reporter: HealthReporter, app_state: AppState) {
+    let mut interval = time::interval(READINESS_INTERVAL);
+    loop {
+        interval.tick().await;
+        set_readiness(&mut reporter, &app_state).await;
+    }
+}
+
+impl meltdown::Service for GrpcHealthServer {
+    type Future = BoxFuture<'static, Result<(), RuntimeError>>;
+
+    fn run(self, token: Token) -> Self::Future {
+        Box::pin(async move {
+            let server_config = &self.app_state.config().server;
+            let Some(port) = server_config.grpc_health_port else {
+                // returning early would shut down the other services
+                token.await;
+                return Ok(());
+            };
+            let addr = SocketAddr::from((server_config.address, port));
+            let listener =
+                TcpListener::bind(addr).await.map_err(InitError::Bind)?;
+            info!(address = %addr, "grpc health server starting");
+            self.serve(listener, token).await?;
+            debug!(name = "grpc-health-task", "task shut down successfully");
+            Ok(())
+        })
+    }
+}
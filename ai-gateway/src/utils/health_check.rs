@@ -1,34 +1,47 @@
+//! Unauthenticated health endpoints.
+//!
+//! - `GET /health` reports that the gateway is up.
+//! - `GET /health/providers` reports whether each load balanced provider is
+//!   passing the checks done independently of live traffic, see
+//!   [`AppState::passes_provider_checks`], and when it was last probed.
 use std::{
     future::{Ready, ready},
     marker::PhantomData,
     task::{Context, Poll},
 };
 
-use axum_core::response::Response;
-use futures::future::Either;
+use axum_core::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, Either};
 use http::{Method, Request};
+use indexmap::IndexSet;
+use serde::Serialize;
 use tower::{Layer, Service};
 
+use crate::{
+    app_state::AppState,
+    types::{json::Json, provider::InferenceProvider},
+};
+
+const HEALTH_PATH: &str = "/health";
+const PROVIDERS_HEALTH_PATH: &str = "/health/providers";
+
 #[derive(Debug, Clone)]
 pub struct HealthCheckLayer<ReqBody, E> {
+    app_state: AppState,
     _marker: PhantomData<(ReqBody, E)>,
 }
 
 impl<ReqBody, E> HealthCheckLayer<ReqBody, E> {
     #[must_use]
-    pub const fn new() -> Self {
+    pub const fn new(app_state: AppState) -> Self {
         Self {
+            app_state,
             _marker: PhantomData,
         }
     }
 }
 
-impl<ReqBody, E> Default for HealthCheckLayer<ReqBody, E> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl<S, ReqBody, E> Layer<S> for HealthCheckLayer<ReqBody, E>
 where
     S: tower::Service<http::Request<ReqBody>, Response = Response, Error = E>,
@@ -36,13 +49,14 @@ where
     type Service = HealthCheck<S, ReqBody, E>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        HealthCheck::new(inner)
+        HealthCheck::new(inner, self.app_state.clone())
     }
 }
 
 #[derive(Debug)]
 pub struct HealthCheck<S, ReqBody, E> {
     inner: S,
+    app_state: AppState,
     _marker: PhantomData<(ReqBody, E)>,
 }
 
@@ -50,6 +64,7 @@ impl<S: Clone, ReqBody, E> Clone for HealthCheck<S, ReqBody, E> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            app_state: self.app_state.clone(),
             _marker: PhantomData,
         }
     }
@@ -59,9 +74,10 @@ impl<S, ReqBody, E> HealthCheck<S, ReqBody, E>
 where
     S: tower::Service<http::Request<ReqBody>, Response = Response, Error = E>,
 {
-    pub const fn new(inner: S) -> Self {
+    pub const fn new(inner: S, app_state: AppState) -> Self {
         Self {
             inner,
+            app_state,
             _marker: PhantomData,
         }
     }
@@ -73,11 +89,18 @@ where
         + Send
         + 'static,
     S::Future: Send + 'static,
+    S::Error: Send + 'static,
     ReqBody: Send + 'static,
 {
     type Response = Response;
     type Error = S::Error;
-    type Future = Either<Ready<Result<Self::Response, Self::Error>>, S::Future>;
+    type Future = Either<
+        Either<
+            Ready<Result<Self::Response, Self::Error>>,
+            BoxFuture<'static, Result<Self::Response, Self::Error>>,
+        >,
+        S::Future,
+    >;
 
     fn poll_ready(
         &mut self,
@@ -87,12 +110,70 @@ where
     }
 
     fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
-        if req.method() == Method::GET && req.uri().path() == "/health" {
-            Either::Left(ready(Ok(healthy_response())))
-        } else {
-            Either::Right(self.inner.call(req))
+        if req.method() != Method::GET {
+            return Either::Right(self.inner.call(req));
         }
+        match req.uri().path() {
+            HEALTH_PATH => {
+                Either::Left(Either::Left(ready(Ok(healthy_response()))))
+            }
+            PROVIDERS_HEALTH_PATH => {
+                let app_state = self.app_state.clone();
+                Either::Left(Either::Right(Box::pin(async move {
+                    Ok(Json(providers_health(&app_state).await).into_response())
+                })))
+            }
+            _ => Either::Right(self.inner.call(req)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderHealth {
+    pub provider: InferenceProvider,
+    pub status: ProviderStatus,
+    /// `None` if the provider hasn't been probed, e.g. because probes are
+    /// disabled.
+    pub last_checked: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProvidersHealth {
+    pub providers: Vec<ProviderHealth>,
+}
+
+async fn providers_health(app_state: &AppState) -> ProvidersHealth {
+    let providers = app_state
+        .0
+        .router_configs
+        .read()
+        .await
+        .values()
+        .flat_map(|router_config| router_config.load_balance.providers())
+        .collect::<IndexSet<_>>();
+    let mut health = Vec::with_capacity(providers.len());
+    for provider in providers {
+        let status = if app_state.passes_provider_checks(&provider).await {
+            ProviderStatus::Up
+        } else {
+            ProviderStatus::Down
+        };
+        let last_checked =
+            app_state.0.last_probed.read().await.get(&provider).copied();
+        health.push(ProviderHealth {
+            provider,
+            status,
+            last_checked,
+        });
     }
+    ProvidersHealth { providers: health }
 }
 
 fn healthy_response() -> Response {
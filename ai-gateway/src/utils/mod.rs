@@ -1,4 +1,6 @@
 pub mod catch_panic;
+pub mod clock;
+pub mod grpc_health;
 pub mod handle_error;
 pub mod health_check;
 pub mod meltdown;
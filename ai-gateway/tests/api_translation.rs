@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        api_translation::ApiTranslation,
+        balance::BalanceConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use axum_core::body::Body;
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn responses_request_is_served_by_chat_completions() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing api translation
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            api_translation: Some(ApiTranslation::ResponsesToChat),
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "instructions": "You are a helpful assistant.",
+            "input": "Hello, world!"
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/responses")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["object"], "response");
+    assert_eq!(body["status"], "completed");
+    let message = &body["output"][0];
+    assert_eq!(message["type"], "message");
+    assert_eq!(message["role"], "assistant");
+    assert_eq!(message["content"][0]["type"], "output_text");
+    assert_eq!(
+        message["content"][0]["text"],
+        "Hello! How can I assist you today?"
+    );
+    assert_eq!(body["usage"]["input_tokens"], 19);
+    assert_eq!(body["usage"]["output_tokens"], 10);
+    assert_eq!(body["usage"]["total_tokens"], 29);
+}
+
+fn responses_harness_config(router_config: RouterConfig) -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        router_config,
+    )]));
+    config
+}
+
+fn responses_request(uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(&body).unwrap()))
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn responses_request_is_served_by_anthropic() {
+    // no api translation is needed for providers other than OpenAI
+    let config = responses_harness_config(RouterConfig {
+        load_balance: BalanceConfig::anthropic_chat(),
+        ..Default::default()
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = responses_request(
+        "http://router.helicone.com/router/my-router/responses",
+        json!({
+            "model": "anthropic/claude-3-7-sonnet",
+            "input": "Hello, world!"
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["object"], "response");
+    assert_eq!(
+        body["output"][0]["content"][0]["text"],
+        "Hi! My name is Claude."
+    );
+    assert_eq!(body["usage"]["input_tokens"], 2095);
+    assert_eq!(body["usage"]["output_tokens"], 503);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_serves_responses_requests() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = responses_request(
+        "http://router.helicone.com/ai/responses",
+        json!({
+            "model": "anthropic/claude-sonnet-4-0",
+            "input": "Hello, world!"
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["object"], "response");
+    assert_eq!(body["output"][0]["type"], "message");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn responses_function_calls_are_sent_as_chat_tool_calls() {
+    let config = responses_harness_config(RouterConfig {
+        load_balance: BalanceConfig::openai_chat(),
+        api_translation: Some(ApiTranslation::ResponsesToChat),
+        ..Default::default()
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = responses_request(
+        "http://router.helicone.com/router/my-router/responses",
+        json!({
+            "model": "openai/gpt-4o-mini",
+            "input": [
+                { "role": "user", "content": "What's the weather in Paris?" },
+                {
+                    "type": "function_call",
+                    "call_id": "call_1",
+                    "name": "get_weather",
+                    "arguments": "{\"city\":\"Paris\"}"
+                },
+                {
+                    "type": "function_call_output",
+                    "call_id": "call_1",
+                    "output": "sunny"
+                }
+            ],
+            "tools": [{
+                "type": "function",
+                "name": "get_weather",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } }
+                }
+            }],
+            "tool_choice": "auto"
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/chat/completions")
+        .expect("openai should receive the request");
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(body["tools"][0]["type"], "function");
+    assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(body["tool_choice"], "auto");
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["tool_calls"][0]["id"], "call_1");
+    assert_eq!(
+        messages[1]["tool_calls"][0]["function"]["arguments"],
+        "{\"city\":\"Paris\"}"
+    );
+    assert_eq!(messages[2]["role"], "tool");
+    assert_eq!(messages[2]["tool_call_id"], "call_1");
+    assert_eq!(messages[2]["content"], "sunny");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn responses_stream_uses_responses_events() {
+    let config = responses_harness_config(RouterConfig {
+        load_balance: BalanceConfig::openai_chat(),
+        api_translation: Some(ApiTranslation::ResponsesToChat),
+        ..Default::default()
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = responses_request(
+        "http://router.helicone.com/router/my-router/responses",
+        json!({
+            "model": "openai/gpt-4o-mini",
+            "input": "Hello, world!",
+            "stream": true
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let events = String::from_utf8(body.to_vec())
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter_map(|data| serde_json::from_str(data).ok())
+        .collect::<Vec<serde_json::Value>>();
+    assert_eq!(events[0]["type"], "response.created");
+    assert_eq!(events[0]["response"]["status"], "in_progress");
+    assert_eq!(events[1]["type"], "response.output_text.delta");
+    assert_eq!(events[1]["delta"], "Hello!");
+}
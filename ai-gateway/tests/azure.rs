@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    tests::{
+        harness::Harness,
+        mock::MockArgs,
+        providers::{
+            assert_rate_limit_removes_provider_from_lb_pool, chat_request,
+            config,
+        },
+    },
+    types::provider::InferenceProvider,
+};
+use http::StatusCode;
+use http_body_util::BodyExt;
+use tower::Service;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn azure_request_uses_deployment_and_api_key() {
+    let mut config = config(InferenceProvider::AzureOpenAI, None);
+    config
+        .providers
+        .get_mut(&InferenceProvider::AzureOpenAI)
+        .unwrap()
+        .deployments
+        .insert("gpt-4o-mini".to_string(), "prod-gpt-4o-mini".to_string());
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:azure:chat_completion", 1.into()),
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(chat_request("openai/gpt-4o-mini", false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-provider").unwrap(),
+        "azure"
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["choices"][0]["message"]["content"],
+        "Hello! How can I assist you today?"
+    );
+
+    let received_requests = harness
+        .mock
+        .azure_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .first()
+        .expect("azure should receive the request");
+    assert_eq!(
+        provider_request.url.path(),
+        "/openai/deployments/prod-gpt-4o-mini/chat/completions"
+    );
+    assert_eq!(provider_request.url.query(), Some("api-version=2024-10-21"));
+    assert_eq!(
+        provider_request.headers.get("api-key").unwrap(),
+        "test-azure-key"
+    );
+    assert!(provider_request.headers.get("authorization").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn weighted_balancer_splits_openai_and_azure() {
+    let num_requests = 100;
+    let tolerance = num_requests as f64 * 0.15;
+    let expected_midpt = num_requests as f64 * 0.5;
+    let range = (expected_midpt - tolerance).floor() as u64
+        ..(expected_midpt + tolerance).ceil() as u64;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", range.clone().into()),
+            ("success:azure:chat_completion", range.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(
+            InferenceProvider::AzureOpenAI,
+            Some(InferenceProvider::OpenAI),
+        ))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for _ in 0..num_requests {
+        let response = harness
+            .call(chat_request("openai/gpt-4o-mini", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn azure_rate_limit_removes_provider_from_lb_pool() {
+    assert_rate_limit_removes_provider_from_lb_pool(
+        InferenceProvider::AzureOpenAI,
+        "rate_limit:azure:chat_completion",
+        "success:azure:chat_completion",
+    )
+    .await;
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::BalanceConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    tests::{
+        TestDefault, encode_event_stream_message, harness::Harness,
+        mock::MockArgs,
+    },
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use stubr::wiremock_rs::{Mock, ResponseTemplate, matchers};
+use tower::Service;
+
+const MODEL: &str = "anthropic.claude-3-5-sonnet-20240620-v1:0";
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    // the mock's host doesn't contain a region
+    config
+        .providers
+        .get_mut(&InferenceProvider::Bedrock)
+        .unwrap()
+        .region = Some("us-west-2".to_string());
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::bedrock(),
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn event(event_type: &str, payload: &serde_json::Value) -> Vec<u8> {
+    encode_event_stream_message(
+        &[(":message-type", "event"), (":event-type", event_type)],
+        &serde_json::to_vec(payload).unwrap(),
+    )
+    .to_vec()
+}
+
+async fn assert_signed_for_region(harness: &Harness) {
+    let received_requests = harness
+        .mock
+        .bedrock_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let authorization = received_requests
+        .last()
+        .expect("bedrock should receive the request")
+        .headers
+        .get("authorization")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(authorization.starts_with("AWS4-HMAC-SHA256"));
+    assert!(authorization.contains("/us-west-2/bedrock/aws4_request"));
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn bedrock_event_stream_is_translated() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let body = [
+        event("messageStart", &json!({ "role": "assistant" })),
+        event(
+            "contentBlockDelta",
+            &json!({ "contentBlockIndex": 0, "delta": { "text": "Hello" } }),
+        ),
+        event(
+            "contentBlockDelta",
+            &json!({ "contentBlockIndex": 0, "delta": { "text": " there!" } }),
+        ),
+        event("contentBlockStop", &json!({ "contentBlockIndex": 0 })),
+        event("messageStop", &json!({ "stopReason": "end_turn" })),
+        event(
+            "metadata",
+            &json!({
+                "usage": {
+                    "inputTokens": 10,
+                    "outputTokens": 3,
+                    "totalTokens": 13
+                },
+                "metrics": { "latencyMs": 100 }
+            }),
+        ),
+    ]
+    .concat();
+    Mock::given(matchers::method("POST"))
+        .and(matchers::path_regex("^/model/[^/]+/converse-stream$"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(body, "application/vnd.amazon.eventstream"),
+        )
+        .expect(1)
+        .named("success:bedrock:converse_stream")
+        .mount(&harness.mock.bedrock_mock.http_server)
+        .await;
+
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": format!("bedrock/{MODEL}"),
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": true
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let chunks = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 5);
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    let content = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect::<String>();
+    assert_eq!(content, "Hello there!");
+    assert_eq!(chunks[3]["choices"][0]["finish_reason"], "stop");
+    assert_eq!(chunks[4]["usage"]["total_tokens"], 13);
+
+    assert_signed_for_region(&harness).await;
+}
+
+/// Requests to `/bedrock/...` are signed and forwarded as is.
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn bedrock_direct_proxy() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:bedrock:converse", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "messages": [
+                {
+                    "role": "user",
+                    "content": [{ "text": "Hello, world!" }]
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/bedrock/model/{MODEL}/converse"
+        ))
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["stopReason"], "end_turn");
+
+    assert_signed_for_region(&harness).await;
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config, body_limit::BodyLimitConfig, helicone::HeliconeFeatures,
+        router::RouterConfigs,
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+const LIMIT: u64 = 1024;
+
+fn oversized_body() -> Vec<u8> {
+    serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "a".repeat(4 * 1024)
+            }
+        ]
+    }))
+    .unwrap()
+}
+
+fn chat_request(body: axum_core::body::Body) -> Request<axum_core::body::Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn oversized_bodies_are_rejected_before_reaching_the_provider() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let mut router_config = config
+        .routers
+        .get(&RouterId::Named(CompactString::new("my-router")))
+        .cloned()
+        .unwrap();
+    router_config.body_limit = Some(BodyLimitConfig {
+        max_request_body_bytes: LIMIT,
+        ..Default::default()
+    });
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        router_config,
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // rejected on its content-length
+    let body = oversized_body();
+    let mut request = chat_request(axum_core::body::Body::from(body.clone()));
+    request
+        .headers_mut()
+        .insert(http::header::CONTENT_LENGTH, body.len().into());
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let response_body = response.into_body().collect().await.unwrap();
+    let error: serde_json::Value =
+        serde_json::from_slice(&response_body.to_bytes()).unwrap();
+    assert!(error["error"]["message"].is_string());
+
+    // rejected while it's read, without a content-length
+    let chunks = oversized_body()
+        .chunks(256)
+        .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+        .collect::<Vec<_>>();
+    let body =
+        axum_core::body::Body::from_stream(futures::stream::iter(chunks));
+    let response = harness.call(chat_request(body)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let upstream_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert!(upstream_requests.is_empty());
+}
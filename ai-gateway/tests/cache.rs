@@ -1,7 +1,11 @@
 use std::{collections::HashMap, time::Duration};
 
 use ai_gateway::{
-    config::{Config, cache::CacheConfig, helicone::HeliconeFeatures},
+    config::{
+        Config,
+        cache::{CacheConfig, CacheStore},
+        helicone::HeliconeFeatures,
+    },
     tests::{TestDefault, harness::Harness, mock::MockArgs},
 };
 use http::{Method, Request, StatusCode};
@@ -314,6 +318,7 @@ async fn cache_enabled_per_router() {
                     directive: None,
                     buckets: 1,
                     seed: Some("router-cached-seed".to_string()),
+                    ..Default::default()
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -432,3 +437,351 @@ async fn cache_enabled_per_router() {
          default router"
     );
 }
+
+/// Sends `count` identical requests at once, returning each response's
+/// status and `helicone-cache` header.
+async fn call_concurrently(
+    harness: &mut Harness,
+    count: usize,
+) -> Vec<(StatusCode, Option<String>)> {
+    let handles = (0..count)
+        .map(|_| {
+            let request = make_request(
+                "http://router.helicone.com/router/my-router/chat/completions",
+                Some(("cache-control", "max-age=3600")),
+            );
+            tokio::spawn(harness.call(request))
+        })
+        .collect::<Vec<_>>();
+    let mut responses = Vec::new();
+    for handle in handles {
+        let response = handle.await.unwrap().unwrap();
+        let cache_header = response
+            .headers()
+            .get("helicone-cache")
+            .map(|value| value.to_str().unwrap().to_string());
+        responses.push((response.status(), cache_header));
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+    responses
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn concurrent_identical_requests_are_coalesced() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.global.cache = Some(CacheConfig::test_default());
+
+    let mock_args = MockArgs::builder()
+        .global_openai_latency(200)
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_cacheable", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let responses = call_concurrently(&mut harness, 5).await;
+    assert!(
+        responses
+            .iter()
+            .all(|(status, _)| *status == StatusCode::OK)
+    );
+    let cache_headers = |value: &str| {
+        responses
+            .iter()
+            .filter(|(_, header)| header.as_deref() == Some(value))
+            .count()
+    };
+    assert_eq!(cache_headers("MISS"), 1);
+    assert_eq!(cache_headers("COALESCED"), 4);
+
+    let openai_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert_eq!(openai_requests.len(), 1);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn coalesced_upstream_errors_are_not_cached() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.global.cache = Some(CacheConfig::test_default());
+
+    let mock_args = MockArgs::builder()
+        .global_openai_latency(200)
+        .stubs(HashMap::from([
+            ("internal_error:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // every waiter gets the error
+    let responses = call_concurrently(&mut harness, 5).await;
+    assert!(
+        responses
+            .iter()
+            .all(|(status, _)| *status == StatusCode::INTERNAL_SERVER_ERROR)
+    );
+    let coalesced = responses
+        .iter()
+        .filter(|(_, header)| header.as_deref() == Some("COALESCED"))
+        .count();
+    assert_eq!(coalesced, 4);
+
+    // and the next request calls the provider again, rather than hitting the
+    // cache
+    harness.mock.reset().await;
+    harness
+        .mock
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_cacheable", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .await;
+    let responses = call_concurrently(&mut harness, 1).await;
+    assert_eq!(responses, vec![(StatusCode::OK, Some("MISS".to_string()))]);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn in_memory_cache_is_restored_from_a_snapshot() {
+    let snapshot_path = std::env::temp_dir().join(format!(
+        "ai-gateway-cache-snapshot-{}.json",
+        std::process::id()
+    ));
+    let config = || {
+        let mut config = Config::test_default();
+        config.helicone.features = HeliconeFeatures::None;
+        config.global.cache = Some(CacheConfig::test_default());
+        config.cache_store = Some(CacheStore::InMemory {
+            max_size: 1024 * 1024,
+            snapshot_path: Some(snapshot_path.clone()),
+        });
+        config
+    };
+    let url = "http://router.helicone.com/router/my-router/chat/completions";
+
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_cacheable", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let response = harness
+        .call(make_request(url, Some(("cache-control", "max-age=3600"))))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("helicone-cache").unwrap(), "MISS");
+    let _response_body = response.into_body().collect().await.unwrap();
+    harness.app_factory.state.snapshot_cache().await;
+    drop(harness);
+
+    // a fresh gateway serves the same prompt from the restored cache
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_cacheable", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let response = harness
+        .call(make_request(url, Some(("cache-control", "max-age=3600"))))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("helicone-cache").unwrap(), "HIT");
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    std::fs::remove_file(snapshot_path).unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn cached_responses_expire_once_the_clock_passes_their_max_age() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.global.cache = Some(CacheConfig::test_default());
+    let url = "http://router.helicone.com/router/my-router/chat/completions";
+
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_cacheable", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let clock = harness.app_factory.state.0.clock.clone();
+    clock.set(chrono::Utc::now());
+
+    let mut cache_result = async || {
+        let response = harness
+            .call(make_request(url, Some(("cache-control", "max-age=3600"))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let result = response.headers()["helicone-cache"].clone();
+        let _response_body = response.into_body().collect().await.unwrap();
+        result
+    };
+    assert_eq!(cache_result().await, "MISS");
+    clock.advance(Duration::from_secs(3000));
+    assert_eq!(cache_result().await, "HIT");
+    // past the response's `max-age=3600`, without waiting an hour
+    clock.advance(Duration::from_secs(601));
+    assert_eq!(cache_result().await, "MISS");
+
+    harness.mock.verify().await;
+}
+
+fn make_tool_request(
+    tools: &serde_json::Value,
+    user: &str,
+) -> Request<axum_core::body::Body> {
+    let request_body = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "What's the weather in Paris?"
+            }
+        ],
+        "tools": tools,
+        "user": user
+    }))
+    .unwrap();
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .header("cache-control", "max-age=3600")
+        .body(axum_core::body::Body::from(request_body))
+        .unwrap()
+}
+
+fn tool(name: &str) -> serde_json::Value {
+    json!([{
+        "type": "function",
+        "function": {
+            "name": name,
+            "parameters": { "type": "object", "properties": {} }
+        }
+    }])
+}
+
+async fn cache_header(
+    harness: &mut Harness,
+    request: Request<axum_core::body::Body>,
+) -> String {
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let header = response
+        .headers()
+        .get("helicone-cache")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let _response_body = response.into_body().collect().await.unwrap();
+    header
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn requests_with_different_tools_do_not_share_cached_responses() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.global.cache = Some(CacheConfig {
+        key_fields: Some(
+            ["model", "messages", "tools", "tool_choice", "temperature"]
+                .map(String::from)
+                .to_vec(),
+        ),
+        ..CacheConfig::test_default()
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_cacheable", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let weather = tool("get_weather");
+    let forecast = tool("get_forecast");
+    let request = make_tool_request(&weather, "alice");
+    assert_eq!(cache_header(&mut harness, request).await, "MISS");
+    let request = make_tool_request(&forecast, "alice");
+    assert_eq!(cache_header(&mut harness, request).await, "MISS");
+
+    // `user` isn't a key field, so it doesn't matter
+    let request = make_tool_request(&weather, "bob");
+    assert_eq!(cache_header(&mut harness, request).await, "HIT");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn truncated_completions_are_not_cached() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.global.cache = Some(CacheConfig::test_default());
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_truncated", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // `finish_reason: length`, so the second request goes to the provider too
+    let url = "http://router.helicone.com/router/my-router/chat/completions";
+    let request = make_request(url, None);
+    assert_eq!(cache_header(&mut harness, request).await, "MISS");
+    let request = make_request(url, None);
+    assert_eq!(cache_header(&mut harness, request).await, "MISS");
+}
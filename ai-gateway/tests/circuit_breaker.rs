@@ -0,0 +1,198 @@
+use std::{collections::HashMap, num::NonZeroU32, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        monitor::{CircuitBreakerConfig, GracePeriod, HealthMonitorConfig},
+        router::{RouterConfig, RouterConfigs},
+    },
+    discover::monitor::{circuit_breaker::CircuitState, health::HealthMonitor},
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+const MIN_REQUESTS: u32 = 4;
+const OPEN_DURATION: Duration = Duration::from_millis(500);
+
+fn circuit_breaker_config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    // only the circuit breaker should remove providers
+    config.discover.monitor.health = HealthMonitorConfig::ErrorRatio {
+        ratio: Decimal::ONE,
+        window: Duration::from_secs(10),
+        buckets: 10,
+        interval: Duration::from_millis(1),
+        grace_period: GracePeriod::Requests { min_requests: 1 },
+    };
+    config.discover.monitor.circuit_breaker = Some(CircuitBreakerConfig {
+        error_ratio: Decimal::try_from(0.5).unwrap(),
+        window: Duration::from_secs(10),
+        min_requests: MIN_REQUESTS,
+        open_duration: OPEN_DURATION,
+        half_open_successes: NonZeroU32::new(2).unwrap(),
+    });
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.50).unwrap(),
+                    schedule: None,
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.50).unwrap(),
+                    schedule: None,
+                },
+            ],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn chat_request() -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+/// Sends requests until the provider's circuit is in `state`, returning the
+/// responses' statuses and providers.
+async fn call_until(
+    harness: &mut Harness,
+    state: fn(CircuitState) -> bool,
+) -> Vec<(StatusCode, Option<String>)> {
+    let circuit_breakers = harness.app_factory.state.0.circuit_breakers.clone();
+    let mut responses = Vec::new();
+    for _ in 0..100 {
+        if state(circuit_breakers.state(&InferenceProvider::OpenAI)) {
+            return responses;
+        }
+        let response = harness.call(chat_request()).await.unwrap();
+        let provider = response
+            .headers()
+            .get("helicone-provider")
+            .map(|provider| provider.to_str().unwrap().to_string());
+        responses.push((response.status(), provider));
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+    panic!("the circuit never changed state");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn failing_provider_is_bypassed_until_trial_requests_succeed() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            (
+                "internal_error:openai:chat_completion",
+                u64::from(MIN_REQUESTS).into(),
+            ),
+            ("success:anthropic:messages", (0..).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(circuit_breaker_config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let health_monitor = HealthMonitor::new(harness.app_factory.state.clone());
+    tokio::spawn(async move {
+        health_monitor.run_forever().await.unwrap();
+    });
+
+    // sustained 500s open the circuit
+    let responses = call_until(&mut harness, |state| {
+        matches!(state, CircuitState::Open { .. })
+    })
+    .await;
+    let errors = responses
+        .iter()
+        .filter(|(status, _)| *status == StatusCode::INTERNAL_SERVER_ERROR)
+        .count();
+    assert_eq!(errors, MIN_REQUESTS as usize);
+
+    // let the health monitor remove the provider
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    for _ in 0..10 {
+        let response = harness.call(chat_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("helicone-provider").unwrap(),
+            "anthropic"
+        );
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+    let openai_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert_eq!(openai_requests.len(), MIN_REQUESTS as usize);
+
+    // once the provider has recovered, the circuit closes after the trial
+    // requests succeed
+    harness.mock.reset().await;
+    harness
+        .mock
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", (2..).into()),
+            ("success:anthropic:messages", (0..).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .await;
+    tokio::time::sleep(OPEN_DURATION).await;
+    let responses =
+        call_until(&mut harness, |state| state == CircuitState::Closed).await;
+    assert!(
+        responses
+            .iter()
+            .all(|(status, _)| *status == StatusCode::OK)
+    );
+    let trial_requests = responses
+        .iter()
+        .filter(|(_, provider)| provider.as_deref() == Some("openai"))
+        .count();
+    assert_eq!(trial_requests, 2);
+}
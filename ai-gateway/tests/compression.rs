@@ -0,0 +1,106 @@
+use std::{collections::HashMap, io::Read};
+
+use ai_gateway::{
+    config::{Config, helicone::HeliconeFeatures},
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+};
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+fn chat_request(
+    stream: bool,
+    accept_encoding: Option<&str>,
+) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": stream
+        }))
+        .unwrap(),
+    );
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json");
+    if let Some(accept_encoding) = accept_encoding {
+        request = request.header("accept-encoding", accept_encoding);
+    }
+    request.body(request_body).unwrap()
+}
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    // the stubbed responses are small
+    config.compression.min_size_bytes = 0;
+    config
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn responses_are_compressed_with_an_accepted_encoding() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request(false, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("content-encoding").is_none());
+    let uncompressed = response.into_body().collect().await.unwrap().to_bytes();
+
+    let response = harness
+        .call(chat_request(false, Some("gzip")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+    let compressed = response.into_body().collect().await.unwrap().to_bytes();
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(compressed.as_ref())
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, uncompressed);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn event_streams_are_not_compressed() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(chat_request(true, Some("gzip, br, zstd")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("content-encoding").is_none());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(body.starts_with(b"data: "));
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{
+            BalanceConfig, BalanceConfigInner, HashKeySource, WeightedProvider,
+        },
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn consistent_hash_config(key: HashKeySource) -> Config {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing balancing
+    config.helicone.features = HeliconeFeatures::None;
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ConsistentHash {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+            ],
+            key,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn chat_request(
+    session_id: Option<&str>,
+    content: &str,
+) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": content
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json");
+    if let Some(session_id) = session_id {
+        request = request.header("x-session-id", session_id);
+    }
+    request.body(request_body).unwrap()
+}
+
+async fn assigned_providers(
+    harness: &mut Harness,
+    requests: impl IntoIterator<Item = Request<axum_core::body::Body>>,
+) -> Vec<String> {
+    let mut providers = Vec::new();
+    for request in requests {
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let provider = response
+            .headers()
+            .get("helicone-provider")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        providers.push(provider);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+    providers
+}
+
+fn mock_args(num_requests: u64) -> MockArgs {
+    MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", (0..=num_requests).into()),
+            ("success:anthropic:messages", (0..=num_requests).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn same_header_key_is_always_sent_to_the_same_provider() {
+    let num_requests = 10;
+    let mut harness = Harness::builder()
+        .with_config(consistent_hash_config(HashKeySource::Header(
+            "x-session-id".to_string(),
+        )))
+        .with_mock_args(mock_args(num_requests))
+        .build()
+        .await;
+
+    let requests = (0..num_requests)
+        .map(|i| chat_request(Some("session-1"), &format!("message {i}")));
+    let mut providers = assigned_providers(&mut harness, requests).await;
+    providers.dedup();
+    assert_eq!(
+        providers.len(),
+        1,
+        "key moved between providers: {providers:?}"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn same_body_key_is_always_sent_to_the_same_provider() {
+    let num_requests = 10;
+    let mut harness = Harness::builder()
+        .with_config(consistent_hash_config(HashKeySource::JsonPointer(
+            "/messages/0/content".to_string(),
+        )))
+        .with_mock_args(mock_args(num_requests))
+        .build()
+        .await;
+
+    // the header is not part of the key, only the body is
+    let requests = (0..num_requests).map(|i| {
+        let session_id = format!("session-{i}");
+        chat_request(Some(&session_id), "Hello, world!")
+    });
+    let mut providers = assigned_providers(&mut harness, requests).await;
+    providers.dedup();
+    assert_eq!(
+        providers.len(),
+        1,
+        "key moved between providers: {providers:?}"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn requests_without_key_fall_back_to_weighted_random() {
+    let num_requests = 5;
+    let mut harness = Harness::builder()
+        .with_config(consistent_hash_config(HashKeySource::Header(
+            "x-session-id".to_string(),
+        )))
+        .with_mock_args(mock_args(num_requests))
+        .build()
+        .await;
+
+    let requests =
+        (0..num_requests).map(|_| chat_request(None, "Hello, world!"));
+    let providers = assigned_providers(&mut harness, requests).await;
+    assert_eq!(providers.len(), 5);
+}
@@ -0,0 +1,176 @@
+use std::{collections::HashMap, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config, cors::CorsConfig, helicone::HeliconeFeatures,
+        router::RouterConfigs,
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode, header};
+use tower::Service;
+
+const ALLOWED_ORIGIN: &str = "https://app.example.com";
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.cors.allowed_origins = vec![ALLOWED_ORIGIN.to_string()];
+    config
+}
+
+fn preflight(path: &str, origin: &str) -> Request<axum_core::body::Body> {
+    Request::builder()
+        .method(Method::OPTIONS)
+        .uri(format!("http://router.helicone.com{path}"))
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .header(
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+            "authorization,content-type",
+        )
+        .body(axum_core::body::Body::empty())
+        .unwrap()
+}
+
+async fn harness(config: Config) -> Harness {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:sign_s3_url", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn preflights_are_answered_without_credentials() {
+    let mut harness = harness(config()).await;
+
+    for path in [
+        "/ai/chat/completions",
+        "/router/my-router/chat/completions",
+        "/openai/v1/chat/completions",
+    ] {
+        let response =
+            harness.call(preflight(path, ALLOWED_ORIGIN)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK, "{path}");
+        let headers = response.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            ALLOWED_ORIGIN,
+            "{path}"
+        );
+        assert!(headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+        assert!(headers.contains_key(header::ACCESS_CONTROL_ALLOW_HEADERS));
+    }
+    let received = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert!(received.is_empty());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn gateway_headers_are_exposed_to_allowed_origins_only() {
+    let mut harness = harness(config()).await;
+    let health_check = |origin| {
+        Request::builder()
+            .method(Method::GET)
+            .uri("http://router.helicone.com/health")
+            .header(header::ORIGIN, origin)
+            .body(axum_core::body::Body::empty())
+            .unwrap()
+    };
+
+    let response = harness.call(health_check(ALLOWED_ORIGIN)).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(
+        headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ALLOWED_ORIGIN
+    );
+    let exposed = headers
+        .get(header::ACCESS_CONTROL_EXPOSE_HEADERS)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(exposed.contains("helicone-cache"), "{exposed}");
+
+    let response = harness
+        .call(health_check("https://evil.example.com"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        !response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn routers_can_override_the_global_cors_config() {
+    let mut config = config();
+    let router_id = RouterId::Named(CompactString::new("my-router"));
+    let mut router_config = config.routers.get(&router_id).cloned().unwrap();
+    router_config.cors = Some(CorsConfig {
+        allowed_origins: vec!["https://*.partner.com".to_string()],
+        max_age: Some(Duration::from_secs(600)),
+        ..Default::default()
+    });
+    config.routers =
+        RouterConfigs::new(HashMap::from([(router_id, router_config)]));
+    let mut harness = harness(config).await;
+    let path = "/router/my-router/chat/completions";
+
+    let response = harness
+        .call(preflight(path, "https://app.partner.com"))
+        .await
+        .unwrap();
+
+    let headers = response.headers();
+    assert_eq!(
+        headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "https://app.partner.com"
+    );
+    assert_eq!(headers.get(header::ACCESS_CONTROL_MAX_AGE).unwrap(), "600");
+
+    let response = harness.call(preflight(path, ALLOWED_ORIGIN)).await.unwrap();
+
+    assert!(
+        !response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+
+    // other routes still use the global config
+    let response = harness
+        .call(preflight("/ai/chat/completions", "https://app.partner.com"))
+        .await
+        .unwrap();
+
+    assert!(
+        !response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+}
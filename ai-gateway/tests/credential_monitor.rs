@@ -0,0 +1,145 @@
+use std::{collections::HashMap, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        monitor::CredentialMonitorConfig,
+        router::{RouterConfig, RouterConfigs},
+    },
+    discover::monitor::{
+        credentials::CredentialMonitor, health::HealthMonitor,
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use opentelemetry_sdk::metrics::{
+    InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+};
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn rejected_credential_removes_provider_from_lb_pool() {
+    let exporter = InMemoryMetricExporter::default();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    opentelemetry::global::set_meter_provider(meter_provider.clone());
+
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing credential checks
+    config.helicone.features = HeliconeFeatures::None;
+    config.discover.monitor.credentials = Some(CredentialMonitorConfig {
+        interval: Duration::from_millis(10),
+        remove_unhealthy: true,
+    });
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.50).unwrap(),
+                    schedule: None,
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.50).unwrap(),
+                    schedule: None,
+                },
+            ],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:models", (1..).into()),
+            ("invalid_key:anthropic:models", (1..).into()),
+            ("success:openai:chat_completion", 20.into()),
+            ("success:anthropic:messages", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let app_state = harness.app_factory.state.clone();
+    let credential_monitor = CredentialMonitor::new(app_state.clone());
+    tokio::spawn(async move {
+        credential_monitor.run_forever().await.unwrap();
+    });
+    let health_monitor = HealthMonitor::new(app_state.clone());
+    tokio::spawn(async move {
+        health_monitor.run_forever().await.unwrap();
+    });
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !app_state
+            .0
+            .invalid_credentials
+            .read()
+            .await
+            .contains(&InferenceProvider::Anthropic)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("anthropic credential should be marked invalid");
+    assert!(
+        !app_state
+            .0
+            .invalid_credentials
+            .read()
+            .await
+            .contains(&InferenceProvider::OpenAI)
+    );
+    // give the health monitor time to remove anthropic from the balancer
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    meter_provider.force_flush().unwrap();
+    let metrics = exporter.get_finished_metrics().unwrap();
+    assert!(format!("{metrics:?}").contains("credential_failures"));
+
+    let body_bytes = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+    for _ in 0..20 {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://router.helicone.com/router/my-router/chat/completions")
+            .body(axum_core::body::Body::from(body_bytes.clone()))
+            .unwrap();
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["helicone-provider"], "openai");
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
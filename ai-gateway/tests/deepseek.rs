@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    tests::{
+        harness::Harness,
+        mock::MockArgs,
+        providers::{chat_request, config},
+    },
+    types::provider::InferenceProvider,
+};
+use http::StatusCode;
+use http_body_util::BodyExt;
+use tower::Service;
+
+const REASONING: &str =
+    "The user greeted me, so I should greet them back and offer to help.";
+
+#[tokio::test]
+#[serial_test::serial]
+async fn balancing_anthropic_and_deepseek_preserves_reasoning_content() {
+    let num_requests = 20;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", (1..).into()),
+            ("success:deepseek:chat_completion", (1..).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(
+            InferenceProvider::DeepSeek,
+            Some(InferenceProvider::Anthropic),
+        ))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for _ in 0..num_requests {
+        let response = harness
+            .call(chat_request("openai/gpt-4o-mini", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let provider = response
+            .headers()
+            .get("helicone-provider")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let message = &body["choices"][0]["message"];
+        match provider.as_str() {
+            "deepseek" => {
+                assert_eq!(message["reasoning_content"], REASONING);
+                assert_eq!(
+                    message["content"],
+                    "Hello! How can I assist you today?"
+                );
+            }
+            "anthropic" => {
+                assert!(message.get("reasoning_content").is_none());
+            }
+            provider => panic!("unexpected provider: {provider}"),
+        }
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn openai_model_is_mapped_to_deepseek() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:deepseek:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(InferenceProvider::DeepSeek, None))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(chat_request("openai/gpt-4o-mini", false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .deepseek_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .first()
+        .expect("deepseek should receive the request");
+    assert_eq!(
+        provider_request.headers.get("authorization").unwrap(),
+        "Bearer test-deepseek-key"
+    );
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(body["model"], "deepseek-chat");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn deepseek_stream_preserves_reasoning_content() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:deepseek:chat_completion_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(InferenceProvider::DeepSeek, None))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(chat_request("deepseek/deepseek-reasoner", true))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let chunks = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    let reasoning = chunks
+        .iter()
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["reasoning_content"].as_str()
+        })
+        .collect::<String>();
+    assert_eq!(reasoning, "The user greeted me.");
+    let content = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect::<String>();
+    assert_eq!(content, "Hello!");
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        cache::CacheConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use base64::Engine;
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config
+}
+
+fn embeddings_request(
+    uri: &str,
+    body: &serde_json::Value,
+) -> Request<axum_core::body::Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(body).unwrap(),
+        ))
+        .unwrap()
+}
+
+async fn gemini_request_body(harness: &Harness) -> serde_json::Value {
+    let received_requests = harness
+        .mock
+        .google_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1beta/openai/embeddings")
+        .expect("gemini should receive the request");
+    serde_json::from_slice(&provider_request.body).unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_embeddings_are_sent_to_openai() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:embeddings", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = embeddings_request(
+        "http://router.helicone.com/ai/embeddings",
+        &json!({
+            "model": "openai/text-embedding-3-small",
+            "input": "Hello, world!"
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["object"], "list");
+    assert_eq!(body["data"][0]["embedding"].as_array().unwrap().len(), 4);
+    assert_eq!(body["usage"]["prompt_tokens"], 4);
+}
+
+/// Gemini only returns floats, so base64 embeddings are encoded by the
+/// gateway.
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_embeddings_from_gemini_are_base64_encoded() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:gemini:embeddings", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = embeddings_request(
+        "http://router.helicone.com/ai/embeddings",
+        &json!({
+            "model": "gemini/text-embedding-004",
+            "input": ["Hello, world!"],
+            "encoding_format": "base64"
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let embedding = body["data"][0]["embedding"]
+        .as_str()
+        .expect("embedding should be base64 encoded");
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(embedding)
+        .unwrap();
+    let floats = bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(floats, vec![0.5, -0.25, 0.125, 1.0]);
+    assert_eq!(body["usage"]["prompt_tokens"], 4);
+
+    let body = gemini_request_body(&harness).await;
+    assert_eq!(body["model"], "text-embedding-004");
+    assert!(body.get("encoding_format").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn router_embeddings_are_mapped_to_gemini() {
+    let mut config = config();
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Embeddings,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![WeightedProvider {
+                provider: InferenceProvider::GoogleGemini,
+                weight: Decimal::try_from(1.0).unwrap(),
+                schedule: None,
+            }],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:gemini:embeddings", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = embeddings_request(
+        "http://router.helicone.com/router/my-router/embeddings",
+        &json!({
+            "model": "openai/text-embedding-3-small",
+            "input": "Hello, world!"
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["data"][0]["embedding"],
+        json!([0.5, -0.25, 0.125, 1.0])
+    );
+
+    let body = gemini_request_body(&harness).await;
+    assert_eq!(body["model"], "text-embedding-004");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_embeddings_are_cached() {
+    let mut config = config();
+    config.global.cache = Some(CacheConfig::test_default());
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:embeddings", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for expected in ["MISS", "HIT"] {
+        let mut request = embeddings_request(
+            "http://router.helicone.com/ai/embeddings",
+            &json!({
+                "model": "openai/text-embedding-3-small",
+                "input": "Hello, world!"
+            }),
+        );
+        request.headers_mut().insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("max-age=3600"),
+        );
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("helicone-cache").unwrap(), expected);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"][0]["embedding"].as_array().unwrap().len(), 4);
+    }
+}
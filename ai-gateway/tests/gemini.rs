@@ -0,0 +1,177 @@
+use std::{collections::HashMap, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    discover::monitor::rate_limit::RateLimitMonitor,
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nev;
+use serde_json::json;
+use tower::Service;
+
+fn config(load_balance: BalanceConfig) -> Config {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing provider mapping
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn chat_request(stream: bool) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "system",
+                    "content": "You are a helpful assistant."
+                },
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": stream
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn gemini_rate_limit_removes_provider() {
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::Priority {
+            providers: nev![
+                InferenceProvider::GoogleGemini,
+                InferenceProvider::Anthropic
+            ],
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("rate_limit:gemini:generate_content", 1.into()),
+            ("success:anthropic:messages", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(balance_config))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let rate_limit_monitor =
+        RateLimitMonitor::new(harness.app_factory.state.clone());
+    tokio::spawn(async move {
+        rate_limit_monitor.run_forever().await.unwrap();
+    });
+    // Give time for the monitor to pick up the new router (polls every 100ms in
+    // test mode)
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    let response = harness.call(chat_request(false)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    // the retry delay from the google error body is surfaced as a header
+    assert_eq!(response.headers().get("retry-after").unwrap(), "2");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("exceeded your current quota")
+    );
+    // give the monitor time to remove the provider
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for _ in 0..2 {
+        let response = harness.call(chat_request(false)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("helicone-provider").unwrap(),
+            "anthropic"
+        );
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn gemini_stream_is_translated() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:gemini:generate_content_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(BalanceConfig::google_gemini()))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request(true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let chunks = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0]["choices"][0]["delta"]["content"], "Hello");
+    assert_eq!(chunks[1]["choices"][0]["finish_reason"], "stop");
+    assert_eq!(chunks[1]["usage"]["total_tokens"], 12);
+
+    let received_requests = harness
+        .mock
+        .google_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| {
+            request.url.path()
+                == "/v1beta/models/gemini-2.0-flash:streamGenerateContent"
+        })
+        .expect("provider should receive the request");
+    assert_eq!(provider_request.url.query(), Some("alt=sse"));
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(
+        body["systemInstruction"]["parts"][0]["text"],
+        "You are a helpful assistant."
+    );
+    assert_eq!(body["contents"][0]["role"], "user");
+}
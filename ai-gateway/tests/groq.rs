@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    tests::{
+        harness::Harness,
+        mock::MockArgs,
+        providers::{
+            assert_rate_limit_removes_provider_from_lb_pool, chat_request,
+            config,
+        },
+    },
+    types::provider::InferenceProvider,
+};
+use http::StatusCode;
+use http_body_util::BodyExt;
+use tower::Service;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn openai_model_is_mapped_to_groq() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:groq:chat_completion", 1.into()),
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(InferenceProvider::Groq, None))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(chat_request("openai/gpt-4o-mini", false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("helicone-provider").unwrap(), "groq");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["choices"][0]["message"]["content"],
+        "Hello! How can I assist you today?"
+    );
+    assert_eq!(body["usage"]["total_tokens"], 29);
+
+    let received_requests = harness
+        .mock
+        .groq_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .first()
+        .expect("groq should receive the request");
+    assert_eq!(
+        provider_request.headers.get("authorization").unwrap(),
+        "Bearer test-groq-key"
+    );
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(body["model"], "llama-3.1-8b-instant");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn groq_stream_usage_is_reported() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:groq:chat_completion_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(InferenceProvider::Groq, None))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(chat_request("groq/llama-3.1-8b-instant", true))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let chunks = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    let content = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect::<String>();
+    assert_eq!(content, "Hello!");
+    let usage = &chunks.last().unwrap()["usage"];
+    assert_eq!(usage["prompt_tokens"], 9);
+    assert_eq!(usage["completion_tokens"], 3);
+    assert_eq!(usage["total_tokens"], 12);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn weighted_balancer_splits_openai_and_groq() {
+    let num_requests = 100;
+    let tolerance = num_requests as f64 * 0.15;
+    let expected_midpt = num_requests as f64 * 0.5;
+    let range = (expected_midpt - tolerance).floor() as u64
+        ..(expected_midpt + tolerance).ceil() as u64;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", range.clone().into()),
+            ("success:groq:chat_completion", range.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(
+            InferenceProvider::Groq,
+            Some(InferenceProvider::OpenAI),
+        ))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for _ in 0..num_requests {
+        let response = harness
+            .call(chat_request("openai/gpt-4o-mini", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn groq_rate_limit_removes_provider_from_lb_pool() {
+    assert_rate_limit_removes_provider_from_lb_pool(
+        InferenceProvider::Groq,
+        "rate_limit:groq:chat_completion",
+        "success:groq:chat_completion",
+    )
+    .await;
+}
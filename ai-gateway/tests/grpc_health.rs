@@ -0,0 +1,70 @@
+use ai_gateway::{
+    config::Config,
+    tests::{TestDefault, harness::Harness},
+    utils::grpc_health::{GrpcHealthServer, READINESS_SERVICE},
+};
+use meltdown::Token;
+use tokio::net::TcpListener;
+use tonic_health::pb::{
+    HealthCheckRequest, health_check_response::ServingStatus,
+    health_client::HealthClient,
+};
+
+/// Serves health checks for the harness's app, returning the statuses of the
+/// liveness and readiness services.
+async fn check(harness: &Harness) -> (ServingStatus, ServingStatus) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let token = Token::new();
+    let server = GrpcHealthServer::new(harness.app_factory.state.clone());
+    let handle = tokio::spawn(server.serve(listener, token.clone()));
+
+    let mut client = HealthClient::connect(format!("http://{addr}"))
+        .await
+        .unwrap();
+    let liveness = status(&mut client, "").await;
+    let readiness = status(&mut client, READINESS_SERVICE).await;
+
+    token.trigger();
+    handle.await.unwrap().unwrap();
+    (liveness, readiness)
+}
+
+async fn status(
+    client: &mut HealthClient<tonic::transport::Channel>,
+    service: &str,
+) -> ServingStatus {
+    let request = HealthCheckRequest {
+        service: service.to_string(),
+    };
+    client.check(request).await.unwrap().into_inner().status()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn sidecar_is_serving_once_control_plane_config_is_populated() {
+    let harness = Harness::builder()
+        .with_config(Config::test_default())
+        .with_mock_auth()
+        .build()
+        .await;
+
+    let (liveness, readiness) = check(&harness).await;
+
+    assert_eq!(liveness, ServingStatus::Serving);
+    assert_eq!(readiness, ServingStatus::Serving);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn sidecar_is_not_ready_without_control_plane_config() {
+    let harness = Harness::builder()
+        .with_config(Config::test_default())
+        .build()
+        .await;
+
+    let (liveness, readiness) = check(&harness).await;
+
+    assert_eq!(liveness, ServingStatus::Serving);
+    assert_eq!(readiness, ServingStatus::NotServing);
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config, header_filter::HeaderFilterConfig, helicone::HeliconeFeatures,
+        router::RouterConfigs,
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use serde_json::json;
+use tower::Service;
+
+fn chat_request(path: &str) -> Request<axum_core::body::Body> {
+    chat_request_with_stream(path, false)
+}
+
+fn chat_request_with_stream(
+    path: &str,
+    stream: bool,
+) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": stream
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://router.helicone.com{path}"))
+        .header("content-type", "application/json")
+        .header("x-tenant-id", "acme")
+        .header("x-stainless-os", "Linux")
+        .header("x-debug-trace", "1")
+        .header("cookie", "session=secret")
+        .body(request_body)
+        .unwrap()
+}
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config
+}
+
+async fn harness(config: Config, stub: &'static str) -> Harness {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            (stub, (1..).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn provider_headers_are_filtered_before_reaching_clients() {
+    let mut harness =
+        harness(config(), "success:openai:chat_completion_with_headers").await;
+
+    let response = harness
+        .call(chat_request("/router/my-router/chat/completions"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers.get("openai-processing-ms").unwrap(), "321");
+    assert_eq!(
+        headers.get("x-ratelimit-remaining-requests").unwrap(),
+        "9999"
+    );
+    assert_eq!(headers.get("helicone-provider-req-id").unwrap(), "req_123");
+    assert!(headers.get("set-cookie").is_none());
+    assert!(headers.get("openai-organization").is_none());
+    assert!(headers.get("cf-ray").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn provider_headers_of_streams_are_filtered_before_reaching_clients() {
+    let mut harness = harness(
+        config(),
+        "success:openai:chat_completion_stream_with_headers",
+    )
+    .await;
+
+    let response = harness
+        .call(chat_request_with_stream(
+            "/router/my-router/chat/completions",
+            true,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(
+        headers.get("content-type").unwrap(),
+        "text/event-stream; charset=utf-8"
+    );
+    assert_eq!(
+        headers.get("x-ratelimit-remaining-requests").unwrap(),
+        "9999"
+    );
+    assert_eq!(headers.get("helicone-provider-req-id").unwrap(), "req_456");
+    assert!(headers.get("set-cookie").is_none());
+    assert!(headers.get("openai-organization").is_none());
+    assert!(headers.get("cf-ray").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn routers_can_override_forwarded_provider_headers() {
+    let mut config = config();
+    let router_id = RouterId::Named(CompactString::new("my-router"));
+    let mut router_config = config.routers.get(&router_id).cloned().unwrap();
+    router_config.response_headers = Some(HeaderFilterConfig {
+        allow: vec!["cf-*".to_string()],
+        deny: vec!["openai-processing-ms".to_string()],
+    });
+    config.routers =
+        RouterConfigs::new(HashMap::from([(router_id, router_config)]));
+    let mut harness =
+        harness(config, "success:openai:chat_completion_with_headers").await;
+
+    let response = harness
+        .call(chat_request("/router/my-router/chat/completions"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers.get("cf-ray").unwrap(), "8f1e2d3c4b5a6978-SJC");
+    assert!(headers.get("openai-processing-ms").is_none());
+    assert!(headers.get("set-cookie").is_none());
+
+    // direct proxies aren't part of the router
+    let response = harness
+        .call(chat_request("/openai/v1/chat/completions"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert!(headers.get("cf-ray").is_none());
+    assert_eq!(headers.get("openai-processing-ms").unwrap(), "321");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn client_headers_are_filtered_before_reaching_providers() {
+    let mut config = config();
+    config.request_headers.allow = vec!["x-tenant-*".to_string()];
+    let mut harness = harness(config, "success:openai:chat_completion").await;
+
+    for path in [
+        "/router/my-router/chat/completions",
+        "/ai/chat/completions",
+        "/openai/v1/chat/completions",
+    ] {
+        let response = harness.call(chat_request(path)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{path}");
+    }
+
+    let received = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert_eq!(received.len(), 3);
+    for provider_request in received {
+        let headers = &provider_request.headers;
+        assert_eq!(headers.get("x-tenant-id").unwrap(), "acme");
+        assert_eq!(headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(headers.get("x-stainless-os").unwrap(), "Linux");
+        assert!(headers.get("x-debug-trace").is_none());
+        assert!(headers.get("cookie").is_none());
+    }
+}
@@ -33,16 +33,21 @@ async fn errors_remove_provider_from_lb_pool() {
                 WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::try_from(0.20).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::try_from(0.40).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::GoogleGemini,
                     weight: Decimal::try_from(0.40).unwrap(),
+                    schedule: None,
                 },
             ],
+            sticky: false,
+            seed: None,
         },
     )]));
     config.routers = RouterConfigs::new(HashMap::from([(
@@ -0,0 +1,217 @@
+use std::{collections::HashMap, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::{HeliconeConfig, HeliconeFeatures},
+        router::{RouterConfig, RouterConfigs},
+    },
+    control_plane::{
+        types::{self as control_plane, MessageTypeRX, Update},
+        websocket::ControlPlaneClient,
+    },
+    discover::router::reload::reload_routers,
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use futures::SinkExt;
+use http::{Method, Request, StatusCode};
+use meltdown::Token;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tower::Service;
+
+fn chat_request(router_id: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router_id}/chat/completions"
+        ))
+        .body(request_body)
+        .unwrap()
+}
+
+/// Adding a router to the config should make it available without
+/// restarting the gateway, and leave the existing router in place.
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn reload_adds_new_router() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing router reloading
+    config.helicone.features = HeliconeFeatures::None;
+    let my_router = RouterId::Named(CompactString::new("my-router"));
+    let my_router_config = RouterConfig {
+        load_balance: BalanceConfig::openai_chat(),
+        ..Default::default()
+    };
+    config.routers = RouterConfigs::new(HashMap::from([(
+        my_router.clone(),
+        my_router_config.clone(),
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 3.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request("new-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = harness.call(chat_request("my-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let new_router = RouterId::Named(CompactString::new("new-router"));
+    let summary = reload_routers(
+        &harness.app_factory.state,
+        RouterConfigs::new(HashMap::from([
+            (my_router, my_router_config),
+            (
+                new_router.clone(),
+                RouterConfig {
+                    load_balance: BalanceConfig::openai_chat(),
+                    ..Default::default()
+                },
+            ),
+        ])),
+    )
+    .await
+    .unwrap();
+    assert_eq!(summary.added, vec![new_router]);
+    assert!(summary.updated.is_empty());
+    assert!(summary.removed.is_empty());
+
+    let response = harness.call(chat_request("new-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = harness.call(chat_request("my-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+fn weighted_to(provider: InferenceProvider) -> RouterConfig {
+    RouterConfig {
+        load_balance: BalanceConfig::from(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ProviderWeighted {
+                providers: nes![WeightedProvider {
+                    provider,
+                    weight: Decimal::ONE,
+                    schedule: None,
+                }],
+                sticky: false,
+                seed: None,
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+/// A router config pushed by the control plane should rebuild that router's
+/// balancer in place, so that traffic follows the new weights.
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn control_plane_update_changes_traffic_split() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing router reloading
+    config.helicone.features = HeliconeFeatures::None;
+    let my_router = RouterId::Named(CompactString::new("my-router"));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        my_router.clone(),
+        weighted_to(InferenceProvider::OpenAI),
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 3.into()),
+            ("success:anthropic:messages", 3.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for _ in 0..3 {
+        let response = harness.call(chat_request("my-router")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["helicone-provider"], "openai");
+    }
+
+    // mock control plane that pushes a config moving all traffic to anthropic
+    let updated_config = weighted_to(InferenceProvider::Anthropic);
+    let message = MessageTypeRX::Update(Update::Config {
+        data: control_plane::Config {
+            router_id: "my-router".to_string(),
+            router_config: serde_json::to_string(&updated_config).unwrap(),
+            ..control_plane::Config::test_default()
+        },
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut websocket = accept_async(stream).await.unwrap();
+        let bytes = serde_json::to_vec(&message).unwrap();
+        websocket.send(Message::Binary(bytes.into())).await.unwrap();
+        // keep the connection open until the test completes
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    });
+
+    let app_state = harness.app_factory.state.clone();
+    let helicone_config = HeliconeConfig {
+        websocket_url: format!("ws://{addr}").parse().unwrap(),
+        ..Default::default()
+    };
+    let client = ControlPlaneClient::connect(
+        app_state.0.control_plane_state.clone(),
+        helicone_config,
+    )
+    .await
+    .unwrap()
+    .with_app_state(app_state.clone());
+    let token = Token::new();
+    tokio::spawn(meltdown::Service::run(client, token.clone()));
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while app_state.0.router_configs.read().await.get(&my_router)
+            != Some(&updated_config)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("router should be rebuilt with the pushed config");
+
+    for _ in 0..3 {
+        let response = harness.call(chat_request("my-router")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["helicone-provider"], "anthropic");
+    }
+    token.trigger();
+}
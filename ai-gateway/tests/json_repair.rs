@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        json_repair::JsonRepairMode,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn config(json_repair: JsonRepairMode) -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::from(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::ProviderWeighted {
+                    providers: nes![WeightedProvider {
+                        provider: InferenceProvider::OpenAI,
+                        weight: Decimal::try_from(1.0).unwrap(),
+                        schedule: None,
+                    }],
+                    sticky: false,
+                    seed: None,
+                },
+            )])),
+            json_repair,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn chat_request(response_format: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "What's the weather in Paris? Answer in JSON."
+                }
+            ],
+            "response_format": { "type": response_format }
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+async fn harness(json_repair: JsonRepairMode, requests: u64) -> Harness {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            (
+                "success:openai:chat_completion_fenced_json",
+                requests.into(),
+            ),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(config(json_repair))
+        .with_mock_args(mock_args)
+        .build()
+        .await
+}
+
+/// The response's `helicone-json-repaired` header and message content.
+async fn call(
+    harness: &mut Harness,
+    response_format: &str,
+) -> (Option<String>, String) {
+    let response = harness.call(chat_request(response_format)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let repaired = response
+        .headers()
+        .get("helicone-json-repaired")
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let content = body["choices"][0]["message"]["content"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    (repaired, content)
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn fenced_json_is_repaired() {
+    let mut harness = harness(JsonRepairMode::Repair, 1).await;
+
+    let (repaired, content) = call(&mut harness, "json_object").await;
+    assert_eq!(repaired.as_deref(), Some("true"));
+    let content: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(content, json!({ "city": "Paris", "temperature": 21 }));
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn invalid_json_is_retried_once_before_being_repaired() {
+    // mocks are verified on drop
+    let mut harness = harness(JsonRepairMode::Retry, 2).await;
+
+    let (repaired, content) = call(&mut harness, "json_object").await;
+    assert_eq!(repaired.as_deref(), Some("true"));
+    assert!(serde_json::from_str::<serde_json::Value>(&content).is_ok());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn text_responses_are_not_repaired() {
+    let mut harness = harness(JsonRepairMode::Repair, 1).await;
+
+    let (repaired, content) = call(&mut harness, "text").await;
+    assert!(repaired.is_none());
+    assert!(content.starts_with("```json"));
+}
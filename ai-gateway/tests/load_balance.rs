@@ -31,11 +31,7 @@ fn p2c_config_openai_anthropic_google() -> RouterConfigs {
                     ],
                 },
             )])),
-            model_mappings: None,
-            cache: None,
-            retries: None,
-            rate_limit: None,
-            providers: None,
+            ..Default::default()
         },
     )]))
 }
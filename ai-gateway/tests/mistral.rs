@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        cache::CacheConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn config(cache: Option<CacheConfig>) -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![WeightedProvider {
+                provider: InferenceProvider::Mistral,
+                weight: Decimal::try_from(1.0).unwrap(),
+                schedule: None,
+            }],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            cache,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn chat_request() -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "What's the weather in Paris?"
+                },
+                {
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_abc123XYZ456def",
+                        "type": "function",
+                        "function": {
+                            "name": "get_weather",
+                            "arguments": "{\"city\":\"Paris\"}"
+                        }
+                    }]
+                },
+                {
+                    "role": "tool",
+                    "tool_call_id": "call_abc123XYZ456def",
+                    "content": "sunny"
+                }
+            ],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "parameters": {
+                        "type": "object",
+                        "properties": { "city": { "type": "string" } }
+                    }
+                }
+            }],
+            "tool_choice": "required"
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .header("cache-control", "max-age=3600")
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn weighted_to_mistral_and_cached() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:mistral:chat_completion_cacheable", 1.into()),
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(Some(CacheConfig::test_default())))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-provider").unwrap(),
+        "mistral"
+    );
+    assert_eq!(response.headers().get("helicone-cache").unwrap(), "MISS");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["choices"][0]["message"]["content"],
+        "Hello! How can I assist you today?"
+    );
+
+    let received_requests = harness
+        .mock
+        .mistral_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/chat/completions")
+        .expect("mistral should receive the request");
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(body["model"], "mistral-small");
+    assert_eq!(body["tool_choice"], "any");
+    let tool_call_id =
+        body["messages"][1]["tool_calls"][0]["id"].as_str().unwrap();
+    assert_eq!(tool_call_id.len(), 9);
+    assert!(
+        tool_call_id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric())
+    );
+    assert_eq!(body["messages"][2]["tool_call_id"], tool_call_id);
+
+    // the repeat request is served from the cache, so the mock still only
+    // receives one request
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("helicone-cache").unwrap(), "HIT");
+    let _response_body = response.into_body().collect().await.unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn mistral_model_is_streamed_with_mistral_options() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:mistral:chat_completion_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(None))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "mistral/mistral-large-latest",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": true,
+            "seed": 42,
+            "safe_prompt": true
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let content = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["content"]
+                .as_str()
+                .map(ToString::to_string)
+        })
+        .collect::<String>();
+    assert_eq!(content, "Hello!");
+
+    let received_requests = harness
+        .mock
+        .mistral_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .first()
+        .expect("mistral should receive the request");
+    assert_eq!(
+        provider_request.headers.get("authorization").unwrap(),
+        "Bearer test-mistral-key"
+    );
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(body["model"], "mistral-large-latest");
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["safe_prompt"], true);
+    assert_eq!(body["random_seed"], 42);
+    assert!(body.get("seed").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn mistral_rate_limit_error_is_normalized() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("rate_limit:mistral:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(None))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get("retry-after").unwrap(), "2");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["message"], "Requests rate limit exceeded");
+    assert_eq!(body["error"]["type"], "rate_limited");
+}
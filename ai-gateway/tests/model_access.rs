@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{Config, helicone::HeliconeFeatures},
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+fn chat_request(model: &str) -> Request<axum_core::body::Body> {
+    let body = serde_json::to_vec(&json!({
+        "model": model,
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .body(axum_core::body::Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn models_not_allowed_by_router_are_rejected() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config
+        .routers
+        .as_mut()
+        .get_mut(&RouterId::Named(CompactString::new("my-router")))
+        .unwrap()
+        .allowed_models = vec!["openai/gpt-4o-mini".parse().unwrap()];
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            // only the allowed request reaches the provider
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request("openai/gpt-4o")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("openai/gpt-4o")
+    );
+
+    let response = harness
+        .call(chat_request("openai/gpt-4o-mini"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+}
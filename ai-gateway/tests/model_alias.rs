@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        model_alias::ModelAliasConfig,
+        retry::RetryConfig,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::{nes, nev};
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn config(aliases: serde_json::Value) -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.model_aliases =
+        serde_json::from_value::<ModelAliasConfig>(aliases).unwrap();
+    config
+}
+
+fn weighted_router(provider: InferenceProvider) -> RouterConfig {
+    RouterConfig {
+        load_balance: BalanceConfig::from(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ProviderWeighted {
+                providers: nes![WeightedProvider {
+                    provider,
+                    weight: Decimal::try_from(1.0).unwrap(),
+                    schedule: None,
+                }],
+                sticky: false,
+                seed: None,
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+fn chat_request(router: &str) -> Request<axum_core::body::Body> {
+    chat_request_for_model(router, "openai/gpt-4o-mini")
+}
+
+fn chat_request_for_model(
+    router: &str,
+    model: &str,
+) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router}/chat/completions"
+        ))
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+async fn received_model(
+    server: &stubr::wiremock_rs::MockServer,
+    path: &str,
+) -> serde_json::Value {
+    let received_requests = server.received_requests().await.unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == path)
+        .expect("the provider should receive the request");
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    body["model"].clone()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn alias_resolves_to_the_selected_providers_model() {
+    let mut config = config(json!({
+        "gpt-4o-mini": {
+            "openai": "gpt-4.1-nano",
+            "anthropic": "claude-3-5-haiku"
+        }
+    }));
+    config.routers = RouterConfigs::new(HashMap::from([
+        (
+            RouterId::Named(CompactString::new("openai-router")),
+            weighted_router(InferenceProvider::OpenAI),
+        ),
+        (
+            RouterId::Named(CompactString::new("anthropic-router")),
+            weighted_router(InferenceProvider::Anthropic),
+        ),
+    ]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for router in ["openai-router", "anthropic-router"] {
+        let response = harness.call(chat_request(router)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+
+    let openai_model = received_model(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(openai_model, "gpt-4.1-nano");
+    let anthropic_model = received_model(
+        &harness.mock.anthropic_mock.http_server,
+        "/v1/messages",
+    )
+    .await;
+    assert_eq!(anthropic_model, "claude-3-5-haiku-latest");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unaliased_provider_fails_over_to_the_next_provider() {
+    let mut config = config(json!({
+        "gpt-4o-mini": {
+            "openai": "gpt-4.1-nano"
+        }
+    }));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::from(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::Priority {
+                    providers: nev![
+                        InferenceProvider::Anthropic,
+                        InferenceProvider::OpenAI
+                    ],
+                },
+            )])),
+            retries: Some(RetryConfig::test_default()),
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request("my-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-provider").unwrap(),
+        "openai"
+    );
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let openai_model = received_model(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(openai_model, "gpt-4.1-nano");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn router_alias_is_rewritten_before_routing() {
+    for preserve_alias_in_response in [false, true] {
+        let mut config = config(json!({}));
+        config.routers = RouterConfigs::new(HashMap::from([(
+            RouterId::Named(CompactString::new("my-router")),
+            RouterConfig {
+                model_aliases: HashMap::from([(
+                    "fast".to_string(),
+                    "anthropic/claude-3-5-haiku".to_string(),
+                )]),
+                preserve_alias_in_response,
+                ..weighted_router(InferenceProvider::Anthropic)
+            },
+        )]));
+        let mock_args = MockArgs::builder()
+            .stubs(HashMap::from([
+                ("success:anthropic:messages", 1.into()),
+                ("success:minio:upload_request", 0.into()),
+                ("success:jawn:log_request", 0.into()),
+            ]))
+            .build();
+        let mut harness = Harness::builder()
+            .with_config(config)
+            .with_mock_args(mock_args)
+            .build()
+            .await;
+
+        let request = chat_request_for_model("my-router", "fast");
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["model"] == "fast", preserve_alias_in_response);
+
+        let anthropic_model = received_model(
+            &harness.mock.anthropic_mock.http_server,
+            "/v1/messages",
+        )
+        .await;
+        assert_eq!(anthropic_model, "claude-3-5-haiku-latest");
+    }
+}
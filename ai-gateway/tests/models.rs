@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn get(uri: &str) -> Request<axum_core::body::Body> {
+    Request::builder()
+        .method(Method::GET)
+        .uri(format!("http://router.helicone.com{uri}"))
+        .body(axum_core::body::Body::empty())
+        .unwrap()
+}
+
+async fn model_ids(harness: &mut Harness, uri: &str) -> Vec<String> {
+    let response = harness.call(get(uri)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["object"], "list");
+    body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::from(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::ProviderWeighted {
+                    providers: nes![WeightedProvider {
+                        provider: InferenceProvider::Anthropic,
+                        weight: Decimal::try_from(1.0).unwrap(),
+                        schedule: None,
+                    }],
+                    sticky: false,
+                    seed: None,
+                },
+            )])),
+            denied_models: vec!["anthropic/claude-3-5-haiku".parse().unwrap()],
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_lists_the_models_of_every_provider() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:models", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let models = model_ids(&mut harness, "/ai/models").await;
+    assert!(models.contains(&"openai/gpt-4o-mini".to_string()));
+    assert!(models.iter().any(|model| model.starts_with("anthropic/")));
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn router_lists_only_the_models_it_allows() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:models", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let models = model_ids(&mut harness, "/router/my-router/models").await;
+    assert!(!models.is_empty());
+    assert!(models.iter().all(|model| model.starts_with("anthropic/")));
+    assert!(
+        !models
+            .iter()
+            .any(|model| model.starts_with("anthropic/claude-3-5-haiku"))
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn provider_models_are_passed_through() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:models", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(get("/openai/v1/models")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "object": "list",
+            "data": [
+                {
+                    "id": "gpt-4o-mini",
+                    "object": "model",
+                    "created": 1_721_172_741,
+                    "owned_by": "system"
+                }
+            ]
+        })
+    );
+}
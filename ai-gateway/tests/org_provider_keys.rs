@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{Config, helicone::HeliconeFeatures},
+    control_plane::types::{
+        Key, MessageTypeRX, OrgProviderKey, Update, hash_key,
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{org::OrgId, provider::InferenceProvider, secret::Secret},
+};
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+use uuid::Uuid;
+
+const ORG1_AUTH: &str = "sk-helicone-org1-key";
+const ORG2_AUTH: &str = "sk-helicone-org2-key";
+
+fn chat_request(auth: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/ai/chat/completions")
+        .header("content-type", "application/json")
+        .header("authorization", format!("Bearer {auth}"))
+        .body(request_body)
+        .unwrap()
+}
+
+fn org_provider_key(org_id: OrgId, key: &str) -> OrgProviderKey {
+    OrgProviderKey {
+        organization_id: org_id,
+        provider: InferenceProvider::OpenAI,
+        key: Secret::from(key.to_string()),
+    }
+}
+
+/// Two organizations, each with a key to authenticate to the gateway.
+async fn harness(
+    provider_keys: Vec<OrgProviderKey>,
+    org1_id: OrgId,
+    org2_id: OrgId,
+    requests: u64,
+) -> Harness {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", requests.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut control_plane_config =
+        ai_gateway::control_plane::types::Config::test_default();
+    control_plane_config.keys = vec![
+        Key {
+            key_hash: hash_key(ORG1_AUTH),
+            owner_id: Uuid::new_v4().to_string(),
+            organization_id: org1_id,
+        },
+        Key {
+            key_hash: hash_key(ORG2_AUTH),
+            owner_id: Uuid::new_v4().to_string(),
+            organization_id: org2_id,
+        },
+    ];
+    control_plane_config.provider_keys = provider_keys;
+    Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_control_plane_config(control_plane_config)
+        .build()
+        .await
+}
+
+/// The `authorization` header of each request the provider received, in
+/// order.
+async fn outbound_authorization(harness: &Harness) -> Vec<String> {
+    harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|request| request.url.path() == "/v1/chat/completions")
+        .map(|request| {
+            request
+                .headers
+                .get("authorization")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+async fn call(harness: &mut Harness, auth: &str) {
+    let response = harness.call(chat_request(auth)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn each_org_uses_its_own_provider_key() {
+    let org1_id = OrgId::new(Uuid::new_v4());
+    let org2_id = OrgId::new(Uuid::new_v4());
+    let mut harness = harness(
+        vec![
+            org_provider_key(org1_id, "sk-org1-openai-key"),
+            org_provider_key(org2_id, "sk-org2-openai-key"),
+        ],
+        org1_id,
+        org2_id,
+        2,
+    )
+    .await;
+
+    call(&mut harness, ORG1_AUTH).await;
+    call(&mut harness, ORG2_AUTH).await;
+
+    assert_eq!(
+        outbound_authorization(&harness).await,
+        vec![
+            "Bearer sk-org1-openai-key".to_string(),
+            "Bearer sk-org2-openai-key".to_string(),
+        ]
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn orgs_without_their_own_key_use_the_platform_key() {
+    let org1_id = OrgId::new(Uuid::new_v4());
+    let org2_id = OrgId::new(Uuid::new_v4());
+    let mut harness = harness(
+        vec![org_provider_key(org1_id, "sk-org1-openai-key")],
+        org1_id,
+        org2_id,
+        3,
+    )
+    .await;
+
+    call(&mut harness, ORG2_AUTH).await;
+
+    // keys pushed by the control plane apply to the next request
+    harness
+        .app_factory
+        .state
+        .0
+        .control_plane_state
+        .write()
+        .await
+        .update(MessageTypeRX::Update(Update::ProviderKeys {
+            data: vec![
+                org_provider_key(org1_id, "sk-org1-rotated-key"),
+                org_provider_key(org2_id, "sk-org2-openai-key"),
+            ],
+        }));
+    call(&mut harness, ORG1_AUTH).await;
+    call(&mut harness, ORG2_AUTH).await;
+
+    assert_eq!(
+        outbound_authorization(&harness).await,
+        vec![
+            "Bearer test-openai-key".to_string(),
+            "Bearer sk-org1-rotated-key".to_string(),
+            "Bearer sk-org2-openai-key".to_string(),
+        ]
+    );
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::{Value, json};
+use tower::Service;
+
+fn weighted_router(
+    provider: InferenceProvider,
+    strict_params: bool,
+) -> RouterConfig {
+    RouterConfig {
+        load_balance: BalanceConfig::from(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ProviderWeighted {
+                providers: nes![WeightedProvider {
+                    provider,
+                    weight: Decimal::try_from(1.0).unwrap(),
+                    schedule: None,
+                }],
+                sticky: false,
+                seed: None,
+            },
+        )])),
+        strict_params,
+        ..Default::default()
+    }
+}
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([
+        (
+            RouterId::Named(CompactString::new("openai")),
+            weighted_router(InferenceProvider::OpenAI, false),
+        ),
+        (
+            RouterId::Named(CompactString::new("openai-strict")),
+            weighted_router(InferenceProvider::OpenAI, true),
+        ),
+        (
+            RouterId::Named(CompactString::new("anthropic")),
+            weighted_router(InferenceProvider::Anthropic, false),
+        ),
+    ]));
+    config
+}
+
+fn chat_request(router: &str, body: &Value) -> Request<axum_core::body::Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router}/chat/completions"
+        ))
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(body).unwrap(),
+        ))
+        .unwrap()
+}
+
+async fn received_body(
+    server: &stubr::wiremock_rs::MockServer,
+    path: &str,
+) -> Value {
+    let received_requests = server.received_requests().await.unwrap();
+    let request = received_requests
+        .into_iter()
+        .find(|request| request.url.path() == path)
+        .expect("the provider should receive the request");
+    serde_json::from_slice(&request.body).unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn reasoning_model_params_are_adjusted() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = chat_request(
+        "openai",
+        &json!({
+            "model": "openai/o3-mini",
+            "max_tokens": 100,
+            "temperature": 0.5,
+            "messages": [{ "role": "user", "content": "Hello, world!" }]
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-params-adjusted").unwrap(),
+        "max_tokens->max_completion_tokens, -temperature"
+    );
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let body = received_body(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(body["max_completion_tokens"], 100);
+    assert!(body.get("max_tokens").is_none());
+    assert!(body.get("temperature").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn strict_params_routers_send_params_as_is() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = chat_request(
+        "openai-strict",
+        &json!({
+            "model": "openai/o3-mini",
+            "max_tokens": 100,
+            "temperature": 0.5,
+            "messages": [{ "role": "user", "content": "Hello, world!" }]
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("helicone-params-adjusted").is_none());
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let body = received_body(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(body["max_tokens"], 100);
+    assert_eq!(body["temperature"], 0.5);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn reasoning_effort_enables_anthropic_extended_thinking() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = chat_request(
+        "anthropic",
+        &json!({
+            "model": "anthropic/claude-3-7-sonnet",
+            "max_completion_tokens": 4000,
+            "reasoning_effort": "high",
+            "messages": [{ "role": "user", "content": "Hello, world!" }]
+        }),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-params-adjusted").unwrap(),
+        "reasoning_effort->thinking.budget_tokens"
+    );
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let body =
+        received_body(&harness.mock.anthropic_mock.http_server, "/v1/messages")
+            .await;
+    assert_eq!(
+        body["thinking"],
+        json!({ "type": "enabled", "budget_tokens": 3200 })
+    );
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashMap, num::NonZeroU32, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        monitor::{ProbeKind, ProbeMonitorConfig},
+        router::{RouterConfig, RouterConfigs},
+    },
+    discover::monitor::{health::HealthMonitor, probe::ProbeMonitor},
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn probe_config(features: HeliconeFeatures) -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = features;
+    config.discover.monitor.probes = Some(ProbeMonitorConfig {
+        interval: Duration::from_millis(10),
+        timeout: Duration::from_secs(1),
+        kind: ProbeKind::Completion,
+        unhealthy_threshold: NonZeroU32::new(2).unwrap(),
+        healthy_threshold: NonZeroU32::MIN,
+    });
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.50).unwrap(),
+                    schedule: None,
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.50).unwrap(),
+                    schedule: None,
+                },
+            ],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn failing_probes_remove_provider_from_lb_pool() {
+    // Disable auth for this test since we're testing probes
+    let config = probe_config(HeliconeFeatures::None);
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            // probes and requests to openai both succeed
+            ("success:openai:chat_completion", (1..).into()),
+            ("error:anthropic:messages", (1..).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let app_state = harness.app_factory.state.clone();
+    let probe_monitor = ProbeMonitor::new(app_state.clone());
+    tokio::spawn(async move {
+        probe_monitor.run_forever().await.unwrap();
+    });
+    let health_monitor = HealthMonitor::new(app_state.clone());
+    tokio::spawn(async move {
+        health_monitor.run_forever().await.unwrap();
+    });
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !app_state
+            .0
+            .unhealthy_probes
+            .read()
+            .await
+            .contains(&InferenceProvider::Anthropic)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("anthropic should fail its probes");
+    assert!(
+        !app_state
+            .0
+            .unhealthy_probes
+            .read()
+            .await
+            .contains(&InferenceProvider::OpenAI)
+    );
+    // give the health monitor time to remove anthropic from the balancer
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let body_bytes = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+    for _ in 0..20 {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://router.helicone.com/router/my-router/chat/completions")
+            .body(axum_core::body::Body::from(body_bytes.clone()))
+            .unwrap();
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["helicone-provider"], "openai");
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn probes_are_not_logged() {
+    let config = probe_config(HeliconeFeatures::All);
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", (1..).into()),
+            ("success:anthropic:messages", (1..).into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+            ("success:jawn:sign_s3_url", 0.into()),
+        ]))
+        .build();
+    let harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+    let probe_monitor = ProbeMonitor::new(harness.app_factory.state.clone());
+    tokio::spawn(async move {
+        probe_monitor.run_forever().await.unwrap();
+    });
+
+    // wait for a few rounds of probes
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let probes = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert!(!probes.is_empty());
+    let logs = harness
+        .mock
+        .jawn_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert!(logs.is_empty());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn providers_failing_model_probes_are_reported_down() {
+    let mut config = probe_config(HeliconeFeatures::None);
+    config.discover.monitor.probes.as_mut().unwrap().kind = ProbeKind::Models;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:models", (1..).into()),
+            ("error:anthropic:models", (1..).into()),
+            ("success:openai:chat_completion", (1..).into()),
+            ("success:anthropic:messages", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let app_state = harness.app_factory.state.clone();
+    let probe_monitor = ProbeMonitor::new(app_state.clone());
+    tokio::spawn(async move {
+        probe_monitor.run_forever().await.unwrap();
+    });
+    let health_monitor = HealthMonitor::new(app_state.clone());
+    tokio::spawn(async move {
+        health_monitor.run_forever().await.unwrap();
+    });
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !app_state
+            .0
+            .unhealthy_probes
+            .read()
+            .await
+            .contains(&InferenceProvider::Anthropic)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("anthropic should fail its probes");
+    // give the health monitor time to remove anthropic from the balancer
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("http://router.helicone.com/health/providers")
+        .body(axum_core::body::Body::empty())
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let providers = body["providers"].as_array().unwrap();
+    assert_eq!(providers.len(), 2);
+    let status_of = |name: &str| {
+        let provider = providers
+            .iter()
+            .find(|provider| provider["provider"] == name)
+            .unwrap();
+        assert!(provider["last_checked"].is_string());
+        provider["status"].as_str().unwrap().to_string()
+    };
+    assert_eq!(status_of("openai"), "up");
+    assert_eq!(status_of("anthropic"), "down");
+
+    let body_bytes = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+    for _ in 0..20 {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://router.helicone.com/router/my-router/chat/completions")
+            .body(axum_core::body::Body::from(body_bytes.clone()))
+            .unwrap();
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["helicone-provider"], "openai");
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::BalanceConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+const PROMPT_CACHING_BETA: &str = "prompt-caching-2024-07-31";
+
+fn config(load_balance: BalanceConfig) -> Config {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing request mapping
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn cached_request(model: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": [
+                        {
+                            "type": "text",
+                            "text": "You are a helpful assistant.",
+                            "cache_control": { "type": "ephemeral" }
+                        }
+                    ]
+                },
+                {
+                    "role": "user",
+                    "content": [
+                        {
+                            "type": "text",
+                            "text": "Here is a very long document."
+                        },
+                        {
+                            "type": "text",
+                            "text": "The end of the document.",
+                            "cache_control": { "type": "ephemeral" }
+                        }
+                    ]
+                },
+                {
+                    "role": "assistant",
+                    "content": "I have read the document."
+                },
+                {
+                    "role": "user",
+                    "content": "Summarize it."
+                }
+            ],
+            "tools": [
+                {
+                    "type": "function",
+                    "function": {
+                        "name": "search",
+                        "parameters": { "type": "object" }
+                    },
+                    "cache_control": { "type": "ephemeral" }
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .header("anthropic-beta", PROMPT_CACHING_BETA)
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn anthropic_request_keeps_cache_control() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(BalanceConfig::anthropic_chat()))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = cached_request("anthropic/claude-sonnet-4-0");
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .anthropic_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/messages")
+        .expect("provider should receive the request");
+    assert_eq!(
+        provider_request.headers.get("anthropic-beta").unwrap(),
+        PROMPT_CACHING_BETA
+    );
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    let ephemeral = json!({ "type": "ephemeral" });
+    assert_eq!(
+        body["system"],
+        json!([{
+            "type": "text",
+            "text": "You are a helpful assistant.",
+            "cache_control": ephemeral
+        }])
+    );
+    let document = &body["messages"][0]["content"];
+    assert!(document[0].get("cache_control").is_none());
+    assert_eq!(document[1]["text"], "The end of the document.");
+    assert_eq!(document[1]["cache_control"], ephemeral);
+    // content without a breakpoint is left as is
+    assert!(!body["messages"][2].to_string().contains("cache_control"));
+    assert_eq!(body["tools"][0]["cache_control"], ephemeral);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn other_providers_do_not_receive_cache_control() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(BalanceConfig::openai_chat()))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request = cached_request("openai/gpt-4o-mini");
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/chat/completions")
+        .expect("provider should receive the request");
+    assert!(!provider_request.headers.contains_key("anthropic-beta"));
+    let body = String::from_utf8_lossy(&provider_request.body);
+    assert!(!body.contains("cache_control"), "forwarded: {body}");
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn automatic_breakpoints_and_vendor_fields_are_mapped_to_anthropic() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config(BalanceConfig::anthropic_chat()))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let document = "A very long document. ".repeat(500);
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "anthropic/claude-sonnet-4-0",
+            "messages": [
+                {
+                    "role": "system",
+                    "content": "You are a helpful assistant."
+                },
+                {
+                    "role": "user",
+                    "content": document
+                },
+                {
+                    "role": "assistant",
+                    "content": "I have read the document."
+                },
+                {
+                    "role": "user",
+                    "content": "Summarize it."
+                }
+            ],
+            "anthropic": {
+                "top_k": 5
+            }
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .header("helicone-prompt-cache", "auto")
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .anthropic_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/messages")
+        .expect("provider should receive the request");
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    let ephemeral = json!({ "type": "ephemeral" });
+    assert_eq!(
+        body["system"],
+        json!([{
+            "type": "text",
+            "text": "You are a helpful assistant.",
+            "cache_control": ephemeral
+        }])
+    );
+    assert_eq!(
+        body["messages"][0]["content"],
+        json!([{
+            "type": "text",
+            "text": document,
+            "cache_control": ephemeral
+        }])
+    );
+    // the last user message is too short to be cached
+    assert!(!body["messages"][2].to_string().contains("cache_control"));
+    assert_eq!(body["top_k"], 5);
+    assert!(body.get("anthropic").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn anthropic_cache_usage_is_reported_and_logged() {
+    let mut config = config(BalanceConfig::anthropic_chat());
+    config.helicone.features = HeliconeFeatures::All;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages_cached", 1.into()),
+            ("success:minio:upload_request", 1.into()),
+            ("success:jawn:sign_s3_url", 1.into()),
+            ("success:jawn:log_request", 1.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+
+    let mut request = cached_request("anthropic/claude-sonnet-4-0");
+    request.headers_mut().insert(
+        "authorization",
+        "Bearer sk-helicone-test-key".parse().unwrap(),
+    );
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let usage = &body["usage"];
+    assert_eq!(usage["prompt_tokens"], 50 + 2048);
+    assert_eq!(usage["total_tokens"], 50 + 2048 + 503);
+    assert_eq!(usage["prompt_tokens_details"]["cached_tokens"], 2048);
+    assert_eq!(usage["cache_creation_input_tokens"], 0);
+    assert_eq!(usage["cache_read_input_tokens"], 2048);
+
+    // sleep so that the background task for logging can complete
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let received_requests = harness
+        .mock
+        .jawn_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let log_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/log/request")
+        .expect("the request should be logged");
+    let log: serde_json::Value =
+        serde_json::from_slice(&log_request.body).unwrap();
+    assert_eq!(log["log"]["response"]["cacheReadInputTokens"], 2048);
+    assert_eq!(log["log"]["response"]["cacheCreationInputTokens"], 0);
+}
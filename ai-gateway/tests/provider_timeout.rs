@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner},
+        dispatcher::TimeoutsConfig,
+        helicone::HeliconeFeatures,
+        retry::RetryConfig,
+        router::{RouterConfig, RouterConfigs},
+    },
+    discover::monitor::rate_limit::RateLimitMonitor,
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::{nes, nev};
+use serde_json::json;
+use stubr::wiremock_rs::{Mock, ResponseTemplate, matchers};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpSocket, TcpStream},
+};
+use tower::Service;
+use url::Url;
+
+fn chat_request() -> Request<axum_core::body::Body> {
+    request("openai/gpt-4o-mini", false)
+}
+
+fn request(model: &str, stream: bool) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": stream
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn slow_provider_times_out_and_is_deprioritized() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing timeouts
+    config.helicone.features = HeliconeFeatures::None;
+    config
+        .providers
+        .get_mut(&InferenceProvider::OpenAI)
+        .unwrap()
+        .timeouts
+        .request = Some(Duration::from_millis(200));
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::Priority {
+            providers: nev![
+                InferenceProvider::OpenAI,
+                InferenceProvider::Anthropic
+            ],
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 0.into()),
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    Mock::given(matchers::method("POST"))
+        .and(matchers::path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({}))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .with_priority(1)
+        .expect(2)
+        .named("slow:openai:chat_completion")
+        .mount(&harness.mock.openai_mock.http_server)
+        .await;
+
+    let rate_limit_monitor =
+        RateLimitMonitor::new(harness.app_factory.state.clone());
+    tokio::spawn(async move {
+        rate_limit_monitor.run_forever().await.unwrap();
+    });
+    // Give time for the monitor to pick up the new router (polls every 100ms in
+    // test mode)
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    // the provider is deprioritized after repeated timeouts
+    for _ in 0..2 {
+        let response = harness.call(chat_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+    // give the monitor time to remove the provider
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-provider").unwrap(),
+        "anthropic"
+    );
+    let _response_body = response.into_body().collect().await.unwrap();
+}
+
+const HYPERBOLIC_MODEL: &str = "hyperbolic/meta-llama/Llama-3.3-70B-Instruct";
+
+/// A router sending every request to `provider`, which has no mock server
+/// overriding its base URL if it's Hyperbolic.
+fn router_config(
+    provider: InferenceProvider,
+    timeouts: TimeoutsConfig,
+) -> Config {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing timeouts
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::from(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::BalancedLatency {
+                    providers: nes![provider],
+                },
+            )])),
+            timeouts,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+async fn harness(config: Config) -> Harness {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await
+}
+
+fn hyperbolic_base_url(config: &mut Config, base_url: Url) {
+    config
+        .providers
+        .get_mut(&InferenceProvider::Named("hyperbolic".into()))
+        .unwrap()
+        .base_url = base_url;
+}
+
+/// A server which never accepts connections. Its backlog is filled, after
+/// which connection attempts go unanswered.
+async fn unresponsive_server() -> (Url, TcpListener, Vec<TcpStream>) {
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(1).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut backlog = Vec::new();
+    for _ in 0..3 {
+        let connect = TcpStream::connect(addr);
+        if let Ok(Ok(stream)) =
+            tokio::time::timeout(Duration::from_millis(100), connect).await
+        {
+            backlog.push(stream);
+        }
+    }
+    let url = Url::parse(&format!("http://{addr}/")).unwrap();
+    (url, listener, backlog)
+}
+
+/// Serves a single SSE response by hand which sends one event and then
+/// nothing, until the connection is closed.
+async fn stalled_sse_server() -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url =
+        Url::parse(&format!("http://{}/", listener.local_addr().unwrap()))
+            .unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        // the request is small enough to arrive in a single read
+        let mut request = [0_u8; 8192];
+        let _ = socket.read(&mut request).await.unwrap();
+        let chunk = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 1_741_569_952,
+            "model": "meta-llama/Llama-3.3-70B-Instruct",
+            "choices": [{
+                "index": 0,
+                "delta": { "role": "assistant", "content": "Hello" },
+                "finish_reason": null
+            }]
+        });
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: \
+             text/event-stream\r\nconnection: close\r\n\r\ndata: {chunk}\n\n"
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        // hold the connection open until the gateway gives up on it
+        let mut buf = [0_u8; 1];
+        let _ = tokio::time::timeout(
+            Duration::from_secs(10),
+            socket.read(&mut buf),
+        )
+        .await;
+    });
+    url
+}
+
+async fn error_message(
+    response: http::Response<axum_core::body::Body>,
+) -> String {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+    body["error"]["message"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn router_request_timeout_responds_with_gateway_timeout() {
+    let timeouts = TimeoutsConfig {
+        request: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let config = router_config(InferenceProvider::OpenAI, timeouts);
+    let mut harness = harness(config).await;
+    Mock::given(matchers::method("POST"))
+        .and(matchers::path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({}))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .expect(1)
+        .named("slow:openai:chat_completion")
+        .mount(&harness.mock.openai_mock.http_server)
+        .await;
+
+    let started = Instant::now();
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(error_message(response).await.contains("did not respond"));
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn each_retry_gets_the_full_request_timeout() {
+    let timeouts = TimeoutsConfig {
+        request: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            timeouts,
+            retries: Some(RetryConfig::test_default()),
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    // the first attempt times out, the retry responds in time
+    Mock::given(matchers::method("POST"))
+        .and(matchers::path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({}))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .with_priority(1)
+        .up_to_n_times(1)
+        .expect(1)
+        .named("slow:openai:chat_completion")
+        .mount(&harness.mock.openai_mock.http_server)
+        .await;
+
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn provider_connect_timeout_responds_with_gateway_timeout() {
+    let (base_url, _listener, _backlog) = unresponsive_server().await;
+    let hyperbolic = InferenceProvider::Named("hyperbolic".into());
+    let mut config =
+        router_config(hyperbolic.clone(), TimeoutsConfig::default());
+    hyperbolic_base_url(&mut config, base_url);
+    config
+        .providers
+        .get_mut(&hyperbolic)
+        .unwrap()
+        .timeouts
+        .connect = Some(Duration::from_millis(200));
+    let mut harness = harness(config).await;
+
+    let started = Instant::now();
+    let response = harness
+        .call(request(HYPERBOLIC_MODEL, false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(
+        error_message(response)
+            .await
+            .contains("did not accept a connection")
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn idle_stream_ends_with_an_error_event() {
+    let timeouts = TimeoutsConfig {
+        stream_idle: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let hyperbolic = InferenceProvider::Named("hyperbolic".into());
+    let mut config = router_config(hyperbolic, timeouts);
+    hyperbolic_base_url(&mut config, stalled_sse_server().await);
+    let mut harness = harness(config).await;
+
+    let response = harness.call(request(HYPERBOLIC_MODEL, true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // the stream ends rather than hanging until the provider gives up
+    let body = tokio::time::timeout(
+        Duration::from_secs(2),
+        response.into_body().collect(),
+    )
+    .await
+    .expect("stream should end once idle")
+    .unwrap()
+    .to_bytes();
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("Hello"));
+    let error_event = body.split("event: error\n").nth(1).unwrap();
+    assert!(error_event.contains("No event received from the provider"));
+}
@@ -1,17 +1,27 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, num::NonZeroU32, time::Duration};
 
 use ai_gateway::{
     config::{
         Config,
+        balance::BalanceConfig,
         helicone::HeliconeFeatures,
-        rate_limit::{RateLimitConfig, RateLimitStore},
+        rate_limit::{
+            GcraConfig, LimitsConfig, ModelLimitConfig, QueueConfig,
+            RateLimitConfig, RateLimitStore,
+        },
+        redis::{RedisConfig, UnavailablePolicy},
+        router::{RouterConfig, RouterConfigs},
+    },
+    control_plane::types::{
+        Key, MessageTypeRX, RateLimitRule, Update, hash_key,
     },
-    control_plane::types::{Key, hash_key},
     tests::{TestDefault, harness::Harness, mock::MockArgs},
-    types::org::OrgId,
+    types::{org::OrgId, router::RouterId, secret::Secret, user::UserId},
 };
+use compact_str::CompactString;
 use http::{Method, Request, StatusCode};
 use http_body_util::BodyExt;
+use indexmap::IndexMap;
 use serde_json::json;
 use tower::Service;
 use uuid::Uuid;
@@ -226,6 +236,475 @@ async fn rate_limit_per_user_isolation_impl(
     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
 }
 
+#[tokio::test]
+#[serial_test::serial]
+async fn rate_limit_headers_are_returned() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit = Some(RateLimitConfig {
+        store: Some(RateLimitStore::InMemory),
+        limits: LimitsConfig {
+            // one request per second, with bursts of 3
+            per_api_key: GcraConfig {
+                capacity: NonZeroU32::new(3).unwrap(),
+                refill_frequency: Duration::from_secs(3),
+            },
+            per_org: None,
+            tokens_per_minute: None,
+            per_model: IndexMap::new(),
+        },
+        queue: None,
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 3.into()),
+            ("success:minio:upload_request", 3.into()),
+            ("success:jawn:log_request", 3.into()),
+            ("success:jawn:sign_s3_url", 3.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+    let auth_header = "Bearer sk-helicone-test-key";
+
+    for remaining in ["2", "1", "0"] {
+        let response = make_chat_request(&mut harness, auth_header).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers.get("x-ratelimit-limit").unwrap(), "3");
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), remaining);
+        assert!(headers.contains_key("x-ratelimit-reset"));
+        let _body = response.into_body().collect().await.unwrap();
+    }
+
+    for _ in 0..3 {
+        let response = make_chat_request(&mut harness, auth_header).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers();
+        assert_eq!(headers.get("retry-after").unwrap(), "1");
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "0");
+        assert_eq!(headers.get("x-ratelimit-reset").unwrap(), "3");
+        let _body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn rate_limit_per_org_is_shared_by_api_keys() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit = Some(RateLimitConfig {
+        store: Some(RateLimitStore::InMemory),
+        limits: LimitsConfig {
+            per_api_key: GcraConfig {
+                capacity: NonZeroU32::new(5).unwrap(),
+                refill_frequency: Duration::from_secs(5),
+            },
+            per_org: Some(GcraConfig {
+                capacity: NonZeroU32::new(3).unwrap(),
+                refill_frequency: Duration::from_secs(3),
+            }),
+            tokens_per_minute: None,
+            per_model: IndexMap::new(),
+        },
+        queue: None,
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 3.into()),
+            ("success:minio:upload_request", 3.into()),
+            ("success:jawn:log_request", 3.into()),
+            ("success:jawn:sign_s3_url", 3.into()),
+        ]))
+        .build();
+    let user1_auth = "sk-helicone-user1-key";
+    let user2_auth = "sk-helicone-user2-key";
+    let org_id = OrgId::new(Uuid::new_v4());
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_auth_keys(vec![
+            Key {
+                key_hash: hash_key(user1_auth),
+                owner_id: Uuid::new_v4().to_string(),
+                organization_id: org_id,
+            },
+            Key {
+                key_hash: hash_key(user2_auth),
+                owner_id: Uuid::new_v4().to_string(),
+                organization_id: org_id,
+            },
+        ])
+        .build()
+        .await;
+
+    // the org's fewer remaining requests are reported
+    for remaining in ["2", "1"] {
+        let response =
+            make_chat_request(&mut harness, &format!("Bearer {user1_auth}"))
+                .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("x-ratelimit-remaining").unwrap(),
+            remaining
+        );
+        let _body = response.into_body().collect().await.unwrap();
+    }
+
+    let response =
+        make_chat_request(&mut harness, &format!("Bearer {user2_auth}")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("x-ratelimit-remaining").unwrap(),
+        "0"
+    );
+    let _body = response.into_body().collect().await.unwrap();
+
+    // user2's own limit isn't exhausted, but the org's is
+    let response =
+        make_chat_request(&mut harness, &format!("Bearer {user2_auth}")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get("x-ratelimit-limit").unwrap(), "3");
+    assert!(response.headers().contains_key("retry-after"));
+    let _body = response.into_body().collect().await.unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn rate_limit_tokens_per_minute() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit = Some(RateLimitConfig {
+        store: Some(RateLimitStore::InMemory),
+        limits: LimitsConfig {
+            per_api_key: GcraConfig {
+                capacity: NonZeroU32::new(10).unwrap(),
+                refill_frequency: Duration::from_secs(1),
+            },
+            per_org: None,
+            // the test prompt is estimated at 11 tokens
+            tokens_per_minute: Some(NonZeroU32::new(20).unwrap()),
+            per_model: IndexMap::new(),
+        },
+        queue: None,
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 1.into()),
+            ("success:jawn:log_request", 1.into()),
+            ("success:jawn:sign_s3_url", 1.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+    let auth_header = "Bearer sk-helicone-test-key";
+
+    // tokens are only reconciled once the response completes, so the first
+    // response is read after the second request
+    let first = make_chat_request(&mut harness, auth_header).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let headers = first.headers();
+    assert_eq!(headers.get("x-ratelimit-limit-tokens").unwrap(), "20");
+    assert_eq!(headers.get("x-ratelimit-remaining-tokens").unwrap(), "9");
+
+    let response = make_chat_request(&mut harness, auth_header).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("estimated 11 of the 20 tokens per minute budget"),
+        "{message}"
+    );
+
+    let _body = first.into_body().collect().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn per_model_rate_limits_apply_to_matching_models() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            rate_limit: Some(RateLimitConfig {
+                store: Some(RateLimitStore::InMemory),
+                limits: LimitsConfig {
+                    per_api_key: GcraConfig {
+                        capacity: NonZeroU32::new(10).unwrap(),
+                        refill_frequency: Duration::from_secs(10),
+                    },
+                    per_org: None,
+                    tokens_per_minute: None,
+                    per_model: IndexMap::from([
+                        (
+                            "openai/o3*".to_string(),
+                            ModelLimitConfig {
+                                rpm: NonZeroU32::new(1).unwrap(),
+                            },
+                        ),
+                        (
+                            "*".to_string(),
+                            ModelLimitConfig {
+                                rpm: NonZeroU32::new(2).unwrap(),
+                            },
+                        ),
+                    ]),
+                },
+                queue: None,
+            }),
+            load_balance: BalanceConfig::openai_chat(),
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 3.into()),
+            ("success:minio:upload_request", 3.into()),
+            ("success:jawn:log_request", 3.into()),
+            ("success:jawn:sign_s3_url", 3.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+    let auth_header = "Bearer sk-helicone-test-key";
+
+    // the model's limit has fewer requests remaining than the API key's
+    let response =
+        make_model_request(&mut harness, auth_header, "openai/o3-mini").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("x-ratelimit-limit").unwrap(), "1");
+    assert_eq!(
+        response.headers().get("x-ratelimit-remaining").unwrap(),
+        "0"
+    );
+    let _body = response.into_body().collect().await.unwrap();
+    let response =
+        make_model_request(&mut harness, auth_header, "openai/o3-mini").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.contains("`openai/o3*`"), "{message}");
+
+    // other models have their own, larger budget
+    for remaining in ["1", "0"] {
+        let response =
+            make_model_request(&mut harness, auth_header, "openai/gpt-4o-mini")
+                .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("x-ratelimit-remaining").unwrap(),
+            remaining
+        );
+        let _body = response.into_body().collect().await.unwrap();
+    }
+    let response =
+        make_model_request(&mut harness, auth_header, "openai/gpt-4o-mini")
+            .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.contains("`*`"), "{message}");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn control_plane_rate_limits_are_enforced_by_the_sidecar() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 4.into()),
+            ("success:minio:upload_request", 4.into()),
+            ("success:jawn:log_request", 4.into()),
+            ("success:jawn:sign_s3_url", 4.into()),
+        ]))
+        .build();
+    let user1_auth = "sk-helicone-user1-key";
+    let user2_auth = "sk-helicone-user2-key";
+    let user1_id = Uuid::new_v4();
+    let org_id = OrgId::new(Uuid::new_v4());
+    let mut control_plane_config =
+        ai_gateway::control_plane::types::Config::test_default();
+    control_plane_config.keys = vec![
+        Key {
+            key_hash: hash_key(user1_auth),
+            owner_id: user1_id.to_string(),
+            organization_id: org_id,
+        },
+        Key {
+            key_hash: hash_key(user2_auth),
+            owner_id: Uuid::new_v4().to_string(),
+            organization_id: org_id,
+        },
+    ];
+    control_plane_config.rate_limits = vec![RateLimitRule {
+        organization_id: org_id,
+        user_id: Some(UserId::new(user1_id)),
+        capacity: NonZeroU32::new(2).unwrap(),
+        refill_frequency_ms: 60_000,
+    }];
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_control_plane_config(control_plane_config)
+        .build()
+        .await;
+
+    for remaining in ["1", "0"] {
+        let response =
+            make_chat_request(&mut harness, &format!("Bearer {user1_auth}"))
+                .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-ratelimit-limit").unwrap(), "2");
+        assert_eq!(
+            response.headers().get("x-ratelimit-remaining").unwrap(),
+            remaining
+        );
+        let _body = response.into_body().collect().await.unwrap();
+    }
+    let response =
+        make_chat_request(&mut harness, &format!("Bearer {user1_auth}")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let _body = response.into_body().collect().await.unwrap();
+
+    // the limit is user1's own, not their organization's
+    let response =
+        make_chat_request(&mut harness, &format!("Bearer {user2_auth}")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-ratelimit-limit"));
+    let _body = response.into_body().collect().await.unwrap();
+
+    // limits are refreshed when the control plane pushes them
+    harness
+        .app_factory
+        .state
+        .0
+        .control_plane_state
+        .write()
+        .await
+        .update(MessageTypeRX::Update(Update::RateLimits {
+            data: Vec::new(),
+        }));
+    let response =
+        make_chat_request(&mut harness, &format!("Bearer {user1_auth}")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+}
+
+/// A global limit of 2 requests every 500ms, whose requests over it wait in a
+/// queue.
+fn queued_rate_limit(max_wait: Duration) -> RateLimitConfig {
+    RateLimitConfig {
+        store: Some(RateLimitStore::InMemory),
+        limits: LimitsConfig {
+            per_api_key: GcraConfig {
+                capacity: NonZeroU32::new(2).unwrap(),
+                refill_frequency: Duration::from_millis(500),
+            },
+            per_org: None,
+            tokens_per_minute: None,
+            per_model: IndexMap::new(),
+        },
+        queue: Some(QueueConfig {
+            max_wait,
+            max_queue_depth: 10,
+        }),
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn queued_requests_over_the_limit_eventually_succeed() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit = Some(queued_rate_limit(Duration::from_secs(5)));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 6.into()),
+            ("success:minio:upload_request", 6.into()),
+            ("success:jawn:log_request", 6.into()),
+            ("success:jawn:sign_s3_url", 6.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+
+    // 4 of the 6 requests are over the limit, and are let through as it
+    // refills, one every 250ms
+    let started = std::time::Instant::now();
+    let requests = (0..6)
+        .map(|_| harness.call(chat_request("Bearer sk-helicone-test-key")))
+        .collect::<Vec<_>>();
+    for response in futures::future::join_all(requests).await {
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _body = response.into_body().collect().await.unwrap();
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn queued_requests_are_rejected_after_max_wait() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit =
+        Some(queued_rate_limit(Duration::from_millis(100)));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 2.into()),
+            ("success:minio:upload_request", 2.into()),
+            ("success:jawn:log_request", 2.into()),
+            ("success:jawn:sign_s3_url", 2.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+    let auth_header = "Bearer sk-helicone-test-key";
+
+    for _ in 0..2 {
+        let response = make_chat_request(&mut harness, auth_header).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let _body = response.into_body().collect().await.unwrap();
+    }
+    // the limit refills in 250ms, longer than the request may wait
+    let response = make_chat_request(&mut harness, auth_header).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let _body = response.into_body().collect().await.unwrap();
+}
+
 #[tokio::test]
 #[serial_test::serial]
 async fn rate_limit_disabled() {
@@ -262,6 +741,82 @@ async fn rate_limit_disabled() {
     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
 }
 
+/// A Redis that nothing listens on.
+fn unreachable_redis(on_unavailable: UnavailablePolicy) -> RateLimitStore {
+    RateLimitStore::Redis(RedisConfig {
+        host_url: Secret::from(
+            "redis://127.0.0.1:1".parse::<url::Url>().unwrap(),
+        ),
+        connection_timeout: Duration::from_millis(100),
+        on_unavailable,
+    })
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn rate_limit_fails_open_without_redis() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit = Some(RateLimitConfig {
+        store: Some(unreachable_redis(UnavailablePolicy::FailOpen)),
+        limits: LimitsConfig::test_default(),
+        queue: None,
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 1.into()),
+            ("success:jawn:log_request", 1.into()),
+            ("success:jawn:sign_s3_url", 1.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+
+    let response =
+        make_chat_request(&mut harness, "Bearer sk-helicone-test-key").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-ratelimit-limit"));
+    let _body = response.into_body().collect().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn rate_limit_fails_closed_without_redis() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::All;
+    config.global.rate_limit = Some(RateLimitConfig {
+        store: Some(unreachable_redis(UnavailablePolicy::FailClosed)),
+        limits: LimitsConfig::test_default(),
+        queue: None,
+    });
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+            ("success:jawn:sign_s3_url", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_mock_auth()
+        .build()
+        .await;
+
+    let response =
+        make_chat_request(&mut harness, "Bearer sk-helicone-test-key").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.headers().contains_key("retry-after"));
+    let _body = response.into_body().collect().await.unwrap();
+}
+
 async fn make_chat_request(
     harness: &mut Harness,
     auth_header: &str,
@@ -271,8 +826,19 @@ async fn make_chat_request(
         Box<dyn std::error::Error + Send + Sync + 'static>,
     >,
 > {
-    let body_bytes = serde_json::to_vec(&json!({
-        "model": "openai/gpt-4o-mini",
+    make_model_request(harness, auth_header, "openai/gpt-4o-mini").await
+}
+
+fn chat_request(auth_header: &str) -> Request<axum_core::body::Body> {
+    model_request(auth_header, "openai/gpt-4o-mini")
+}
+
+fn model_request(
+    auth_header: &str,
+    model: &str,
+) -> Request<axum_core::body::Body> {
+    let body = serde_json::to_vec(&json!({
+        "model": model,
         "messages": [
             {
                 "role": "user",
@@ -281,15 +847,25 @@ async fn make_chat_request(
         ]
     }))
     .unwrap();
-
-    let request_body = axum_core::body::Body::from(body_bytes);
-    let request = Request::builder()
+    Request::builder()
         .method(Method::POST)
         .header("authorization", auth_header)
         .uri("http://router.helicone.com/router/my-router/chat/completions")
-        .body(request_body)
-        .unwrap();
+        .body(axum_core::body::Body::from(body))
+        .unwrap()
+}
 
+async fn make_model_request(
+    harness: &mut Harness,
+    auth_header: &str,
+    model: &str,
+) -> http::Response<
+    tower_http::body::UnsyncBoxBody<
+        bytes::Bytes,
+        Box<dyn std::error::Error + Send + Sync + 'static>,
+    >,
+> {
+    let request = model_request(auth_header, model);
     let response = harness.call(request).await.unwrap();
 
     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
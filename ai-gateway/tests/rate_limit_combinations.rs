@@ -15,6 +15,7 @@ use ai_gateway::{
 use compact_str::CompactString;
 use http::{Method, Request, StatusCode};
 use http_body_util::BodyExt;
+use indexmap::IndexMap;
 use serde_json::json;
 use tower::Service;
 
@@ -24,6 +25,9 @@ fn create_test_limits(capacity: u32, duration_ms: u64) -> LimitsConfig {
             capacity: capacity.try_into().unwrap(),
             refill_frequency: Duration::from_millis(duration_ms),
         },
+        per_org: None,
+        tokens_per_minute: None,
+        per_model: IndexMap::new(),
     }
 }
 
@@ -121,6 +125,7 @@ async fn test_global_rate_limit_with_router_none() {
         // 3 requests per second
         limits: create_test_limits(3, 1000),
         store: None,
+        queue: None,
     });
     config.rate_limit_store = Some(RateLimitStore::InMemory);
 
@@ -199,6 +204,7 @@ async fn test_router_specific_with_custom_limits() {
             rate_limit: Some(RateLimitConfig {
                 limits: create_test_limits(2, 1000), // 2 requests per second
                 store: None,
+                queue: None,
             }),
             load_balance:
                 ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -256,6 +262,7 @@ async fn test_global_with_custom_router_override() {
         // 5 requests per second
         limits: create_test_limits(5, 1000),
         store: None,
+        queue: None,
     });
     config.rate_limit_store = Some(RateLimitStore::InMemory);
     // Router overrides with stricter custom limits
@@ -266,6 +273,7 @@ async fn test_global_with_custom_router_override() {
                 limits: create_test_limits(2, 1000), /* 2 requests per second
                                                       * for this router */
                 store: None,
+                queue: None,
             }),
             load_balance:
                 ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -331,6 +339,7 @@ async fn test_router_independence_different_rate_limits() {
                     store: None,
                     limits: create_test_limits(1, 1000), /* 1 request per
                                                          second - strict */
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -344,6 +353,7 @@ async fn test_router_independence_different_rate_limits() {
                     store: None,
                     limits: create_test_limits(5, 1000), /* 5 requests per
                                                          second - lenient */
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -502,6 +512,7 @@ async fn test_multi_router_different_rate_limits_in_memory() {
                 rate_limit: Some(RateLimitConfig {
                     store: Some(RateLimitStore::InMemory),
                     limits: create_test_limits(1, 1000),
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -514,6 +525,7 @@ async fn test_multi_router_different_rate_limits_in_memory() {
                 rate_limit: Some(RateLimitConfig {
                     store: Some(RateLimitStore::InMemory),
                     limits: create_test_limits(3, 1000),
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -322,6 +322,7 @@ async fn cache_enabled_per_router() {
                     directive: None,
                     buckets: 1,
                     seed: Some("router-cached-seed".to_string()),
+                    ..Default::default()
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -16,6 +16,7 @@ use ai_gateway::{
 use compact_str::CompactString;
 use http::{Method, Request, StatusCode};
 use http_body_util::BodyExt;
+use indexmap::IndexMap;
 use serde_json::json;
 use tower::Service;
 
@@ -25,6 +26,9 @@ fn create_test_limits(capacity: u32, duration_ms: u64) -> LimitsConfig {
             capacity: capacity.try_into().unwrap(),
             refill_frequency: Duration::from_millis(duration_ms),
         },
+        per_org: None,
+        tokens_per_minute: None,
+        per_model: IndexMap::new(),
     }
 }
 
@@ -124,10 +128,12 @@ async fn test_global_rate_limit_with_router_none() {
         // 3 requests per 5 seconds
         limits: create_test_limits(3, 1000),
         store: None,
+        queue: None,
     });
     config.rate_limit_store = Some(RateLimitStore::Redis(RedisConfig {
         host_url: Secret::from(REDIS_URL.parse::<url::Url>().unwrap()),
         connection_timeout: Duration::from_secs(10),
+        ..Default::default()
     }));
 
     // Router doesn't override rate limiting
@@ -199,6 +205,7 @@ async fn test_router_specific_with_custom_limits() {
     config.rate_limit_store = Some(RateLimitStore::Redis(RedisConfig {
         host_url: Secret::from(REDIS_URL.parse::<url::Url>().unwrap()),
         connection_timeout: Duration::from_secs(1),
+        ..Default::default()
     }));
 
     // Router provides its own custom rate limits
@@ -211,8 +218,10 @@ async fn test_router_specific_with_custom_limits() {
                         REDIS_URL.parse::<url::Url>().unwrap(),
                     ),
                     connection_timeout: Duration::from_secs(1),
+                    ..Default::default()
                 })),
                 limits: create_test_limits(2, 1000), // 2 requests per second
+                queue: None,
             }),
             load_balance:
                 ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -270,10 +279,12 @@ async fn test_global_with_custom_router_override() {
         // 5 requests per second
         limits: create_test_limits(5, 1000),
         store: None,
+        queue: None,
     });
     config.rate_limit_store = Some(RateLimitStore::Redis(RedisConfig {
         host_url: Secret::from(REDIS_URL.parse::<url::Url>().unwrap()),
         connection_timeout: Duration::from_secs(1),
+        ..Default::default()
     }));
 
     // Router overrides with stricter custom limits
@@ -286,9 +297,11 @@ async fn test_global_with_custom_router_override() {
                         REDIS_URL.parse::<url::Url>().unwrap(),
                     ),
                     connection_timeout: Duration::from_secs(1),
+                    ..Default::default()
                 })),
                 limits: create_test_limits(2, 1000), /* 2 requests per second
                                                       * for this router */
+                queue: None,
             }),
             load_balance:
                 ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -344,6 +357,7 @@ async fn test_router_independence_different_rate_limits() {
     config.rate_limit_store = Some(RateLimitStore::Redis(RedisConfig {
         host_url: Secret::from(REDIS_URL.parse::<url::Url>().unwrap()),
         connection_timeout: Duration::from_secs(1),
+        ..Default::default()
     }));
 
     let strict_router_id = RouterId::Named(CompactString::from("strict"));
@@ -360,9 +374,11 @@ async fn test_router_independence_different_rate_limits() {
                             REDIS_URL.parse::<url::Url>().unwrap(),
                         ),
                         connection_timeout: Duration::from_secs(1),
+                        ..Default::default()
                     })),
                     limits: create_test_limits(1, 1000), /* 1 request per
                                                          second - strict */
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -378,9 +394,11 @@ async fn test_router_independence_different_rate_limits() {
                             REDIS_URL.parse::<url::Url>().unwrap(),
                         ),
                         connection_timeout: Duration::from_secs(1),
+                        ..Default::default()
                     })),
                     limits: create_test_limits(5, 1000), /* 5 requests per
                                                          second - lenient */
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -529,6 +547,7 @@ async fn test_multi_router_different_rate_limits_in_memory() {
     config.rate_limit_store = Some(RateLimitStore::Redis(RedisConfig {
         host_url: Secret::from(REDIS_URL.parse::<url::Url>().unwrap()),
         connection_timeout: Duration::from_secs(1),
+        ..Default::default()
     }));
     let router_a_id = RouterId::Named(CompactString::from("router-a"));
     let router_b_id = RouterId::Named(CompactString::from("router-b"));
@@ -545,8 +564,10 @@ async fn test_multi_router_different_rate_limits_in_memory() {
                             REDIS_URL.parse::<url::Url>().unwrap(),
                         ),
                         connection_timeout: Duration::from_secs(1),
+                        ..Default::default()
                     })),
                     limits: create_test_limits(1, 1000),
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -562,8 +583,10 @@ async fn test_multi_router_different_rate_limits_in_memory() {
                             REDIS_URL.parse::<url::Url>().unwrap(),
                         ),
                         connection_timeout: Duration::from_secs(1),
+                        ..Default::default()
                     })),
                     limits: create_test_limits(3, 1000),
+                    queue: None,
                 }),
                 load_balance:
                     ai_gateway::config::balance::BalanceConfig::openai_chat(),
@@ -667,3 +690,45 @@ async fn test_multi_router_different_rate_limits_in_memory() {
     }
     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
 }
+
+/// Replicas each have their own store, which must agree on one limit even
+/// when their requests race.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[serial_test::serial]
+async fn test_replicas_share_one_limit() {
+    use ai_gateway::middleware::rate_limit::store::{
+        Gcra, GcraStore, RedisStore,
+    };
+
+    let redis_config = RedisConfig {
+        host_url: Secret::from(REDIS_URL.parse::<url::Url>().unwrap()),
+        connection_timeout: Duration::from_secs(1),
+        ..Default::default()
+    };
+    let replicas = [
+        RedisStore::new(&redis_config).unwrap(),
+        RedisStore::new(&redis_config).unwrap(),
+    ];
+    let gcra = Gcra::from(&GcraConfig {
+        capacity: std::num::NonZeroU32::new(5).unwrap(),
+        refill_frequency: Duration::from_secs(60),
+    });
+    let key = format!("rl:per-api-key:test:{}", uuid::Uuid::new_v4());
+    let now = chrono::Utc::now().timestamp_micros();
+
+    let checks = (0..20)
+        .map(|i| {
+            let store = replicas[i % replicas.len()].clone();
+            let key = key.clone();
+            tokio::spawn(async move {
+                store.check(&key, gcra, 1, now).await.unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    let decisions = futures::future::try_join_all(checks).await.unwrap();
+    let allowed = decisions
+        .iter()
+        .filter(|decision| decision.retry_after.is_none())
+        .count();
+    assert_eq!(allowed, 5, "{decisions:#?}");
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner},
+        helicone::HeliconeFeatures,
+        retry::RetryConfig,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nev;
+use serde_json::json;
+use tower::Service;
+
+/// The `Retry-After` of the OpenAI rate limit stub.
+const RETRY_AFTER: Duration = Duration::from_secs(2);
+
+fn priority_router_config() -> RouterConfig {
+    RouterConfig {
+        load_balance: BalanceConfig::from(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::Priority {
+                providers: nev![
+                    InferenceProvider::OpenAI,
+                    InferenceProvider::Anthropic
+                ],
+            },
+        )])),
+        retries: Some(RetryConfig::test_default()),
+        ..Default::default()
+    }
+}
+
+fn priority_config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([
+        (
+            RouterId::Named(CompactString::new("my-router")),
+            priority_router_config(),
+        ),
+        (
+            RouterId::Named(CompactString::new("other-router")),
+            priority_router_config(),
+        ),
+    ]));
+    config
+}
+
+fn chat_request() -> Request<axum_core::body::Body> {
+    router_chat_request("my-router")
+}
+
+fn router_chat_request(router: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router}/chat/completions"
+        ))
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+/// Sends a request, returning the response's status and provider.
+async fn call(harness: &mut Harness) -> (StatusCode, String) {
+    call_router(harness, "my-router").await
+}
+
+async fn call_router(
+    harness: &mut Harness,
+    router: &str,
+) -> (StatusCode, String) {
+    let response = harness.call(router_chat_request(router)).await.unwrap();
+    let status = response.status();
+    let provider = response
+        .headers()
+        .get("helicone-provider")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let _response_body = response.into_body().collect().await.unwrap();
+    (status, provider)
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn rate_limited_provider_is_skipped_until_retry_after_elapses() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("rate_limit:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(priority_config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // the next provider is tried immediately
+    let start = Instant::now();
+    assert_eq!(
+        call(&mut harness).await,
+        (StatusCode::OK, "anthropic".to_string())
+    );
+    assert!(start.elapsed() < RETRY_AFTER);
+
+    // and receives requests until the rate limited provider's backoff has
+    // elapsed
+    assert_eq!(
+        call(&mut harness).await,
+        (StatusCode::OK, "anthropic".to_string())
+    );
+    let openai_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    assert_eq!(openai_requests.len(), 1);
+
+    harness.mock.reset().await;
+    harness
+        .mock
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .await;
+    tokio::time::sleep(RETRY_AFTER).await;
+    assert_eq!(
+        call(&mut harness).await,
+        (StatusCode::OK, "openai".to_string())
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn all_providers_rate_limited_returns_the_shortest_retry_after() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("rate_limit:openai:chat_completion", 1.into()),
+            ("rate_limit:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(priority_config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        response.headers().get(http::header::RETRY_AFTER).unwrap(),
+        "2"
+    );
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    // neither provider is sent requests while both are backing off
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        response.headers().get(http::header::RETRY_AFTER).unwrap(),
+        "2"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn rate_limited_provider_only_backs_off_for_its_router() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("rate_limit:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(priority_config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    assert_eq!(
+        call(&mut harness).await,
+        (StatusCode::OK, "anthropic".to_string())
+    );
+
+    harness.mock.reset().await;
+    harness
+        .mock
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .await;
+    // routers may call the provider with different credentials, so other
+    // routers keep sending it requests
+    assert_eq!(
+        call_router(&mut harness, "other-router").await,
+        (StatusCode::OK, "openai".to_string())
+    );
+    harness.mock.verify().await;
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashMap, num::NonZeroUsize, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::BalanceConfig,
+        concurrency_limit::{ConcurrencyLimitConfig, PriorityWeights},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+fn chat_request(router: &str) -> Request<axum_core::body::Body> {
+    prioritized_chat_request(router, None)
+}
+
+fn prioritized_chat_request(
+    router: &str,
+    priority: Option<&str>,
+) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router}/chat/completions"
+        ))
+        .header("content-type", "application/json");
+    if let Some(priority) = priority {
+        request = request.header("helicone-priority", priority);
+    }
+    request.body(request_body).unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn saturated_router_sheds_requests_without_affecting_others() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing concurrency limits
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([
+        (
+            RouterId::Named(CompactString::new("limited-router")),
+            RouterConfig {
+                load_balance: BalanceConfig::openai_chat(),
+                max_concurrent_requests: Some(ConcurrencyLimitConfig {
+                    limit: NonZeroUsize::MIN,
+                    queue_depth: 0,
+                    queue_timeout: Duration::from_secs(5),
+                    priority_weights: PriorityWeights::default(),
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            RouterId::Named(CompactString::new("other-router")),
+            RouterConfig {
+                load_balance: BalanceConfig::openai_chat(),
+                ..Default::default()
+            },
+        ),
+    ]));
+
+    let mock_args = MockArgs::builder()
+        .global_openai_latency(500)
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // takes the limited router's only slot
+    let first = tokio::spawn(harness.call(chat_request("limited-router")));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // there's no room to queue, so this is shed
+    let response = harness.call(chat_request("limited-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.headers().get("retry-after").is_some());
+    let _body = response.into_body().collect().await.unwrap();
+
+    // while another router is unaffected
+    let response = harness.call(chat_request("other-router")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+
+    let response = first.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn requests_beyond_limit_are_queued() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing concurrency limits
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            max_concurrent_requests: Some(
+                ConcurrencyLimitConfig::test_default(),
+            ),
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .global_openai_latency(300)
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let first = tokio::spawn(harness.call(chat_request("my-router")));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // waits in the queue for the first request
+    let second = tokio::spawn(harness.call(chat_request("my-router")));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!second.is_finished(), "second request should be queued");
+
+    for handle in [first, second] {
+        let response = handle.await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn high_priority_requests_wait_less_than_low_priority_ones() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing concurrency limits
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            max_concurrent_requests: Some(ConcurrencyLimitConfig {
+                limit: NonZeroUsize::MIN,
+                queue_depth: 10,
+                queue_timeout: Duration::from_secs(10),
+                priority_weights: PriorityWeights::default(),
+            }),
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .global_openai_latency(100)
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 7.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // saturates the router
+    let first = tokio::spawn(harness.call(chat_request("my-router")));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // low priority requests are queued first, yet admitted after the high
+    // priority ones
+    let mut queued = Vec::new();
+    for priority in ["low", "low", "low", "high", "high", "high"] {
+        let start = tokio::time::Instant::now();
+        let request =
+            harness.call(prioritized_chat_request("my-router", Some(priority)));
+        queued.push((
+            priority,
+            tokio::spawn(async move {
+                let response = request.await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let _body = response.into_body().collect().await.unwrap();
+                start.elapsed()
+            }),
+        ));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let response = first.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+
+    let mut waits = HashMap::<&str, Vec<Duration>>::new();
+    for (priority, handle) in queued {
+        waits
+            .entry(priority)
+            .or_default()
+            .push(handle.await.unwrap());
+    }
+    let mean = |waits: &[Duration]| {
+        waits.iter().sum::<Duration>() / u32::try_from(waits.len()).unwrap()
+    };
+    assert!(
+        mean(&waits["high"]) < mean(&waits["low"]),
+        "high priority requests should wait less: {waits:?}"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unknown_priorities_are_rejected() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing concurrency limits
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            max_concurrent_requests: Some(
+                ConcurrencyLimitConfig::test_default(),
+            ),
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(prioritized_chat_request("my-router", Some("urgent")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+        routing_rules::RoutingRulesConfig,
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn chat_request(prompt: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+async fn received_models(
+    server: &stubr::wiremock_rs::MockServer,
+    path: &str,
+) -> Vec<serde_json::Value> {
+    let received_requests = server.received_requests().await.unwrap();
+    received_requests
+        .iter()
+        .filter(|request| request.url.path() == path)
+        .map(|request| {
+            let body: serde_json::Value =
+                serde_json::from_slice(&request.body).unwrap();
+            body["model"].clone()
+        })
+        .collect()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn short_prompts_route_to_a_cheaper_model() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let weighted = |provider| WeightedProvider {
+        provider,
+        weight: Decimal::new(5, 1),
+        schedule: None,
+    };
+    let routing_rules = serde_json::from_value::<RoutingRulesConfig>(json!({
+        "rules": [
+            {
+                "when": { "max-input-tokens": 50 },
+                "model": "openai/gpt-4o-mini",
+                "provider": "openai"
+            }
+        ],
+        "default": {
+            "model": "anthropic/claude-sonnet-4-0",
+            "provider": "anthropic"
+        }
+    }))
+    .unwrap();
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::from(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::ProviderWeighted {
+                    providers: nes![
+                        weighted(InferenceProvider::OpenAI),
+                        weighted(InferenceProvider::Anthropic)
+                    ],
+                    sticky: false,
+                    seed: None,
+                },
+            )])),
+            routing_rules: Some(routing_rules),
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let long_prompt =
+        "Summarize the history of the printing press. ".repeat(20);
+    for prompt in ["Hello, world!", long_prompt.as_str()] {
+        let response = harness.call(chat_request(prompt)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+
+    let openai_models = received_models(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(openai_models, vec![json!("gpt-4o-mini")]);
+    let anthropic_models = received_models(
+        &harness.mock.anthropic_mock.http_server,
+        "/v1/messages",
+    )
+    .await;
+    assert_eq!(anthropic_models.len(), 1);
+    assert!(
+        anthropic_models[0]
+            .as_str()
+            .is_some_and(|model| model.starts_with("claude-sonnet-4")),
+        "the long prompt should be sent to the capable model, got {:?}",
+        anthropic_models[0]
+    );
+}
@@ -12,6 +12,7 @@ use ai_gateway::{
 };
 use compact_str::CompactString;
 use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
 use serde_json::json;
 use tower::Service;
 
@@ -59,7 +60,7 @@ async fn openai() {
 }
 
 /// Sending a request to https://localhost/router should
-// result in the proxied request targeting https://generativelanguage.googleapis.com/v1beta/openai/chat/completions
+// result in the proxied request targeting https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent
 #[tokio::test]
 #[serial_test::serial(default_mock)]
 async fn google_with_openai_request_style() {
@@ -198,6 +199,87 @@ async fn anthropic_with_openai_request_style() {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn anthropic_stream_with_openai_request_style() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing basic provider
+    // functionality
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::anthropic_chat(),
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages_stream", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "anthropic/claude-3-7-sonnet",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let events = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect::<Vec<_>>();
+    assert_eq!(events.last(), Some(&"[DONE]"));
+    let chunks = events[..events.len() - 1]
+        .iter()
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| chunk["id"] == "msg_013Zva2CMHLNnXjNJJKqJ2EF")
+    );
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    let content = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect::<String>();
+    assert_eq!(content, "Hi! My name is Claude.");
+    let (usage, chunks) = chunks.split_last().unwrap();
+    assert_eq!(usage["choices"], json!([]));
+    assert_eq!(usage["usage"]["prompt_tokens"], 25);
+    assert_eq!(usage["usage"]["completion_tokens"], 12);
+    assert!(chunks.iter().all(|chunk| chunk["usage"].is_null()));
+    assert_eq!(
+        chunks.last().unwrap()["choices"][0]["finish_reason"],
+        "stop"
+    );
+}
+
 /// Sending a request to https://localhost/router should
 /// result in the proxied request targeting Ollama chat completions endpoint
 #[tokio::test]
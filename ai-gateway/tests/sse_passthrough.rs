@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::oneshot,
+};
+use tower::Service;
+use url::Url;
+
+const CHUNK_DELAY: Duration = Duration::from_millis(500);
+
+fn chunk(content: &str, finish_reason: Option<&str>) -> String {
+    let chunk = json!({
+        "id": "chatcmpl-B9MBs8CjcvOU2jLn4n570S5qMJKcT",
+        "object": "chat.completion.chunk",
+        "created": 1_741_569_952,
+        "model": "llama-3.1-8b-instant",
+        "choices": [
+            {
+                "index": 0,
+                "delta": {
+                    "role": "assistant",
+                    "content": content
+                },
+                "logprobs": null,
+                "finish_reason": finish_reason
+            }
+        ]
+    });
+    format!("data: {chunk}\n\n")
+}
+
+/// The mock servers only respond with complete bodies, so this serves a
+/// single SSE response by hand, sending the last event `CHUNK_DELAY` after
+/// the first.
+///
+/// Sends the time the last event was written, or `None` if the connection
+/// was closed before then.
+async fn slow_sse_server() -> (Url, oneshot::Receiver<Option<Instant>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url =
+        Url::parse(&format!("http://{}/", listener.local_addr().unwrap()))
+            .unwrap();
+    let (last_sent_tx, last_sent_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        // the request is small enough to arrive in a single read
+        let mut request = [0_u8; 8192];
+        let _ = socket.read(&mut request).await.unwrap();
+        let headers = "HTTP/1.1 200 OK\r\ncontent-type: \
+                       text/event-stream\r\nconnection: close\r\n\r\n";
+        socket.write_all(headers.as_bytes()).await.unwrap();
+        socket
+            .write_all(chunk("Hello", None).as_bytes())
+            .await
+            .unwrap();
+        // a disconnected client closes the connection while we wait
+        let mut buf = [0_u8; 1];
+        let closed = tokio::time::timeout(CHUNK_DELAY, socket.read(&mut buf))
+            .await
+            .is_ok_and(|read| matches!(read, Ok(0) | Err(_)));
+        if closed {
+            let _ = last_sent_tx.send(None);
+            return;
+        }
+        let last = format!("{}data: [DONE]\n\n", chunk("!", Some("stop")));
+        socket.write_all(last.as_bytes()).await.unwrap();
+        let _ = last_sent_tx.send(Some(Instant::now()));
+    });
+    (url, last_sent_rx)
+}
+
+/// Hyperbolic has no mock server overriding its base URL.
+fn hyperbolic_config(base_url: Url) -> Config {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing streaming
+    config.helicone.features = HeliconeFeatures::None;
+    let hyperbolic = InferenceProvider::Named("hyperbolic".into());
+    config.providers.get_mut(&hyperbolic).unwrap().base_url = base_url;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig(HashMap::from([(
+                EndpointType::Chat,
+                BalanceConfigInner::BalancedLatency {
+                    providers: nes![hyperbolic],
+                },
+            )])),
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn stream_request() -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "hyperbolic/meta-llama/Llama-3.3-70B-Instruct",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": true
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn first_chunk_is_received_before_last_is_sent() {
+    let (base_url, last_sent_rx) = slow_sse_server().await;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(hyperbolic_config(base_url))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(stream_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut body = response.into_body();
+    let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+    let first_received_at = Instant::now();
+    assert!(String::from_utf8_lossy(&first).contains("Hello"));
+
+    let rest = body.collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&rest).contains("stop"));
+    let last_sent_at = last_sent_rx.await.unwrap().unwrap();
+    assert!(first_received_at < last_sent_at);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn client_disconnect_cancels_upstream_stream() {
+    let (base_url, last_sent_rx) = slow_sse_server().await;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(hyperbolic_config(base_url))
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(stream_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut body = response.into_body();
+    let _first = body.frame().await.unwrap().unwrap();
+    // disconnect before the provider sends the last event
+    drop(body);
+
+    let last_sent_at = last_sent_rx.await.unwrap();
+    assert!(last_sent_at.is_none());
+}
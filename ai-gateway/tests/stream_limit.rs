@@ -0,0 +1,98 @@
+use std::{collections::HashMap, num::NonZeroUsize, time::Duration};
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::BalanceConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+        stream_limit::StreamLimitConfig,
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+fn stream_request() -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ],
+            "stream": true
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn streams_beyond_limit_are_queued() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing stream limits
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            max_concurrent_streams: Some(StreamLimitConfig {
+                limit: NonZeroUsize::MIN,
+                queue_depth: 1,
+                queue_timeout: Duration::from_secs(5),
+            }),
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .global_openai_latency(300)
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion_stream", 2.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    // takes the only slot until its stream completes
+    let first = tokio::spawn(harness.call(stream_request()));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // waits in the queue for the first stream
+    let second = tokio::spawn(harness.call(stream_request()));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!second.is_finished(), "second stream should be queued");
+
+    // the queue is full, so this is rejected
+    let response = harness.call(stream_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("retry-after").is_some());
+
+    let response = first.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!second.is_finished(), "slot is held until the stream ends");
+    let _body = response.into_body().collect().await.unwrap();
+
+    // completing the first stream admits the queued one
+    let response = second.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+}
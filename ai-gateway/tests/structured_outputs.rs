@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::{Value, json};
+use tower::Service;
+
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let router = |provider| RouterConfig {
+        load_balance: BalanceConfig::from(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ProviderWeighted {
+                providers: nes![WeightedProvider {
+                    provider,
+                    weight: Decimal::try_from(1.0).unwrap(),
+                    schedule: None,
+                }],
+                sticky: false,
+                seed: None,
+            },
+        )])),
+        ..Default::default()
+    };
+    config.routers = RouterConfigs::new(HashMap::from([
+        (
+            RouterId::Named(CompactString::new("anthropic")),
+            router(InferenceProvider::Anthropic),
+        ),
+        (
+            RouterId::Named(CompactString::new("openai")),
+            router(InferenceProvider::OpenAI),
+        ),
+    ]));
+    config
+}
+
+fn response_format() -> Value {
+    json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "weather",
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "city": { "type": "string" },
+                    "temperature": { "type": "number" }
+                },
+                "required": ["city", "temperature"]
+            }
+        }
+    })
+}
+
+fn structured_output_request(
+    router: &str,
+    stream: bool,
+) -> Request<axum_core::body::Body> {
+    let body = json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            { "role": "user", "content": "What's the weather in Paris?" }
+        ],
+        "response_format": response_format(),
+        "stream": stream
+    });
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router}/chat/completions"
+        ))
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(&body).unwrap(),
+        ))
+        .unwrap()
+}
+
+async fn provider_request(
+    server: &stubr::wiremock_rs::MockServer,
+    path: &str,
+) -> Value {
+    let received_requests = server.received_requests().await.unwrap();
+    let request = received_requests
+        .into_iter()
+        .find(|request| request.url.path() == path)
+        .expect("the provider should receive the request");
+    serde_json::from_slice(&request.body).unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn anthropic_structured_outputs_round_trip() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages_structured_output", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(structured_output_request("anthropic", false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let choice = &body["choices"][0];
+    assert_eq!(choice["finish_reason"], "stop");
+    assert!(choice["message"]["tool_calls"].is_null());
+    let content: Value =
+        serde_json::from_str(choice["message"]["content"].as_str().unwrap())
+            .unwrap();
+    assert_eq!(content, json!({ "city": "Paris", "temperature": 21 }));
+
+    let provider_request = provider_request(
+        &harness.mock.anthropic_mock.http_server,
+        "/v1/messages",
+    )
+    .await;
+    assert_eq!(
+        provider_request["tools"][0]["input_schema"],
+        response_format()["json_schema"]["schema"]
+    );
+    assert_eq!(
+        provider_request["tool_choice"],
+        json!({ "type": "tool", "name": "structured_output" })
+    );
+    assert!(provider_request.get("response_format").is_none());
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn streamed_anthropic_structured_outputs_are_rejected() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(structured_output_request("anthropic", true))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Structured outputs can't be streamed")
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn openai_structured_outputs_are_passed_through() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness
+        .call(structured_output_request("openai", false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let provider_request = provider_request(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(provider_request["response_format"]["type"], "json_schema");
+    assert_eq!(
+        provider_request["response_format"]["json_schema"]["schema"],
+        response_format()["json_schema"]["schema"]
+    );
+    assert!(provider_request.get("tools").is_none());
+}
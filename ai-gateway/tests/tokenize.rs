@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{Config, helicone::HeliconeFeatures},
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+};
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+fn tokenize_request(
+    body: &serde_json::Value,
+) -> Request<axum_core::body::Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/ai/tokenize")
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(body).unwrap(),
+        ))
+        .unwrap()
+}
+
+async fn harness(count_tokens_requests: u64) -> Harness {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 0.into()),
+            (
+                "success:anthropic:count_tokens",
+                count_tokens_requests.into(),
+            ),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await
+}
+
+async fn call(
+    harness: &mut Harness,
+    body: &serde_json::Value,
+) -> (StatusCode, serde_json::Value) {
+    let response = harness.call(tokenize_request(body)).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn openai_tokens_are_counted_locally() {
+    let mut harness = harness(0).await;
+
+    let (status, body) = call(
+        &mut harness,
+        &json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                { "role": "system", "content": "You are a helpful assistant." },
+                { "role": "user", "content": "Hello, world!" }
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["model"], "openai/gpt-4o-mini");
+    assert_eq!(body["input_tokens"], 21);
+
+    let (status, body) = call(
+        &mut harness,
+        &json!({ "model": "openai/gpt-4o-mini", "text": "Hello, world!" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["input_tokens"], 4);
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn anthropic_tokens_are_counted_by_the_provider() {
+    let mut harness = harness(1).await;
+
+    let (status, body) = call(
+        &mut harness,
+        &json!({
+            "model": "anthropic/claude-3-5-haiku",
+            "messages": [
+                { "role": "system", "content": "You are a helpful assistant." },
+                { "role": "user", "content": "Hello, world!" }
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["input_tokens"], 14);
+
+    let received_requests = harness
+        .mock
+        .anthropic_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/messages/count_tokens")
+        .expect("anthropic should receive the request");
+    assert_eq!(
+        request.headers.get("x-api-key").unwrap(),
+        "test-anthropic-key"
+    );
+    let request_body: serde_json::Value =
+        serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(request_body["system"], "You are a helpful assistant.");
+    assert_eq!(
+        request_body["messages"],
+        json!([{ "role": "user", "content": "Hello, world!" }])
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unknown_models_are_unprocessable() {
+    let mut harness = harness(0).await;
+
+    let (status, body) = call(
+        &mut harness,
+        &json!({ "model": "openai/text-embedding-3-small", "text": "Hi" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.contains("openai/gpt-4o"));
+    assert!(message.contains("anthropic/claude"));
+}
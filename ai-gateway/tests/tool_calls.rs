@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn anthropic_router_config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![WeightedProvider {
+                provider: InferenceProvider::Anthropic,
+                weight: Decimal::try_from(1.0).unwrap(),
+                schedule: None,
+            }],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn tool_calling_request() -> Request<axum_core::body::Body> {
+    let body = json!({
+        "model": "openai/gpt-4o-mini",
+        "tool_choice": "required",
+        "tools": [{
+            "type": "function",
+            "function": {
+                "name": "get_weather",
+                "description": "Get the weather in a city",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } },
+                    "required": ["city"]
+                }
+            }
+        }],
+        "messages": [
+            { "role": "user", "content": "What's the weather in Lisbon?" },
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_lisbon",
+                    "type": "function",
+                    "function": {
+                        "name": "get_weather",
+                        "arguments": "{\"city\":\"Lisbon\"}"
+                    }
+                }]
+            },
+            {
+                "role": "tool",
+                "tool_call_id": "call_lisbon",
+                "content": "sunny"
+            },
+            { "role": "user", "content": "And in Paris and Oslo?" }
+        ]
+    });
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(&body).unwrap(),
+        ))
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn anthropic_tool_use_is_returned_as_openai_tool_calls() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages_tool_use", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(anthropic_router_config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(tool_calling_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let choice = &body["choices"][0];
+    assert_eq!(choice["finish_reason"], "tool_calls");
+    assert_eq!(
+        choice["message"]["content"],
+        "Let me check the weather in both cities."
+    );
+    let tool_calls = choice["message"]["tool_calls"].as_array().unwrap();
+    assert_eq!(tool_calls.len(), 2);
+    for (tool_call, city) in tool_calls.iter().zip(["Paris", "Oslo"]) {
+        assert!(tool_call["id"].as_str().unwrap().starts_with("toolu_"));
+        assert_eq!(tool_call["type"], "function");
+        assert_eq!(tool_call["function"]["name"], "get_weather");
+        let arguments: serde_json::Value = serde_json::from_str(
+            tool_call["function"]["arguments"].as_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(arguments, json!({ "city": city }));
+    }
+
+    let received_requests = harness
+        .mock
+        .anthropic_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/messages")
+        .expect("anthropic should receive the request");
+    let provider_request: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(provider_request["tool_choice"], json!({ "type": "any" }));
+    assert_eq!(provider_request["tools"][0]["name"], "get_weather");
+    let messages = provider_request["messages"].as_array().unwrap();
+    assert_eq!(messages[1]["content"][0]["type"], "tool_use");
+    assert_eq!(
+        messages[1]["content"][0]["input"],
+        json!({ "city": "Lisbon" })
+    );
+    assert_eq!(
+        messages[2]["content"],
+        json!([{
+            "type": "tool_result",
+            "tool_use_id": "call_lisbon",
+            "content": "sunny"
+        }])
+    );
+}
+
+fn unified_tool_calling_request(
+    stream: bool,
+) -> Request<axum_core::body::Body> {
+    let body = json!({
+        "model": "anthropic/claude-3-7-sonnet",
+        "stream": stream,
+        "tools": [{
+            "type": "function",
+            "function": {
+                "name": "get_weather",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } },
+                    "required": ["city"]
+                }
+            }
+        }],
+        "messages": [
+            {
+                "role": "user",
+                "content": "What's the weather in Paris and Oslo?"
+            }
+        ]
+    });
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/ai/chat/completions")
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(&body).unwrap(),
+        ))
+        .unwrap()
+}
+
+async fn unified_api_harness(stub: &'static str) -> Harness {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            (stub, 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_returns_anthropic_tool_use_as_tool_calls() {
+    let mut harness =
+        unified_api_harness("success:anthropic:messages_tool_use").await;
+
+    let response = harness
+        .call(unified_tool_calling_request(false))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(
+        body["choices"][0]["message"]["tool_calls"],
+        json!([
+            {
+                "id": "toolu_01A09q90qw90lq917835lq9",
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "arguments": "{\"city\":\"Paris\"}"
+                }
+            },
+            {
+                "id": "toolu_01B19r01rx01mr028946mr0",
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "arguments": "{\"city\":\"Oslo\"}"
+                }
+            }
+        ])
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn unified_api_streams_anthropic_tool_use_as_tool_call_deltas() {
+    let mut harness =
+        unified_api_harness("success:anthropic:messages_tool_use_stream").await;
+
+    let response = harness
+        .call(unified_tool_calling_request(true))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let chunks = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str::<serde_json::Value>(data).unwrap())
+        .collect::<Vec<_>>();
+
+    // accumulate the deltas the way `OpenAI` clients do, by tool call index
+    let mut tool_calls: Vec<(String, String, String)> = Vec::new();
+    for delta in chunks
+        .iter()
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["tool_calls"].as_array()
+        })
+        .flatten()
+    {
+        let index = usize::try_from(delta["index"].as_u64().unwrap()).unwrap();
+        if index == tool_calls.len() {
+            tool_calls.push(Default::default());
+        }
+        let (id, name, arguments) = &mut tool_calls[index];
+        if let Some(delta_id) = delta["id"].as_str() {
+            *id = delta_id.to_string();
+        }
+        if let Some(delta_name) = delta["function"]["name"].as_str() {
+            *name = delta_name.to_string();
+        }
+        if let Some(delta_arguments) = delta["function"]["arguments"].as_str() {
+            arguments.push_str(delta_arguments);
+        }
+    }
+    assert_eq!(tool_calls.len(), 2);
+    for ((id, name, arguments), (expected_id, city)) in tool_calls
+        .iter()
+        .zip([("toolu_01", "Paris"), ("toolu_02", "Oslo")])
+    {
+        assert_eq!(id, expected_id);
+        assert_eq!(name, "get_weather");
+        let arguments: serde_json::Value =
+            serde_json::from_str(arguments).unwrap();
+        assert_eq!(arguments, json!({ "city": city }));
+    }
+    assert!(
+        chunks
+            .iter()
+            .any(|chunk| chunk["choices"][0]["finish_reason"] == "tool_calls")
+    );
+}
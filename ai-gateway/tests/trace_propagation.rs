@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{Config, helicone::HeliconeFeatures},
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+};
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use opentelemetry::{global, trace::TracerProvider};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator, trace::SdkTracerProvider,
+};
+use serde_json::json;
+use tower::Service;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+/// An incoming `traceparent` should be continued on the request to the
+/// provider, with the gateway's own span as the parent.
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn traceparent_is_propagated_to_provider() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer_provider = SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer_provider.tracer("trace-propagation-test")),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("traceparent", format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-01"))
+        .body(request_body)
+        .unwrap();
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .first()
+        .expect("openai should receive the request");
+    let traceparent = provider_request
+        .headers
+        .get("traceparent")
+        .expect("traceparent should be forwarded")
+        .to_str()
+        .unwrap();
+    let parts = traceparent.split('-').collect::<Vec<_>>();
+    assert_eq!(parts.len(), 4, "malformed traceparent: {traceparent}");
+    assert_eq!(parts[1], TRACE_ID);
+    assert_ne!(parts[2], PARENT_SPAN_ID);
+}
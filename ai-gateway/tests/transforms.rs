@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::BalanceConfig,
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+        transform::TransformRule,
+    },
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::router::RouterId,
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::Service;
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn transforms_are_applied_before_forwarding() {
+    let mut config = Config::test_default();
+    // Disable auth for this test since we're testing request transforms
+    config.helicone.features = HeliconeFeatures::None;
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: BalanceConfig::openai_chat(),
+            transforms: vec![
+                TransformRule::PrependSystemMessage {
+                    content: "Always answer in French.".to_string(),
+                },
+                TransformRule::RemoveHeader {
+                    name: "x-client-secret".to_string(),
+                },
+                TransformRule::DefaultParam {
+                    param: "temperature".to_string(),
+                    value: json!(0.2),
+                },
+                TransformRule::DefaultParam {
+                    param: "max_tokens".to_string(),
+                    value: json!(100),
+                },
+            ],
+            ..Default::default()
+        },
+    )]));
+
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "max_tokens": 10,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .header("x-client-secret", "do-not-forward")
+        .body(request_body)
+        .unwrap();
+
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .openai_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/chat/completions")
+        .expect("provider should receive the request");
+    assert!(!provider_request.headers.contains_key("x-client-secret"));
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(
+        body["messages"][0],
+        json!({
+            "role": "system",
+            "content": "Always answer in French."
+        })
+    );
+    assert_eq!(body["messages"][1]["role"], "user");
+    assert_eq!(body["temperature"], json!(0.2));
+    // parameters sent by the client are not overridden
+    assert_eq!(body["max_tokens"], json!(10));
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use indexmap::IndexMap;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+fn weighted_router(provider: InferenceProvider) -> RouterConfig {
+    RouterConfig {
+        load_balance: BalanceConfig::from(HashMap::from([(
+            EndpointType::Chat,
+            BalanceConfigInner::ProviderWeighted {
+                providers: nes![WeightedProvider {
+                    provider,
+                    weight: Decimal::try_from(1.0).unwrap(),
+                    schedule: None,
+                }],
+                sticky: false,
+                seed: None,
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+fn chat_request(router: &str) -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "http://router.helicone.com/router/{router}/chat/completions"
+        ))
+        .header("content-type", "application/json")
+        .header("user-agent", "client/1.0")
+        .body(request_body)
+        .unwrap()
+}
+
+async fn received_request(
+    server: &stubr::wiremock_rs::MockServer,
+    path: &str,
+) -> stubr::wiremock_rs::Request {
+    let received_requests = server.received_requests().await.unwrap();
+    received_requests
+        .into_iter()
+        .find(|request| request.url.path() == path)
+        .expect("the provider should receive the request")
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn default_upstream_headers_are_sent_to_every_provider() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config.dispatcher.default_upstream_headers = IndexMap::from([
+        ("x-company-tenant".to_string(), "acme".to_string()),
+        ("user-agent".to_string(), "acme-gateway/1.0".to_string()),
+    ]);
+    config
+        .providers
+        .get_mut(&InferenceProvider::Anthropic)
+        .unwrap()
+        .upstream_headers = IndexMap::from([(
+        "user-agent".to_string(),
+        "acme-anthropic/1.0".to_string(),
+    )]);
+    config.routers = RouterConfigs::new(HashMap::from([
+        (
+            RouterId::Named(CompactString::new("openai-router")),
+            weighted_router(InferenceProvider::OpenAI),
+        ),
+        (
+            RouterId::Named(CompactString::new("anthropic-router")),
+            weighted_router(InferenceProvider::Anthropic),
+        ),
+    ]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", 1.into()),
+            ("success:anthropic:messages", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for router in ["openai-router", "anthropic-router"] {
+        let response = harness.call(chat_request(router)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+
+    let openai_request = received_request(
+        &harness.mock.openai_mock.http_server,
+        "/v1/chat/completions",
+    )
+    .await;
+    assert_eq!(
+        openai_request.headers.get("x-company-tenant").unwrap(),
+        "acme"
+    );
+    assert_eq!(
+        openai_request.headers.get("user-agent").unwrap(),
+        "acme-gateway/1.0"
+    );
+    assert!(openai_request.headers.get("authorization").is_some());
+
+    let anthropic_request = received_request(
+        &harness.mock.anthropic_mock.http_server,
+        "/v1/messages",
+    )
+    .await;
+    assert_eq!(
+        anthropic_request.headers.get("x-company-tenant").unwrap(),
+        "acme"
+    );
+    assert_eq!(
+        anthropic_request.headers.get("user-agent").unwrap(),
+        "acme-anthropic/1.0"
+    );
+    assert!(anthropic_request.headers.get("x-api-key").is_some());
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+/// Routes chat requests to Vertex AI.
+fn config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    config
+        .providers
+        .get_mut(&InferenceProvider::VertexAI)
+        .unwrap()
+        .project = Some("test-project".to_string());
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![WeightedProvider {
+                provider: InferenceProvider::VertexAI,
+                weight: Decimal::try_from(1.0).unwrap(),
+                schedule: None,
+            }],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn chat_request() -> Request<axum_core::body::Body> {
+    let request_body = axum_core::body::Body::from(
+        serde_json::to_vec(&json!({
+            "model": "gemini/gemini-2.0-flash",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "Hello, world!"
+                }
+            ]
+        }))
+        .unwrap(),
+    );
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(request_body)
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn vertex_request_uses_project_path_and_access_token() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:vertex:token", 1.into()),
+            ("success:vertex:chat_completion", 1.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let response = harness.call(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("helicone-provider").unwrap(),
+        "vertex"
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["choices"][0]["message"]["content"],
+        "Hello! How can I help you today?"
+    );
+
+    let received_requests = harness
+        .mock
+        .vertex_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let token_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/token")
+        .expect("an access token should be requested");
+    let token_body = String::from_utf8_lossy(&token_request.body);
+    assert!(token_body.contains(
+        "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer"
+    ));
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() != "/token")
+        .expect("vertex should receive the request");
+    assert_eq!(
+        provider_request.url.path(),
+        "/v1/projects/test-project/locations/us-central1/endpoints/openapi/\
+         chat/completions"
+    );
+    assert_eq!(
+        provider_request.headers.get("authorization").unwrap(),
+        "Bearer test-vertex-access-token"
+    );
+    let provider_body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(provider_body["model"], "google/gemini-2.0-flash");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn vertex_access_token_is_cached() {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:vertex:token", 1.into()),
+            ("success:vertex:chat_completion", 3.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for _ in 0..3 {
+        let response = harness.call(chat_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let _response_body = response.into_body().collect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn vertex_token_refresh_failure_is_retried_then_backs_off() {
+    // the first request's refresh is attempted three times, and the second
+    // request fails fast while the refresh is backing off
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("error:vertex:token", 3.into()),
+            ("success:vertex:chat_completion", 0.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config())
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    for _ in 0..2 {
+        let response = harness.call(chat_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "provider_token_refresh_failed");
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use ai_gateway::{
+    config::{
+        Config,
+        balance::{BalanceConfig, BalanceConfigInner, WeightedProvider},
+        helicone::HeliconeFeatures,
+        router::{RouterConfig, RouterConfigs},
+    },
+    endpoints::EndpointType,
+    tests::{TestDefault, harness::Harness, mock::MockArgs},
+    types::{provider::InferenceProvider, router::RouterId},
+};
+use compact_str::CompactString;
+use http::{Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use nonempty_collections::nes;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tower::Service;
+
+/// The 8 byte PNG signature.
+const PNG: &str = "iVBORw0KGgo=";
+
+fn anthropic_router_config() -> Config {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![WeightedProvider {
+                provider: InferenceProvider::Anthropic,
+                weight: Decimal::try_from(1.0).unwrap(),
+                schedule: None,
+            }],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    config
+}
+
+fn image_request(url: &str) -> Request<axum_core::body::Body> {
+    let body = json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "What is in this image?" },
+                { "type": "image_url", "image_url": { "url": url } }
+            ]
+        }]
+    });
+    Request::builder()
+        .method(Method::POST)
+        .uri("http://router.helicone.com/router/my-router/chat/completions")
+        .header("content-type", "application/json")
+        .body(axum_core::body::Body::from(
+            serde_json::to_vec(&body).unwrap(),
+        ))
+        .unwrap()
+}
+
+async fn harness(anthropic_requests: u64) -> Harness {
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:anthropic:messages", anthropic_requests.into()),
+            ("success:minio:upload_request", 0.into()),
+            ("success:jawn:log_request", 0.into()),
+        ]))
+        .build();
+    Harness::builder()
+        .with_config(anthropic_router_config())
+        .with_mock_args(mock_args)
+        .build()
+        .await
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn base64_images_are_sent_to_anthropic_as_image_blocks() {
+    let mut harness = harness(1).await;
+
+    let request = image_request(&format!("data:image/png;base64,{PNG}"));
+    let response = harness.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let _response_body = response.into_body().collect().await.unwrap();
+
+    let received_requests = harness
+        .mock
+        .anthropic_mock
+        .http_server
+        .received_requests()
+        .await
+        .unwrap();
+    let provider_request = received_requests
+        .iter()
+        .find(|request| request.url.path() == "/v1/messages")
+        .expect("anthropic should receive the request");
+    let body: serde_json::Value =
+        serde_json::from_slice(&provider_request.body).unwrap();
+    assert_eq!(
+        body["messages"][0]["content"][1],
+        json!({
+            "type": "image",
+            "source": {
+                "type": "base64",
+                "media_type": "image/png",
+                "data": PNG
+            }
+        })
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial(default_mock)]
+async fn images_anthropic_cannot_accept_are_rejected_by_the_gateway() {
+    let mut harness = harness(0).await;
+    // anthropic's limit is 5MB once decoded
+    let oversized = "A".repeat(6 * 1024 * 1024 / 3 * 4);
+    for (url, status) in [
+        (
+            "https://example.com/cat.png".to_string(),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            format!("data:image/png;base64,{oversized}"),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ),
+    ] {
+        let response = harness.call(image_request(&url)).await.unwrap();
+        assert_eq!(response.status(), status);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+}
@@ -1,18 +1,27 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use ai_gateway::{
     config::{
         Config,
         balance::{
-            BalanceConfig, BalanceConfigInner, WeightedModel, WeightedProvider,
+            BalanceConfig, BalanceConfigInner, ExclusionWindow,
+            ProviderSchedule, WeightedModel, WeightedProvider,
         },
         helicone::HeliconeFeatures,
         router::{RouterConfig, RouterConfigs},
     },
+    control_plane::types::{Key, hash_key},
     endpoints::EndpointType,
     tests::{TestDefault, harness::Harness, mock::MockArgs},
-    types::{model_id::ModelId, provider::InferenceProvider, router::RouterId},
+    types::{
+        model_id::ModelId, org::OrgId, provider::InferenceProvider,
+        router::RouterId,
+    },
 };
+use chrono::{NaiveTime, TimeZone, Utc};
 use compact_str::CompactString;
 use http::{Method, Request, StatusCode};
 use http_body_util::BodyExt;
@@ -20,6 +29,7 @@ use nonempty_collections::nes;
 use rust_decimal::Decimal;
 use serde_json::json;
 use tower::Service;
+use uuid::Uuid;
 
 #[tokio::test]
 #[serial_test::serial]
@@ -34,12 +44,16 @@ async fn weighted_balancer_anthropic_preferred() {
                 WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::try_from(0.75).unwrap(),
+                    schedule: None,
                 },
             ],
+            sticky: false,
+            seed: None,
         },
     )]));
     config.routers = RouterConfigs::new(HashMap::from([(
@@ -120,12 +134,16 @@ async fn weighted_balancer_openai_preferred() {
                 WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::try_from(0.75).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
             ],
+            sticky: false,
+            seed: None,
         },
     )]));
     config.routers = RouterConfigs::new(HashMap::from([(
@@ -206,12 +224,16 @@ async fn weighted_balancer_anthropic_heavily_preferred() {
                 WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::try_from(0.05).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::try_from(0.95).unwrap(),
+                    schedule: None,
                 },
             ],
+            sticky: false,
+            seed: None,
         },
     )]));
     config.routers = RouterConfigs::new(HashMap::from([(
@@ -298,20 +320,26 @@ async fn weighted_balancer_equal_four_providers() {
                 WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::GoogleGemini,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Ollama,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
             ],
+            sticky: false,
+            seed: None,
         },
     )]));
     config.routers = RouterConfigs::new(HashMap::from([(
@@ -395,20 +423,26 @@ async fn weighted_balancer_bedrock() {
                 WeightedProvider {
                     provider: InferenceProvider::OpenAI,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Anthropic,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Ollama,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
                 WeightedProvider {
                     provider: InferenceProvider::Bedrock,
                     weight: Decimal::try_from(0.25).unwrap(),
+                    schedule: None,
                 },
             ],
+            sticky: false,
+            seed: None,
         },
     )]));
     config.routers = RouterConfigs::new(HashMap::from([(
@@ -568,3 +602,316 @@ async fn model_weighted() {
     // sleep so that the background task for logging can complete
     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
 }
+
+#[tokio::test]
+#[serial_test::serial]
+async fn sticky_weighted_balancer_pins_users_to_providers() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::Auth;
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+            ],
+            sticky: true,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", (1..).into()),
+            ("success:anthropic:messages", (1..).into()),
+        ]))
+        .build();
+    // with an even split, the odds of every user landing on the same
+    // provider are negligible
+    let num_users = 16;
+    let requests_per_user = 3;
+    let api_keys = (0..num_users)
+        .map(|i| format!("sk-helicone-sticky-user-{i}"))
+        .collect::<Vec<_>>();
+    let keys = api_keys
+        .iter()
+        .map(|api_key| Key {
+            key_hash: hash_key(api_key),
+            owner_id: Uuid::new_v4().to_string(),
+            organization_id: OrgId::new(Uuid::new_v4()),
+        })
+        .collect();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .with_auth_keys(keys)
+        .build()
+        .await;
+
+    let body_bytes = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+
+    let url = "http://router.helicone.com/router/my-router/chat/completions";
+    let mut provider_by_user = HashMap::new();
+    for api_key in &api_keys {
+        for _ in 0..requests_per_user {
+            let request_body = axum_core::body::Body::from(body_bytes.clone());
+            let request = Request::builder()
+                .method(Method::POST)
+                .header("authorization", format!("Bearer {api_key}"))
+                .uri(url)
+                .body(request_body)
+                .unwrap();
+            let response = harness.call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let provider = response
+                .headers()
+                .get("helicone-provider")
+                .expect("provider header should be present")
+                .to_str()
+                .unwrap()
+                .to_string();
+            let _response_body = response.into_body().collect().await.unwrap();
+
+            let sticky_provider = provider_by_user
+                .entry(api_key.clone())
+                .or_insert_with(|| provider.clone());
+            assert_eq!(
+                *sticky_provider, provider,
+                "requests from one user should land on one provider"
+            );
+        }
+    }
+
+    let providers = provider_by_user.values().collect::<HashSet<_>>();
+    assert_eq!(
+        providers.len(),
+        2,
+        "different users should land on different providers"
+    );
+    harness.mock.verify().await;
+}
+
+/// Sends `num_requests` requests through a provider weighted router seeded
+/// with `seed`, returning the provider each request was routed to.
+async fn seeded_provider_sequence(
+    seed: u64,
+    num_requests: usize,
+) -> Vec<String> {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+            ],
+            sticky: false,
+            seed: Some(seed),
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", (1..).into()),
+            ("success:anthropic:messages", (1..).into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let body_bytes = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+
+    let mut providers = Vec::with_capacity(num_requests);
+    for _ in 0..num_requests {
+        let request_body = axum_core::body::Body::from(body_bytes.clone());
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://router.helicone.com/router/my-router/chat/completions")
+            .body(request_body)
+            .unwrap();
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let provider = response
+            .headers()
+            .get("helicone-provider")
+            .expect("provider header should be present")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let _response_body = response.into_body().collect().await.unwrap();
+        providers.push(provider);
+    }
+    providers
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn seeded_weighted_balancer_is_reproducible() {
+    let num_requests = 30;
+    let first = seeded_provider_sequence(42, num_requests).await;
+    let second = seeded_provider_sequence(42, num_requests).await;
+    assert_eq!(
+        first, second,
+        "the same seed should route the same requests to the same providers"
+    );
+
+    let counts = first.iter().fold(HashMap::new(), |mut counts, provider| {
+        *counts.entry(provider.as_str()).or_insert(0) += 1;
+        counts
+    });
+    assert_eq!(
+        counts.len(),
+        2,
+        "requests should still be distributed across providers"
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn scheduled_providers_get_no_traffic_during_their_windows() {
+    let mut config = Config::test_default();
+    config.helicone.features = HeliconeFeatures::None;
+    let schedule = ProviderSchedule {
+        timezone: chrono_tz::Tz::UTC,
+        exclude: vec![ExclusionWindow {
+            days: vec![],
+            start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        }],
+    };
+    let balance_config = BalanceConfig::from(HashMap::from([(
+        EndpointType::Chat,
+        BalanceConfigInner::ProviderWeighted {
+            providers: nes![
+                WeightedProvider {
+                    provider: InferenceProvider::OpenAI,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: Some(schedule),
+                },
+                WeightedProvider {
+                    provider: InferenceProvider::Anthropic,
+                    weight: Decimal::try_from(0.5).unwrap(),
+                    schedule: None,
+                },
+            ],
+            sticky: false,
+            seed: None,
+        },
+    )]));
+    config.routers = RouterConfigs::new(HashMap::from([(
+        RouterId::Named(CompactString::new("my-router")),
+        RouterConfig {
+            load_balance: balance_config,
+            ..Default::default()
+        },
+    )]));
+    let mock_args = MockArgs::builder()
+        .stubs(HashMap::from([
+            ("success:openai:chat_completion", (1..).into()),
+            ("success:anthropic:messages", (20..).into()),
+        ]))
+        .build();
+    let mut harness = Harness::builder()
+        .with_config(config)
+        .with_mock_args(mock_args)
+        .build()
+        .await;
+
+    let body_bytes = serde_json::to_vec(&json!({
+        "model": "openai/gpt-4o-mini",
+        "messages": [
+            {
+                "role": "user",
+                "content": "Hello, world!"
+            }
+        ]
+    }))
+    .unwrap();
+    let url = "http://router.helicone.com/router/my-router/chat/completions";
+    let clock = harness.app_factory.state.0.clock.clone();
+    let mut send_requests = async |num_requests| {
+        let mut providers = HashSet::new();
+        for _ in 0..num_requests {
+            let request_body = axum_core::body::Body::from(body_bytes.clone());
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(url)
+                .body(request_body)
+                .unwrap();
+            let response = harness.call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let provider = response
+                .headers()
+                .get("helicone-provider")
+                .expect("provider header should be present")
+                .to_str()
+                .unwrap()
+                .to_string();
+            let _response_body = response.into_body().collect().await.unwrap();
+            providers.insert(provider);
+        }
+        providers
+    };
+
+    // inside the window, anthropic absorbs all of openai's traffic
+    clock.set(Utc.with_ymd_and_hms(2025, 6, 2, 12, 0, 0).unwrap());
+    let providers = send_requests(20).await;
+    assert_eq!(providers, HashSet::from(["anthropic".to_string()]));
+
+    // and outside of it, openai is weighted as usual again
+    clock.set(Utc.with_ymd_and_hms(2025, 6, 2, 18, 0, 0).unwrap());
+    let providers = send_requests(20).await;
+    assert!(providers.contains("openai"));
+
+    harness.mock.verify().await;
+}
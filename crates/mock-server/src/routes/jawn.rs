@@ -103,6 +103,7 @@ fn mock_auth() -> ai_gateway::control_plane::types::Config {
             owner_id: user_id,
             organization_id: OrgId::try_from(organization_id.as_str()).unwrap(),
         }],
+        provider_keys: Vec::new(),
         router_id: "my-router".to_string(),
         router_config: "{}".to_string(),
     }
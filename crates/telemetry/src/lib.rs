@@ -37,6 +37,10 @@ pub struct Config {
     pub exporter: Exporter,
     #[serde(default = "default_otlp_endpoint")]
     pub otlp_endpoint: String,
+    /// Where spans are exported to, for when traces are collected separately
+    /// from logs and metrics. Defaults to `otlp-endpoint`.
+    #[serde(default)]
+    pub otlp_traces_endpoint: Option<String>,
     #[serde(default = "default_true")]
     pub propagate: bool,
 }
@@ -48,6 +52,7 @@ impl Default for Config {
             service_name: default_service_name(),
             exporter: Exporter::default(),
             otlp_endpoint: default_otlp_endpoint(),
+            otlp_traces_endpoint: None,
             propagate: default_true(),
         }
     }
@@ -280,9 +285,13 @@ fn tracer_provider(
                 .build())
         }
         Exporter::Otlp | Exporter::Both => {
+            let endpoint = config
+                .otlp_traces_endpoint
+                .as_ref()
+                .unwrap_or(&config.otlp_endpoint);
             let exporter = SpanExporter::builder()
                 .with_tonic()
-                .with_endpoint(config.otlp_endpoint.clone())
+                .with_endpoint(endpoint.clone())
                 .build()?;
             let provider = SdkTracerProvider::builder()
                 .with_resource(resource)
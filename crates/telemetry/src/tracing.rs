@@ -1,4 +1,4 @@
-use http::HeaderValue;
+use http::{HeaderMap, HeaderValue};
 use tower_http::request_id::RequestId;
 
 #[derive(Clone, Default)]
@@ -19,3 +19,19 @@ impl tower_http::request_id::MakeRequestId for MakeRequestId {
         Some(RequestId::new(header))
     }
 }
+
+/// Injects the current span's context into outgoing request headers (e.g. as
+/// a `traceparent` header), so that upstream spans join the same trace.
+///
+/// This is a no-op when propagation is disabled, since the global propagator
+/// is then a noop propagator.
+pub fn inject_current_context(headers: &mut HeaderMap) {
+    use opentelemetry::global;
+    use opentelemetry_http::HeaderInjector;
+    use tracing::Span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers));
+    });
+}
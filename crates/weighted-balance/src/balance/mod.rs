@@ -26,8 +26,9 @@
 pub mod make;
 
 use std::{
+    collections::HashMap,
     fmt,
-    hash::Hash,
+    hash::{Hash, Hasher},
     marker::PhantomData,
     pin::Pin,
     task::{Context, Poll},
@@ -37,7 +38,8 @@ use futures::{
     future::{self, TryFutureExt},
     ready,
 };
-use rand::{SeedableRng, rngs::SmallRng};
+use rand::{Rng, SeedableRng, distr::weighted, rngs::SmallRng};
+use seahash::SeaHasher;
 use tower::{
     Service,
     discover::{Change, Discover},
@@ -45,7 +47,24 @@ use tower::{
 };
 use tracing::{debug, trace};
 
-use crate::weight::HasWeight;
+use crate::weight::{HasWeight, Weight};
+
+/// The maximum number of remembered sticky assignments. Once reached, the
+/// assignments are cleared, which only affects keys that previously had to
+/// fall back from their preferred service.
+const MAX_STICKY_ASSIGNMENTS: usize = 10_000;
+
+/// Extracts the key used to pin a request to a service, or `None` if the
+/// request should be balanced normally.
+pub type StickyKeyFn<Req> = fn(&Req) -> Option<u64>;
+
+/// Whether a request prefers to be sent to the service with the given key.
+pub type PreferFn<K, Req> = fn(&Req, &K) -> bool;
+
+struct Sticky<K, Req> {
+    key_fn: StickyKeyFn<Req>,
+    assignments: HashMap<u64, K>,
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -78,6 +97,11 @@ where
     ready_index: Option<usize>,
 
     rng: SmallRng,
+    seeded: bool,
+
+    sticky: Option<Sticky<D::Key, Req>>,
+
+    prefer: Option<PreferFn<D::Key, Req>>,
 
     _req: PhantomData<Req>,
 }
@@ -107,14 +131,56 @@ where
         tracing::trace!("WeightedBalance::new");
         Self {
             rng: SmallRng::from_rng(&mut rand::rng()),
+            seeded: false,
             discover,
             services: ReadyCache::default(),
             ready_index: None,
+            sticky: None,
+            prefer: None,
 
             _req: PhantomData,
         }
     }
 
+    /// Sample services with an RNG seeded with `seed`, so that the same
+    /// sequence of requests is sent to the same sequence of services, e.g.
+    /// in tests.
+    ///
+    /// Ready services are sampled in the order of their keys' hashes rather
+    /// than the order they became ready in, which varies between runs.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self.seeded = true;
+        self
+    }
+
+    /// Pin requests with the same sticky key to the same service.
+    ///
+    /// Keys are initially assigned with weighted rendezvous hashing, so the
+    /// configured weights are respected across the population of keys. If
+    /// the assigned service is removed from the set, the key is reassigned to
+    /// another ready service, and that choice is remembered.
+    #[must_use]
+    pub fn with_sticky_key(mut self, key_fn: StickyKeyFn<Req>) -> Self {
+        self.sticky = Some(Sticky {
+            key_fn,
+            assignments: HashMap::new(),
+        });
+        self
+    }
+
+    /// Send requests to a ready service they prefer rather than a sampled
+    /// one, e.g. when the choice of service was made before the request
+    /// reached the balancer.
+    ///
+    /// Requests which don't prefer any ready service are balanced normally.
+    #[must_use]
+    pub fn with_preference(mut self, prefer: PreferFn<D::Key, Req>) -> Self {
+        self.prefer = Some(prefer);
+        self
+    }
+
     /// Returns the number of endpoints currently tracked by the balancer.
     pub fn len(&self) -> usize {
         self.services.len()
@@ -194,10 +260,13 @@ where
             0 => Ok(None),
             1 => Ok(Some(0)),
             len => {
+                let order = self.seeded.then(|| self.seeded_order());
+                let index_of =
+                    |idx: usize| order.as_ref().map_or(idx, |order| order[idx]);
                 let sample_fn = |idx| {
                     let (key, _service) = self
                         .services
-                        .get_ready_index(idx)
+                        .get_ready_index(index_of(idx))
                         .expect("invalid index");
 
                     key.weight()
@@ -205,21 +274,110 @@ where
                 // NOTE: This is O(n) over number of services, but it can
                 // be made to O(1) using precomputed probability tables as
                 // described here: https://www.keithschwarz.com/darts-dice-coins/
-                let sample = rand::seq::index::sample_weighted(
+                let sample = match rand::seq::index::sample_weighted(
                     &mut self.rng,
                     len,
                     sample_fn,
                     1,
-                )?;
-                let chosen = sample.index(0);
+                ) {
+                    Ok(sample) => sample.index(0),
+                    // every ready service is weighted zero, e.g. they're all
+                    // scheduled out of the rotation, which is still better
+                    // than sending the request nowhere
+                    Err(weighted::Error::InsufficientNonZero) => {
+                        self.rng.random_range(0..len)
+                    }
+                    Err(error) => return Err(error.into()),
+                };
+                let chosen = index_of(sample);
 
                 trace!(chosen = chosen, "p2c");
-                Ok(Some(chosen as usize))
+                Ok(Some(chosen))
+            }
+        }
+    }
+
+    /// The ready indices ordered by the hashes of their keys, with a fixed
+    /// hash function and seed so that the order is the same across restarts,
+    /// toolchain upgrades and replicas.
+    fn seeded_order(&self) -> Vec<usize> {
+        let mut order = (0..self.services.ready_len()).collect::<Vec<_>>();
+        order.sort_by_cached_key(|index| {
+            let (key, _service) = self
+                .services
+                .get_ready_index(*index)
+                .expect("invalid index");
+            let mut hasher = SeaHasher::new();
+            key.hash(&mut hasher);
+            hasher.finish()
+        });
+        order
+    }
+
+    /// Returns the index of the ready service assigned to `sticky_key`, if
+    /// any.
+    fn sticky_index(&mut self, sticky_key: u64) -> Option<usize> {
+        let sticky = self.sticky.as_mut()?;
+        if let Some(key) = sticky.assignments.get(&sticky_key) {
+            match self.services.get_ready(key) {
+                Some((index, key, _)) if key.weight() > Weight::MIN => {
+                    return Some(index);
+                }
+                Some(_) => debug!("sticky service weighted zero, reassigning"),
+                None if self.services.pending_contains(key) => {
+                    // the service is still in the set, it's just busy; don't
+                    // reassign the key for a transient condition
+                    trace!("sticky service pending, balancing request");
+                    return None;
+                }
+                None => debug!("sticky service removed, reassigning"),
             }
         }
+
+        let index = (0..self.services.ready_len())
+            .map(|index| {
+                let (key, _service) = self
+                    .services
+                    .get_ready_index(index)
+                    .expect("invalid index");
+                (index, rendezvous_score(sticky_key, key))
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)?;
+        let (key, _service) =
+            self.services.get_ready_index(index).expect("invalid index");
+        if sticky.assignments.len() >= MAX_STICKY_ASSIGNMENTS {
+            sticky.assignments.clear();
+        }
+        sticky.assignments.insert(sticky_key, key.clone());
+        Some(index)
+    }
+
+    /// Returns the index of the first ready service `request` prefers, if
+    /// any.
+    fn preferred_index(&self, request: &Req) -> Option<usize> {
+        let prefer = self.prefer?;
+        (0..self.services.ready_len()).find(|index| {
+            self.services
+                .get_ready_index(*index)
+                .is_some_and(|(key, _service)| prefer(request, key))
+        })
     }
 }
 
+/// Weighted rendezvous hashing score of `key` for `sticky_key`, see:
+/// <https://en.wikipedia.org/wiki/Rendezvous_hashing#Weighted_rendezvous_hash>
+fn rendezvous_score<K: Hash + HasWeight>(sticky_key: u64, key: &K) -> f64 {
+    let mut hasher = SeaHasher::new();
+    sticky_key.hash(&mut hasher);
+    key.hash(&mut hasher);
+    // map the hash into (0, 1)
+    #[allow(clippy::cast_precision_loss)]
+    let unit = (hasher.finish() as f64 + 1.0) / (u64::MAX as f64 + 2.0);
+    -f64::from(key.weight()) / unit.ln()
+}
+
 impl<D, Req> Service<Req> for WeightedBalance<D, Req>
 where
     D: Discover + Unpin,
@@ -284,7 +442,19 @@ where
 
     fn call(&mut self, request: Req) -> Self::Future {
         tracing::trace!("WeightedBalance::call");
-        let index = self.ready_index.take().expect("called before ready");
+        let mut index = self.ready_index.take().expect("called before ready");
+        if let Some(sticky_key) = self
+            .sticky
+            .as_ref()
+            .and_then(|sticky| (sticky.key_fn)(&request))
+            && let Some(sticky_index) = self.sticky_index(sticky_key)
+        {
+            trace!(index = sticky_index, "sticky");
+            index = sticky_index;
+        } else if let Some(preferred_index) = self.preferred_index(&request) {
+            trace!(index = preferred_index, "preferred");
+            index = preferred_index;
+        }
         self.services
             .call_ready_index(index, request)
             .map_err(Into::into)